@@ -3,6 +3,8 @@ use std::process::Command;
 
 fn main() {
     let target = std::env::var("TARGET").unwrap();
+    // Set when Portaudio was built with its ALSA host API.
+    println!("cargo:rustc-check-cfg=cfg(portaudio_alsa)");
 
     if !Path::new("portaudio/CMakeLists.txt").exists() {
         Command::new("git")
@@ -33,13 +35,30 @@ fn main() {
         .expect("Couldn't write bindings to file.");
 
     // Actually build.
-    let dst = cmake::Config::new("portaudio")
+    let mut config = cmake::Config::new("portaudio");
+    if target.contains("windows") && std::env::var_os("CARGO_FEATURE_ASIO").is_some() {
+        println!("cargo:rerun-if-env-changed=ASIOSDK_DIR");
+        let sdk_dir = std::env::var("ASIOSDK_DIR").expect(
+            "The asio feature needs the ASIO SDK. Download it from Steinberg's website, and point \
+             ASIOSDK_DIR to it.",
+        );
+        config
+            .define("PA_USE_ASIO", "ON")
+            .define("ASIOSDK_ROOT_DIR", &sdk_dir)
+            .define("ASIOSDK_PATH_HINT", &sdk_dir);
+    }
+    // Don't use legacy windows APIs (DirectSound, MME, WDMKS), unless asked to.
+    let legacy = if std::env::var_os("CARGO_FEATURE_WINDOWS_LEGACY").is_some() {
+        "ON"
+    } else {
+        "OFF"
+    };
+    let dst = config
         .define("PA_BUILD_SHARED", "OFF")
-        // Don't use legacy windows APIs (DirectSound, MME, WDMKS).
-        .define("PA_USE_DS", "OFF")
-        .define("PA_USE_WMME", "OFF")
-        .define("PA_USE_WDMKS", "OFF")
-        .define("PA_USE_WDMKS_DEVICE_INFO", "OFF")
+        .define("PA_USE_DS", legacy)
+        .define("PA_USE_WMME", legacy)
+        .define("PA_USE_WDMKS", legacy)
+        .define("PA_USE_WDMKS_DEVICE_INFO", legacy)
         // Keep library names consistent in Windows (since we don't build shared).
         .define("PA_LIBNAME_ADD_SUFFIX", "OFF")
         // Enable the usage of the skeleton API.
@@ -59,12 +78,24 @@ fn main() {
     } else if target.contains("windows") {
         println!("cargo:rustc-link-lib=ole32");
         println!("cargo:rustc-link-lib=uuid");
+        if std::env::var_os("CARGO_FEATURE_ASIO").is_some() {
+            // The SDK looks up installed drivers in the registry.
+            println!("cargo:rustc-link-lib=advapi32");
+            println!("cargo:rustc-link-lib=winmm");
+        }
+        if std::env::var_os("CARGO_FEATURE_WINDOWS_LEGACY").is_some() {
+            // MME is WinMM, and WDM-KS finds its devices through SetupAPI. DirectSound is loaded
+            // at runtime.
+            println!("cargo:rustc-link-lib=winmm");
+            println!("cargo:rustc-link-lib=setupapi");
+        }
     } else if target.contains("linux") {
         let out_dir = std::env::var("OUT_DIR").unwrap();
         // This is the easiest way I can think of to figure out if portaudio was compiled
         // with ALSA.
         if Path::new(&out_dir).join("include/pa_linux_alsa.h").exists() {
             println!("cargo:rustc-link-lib=asound");
+            println!("cargo:rustc-cfg=portaudio_alsa");
         } else {
             println!(
                 "cargo:warning=Could not find ALSA (libasound2-dev) on this machine. \
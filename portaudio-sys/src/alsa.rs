@@ -0,0 +1,23 @@
+//! Bindings to the parts of pa_linux_alsa.h, the ALSA host API's extensions, that tune streams.
+//! Only available on Linux. Portaudio builds without its ALSA host API when ALSA's headers aren't
+//! installed: The functions then do nothing, and return `paHostApiNotFound`.
+use std::os::raw::c_int;
+
+use crate::{PaError, PaStream};
+
+#[cfg(portaudio_alsa)]
+extern "C" {
+    /// Sets how many periods the buffers of ALSA streams opened from now on have. 4 by default.
+    pub fn PaAlsa_SetNumPeriods(numPeriods: c_int) -> PaError;
+    /// Has the stream's thread schedule itself with `SCHED_FIFO`. Must be called before the stream
+    /// starts.
+    pub fn PaAlsa_EnableRealtimeScheduling(s: *mut PaStream, enable: c_int);
+}
+
+#[cfg(not(portaudio_alsa))]
+pub unsafe fn PaAlsa_SetNumPeriods(_numPeriods: c_int) -> PaError {
+    PaError(crate::PaErrorCode::paHostApiNotFound as c_int)
+}
+
+#[cfg(not(portaudio_alsa))]
+pub unsafe fn PaAlsa_EnableRealtimeScheduling(_s: *mut PaStream, _enable: c_int) {}
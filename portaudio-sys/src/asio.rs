@@ -0,0 +1,50 @@
+//! Bindings to pa_asio.h, the ASIO host API's extensions. Only available on Windows, when
+//! Portaudio is built with the ASIO SDK.
+use std::os::raw::{c_char, c_int, c_long, c_ulong, c_void};
+
+use crate::{PaDeviceIndex, PaError, PaHostApiTypeId};
+
+/// Set in [`PaAsioStreamInfo::flags`] to use [`PaAsioStreamInfo::channelSelectors`].
+pub const paAsioUseChannelSelectors: c_ulong = 0x01;
+
+/// Passed as a stream parameter's `hostApiSpecificStreamInfo` to pick specific driver channels.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PaAsioStreamInfo {
+    /// Must be `size_of::<PaAsioStreamInfo>()`.
+    pub size: c_ulong,
+    /// Must be `paASIO`.
+    pub hostApiType: PaHostApiTypeId,
+    /// Must be 1.
+    pub version: c_ulong,
+    pub flags: c_ulong,
+    /// One driver channel index per stream channel.
+    pub channelSelectors: *mut c_int,
+}
+
+extern "C" {
+    /// Retrieves legal native buffer sizes for the specified device, in sample frames. A
+    /// granularity of -1 means that only powers of two between the minimum and maximum are legal.
+    pub fn PaAsio_GetAvailableBufferSizes(
+        device: PaDeviceIndex,
+        minBufferSizeFrames: *mut c_long,
+        maxBufferSizeFrames: *mut c_long,
+        preferredBufferSizeFrames: *mut c_long,
+        granularity: *mut c_long,
+    ) -> PaError;
+
+    /// Displays the ASIO driver's control panel. `systemSpecific` is the parent window handle.
+    pub fn PaAsio_ShowControlPanel(device: PaDeviceIndex, systemSpecific: *mut c_void) -> PaError;
+
+    pub fn PaAsio_GetInputChannelName(
+        device: PaDeviceIndex,
+        channelIndex: c_int,
+        channelName: *mut *const c_char,
+    ) -> PaError;
+
+    pub fn PaAsio_GetOutputChannelName(
+        device: PaDeviceIndex,
+        channelIndex: c_int,
+        channelName: *mut *const c_char,
+    ) -> PaError;
+}
@@ -2,6 +2,12 @@
 
 use std::os::raw::c_int;
 
+#[cfg(target_os = "linux")]
+pub use alsa::*;
+#[cfg(all(windows, feature = "asio"))]
+pub use asio::*;
+#[cfg(windows)]
+pub use wasapi::*;
 pub use bindings::*;
 pub use flags::*;
 
@@ -10,6 +16,20 @@ pub use flags::*;
 #[allow(non_camel_case_types)]
 mod bindings;
 
+#[cfg(target_os = "linux")]
+#[allow(non_snake_case)]
+mod alsa;
+
+#[cfg(all(windows, feature = "asio"))]
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+mod asio;
+
+#[cfg(windows)]
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+mod wasapi;
+
 impl From<PaError> for Result<c_int, PaErrorCode> {
     fn from(error: PaError) -> Result<c_int, PaErrorCode> {
         if error.0 >= 0 {
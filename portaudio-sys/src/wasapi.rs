@@ -0,0 +1,61 @@
+//! Bindings to the parts of pa_win_wasapi.h, the WASAPI host API's extensions, that open streams.
+//! Only available on Windows.
+use std::os::raw::{c_int, c_ulong, c_void};
+
+use crate::PaHostApiTypeId;
+
+// PaWasapiFlags.
+pub const paWinWasapiExclusive: c_ulong = 1 << 0;
+pub const paWinWasapiRedirectHostProcessor: c_ulong = 1 << 1;
+pub const paWinWasapiUseChannelMask: c_ulong = 1 << 2;
+pub const paWinWasapiPolling: c_ulong = 1 << 3;
+pub const paWinWasapiThreadPriority: c_ulong = 1 << 4;
+pub const paWinWasapiExplicitSampleFormat: c_ulong = 1 << 5;
+pub const paWinWasapiAutoConvert: c_ulong = 1 << 6;
+
+/// The MMCSS task the stream's thread joins, with [`paWinWasapiThreadPriority`].
+pub type PaWasapiThreadPriority = c_int;
+pub const eThreadPriorityNone: PaWasapiThreadPriority = 0;
+pub const eThreadPriorityAudio: PaWasapiThreadPriority = 1;
+pub const eThreadPriorityCapture: PaWasapiThreadPriority = 2;
+pub const eThreadPriorityDistribution: PaWasapiThreadPriority = 3;
+pub const eThreadPriorityGames: PaWasapiThreadPriority = 4;
+pub const eThreadPriorityPlayback: PaWasapiThreadPriority = 5;
+pub const eThreadPriorityProAudio: PaWasapiThreadPriority = 6;
+pub const eThreadPriorityWindowManager: PaWasapiThreadPriority = 7;
+
+pub type PaWasapiStreamCategory = c_int;
+pub const eAudioCategoryOther: PaWasapiStreamCategory = 0;
+
+pub type PaWasapiStreamOption = c_int;
+pub const eStreamOptionNone: PaWasapiStreamOption = 0;
+
+pub type PaWasapiHostProcessorCallback = Option<
+    unsafe extern "C" fn(
+        inputBuffer: *mut c_void,
+        inputFrames: c_ulong,
+        outputBuffer: *mut c_void,
+        outputFrames: c_ulong,
+        userData: *mut c_void,
+    ),
+>;
+
+/// Passed as a stream parameter's `hostApiSpecificStreamInfo` to set how the stream uses WASAPI.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PaWasapiStreamInfo {
+    /// Must be `size_of::<PaWasapiStreamInfo>()`.
+    pub size: c_ulong,
+    /// Must be `paWASAPI`.
+    pub hostApiType: PaHostApiTypeId,
+    /// Must be 1.
+    pub version: c_ulong,
+    pub flags: c_ulong,
+    /// A `PaWinWaveFormatChannelMask`, with [`paWinWasapiUseChannelMask`].
+    pub channelMask: c_ulong,
+    pub hostProcessorOutput: PaWasapiHostProcessorCallback,
+    pub hostProcessorInput: PaWasapiHostProcessorCallback,
+    pub threadPriority: PaWasapiThreadPriority,
+    pub streamCategory: PaWasapiStreamCategory,
+    pub streamOption: PaWasapiStreamOption,
+}
@@ -0,0 +1,81 @@
+use crate::aaudio::stream::{self, Stream};
+use crate::aaudio::{PerformanceMode, SharingMode};
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+
+/// An AAudio output or input device.
+pub struct Device {
+    // None for the system's default.
+    id: Option<i32>,
+    name: String,
+    is_output: bool,
+    performance_mode: PerformanceMode,
+    sharing_mode: SharingMode,
+}
+
+impl Device {
+    pub(super) fn new(id: Option<i32>, is_output: bool) -> Device {
+        Device {
+            id,
+            name: id.map_or_else(
+                || "default".to_owned(),
+                |id| format!("AAudio device {}", id),
+            ),
+            is_output,
+            performance_mode: PerformanceMode::default(),
+            sharing_mode: SharingMode::default(),
+        }
+    }
+
+    /// "default", or the device's ID.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The performance mode of streams opened from now on. Defaults to
+    /// [`PerformanceMode::LowLatency`].
+    pub fn performance_mode(&self) -> PerformanceMode {
+        self.performance_mode
+    }
+
+    /// Sets the performance mode of streams opened from now on.
+    pub fn set_performance_mode(&mut self, performance_mode: PerformanceMode) {
+        self.performance_mode = performance_mode;
+    }
+
+    /// The sharing mode of streams opened from now on. Defaults to [`SharingMode::Shared`].
+    pub fn sharing_mode(&self) -> SharingMode {
+        self.sharing_mode
+    }
+
+    /// Sets the sharing mode of streams opened from now on.
+    pub fn set_sharing_mode(&mut self, sharing_mode: SharingMode) {
+        self.sharing_mode = sharing_mode;
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+
+    pub(super) fn id(&self) -> Option<i32> {
+        self.id
+    }
+}
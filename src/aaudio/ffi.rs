@@ -0,0 +1,166 @@
+//! Hand-written bindings to the subset of AAudio (libaaudio, API level 26+) used by the backend.
+//!
+//! libaaudio is loaded at runtime rather than linked, so that apps still load on older devices.
+//! The functions panic if called before [`load`] succeeded.
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+
+use std::os::raw::{c_char, c_int, c_void};
+
+use lazy_static::lazy_static;
+
+pub enum AAudioStreamBuilder {}
+pub enum AAudioStream {}
+
+pub type aaudio_result_t = i32;
+pub type aaudio_direction_t = i32;
+pub type aaudio_format_t = i32;
+pub type aaudio_sharing_mode_t = i32;
+pub type aaudio_performance_mode_t = i32;
+pub type aaudio_data_callback_result_t = i32;
+
+pub const AAUDIO_UNSPECIFIED: i32 = 0;
+
+pub const AAUDIO_DIRECTION_OUTPUT: aaudio_direction_t = 0;
+pub const AAUDIO_DIRECTION_INPUT: aaudio_direction_t = 1;
+
+pub const AAUDIO_FORMAT_PCM_I16: aaudio_format_t = 1;
+pub const AAUDIO_FORMAT_PCM_FLOAT: aaudio_format_t = 2;
+/// Packed 24-bit samples (3 bytes each). API level 31+.
+pub const AAUDIO_FORMAT_PCM_I24_PACKED: aaudio_format_t = 3;
+/// API level 31+.
+pub const AAUDIO_FORMAT_PCM_I32: aaudio_format_t = 4;
+
+pub const AAUDIO_SHARING_MODE_EXCLUSIVE: aaudio_sharing_mode_t = 0;
+pub const AAUDIO_SHARING_MODE_SHARED: aaudio_sharing_mode_t = 1;
+
+pub const AAUDIO_PERFORMANCE_MODE_NONE: aaudio_performance_mode_t = 10;
+pub const AAUDIO_PERFORMANCE_MODE_POWER_SAVING: aaudio_performance_mode_t = 11;
+pub const AAUDIO_PERFORMANCE_MODE_LOW_LATENCY: aaudio_performance_mode_t = 12;
+
+pub const AAUDIO_CALLBACK_RESULT_CONTINUE: aaudio_data_callback_result_t = 0;
+
+pub const AAUDIO_OK: aaudio_result_t = 0;
+pub const AAUDIO_ERROR_DISCONNECTED: aaudio_result_t = -899;
+pub const AAUDIO_ERROR_ILLEGAL_ARGUMENT: aaudio_result_t = -898;
+pub const AAUDIO_ERROR_INVALID_STATE: aaudio_result_t = -895;
+pub const AAUDIO_ERROR_UNAVAILABLE: aaudio_result_t = -889;
+pub const AAUDIO_ERROR_NO_MEMORY: aaudio_result_t = -887;
+pub const AAUDIO_ERROR_INVALID_FORMAT: aaudio_result_t = -883;
+pub const AAUDIO_ERROR_OUT_OF_RANGE: aaudio_result_t = -882;
+pub const AAUDIO_ERROR_NO_SERVICE: aaudio_result_t = -881;
+pub const AAUDIO_ERROR_INVALID_RATE: aaudio_result_t = -880;
+
+pub type AAudioStream_dataCallback = unsafe extern "C" fn(
+    stream: *mut AAudioStream,
+    user_data: *mut c_void,
+    audio_data: *mut c_void,
+    num_frames: i32,
+) -> aaudio_data_callback_result_t;
+
+/// Declares the library's functions: A table of function pointers filled in by `dlsym`, and a
+/// wrapper for each that calls through it.
+macro_rules! dynamic_functions {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        struct Library {
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl Library {
+            unsafe fn load(handle: *mut c_void) -> Option<Library> {
+                Some(Library {
+                    $($name: {
+                        let symbol = dlsym(handle, concat!(stringify!($name), "\0").as_ptr() as *const c_char);
+                        if symbol.is_null() {
+                            return None;
+                        }
+                        std::mem::transmute(symbol)
+                    },)*
+                })
+            }
+        }
+
+        $(pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+            (LIBRARY.as_ref().expect("libaaudio is not loaded.").$name)($($arg),*)
+        })*
+    };
+}
+
+dynamic_functions! {
+    pub fn AAudio_createStreamBuilder(builder: *mut *mut AAudioStreamBuilder) -> aaudio_result_t;
+
+    pub fn AAudioStreamBuilder_setDeviceId(builder: *mut AAudioStreamBuilder, device_id: i32);
+    pub fn AAudioStreamBuilder_setDirection(
+        builder: *mut AAudioStreamBuilder,
+        direction: aaudio_direction_t,
+    );
+    pub fn AAudioStreamBuilder_setSharingMode(
+        builder: *mut AAudioStreamBuilder,
+        sharing_mode: aaudio_sharing_mode_t,
+    );
+    pub fn AAudioStreamBuilder_setPerformanceMode(
+        builder: *mut AAudioStreamBuilder,
+        mode: aaudio_performance_mode_t,
+    );
+    pub fn AAudioStreamBuilder_setSampleRate(builder: *mut AAudioStreamBuilder, sample_rate: i32);
+    pub fn AAudioStreamBuilder_setChannelCount(
+        builder: *mut AAudioStreamBuilder,
+        channel_count: i32,
+    );
+    pub fn AAudioStreamBuilder_setFormat(
+        builder: *mut AAudioStreamBuilder,
+        format: aaudio_format_t,
+    );
+    pub fn AAudioStreamBuilder_setFramesPerDataCallback(
+        builder: *mut AAudioStreamBuilder,
+        num_frames: i32,
+    );
+    pub fn AAudioStreamBuilder_setDataCallback(
+        builder: *mut AAudioStreamBuilder,
+        callback: Option<AAudioStream_dataCallback>,
+        user_data: *mut c_void,
+    );
+    pub fn AAudioStreamBuilder_openStream(
+        builder: *mut AAudioStreamBuilder,
+        stream: *mut *mut AAudioStream,
+    ) -> aaudio_result_t;
+    pub fn AAudioStreamBuilder_delete(builder: *mut AAudioStreamBuilder) -> aaudio_result_t;
+
+    pub fn AAudioStream_requestStart(stream: *mut AAudioStream) -> aaudio_result_t;
+    pub fn AAudioStream_close(stream: *mut AAudioStream) -> aaudio_result_t;
+    pub fn AAudioStream_getSampleRate(stream: *mut AAudioStream) -> i32;
+    pub fn AAudioStream_getFramesPerBurst(stream: *mut AAudioStream) -> i32;
+    pub fn AAudioStream_getBufferSizeInFrames(stream: *mut AAudioStream) -> i32;
+    pub fn AAudioStream_getBufferCapacityInFrames(stream: *mut AAudioStream) -> i32;
+    pub fn AAudioStream_getFramesPerDataCallback(stream: *mut AAudioStream) -> i32;
+    pub fn AAudioStream_setBufferSizeInFrames(
+        stream: *mut AAudioStream,
+        num_frames: i32,
+    ) -> aaudio_result_t;
+    pub fn AAudioStream_getPerformanceMode(stream: *mut AAudioStream) -> aaudio_performance_mode_t;
+    pub fn AAudioStream_getSharingMode(stream: *mut AAudioStream) -> aaudio_sharing_mode_t;
+}
+
+lazy_static! {
+    static ref LIBRARY: Option<Library> = unsafe {
+        let handle = dlopen(b"libaaudio.so\0".as_ptr() as *const c_char, RTLD_NOW);
+        if handle.is_null() {
+            None
+        } else {
+            Library::load(handle)
+        }
+    };
+}
+
+/// Loads libaaudio, once. Returns whether it is available on this device.
+pub fn load() -> bool {
+    LIBRARY.is_some()
+}
+
+const RTLD_NOW: c_int = 2;
+
+#[link(name = "dl")]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
@@ -0,0 +1,42 @@
+use crate::aaudio::device::Device;
+use crate::aaudio::ffi;
+use crate::error::{Error, Result};
+
+/// The AAudio host. AAudio has no native device enumeration: Device IDs come from Java's
+/// `AudioManager.getDevices()`.
+pub struct Host(());
+
+impl Host {
+    /// Loads AAudio. Returns [`Error::BackendUnavailable`] on devices older than API level 26.
+    pub fn new() -> Result<Host> {
+        if !ffi::load() {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(Host(()))
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "AAudio"
+    }
+
+    /// Returns the default output (e.g. the speaker, or plugged-in headphones).
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new(None, true))
+    }
+
+    /// Returns the default input (usually the main microphone).
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new(None, false))
+    }
+
+    /// Returns the output with the given `AudioDeviceInfo.getId()`.
+    pub fn output_device(&mut self, device_id: i32) -> Result<Device> {
+        Ok(Device::new(Some(device_id), true))
+    }
+
+    /// Returns the input with the given `AudioDeviceInfo.getId()`.
+    pub fn input_device(&mut self, device_id: i32) -> Result<Device> {
+        Ok(Device::new(Some(device_id), false))
+    }
+}
@@ -0,0 +1,93 @@
+//! AAudio backend for Android (API level 26+).
+//!
+//! Talks to the NDK's libaaudio directly. Exposes the same [`Host`]/[`Device`]/[`Stream`] surface
+//! as the crate's default backend, plus AAudio's low-latency performance mode (see
+//! [`PerformanceMode`]) and exclusive streams (see [`SharingMode`]).
+//!
+//! Stream callbacks run on AAudio's real-time thread: They must not block, lock, or allocate.
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// The latency/power trade-off of streams opened on a [`Device`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerformanceMode {
+    /// Small buffers, serviced by a high-priority thread. Often the only way to get a "fast
+    /// track" (and sub-20ms latency) on a device. Used by default, since it's what games and
+    /// music apps need.
+    LowLatency,
+    /// Large buffers, to save power (e.g. for background music playback).
+    PowerSaving,
+    /// Lets the system decide.
+    None,
+}
+
+impl Default for PerformanceMode {
+    fn default() -> PerformanceMode {
+        PerformanceMode::LowLatency
+    }
+}
+
+/// How streams opened on a [`Device`] share the audio hardware with the rest of the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharingMode {
+    /// The stream goes through the system mixer, alongside other applications.
+    Shared,
+    /// The stream gets a dedicated MMAP endpoint for the lowest possible latency, if the device
+    /// supports it. AAudio silently falls back to a shared stream otherwise.
+    Exclusive,
+}
+
+impl Default for SharingMode {
+    fn default() -> SharingMode {
+        SharingMode::Shared
+    }
+}
+
+/// Converts an AAudio result code (negative on failure) into a [`Result`].
+fn check(result: ffi::aaudio_result_t) -> Result<i32> {
+    match result {
+        result if result >= ffi::AAUDIO_OK => Ok(result),
+        ffi::AAUDIO_ERROR_NO_MEMORY => Err(Error::OutOfMemory),
+        ffi::AAUDIO_ERROR_DISCONNECTED | ffi::AAUDIO_ERROR_UNAVAILABLE => Err(Error::NoSuchDevice),
+        ffi::AAUDIO_ERROR_NO_SERVICE => Err(Error::BackendUnavailable),
+        ffi::AAUDIO_ERROR_INVALID_RATE => Err(Error::IncompatibleSampleRate),
+        ffi::AAUDIO_ERROR_OUT_OF_RANGE => Err(Error::InvalidFramesPerBuffer),
+        ffi::AAUDIO_ERROR_ILLEGAL_ARGUMENT => Err(Error::Invalid),
+        ffi::AAUDIO_ERROR_INVALID_STATE => Err(Error::StreamAlreadyStarted),
+        result => Err(Error::Backend(BackendError::new(
+            Some(Backend::AAudio),
+            ErrorCode::AAudio(result),
+            "Unexpected AAudio error.",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_result_codes() {
+        assert_eq!(check(5), Ok(5));
+        assert_eq!(
+            check(ffi::AAUDIO_ERROR_INVALID_RATE),
+            Err(Error::IncompatibleSampleRate)
+        );
+        assert_eq!(
+            check(ffi::AAUDIO_ERROR_NO_SERVICE),
+            Err(Error::BackendUnavailable)
+        );
+    }
+}
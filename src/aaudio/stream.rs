@@ -0,0 +1,287 @@
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use crate::aaudio::device::Device;
+use crate::aaudio::{check, ffi, PerformanceMode, SharingMode};
+use crate::error::{Error, Result};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// An AAudio stream. Closed when dropped.
+pub struct Stream<Frame> {
+    // Closed before the state it points to is dropped.
+    stream: StreamPtr,
+    is_started: bool,
+    _state: Box<dyn Send>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        check(unsafe { ffi::AAudioStream_requestStart(self.stream.0) })?;
+        self.is_started = true;
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// The number of frames the device reads or writes at a time. Callbacks get a multiple of it
+    /// unless `frames_per_buffer` was set.
+    pub fn frames_per_burst(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getFramesPerBurst(self.stream.0) }
+    }
+
+    /// Streams with a `frames_per_buffer` call back with that many frames every time. Others get
+    /// whatever the device delivers, up to the buffer's capacity.
+    pub fn info(&self) -> StreamInfo {
+        match unsafe { ffi::AAudioStream_getFramesPerDataCallback(self.stream.0) } {
+            frames if frames > 0 => StreamInfo::fixed(frames as usize),
+            _ => StreamInfo::at_most(
+                unsafe { ffi::AAudioStream_getBufferCapacityInFrames(self.stream.0) }.max(0)
+                    as usize,
+            ),
+        }
+    }
+
+    /// The stream's buffer size in frames, i.e. its latency.
+    pub fn buffer_size(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getBufferSizeInFrames(self.stream.0) }
+    }
+
+    /// The performance mode the stream actually got, which may differ from the device's.
+    pub fn performance_mode(&self) -> PerformanceMode {
+        match unsafe { ffi::AAudioStream_getPerformanceMode(self.stream.0) } {
+            ffi::AAUDIO_PERFORMANCE_MODE_LOW_LATENCY => PerformanceMode::LowLatency,
+            ffi::AAUDIO_PERFORMANCE_MODE_POWER_SAVING => PerformanceMode::PowerSaving,
+            _ => PerformanceMode::None,
+        }
+    }
+
+    /// The sharing mode the stream actually got. Exclusive streams fall back to shared ones when
+    /// the device can't do it.
+    pub fn sharing_mode(&self) -> SharingMode {
+        match unsafe { ffi::AAudioStream_getSharingMode(self.stream.0) } {
+            ffi::AAUDIO_SHARING_MODE_EXCLUSIVE => SharingMode::Exclusive,
+            _ => SharingMode::Shared,
+        }
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        // Also stops the stream, and waits for any running callback to return.
+        unsafe { ffi::AAudioStream_close(self.stream.0) };
+    }
+}
+
+struct StreamPtr(*mut ffi::AAudioStream);
+
+// AAudio's stream API is thread-safe.
+unsafe impl Send for StreamPtr {}
+unsafe impl Sync for StreamPtr {}
+
+/// A stream builder. Deleted when dropped.
+struct Builder(*mut ffi::AAudioStreamBuilder);
+
+impl Drop for Builder {
+    fn drop(&mut self) {
+        unsafe { ffi::AAudioStreamBuilder_delete(self.0) };
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    open(
+        device,
+        options,
+        ffi::AAUDIO_DIRECTION_OUTPUT,
+        outstream_callback::<Frame>,
+    )
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    open(
+        device,
+        options,
+        ffi::AAUDIO_DIRECTION_INPUT,
+        instream_callback::<Frame>,
+    )
+}
+
+/// Maps the options to a stream builder, and opens the stream.
+fn open<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: StreamOptions<Frame, Kind>,
+    direction: ffi::aaudio_direction_t,
+    data_callback: ffi::AAudioStream_dataCallback,
+) -> Result<Stream<Frame>>
+where
+    Kind::Callback<Frame>: Send + 'static,
+{
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+        _ => ffi::AAUDIO_UNSPECIFIED,
+    };
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer,
+        None => ffi::AAUDIO_UNSPECIFIED,
+    };
+    options.validate_frame_size()?;
+    let format = aaudio_format(options.format)?;
+
+    let mut builder = std::ptr::null_mut();
+    check(unsafe { ffi::AAudio_createStreamBuilder(&mut builder) })?;
+    let builder = Builder(builder);
+    let mut state = Box::new(options.callback);
+    unsafe {
+        ffi::AAudioStreamBuilder_setDeviceId(
+            builder.0,
+            device.id().unwrap_or(ffi::AAUDIO_UNSPECIFIED),
+        );
+        ffi::AAudioStreamBuilder_setDirection(builder.0, direction);
+        ffi::AAudioStreamBuilder_setPerformanceMode(
+            builder.0,
+            aaudio_performance_mode(device.performance_mode()),
+        );
+        ffi::AAudioStreamBuilder_setSharingMode(
+            builder.0,
+            aaudio_sharing_mode(device.sharing_mode()),
+        );
+        ffi::AAudioStreamBuilder_setSampleRate(builder.0, sample_rate);
+        ffi::AAudioStreamBuilder_setChannelCount(builder.0, options.n_channels);
+        ffi::AAudioStreamBuilder_setFormat(builder.0, format);
+        ffi::AAudioStreamBuilder_setFramesPerDataCallback(builder.0, frames_per_buffer);
+        ffi::AAudioStreamBuilder_setDataCallback(
+            builder.0,
+            Some(data_callback),
+            &mut *state as *mut Kind::Callback<Frame> as *mut c_void,
+        );
+    }
+    let mut stream = std::ptr::null_mut();
+    match unsafe { ffi::AAudioStreamBuilder_openStream(builder.0, &mut stream) } {
+        // Formats newer than the device's API level are rejected as invalid.
+        ffi::AAUDIO_ERROR_INVALID_FORMAT => return Err(Error::IncompatibleFormat(options.format)),
+        result => check(result)?,
+    };
+    let stream = Stream {
+        stream: StreamPtr(stream),
+        is_started: false,
+        _state: state,
+        _frame: PhantomData,
+    };
+    if let SampleRate::Exact(rate) = options.sample_rate {
+        if unsafe { ffi::AAudioStream_getSampleRate(stream.stream.0) } != rate {
+            return Err(Error::IncompatibleSampleRate);
+        }
+    }
+    if stream.performance_mode() == PerformanceMode::LowLatency {
+        // Low-latency streams start with a large buffer. Double buffering the device's bursts is
+        // the usual sweet spot between latency and glitches.
+        let frames_per_burst = stream.frames_per_burst();
+        unsafe { ffi::AAudioStream_setBufferSizeInFrames(stream.stream.0, frames_per_burst * 2) };
+    }
+    Ok(stream)
+}
+
+fn aaudio_format(format: Format) -> Result<ffi::aaudio_format_t> {
+    Ok(match format {
+        Format::F32 => ffi::AAUDIO_FORMAT_PCM_FLOAT,
+        Format::I32 => ffi::AAUDIO_FORMAT_PCM_I32,
+        Format::I24 => ffi::AAUDIO_FORMAT_PCM_I24_PACKED,
+        Format::I16 => ffi::AAUDIO_FORMAT_PCM_I16,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    })
+}
+
+fn aaudio_performance_mode(mode: PerformanceMode) -> ffi::aaudio_performance_mode_t {
+    match mode {
+        PerformanceMode::LowLatency => ffi::AAUDIO_PERFORMANCE_MODE_LOW_LATENCY,
+        PerformanceMode::PowerSaving => ffi::AAUDIO_PERFORMANCE_MODE_POWER_SAVING,
+        PerformanceMode::None => ffi::AAUDIO_PERFORMANCE_MODE_NONE,
+    }
+}
+
+fn aaudio_sharing_mode(mode: SharingMode) -> ffi::aaudio_sharing_mode_t {
+    match mode {
+        SharingMode::Shared => ffi::AAUDIO_SHARING_MODE_SHARED,
+        SharingMode::Exclusive => ffi::AAUDIO_SHARING_MODE_EXCLUSIVE,
+    }
+}
+
+unsafe extern "C" fn outstream_callback<Frame>(
+    _stream: *mut ffi::AAudioStream,
+    user_data: *mut c_void,
+    audio_data: *mut c_void,
+    num_frames: i32,
+) -> ffi::aaudio_data_callback_result_t {
+    let callback = (user_data as *mut Callback<Frame>)
+        .as_mut()
+        .expect("Could not get Callback from user data.");
+    callback(std::slice::from_raw_parts_mut(
+        audio_data as *mut Frame,
+        num_frames as usize,
+    ));
+    ffi::AAUDIO_CALLBACK_RESULT_CONTINUE
+}
+
+unsafe extern "C" fn instream_callback<Frame>(
+    _stream: *mut ffi::AAudioStream,
+    user_data: *mut c_void,
+    audio_data: *mut c_void,
+    num_frames: i32,
+) -> ffi::aaudio_data_callback_result_t {
+    let callback = (user_data as *mut InputCallback<Frame>)
+        .as_mut()
+        .expect("Could not get InputCallback from user data.");
+    callback(std::slice::from_raw_parts(
+        audio_data as *const Frame,
+        num_frames as usize,
+    ));
+    ffi::AAUDIO_CALLBACK_RESULT_CONTINUE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaudio::Host;
+
+    #[test]
+    fn maps_formats() {
+        assert_eq!(aaudio_format(Format::I16), Ok(ffi::AAUDIO_FORMAT_PCM_I16));
+        assert_eq!(
+            aaudio_format(Format::U8),
+            Err(Error::IncompatibleFormat(Format::U8))
+        );
+    }
+
+    #[test]
+    fn can_start_low_latency_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        device.set_sharing_mode(SharingMode::Exclusive);
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        println!(
+            "Got a {:?} stream with {} frames of buffering.",
+            stream.sharing_mode(),
+            stream.buffer_size()
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
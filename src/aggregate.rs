@@ -0,0 +1,336 @@
+//! Aggregate input streams, which capture from several devices through one callback, like macOS's
+//! aggregate devices: e.g. 2 channels from a USB microphone and 2 from the built-in input. See
+//! [`AggregateInput`].
+//!
+//! Every device runs on its own clock, so their streams drift apart even at the same nominal rate.
+//! The first device's stream runs the callback. The other devices' frames are buffered, and
+//! resampled to keep pace with it: Slightly faster when their buffer fills up, and slightly
+//! slower when it drains. Every device's frames are delayed by the same [`LATENCY`], so that they
+//! line up.
+
+use crate::error::{Error, Result};
+use crate::ring::{Consumer, Producer, RingBuffer};
+use crate::stream_options::{Input, InputCallback, SampleRate, StreamOptions};
+use crate::{Device, StreamGroup};
+
+/// How many frames each device's buffer holds: about 170ms at 48kHz.
+const CAPACITY: usize = 8192;
+/// How many frames each device's buffer is kept at, which delays every device by as much: about
+/// 43ms at 48kHz.
+const LATENCY: usize = 2048;
+/// How far from their nominal rate devices are resampled, at most: 0.5%, well beyond the drift of
+/// real clocks.
+const MAX_DRIFT: f64 = 0.005;
+/// How much the rate changes with the fill level: Devices a whole latency ahead are resampled 1%
+/// faster (if that was allowed).
+const GAIN: f64 = 0.01;
+/// How much each callback's fill level counts against the previous ones', so that the rate
+/// doesn't follow the jitter of the devices' buffers.
+const SMOOTHING: f64 = 0.01;
+
+/// What a device's stream passes its interleaved samples to.
+type Sink = Box<dyn FnMut(&[f32]) + Send>;
+
+/// Opens a device's stream with the sink as its callback, and adds it to the group.
+type Opener<'a> = Box<dyn FnOnce(Sink, &mut StreamGroup) -> Result<()> + 'a>;
+
+/// Combines inputs of several devices into a single callback, whose frames have the channels of
+/// every device, in the order they were added.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use audiohal::*;
+/// let mut host = Host::with_default_backend()?;
+/// let mut microphone = host.device_by_name("USB microphone")?;
+/// let mut built_in = host.default_input_device()?;
+/// let mut streams = AggregateInput::new(48000)
+///     .device::<2>(&mut microphone)
+///     .device::<2>(&mut built_in)
+///     .open(Box::new(|frames: &[[f32; 4]]| {
+///         // The microphone's channels come first.
+///         # frames;
+///     }))?;
+/// streams.start()?;
+/// # Result::Ok(())
+/// ```
+pub struct AggregateInput<'a> {
+    sample_rate: i32,
+    members: Vec<(usize, Opener<'a>)>,
+}
+
+impl<'a> AggregateInput<'a> {
+    /// Runs every device at `sample_rate`, resampled if they don't support it.
+    pub fn new(sample_rate: i32) -> AggregateInput<'a> {
+        AggregateInput {
+            sample_rate,
+            members: Vec::new(),
+        }
+    }
+
+    /// Adds `N` channels of `device`. The first device added runs the callback.
+    pub fn device<const N: usize>(mut self, device: &'a mut Device) -> AggregateInput<'a>
+    where
+        [f32; N]: sample::Frame<Sample = f32>,
+    {
+        let sample_rate = self.sample_rate;
+        let open: Opener<'a> = Box::new(move |mut sink: Sink, group: &mut StreamGroup| {
+            group.open_input_stream(
+                device,
+                StreamOptions::<[f32; N], Input> {
+                    sample_rate: SampleRate::Exact(sample_rate),
+                    resample_if_needed: true,
+                    callback: Box::new(move |frames: &[[f32; N]]| {
+                        sink(unsafe {
+                            std::slice::from_raw_parts(
+                                frames.as_ptr() as *const f32,
+                                frames.len() * N,
+                            )
+                        })
+                    }),
+                    ..Default::default()
+                },
+            )
+        });
+        self.members.push((N, open));
+        self
+    }
+
+    /// Opens every device's stream, and returns them as a group, which starts them together.
+    /// `N` must be the devices' total channel count, or this returns
+    /// [`Error::IncompatibleNChannels`].
+    ///
+    /// The callback sees nothing but silence until every device buffered [`LATENCY`] frames, and
+    /// silence in place of a device's channels whenever it falls behind (e.g. once it stopped).
+    pub fn open<const N: usize>(self, callback: InputCallback<[f32; N]>) -> Result<StreamGroup> {
+        if self
+            .members
+            .iter()
+            .map(|(n_channels, _)| n_channels)
+            .sum::<usize>()
+            != N
+            || N == 0
+        {
+            return Err(Error::IncompatibleNChannels);
+        }
+        if self.sample_rate <= 0 {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        let mut sources = Vec::new();
+        let mut producers = Vec::new();
+        for (index, &(n_channels, _)) in self.members.iter().enumerate() {
+            let (producer, consumer) = RingBuffer::new(CAPACITY * n_channels).split();
+            // The first device runs the callback, at its own pace.
+            sources.push(Source::new(consumer, n_channels, index > 0));
+            producers.push((producer, n_channels));
+        }
+        let mut producers = producers.into_iter();
+        let (first, first_n_channels) = producers.next().expect("Aggregates have a device.");
+        let mut sinks: Vec<Sink> = vec![aggregate(first, first_n_channels, sources, callback)];
+        sinks.extend(producers.map(|(mut producer, n_channels)| {
+            Box::new(move |samples: &[f32]| push_frames(&mut producer, samples, n_channels)) as Sink
+        }));
+        let mut group = StreamGroup::new();
+        for ((_, open), sink) in self.members.into_iter().zip(sinks) {
+            open(sink, &mut group)?;
+        }
+        Ok(group)
+    }
+}
+
+/// Pushes as many whole frames of `samples` as fit, so that the buffer never holds part of one.
+/// The others are dropped.
+fn push_frames(producer: &mut Producer<f32>, samples: &[f32], n_channels: usize) {
+    let room = (producer.capacity() - producer.len()) / n_channels * n_channels;
+    producer.push_slice(&samples[..room.min(samples.len())]);
+}
+
+/// The first device's sink, which buffers its samples like the others', and then reads every
+/// device's buffer into the callback's frames.
+fn aggregate<const N: usize>(
+    mut producer: Producer<f32>,
+    n_channels: usize,
+    mut sources: Vec<Source>,
+    mut callback: InputCallback<[f32; N]>,
+) -> Sink {
+    let mut frames = Vec::with_capacity(CAPACITY);
+    Box::new(move |samples: &[f32]| {
+        push_frames(&mut producer, samples, n_channels);
+        // Only allocates for buffers larger than any before.
+        frames.resize(samples.len() / n_channels, [0.0; N]);
+        let output = unsafe {
+            std::slice::from_raw_parts_mut(frames.as_mut_ptr() as *mut f32, frames.len() * N)
+        };
+        let mut offset = 0;
+        for source in &mut sources {
+            source.read(output, N, offset);
+            offset += source.n_channels;
+        }
+        callback(&frames);
+    })
+}
+
+/// A device's buffered frames, read at the callback's pace.
+struct Source {
+    consumer: Consumer<f32>,
+    n_channels: usize,
+    /// Whether the device is resampled to keep its buffer at [`LATENCY`]. The first device isn't:
+    /// It runs the callback.
+    follows: bool,
+    /// Whether the buffer was filled up to [`LATENCY`], and hasn't run dry since.
+    is_playing: bool,
+    /// How many frames the buffer holds, smoothed over the callbacks.
+    fill: f64,
+    /// The two frames the next one is interpolated between.
+    frames: Vec<f32>,
+    /// How far the next frame is from the first of the two.
+    phase: f64,
+}
+
+impl Source {
+    fn new(consumer: Consumer<f32>, n_channels: usize, follows: bool) -> Source {
+        Source {
+            consumer,
+            n_channels,
+            follows,
+            is_playing: false,
+            fill: 0.0,
+            frames: vec![0.0; 2 * n_channels],
+            phase: 0.0,
+        }
+    }
+
+    /// Writes the device's channels of each of the `output` frames, `stride` samples apart,
+    /// starting `offset` samples into each.
+    fn read(&mut self, output: &mut [f32], stride: usize, offset: usize) {
+        let n_channels = self.n_channels;
+        let available = self.consumer.len() / n_channels;
+        if !self.is_playing {
+            if available < LATENCY {
+                silence(output, stride, offset, n_channels);
+                return;
+            }
+            self.is_playing = true;
+            self.fill = available as f64;
+            self.phase = 1.0;
+            self.consumer.pop_slice(&mut self.frames[n_channels..]);
+        }
+        self.fill += (available as f64 - self.fill) * SMOOTHING;
+        // How many of the device's frames each of the callback's takes.
+        let ratio = if self.follows {
+            let error = (self.fill - LATENCY as f64) / LATENCY as f64;
+            1.0 + (error * GAIN).clamp(-MAX_DRIFT, MAX_DRIFT)
+        } else {
+            1.0
+        };
+        for (index, frame) in output.chunks_exact_mut(stride).enumerate() {
+            while self.phase >= 1.0 {
+                self.frames.copy_within(n_channels.., 0);
+                if self.consumer.pop_slice(&mut self.frames[n_channels..]) < n_channels {
+                    // Buffers the latency again before playing on.
+                    self.is_playing = false;
+                    silence(&mut output[index * stride..], stride, offset, n_channels);
+                    return;
+                }
+                self.phase -= 1.0;
+            }
+            let (previous, next) = self.frames.split_at(n_channels);
+            for (channel, sample) in frame[offset..offset + n_channels].iter_mut().enumerate() {
+                *sample =
+                    previous[channel] + (next[channel] - previous[channel]) * self.phase as f32;
+            }
+            self.phase += ratio;
+        }
+    }
+}
+
+fn silence(output: &mut [f32], stride: usize, offset: usize, n_channels: usize) {
+    for frame in output.chunks_exact_mut(stride) {
+        frame[offset..offset + n_channels]
+            .iter_mut()
+            .for_each(|sample| *sample = 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Host};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A source of `n_frames` mono frames, each `value`.
+    fn source(n_frames: usize, value: f32, follows: bool) -> Source {
+        let (mut producer, consumer) = RingBuffer::new(CAPACITY).split();
+        producer.push_slice(&vec![value; n_frames]);
+        Source::new(consumer, 1, follows)
+    }
+
+    #[test]
+    fn waits_for_the_latency() {
+        let mut source = source(LATENCY - 1, 1.0, false);
+        let mut output = [1.0; 4];
+        source.read(&mut output, 2, 1);
+        assert_eq!(output, [1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(source.consumer.len(), LATENCY - 1);
+    }
+
+    #[test]
+    fn follows_the_fill_level() {
+        let mut output = vec![0.0; 1024];
+        // Devices that run the callback are read at their own pace: The first read pops the
+        // frame interpolated from, too.
+        let mut first = source(2 * LATENCY, 0.5, false);
+        first.read(&mut output, 1, 0);
+        assert_eq!(first.consumer.len(), 2 * LATENCY - 1025);
+        assert!(output.iter().all(|&sample| sample == 0.5));
+        // Others catch up when they are ahead.
+        let mut ahead = source(2 * LATENCY, 0.5, true);
+        ahead.read(&mut output, 1, 0);
+        assert_lt!(ahead.consumer.len(), 2 * LATENCY - 1025);
+        assert!(output.iter().all(|&sample| sample == 0.5));
+        // And fall back when they are behind.
+        let mut behind = source(LATENCY, 0.5, true);
+        behind.read(&mut output[..1], 1, 0);
+        behind.fill = 0.0;
+        let before = behind.consumer.len();
+        behind.read(&mut output, 1, 0);
+        assert_lt!(before - behind.consumer.len(), 1024);
+    }
+
+    #[test]
+    fn aggregates_devices() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        let mut first = host.default_input_device()?;
+        let mut second = host.default_input_device()?;
+        let called = Arc::new(AtomicBool::new(false));
+        let cb_called = Arc::clone(&called);
+        let mut streams = AggregateInput::new(48000)
+            .device::<2>(&mut first)
+            .device::<1>(&mut second)
+            .open(Box::new(move |frames: &[[f32; 3]]| {
+                // The dummy devices capture silence.
+                assert!(frames.iter().all(|&frame| frame == [0.0; 3]));
+                cb_called.store(true, Ordering::Relaxed);
+            }))?;
+        assert_eq!(streams.len(), 2);
+        streams.start()?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(called.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_channel_counts() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_input_device()?;
+        assert_eq!(
+            AggregateInput::new(48000)
+                .device::<2>(&mut device)
+                .open(Box::new(|_: &[[f32; 3]]| {}))
+                .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use crate::alsa::stream::{self, Stream};
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+
+/// The default number of periods in a stream's ring buffer.
+const DEFAULT_PERIODS: u32 = 2;
+
+pub struct Device {
+    name: String,
+    is_output: bool,
+    periods: u32,
+}
+
+impl Device {
+    pub(super) fn new(name: &str, is_output: bool) -> Device {
+        Device {
+            name: name.to_owned(),
+            is_output,
+            periods: DEFAULT_PERIODS,
+        }
+    }
+
+    /// The device's PCM name (e.g. "default" or "hw:0,0").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of periods in the ring buffer of streams opened from now on. A period is
+    /// `frames_per_buffer` frames long, and each one results in a callback. Defaults to 2.
+    pub fn periods(&self) -> u32 {
+        self.periods
+    }
+
+    /// Sets the number of periods of streams opened from now on. More periods trade latency for
+    /// resilience against underruns. ALSA picks the nearest supported count.
+    pub fn set_periods(&mut self, periods: u32) -> Result<()> {
+        if periods < 2 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        self.periods = periods;
+        Ok(())
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alsa::Host;
+    use crate::error::{Error, Result};
+    use crate::stream_options::{Input, StreamOptions};
+
+    #[test]
+    fn validates_periods() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        assert_eq!(device.periods(), 2);
+        assert_eq!(device.set_periods(1), Err(Error::InvalidFramesPerBuffer));
+        device.set_periods(4)?;
+        assert_eq!(device.periods(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_direction_mismatches() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let result = device.open_input_stream(StreamOptions::<[f32; 1], Input>::default());
+        assert_eq!(result.err(), Some(Error::IncompatibleNChannels));
+        Ok(())
+    }
+}
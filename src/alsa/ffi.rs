@@ -0,0 +1,189 @@
+//! Hand-written bindings to the subset of alsa-lib (and libc) used by the backend.
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong, c_ushort, c_void};
+
+pub enum snd_pcm_t {}
+pub enum snd_pcm_hw_params_t {}
+pub enum snd_pcm_sw_params_t {}
+
+pub type snd_pcm_uframes_t = c_ulong;
+pub type snd_pcm_sframes_t = c_long;
+pub type snd_pcm_format_t = c_int;
+
+pub const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+pub const SND_PCM_STREAM_CAPTURE: c_int = 1;
+pub const SND_PCM_NONBLOCK: c_int = 1;
+pub const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+
+pub const SND_PCM_FORMAT_S8: snd_pcm_format_t = 0;
+pub const SND_PCM_FORMAT_U8: snd_pcm_format_t = 1;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_S16: snd_pcm_format_t = 2;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_S16: snd_pcm_format_t = 3;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_U16: snd_pcm_format_t = 4;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_U16: snd_pcm_format_t = 5;
+/// 24-bit samples in the low 3 bytes of 4.
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_S24: snd_pcm_format_t = 6;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_S24: snd_pcm_format_t = 7;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_S32: snd_pcm_format_t = 10;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_S32: snd_pcm_format_t = 11;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_U32: snd_pcm_format_t = 12;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_U32: snd_pcm_format_t = 13;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_FLOAT: snd_pcm_format_t = 14;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_FLOAT: snd_pcm_format_t = 15;
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_FLOAT64: snd_pcm_format_t = 16;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_FLOAT64: snd_pcm_format_t = 17;
+/// Packed 24-bit samples (3 bytes each).
+#[cfg(target_endian = "little")]
+pub const SND_PCM_FORMAT_S24_3: snd_pcm_format_t = 32;
+#[cfg(target_endian = "big")]
+pub const SND_PCM_FORMAT_S24_3: snd_pcm_format_t = 33;
+
+pub const ENOENT: c_int = 2;
+pub const EAGAIN: c_int = 11;
+pub const ENOMEM: c_int = 12;
+pub const EBUSY: c_int = 16;
+pub const ENODEV: c_int = 19;
+pub const EINVAL: c_int = 22;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct pollfd {
+    pub fd: c_int,
+    pub events: c_short,
+    pub revents: c_short,
+}
+
+pub const POLLIN: c_short = 0x1;
+pub const POLLOUT: c_short = 0x4;
+pub const POLLERR: c_short = 0x8;
+
+#[link(name = "asound")]
+extern "C" {
+    pub fn snd_pcm_open(
+        pcm: *mut *mut snd_pcm_t,
+        name: *const c_char,
+        stream: c_int,
+        mode: c_int,
+    ) -> c_int;
+    pub fn snd_pcm_close(pcm: *mut snd_pcm_t) -> c_int;
+
+    pub fn snd_pcm_hw_params_malloc(ptr: *mut *mut snd_pcm_hw_params_t) -> c_int;
+    pub fn snd_pcm_hw_params_free(obj: *mut snd_pcm_hw_params_t);
+    pub fn snd_pcm_hw_params_any(pcm: *mut snd_pcm_t, params: *mut snd_pcm_hw_params_t) -> c_int;
+    pub fn snd_pcm_hw_params_set_access(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        access: c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_format(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        format: snd_pcm_format_t,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_channels(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        val: c_uint,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_rate(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        val: c_uint,
+        dir: c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_rate_near(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        val: *mut c_uint,
+        dir: *mut c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_period_size_near(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        val: *mut snd_pcm_uframes_t,
+        dir: *mut c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_set_periods_near(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_hw_params_t,
+        val: *mut c_uint,
+        dir: *mut c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params(pcm: *mut snd_pcm_t, params: *mut snd_pcm_hw_params_t) -> c_int;
+    pub fn snd_pcm_hw_params_get_period_size(
+        params: *const snd_pcm_hw_params_t,
+        val: *mut snd_pcm_uframes_t,
+        dir: *mut c_int,
+    ) -> c_int;
+    pub fn snd_pcm_hw_params_get_buffer_size(
+        params: *const snd_pcm_hw_params_t,
+        val: *mut snd_pcm_uframes_t,
+    ) -> c_int;
+
+    pub fn snd_pcm_sw_params_malloc(ptr: *mut *mut snd_pcm_sw_params_t) -> c_int;
+    pub fn snd_pcm_sw_params_free(obj: *mut snd_pcm_sw_params_t);
+    pub fn snd_pcm_sw_params_current(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_sw_params_t,
+    ) -> c_int;
+    pub fn snd_pcm_sw_params_set_avail_min(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_sw_params_t,
+        val: snd_pcm_uframes_t,
+    ) -> c_int;
+    pub fn snd_pcm_sw_params_set_start_threshold(
+        pcm: *mut snd_pcm_t,
+        params: *mut snd_pcm_sw_params_t,
+        val: snd_pcm_uframes_t,
+    ) -> c_int;
+    pub fn snd_pcm_sw_params(pcm: *mut snd_pcm_t, params: *mut snd_pcm_sw_params_t) -> c_int;
+
+    pub fn snd_pcm_start(pcm: *mut snd_pcm_t) -> c_int;
+    pub fn snd_pcm_drop(pcm: *mut snd_pcm_t) -> c_int;
+    pub fn snd_pcm_drain(pcm: *mut snd_pcm_t) -> c_int;
+    pub fn snd_pcm_nonblock(pcm: *mut snd_pcm_t, nonblock: c_int) -> c_int;
+    pub fn snd_pcm_recover(pcm: *mut snd_pcm_t, err: c_int, silent: c_int) -> c_int;
+    pub fn snd_pcm_avail_update(pcm: *mut snd_pcm_t) -> snd_pcm_sframes_t;
+    pub fn snd_pcm_writei(
+        pcm: *mut snd_pcm_t,
+        buffer: *const c_void,
+        size: snd_pcm_uframes_t,
+    ) -> snd_pcm_sframes_t;
+    pub fn snd_pcm_readi(
+        pcm: *mut snd_pcm_t,
+        buffer: *mut c_void,
+        size: snd_pcm_uframes_t,
+    ) -> snd_pcm_sframes_t;
+
+    pub fn snd_pcm_poll_descriptors_count(pcm: *mut snd_pcm_t) -> c_int;
+    pub fn snd_pcm_poll_descriptors(pcm: *mut snd_pcm_t, pfds: *mut pollfd, space: c_uint)
+        -> c_int;
+    pub fn snd_pcm_poll_descriptors_revents(
+        pcm: *mut snd_pcm_t,
+        pfds: *mut pollfd,
+        nfds: c_uint,
+        revents: *mut c_ushort,
+    ) -> c_int;
+}
+
+extern "C" {
+    pub fn poll(fds: *mut pollfd, nfds: c_ulong, timeout: c_int) -> c_int;
+    pub fn pipe(fds: *mut c_int) -> c_int;
+    pub fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+    pub fn close(fd: c_int) -> c_int;
+}
@@ -0,0 +1,38 @@
+use crate::alsa::device::Device;
+use crate::error::Result;
+
+/// The ALSA host. ALSA needs no global initialization.
+pub struct Host(());
+
+impl Host {
+    pub fn new() -> Result<Host> {
+        Ok(Host(()))
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "ALSA"
+    }
+
+    /// Returns ALSA's "default" playback PCM, which is usually routed through dmix or a sound
+    /// server.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new("default", true))
+    }
+
+    /// Returns ALSA's "default" capture PCM.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new("default", false))
+    }
+
+    /// Returns the playback PCM with the given name, e.g. "hw:0,0" or "plughw:1". The PCM is only
+    /// opened once a stream is.
+    pub fn output_device(&mut self, pcm_name: &str) -> Result<Device> {
+        Ok(Device::new(pcm_name, true))
+    }
+
+    /// Returns the capture PCM with the given name.
+    pub fn input_device(&mut self, pcm_name: &str) -> Result<Device> {
+        Ok(Device::new(pcm_name, false))
+    }
+}
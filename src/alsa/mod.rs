@@ -0,0 +1,132 @@
+//! Native ALSA backend for Linux.
+//!
+//! Talks to alsa-lib directly, instead of going through Portaudio. Exposes the same
+//! [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend. Streams are driven by a
+//! dedicated thread that polls the PCM, and call back once per period.
+use std::os::raw::{c_int, c_void};
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// Converts an alsa-lib return code (a negative errno on failure) into a [`Result`].
+fn check(code: c_int) -> Result<c_int> {
+    match code {
+        code if code >= 0 => Ok(code),
+        code => Err(match -code {
+            ffi::ENOENT | ffi::ENODEV => Error::NoSuchDevice,
+            ffi::ENOMEM => Error::OutOfMemory,
+            ffi::EINVAL => Error::Invalid,
+            ffi::EBUSY => Error::Backend(
+                BackendError::new(
+                    Some(Backend::Alsa),
+                    ErrorCode::Errno(ffi::EBUSY),
+                    "The ALSA device is busy.",
+                )
+                .with_category(ErrorCategory::DeviceUnavailable),
+            ),
+            errno => Error::Backend(BackendError::new(
+                Some(Backend::Alsa),
+                ErrorCode::Errno(errno),
+                "Unexpected ALSA error.",
+            )),
+        }),
+    }
+}
+
+/// An open PCM handle. Closed when dropped.
+struct Pcm(*mut ffi::snd_pcm_t);
+
+// A PCM is only ever used by one thread at a time: The opening thread until the stream is started,
+// and the stream's worker afterwards.
+unsafe impl Send for Pcm {}
+unsafe impl Sync for Pcm {}
+
+impl Drop for Pcm {
+    fn drop(&mut self) {
+        unsafe { ffi::snd_pcm_close(self.0) };
+    }
+}
+
+/// A self-pipe used to wake up a polling worker.
+struct Pipe {
+    read: c_int,
+    write: c_int,
+}
+
+impl Pipe {
+    fn new() -> Result<Pipe> {
+        let mut fds = [0; 2];
+        if unsafe { ffi::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(Error::from_source(
+                Some(Backend::Alsa),
+                "Could not create a pipe.",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(Pipe {
+            read: fds[0],
+            write: fds[1],
+        })
+    }
+
+    fn notify(&self) {
+        let byte = 1_u8;
+        unsafe { ffi::write(self.write, &byte as *const u8 as *const c_void, 1) };
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::close(self.read);
+            ffi::close(self.write);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_error_codes() {
+        assert_eq!(check(3), Ok(3));
+        assert_eq!(check(-ffi::ENOENT), Err(Error::NoSuchDevice));
+        assert_eq!(check(-ffi::EINVAL), Err(Error::Invalid));
+        let busy = check(-ffi::EBUSY).unwrap_err();
+        assert_eq!(busy.category(), ErrorCategory::DeviceUnavailable);
+        assert_eq!(busy.backend(), Some(Backend::Alsa));
+        assert_eq!(busy.code(), Some(ErrorCode::Errno(ffi::EBUSY)));
+        // The system describes errnos.
+        assert!(
+            std::error::Error::source(&busy).is_some_and(|source| source.is::<std::io::Error>())
+        );
+    }
+
+    #[test]
+    fn pipe_wakes_poll() -> Result<()> {
+        let pipe = Pipe::new()?;
+        let mut fd = ffi::pollfd {
+            fd: pipe.read,
+            events: ffi::POLLIN,
+            revents: 0,
+        };
+        assert_eq!(unsafe { ffi::poll(&mut fd, 1, 0) }, 0);
+        pipe.notify();
+        assert_eq!(unsafe { ffi::poll(&mut fd, 1, 0) }, 1);
+        assert_eq!(fd.revents, ffi::POLLIN);
+        Ok(())
+    }
+}
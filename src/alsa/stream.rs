@@ -0,0 +1,634 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_uint, c_void};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::alsa::device::Device;
+use crate::alsa::{check, ffi, Pcm, Pipe};
+use crate::error::{Error, Result};
+use crate::priority;
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// The rate used when the options ask for the device's default. ALSA has no notion of one.
+const FALLBACK_SAMPLE_RATE: c_uint = 48_000;
+
+type WorkerFn = Box<dyn FnOnce(Sender<Result<()>>) + Send>;
+
+/// A stream on an ALSA PCM. The callback runs on a dedicated thread, once per period.
+pub struct Stream<Frame> {
+    pcm: Arc<Pcm>,
+    stop_pipe: Arc<Pipe>,
+    period_size: usize,
+    buffer_size: usize,
+    info: StreamInfo,
+    // The worker's body until the stream is started, and its handle afterwards.
+    pending_worker: Option<WorkerFn>,
+    worker: Option<JoinHandle<()>>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    /// Starts the stream. For output streams, this first primes the PCM's buffer by calling the
+    /// callback until it is full.
+    pub fn start(&mut self) -> Result<()> {
+        let worker = self
+            .pending_worker
+            .take()
+            .ok_or(Error::StreamAlreadyStarted)?;
+        let (started, on_started) = mpsc::channel();
+        self.worker = Some(std::thread::spawn(move || worker(started)));
+        on_started
+            .recv()
+            .unwrap_or(Err(Error::Unknown("The ALSA worker exited unexpectedly.")))
+    }
+
+    /// The negotiated number of frames per period, i.e. per callback.
+    pub fn period_size(&self) -> usize {
+        self.period_size
+    }
+
+    /// The negotiated size of the PCM's ring buffer, in frames.
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Output callbacks get a period at a time. Input callbacks get at most a period: Reads return
+    /// what was captured.
+    pub fn info(&self) -> StreamInfo {
+        self.info
+    }
+
+    /// Stops calling the callback, and blocks until the frames already in the PCM's buffer are
+    /// played. The stream can't be started again.
+    pub fn drain(&mut self) -> Result<()> {
+        self.stop_pipe.notify();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.pending_worker = None;
+        // Draining a non-blocking PCM returns right away.
+        check(unsafe { ffi::snd_pcm_nonblock(self.pcm.0, 0) })?;
+        check(unsafe { ffi::snd_pcm_drain(self.pcm.0) })?;
+        Ok(())
+    }
+
+    pub fn close(self) {}
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.stop_pipe.notify();
+        if let Some(worker) = self.worker.take() {
+            // A panicking callback has nothing left to clean up.
+            let _ = worker.join();
+        }
+        unsafe { ffi::snd_pcm_drop(self.pcm.0) };
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let mut options = options;
+    let config = open_pcm(device, &mut options, true)?;
+    let realtime_priority = realtime_priority(&options);
+    let stop_pipe = Arc::new(Pipe::new()?);
+    let worker = RenderWorker {
+        pcm: Arc::clone(&config.pcm),
+        buffer: period_buffer::<Frame>(config.period_size),
+        period_size: config.period_size,
+        buffer_size: config.buffer_size,
+        callback: options.callback,
+    };
+    let poller = Poller::new(&config.pcm, &stop_pipe)?;
+    let info = StreamInfo::fixed(config.period_size);
+    Ok(config.into_stream(
+        stop_pipe,
+        info,
+        Box::new(move |started| {
+            if realtime_priority {
+                priority::promote_current_thread(None);
+            }
+            worker.run(poller, started)
+        }),
+    ))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let mut options = options;
+    let config = open_pcm(device, &mut options, false)?;
+    let realtime_priority = realtime_priority(&options);
+    let stop_pipe = Arc::new(Pipe::new()?);
+    let worker = CaptureWorker {
+        pcm: Arc::clone(&config.pcm),
+        buffer: period_buffer::<Frame>(config.period_size),
+        period_size: config.period_size,
+        buffer_size: config.buffer_size,
+        callback: options.callback,
+    };
+    let poller = Poller::new(&config.pcm, &stop_pipe)?;
+    let info = StreamInfo::at_most(config.period_size);
+    Ok(config.into_stream(
+        stop_pipe,
+        info,
+        Box::new(move |started| {
+            if realtime_priority {
+                priority::promote_current_thread(None);
+            }
+            worker.run(poller, started)
+        }),
+    ))
+}
+
+/// Whether the stream's worker is promoted to real-time priority.
+fn realtime_priority<Frame, Kind: CallbackKind>(options: &StreamOptions<Frame, Kind>) -> bool {
+    options.realtime_priority || options.alsa.is_some_and(|alsa| alsa.realtime_scheduling)
+}
+
+/// A configured PCM, along with its negotiated sizes.
+struct PcmConfig {
+    pcm: Arc<Pcm>,
+    period_size: usize,
+    buffer_size: usize,
+}
+
+impl PcmConfig {
+    fn into_stream<Frame>(
+        self,
+        stop_pipe: Arc<Pipe>,
+        info: StreamInfo,
+        worker: WorkerFn,
+    ) -> Stream<Frame> {
+        Stream {
+            pcm: self.pcm,
+            stop_pipe,
+            period_size: self.period_size,
+            buffer_size: self.buffer_size,
+            info,
+            pending_worker: Some(worker),
+            worker: None,
+            _frame: PhantomData,
+        }
+    }
+}
+
+/// Opens the device's PCM, and negotiates its hardware and software parameters.
+fn open_pcm<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &mut StreamOptions<Frame, Kind>,
+    is_output: bool,
+) -> Result<PcmConfig> {
+    let name = pcm_name(device.name(), std::mem::take(&mut options.exclusive))?;
+    let alsa = options.alsa.unwrap_or_default();
+    alsa.validate()?;
+    options.frames_per_buffer = alsa.frames_per_buffer(options.frames_per_buffer)?;
+    match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+    }
+    options.validate_frame_size()?;
+    let format = alsa_format(options.format)?;
+
+    let name = CString::new(name).or(Err(Error::NoSuchDevice))?;
+    let direction = if is_output {
+        ffi::SND_PCM_STREAM_PLAYBACK
+    } else {
+        ffi::SND_PCM_STREAM_CAPTURE
+    };
+    let mut pcm = std::ptr::null_mut();
+    check(unsafe { ffi::snd_pcm_open(&mut pcm, name.as_ptr(), direction, ffi::SND_PCM_NONBLOCK) })?;
+    let pcm = Pcm(pcm);
+
+    let hw_params = HwParams::new()?;
+    let hw = hw_params.0;
+    unsafe {
+        check(ffi::snd_pcm_hw_params_any(pcm.0, hw))?;
+        check(ffi::snd_pcm_hw_params_set_access(
+            pcm.0,
+            hw,
+            ffi::SND_PCM_ACCESS_RW_INTERLEAVED,
+        ))?;
+        check(ffi::snd_pcm_hw_params_set_format(pcm.0, hw, format))
+            .or(Err(Error::IncompatibleFormat(options.format)))?;
+        check(ffi::snd_pcm_hw_params_set_channels(
+            pcm.0,
+            hw,
+            options.n_channels as c_uint,
+        ))
+        .or(Err(Error::IncompatibleNChannels))?;
+        match options.sample_rate {
+            SampleRate::Exact(rate) => check(ffi::snd_pcm_hw_params_set_rate(
+                pcm.0,
+                hw,
+                rate as c_uint,
+                0,
+            )),
+            SampleRate::NearestTo(rate) => {
+                let mut rate = rate as c_uint;
+                check(ffi::snd_pcm_hw_params_set_rate_near(
+                    pcm.0,
+                    hw,
+                    &mut rate,
+                    std::ptr::null_mut(),
+                ))
+            }
+            _ => {
+                let mut rate = FALLBACK_SAMPLE_RATE;
+                check(ffi::snd_pcm_hw_params_set_rate_near(
+                    pcm.0,
+                    hw,
+                    &mut rate,
+                    std::ptr::null_mut(),
+                ))
+            }
+        }
+        .or(Err(Error::IncompatibleSampleRate))?;
+        if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+            let mut period_size = frames_per_buffer as ffi::snd_pcm_uframes_t;
+            check(ffi::snd_pcm_hw_params_set_period_size_near(
+                pcm.0,
+                hw,
+                &mut period_size,
+                std::ptr::null_mut(),
+            ))
+            .or(Err(Error::InvalidFramesPerBuffer))?;
+        }
+        let mut periods = alsa.periods.unwrap_or_else(|| device.periods());
+        check(ffi::snd_pcm_hw_params_set_periods_near(
+            pcm.0,
+            hw,
+            &mut periods,
+            std::ptr::null_mut(),
+        ))
+        .or(Err(Error::InvalidFramesPerBuffer))?;
+        check(ffi::snd_pcm_hw_params(pcm.0, hw))?;
+    }
+    let (mut period_size, mut buffer_size) = (0, 0);
+    unsafe {
+        check(ffi::snd_pcm_hw_params_get_period_size(
+            hw,
+            &mut period_size,
+            std::ptr::null_mut(),
+        ))?;
+        check(ffi::snd_pcm_hw_params_get_buffer_size(hw, &mut buffer_size))?;
+    }
+
+    let sw_params = SwParams::new()?;
+    let sw = sw_params.0;
+    unsafe {
+        check(ffi::snd_pcm_sw_params_current(pcm.0, sw))?;
+        // Wake up once a whole period can be transferred.
+        check(ffi::snd_pcm_sw_params_set_avail_min(pcm.0, sw, period_size))?;
+        // Playback starts by itself once the worker has primed every whole period of the buffer,
+        // including after recovering from an underrun. Capture is started explicitly.
+        check(ffi::snd_pcm_sw_params_set_start_threshold(
+            pcm.0,
+            sw,
+            buffer_size / period_size * period_size,
+        ))?;
+        check(ffi::snd_pcm_sw_params(pcm.0, sw))?;
+    }
+    Ok(PcmConfig {
+        pcm: Arc::new(pcm),
+        period_size: period_size as usize,
+        buffer_size: buffer_size as usize,
+    })
+}
+
+fn alsa_format(format: Format) -> Result<ffi::snd_pcm_format_t> {
+    Ok(match format {
+        Format::F64 => ffi::SND_PCM_FORMAT_FLOAT64,
+        Format::F32 => ffi::SND_PCM_FORMAT_FLOAT,
+        Format::I32 => ffi::SND_PCM_FORMAT_S32,
+        Format::I24 => ffi::SND_PCM_FORMAT_S24_3,
+        Format::I24In32 => ffi::SND_PCM_FORMAT_S24,
+        Format::I16 => ffi::SND_PCM_FORMAT_S16,
+        Format::I8 => ffi::SND_PCM_FORMAT_S8,
+        Format::U32 => ffi::SND_PCM_FORMAT_U32,
+        Format::U16 => ffi::SND_PCM_FORMAT_U16,
+        Format::U8 => ffi::SND_PCM_FORMAT_U8,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    })
+}
+
+/// Allocates room for a period of frames. Stored as u64s for alignment, and so that workers stay
+/// Send regardless of Frame.
+fn period_buffer<Frame>(period_size: usize) -> Vec<u64> {
+    vec![0; (period_size * std::mem::size_of::<Frame>()).div_ceil(8)]
+}
+
+struct HwParams(*mut ffi::snd_pcm_hw_params_t);
+
+impl HwParams {
+    fn new() -> Result<HwParams> {
+        let mut params = std::ptr::null_mut();
+        check(unsafe { ffi::snd_pcm_hw_params_malloc(&mut params) })?;
+        Ok(HwParams(params))
+    }
+}
+
+impl Drop for HwParams {
+    fn drop(&mut self) {
+        unsafe { ffi::snd_pcm_hw_params_free(self.0) };
+    }
+}
+
+struct SwParams(*mut ffi::snd_pcm_sw_params_t);
+
+impl SwParams {
+    fn new() -> Result<SwParams> {
+        let mut params = std::ptr::null_mut();
+        check(unsafe { ffi::snd_pcm_sw_params_malloc(&mut params) })?;
+        Ok(SwParams(params))
+    }
+}
+
+impl Drop for SwParams {
+    fn drop(&mut self) {
+        unsafe { ffi::snd_pcm_sw_params_free(self.0) };
+    }
+}
+
+/// Polls a PCM's descriptors along with the stream's stop pipe.
+struct Poller {
+    // The stop pipe's read end comes first, followed by the PCM's descriptors.
+    fds: Vec<ffi::pollfd>,
+}
+
+impl Poller {
+    fn new(pcm: &Pcm, stop_pipe: &Pipe) -> Result<Poller> {
+        let count = check(unsafe { ffi::snd_pcm_poll_descriptors_count(pcm.0) })? as usize;
+        let empty = ffi::pollfd {
+            fd: -1,
+            events: 0,
+            revents: 0,
+        };
+        let mut fds = vec![empty; count + 1];
+        fds[0] = ffi::pollfd {
+            fd: stop_pipe.read,
+            events: ffi::POLLIN,
+            revents: 0,
+        };
+        check(unsafe {
+            ffi::snd_pcm_poll_descriptors(pcm.0, fds[1..].as_mut_ptr(), count as c_uint)
+        })?;
+        Ok(Poller { fds })
+    }
+
+    /// Blocks until the PCM is ready for a transfer (returns true), or the stream is stopped
+    /// (false). Errors such as underruns also count as ready, to be reported by the transfer.
+    fn wait(&mut self, pcm: &Pcm) -> Result<bool> {
+        loop {
+            // A failed poll (e.g. interrupted by a signal) is simply retried.
+            if unsafe { ffi::poll(self.fds.as_mut_ptr(), self.fds.len() as _, -1) } < 0 {
+                continue;
+            }
+            if self.fds[0].revents != 0 {
+                return Ok(false);
+            }
+            let mut revents = 0;
+            check(unsafe {
+                ffi::snd_pcm_poll_descriptors_revents(
+                    pcm.0,
+                    self.fds[1..].as_mut_ptr(),
+                    (self.fds.len() - 1) as c_uint,
+                    &mut revents,
+                )
+            })?;
+            if revents as i16 & (ffi::POLLIN | ffi::POLLOUT | ffi::POLLERR) != 0 {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Recovers from an underrun, overrun, or suspend.
+fn recover(pcm: &Pcm, error: c_int) -> Result<()> {
+    check(unsafe { ffi::snd_pcm_recover(pcm.0, error, 1) }).map(|_| ())
+}
+
+struct RenderWorker<Frame> {
+    pcm: Arc<Pcm>,
+    buffer: Vec<u64>,
+    period_size: usize,
+    buffer_size: usize,
+    callback: Callback<Frame>,
+}
+
+impl<Frame> RenderWorker<Frame> {
+    /// Runs until the stream is stopped, or the device fails (e.g. it is unplugged).
+    fn run(mut self, mut poller: Poller, started: Sender<Result<()>>) {
+        let primed = self.fill();
+        let failed = primed.is_err();
+        let _ = started.send(primed);
+        if failed {
+            return;
+        }
+        while let Ok(true) = poller.wait(&self.pcm) {
+            if self.fill().is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Writes whole periods for as long as there is room for them, up to a buffer's worth.
+    fn fill(&mut self) -> Result<()> {
+        // Bounded, so that a PCM that consumes frames faster than real time (e.g. "null") can't
+        // keep the worker from noticing it is being stopped.
+        for _ in 0..self.buffer_size / self.period_size {
+            let avail = unsafe { ffi::snd_pcm_avail_update(self.pcm.0) };
+            if avail < 0 {
+                recover(&self.pcm, avail as c_int)?;
+                continue;
+            }
+            if (avail as usize) < self.period_size {
+                return Ok(());
+            }
+            let output = unsafe {
+                std::slice::from_raw_parts_mut(
+                    self.buffer.as_mut_ptr() as *mut Frame,
+                    self.period_size,
+                )
+            };
+            (self.callback)(output);
+            let written = unsafe {
+                ffi::snd_pcm_writei(
+                    self.pcm.0,
+                    self.buffer.as_ptr() as *const c_void,
+                    self.period_size as ffi::snd_pcm_uframes_t,
+                )
+            };
+            match written as c_int {
+                written if written >= 0 => (),
+                written if written == -ffi::EAGAIN => return Ok(()),
+                // The period is lost, but the stream carries on.
+                written => recover(&self.pcm, written)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CaptureWorker<Frame> {
+    pcm: Arc<Pcm>,
+    buffer: Vec<u64>,
+    period_size: usize,
+    buffer_size: usize,
+    callback: InputCallback<Frame>,
+}
+
+impl<Frame> CaptureWorker<Frame> {
+    /// Runs until the stream is stopped, or the device fails (e.g. it is unplugged).
+    fn run(mut self, mut poller: Poller, started: Sender<Result<()>>) {
+        let result = check(unsafe { ffi::snd_pcm_start(self.pcm.0) }).map(|_| ());
+        let failed = result.is_err();
+        let _ = started.send(result);
+        if failed {
+            return;
+        }
+        while let Ok(true) = poller.wait(&self.pcm) {
+            if self.drain().is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Reads and calls back with periods for as long as there are frames available, up to a
+    /// buffer's worth.
+    fn drain(&mut self) -> Result<()> {
+        for _ in 0..self.buffer_size / self.period_size {
+            let read = unsafe {
+                ffi::snd_pcm_readi(
+                    self.pcm.0,
+                    self.buffer.as_mut_ptr() as *mut c_void,
+                    self.period_size as ffi::snd_pcm_uframes_t,
+                )
+            };
+            match read as c_int {
+                read if read == -ffi::EAGAIN => return Ok(()),
+                read if read < 0 => {
+                    recover(&self.pcm, read)?;
+                    check(unsafe { ffi::snd_pcm_start(self.pcm.0) })?;
+                }
+                read => {
+                    let input = unsafe {
+                        std::slice::from_raw_parts(
+                            self.buffer.as_ptr() as *const Frame,
+                            read as usize,
+                        )
+                    };
+                    (self.callback)(input);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of the PCM to open. Exclusive streams open the hardware directly, without the plugins
+/// that convert and mix, so they need a hardware PCM.
+fn pcm_name(name: &str, exclusive: bool) -> Result<String> {
+    if !exclusive || name.starts_with("hw:") {
+        return Ok(name.to_string());
+    }
+    match name.strip_prefix("plughw:") {
+        Some(card) => Ok(format!("hw:{}", card)),
+        None => Err(Error::IncompatibleStreamMode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alsa::Host;
+    use crate::linux::AlsaOptions;
+    use crate::stream_options::BufferSize;
+
+    #[test]
+    fn maps_formats() {
+        assert_eq!(alsa_format(Format::I24), Ok(ffi::SND_PCM_FORMAT_S24_3));
+        assert_eq!(alsa_format(Format::I24In32), Ok(ffi::SND_PCM_FORMAT_S24));
+        assert_eq!(alsa_format(Format::U8), Ok(ffi::SND_PCM_FORMAT_U8));
+    }
+
+    #[test]
+    fn opens_hardware_pcms_for_exclusive_streams() {
+        assert_eq!(pcm_name("default", false), Ok("default".to_string()));
+        assert_eq!(pcm_name("hw:0,0", true), Ok("hw:0,0".to_string()));
+        assert_eq!(pcm_name("plughw:1", true), Ok("hw:1".to_string()));
+        assert_eq!(
+            pcm_name("default", true),
+            Err(Error::IncompatibleStreamMode)
+        );
+    }
+
+    #[test]
+    fn sizes_period_buffers() {
+        assert_eq!(period_buffer::<[i16; 2]>(3).len(), 2);
+        assert_eq!(period_buffer::<[f32; 2]>(4).len(), 4);
+    }
+
+    #[test]
+    fn applies_alsa_options() {
+        let alsa = |periods, period_size| AlsaOptions {
+            periods,
+            period_size,
+            ..Default::default()
+        };
+        assert_eq!(alsa(Some(3), Some(64)).validate(), Ok(()));
+        assert_eq!(
+            alsa(Some(1), None).validate(),
+            Err(Error::InvalidFramesPerBuffer)
+        );
+        assert_eq!(
+            alsa(None, Some(0)).validate(),
+            Err(Error::InvalidFramesPerBuffer)
+        );
+        assert_eq!(
+            alsa(None, Some(64)).frames_per_buffer(BufferSize::Exact(256)),
+            Ok(BufferSize::Exact(64))
+        );
+        assert_eq!(
+            alsa(None, None).frames_per_buffer(BufferSize::Exact(256)),
+            Ok(BufferSize::Exact(256))
+        );
+        let options = StreamOptions::<[f32; 2]> {
+            realtime_priority: false,
+            alsa: Some(AlsaOptions {
+                realtime_scheduling: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(realtime_priority(&options));
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]> {
+            frames_per_buffer: BufferSize::Exact(256),
+            ..Default::default()
+        })?;
+        assert_gt!(stream.buffer_size(), stream.period_size());
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! Helps the root [`Host`](crate::Host) pick between the AAudio and OpenSL ES backends at runtime.
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+/// AAudio shipped in API level 26, but was too buggy to be used there.
+const MIN_AAUDIO_API_LEVEL: i32 = 27;
+
+/// Whether the device's AAudio is recent enough to be used by default.
+pub(crate) fn supports_aaudio() -> bool {
+    api_level() >= MIN_AAUDIO_API_LEVEL
+}
+
+/// The device's API level (`Build.VERSION.SDK_INT`), or 0 if it can't be read.
+fn api_level() -> i32 {
+    // PROP_VALUE_MAX.
+    let mut value = [0 as c_char; 92];
+    let sdk = unsafe {
+        __system_property_get(
+            b"ro.build.version.sdk\0".as_ptr() as *const c_char,
+            value.as_mut_ptr(),
+        );
+        CStr::from_ptr(value.as_ptr())
+    };
+    sdk.to_str()
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+// Part of bionic's libc.
+extern "C" {
+    fn __system_property_get(name: *const c_char, value: *mut c_char) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_a_backend() -> crate::Result<()> {
+        let host = crate::Host::with_default_backend()?;
+        println!("API level {} got {}.", api_level(), host.name());
+        assert_gt!(api_level(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Adapters that expose streams as tokio [`AsyncRead`]ers and [`AsyncWrite`]rs of raw bytes.
+//!
+//! The bytes are the stream's interleaved samples in native endianness, exactly as they are laid
+//! out in the stream's frames.
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::task::AtomicWaker;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::error::Result;
+use crate::ring::{Consumer, Producer, RingBuffer};
+use crate::stream_options::{Input, NoCallback, Output, StreamOptions};
+use crate::{Device, Stream};
+
+/// An output stream that plays the bytes written to it through [`AsyncWrite`].
+///
+/// Written bytes are buffered in a ring buffer of a fixed capacity, which the stream callback
+/// drains one whole frame at a time. Silence is played whenever the buffer runs dry.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// use tokio::io::AsyncWriteExt as _;
+/// let mut device = Host::with_default_backend()?.default_output_device()?;
+/// let mut writer =
+///     AsyncOutputWriter::<[i16; 2]>::open(&mut device, StreamOptions::default(), 4_800)?;
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(async {
+///     // A tenth of a second of stereo i16 silence.
+///     writer.write_all(&[0; 4_800 * 4]).await?;
+///     writer.flush().await
+/// }).unwrap();
+/// # Result::Ok(())
+/// ```
+pub struct AsyncOutputWriter<Frame> {
+    producer: Producer<u8>,
+    waker: Arc<AtomicWaker>,
+    _stream: Stream<Frame>,
+}
+
+/// An input stream whose captured bytes are read through [`AsyncRead`].
+///
+/// The stream callback pushes captured frames into a ring buffer of a fixed capacity. When the
+/// buffer is full (i.e. bytes are not read fast enough), newly captured frames are dropped.
+pub struct AsyncInputReader<Frame> {
+    consumer: Consumer<u8>,
+    waker: Arc<AtomicWaker>,
+    _stream: Stream<Frame>,
+}
+
+impl<Frame> AsyncOutputWriter<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an output stream on `device`, buffering up to `capacity` frames.
+    pub fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<AsyncOutputWriter<Frame>> {
+        let frame_size = std::mem::size_of::<Frame>();
+        let (producer, mut consumer) = RingBuffer::new(capacity * frame_size).split();
+        let waker = Arc::new(AtomicWaker::new());
+        let cb_waker = Arc::clone(&waker);
+        let mut stream = device.open_outstream(options.with_callback::<Output>(Box::new(
+            move |buffer: &mut [Frame]| {
+                // Only consume whole frames; a partially written frame waits for its remainder.
+                let frame_count = buffer.len().min(consumer.len() / frame_size);
+                consumer.pop_slice(as_bytes_mut(&mut buffer[..frame_count]));
+                for frame in &mut buffer[frame_count..] {
+                    *frame = Frame::equilibrium();
+                }
+                cb_waker.wake();
+            },
+        )))?;
+        stream.start()?;
+        Ok(AsyncOutputWriter {
+            producer,
+            waker,
+            _stream: stream,
+        })
+    }
+}
+
+impl<Frame> AsyncInputReader<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an input stream on `device`, buffering up to `capacity` frames.
+    pub fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<AsyncInputReader<Frame>> {
+        let frame_size = std::mem::size_of::<Frame>();
+        let (mut producer, consumer) = RingBuffer::new(capacity * frame_size).split();
+        let waker = Arc::new(AtomicWaker::new());
+        let cb_waker = Arc::clone(&waker);
+        let mut stream = device.open_input_stream(options.with_callback::<Input>(Box::new(
+            move |captured: &[Frame]| {
+                // Only push whole frames, so that readers never observe a torn frame.
+                let free_frames = (producer.capacity() - producer.len()) / frame_size;
+                let frame_count = captured.len().min(free_frames);
+                producer.push_slice(as_bytes(&captured[..frame_count]));
+                cb_waker.wake();
+            },
+        )))?;
+        stream.start()?;
+        Ok(AsyncInputReader {
+            consumer,
+            waker,
+            _stream: stream,
+        })
+    }
+}
+
+impl<Frame: Unpin> AsyncWrite for AsyncOutputWriter<Frame> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let count = this.producer.push_slice(buf);
+        if count > 0 || buf.is_empty() {
+            return Poll::Ready(Ok(count));
+        }
+        this.waker.register(cx.waker());
+        // Try again in case the callback ran before the waker was registered.
+        match this.producer.push_slice(buf) {
+            0 => Poll::Pending,
+            count => Poll::Ready(Ok(count)),
+        }
+    }
+
+    /// Completes once the stream callback has consumed every buffered whole frame.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let frame_size = std::mem::size_of::<Frame>();
+        if this.producer.len() < frame_size {
+            return Poll::Ready(Ok(()));
+        }
+        this.waker.register(cx.waker());
+        if this.producer.len() < frame_size {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<Frame: Unpin> AsyncRead for AsyncInputReader<Frame> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut count = this.consumer.pop_slice(buf.initialize_unfilled());
+        if count == 0 && buf.remaining() > 0 {
+            this.waker.register(cx.waker());
+            count = this.consumer.pop_slice(buf.initialize_unfilled());
+            if count == 0 {
+                return Poll::Pending;
+            }
+        }
+        buf.advance(count);
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Frames are plain arrays of samples, so they can be viewed as bytes.
+fn as_bytes<Frame: sample::Frame>(frames: &[Frame]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(frames.as_ptr() as *const u8, std::mem::size_of_val(frames))
+    }
+}
+
+fn as_bytes_mut<Frame: sample::Frame>(frames: &mut [Frame]) -> &mut [u8] {
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            frames.as_mut_ptr() as *mut u8,
+            std::mem::size_of_val(frames),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "portaudio"))]
+mod tests {
+    use super::*;
+    use crate::portaudio::test_prelude::*;
+    use crate::Host;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn adapters_are_send() {
+        assert_send::<AsyncOutputWriter<[f32; 2]>>();
+        assert_send::<AsyncInputReader<[f32; 1]>>();
+    }
+
+    #[test]
+    fn views_frames_as_bytes() {
+        let mut frames = [[1_i16, 2], [3, 4]];
+        assert_eq!(as_bytes(&frames).len(), 8);
+        as_bytes_mut(&mut frames)[..2].copy_from_slice(&5_i16.to_ne_bytes());
+        assert_eq!(frames, [[5, 2], [3, 4]]);
+    }
+
+    #[test]
+    fn writer_plays_bytes() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut writer =
+            AsyncOutputWriter::<[f32; 2]>::open(&mut device, StreamOptions::default(), 256)?;
+        block_on(async {
+            writer.write_all(&[0; 1024 * 8]).await?;
+            writer.flush().await
+        })
+        .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn reader_captures_bytes() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_input_device()?;
+        let mut reader =
+            AsyncInputReader::<[f32; 1]>::open(&mut device, StreamOptions::default(), 1024)?;
+        let mut captured = [0; 512 * 4];
+        block_on(reader.read_exact(&mut captured)).unwrap();
+        Ok(())
+    }
+}
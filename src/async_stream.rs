@@ -0,0 +1,204 @@
+//! Adapters that expose streams as [`futures::Sink`]s and [`futures::Stream`]s.
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::task::AtomicWaker;
+
+use crate::error::{Error, Result};
+use crate::ring::{Consumer, Producer, RingBuffer};
+use crate::stream_options::{Input, NoCallback, Output, StreamOptions};
+use crate::{Device, Stream};
+
+/// An output stream that is fed through a [`futures::Sink`] of frames.
+///
+/// Frames sent to the sink are buffered in a ring buffer of a fixed capacity, which the stream
+/// callback drains. Silence is played whenever the buffer runs dry.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// use futures::SinkExt as _;
+/// let mut device = Host::with_default_backend()?.default_output_device()?;
+/// let mut sink = OutputSink::<[f32; 2]>::open(&mut device, StreamOptions::default(), 4_800)?;
+/// futures::executor::block_on(async {
+///     for _ in 0..4_800 {
+///         sink.feed([0.0, 0.0]).await?;
+///     }
+///     sink.flush().await
+/// })?;
+/// # Result::Ok(())
+/// ```
+pub struct OutputSink<Frame> {
+    producer: Producer<Frame>,
+    waker: Arc<AtomicWaker>,
+    _stream: Stream<Frame>,
+}
+
+/// An input stream whose captured frames are read through a [`futures::Stream`].
+///
+/// The stream callback pushes captured frames into a ring buffer of a fixed capacity. When the
+/// buffer is full (i.e. frames are not consumed fast enough), newly captured frames are dropped.
+pub struct InputSource<Frame> {
+    consumer: Consumer<Frame>,
+    waker: Arc<AtomicWaker>,
+    _stream: Stream<Frame>,
+}
+
+impl<Frame> OutputSink<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an output stream on `device`, buffering up to `capacity` frames.
+    pub fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<OutputSink<Frame>> {
+        let (producer, mut consumer) = RingBuffer::new(capacity).split();
+        let waker = Arc::new(AtomicWaker::new());
+        let cb_waker = Arc::clone(&waker);
+        let mut stream = device.open_outstream(options.with_callback::<Output>(Box::new(
+            move |buffer: &mut [Frame]| {
+                let count = consumer.pop_slice(buffer);
+                for frame in &mut buffer[count..] {
+                    *frame = Frame::equilibrium();
+                }
+                cb_waker.wake();
+            },
+        )))?;
+        stream.start()?;
+        Ok(OutputSink {
+            producer,
+            waker,
+            _stream: stream,
+        })
+    }
+}
+
+impl<Frame> InputSource<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an input stream on `device`, buffering up to `capacity` frames.
+    pub fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<InputSource<Frame>> {
+        let (mut producer, consumer) = RingBuffer::new(capacity).split();
+        let waker = Arc::new(AtomicWaker::new());
+        let cb_waker = Arc::clone(&waker);
+        let mut stream = device.open_input_stream(options.with_callback::<Input>(Box::new(
+            move |captured: &[Frame]| {
+                producer.push_slice(captured);
+                cb_waker.wake();
+            },
+        )))?;
+        stream.start()?;
+        Ok(InputSource {
+            consumer,
+            waker,
+            _stream: stream,
+        })
+    }
+}
+
+impl<Frame: Copy + Unpin> futures::Sink<Frame> for OutputSink<Frame> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if !this.producer.is_full() {
+            return Poll::Ready(Ok(()));
+        }
+        this.waker.register(cx.waker());
+        // Check again in case the callback ran before the waker was registered.
+        if this.producer.is_full() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, frame: Frame) -> Result<()> {
+        self.get_mut()
+            .producer
+            .push(frame)
+            .or(Err(Error::Unknown("start_send called without poll_ready.")))
+    }
+
+    /// Completes once the stream callback has consumed every buffered frame.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if this.producer.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        this.waker.register(cx.waker());
+        if this.producer.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<Frame: Copy + Unpin> futures::Stream for InputSource<Frame> {
+    type Item = Frame;
+
+    /// Never completes; the stream captures for as long as it is alive.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Frame>> {
+        let this = self.get_mut();
+        if let Some(frame) = this.consumer.pop() {
+            return Poll::Ready(Some(frame));
+        }
+        this.waker.register(cx.waker());
+        match this.consumer.pop() {
+            Some(frame) => Poll::Ready(Some(frame)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "portaudio"))]
+mod tests {
+    use super::*;
+    use crate::portaudio::test_prelude::*;
+    use crate::Host;
+    use futures::{SinkExt as _, StreamExt as _};
+
+    #[test]
+    fn sink_is_send() {
+        assert_send::<OutputSink<[f32; 2]>>();
+        assert_send::<InputSource<[f32; 1]>>();
+    }
+
+    #[test]
+    fn sink_plays_frames() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut sink = OutputSink::<[f32; 2]>::open(&mut device, StreamOptions::default(), 256)?;
+        futures::executor::block_on(async {
+            // Send more frames than fit in the buffer to exercise back-pressure.
+            for _ in 0..1024 {
+                sink.feed([0.0, 0.0]).await?;
+            }
+            sink.flush().await
+        })
+    }
+
+    #[test]
+    fn source_captures_frames() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_input_device()?;
+        let source = InputSource::<[f32; 1]>::open(&mut device, StreamOptions::default(), 1024)?;
+        let captured = futures::executor::block_on(source.take(512).collect::<Vec<_>>());
+        assert_eq!(captured.len(), 512);
+        Ok(())
+    }
+}
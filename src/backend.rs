@@ -1,11 +1,47 @@
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Backend {
     None,
     Jack,
     Alsa,
     CoreAudio,
     Wasapi,
+    /// Steinberg's ASIO, for low-latency audio interfaces on Windows. Needs the `asio` feature.
+    Asio,
+    /// Windows's DirectSound, through Portaudio. Needs the `windows-legacy` feature.
+    DirectSound,
+    /// Windows's MME (WinMM), through Portaudio. Needs the `windows-legacy` feature.
+    Mme,
+    /// Windows's kernel streaming (WDM-KS), through Portaudio. Needs the `windows-legacy` feature.
+    WdmKs,
     LinuxFallback,
+    /// The [`null`](crate::null) backend, whose devices don't need any hardware. Always available.
     Dummy,
+    /// Android's AAudio (API level 27+). Only supported on Android.
+    AAudio,
+    /// Android's OpenSL ES. Only supported on Android.
+    OpenSles,
+    /// The PipeWire daemon. Needs the `pipewire` feature.
+    PipeWire,
+    /// The PulseAudio daemon (or PipeWire's replacement for it). Needs the `pulseaudio` feature.
+    PulseAudio,
+    /// The browser's WebAudio. Only supported on `wasm32-unknown-unknown`.
+    WebAudio,
+}
+
+/// One of the host APIs Portaudio was compiled with, as [`Host::host_apis`](crate::Host::host_apis)
+/// lists them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostApi {
+    /// Portaudio's name for it (e.g. "Windows WASAPI").
+    pub name: String,
+    /// The backend that opens a host on it with [`Host::with_backend`](crate::Host::with_backend),
+    /// or [`Backend::None`] if it isn't one of ours.
+    pub backend: Backend,
+    /// How many devices it has, input and output.
+    pub n_devices: usize,
+    /// Whether it's Portaudio's default, which [`Host::with_default_backend`] picks.
+    ///
+    /// [`Host::with_default_backend`]: crate::Host::with_default_backend
+    pub is_default: bool,
 }
@@ -0,0 +1,248 @@
+//! Streams that buffer frames for the application's threads, for apps that would rather push and
+//! pull frames than structure their code around callbacks. See
+//! [`Device::open_output_writer`] and [`Device::open_input_reader`].
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::ring::{Consumer, Producer, RingBuffer};
+use crate::stream_options::{Input, NoCallback, Output, StreamOptions};
+use crate::{Device, Stream};
+
+/// How often [`OutputWriter::drain`] checks whether its buffer ran dry.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// How long [`OutputWriter::drain`] waits for its buffer to shrink before it gives up on it.
+const DRAIN_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An output stream that plays the frames written to it.
+///
+/// Written frames are buffered in a ring buffer of a fixed capacity, which the stream callback
+/// drains. Silence is played whenever the buffer runs dry. Writers can be sent to any thread, and
+/// never block.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// let mut device = Host::with_default_backend()?.default_output_device()?;
+/// let mut writer = device.open_output_writer::<[f32; 2]>(StreamOptions::default(), 4_800)?;
+/// // A tenth of a second of silence, at 48kHz.
+/// let silence = [[0.0; 2]; 4_800];
+/// let mut written = 0;
+/// while written < silence.len() {
+///     written += writer.write(&silence[written..]);
+///     std::thread::sleep(std::time::Duration::from_millis(10));
+/// }
+/// # Result::Ok(())
+/// ```
+pub struct OutputWriter<Frame> {
+    producer: Producer<Frame>,
+    /// Whether the writer is being drained, and takes no more frames.
+    draining: bool,
+    stream: Stream<Frame>,
+}
+
+/// An input stream whose captured frames are read from it.
+///
+/// The stream callback pushes captured frames into a ring buffer of a fixed capacity, for the
+/// reader to consume at its own pace. Frames captured while the buffer is full (i.e. when frames
+/// aren't read fast enough) are dropped, and counted. Readers can be sent to any thread, and
+/// never block.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// let mut device = Host::with_default_backend()?.default_input_device()?;
+/// let mut reader = device.open_input_reader::<[f32; 1]>(StreamOptions::default(), 48_000)?;
+/// std::thread::sleep(std::time::Duration::from_millis(100));
+/// let mut captured = vec![[0.0]; reader.available()];
+/// reader.read(&mut captured);
+/// # Result::Ok(())
+/// ```
+pub struct InputReader<Frame> {
+    consumer: Consumer<Frame>,
+    dropped_frames: Arc<AtomicU64>,
+    _stream: Stream<Frame>,
+}
+
+impl<Frame> OutputWriter<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an output stream on `device`, buffering up to `capacity` frames, the first
+    /// of which are `primer`.
+    pub(crate) fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+        primer: &[Frame],
+    ) -> Result<OutputWriter<Frame>> {
+        if primer.len() > capacity {
+            return Err(Error::Invalid);
+        }
+        let (mut producer, mut consumer) = RingBuffer::new(capacity).split();
+        producer.push_slice(primer);
+        let mut stream = device.open_outstream(options.with_callback::<Output>(Box::new(
+            move |buffer: &mut [Frame]| {
+                let count = consumer.pop_slice(buffer);
+                for frame in &mut buffer[count..] {
+                    *frame = Frame::equilibrium();
+                }
+            },
+        )))?;
+        stream.start()?;
+        Ok(OutputWriter {
+            producer,
+            draining: false,
+            stream,
+        })
+    }
+}
+
+impl<Frame: Copy> OutputWriter<Frame> {
+    /// Buffers as many of `frames` as there is room for, and returns how many it buffered.
+    pub fn write(&mut self, frames: &[Frame]) -> usize {
+        if self.draining {
+            return 0;
+        }
+        self.producer.push_slice(frames)
+    }
+
+    /// How many frames there is room for. None, once the writer is drained.
+    pub fn available(&self) -> usize {
+        if self.draining {
+            return 0;
+        }
+        self.producer.capacity() - self.producer.len()
+    }
+
+    /// Stops taking frames, and returns once the buffered ones are played: e.g. before dropping
+    /// the writer, which cuts them off. See [`Stream::drain`].
+    pub fn drain(&mut self) -> Result<()> {
+        self.draining = true;
+        // Streams whose callback isn't called anymore (e.g. that lost their device) won't play the
+        // rest.
+        let mut progress = (self.producer.len(), Instant::now());
+        while !self.producer.is_empty() && progress.1.elapsed() < DRAIN_STALL_TIMEOUT {
+            std::thread::sleep(DRAIN_POLL_INTERVAL);
+            if self.producer.len() != progress.0 {
+                progress = (self.producer.len(), Instant::now());
+            }
+        }
+        self.stream.drain()
+    }
+}
+
+impl<Frame> InputReader<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Opens and starts an input stream on `device`, buffering up to `capacity` frames.
+    pub(crate) fn open(
+        device: &mut Device,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<InputReader<Frame>> {
+        let (mut producer, consumer) = RingBuffer::new(capacity).split();
+        let dropped_frames = Arc::new(AtomicU64::new(0));
+        let cb_dropped_frames = Arc::clone(&dropped_frames);
+        let mut stream = device.open_input_stream(options.with_callback::<Input>(Box::new(
+            move |captured: &[Frame]| {
+                let dropped = captured.len() - producer.push_slice(captured);
+                if dropped > 0 {
+                    cb_dropped_frames.fetch_add(dropped as u64, Ordering::Relaxed);
+                }
+            },
+        )))?;
+        stream.start()?;
+        Ok(InputReader {
+            consumer,
+            dropped_frames,
+            _stream: stream,
+        })
+    }
+}
+
+impl<Frame: Copy> InputReader<Frame> {
+    /// Reads as many captured frames into `frames` as are buffered, and returns how many it read.
+    pub fn read(&mut self, frames: &mut [Frame]) -> usize {
+        self.consumer.pop_slice(frames)
+    }
+
+    /// How many captured frames are buffered.
+    pub fn available(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// How many captured frames were dropped because the buffer was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Host};
+    use std::time::Duration;
+
+    #[test]
+    fn writer_plays_frames() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let mut writer = device.open_output_writer::<[f32; 2]>(StreamOptions::default(), 4)?;
+        assert_eq!(writer.available(), 4);
+        assert_eq!(writer.write(&[[0.5, -0.5]; 6]), 4);
+        // The dummy device drains the frames in its next buffer.
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(writer.available(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn writer_drains_frames() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let mut writer = device.open_output_writer::<[f32; 2]>(StreamOptions::default(), 4)?;
+        assert_eq!(writer.write(&[[0.5, -0.5]; 4]), 4);
+        writer.drain()?;
+        assert_eq!(writer.available(), 0);
+        assert_eq!(writer.write(&[[0.5, -0.5]; 4]), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn primes_writer() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let too_many = [[0.5, -0.5]; 5];
+        assert_eq!(
+            device
+                .open_primed_output_writer(StreamOptions::<[f32; 2], _>::default(), 4, &too_many)
+                .err(),
+            Some(Error::Invalid)
+        );
+        let writer = device.open_primed_output_writer(
+            StreamOptions::<[f32; 2], _>::default(),
+            4,
+            &[[0.5, -0.5]; 4],
+        )?;
+        // The dummy device plays the primer in its first buffer.
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(writer.available(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn reader_captures_frames() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_input_device()?;
+        let mut reader = device.open_input_reader::<[f32; 1]>(StreamOptions::default(), 16)?;
+        // The dummy device captures more silence than fits.
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(reader.available(), 16);
+        assert_gt!(reader.dropped_frames(), 0);
+        let mut frames = [[1.0]; 32];
+        assert_eq!(reader.read(&mut frames), 16);
+        assert_eq!(frames[..16], [[0.0]; 16]);
+        Ok(())
+    }
+}
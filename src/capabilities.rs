@@ -0,0 +1,44 @@
+//! What a device supports, for presenting only valid configurations (e.g. in a settings UI)
+//! before opening a stream.
+use crate::stream_options::Format;
+
+/// The sample rates devices are checked for, on top of their default one.
+#[cfg_attr(
+    not(all(
+        feature = "portaudio",
+        not(any(target_os = "android", target_arch = "wasm32"))
+    )),
+    allow(dead_code)
+)]
+pub(crate) const STANDARD_SAMPLE_RATES: [i32; 11] = [
+    8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 176_400, 192_000,
+];
+
+/// What a device supports. See [`Device::capabilities`](crate::Device::capabilities).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviceCapabilities {
+    /// The standard sample rates the device runs at (and its default one, if it isn't standard),
+    /// in ascending order. Streams at other rates are resampled, if they ask to be (see
+    /// [`StreamOptions::resample_if_needed`](crate::StreamOptions::resample_if_needed)).
+    pub sample_rates: Vec<i32>,
+    /// The formats the device takes natively, from the most precise. Streams can use other
+    /// formats, which are converted.
+    pub formats: Vec<Format>,
+    /// How many channels output streams can have. 0 for input devices.
+    pub max_output_channels: i32,
+    /// How many channels input streams can have. 0 for output devices.
+    pub max_input_channels: i32,
+}
+
+impl DeviceCapabilities {
+    /// The lowest of the [`sample_rates`](DeviceCapabilities::sample_rates).
+    pub fn min_sample_rate(&self) -> Option<i32> {
+        self.sample_rates.first().copied()
+    }
+
+    /// The highest of the [`sample_rates`](DeviceCapabilities::sample_rates).
+    pub fn max_sample_rate(&self) -> Option<i32> {
+        self.sample_rates.last().copied()
+    }
+}
@@ -0,0 +1,420 @@
+//! Interleaves and deinterleaves buffers of samples: between interleaved frames, as most streams
+//! take them, and one buffer per channel, as planar streams and most DSP code take them.
+//!
+//! Channel counts up to 8 have their own loops, which the compiler vectorizes. Stereo buffers of
+//! 16-bit and 32-bit samples (e.g. `i16` and `f32`) are shuffled with SIMD instructions on x86_64
+//! (SSE2) and aarch64 (NEON), which both always have them.
+//!
+//! # Examples
+//!
+//! ```
+//! use audiohal::convert;
+//! let (left, right) = ([1.0f32, 3.0], [2.0f32, 4.0]);
+//! let mut interleaved = [0.0; 4];
+//! convert::interleave(&[&left, &right], &mut interleaved);
+//! assert_eq!(interleaved, [1.0, 2.0, 3.0, 4.0]);
+//!
+//! let (mut left, mut right) = ([0.0; 2], [0.0; 2]);
+//! convert::deinterleave(&interleaved, &mut [&mut left, &mut right]);
+//! assert_eq!((left, right), ([1.0, 3.0], [2.0, 4.0]));
+//! ```
+use std::convert::TryInto;
+use std::mem::{align_of, size_of};
+
+/// Interleaves `channels`, one buffer per channel, into `interleaved`.
+///
+/// # Panics
+///
+/// If the channels aren't all as long, or `interleaved` isn't as long as all of them together.
+pub fn interleave<S: Copy>(channels: &[&[S]], interleaved: &mut [S]) {
+    let n_frames = n_frames(
+        channels.iter().map(|channel| channel.len()),
+        interleaved.len(),
+    );
+    match channels.len() {
+        0 => (),
+        1 => interleaved.copy_from_slice(channels[0]),
+        2 => interleave_stereo(channels[0], channels[1], interleaved),
+        3 => interleave_n::<S, 3>(channels, interleaved, n_frames),
+        4 => interleave_n::<S, 4>(channels, interleaved, n_frames),
+        5 => interleave_n::<S, 5>(channels, interleaved, n_frames),
+        6 => interleave_n::<S, 6>(channels, interleaved, n_frames),
+        7 => interleave_n::<S, 7>(channels, interleaved, n_frames),
+        8 => interleave_n::<S, 8>(channels, interleaved, n_frames),
+        n_channels => {
+            for (i, frame) in interleaved.chunks_exact_mut(n_channels).enumerate() {
+                for (sample, channel) in frame.iter_mut().zip(channels) {
+                    *sample = channel[i];
+                }
+            }
+        }
+    }
+}
+
+/// Deinterleaves `interleaved` into `channels`, one buffer per channel.
+///
+/// # Panics
+///
+/// If the channels aren't all as long, or `interleaved` isn't as long as all of them together.
+pub fn deinterleave<S: Copy>(interleaved: &[S], channels: &mut [&mut [S]]) {
+    let n_frames = n_frames(
+        channels.iter().map(|channel| channel.len()),
+        interleaved.len(),
+    );
+    match channels {
+        [] => (),
+        [mono] => mono.copy_from_slice(interleaved),
+        [left, right] => deinterleave_stereo(interleaved, left, right),
+        _ => match channels.len() {
+            3 => deinterleave_n::<S, 3>(interleaved, channels, n_frames),
+            4 => deinterleave_n::<S, 4>(interleaved, channels, n_frames),
+            5 => deinterleave_n::<S, 5>(interleaved, channels, n_frames),
+            6 => deinterleave_n::<S, 6>(interleaved, channels, n_frames),
+            7 => deinterleave_n::<S, 7>(interleaved, channels, n_frames),
+            8 => deinterleave_n::<S, 8>(interleaved, channels, n_frames),
+            n_channels => {
+                for (i, frame) in interleaved.chunks_exact(n_channels).enumerate() {
+                    for (&sample, channel) in frame.iter().zip(channels.iter_mut()) {
+                        channel[i] = sample;
+                    }
+                }
+            }
+        },
+    }
+}
+
+/// The channels' length, checked against each other's and against the interleaved buffer's.
+fn n_frames(mut lengths: impl ExactSizeIterator<Item = usize>, n_samples: usize) -> usize {
+    let n_channels = lengths.len();
+    let n_frames = lengths.next().unwrap_or(0);
+    assert!(
+        lengths.all(|length| length == n_frames),
+        "Channels should all be as long."
+    );
+    assert_eq!(
+        n_frames * n_channels,
+        n_samples,
+        "The interleaved buffer should be as long as the channels together."
+    );
+    n_frames
+}
+
+fn interleave_n<S: Copy, const N: usize>(
+    channels: &[&[S]],
+    interleaved: &mut [S],
+    n_frames: usize,
+) {
+    let channels: &[&[S]; N] = channels.try_into().unwrap();
+    // Slicing up front spares the loop its bounds checks, so that it vectorizes.
+    let channels = channels.map(|channel| &channel[..n_frames]);
+    for (i, frame) in interleaved.chunks_exact_mut(N).enumerate() {
+        for (sample, channel) in frame.iter_mut().zip(&channels) {
+            *sample = channel[i];
+        }
+    }
+}
+
+fn deinterleave_n<S: Copy, const N: usize>(
+    interleaved: &[S],
+    channels: &mut [&mut [S]],
+    n_frames: usize,
+) {
+    let channels: &mut [&mut [S]; N] = channels.try_into().unwrap();
+    for channel in channels.iter_mut() {
+        *channel = &mut std::mem::take(channel)[..n_frames];
+    }
+    for (i, frame) in interleaved.chunks_exact(N).enumerate() {
+        for (&sample, channel) in frame.iter().zip(channels.iter_mut()) {
+            channel[i] = sample;
+        }
+    }
+}
+
+fn interleave_stereo<S: Copy>(left: &[S], right: &[S], interleaved: &mut [S]) {
+    // Samples are only reinterpreted as lanes of their own size and alignment, and only copied.
+    let n_shuffled = unsafe {
+        match (size_of::<S>(), align_of::<S>()) {
+            (2, 2) => simd::interleave_stereo_16(
+                left.as_ptr().cast(),
+                right.as_ptr().cast(),
+                interleaved.as_mut_ptr().cast(),
+                left.len(),
+            ),
+            (4, 4) => simd::interleave_stereo_32(
+                left.as_ptr().cast(),
+                right.as_ptr().cast(),
+                interleaved.as_mut_ptr().cast(),
+                left.len(),
+            ),
+            _ => 0,
+        }
+    };
+    let frames = interleaved.chunks_exact_mut(2).skip(n_shuffled);
+    for ((frame, &left), &right) in frames.zip(&left[n_shuffled..]).zip(&right[n_shuffled..]) {
+        frame[0] = left;
+        frame[1] = right;
+    }
+}
+
+fn deinterleave_stereo<S: Copy>(interleaved: &[S], left: &mut [S], right: &mut [S]) {
+    let n_shuffled = unsafe {
+        match (size_of::<S>(), align_of::<S>()) {
+            (2, 2) => simd::deinterleave_stereo_16(
+                interleaved.as_ptr().cast(),
+                left.as_mut_ptr().cast(),
+                right.as_mut_ptr().cast(),
+                left.len(),
+            ),
+            (4, 4) => simd::deinterleave_stereo_32(
+                interleaved.as_ptr().cast(),
+                left.as_mut_ptr().cast(),
+                right.as_mut_ptr().cast(),
+                left.len(),
+            ),
+            _ => 0,
+        }
+    };
+    let frames = interleaved.chunks_exact(2).skip(n_shuffled);
+    let channels = left[n_shuffled..].iter_mut().zip(&mut right[n_shuffled..]);
+    for (frame, (left, right)) in frames.zip(channels) {
+        *left = frame[0];
+        *right = frame[1];
+    }
+}
+
+/// Shuffles the first stereo frames of buffers of `n_frames` frames, as many as fill whole
+/// vectors, and returns how many that is. The pointers needn't be aligned further than their lanes.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    pub unsafe fn interleave_stereo_16(
+        left: *const u16,
+        right: *const u16,
+        interleaved: *mut u16,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 8 * 8;
+        for i in (0..n_shuffled).step_by(8) {
+            let left = _mm_loadu_si128(left.add(i).cast());
+            let right = _mm_loadu_si128(right.add(i).cast());
+            let interleaved = interleaved.add(2 * i).cast::<__m128i>();
+            _mm_storeu_si128(interleaved, _mm_unpacklo_epi16(left, right));
+            _mm_storeu_si128(interleaved.add(1), _mm_unpackhi_epi16(left, right));
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn deinterleave_stereo_16(
+        interleaved: *const u16,
+        left: *mut u16,
+        right: *mut u16,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 8 * 8;
+        for i in (0..n_shuffled).step_by(8) {
+            let interleaved = interleaved.add(2 * i).cast::<__m128i>();
+            let (low, high) = (
+                _mm_loadu_si128(interleaved),
+                _mm_loadu_si128(interleaved.add(1)),
+            );
+            // Each frame is a 32-bit lane. Sign-extending either of its halves keeps the packs
+            // from saturating, so that they only narrow it back.
+            let lefts = |frames| _mm_srai_epi32::<16>(_mm_slli_epi32::<16>(frames));
+            let rights = |frames| _mm_srai_epi32::<16>(frames);
+            _mm_storeu_si128(left.add(i).cast(), _mm_packs_epi32(lefts(low), lefts(high)));
+            _mm_storeu_si128(
+                right.add(i).cast(),
+                _mm_packs_epi32(rights(low), rights(high)),
+            );
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn interleave_stereo_32(
+        left: *const u32,
+        right: *const u32,
+        interleaved: *mut u32,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 4 * 4;
+        for i in (0..n_shuffled).step_by(4) {
+            let left = _mm_loadu_si128(left.add(i).cast());
+            let right = _mm_loadu_si128(right.add(i).cast());
+            let interleaved = interleaved.add(2 * i).cast::<__m128i>();
+            _mm_storeu_si128(interleaved, _mm_unpacklo_epi32(left, right));
+            _mm_storeu_si128(interleaved.add(1), _mm_unpackhi_epi32(left, right));
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn deinterleave_stereo_32(
+        interleaved: *const u32,
+        left: *mut u32,
+        right: *mut u32,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 4 * 4;
+        for i in (0..n_shuffled).step_by(4) {
+            // Float shuffles only move the samples' bits, whatever their type.
+            let interleaved = interleaved.add(2 * i).cast::<f32>();
+            let (low, high) = (_mm_loadu_ps(interleaved), _mm_loadu_ps(interleaved.add(4)));
+            _mm_storeu_ps(
+                left.add(i).cast(),
+                _mm_shuffle_ps::<0b10_00_10_00>(low, high),
+            );
+            _mm_storeu_ps(
+                right.add(i).cast(),
+                _mm_shuffle_ps::<0b11_01_11_01>(low, high),
+            );
+        }
+        n_shuffled
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod simd {
+    use std::arch::aarch64::*;
+
+    pub unsafe fn interleave_stereo_16(
+        left: *const u16,
+        right: *const u16,
+        interleaved: *mut u16,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 8 * 8;
+        for i in (0..n_shuffled).step_by(8) {
+            let frames = uint16x8x2_t(vld1q_u16(left.add(i)), vld1q_u16(right.add(i)));
+            vst2q_u16(interleaved.add(2 * i), frames);
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn deinterleave_stereo_16(
+        interleaved: *const u16,
+        left: *mut u16,
+        right: *mut u16,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 8 * 8;
+        for i in (0..n_shuffled).step_by(8) {
+            let frames = vld2q_u16(interleaved.add(2 * i));
+            vst1q_u16(left.add(i), frames.0);
+            vst1q_u16(right.add(i), frames.1);
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn interleave_stereo_32(
+        left: *const u32,
+        right: *const u32,
+        interleaved: *mut u32,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 4 * 4;
+        for i in (0..n_shuffled).step_by(4) {
+            let frames = uint32x4x2_t(vld1q_u32(left.add(i)), vld1q_u32(right.add(i)));
+            vst2q_u32(interleaved.add(2 * i), frames);
+        }
+        n_shuffled
+    }
+
+    pub unsafe fn deinterleave_stereo_32(
+        interleaved: *const u32,
+        left: *mut u32,
+        right: *mut u32,
+        n_frames: usize,
+    ) -> usize {
+        let n_shuffled = n_frames / 4 * 4;
+        for i in (0..n_shuffled).step_by(4) {
+            let frames = vld2q_u32(interleaved.add(2 * i));
+            vst1q_u32(left.add(i), frames.0);
+            vst1q_u32(right.add(i), frames.1);
+        }
+        n_shuffled
+    }
+}
+
+/// Elsewhere, stereo frames go through the plain loops.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd {
+    pub unsafe fn interleave_stereo_16(
+        _: *const u16,
+        _: *const u16,
+        _: *mut u16,
+        _: usize,
+    ) -> usize {
+        0
+    }
+
+    pub unsafe fn deinterleave_stereo_16(
+        _: *const u16,
+        _: *mut u16,
+        _: *mut u16,
+        _: usize,
+    ) -> usize {
+        0
+    }
+
+    pub unsafe fn interleave_stereo_32(
+        _: *const u32,
+        _: *const u32,
+        _: *mut u32,
+        _: usize,
+    ) -> usize {
+        0
+    }
+
+    pub unsafe fn deinterleave_stereo_32(
+        _: *const u32,
+        _: *mut u32,
+        _: *mut u32,
+        _: usize,
+    ) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks a round trip of `n_frames` frames against the plain loops, for every channel count
+    /// with a loop of its own, and one past them.
+    fn round_trips<S: Copy + PartialEq + std::fmt::Debug>(n_frames: usize, sample: fn(usize) -> S) {
+        for n_channels in 1..=9 {
+            let channels: Vec<Vec<S>> = (0..n_channels)
+                .map(|channel| (0..n_frames).map(|i| sample(i * 16 + channel)).collect())
+                .collect();
+            let expected: Vec<S> = (0..n_frames * n_channels)
+                .map(|i| channels[i % n_channels][i / n_channels])
+                .collect();
+            let mut interleaved = vec![sample(0); n_frames * n_channels];
+            let slices: Vec<&[S]> = channels.iter().map(Vec::as_slice).collect();
+            interleave(&slices, &mut interleaved);
+            assert_eq!(interleaved, expected, "{} channels", n_channels);
+
+            let mut deinterleaved = vec![vec![sample(0); n_frames]; n_channels];
+            let mut slices: Vec<&mut [S]> =
+                deinterleaved.iter_mut().map(Vec::as_mut_slice).collect();
+            deinterleave(&interleaved, &mut slices);
+            assert_eq!(deinterleaved, channels, "{} channels", n_channels);
+        }
+    }
+
+    #[test]
+    fn round_trips_common_sample_types() {
+        // Long enough for whole vectors, and a few frames past them.
+        round_trips(19, |i| i as f32);
+        round_trips(19, |i| -(i as i16));
+        round_trips(19, |i| i as f64);
+        round_trips(19, |i| [i as u8, 1, 2]);
+        round_trips(0, |i| i as f32);
+    }
+
+    #[test]
+    #[should_panic(expected = "as long as the channels")]
+    fn checks_buffer_lengths() {
+        interleave(&[&[0.0f32; 4], &[0.0; 4]], &mut [0.0; 6]);
+    }
+}
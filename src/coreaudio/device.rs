@@ -0,0 +1,186 @@
+use crate::coreaudio::property_address;
+use crate::coreaudio::stream::{self, Stream};
+use crate::coreaudio::{
+    ffi, get_property, get_property_bytes, get_string_property, set_property, PropertyBytes,
+};
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+
+const VOLUME: ffi::AudioObjectPropertySelector =
+    ffi::kAudioHardwareServiceDeviceProperty_VirtualMainVolume;
+const MUTE: ffi::AudioObjectPropertySelector = ffi::kAudioDevicePropertyMute;
+
+pub struct Device {
+    id: ffi::AudioDeviceID,
+    name: String,
+}
+
+impl Device {
+    pub(super) fn from_id(id: ffi::AudioDeviceID) -> Result<Device> {
+        let name = get_string_property(
+            id,
+            &property_address(
+                ffi::kAudioObjectPropertyName,
+                ffi::kAudioObjectPropertyScopeGlobal,
+            ),
+        )?;
+        Ok(Device { id, name })
+    }
+
+    /// The device's system name (e.g. "Built-in Output").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        stream::new_instream(self, options)
+    }
+
+    /// The device's volume, from 0 to 1, across all its channels.
+    pub fn volume(&self) -> Result<f32> {
+        get_property(self.id, &self.control_address(VOLUME)?)
+    }
+
+    /// Sets the device's volume, from 0 to 1, for every application.
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(Error::Invalid);
+        }
+        set_property(self.id, &self.control_address(VOLUME)?, &volume)
+    }
+
+    pub fn is_muted(&self) -> Result<bool> {
+        let muted: u32 = get_property(self.id, &self.control_address(MUTE)?)?;
+        Ok(muted != 0)
+    }
+
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        set_property(self.id, &self.control_address(MUTE)?, &u32::from(muted))
+    }
+
+    /// Where the volume or mute control is: on the output side of output devices, and the input
+    /// side of input devices.
+    fn control_address(
+        &self,
+        selector: ffi::AudioObjectPropertySelector,
+    ) -> Result<ffi::AudioObjectPropertyAddress> {
+        let scope = if self.n_channels(true)? > 0 {
+            ffi::kAudioDevicePropertyScopeOutput
+        } else {
+            ffi::kAudioDevicePropertyScopeInput
+        };
+        Ok(property_address(selector, scope))
+    }
+
+    pub(super) fn id(&self) -> ffi::AudioDeviceID {
+        self.id
+    }
+
+    /// The rate the device hardware is currently running at.
+    pub(super) fn nominal_sample_rate(&self) -> Result<f64> {
+        get_property(
+            self.id,
+            &property_address(
+                ffi::kAudioDevicePropertyNominalSampleRate,
+                ffi::kAudioObjectPropertyScopeGlobal,
+            ),
+        )
+    }
+
+    /// Switches the device hardware to the given rate, for every client of the device. The HAL
+    /// switches rates asynchronously: Waits, up to a second, for the switch to land.
+    pub(super) fn set_nominal_sample_rate(&self, sample_rate: f64) -> Result<()> {
+        if self.nominal_sample_rate()? == sample_rate {
+            return Ok(());
+        }
+        let address = property_address(
+            ffi::kAudioDevicePropertyNominalSampleRate,
+            ffi::kAudioObjectPropertyScopeGlobal,
+        );
+        match set_property(self.id, &address, &sample_rate) {
+            Err(Error::Backend(_)) => return Err(Error::IncompatibleSampleRate),
+            result => result?,
+        }
+        for _ in 0..100 {
+            if self.nominal_sample_rate()? == sample_rate {
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        Err(Error::IncompatibleSampleRate)
+    }
+
+    /// The total number of channels across all of the device's streams in the given direction.
+    pub(super) fn n_channels(&self, is_output: bool) -> Result<i32> {
+        let scope = if is_output {
+            ffi::kAudioDevicePropertyScopeOutput
+        } else {
+            ffi::kAudioDevicePropertyScopeInput
+        };
+        let bytes = get_property_bytes(
+            self.id,
+            &property_address(ffi::kAudioDevicePropertyStreamConfiguration, scope),
+        )?;
+        Ok(count_channels(&bytes))
+    }
+}
+
+/// The total number of channels of an AudioBufferList property's buffers. Buffers that the reply
+/// is too short to hold aren't counted.
+fn count_channels(bytes: &PropertyBytes) -> i32 {
+    use std::mem::size_of;
+    if bytes.len() < size_of::<ffi::AudioBufferList>() {
+        return 0;
+    }
+    // The property is a variable-length AudioBufferList, which its storage is aligned for.
+    let list = bytes.as_ptr() as *const ffi::AudioBufferList;
+    let n_buffers = unsafe { (*list).mNumberBuffers } as usize;
+    let buffers = unsafe { (*list).mBuffers.as_ptr() };
+    let header_size = size_of::<ffi::AudioBufferList>() - size_of::<ffi::AudioBuffer>();
+    let max_buffers = (bytes.len() - header_size) / size_of::<ffi::AudioBuffer>();
+    (0..n_buffers.min(max_buffers))
+        .map(|i| unsafe { (*buffers.add(i)).mNumberChannels } as i32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coreaudio::Host;
+    use crate::error::Result;
+
+    #[test]
+    fn counts_the_channels_of_short_replies() {
+        // An AudioBufferList of two buffers, of 2 and 6 channels (little-endian): its buffer
+        // count, and each buffer's channel count, byte size, and data pointer.
+        let storage = vec![2, 2, 0, 6, 0];
+        for len in [0, 4, 7, 8, 23] {
+            assert_eq!(count_channels(&PropertyBytes::new(storage.clone(), len)), 0);
+        }
+        // The reply only holds the first buffer.
+        assert_eq!(count_channels(&PropertyBytes::new(storage.clone(), 24)), 2);
+        assert_eq!(count_channels(&PropertyBytes::new(storage.clone(), 39)), 2);
+        assert_eq!(count_channels(&PropertyBytes::new(storage, 40)), 8);
+    }
+
+    #[test]
+    fn queries_default_output_device() -> Result<()> {
+        let device = Host::new()?.default_output_device()?;
+        assert!(!device.name().is_empty());
+        assert!(device.nominal_sample_rate()? > 0.0);
+        assert!(device.n_channels(true)? > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Hand-written bindings to the subset of CoreAudio, AudioToolbox, and CoreFoundation used by the
+//! backend.
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_void};
+
+pub type OSStatus = i32;
+pub type AudioObjectID = u32;
+pub type AudioDeviceID = AudioObjectID;
+pub type AudioObjectPropertySelector = u32;
+pub type AudioObjectPropertyScope = u32;
+pub type AudioObjectPropertyElement = u32;
+pub type AudioUnitPropertyID = u32;
+pub type AudioUnitScope = u32;
+pub type AudioUnitElement = u32;
+pub type AudioUnitRenderActionFlags = u32;
+pub type AudioComponent = *mut c_void;
+pub type AudioUnit = *mut c_void;
+pub type CFStringRef = *const c_void;
+pub type CFIndex = isize;
+pub type Boolean = u8;
+
+/// Packs a four character code, e.g. `fourcc(b"auou")`.
+pub const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) << 24 | (code[1] as u32) << 16 | (code[2] as u32) << 8 | code[3] as u32
+}
+
+pub const noErr: OSStatus = 0;
+pub const kAudioHardwareBadObjectError: OSStatus = fourcc(b"!obj") as OSStatus;
+pub const kAudioHardwareBadDeviceError: OSStatus = fourcc(b"!dev") as OSStatus;
+pub const kAudioHardwareIllegalOperationError: OSStatus = fourcc(b"nope") as OSStatus;
+pub const kAudioDeviceUnsupportedFormatError: OSStatus = fourcc(b"!dat") as OSStatus;
+pub const kAudioDevicePermissionsError: OSStatus = fourcc(b"!hog") as OSStatus;
+pub const kAudioUnitErr_FormatNotSupported: OSStatus = -10868;
+pub const kAudioUnitErr_InvalidPropertyValue: OSStatus = -10851;
+
+pub const kAudioObjectSystemObject: AudioObjectID = 1;
+pub const kAudioObjectPropertyScopeGlobal: AudioObjectPropertyScope = fourcc(b"glob");
+pub const kAudioObjectPropertyElementMaster: AudioObjectPropertyElement = 0;
+pub const kAudioObjectPropertyName: AudioObjectPropertySelector = fourcc(b"lnam");
+pub const kAudioHardwarePropertyDefaultInputDevice: AudioObjectPropertySelector = fourcc(b"dIn ");
+pub const kAudioHardwarePropertyDefaultOutputDevice: AudioObjectPropertySelector = fourcc(b"dOut");
+pub const kAudioDevicePropertyScopeInput: AudioObjectPropertyScope = fourcc(b"inpt");
+pub const kAudioDevicePropertyScopeOutput: AudioObjectPropertyScope = fourcc(b"outp");
+pub const kAudioDevicePropertyNominalSampleRate: AudioObjectPropertySelector = fourcc(b"nsrt");
+pub const kAudioDevicePropertyStreamConfiguration: AudioObjectPropertySelector = fourcc(b"slay");
+pub const kAudioDevicePropertyBufferFrameSize: AudioObjectPropertySelector = fourcc(b"fsiz");
+pub const kAudioDevicePropertyHogMode: AudioObjectPropertySelector = fourcc(b"oink");
+pub const kAudioDevicePropertyMute: AudioObjectPropertySelector = fourcc(b"mute");
+// The volume of the device's channels together, for devices without a master volume control.
+pub const kAudioHardwareServiceDeviceProperty_VirtualMainVolume: AudioObjectPropertySelector =
+    fourcc(b"vmvc");
+
+pub const kAudioUnitType_Output: u32 = fourcc(b"auou");
+pub const kAudioUnitSubType_HALOutput: u32 = fourcc(b"ahal");
+pub const kAudioUnitManufacturer_Apple: u32 = fourcc(b"appl");
+
+pub const kAudioUnitScope_Global: AudioUnitScope = 0;
+pub const kAudioUnitScope_Input: AudioUnitScope = 1;
+pub const kAudioUnitScope_Output: AudioUnitScope = 2;
+
+pub const kAudioUnitProperty_StreamFormat: AudioUnitPropertyID = 8;
+pub const kAudioUnitProperty_MaximumFramesPerSlice: AudioUnitPropertyID = 14;
+pub const kAudioUnitProperty_SetRenderCallback: AudioUnitPropertyID = 23;
+pub const kAudioOutputUnitProperty_CurrentDevice: AudioUnitPropertyID = 2000;
+pub const kAudioOutputUnitProperty_EnableIO: AudioUnitPropertyID = 2003;
+pub const kAudioOutputUnitProperty_SetInputCallback: AudioUnitPropertyID = 2005;
+
+pub const kAudioFormatLinearPCM: u32 = fourcc(b"lpcm");
+pub const kAudioFormatFlagIsFloat: u32 = 1 << 0;
+pub const kAudioFormatFlagIsSignedInteger: u32 = 1 << 2;
+pub const kAudioFormatFlagIsPacked: u32 = 1 << 3;
+pub const kAudioFormatFlagIsNonInterleaved: u32 = 1 << 5;
+
+pub const kCFStringEncodingUTF8: u32 = 0x0800_0100;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AudioObjectPropertyAddress {
+    pub mSelector: AudioObjectPropertySelector,
+    pub mScope: AudioObjectPropertyScope,
+    pub mElement: AudioObjectPropertyElement,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct AudioStreamBasicDescription {
+    pub mSampleRate: f64,
+    pub mFormatID: u32,
+    pub mFormatFlags: u32,
+    pub mBytesPerPacket: u32,
+    pub mFramesPerPacket: u32,
+    pub mBytesPerFrame: u32,
+    pub mChannelsPerFrame: u32,
+    pub mBitsPerChannel: u32,
+    pub mReserved: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AudioComponentDescription {
+    pub componentType: u32,
+    pub componentSubType: u32,
+    pub componentManufacturer: u32,
+    pub componentFlags: u32,
+    pub componentFlagsMask: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AudioBuffer {
+    pub mNumberChannels: u32,
+    pub mDataByteSize: u32,
+    pub mData: *mut c_void,
+}
+
+/// Variable-length in C. Only ever used here with a single (interleaved) buffer, or read through a
+/// pointer when querying a device's stream configuration.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AudioBufferList {
+    pub mNumberBuffers: u32,
+    pub mBuffers: [AudioBuffer; 1],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SMPTETime {
+    pub mSubframes: i16,
+    pub mSubframeDivisor: i16,
+    pub mCounter: u32,
+    pub mType: u32,
+    pub mFlags: u32,
+    pub mHours: i16,
+    pub mMinutes: i16,
+    pub mSeconds: i16,
+    pub mFrames: i16,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AudioTimeStamp {
+    pub mSampleTime: f64,
+    pub mHostTime: u64,
+    pub mRateScalar: f64,
+    pub mWordClockTime: u64,
+    pub mSMPTETime: SMPTETime,
+    pub mFlags: u32,
+    pub mReserved: u32,
+}
+
+pub type AURenderCallback = extern "C" fn(
+    inRefCon: *mut c_void,
+    ioActionFlags: *mut AudioUnitRenderActionFlags,
+    inTimeStamp: *const AudioTimeStamp,
+    inBusNumber: u32,
+    inNumberFrames: u32,
+    ioData: *mut AudioBufferList,
+) -> OSStatus;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AURenderCallbackStruct {
+    pub inputProc: AURenderCallback,
+    pub inputProcRefCon: *mut c_void,
+}
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    pub fn AudioObjectGetPropertyDataSize(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        outDataSize: *mut u32,
+    ) -> OSStatus;
+
+    pub fn AudioObjectGetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        ioDataSize: *mut u32,
+        outData: *mut c_void,
+    ) -> OSStatus;
+
+    pub fn AudioObjectSetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        inDataSize: u32,
+        inData: *const c_void,
+    ) -> OSStatus;
+}
+
+#[link(name = "AudioToolbox", kind = "framework")]
+extern "C" {
+    pub fn AudioComponentFindNext(
+        inComponent: AudioComponent,
+        inDesc: *const AudioComponentDescription,
+    ) -> AudioComponent;
+    pub fn AudioComponentInstanceNew(
+        inComponent: AudioComponent,
+        outInstance: *mut AudioUnit,
+    ) -> OSStatus;
+    pub fn AudioComponentInstanceDispose(inInstance: AudioUnit) -> OSStatus;
+
+    pub fn AudioUnitInitialize(inUnit: AudioUnit) -> OSStatus;
+    pub fn AudioUnitUninitialize(inUnit: AudioUnit) -> OSStatus;
+    pub fn AudioUnitSetProperty(
+        inUnit: AudioUnit,
+        inID: AudioUnitPropertyID,
+        inScope: AudioUnitScope,
+        inElement: AudioUnitElement,
+        inData: *const c_void,
+        inDataSize: u32,
+    ) -> OSStatus;
+    pub fn AudioUnitGetProperty(
+        inUnit: AudioUnit,
+        inID: AudioUnitPropertyID,
+        inScope: AudioUnitScope,
+        inElement: AudioUnitElement,
+        outData: *mut c_void,
+        ioDataSize: *mut u32,
+    ) -> OSStatus;
+    pub fn AudioUnitRender(
+        inUnit: AudioUnit,
+        ioActionFlags: *mut AudioUnitRenderActionFlags,
+        inTimeStamp: *const AudioTimeStamp,
+        inOutputBusNumber: u32,
+        inNumberFrames: u32,
+        ioData: *mut AudioBufferList,
+    ) -> OSStatus;
+    pub fn AudioOutputUnitStart(ci: AudioUnit) -> OSStatus;
+    pub fn AudioOutputUnitStop(ci: AudioUnit) -> OSStatus;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    pub fn CFStringGetLength(theString: CFStringRef) -> CFIndex;
+    pub fn CFStringGetMaximumSizeForEncoding(length: CFIndex, encoding: u32) -> CFIndex;
+    pub fn CFStringGetCString(
+        theString: CFStringRef,
+        buffer: *mut c_char,
+        bufferSize: CFIndex,
+        encoding: u32,
+    ) -> Boolean;
+    pub fn CFRelease(cf: *const c_void);
+}
@@ -0,0 +1,63 @@
+use crate::coreaudio::device::Device;
+use crate::coreaudio::{ffi, get_property, property_address};
+use crate::error::{Error, Result};
+
+/// The CoreAudio host. Unlike Portaudio, CoreAudio needs no global initialization.
+pub struct Host(());
+
+impl Host {
+    /// Connects to the system's CoreAudio HAL.
+    pub fn new() -> Result<Host> {
+        Ok(Host(()))
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "CoreAudio"
+    }
+
+    /// Creates and returns the system's default output device.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Device::from_id(default_device_id(true)?)
+    }
+
+    /// Creates and returns the system's default input device.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Device::from_id(default_device_id(false)?)
+    }
+}
+
+fn default_device_id(is_output: bool) -> Result<ffi::AudioDeviceID> {
+    let selector = if is_output {
+        ffi::kAudioHardwarePropertyDefaultOutputDevice
+    } else {
+        ffi::kAudioHardwarePropertyDefaultInputDevice
+    };
+    let id: ffi::AudioDeviceID = get_property(
+        ffi::kAudioObjectSystemObject,
+        &property_address(selector, ffi::kAudioObjectPropertyScopeGlobal),
+    )?;
+    // kAudioObjectUnknown.
+    if id == 0 {
+        return Err(Error::NoSuchDevice);
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Host>();
+    }
+
+    #[test]
+    fn creates_default_devices() -> Result<()> {
+        let mut host = Host::new()?;
+        println!("Default output is {}", host.default_output_device()?.name());
+        Ok(())
+    }
+}
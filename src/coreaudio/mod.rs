@@ -0,0 +1,207 @@
+//! Native CoreAudio backend for macOS.
+//!
+//! Talks to the HAL and the AUHAL audio unit directly, instead of going through Portaudio. Exposes
+//! the same [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend.
+use std::os::raw::c_void;
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// Converts a CoreAudio status code into a [`Result`].
+fn check(status: ffi::OSStatus) -> Result<()> {
+    match status {
+        ffi::noErr => Ok(()),
+        ffi::kAudioHardwareBadObjectError | ffi::kAudioHardwareBadDeviceError => {
+            Err(Error::NoSuchDevice)
+        }
+        ffi::kAudioHardwareIllegalOperationError => Err(Error::Backend(
+            BackendError::new(
+                Some(Backend::CoreAudio),
+                ErrorCode::OsStatus(status),
+                "CoreAudio reported an illegal operation.",
+            )
+            .with_category(ErrorCategory::InvalidUsage),
+        )),
+        status => Err(Error::Backend(BackendError::new(
+            Some(Backend::CoreAudio),
+            ErrorCode::OsStatus(status),
+            "Unexpected CoreAudio error.",
+        ))),
+    }
+}
+
+fn property_address(
+    selector: ffi::AudioObjectPropertySelector,
+    scope: ffi::AudioObjectPropertyScope,
+) -> ffi::AudioObjectPropertyAddress {
+    ffi::AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: scope,
+        mElement: ffi::kAudioObjectPropertyElementMaster,
+    }
+}
+
+/// Reads a fixed-size property of an audio object.
+fn get_property<T: Copy + Default>(
+    object: ffi::AudioObjectID,
+    address: &ffi::AudioObjectPropertyAddress,
+) -> Result<T> {
+    let mut value = T::default();
+    let mut size = std::mem::size_of::<T>() as u32;
+    check(unsafe {
+        ffi::AudioObjectGetPropertyData(
+            object,
+            address,
+            0,
+            std::ptr::null(),
+            &mut size,
+            &mut value as *mut T as *mut c_void,
+        )
+    })?;
+    Ok(value)
+}
+
+/// Writes a fixed-size property of an audio object.
+fn set_property<T>(
+    object: ffi::AudioObjectID,
+    address: &ffi::AudioObjectPropertyAddress,
+    value: &T,
+) -> Result<()> {
+    check(unsafe {
+        ffi::AudioObjectSetPropertyData(
+            object,
+            address,
+            0,
+            std::ptr::null(),
+            std::mem::size_of::<T>() as u32,
+            value as *const T as *const c_void,
+        )
+    })
+}
+
+/// The bytes of a variable-sized property, in `u64` storage so that they're suitably aligned for
+/// any CoreAudio struct.
+pub(crate) struct PropertyBytes {
+    storage: Vec<u64>,
+    len: usize,
+}
+
+impl PropertyBytes {
+    /// Holds the first `len` bytes of `storage`.
+    pub fn new(storage: Vec<u64>, len: usize) -> PropertyBytes {
+        PropertyBytes {
+            len: len.min(storage.len() * std::mem::size_of::<u64>()),
+            storage,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.storage.as_ptr() as *const u8
+    }
+}
+
+/// Reads a variable-sized property of an audio object.
+fn get_property_bytes(
+    object: ffi::AudioObjectID,
+    address: &ffi::AudioObjectPropertyAddress,
+) -> Result<PropertyBytes> {
+    let mut size = 0;
+    check(unsafe {
+        ffi::AudioObjectGetPropertyDataSize(object, address, 0, std::ptr::null(), &mut size)
+    })?;
+    let mut storage = vec![0_u64; (size as usize).div_ceil(8)];
+    check(unsafe {
+        ffi::AudioObjectGetPropertyData(
+            object,
+            address,
+            0,
+            std::ptr::null(),
+            &mut size,
+            storage.as_mut_ptr() as *mut c_void,
+        )
+    })?;
+    Ok(PropertyBytes::new(storage, size as usize))
+}
+
+/// Reads a CFString property of an audio object.
+fn get_string_property(
+    object: ffi::AudioObjectID,
+    address: &ffi::AudioObjectPropertyAddress,
+) -> Result<String> {
+    let cf_string: usize = get_property(object, address)?;
+    let cf_string = cf_string as ffi::CFStringRef;
+    if cf_string.is_null() {
+        return Err(Error::Unknown("CoreAudio returned a null string."));
+    }
+    let result = cf_string_to_string(cf_string);
+    unsafe { ffi::CFRelease(cf_string) };
+    result
+}
+
+fn cf_string_to_string(cf_string: ffi::CFStringRef) -> Result<String> {
+    let capacity = unsafe {
+        ffi::CFStringGetMaximumSizeForEncoding(
+            ffi::CFStringGetLength(cf_string),
+            ffi::kCFStringEncodingUTF8,
+        )
+    } + 1;
+    let mut buffer = vec![0_u8; capacity as usize];
+    if unsafe {
+        ffi::CFStringGetCString(
+            cf_string,
+            buffer.as_mut_ptr() as *mut _,
+            capacity,
+            ffi::kCFStringEncodingUTF8,
+        )
+    } == 0
+    {
+        return Err(Error::Unknown("Could not convert CFString to UTF-8."));
+    }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    buffer.truncate(len);
+    String::from_utf8(buffer).map_err(|error| {
+        Error::from_source(
+            Some(Backend::CoreAudio),
+            "Could not convert CFString to UTF-8.",
+            error,
+        )
+    })
+}
+
+/// Wraps a raw CoreAudio handle so it can be sent across threads. CoreAudio objects are
+/// thread-safe.
+struct Handle<T>(*mut T);
+
+unsafe impl<T> Send for Handle<T> {}
+unsafe impl<T> Sync for Handle<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_status_codes() {
+        assert_eq!(check(ffi::noErr), Ok(()));
+        assert_eq!(
+            check(ffi::kAudioHardwareBadDeviceError),
+            Err(Error::NoSuchDevice)
+        );
+        assert_eq!(ffi::fourcc(b"lpcm"), 0x6c70_636d);
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::backend::Backend;
+use crate::coreaudio::device::Device;
+use crate::coreaudio::{check, ffi, get_property, property_address, set_property, Handle};
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+use crate::macos::CoreAudioOptions;
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+// AUHAL's element 0 is the output side of the device, and element 1 the input side.
+const OUTPUT_ELEMENT: ffi::AudioUnitElement = 0;
+const INPUT_ELEMENT: ffi::AudioUnitElement = 1;
+
+/// A stream running on an AUHAL audio unit.
+pub struct Stream<Frame> {
+    unit: Handle<c_void>,
+    // Owns the state pointed to by the unit's callback. Must outlive the unit.
+    _state: Box<dyn Send>,
+    // Released once the unit is disposed of.
+    _hog: Option<Hog>,
+    info: StreamInfo,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        check(unsafe { ffi::AudioOutputUnitStart(self.unit.0) })
+    }
+
+    pub fn close(self) {}
+
+    /// The callback gets as many frames as the device delivers, up to the unit's maximum frames
+    /// per slice.
+    pub fn info(&self) -> StreamInfo {
+        self.info
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        // Teardown errors are not actionable. Stopping also waits for a running callback to return,
+        // so the callback state can safely be dropped afterwards.
+        unsafe {
+            ffi::AudioOutputUnitStop(self.unit.0);
+            ffi::AudioUnitUninitialize(self.unit.0);
+            ffi::AudioComponentInstanceDispose(self.unit.0);
+        }
+    }
+}
+
+struct OutputState<Frame> {
+    callback: Callback<Frame>,
+}
+
+struct InputState<Frame> {
+    unit: Handle<c_void>,
+    callback: InputCallback<Frame>,
+    // Holds up to `max_frames` captured frames. Stored as u64s for alignment, and so that the state
+    // stays Send regardless of Frame.
+    buffer: Vec<u64>,
+    max_frames: usize,
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let mut options = options;
+    let exclusive = std::mem::take(&mut options.exclusive);
+    let coreaudio = options.coreaudio.unwrap_or_default();
+    validate_options(device, &options, true)?;
+    set_nominal_sample_rate(device, options.sample_rate, &coreaudio)?;
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate as f64,
+        SampleRate::DeviceDefault => device.nominal_sample_rate()?,
+    };
+    let hog = if exclusive {
+        Some(Hog::new(device)?)
+    } else {
+        None
+    };
+    let unit = UnitGuard::new()?;
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_EnableIO,
+        ffi::kAudioUnitScope_Output,
+        OUTPUT_ELEMENT,
+        &1_u32,
+    )?;
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_CurrentDevice,
+        ffi::kAudioUnitScope_Global,
+        0,
+        &device.id(),
+    )?;
+    // The output unit converts the client format on its input scope to whatever the device uses.
+    let description = stream_description(options.format, options.n_channels, sample_rate)?;
+    if !coreaudio.converter {
+        let device_description =
+            unit_stream_format(unit.0, ffi::kAudioUnitScope_Output, OUTPUT_ELEMENT)?;
+        check_unconverted(&description, &device_description, options.format)?;
+    }
+    set_unit_property(
+        unit.0,
+        ffi::kAudioUnitProperty_StreamFormat,
+        ffi::kAudioUnitScope_Input,
+        OUTPUT_ELEMENT,
+        &description,
+    )?;
+    set_buffer_size(
+        device,
+        io_buffer_frames(device, &options, &coreaudio)?,
+        true,
+    )?;
+
+    let max_frames = max_frames_per_slice(unit.0)?;
+    let mut state = Box::new(OutputState {
+        callback: options.callback,
+    });
+    set_unit_property(
+        unit.0,
+        ffi::kAudioUnitProperty_SetRenderCallback,
+        ffi::kAudioUnitScope_Input,
+        OUTPUT_ELEMENT,
+        &ffi::AURenderCallbackStruct {
+            inputProc: output_callback::<Frame>,
+            inputProcRefCon: &mut *state as *mut OutputState<Frame> as *mut c_void,
+        },
+    )?;
+    unit.initialize(state, hog, StreamInfo::at_most(max_frames))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let mut options = options;
+    let exclusive = std::mem::take(&mut options.exclusive);
+    let coreaudio = options.coreaudio.unwrap_or_default();
+    validate_options(device, &options, false)?;
+    set_nominal_sample_rate(device, options.sample_rate, &coreaudio)?;
+    // AUHAL does not resample input, so the client rate must match the device's.
+    let sample_rate = device.nominal_sample_rate()?;
+    match options.sample_rate {
+        SampleRate::Exact(rate) if rate as f64 != sample_rate => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    let hog = if exclusive {
+        Some(Hog::new(device)?)
+    } else {
+        None
+    };
+    let unit = UnitGuard::new()?;
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_EnableIO,
+        ffi::kAudioUnitScope_Input,
+        INPUT_ELEMENT,
+        &1_u32,
+    )?;
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_EnableIO,
+        ffi::kAudioUnitScope_Output,
+        OUTPUT_ELEMENT,
+        &0_u32,
+    )?;
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_CurrentDevice,
+        ffi::kAudioUnitScope_Global,
+        0,
+        &device.id(),
+    )?;
+    let description = stream_description(options.format, options.n_channels, sample_rate)?;
+    if !coreaudio.converter {
+        let device_description =
+            unit_stream_format(unit.0, ffi::kAudioUnitScope_Input, INPUT_ELEMENT)?;
+        check_unconverted(&description, &device_description, options.format)?;
+    }
+    set_unit_property(
+        unit.0,
+        ffi::kAudioUnitProperty_StreamFormat,
+        ffi::kAudioUnitScope_Output,
+        INPUT_ELEMENT,
+        &description,
+    )?;
+    set_buffer_size(
+        device,
+        io_buffer_frames(device, &options, &coreaudio)?,
+        false,
+    )?;
+
+    let max_frames = max_frames_per_slice(unit.0)?;
+    let mut state = Box::new(InputState {
+        unit: Handle(unit.0),
+        callback: options.callback,
+        buffer: vec![0; (max_frames * std::mem::size_of::<Frame>()).div_ceil(8)],
+        max_frames,
+    });
+    set_unit_property(
+        unit.0,
+        ffi::kAudioOutputUnitProperty_SetInputCallback,
+        ffi::kAudioUnitScope_Global,
+        0,
+        &ffi::AURenderCallbackStruct {
+            inputProc: input_callback::<Frame>,
+            inputProcRefCon: &mut *state as *mut InputState<Frame> as *mut c_void,
+        },
+    )?;
+    unit.initialize(state, hog, StreamInfo::at_most(max_frames))
+}
+
+fn validate_options<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &StreamOptions<Frame, Kind>,
+    is_output: bool,
+) -> Result<()> {
+    match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    if options.n_channels <= 0 || options.n_channels > device.n_channels(is_output)? {
+        return Err(Error::IncompatibleNChannels);
+    }
+    if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+    }
+    options.validate_frame_size()
+}
+
+/// Describes interleaved linear PCM in the given format.
+fn stream_description(
+    format: Format,
+    n_channels: i32,
+    sample_rate: f64,
+) -> Result<ffi::AudioStreamBasicDescription> {
+    let flags = match format {
+        Format::F64 | Format::F32 => ffi::kAudioFormatFlagIsFloat | ffi::kAudioFormatFlagIsPacked,
+        Format::I32 | Format::I24 | Format::I16 | Format::I8 => {
+            ffi::kAudioFormatFlagIsSignedInteger | ffi::kAudioFormatFlagIsPacked
+        }
+        // Neither packed nor aligned high: The sample is in the container's low bits.
+        Format::I24In32 => ffi::kAudioFormatFlagIsSignedInteger,
+        // Unsigned integer PCM is signalled by the absence of the signed flag.
+        Format::U32 | Format::U16 | Format::U8 => ffi::kAudioFormatFlagIsPacked,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    };
+    let bits_per_channel = match format {
+        Format::I24In32 => 24,
+        _ => (format.sample_size() * 8) as u32,
+    };
+    let bytes_per_frame = (format.sample_size() * n_channels as usize) as u32;
+    Ok(ffi::AudioStreamBasicDescription {
+        mSampleRate: sample_rate,
+        mFormatID: ffi::kAudioFormatLinearPCM,
+        mFormatFlags: flags,
+        mBytesPerPacket: bytes_per_frame,
+        mFramesPerPacket: 1,
+        mBytesPerFrame: bytes_per_frame,
+        mChannelsPerFrame: n_channels as u32,
+        mBitsPerChannel: bits_per_channel,
+        mReserved: 0,
+    })
+}
+
+/// Switches the device to the stream's rate, if the stream asks for it.
+fn set_nominal_sample_rate(
+    device: &Device,
+    sample_rate: SampleRate,
+    coreaudio: &CoreAudioOptions,
+) -> Result<()> {
+    match sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate)
+            if coreaudio.set_nominal_sample_rate =>
+        {
+            device.set_nominal_sample_rate(rate as f64)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails unless the device's side of the unit has the stream's format, so that AUHAL's converter
+/// has nothing to do. Interleaving doesn't touch the samples, so it doesn't count.
+fn check_unconverted(
+    description: &ffi::AudioStreamBasicDescription,
+    device_description: &ffi::AudioStreamBasicDescription,
+    format: Format,
+) -> Result<()> {
+    let flags = |description: &ffi::AudioStreamBasicDescription| {
+        description.mFormatFlags & !ffi::kAudioFormatFlagIsNonInterleaved
+    };
+    if description.mSampleRate != device_description.mSampleRate {
+        Err(Error::IncompatibleSampleRate)
+    } else if description.mChannelsPerFrame != device_description.mChannelsPerFrame {
+        Err(Error::IncompatibleNChannels)
+    } else if description.mFormatID != device_description.mFormatID
+        || flags(description) != flags(device_description)
+        || description.mBitsPerChannel != device_description.mBitsPerChannel
+    {
+        Err(Error::IncompatibleFormat(format))
+    } else {
+        Ok(())
+    }
+}
+
+/// The stream's buffer size, in frames: Its IO buffer duration at the device's rate, if it has
+/// one.
+fn io_buffer_frames<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &StreamOptions<Frame, Kind>,
+    coreaudio: &CoreAudioOptions,
+) -> Result<Option<i32>> {
+    let duration = match coreaudio.io_buffer_duration {
+        Some(duration) => duration,
+        None => return Ok(options.frames_per_buffer.frames()),
+    };
+    let frames = (duration.as_secs_f64() * device.nominal_sample_rate()?).round();
+    if frames < 1.0 || frames > i32::MAX as f64 {
+        return Err(Error::InvalidFramesPerBuffer);
+    }
+    Ok(Some(frames as i32))
+}
+
+/// The most frames the unit renders at a time, which callbacks get at most.
+fn max_frames_per_slice(unit: ffi::AudioUnit) -> Result<usize> {
+    let mut max_frames = 0_u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    check(unsafe {
+        ffi::AudioUnitGetProperty(
+            unit,
+            ffi::kAudioUnitProperty_MaximumFramesPerSlice,
+            ffi::kAudioUnitScope_Global,
+            0,
+            &mut max_frames as *mut u32 as *mut c_void,
+            &mut size,
+        )
+    })?;
+    Ok(max_frames as usize)
+}
+
+/// Applies the requested buffer size to the device. Note that this affects every client of the
+/// device.
+fn set_buffer_size(device: &Device, frames_per_buffer: Option<i32>, is_output: bool) -> Result<()> {
+    let frames_per_buffer = match frames_per_buffer {
+        Some(frames_per_buffer) => frames_per_buffer as u32,
+        None => return Ok(()),
+    };
+    let scope = if is_output {
+        ffi::kAudioDevicePropertyScopeOutput
+    } else {
+        ffi::kAudioDevicePropertyScopeInput
+    };
+    let address = property_address(ffi::kAudioDevicePropertyBufferFrameSize, scope);
+    match check(unsafe {
+        ffi::AudioObjectSetPropertyData(
+            device.id(),
+            &address,
+            0,
+            std::ptr::null(),
+            std::mem::size_of::<u32>() as u32,
+            &frames_per_buffer as *const u32 as *const c_void,
+        )
+    }) {
+        Err(Error::Backend(_)) => Err(Error::InvalidFramesPerBuffer),
+        result => result,
+    }
+}
+
+/// The stream format on one of the unit's scopes.
+fn unit_stream_format(
+    unit: ffi::AudioUnit,
+    scope: ffi::AudioUnitScope,
+    element: ffi::AudioUnitElement,
+) -> Result<ffi::AudioStreamBasicDescription> {
+    let mut description = ffi::AudioStreamBasicDescription::default();
+    let mut size = std::mem::size_of::<ffi::AudioStreamBasicDescription>() as u32;
+    check(unsafe {
+        ffi::AudioUnitGetProperty(
+            unit,
+            ffi::kAudioUnitProperty_StreamFormat,
+            scope,
+            element,
+            &mut description as *mut ffi::AudioStreamBasicDescription as *mut c_void,
+            &mut size,
+        )
+    })?;
+    Ok(description)
+}
+
+fn set_unit_property<T>(
+    unit: ffi::AudioUnit,
+    property: ffi::AudioUnitPropertyID,
+    scope: ffi::AudioUnitScope,
+    element: ffi::AudioUnitElement,
+    value: &T,
+) -> Result<()> {
+    let status = unsafe {
+        ffi::AudioUnitSetProperty(
+            unit,
+            property,
+            scope,
+            element,
+            value as *const T as *const c_void,
+            std::mem::size_of::<T>() as u32,
+        )
+    };
+    match status {
+        ffi::kAudioUnitErr_FormatNotSupported | ffi::kAudioDeviceUnsupportedFormatError => {
+            Err(Error::Backend(
+                BackendError::new(
+                    Some(Backend::CoreAudio),
+                    ErrorCode::OsStatus(status),
+                    "CoreAudio does not support the stream format.",
+                )
+                .with_category(ErrorCategory::FormatUnsupported),
+            ))
+        }
+        ffi::kAudioUnitErr_InvalidPropertyValue => Err(Error::Invalid),
+        status => check(status),
+    }
+}
+
+/// Disposes of the audio unit if stream creation fails midway.
+struct UnitGuard(ffi::AudioUnit);
+
+impl UnitGuard {
+    fn new() -> Result<UnitGuard> {
+        let description = ffi::AudioComponentDescription {
+            componentType: ffi::kAudioUnitType_Output,
+            componentSubType: ffi::kAudioUnitSubType_HALOutput,
+            componentManufacturer: ffi::kAudioUnitManufacturer_Apple,
+            componentFlags: 0,
+            componentFlagsMask: 0,
+        };
+        let component = unsafe { ffi::AudioComponentFindNext(std::ptr::null_mut(), &description) };
+        if component.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        let mut unit = std::ptr::null_mut();
+        check(unsafe { ffi::AudioComponentInstanceNew(component, &mut unit) })?;
+        Ok(UnitGuard(unit))
+    }
+
+    /// Initializes the unit and hands its ownership to a new stream.
+    fn initialize<Frame, State: Send + 'static>(
+        self,
+        state: Box<State>,
+        hog: Option<Hog>,
+        info: StreamInfo,
+    ) -> Result<Stream<Frame>> {
+        check(unsafe { ffi::AudioUnitInitialize(self.0) })?;
+        let unit = Handle(self.0);
+        std::mem::forget(self);
+        Ok(Stream {
+            unit,
+            _state: state,
+            _hog: hog,
+            info,
+            _frame: PhantomData,
+        })
+    }
+}
+
+impl Drop for UnitGuard {
+    fn drop(&mut self) {
+        unsafe { ffi::AudioComponentInstanceDispose(self.0) };
+    }
+}
+
+/// Hog mode on a device, for exclusive streams: Other processes can't use the device until it's
+/// released, when the process' last exclusive stream on it is dropped.
+struct Hog(ffi::AudioDeviceID);
+
+lazy_static! {
+    // How many of the process' streams hog each device.
+    static ref HOGS: Mutex<HashMap<ffi::AudioDeviceID, usize>> = Mutex::new(HashMap::new());
+}
+
+impl Hog {
+    fn new(device: &Device) -> Result<Hog> {
+        let mut hogs = HOGS.lock();
+        if let Some(count) = hogs.get_mut(&device.id()) {
+            *count += 1;
+            return Ok(Hog(device.id()));
+        }
+        // Another process already hogs the device if another pid is set.
+        let pid = std::process::id() as i32;
+        let owner = hog_owner(device.id())?;
+        if owner != -1 && owner != pid {
+            return Err(Error::Backend(
+                BackendError::new(
+                    Some(Backend::CoreAudio),
+                    ErrorCode::OsStatus(ffi::kAudioDevicePermissionsError),
+                    "The device is hogged by another process.",
+                )
+                .with_category(ErrorCategory::DeviceUnavailable),
+            ));
+        }
+        if owner == -1 {
+            set_hog_owner(device.id(), pid)?;
+        }
+        hogs.insert(device.id(), 1);
+        Ok(Hog(device.id()))
+    }
+}
+
+impl Drop for Hog {
+    fn drop(&mut self) {
+        let mut hogs = HOGS.lock();
+        let count = hogs.entry(self.0).or_insert(1);
+        *count -= 1;
+        if *count == 0 {
+            hogs.remove(&self.0);
+            let _ = set_hog_owner(self.0, -1);
+        }
+    }
+}
+
+fn hog_owner(device: ffi::AudioDeviceID) -> Result<i32> {
+    get_property(device, &hog_mode_address())
+}
+
+fn set_hog_owner(device: ffi::AudioDeviceID, owner: i32) -> Result<()> {
+    set_property(device, &hog_mode_address(), &owner)
+}
+
+fn hog_mode_address() -> ffi::AudioObjectPropertyAddress {
+    property_address(
+        ffi::kAudioDevicePropertyHogMode,
+        ffi::kAudioObjectPropertyScopeGlobal,
+    )
+}
+
+extern "C" fn output_callback<Frame>(
+    ref_con: *mut c_void,
+    _action_flags: *mut ffi::AudioUnitRenderActionFlags,
+    _time_stamp: *const ffi::AudioTimeStamp,
+    _bus: u32,
+    frame_count: u32,
+    data: *mut ffi::AudioBufferList,
+) -> ffi::OSStatus {
+    let state = unsafe { (ref_con as *mut OutputState<Frame>).as_mut() }
+        .expect("Could not get OutputState from ref_con.");
+    // The client format is interleaved, so there is exactly one buffer.
+    let buffer = unsafe { &mut (*data).mBuffers[0] };
+    let output =
+        unsafe { std::slice::from_raw_parts_mut(buffer.mData as *mut Frame, frame_count as usize) };
+    (state.callback)(output);
+    ffi::noErr
+}
+
+extern "C" fn input_callback<Frame>(
+    ref_con: *mut c_void,
+    action_flags: *mut ffi::AudioUnitRenderActionFlags,
+    time_stamp: *const ffi::AudioTimeStamp,
+    bus: u32,
+    frame_count: u32,
+    _data: *mut ffi::AudioBufferList,
+) -> ffi::OSStatus {
+    let state = unsafe { (ref_con as *mut InputState<Frame>).as_mut() }
+        .expect("Could not get InputState from ref_con.");
+    let frame_count = (frame_count as usize).min(state.max_frames);
+    let mut list = ffi::AudioBufferList {
+        mNumberBuffers: 1,
+        mBuffers: [ffi::AudioBuffer {
+            mNumberChannels: 0,
+            mDataByteSize: (frame_count * std::mem::size_of::<Frame>()) as u32,
+            mData: state.buffer.as_mut_ptr() as *mut c_void,
+        }],
+    };
+    let status = unsafe {
+        ffi::AudioUnitRender(
+            state.unit.0,
+            action_flags,
+            time_stamp,
+            bus,
+            frame_count as u32,
+            &mut list,
+        )
+    };
+    if status != ffi::noErr {
+        return status;
+    }
+    let input =
+        unsafe { std::slice::from_raw_parts(state.buffer.as_ptr() as *const Frame, frame_count) };
+    (state.callback)(input);
+    ffi::noErr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coreaudio::Host;
+
+    #[test]
+    fn describes_formats() -> Result<()> {
+        let description = stream_description(Format::I24, 2, 48_000.0)?;
+        assert_eq!(description.mBytesPerFrame, 6);
+        assert_eq!(description.mBitsPerChannel, 24);
+        assert_eq!(
+            description.mFormatFlags,
+            ffi::kAudioFormatFlagIsSignedInteger | ffi::kAudioFormatFlagIsPacked
+        );
+        let description = stream_description(Format::I24In32, 2, 48_000.0)?;
+        assert_eq!(description.mBytesPerFrame, 8);
+        assert_eq!(description.mBitsPerChannel, 24);
+        assert_eq!(
+            description.mFormatFlags,
+            ffi::kAudioFormatFlagIsSignedInteger
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn checks_unconverted_formats() -> Result<()> {
+        let description = stream_description(Format::F32, 2, 48_000.0)?;
+        let mut device_description = description;
+        device_description.mFormatFlags |= ffi::kAudioFormatFlagIsNonInterleaved;
+        assert_eq!(
+            check_unconverted(&description, &device_description, Format::F32),
+            Ok(())
+        );
+        device_description.mChannelsPerFrame = 4;
+        assert_eq!(
+            check_unconverted(&description, &device_description, Format::F32),
+            Err(Error::IncompatibleNChannels)
+        );
+        let device_description = stream_description(Format::I24, 2, 48_000.0)?;
+        assert_eq!(
+            check_unconverted(&description, &device_description, Format::F32),
+            Err(Error::IncompatibleFormat(Format::F32))
+        );
+        let device_description = stream_description(Format::F32, 2, 44_100.0)?;
+        assert_eq!(
+            check_unconverted(&description, &device_description, Format::F32),
+            Err(Error::IncompatibleSampleRate)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_frame_size_mismatches() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let result = device.open_outstream(StreamOptions::<[f32; 2]> {
+            format: Format::I16,
+            ..Default::default()
+        });
+        assert_eq!(
+            result.err(),
+            Some(Error::InvalidFrameSize {
+                expected: 4,
+                actual: 8
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
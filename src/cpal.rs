@@ -0,0 +1,190 @@
+//! Conversions between audiohal's formats, configs, and devices and [cpal](https://docs.rs/cpal)'s,
+//! for projects that move to audiohal a stream at a time, or that use it for the backends cpal
+//! doesn't have. Devices are matched by name, as neither library has another identifier the other
+//! knows.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use audiohal::{Host, StreamConfig, StreamOptions};
+//! use cpal::traits::{DeviceTrait as _, HostTrait as _};
+//! use std::convert::TryFrom;
+//! # fn main() -> audiohal::Result<()> {
+//! let cpal_device = cpal::default_host().default_output_device().unwrap();
+//! let mut device = audiohal::cpal::device(&mut Host::with_default_backend()?, &cpal_device)?;
+//! // Plays the way cpal would have.
+//! let config = StreamConfig::try_from(cpal_device.default_output_config().unwrap())?;
+//! let mut stream = device.open_outstream(StreamOptions::<[f32; 2]> {
+//!     callback: Box::new(|buffer| buffer.iter_mut().for_each(|frame| *frame = [0.0; 2])),
+//!     ..config.into()
+//! })?;
+//! stream.start()?;
+//! # Ok(())
+//! # }
+//! ```
+use ::cpal::traits::{DeviceTrait as _, HostTrait as _};
+use std::convert::TryFrom;
+
+use crate::error::{Error, Result};
+use crate::stream_options::{BufferSize, Format, SampleRate, StreamConfig, StreamOptions};
+use crate::{Device, Host};
+
+/// cpal has no 24-bit formats.
+impl TryFrom<Format> for ::cpal::SampleFormat {
+    type Error = Error;
+
+    fn try_from(format: Format) -> Result<::cpal::SampleFormat> {
+        use ::cpal::SampleFormat as F;
+        Ok(match format {
+            Format::F64 => F::F64,
+            Format::F32 => F::F32,
+            Format::I32 => F::I32,
+            Format::I16 => F::I16,
+            Format::I8 => F::I8,
+            Format::U32 => F::U32,
+            Format::U16 => F::U16,
+            Format::U8 => F::U8,
+            format => return Err(Error::IncompatibleFormat(format)),
+        })
+    }
+}
+
+/// Audiohal has no 64-bit integer formats, which return [`Error::Invalid`].
+impl TryFrom<::cpal::SampleFormat> for Format {
+    type Error = Error;
+
+    fn try_from(format: ::cpal::SampleFormat) -> Result<Format> {
+        use ::cpal::SampleFormat as F;
+        Ok(match format {
+            F::F64 => Format::F64,
+            F::F32 => Format::F32,
+            F::I32 => Format::I32,
+            F::I16 => Format::I16,
+            F::I8 => Format::I8,
+            F::U32 => Format::U32,
+            F::U16 => Format::U16,
+            F::U8 => Format::U8,
+            _ => return Err(Error::Invalid),
+        })
+    }
+}
+
+/// cpal streams run at an exact rate: Configs at the device's default rate return
+/// [`Error::IncompatibleSampleRate`].
+impl TryFrom<StreamConfig> for ::cpal::StreamConfig {
+    type Error = Error;
+
+    fn try_from(config: StreamConfig) -> Result<::cpal::StreamConfig> {
+        let rate = match config.sample_rate {
+            SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+            _ => return Err(Error::IncompatibleSampleRate),
+        };
+        Ok(::cpal::StreamConfig {
+            channels: u16::try_from(config.n_channels).map_err(|_| Error::IncompatibleNChannels)?,
+            sample_rate: ::cpal::SampleRate(
+                u32::try_from(rate).map_err(|_| Error::IncompatibleSampleRate)?,
+            ),
+            buffer_size: match config.frames_per_buffer.frames() {
+                Some(frames) => ::cpal::BufferSize::Fixed(
+                    u32::try_from(frames).map_err(|_| Error::InvalidFramesPerBuffer)?,
+                ),
+                None => ::cpal::BufferSize::Default,
+            },
+        })
+    }
+}
+
+/// The config of cpal's supported config, with audiohal's defaults for the settings cpal doesn't
+/// have.
+impl TryFrom<::cpal::SupportedStreamConfig> for StreamConfig {
+    type Error = Error;
+
+    fn try_from(config: ::cpal::SupportedStreamConfig) -> Result<StreamConfig> {
+        stream_config(&config.config(), config.sample_format())
+    }
+}
+
+/// The config of cpal's stream config, in `format`, with audiohal's defaults for the settings
+/// cpal doesn't have.
+pub fn stream_config(
+    config: &::cpal::StreamConfig,
+    format: ::cpal::SampleFormat,
+) -> Result<StreamConfig> {
+    Ok(StreamConfig {
+        format: Format::try_from(format)?,
+        n_channels: i32::from(config.channels),
+        frames_per_buffer: match config.buffer_size {
+            ::cpal::BufferSize::Fixed(frames) => {
+                BufferSize::Exact(i32::try_from(frames).map_err(|_| Error::InvalidFramesPerBuffer)?)
+            }
+            ::cpal::BufferSize::Default => BufferSize::Default,
+        },
+        sample_rate: SampleRate::Exact(
+            i32::try_from(config.sample_rate.0).map_err(|_| Error::IncompatibleSampleRate)?,
+        ),
+        ..StreamOptions::<[f32; 1]>::default().config()
+    })
+}
+
+/// The device of `host` with the name of cpal's `device`. Returns [`Error::NoSuchDevice`] if it
+/// has none.
+pub fn device(host: &mut Host, device: &::cpal::Device) -> Result<Device> {
+    let name = device.name().map_err(|_| Error::NoSuchDevice)?;
+    host.device_by_name(&name)
+}
+
+/// The device of cpal's `host` with the name of `device`. Returns [`Error::NoSuchDevice`] if it
+/// has none.
+pub fn cpal_device(host: &::cpal::Host, device: &Device) -> Result<::cpal::Device> {
+    host.devices()
+        .map_err(|error| Error::from_source(None, "Could not list cpal's devices.", error))?
+        .find(|cpal_device| {
+            cpal_device
+                .name()
+                .map_or(false, |name| name == device.name())
+        })
+        .ok_or(Error::NoSuchDevice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_formats() {
+        assert_eq!(
+            ::cpal::SampleFormat::try_from(Format::I16),
+            Ok(::cpal::SampleFormat::I16)
+        );
+        assert_eq!(
+            ::cpal::SampleFormat::try_from(Format::I24),
+            Err(Error::IncompatibleFormat(Format::I24))
+        );
+        assert_eq!(Format::try_from(::cpal::SampleFormat::F32), Ok(Format::F32));
+        assert_eq!(
+            Format::try_from(::cpal::SampleFormat::I64),
+            Err(Error::Invalid)
+        );
+    }
+
+    #[test]
+    fn converts_configs() -> Result<()> {
+        let config = StreamConfig {
+            frames_per_buffer: BufferSize::Exact(256),
+            sample_rate: SampleRate::Exact(48000),
+            ..StreamOptions::<[f32; 2]>::default().config()
+        };
+        let cpal_config = ::cpal::StreamConfig::try_from(config)?;
+        assert_eq!(cpal_config.channels, 2);
+        assert_eq!(cpal_config.sample_rate, ::cpal::SampleRate(48000));
+        assert_eq!(
+            stream_config(&cpal_config, ::cpal::SampleFormat::F32)?,
+            config
+        );
+        assert_eq!(
+            ::cpal::StreamConfig::try_from(StreamOptions::<[f32; 2]>::default().config()).err(),
+            Some(Error::IncompatibleSampleRate)
+        );
+        Ok(())
+    }
+}
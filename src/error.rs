@@ -1,10 +1,16 @@
+use std::cell::Cell;
 use std::fmt;
 use std::result;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::Format;
+use crate::{Backend, Format};
 
+/// The errors of every backend. Callers that handle errors by kind rather than one by one can
+/// match on their [`category`](Error::category) instead.
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// An out-of-memory occurred while allocating in a C library.
     OutOfMemory,
@@ -22,6 +28,10 @@ pub enum Error {
     BackendUnavailable,
     /// The requested device was unavailable.
     NoSuchDevice,
+    /// The stream's device disappeared while the stream was open: e.g. it was unplugged, or its
+    /// Bluetooth connection dropped. The stream can't be restarted, and has to be reopened (e.g.
+    /// on the new default device).
+    DeviceLost,
     /// The requested format is not compatible with the device in-use.
     IncompatibleFormat(Format),
     /// The requested sample rate is not compatible with the device.
@@ -30,15 +40,393 @@ pub enum Error {
     IncompatibleNChannels,
     /// ['Stream::start`] called on stream that has already started.
     StreamAlreadyStarted,
+    /// The operation is not supported by this kind of stream. E.g. [`Stream::write`] called on an
+    /// input stream, or on a stream that was created with a callback.
+    IncompatibleStreamMode,
+    /// An error of a backend's native API that none of the others describe, with its native code.
+    Backend(BackendError),
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// What kind of failure an [`Error`] is.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The device or backend is missing, disconnected, busy, or not allowed: e.g.
+    /// [`Error::NoSuchDevice`].
+    DeviceUnavailable,
+    /// The device doesn't take the stream's format, sample rate, channel count, or buffer size.
+    FormatUnsupported,
+    /// The call doesn't apply to the stream, or was given invalid arguments.
+    InvalidUsage,
+    /// The backend failed otherwise, e.g. ran out of memory.
+    Backend,
+}
+
+impl Error {
+    pub fn category(&self) -> ErrorCategory {
+        use Error::*;
+        match self {
+            NoSuchDevice | DeviceLost | BackendUnavailable => ErrorCategory::DeviceUnavailable,
+            IncompatibleFormat(_)
+            | IncompatibleSampleRate
+            | IncompatibleNChannels
+            | InvalidFrameSize { .. }
+            | InvalidFramesPerBuffer => ErrorCategory::FormatUnsupported,
+            Invalid | StreamAlreadyStarted | IncompatibleStreamMode => ErrorCategory::InvalidUsage,
+            OutOfMemory | Unknown(_) => ErrorCategory::Backend,
+            Backend(error) => error.category,
+        }
+    }
+
+    /// The backend that returned the error, if it came from one's native API.
+    pub fn backend(&self) -> Option<Backend> {
+        match self {
+            Error::Backend(error) => error.backend,
+            _ => None,
+        }
+    }
+
+    /// The native error code, if the error came from a backend's native API.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::Backend(error) => Some(error.code),
+            _ => None,
+        }
+    }
+
+    /// The name of the device the error happened on, if the backend reported it while opening a
+    /// stream.
+    pub fn device(&self) -> Option<&str> {
+        match self {
+            Error::Backend(error) => error.device.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// A backend's error that has no native code, but wraps another error (e.g. an I/O error)
+    /// that describes it: See [`ErrorCode::Other`].
+    // Unused by builds without the backends that wrap errors.
+    #[allow(dead_code)]
+    pub(crate) fn from_source(
+        backend: Option<Backend>,
+        message: &'static str,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Error {
+        Error::Backend(BackendError {
+            source: Some(Arc::new(source)),
+            ..BackendError::new(backend, ErrorCode::Other, message)
+        })
+    }
+
+    /// Records that the error happened on `device`, if it came from a backend's native API.
+    pub(crate) fn on_device(self, device: &str) -> Error {
+        match self {
+            Error::Backend(error) => Error::Backend(BackendError {
+                device: Some(device.to_owned()),
+                ..error
+            }),
+            error => error,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // TODO: Actually make this nice.
-        fmt::Debug::fmt(self, f)
+        match self {
+            Error::Backend(error) => fmt::Display::fmt(error, f),
+            // TODO: Actually make this nice.
+            error => fmt::Debug::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    /// The source of [`Error::Backend`]'s error, which the error displays itself.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Backend(error) => std::error::Error::source(error),
+            _ => None,
+        }
     }
 }
 
-impl std::error::Error for Error {}
+/// A native error code, as the API that returned it defines it.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// A Portaudio `PaError`.
+    PaError(i32),
+    /// A CoreAudio `OSStatus`.
+    OsStatus(i32),
+    /// A COM `HRESULT`, from WASAPI.
+    HResult(i32),
+    /// An `errno` value, as ALSA returns them (negated) and JACK and OSS set them.
+    Errno(i32),
+    /// A PulseAudio `pa_error_code`.
+    PulseAudio(i32),
+    /// An AAudio `aaudio_result_t`.
+    AAudio(i32),
+    /// An OpenSL ES `SLresult`.
+    OpenSles(u32),
+    /// No native code, for errors that wrap another error (e.g. an I/O error) as their
+    /// [source](std::error::Error::source), which describes them.
+    Other,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::PaError(code) => write!(f, "PaError {}", code),
+            ErrorCode::OsStatus(status) => write!(f, "OSStatus {}", status),
+            ErrorCode::HResult(hr) => write!(f, "HRESULT {:#010x}", hr),
+            ErrorCode::Errno(errno) => write!(f, "errno {}", errno),
+            ErrorCode::PulseAudio(code) => write!(f, "PulseAudio error {}", code),
+            ErrorCode::AAudio(result) => write!(f, "AAudio result {}", result),
+            ErrorCode::OpenSles(result) => write!(f, "SLresult {}", result),
+            ErrorCode::Other => write!(f, "no native code"),
+        }
+    }
+}
+
+/// An error of a backend's native API: See [`Error::Backend`].
+#[derive(Debug, Clone)]
+pub struct BackendError {
+    /// Unknown for Portaudio errors that aren't its host API's.
+    backend: Option<Backend>,
+    code: ErrorCode,
+    category: ErrorCategory,
+    message: &'static str,
+    device: Option<String>,
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl BackendError {
+    // Unused by builds without native backends.
+    #[allow(dead_code)]
+    pub(crate) fn new(backend: Option<Backend>, code: ErrorCode, message: &'static str) -> Self {
+        BackendError {
+            backend,
+            code,
+            category: ErrorCategory::Backend,
+            message,
+            device: None,
+            // The system describes its own codes.
+            source: match code {
+                ErrorCode::Errno(errno) => Some(Arc::new(std::io::Error::from_raw_os_error(errno))),
+                _ => None,
+            },
+        }
+    }
+
+    /// For native errors that fall in another category: e.g. devices in use.
+    #[allow(dead_code)]
+    pub(crate) fn with_category(self, category: ErrorCategory) -> Self {
+        BackendError { category, ..self }
+    }
+
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        self.category
+    }
+
+    pub fn message(&self) -> &str {
+        self.message
+    }
+
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+}
+
+/// Ignores the source, which is derived from the code.
+impl PartialEq for BackendError {
+    fn eq(&self, other: &Self) -> bool {
+        self.backend == other.backend
+            && self.code == other.code
+            && self.category == other.category
+            && self.message == other.message
+            && self.device == other.device
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        let code = Some(self.code).filter(|&code| code != ErrorCode::Other);
+        match (code, &self.device) {
+            (Some(code), Some(device)) => write!(f, " ({}, on {})", code, device),
+            (Some(code), None) => write!(f, " ({})", code),
+            (None, Some(device)) => write!(f, " (on {})", device),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// An error in a stream's callback. Streams send them to the receiver from
+/// [`Stream::take_error_receiver`](crate::Stream::take_error_receiver).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallbackError {
+    /// Reported by the callback with [`report_callback_error`]. The stream goes on.
+    Reported(String),
+    /// The callback panicked, with the given message. The stream is aborted.
+    Panicked(String),
+    /// The callback's buffer had the given number of output samples out of range, which were
+    /// clamped. Only streams with [`ClipPolicy::ErrorOnClip`](crate::ClipPolicy::ErrorOnClip)
+    /// send it. The stream goes on.
+    Clipped(usize),
+    /// The stream's callback wasn't called for the given time, while the stream was running: e.g.
+    /// because its device stalled. Only streams with a
+    /// [watchdog](crate::StreamOptions::watchdog) send it, once per stall. The stream may never
+    /// recover, and can be reopened.
+    StreamStalled(Duration),
+    /// The stream's device disappeared, and the stream stopped: See
+    /// [`Error::DeviceLost`](crate::Error::DeviceLost). Sent once, instead of nothing, when the
+    /// stream stops on its own.
+    DeviceLost,
+    /// The stream reopened after its device disappeared, by its
+    /// [reconnect policy](crate::StreamOptions::reconnect). The stream goes on, with the same
+    /// callback.
+    Reconnected,
+    /// The callback made the given number of [real-time safety violations](crate::rt), the first
+    /// of which is given. The stream goes on.
+    #[cfg(feature = "rt-check")]
+    NotRealTime(crate::rt::Violation, usize),
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackError::Reported(message) => write!(f, "{}", message),
+            CallbackError::Panicked(message) => write!(f, "Stream callback panicked: {}", message),
+            CallbackError::Clipped(n_samples) => {
+                write!(f, "Stream callback clipped {} samples.", n_samples)
+            }
+            CallbackError::StreamStalled(stalled) => {
+                write!(f, "Stream callback wasn't called for {:?}.", stalled)
+            }
+            CallbackError::DeviceLost => write!(f, "The stream's device disappeared."),
+            CallbackError::Reconnected => write!(f, "The stream reopened after losing its device."),
+            #[cfg(feature = "rt-check")]
+            CallbackError::NotRealTime(first, count) => write!(
+                f,
+                "Stream callback wasn't real-time safe {} times, first with {:?}.",
+                count, first
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+thread_local! {
+    /// Where errors go while a stream's callback runs on this thread. Null outside of callbacks.
+    static CALLBACK_ERRORS: Cell<*const SyncSender<CallbackError>> =
+        const { Cell::new(std::ptr::null()) };
+}
+
+/// Reports an error from inside a stream's callback, to its stream's error receiver. Returns
+/// whether there was a callback to report from: Only Portaudio streams take errors.
+///
+/// The message is the only thing reporting allocates (unless it's already a `String`). Errors
+/// the receiver hasn't taken yet are bounded: Past the bound, reported errors are dropped.
+pub fn report_callback_error(message: impl Into<String>) -> bool {
+    CALLBACK_ERRORS.with(|errors| match unsafe { errors.get().as_ref() } {
+        Some(errors) => {
+            // Nobody may be listening, and callbacks can't wait for someone to.
+            errors
+                .try_send(CallbackError::Reported(message.into()))
+                .ok();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Runs a callback, sending the errors it reports to `errors`.
+#[cfg_attr(
+    not(all(
+        feature = "portaudio",
+        not(any(target_os = "android", target_arch = "wasm32"))
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn with_callback_errors<R>(
+    errors: &SyncSender<CallbackError>,
+    f: impl FnOnce() -> R,
+) -> R {
+    let previous = CALLBACK_ERRORS.with(|callback_errors| callback_errors.replace(errors));
+    let result = f();
+    CALLBACK_ERRORS.with(|callback_errors| callback_errors.set(previous));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categorizes_errors() {
+        assert_eq!(
+            Error::NoSuchDevice.category(),
+            ErrorCategory::DeviceUnavailable
+        );
+        assert_eq!(
+            Error::IncompatibleSampleRate.category(),
+            ErrorCategory::FormatUnsupported
+        );
+        let error = Error::Backend(
+            BackendError::new(Some(Backend::Wasapi), ErrorCode::HResult(-1), "In use.")
+                .with_category(ErrorCategory::DeviceUnavailable),
+        );
+        assert_eq!(error.category(), ErrorCategory::DeviceUnavailable);
+        assert_eq!(error.device(), None);
+        let error = error.on_device("Speakers");
+        assert_eq!(error.device(), Some("Speakers"));
+        assert_eq!(
+            error.to_string(),
+            "In use. (HRESULT 0xffffffff, on Speakers)"
+        );
+        // Only native errors are attributed to devices.
+        assert_eq!(Error::Invalid.on_device("Speakers").device(), None);
+    }
+
+    #[test]
+    fn chains_sources() {
+        use std::error::Error as _;
+
+        let error = Error::from_source(
+            None,
+            "Could not read the WAV file.",
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Out of data."),
+        );
+        assert_eq!(error.to_string(), "Could not read the WAV file.");
+        assert_eq!(error.code(), Some(ErrorCode::Other));
+        assert_eq!(error.source().unwrap().to_string(), "Out of data.");
+        // The backend's error isn't its own source, which would display it twice.
+        let error = Error::Backend(BackendError::new(
+            Some(Backend::Alsa),
+            ErrorCode::Errno(2),
+            "Could not open the PCM.",
+        ));
+        assert!(error.source().unwrap().is::<std::io::Error>());
+        assert!(Error::Invalid.source().is_none());
+    }
+}
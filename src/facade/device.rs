@@ -0,0 +1,1009 @@
+use crate::buffered::{InputReader, OutputWriter};
+use crate::capabilities::DeviceCapabilities;
+use crate::error::{Error, Result};
+use crate::facade::{dispatch, DeviceImpl, Stream, StreamImpl};
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+use crate::facade::{
+    dyn_stream::DynStreamImpl, DynBuffer, DynCallback, DynInputBuffer, DynInputCallback, DynStream,
+};
+#[cfg(all(windows, feature = "asio"))]
+use crate::portaudio::AsioBufferSizes;
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+use crate::stream_options::{
+    CallbackKind, DuplexCallback, DuplexInfoCallback, DynamicInput, DynamicOutput, Format,
+    InputWithInfo, OutputWithInfo, PlanarInput, PlanarOutput, RawInput, RawOutput, StreamConfig,
+};
+use crate::stream_options::{Input, NoCallback, StreamOptions};
+use crate::surround::ChannelPosition;
+use crate::trace;
+use std::fmt;
+
+/// An output or input device of the [`Host`](crate::Host) it came from.
+pub struct Device(pub(super) DeviceImpl);
+
+/// Identifies a device across sessions, reboots, and changes in the order devices are listed in,
+/// for saving the user's picks. Based on the platform's identifier for the device (see
+/// [`Device::uid`]), and converts to and from strings (and, with the `serde`
+/// feature, is serialized as one).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct DeviceId(String);
+
+impl DeviceId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for DeviceId {
+    fn from(uid: String) -> DeviceId {
+        DeviceId(uid)
+    }
+}
+
+impl From<&str> for DeviceId {
+    fn from(uid: &str) -> DeviceId {
+        DeviceId(uid.to_string())
+    }
+}
+
+impl From<DeviceId> for String {
+    fn from(id: DeviceId) -> String {
+        id.0
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Device {
+    /// The device's system name (e.g. "Built-in Output").
+    pub fn name(&self) -> &str {
+        dispatch!(&self.0, DeviceImpl, device => device.name())
+    }
+
+    /// An identifier for the device that stays the same across sessions, for reopening it with
+    /// [`Host::device_by_uid`](crate::Host::device_by_uid): e.g. the device the user picked last
+    /// time. WASAPI devices return their endpoint ID, and other backends' their name.
+    pub fn uid(&self) -> String {
+        match &self.0 {
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device.id().to_string(),
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// The device's [`DeviceId`], for reopening it with
+    /// [`Host::device_by_id`](crate::Host::device_by_id).
+    pub fn id(&self) -> DeviceId {
+        DeviceId(self.uid())
+    }
+
+    /// The backend's index for the device, for backends that number their devices (Portaudio).
+    /// Otherwise, devices are numbered by their position in [`Host::devices`](crate::Host::devices).
+    pub fn index(&self) -> Option<i32> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            DeviceImpl::Portaudio(device) => Some(device.index()),
+            _ => None,
+        }
+    }
+
+    /// The sample rates and formats the device supports, and how many channels it has, so that
+    /// only valid configurations are offered (e.g. in a settings UI). `None` for backends that
+    /// can't tell without opening a stream: Only Portaudio devices report them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let device = Host::with_default_backend()?.default_output_device()?;
+    /// if let Some(capabilities) = device.capabilities() {
+    ///     println!("Runs at {:?}", capabilities.sample_rates);
+    ///     println!("Takes {:?}", capabilities.formats);
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn capabilities(&self) -> Option<DeviceCapabilities> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            DeviceImpl::Portaudio(device) => Some(device.capabilities()),
+            _ => None,
+        }
+    }
+
+    /// The positions of the device's output channels (or input channels, if not `is_output`), in
+    /// their order, for laying out streams with a
+    /// [`channel_mask`](crate::StreamOptions::channel_mask). `None` if the backend doesn't know.
+    ///
+    /// Portaudio devices report the usual layout for their channel count on their host API, and
+    /// WASAPI devices their mixer's. Other backends return `None`.
+    pub fn channel_positions(&self, is_output: bool) -> Option<Vec<ChannelPosition>> {
+        match (&self.0, is_output) {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            (DeviceImpl::Portaudio(device), is_output) => device.channel_positions(is_output),
+            // WASAPI devices are either render or capture endpoints.
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            (DeviceImpl::Wasapi(device), is_output) if device.is_output() == is_output => {
+                device.channel_positions().ok().flatten()
+            }
+            _ => None,
+        }
+    }
+
+    /// The device's master volume, from 0 to 1, as the system's mixer shows it. Only WASAPI and
+    /// CoreAudio devices have one: Other backends return [`Error::IncompatibleStreamMode`].
+    pub fn volume(&self) -> Result<f32> {
+        match &self.0 {
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            DeviceImpl::CoreAudio(device) => device.volume(),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device.volume(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Sets the device's master [`volume`](Device::volume), for every application. Returns
+    /// [`Error::Invalid`] for volumes outside of 0 to 1.
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        match &mut self.0 {
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            DeviceImpl::CoreAudio(device) => device.set_volume(volume),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device.set_volume(volume),
+            _ => {
+                let _ = volume;
+                Err(Error::IncompatibleStreamMode)
+            }
+        }
+    }
+
+    /// Whether the device is muted in the system's mixer. See [`volume`](Device::volume).
+    pub fn is_muted(&self) -> Result<bool> {
+        match &self.0 {
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            DeviceImpl::CoreAudio(device) => device.is_muted(),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device.is_muted(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Mutes or unmutes the device, for every application. See [`volume`](Device::volume).
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        match &mut self.0 {
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            DeviceImpl::CoreAudio(device) => device.set_muted(muted),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device.set_muted(muted),
+            _ => {
+                let _ = muted;
+                Err(Error::IncompatibleStreamMode)
+            }
+        }
+    }
+
+    /// Creates an output stream. Errors of the backend's native API carry the device's
+    /// [name](Error::device).
+    ///
+    /// `Frame` is the stream's frame type, and is inferred from the stream callback.
+    ///
+    /// Output streams stream digital audio (in the form of frames) to a system's output device.
+    /// The callback in  [`StreamOptions`] is called multiple times per second (depending on how you
+    /// setup frames_per_buffer) in order to satisfy the requested sample-rate. See
+    /// [`Stream`] for more details.
+    ///
+    /// If the device doesn't take the options' format, Portaudio streams run it in one it does
+    /// take (e.g. [`Format::I16`](crate::Format::I16)), and scale the callback's samples to and
+    /// from it. Other backends return
+    /// [`Error::IncompatibleFormat`](crate::Error::IncompatibleFormat).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// fn callback(buffer: &mut [[f32; 2]]) {
+    ///     # buffer;
+    /// }
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_outstream(
+    ///     StreamOptions {
+    ///         callback: Box::new(callback),
+    ///         // The rest of the parameters will be set to device defaults.
+    ///         ..Default::default()
+    ///     });
+    /// assert!(stream.is_ok());
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(
+            dispatch!(&mut self.0, DeviceImpl, device => device.open_outstream(options), map StreamImpl)
+                .map(Stream)
+                .map_err(|error| error.on_device(self.name())),
+        )
+    }
+
+    /// Creates an input stream.
+    ///
+    /// `Frame` is the stream's frame type, and is inferred from the stream callback.
+    ///
+    /// Input streams capture digital audio (in the form of frames) from a system's input device.
+    /// The callback in [`StreamOptions`] receives the captured frames, and is called multiple times
+    /// per second (depending on how you setup frames_per_buffer). See [`Stream`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// fn callback(captured: &[[f32; 1]]) {
+    ///     # captured;
+    /// }
+    /// let mut device = Host::with_default_backend()?.default_input_device()?;
+    /// let stream = device.open_input_stream(
+    ///     StreamOptions {
+    ///         callback: Box::new(callback),
+    ///         ..Default::default()
+    ///     });
+    /// assert!(stream.is_ok());
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(
+            dispatch!(&mut self.0, DeviceImpl, device => device.open_input_stream(options), map StreamImpl)
+                .map(Stream)
+                .map_err(|error| error.on_device(self.name())),
+        )
+    }
+
+    /// Opens and starts an output stream that plays the frames written to the returned
+    /// [`OutputWriter`] from any thread, instead of calling a callback. Buffers up to `capacity`
+    /// frames.
+    pub fn open_output_writer<Frame>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<OutputWriter<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        OutputWriter::open(self, options, capacity, &[])
+    }
+
+    /// Like [`open_output_writer`](Device::open_output_writer), with `frames` buffered before the
+    /// stream starts, so that it starts with them rather than with silence. Returns
+    /// [`Error::Invalid`] if there are more than `capacity` of them.
+    pub fn open_primed_output_writer<Frame>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+        frames: &[Frame],
+    ) -> Result<OutputWriter<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        OutputWriter::open(self, options, capacity, frames)
+    }
+
+    /// Opens and starts an input stream whose captured frames are read from the returned
+    /// [`InputReader`] from any thread, instead of passed to a callback. Buffers up to `capacity`
+    /// frames.
+    pub fn open_input_reader<Frame>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+        capacity: usize,
+    ) -> Result<InputReader<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        InputReader::open(self, options, capacity)
+    }
+
+    /// Creates an input stream that captures what this output device plays (i.e. "what you
+    /// hear"), e.g. for recording or sharing the system's audio. Takes the same options as
+    /// [`open_input_stream`](Device::open_input_stream).
+    ///
+    /// WASAPI streams capture the endpoint in loopback mode (so shared-mode devices only), and
+    /// PulseAudio and PipeWire streams capture the sink's monitor. Input devices return
+    /// [`Error::IncompatibleNChannels`], and other backends [`Error::IncompatibleStreamMode`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let mut device = Host::with_backend(Backend::PulseAudio)?.default_output_device()?;
+    /// let stream = device.open_loopback_stream(StreamOptions {
+    ///     callback: Box::new(|played: &[[f32; 2]]| println!("Played {} frames.", played.len())),
+    ///     ..Default::default()
+    /// })?;
+    /// # stream;
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_loopback_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "loopback", &options);
+        trace::opened(match &mut self.0 {
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            DeviceImpl::PipeWire(device) => device
+                .open_loopback_stream(options)
+                .map(|stream| Stream(StreamImpl::PipeWire(stream))),
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            DeviceImpl::PulseAudio(device) => device
+                .open_loopback_stream(options)
+                .map(|stream| Stream(StreamImpl::PulseAudio(stream))),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            DeviceImpl::Wasapi(device) => device
+                .open_loopback_stream(options)
+                .map(|stream| Stream(StreamImpl::Wasapi(stream))),
+            _ => {
+                // Only the backends above have loopback streams.
+                let _ = options;
+                Err(Error::IncompatibleStreamMode)
+            }
+        })
+    }
+}
+
+// Blocking, duplex, planar, dynamic, and info streams, and format checks, are only implemented by
+// Portaudio. Other backends return Error::IncompatibleStreamMode.
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+impl Device {
+    /// Whether the device takes streams with the options' format, channel count, and sample rate,
+    /// checked without the cost of opening one. Output options are checked for output streams,
+    /// and input options for input streams. Others (e.g. a blocking stream's) are checked for
+    /// either.
+    ///
+    /// Streams the device doesn't take may still open, when they ask to be resampled (see
+    /// [`StreamOptions::resample_if_needed`]) or mixed (see
+    /// [`StreamOptions::channel_mix_policy`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let device = Host::with_default_backend()?.default_output_device()?;
+    /// let options = StreamOptions::<[f32; 2]> {
+    ///     sample_rate: SampleRate::Exact(96000),
+    ///     ..Default::default()
+    /// };
+    /// if !device.supports(&options)? {
+    ///     println!("The device doesn't run at 96kHz.");
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn supports<F, K: CallbackKind>(&self, options: &StreamOptions<F, K>) -> Result<bool> {
+        match &self.0 {
+            DeviceImpl::Portaudio(device) => Ok(device.supports(options)),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Whether the device takes output or input streams in the format, checked without opening
+    /// one.
+    pub fn supports_config(
+        &self,
+        format: Format,
+        n_channels: i32,
+        sample_rate: i32,
+    ) -> Result<bool> {
+        match &self.0 {
+            DeviceImpl::Portaudio(device) => {
+                Ok(device.supports_config(format, n_channels, sample_rate))
+            }
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Creates a blocking output stream.
+    ///
+    /// Blocking streams have no callback. Instead, frames are played by calling [`Stream::write`],
+    /// which blocks until the device has consumed enough data to accept them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let mut stream = device.open_blocking_outstream(StreamOptions::<[f32; 2], _>::default())?;
+    /// stream.start()?;
+    /// // Play a tenth of a second of silence.
+    /// stream.write(&[[0.0; 2]; 4_800])?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_blocking_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_blocking_outstream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a blocking input stream.
+    ///
+    /// Blocking streams have no callback. Instead, captured frames are fetched by calling
+    /// [`Stream::read`], which blocks until enough frames are available.
+    pub fn open_blocking_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_blocking_input_stream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a planar (non-interleaved) output stream.
+    ///
+    /// The callback is passed a buffer per channel instead of a buffer of frames, which saves
+    /// (de)interleaving for code that processes channels independently. `Sample` is the stream's
+    /// sample type. Only formats the device supports natively are available: e.g.
+    /// [`Format::F64`](crate::Format::F64) returns
+    /// [`Error::IncompatibleFormat`](crate::Error::IncompatibleFormat).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_planar_outstream(StreamOptions::<f32, PlanarOutput> {
+    ///     format: Format::F32,
+    ///     n_channels: 2,
+    ///     frames_per_buffer: BufferSize::Default,
+    ///     sample_rate: SampleRate::DeviceDefault,
+    ///     resample_if_needed: false,
+    ///     resampler_quality: ResamplerQuality::Linear,
+    ///     channel_mix_policy: ChannelMixPolicy::Exact,
+    ///     channel_map: None,
+    ///     channel_mask: None,
+    ///     channels: ChannelSelection::All,
+    ///     exclusive: false,
+    ///     latency: LatencyHint::High,
+    ///     realtime_priority: true,
+    ///     prime_output: false,
+    ///     watchdog: None,
+    ///     reconnect: ReconnectPolicy::None,
+    ///     wasapi: None,
+    ///     coreaudio: None,
+    ///     alsa: None,
+    ///     gain: 1.0,
+    ///     channel_gains: None,
+    ///     dither: DitherMode::Tpdf,
+    ///     clip_policy: ClipPolicy::Clamp,
+    ///     on_finished: None,
+    ///     callback: Box::new(|channels: &mut [&mut [f32]]| {
+    ///         for channel in channels {
+    ///             channel.iter_mut().for_each(|sample| *sample = 0.0);
+    ///         }
+    ///     }),
+    /// });
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_planar_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, PlanarOutput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_planar_outstream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a planar (non-interleaved) input stream. The callback is passed a buffer of
+    /// captured samples per channel. See
+    /// [`open_planar_outstream`](Device::open_planar_outstream).
+    pub fn open_planar_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, PlanarInput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_planar_input_stream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a dynamic output stream, whose channel count is only known at runtime.
+    ///
+    /// The callback is passed a buffer of interleaved samples, along with the options'
+    /// `n_channels`: e.g. for channel counts that come from a configuration file. `Sample` is the
+    /// stream's sample type. Like planar streams, only formats the device supports natively are
+    /// available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// # let n_channels_from_config = 2;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_dynamic_outstream(StreamOptions::<f32, DynamicOutput> {
+    ///     format: Format::F32,
+    ///     n_channels: n_channels_from_config,
+    ///     frames_per_buffer: BufferSize::Default,
+    ///     sample_rate: SampleRate::DeviceDefault,
+    ///     resample_if_needed: false,
+    ///     resampler_quality: ResamplerQuality::Linear,
+    ///     channel_mix_policy: ChannelMixPolicy::Exact,
+    ///     channel_map: None,
+    ///     channel_mask: None,
+    ///     channels: ChannelSelection::All,
+    ///     exclusive: false,
+    ///     latency: LatencyHint::High,
+    ///     realtime_priority: true,
+    ///     prime_output: false,
+    ///     watchdog: None,
+    ///     reconnect: ReconnectPolicy::None,
+    ///     wasapi: None,
+    ///     coreaudio: None,
+    ///     alsa: None,
+    ///     gain: 1.0,
+    ///     channel_gains: None,
+    ///     dither: DitherMode::Tpdf,
+    ///     clip_policy: ClipPolicy::Clamp,
+    ///     on_finished: None,
+    ///     callback: Box::new(|buffer: &mut [f32], n_channels: usize| {
+    ///         for frame in buffer.chunks_exact_mut(n_channels) {
+    ///             frame.iter_mut().for_each(|sample| *sample = 0.0);
+    ///         }
+    ///     }),
+    /// });
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_dynamic_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, DynamicOutput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_dynamic_outstream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a dynamic input stream. The callback is passed the captured interleaved samples,
+    /// and the channel count. See [`open_dynamic_outstream`](Device::open_dynamic_outstream).
+    pub fn open_dynamic_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, DynamicInput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_dynamic_input_stream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a raw output stream, whose callback fills the backend's own buffer in place: No
+    /// copy, conversion, or gain on the way to the device, for callbacks that already produce its
+    /// format and can't spare the time. The callback is passed a [`RawBuffer`](crate::RawBuffer), which is only
+    /// typed by its [`Format`]. Like dynamic streams, only formats the device supports natively
+    /// are available, and streams with a gain return [`Error::IncompatibleStreamMode`]. Only
+    /// Portaudio streams are raw.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_raw_outstream(StreamOptions::<f32, RawOutput> {
+    ///     format: Format::F32,
+    ///     n_channels: 2,
+    ///     frames_per_buffer: BufferSize::Default,
+    ///     sample_rate: SampleRate::DeviceDefault,
+    ///     resample_if_needed: false,
+    ///     resampler_quality: ResamplerQuality::Linear,
+    ///     channel_mix_policy: ChannelMixPolicy::Exact,
+    ///     channel_map: None,
+    ///     channel_mask: None,
+    ///     channels: ChannelSelection::All,
+    ///     exclusive: false,
+    ///     latency: LatencyHint::Low,
+    ///     realtime_priority: true,
+    ///     prime_output: false,
+    ///     watchdog: None,
+    ///     reconnect: ReconnectPolicy::None,
+    ///     wasapi: None,
+    ///     coreaudio: None,
+    ///     alsa: None,
+    ///     gain: 1.0,
+    ///     channel_gains: None,
+    ///     dither: DitherMode::Tpdf,
+    ///     clip_policy: ClipPolicy::Clamp,
+    ///     on_finished: None,
+    ///     callback: Box::new(|mut buffer: RawBuffer| {
+    ///         // The buffer is F32, as the options ask for.
+    ///         unsafe { buffer.samples_mut::<f32>() }.fill(0.0);
+    ///     }),
+    /// });
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_raw_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, RawOutput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_raw_outstream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a raw input stream. The callback is passed a [`RawInputBuffer`](crate::RawInputBuffer), the backend's own
+    /// buffer of captured samples. See [`open_raw_outstream`](Device::open_raw_outstream).
+    pub fn open_raw_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, RawInput>,
+    ) -> Result<Stream<Sample>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_raw_input_stream(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates an output stream whose format is picked at runtime, from the config's: e.g. by a
+    /// plugin host that opens whatever format its plugin asks for. The callback is passed a
+    /// [`DynBuffer`] of the config's format, along with its channel count. Like dynamic streams,
+    /// only formats the device supports natively are available: Others return
+    /// [`Error::IncompatibleFormat`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// # let format_from_plugin = Format::I16;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let config = StreamConfig {
+    ///     format: format_from_plugin,
+    ///     n_channels: 2,
+    ///     ..StreamOptions::<[f32; 2]>::default().config()
+    /// };
+    /// let stream = device.open_dyn_outstream(
+    ///     config,
+    ///     Box::new(|mut buffer: DynBuffer, _n_channels: usize| buffer.fill_silence()),
+    /// );
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_dyn_outstream(
+        &mut self,
+        config: StreamConfig,
+        callback: DynCallback,
+    ) -> Result<DynStream> {
+        let stream = match config.format {
+            Format::F32 => DynStreamImpl::F32(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::F32(buffer),
+            ))?),
+            Format::I32 => DynStreamImpl::I32(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::I32(buffer),
+            ))?),
+            Format::I24 => DynStreamImpl::I24(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::I24(buffer),
+            ))?),
+            Format::I16 => DynStreamImpl::I16(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::I16(buffer),
+            ))?),
+            Format::I8 => DynStreamImpl::I8(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::I8(buffer),
+            ))?),
+            Format::U8 => DynStreamImpl::U8(self.open_dynamic_outstream(dyn_output(
+                config,
+                callback,
+                |buffer| DynBuffer::U8(buffer),
+            ))?),
+            format => return Err(Error::IncompatibleFormat(format)),
+        };
+        Ok(DynStream(stream))
+    }
+
+    /// Creates an input stream whose format is picked at runtime. The callback is passed a
+    /// [`DynInputBuffer`] of the captured samples, and the channel count. See
+    /// [`open_dyn_outstream`](Device::open_dyn_outstream).
+    pub fn open_dyn_input_stream(
+        &mut self,
+        config: StreamConfig,
+        callback: DynInputCallback,
+    ) -> Result<DynStream> {
+        let stream = match config.format {
+            Format::F32 => DynStreamImpl::F32(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::F32(buffer),
+            ))?),
+            Format::I32 => DynStreamImpl::I32(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::I32(buffer),
+            ))?),
+            Format::I24 => DynStreamImpl::I24(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::I24(buffer),
+            ))?),
+            Format::I16 => DynStreamImpl::I16(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::I16(buffer),
+            ))?),
+            Format::I8 => DynStreamImpl::I8(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::I8(buffer),
+            ))?),
+            Format::U8 => DynStreamImpl::U8(self.open_dynamic_input_stream(dyn_input(
+                config,
+                callback,
+                |buffer| DynInputBuffer::U8(buffer),
+            ))?),
+            format => return Err(Error::IncompatibleFormat(format)),
+        };
+        Ok(DynStream(stream))
+    }
+
+    /// Creates an output stream whose callback is passed a [`CallbackInfo`](crate::CallbackInfo)
+    /// along with the buffer to fill: e.g. to know when the buffer will be heard, for lip-sync.
+    /// The callback returns a [`StreamFlow`](crate::StreamFlow), which can end the stream from
+    /// inside it.
+    /// Like dynamic streams, only formats the device supports natively are available, and the
+    /// stream isn't resampled or mixed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_outstream_with_info(StreamOptions::<[f32; 2], OutputWithInfo> {
+    ///     callback: Box::new(|buffer: &mut [[f32; 2]], info: &CallbackInfo| {
+    ///         let _latency = info.playback_time.unwrap() - info.current_time;
+    ///         # buffer;
+    ///         StreamFlow::Continue
+    ///     }),
+    ///     ..Default::default()
+    /// });
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_outstream_with_info<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, OutputWithInfo>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "output", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_outstream_with_info(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates an input stream whose callback is passed a [`CallbackInfo`](crate::CallbackInfo)
+    /// along with the captured frames. See
+    /// [`open_outstream_with_info`](Device::open_outstream_with_info).
+    pub fn open_input_stream_with_info<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, InputWithInfo>,
+    ) -> Result<Stream<Frame>> {
+        let _span = trace::open_span(self.name(), "input", &options);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_input_stream_with_info(options)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream))),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a full-duplex stream, which simultaneously captures from and plays to this device.
+    ///
+    /// `input` and `output` configure each half of the stream. Both must resolve to the same sample
+    /// rate and frames_per_buffer. The callback receives the captured input frames along with the
+    /// output buffer to fill in a single invocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// // Plays back whatever is captured.
+    /// fn callback(input: &[[f32; 1]], output: &mut [[f32; 1]]) {
+    ///     output.copy_from_slice(input);
+    /// }
+    /// let mut device = Host::with_default_backend()?.default_input_device()?;
+    /// let stream = device.open_duplex_stream(
+    ///     StreamOptions::default(),
+    ///     StreamOptions::default(),
+    ///     Box::new(callback),
+    /// );
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_duplex_stream<InFrame: 'static, OutFrame: 'static>(
+        &mut self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexCallback<InFrame, OutFrame>,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        let _span = trace::open_span(self.name(), "duplex", &output);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_duplex_stream(input, output, callback)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream)))
+                .map_err(|error| error.on_device(self.name())),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+
+    /// Creates a full-duplex stream whose callback is passed a
+    /// [`CallbackInfo`](crate::CallbackInfo) along with the captured frames and the output buffer:
+    /// e.g. for echo cancellation, which needs to know how far apart the input was captured and
+    /// the output will be played. Both buffers always have as many frames. See
+    /// [`open_duplex_stream`](Device::open_duplex_stream).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_input_device()?;
+    /// let stream = device.open_duplex_stream_with_info(
+    ///     StreamOptions::default(),
+    ///     StreamOptions::default(),
+    ///     Box::new(
+    ///         |input: &[[f32; 1]], output: &mut [[f32; 1]], info: &CallbackInfo| {
+    ///             // How long the input takes to come out, once played back.
+    ///             let _delay = info.playback_time.unwrap() - info.capture_time.unwrap();
+    ///             output.copy_from_slice(input);
+    ///         },
+    ///     ),
+    /// );
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_duplex_stream_with_info<InFrame: 'static, OutFrame: 'static>(
+        &mut self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexInfoCallback<InFrame, OutFrame>,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        let _span = trace::open_span(self.name(), "duplex", &output);
+        trace::opened(match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device
+                .open_duplex_stream_with_info(input, output, callback)
+                .map(|stream| Stream(StreamImpl::Portaudio(stream)))
+                .map_err(|error| error.on_device(self.name())),
+            _ => Err(Error::IncompatibleStreamMode),
+        })
+    }
+}
+
+/// A dynamic stream's options, whose callback passes its buffer to `callback` as a `wrap` variant.
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+fn dyn_output<Sample: 'static>(
+    config: StreamConfig,
+    mut callback: DynCallback,
+    wrap: fn(&mut [Sample]) -> DynBuffer,
+) -> StreamOptions<Sample, DynamicOutput> {
+    config.with_callback::<Sample, DynamicOutput>(Box::new(move |buffer, n_channels| {
+        callback(wrap(buffer), n_channels)
+    }))
+}
+
+/// A dynamic input stream's options. See [`dyn_output`].
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+fn dyn_input<Sample: 'static>(
+    config: StreamConfig,
+    mut callback: DynInputCallback,
+    wrap: fn(&[Sample]) -> DynInputBuffer,
+) -> StreamOptions<Sample, DynamicInput> {
+    config.with_callback::<Sample, DynamicInput>(Box::new(move |buffer, n_channels| {
+        callback(wrap(buffer), n_channels)
+    }))
+}
+
+#[cfg(all(windows, feature = "asio"))]
+impl Device {
+    /// Returns the buffer sizes the device's ASIO driver supports.
+    ///
+    /// Streams opened on ASIO devices run at the driver's preferred size if their
+    /// `frames_per_buffer` allows it, or else at the supported size nearest to it. Returns
+    /// [`Error::BackendUnavailable`](crate::Error::BackendUnavailable) if the device isn't an ASIO
+    /// device.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let mut device = Host::with_backend(Backend::Asio)?.default_output_device()?;
+    /// let sizes = device.asio_buffer_sizes()?;
+    /// // Ask for the smallest buffer the driver can do.
+    /// let stream = device.open_outstream(StreamOptions::<[f32; 2]> {
+    ///     frames_per_buffer: BufferSize::Exact(sizes.min),
+    ///     ..Default::default()
+    /// })?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn asio_buffer_sizes(&self) -> Result<AsioBufferSizes> {
+        match &self.0 {
+            DeviceImpl::Portaudio(device) => device.asio_buffer_sizes(),
+            _ => Err(Error::BackendUnavailable),
+        }
+    }
+
+    /// Opens the ASIO driver's control panel, where users usually pick its preferred buffer size.
+    pub fn show_asio_control_panel(&mut self) -> Result<()> {
+        match &mut self.0 {
+            DeviceImpl::Portaudio(device) => device.show_asio_control_panel(),
+            _ => Err(Error::BackendUnavailable),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error::Result;
+use crate::facade::Stream;
+use crate::stream_options::{Format, StreamInfo};
+use std::time::Duration;
+
+/// An output stream's buffer of interleaved samples, in the stream's format. See
+/// [`Device::open_dyn_outstream`](crate::Device::open_dyn_outstream).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DynBuffer<'a> {
+    F32(&'a mut [f32]),
+    I32(&'a mut [i32]),
+    /// Packed, little-endian 24-bit samples.
+    I24(&'a mut [[u8; 3]]),
+    I16(&'a mut [i16]),
+    I8(&'a mut [i8]),
+    U8(&'a mut [u8]),
+}
+
+/// An input stream's buffer of interleaved samples, in the stream's format. See
+/// [`Device::open_dyn_input_stream`](crate::Device::open_dyn_input_stream).
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum DynInputBuffer<'a> {
+    F32(&'a [f32]),
+    I32(&'a [i32]),
+    /// Packed, little-endian 24-bit samples.
+    I24(&'a [[u8; 3]]),
+    I16(&'a [i16]),
+    I8(&'a [i8]),
+    U8(&'a [u8]),
+}
+
+/// The callback of streams whose format is picked at runtime. The callback is passed the buffer
+/// to fill, and the channel count.
+pub type DynCallback = Box<dyn FnMut(DynBuffer, usize) + Send>;
+/// The callback of input streams whose format is picked at runtime. The callback is passed the
+/// captured buffer, and the channel count.
+pub type DynInputCallback = Box<dyn FnMut(DynInputBuffer, usize) + Send>;
+
+impl DynBuffer<'_> {
+    pub fn format(&self) -> Format {
+        match self {
+            DynBuffer::F32(_) => Format::F32,
+            DynBuffer::I32(_) => Format::I32,
+            DynBuffer::I24(_) => Format::I24,
+            DynBuffer::I16(_) => Format::I16,
+            DynBuffer::I8(_) => Format::I8,
+            DynBuffer::U8(_) => Format::U8,
+        }
+    }
+
+    /// The number of samples, across all channels.
+    pub fn len(&self) -> usize {
+        match self {
+            DynBuffer::F32(buffer) => buffer.len(),
+            DynBuffer::I32(buffer) => buffer.len(),
+            DynBuffer::I24(buffer) => buffer.len(),
+            DynBuffer::I16(buffer) => buffer.len(),
+            DynBuffer::I8(buffer) => buffer.len(),
+            DynBuffer::U8(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills the buffer with silence, which is mid-scale for unsigned formats.
+    pub fn fill_silence(&mut self) {
+        match self {
+            DynBuffer::F32(buffer) => buffer.iter_mut().for_each(|sample| *sample = 0.0),
+            DynBuffer::I32(buffer) => buffer.iter_mut().for_each(|sample| *sample = 0),
+            DynBuffer::I24(buffer) => buffer.iter_mut().for_each(|sample| *sample = [0; 3]),
+            DynBuffer::I16(buffer) => buffer.iter_mut().for_each(|sample| *sample = 0),
+            DynBuffer::I8(buffer) => buffer.iter_mut().for_each(|sample| *sample = 0),
+            DynBuffer::U8(buffer) => buffer.iter_mut().for_each(|sample| *sample = 0x80),
+        }
+    }
+}
+
+impl DynInputBuffer<'_> {
+    pub fn format(&self) -> Format {
+        match self {
+            DynInputBuffer::F32(_) => Format::F32,
+            DynInputBuffer::I32(_) => Format::I32,
+            DynInputBuffer::I24(_) => Format::I24,
+            DynInputBuffer::I16(_) => Format::I16,
+            DynInputBuffer::I8(_) => Format::I8,
+            DynInputBuffer::U8(_) => Format::U8,
+        }
+    }
+
+    /// The number of samples, across all channels.
+    pub fn len(&self) -> usize {
+        match self {
+            DynInputBuffer::F32(buffer) => buffer.len(),
+            DynInputBuffer::I32(buffer) => buffer.len(),
+            DynInputBuffer::I24(buffer) => buffer.len(),
+            DynInputBuffer::I16(buffer) => buffer.len(),
+            DynInputBuffer::I8(buffer) => buffer.len(),
+            DynInputBuffer::U8(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A stream whose format is picked at runtime, for hosts (e.g. DAWs or plugin hosts) that can't
+/// know their sample type at compile time. It holds the [`Stream`] of whichever sample type its
+/// format has. See [`Device::open_dyn_outstream`](crate::Device::open_dyn_outstream).
+pub struct DynStream(pub(super) DynStreamImpl);
+
+pub(super) enum DynStreamImpl {
+    F32(Stream<f32>),
+    I32(Stream<i32>),
+    I24(Stream<[u8; 3]>),
+    I16(Stream<i16>),
+    I8(Stream<i8>),
+    U8(Stream<u8>),
+}
+
+/// Evaluates `$body` with `$stream` bound to the typed stream held by `$value`.
+macro_rules! dispatch_dyn {
+    ($value:expr, $stream:ident => $body:expr) => {
+        match $value {
+            DynStreamImpl::F32($stream) => $body,
+            DynStreamImpl::I32($stream) => $body,
+            DynStreamImpl::I24($stream) => $body,
+            DynStreamImpl::I16($stream) => $body,
+            DynStreamImpl::I8($stream) => $body,
+            DynStreamImpl::U8($stream) => $body,
+        }
+    };
+}
+
+impl DynStream {
+    /// The format of the stream's samples, which is its callback's buffer variant.
+    pub fn format(&self) -> Format {
+        match &self.0 {
+            DynStreamImpl::F32(_) => Format::F32,
+            DynStreamImpl::I32(_) => Format::I32,
+            DynStreamImpl::I24(_) => Format::I24,
+            DynStreamImpl::I16(_) => Format::I16,
+            DynStreamImpl::I8(_) => Format::I8,
+            DynStreamImpl::U8(_) => Format::U8,
+        }
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        dispatch_dyn!(&mut self.0, stream => stream.start())
+    }
+
+    pub fn close(self) {
+        dispatch_dyn!(self.0, stream => stream.close())
+    }
+
+    /// See [`Stream::info`].
+    pub fn info(&self) -> StreamInfo {
+        dispatch_dyn!(&self.0, stream => stream.info())
+    }
+
+    /// See [`Stream::input_latency`].
+    pub fn input_latency(&self) -> Option<Duration> {
+        dispatch_dyn!(&self.0, stream => stream.input_latency())
+    }
+
+    /// See [`Stream::output_latency`].
+    pub fn output_latency(&self) -> Option<Duration> {
+        dispatch_dyn!(&self.0, stream => stream.output_latency())
+    }
+
+    /// See [`Stream::time`].
+    pub fn time(&self) -> Option<Duration> {
+        dispatch_dyn!(&self.0, stream => stream.time())
+    }
+
+    /// See [`Stream::cpu_load`].
+    pub fn cpu_load(&self) -> Option<f64> {
+        dispatch_dyn!(&self.0, stream => stream.cpu_load())
+    }
+
+    pub fn is_active(&self) -> Result<bool> {
+        dispatch_dyn!(&self.0, stream => stream.is_active())
+    }
+
+    pub fn is_stopped(&self) -> Result<bool> {
+        dispatch_dyn!(&self.0, stream => stream.is_stopped())
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        dispatch_dyn!(&mut self.0, stream => stream.stop())
+    }
+
+    /// See [`Stream::drain`].
+    pub fn drain(&mut self) -> Result<()> {
+        dispatch_dyn!(&mut self.0, stream => stream.drain())
+    }
+
+    pub fn abort(&mut self) -> Result<()> {
+        dispatch_dyn!(&mut self.0, stream => stream.abort())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_silence_in_every_format() {
+        let mut floats = [1.0f32; 4];
+        let mut unsigned = [0u8; 4];
+        let mut packed = [[1u8; 3]; 4];
+        for mut buffer in [
+            DynBuffer::F32(&mut floats),
+            DynBuffer::U8(&mut unsigned),
+            DynBuffer::I24(&mut packed),
+        ] {
+            assert_eq!(buffer.len(), 4);
+            buffer.fill_silence();
+        }
+        assert_eq!(floats, [0.0; 4]);
+        assert_eq!(unsigned, [0x80; 4]);
+        assert_eq!(packed, [[0; 3]; 4]);
+        assert_eq!(DynInputBuffer::I24(&packed).format(), Format::I24);
+    }
+}
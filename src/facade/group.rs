@@ -0,0 +1,146 @@
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+use crate::portaudio;
+
+use crate::error::Result;
+use crate::stream_options::{Input, StreamOptions};
+use crate::{Device, Stream};
+
+/// Streams, possibly on several devices, that start together: e.g. for rigs of several devices
+/// whose timelines have to line up. Dropping the group closes its streams.
+///
+/// Output streams opened through the group [prime](StreamOptions::prime_output) their buffers, so
+/// that they play their callback's first frames as soon as they start, and Portaudio streams are
+/// all started while holding Portaudio's lock, so that no other call runs between them. The
+/// streams still start one after the other: Devices with their own clocks drift apart over time.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use audiohal::*;
+/// let mut host = Host::with_default_backend()?;
+/// let mut group = StreamGroup::new();
+/// for name in ["Front speakers", "Rear speakers"] {
+///     let mut device = host.device_by_name(name)?;
+///     group.open_outstream(&mut device, StreamOptions::<[f32; 2]>::default())?;
+/// }
+/// group.start()?;
+/// # Result::Ok(())
+/// ```
+#[derive(Default)]
+pub struct StreamGroup {
+    streams: Vec<Box<dyn Member>>,
+}
+
+/// What the group does with its streams, whatever their frames.
+trait Member {
+    fn start(&mut self) -> Result<()>;
+}
+
+impl<Frame> Member for Stream<Frame> {
+    fn start(&mut self) -> Result<()> {
+        Stream::start(self)
+    }
+}
+
+impl StreamGroup {
+    pub fn new() -> StreamGroup {
+        StreamGroup::default()
+    }
+
+    /// Opens an output stream on `device` (see [`Device::open_outstream`]) that primes its
+    /// buffers, and adds it to the group.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        device: &mut Device,
+        options: StreamOptions<Frame>,
+    ) -> Result<()> {
+        let stream = device.open_outstream(StreamOptions {
+            prime_output: true,
+            ..options
+        })?;
+        self.add(stream);
+        Ok(())
+    }
+
+    /// Opens an input stream on `device` (see [`Device::open_input_stream`]), and adds it to the
+    /// group.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        device: &mut Device,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<()> {
+        let stream = device.open_input_stream(options)?;
+        self.add(stream);
+        Ok(())
+    }
+
+    /// Adds a stream that was opened some other way: e.g. a duplex stream. Streams that should
+    /// play right away should have been opened with [`prime_output`](StreamOptions::prime_output).
+    pub fn add<Frame: 'static>(&mut self, stream: Stream<Frame>) {
+        self.streams.push(Box::new(stream));
+    }
+
+    /// How many streams the group has.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Starts every stream, in the order they were added. Stops at the first stream that fails to
+    /// start, and returns its error: The streams started before it keep running until the group
+    /// is dropped.
+    pub fn start(&mut self) -> Result<()> {
+        #[cfg(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        ))]
+        let _guard = portaudio::global_lock();
+        self.streams
+            .iter_mut()
+            .try_for_each(|stream| stream.start())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Error, Host};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn starts_streams_together() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        let mut output = host.default_output_device()?;
+        let mut input = host.default_input_device()?;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut group = StreamGroup::new();
+        for _ in 0..2 {
+            let calls = Arc::clone(&calls);
+            group.open_outstream(
+                &mut output,
+                StreamOptions::<[f32; 2]> {
+                    callback: Box::new(move |_| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                    }),
+                    ..Default::default()
+                },
+            )?;
+        }
+        group.open_input_stream(&mut input, StreamOptions::<[f32; 1], Input>::default())?;
+        assert_eq!(group.len(), 3);
+        group.start()?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert_ge!(calls.load(Ordering::Relaxed), 2);
+        // Started streams can't be started again.
+        assert_eq!(group.start().err(), Some(Error::StreamAlreadyStarted));
+        Ok(())
+    }
+}
@@ -0,0 +1,609 @@
+#[cfg(all(target_os = "android", feature = "aaudio"))]
+use crate::android;
+use crate::backend::{Backend, HostApi};
+use crate::error::{Error, Result};
+use crate::facade::hotplug;
+use crate::facade::*;
+use crate::stream_options::StreamOptions;
+use crate::surround;
+use crate::trace;
+use std::sync::mpsc::Receiver;
+
+/// An audio API, through which devices are found.
+///
+/// A host can have any number of streams open at once, on any of its devices. Devices and streams
+/// keep what they need of their host alive: Dropping the host doesn't stop or invalidate them, and
+/// the backend only shuts down once the last of them is dropped too.
+///
+/// ```
+/// # use audiohal::*;
+/// let mut host = Host::with_default_backend()?;
+/// let mut output = host.default_output_device()?;
+/// let mut input = host.default_input_device()?;
+/// drop(host);
+/// let outstream = output.open_outstream(StreamOptions::<[f32; 2]>::default());
+/// let instream = input.open_input_stream(StreamOptions::<[f32; 1], Input>::default());
+/// # (outstream.ok(), instream.ok());
+/// # Result::Ok(())
+/// ```
+pub struct Host(HostImpl);
+
+impl Host {
+    /// Creates a host with the default system backend.
+    ///
+    /// Tries the [available backends](Host::available_backends) in order, and returns the first
+    /// one that initializes. The [`Backend::Dummy`] backend is never picked.
+    pub fn with_default_backend() -> Result<Host> {
+        #[cfg(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        ))]
+        {
+            if let Ok(host) = portaudio::Host::with_default_backend() {
+                return trace::host(None, Ok(Host(HostImpl::Portaudio(host))));
+            }
+        }
+        let mut result = Err(Error::BackendUnavailable);
+        for backend in Host::available_backends() {
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            {
+                if backend == Backend::AAudio && !android::supports_aaudio() {
+                    continue;
+                }
+            }
+            if backend == Backend::Dummy {
+                continue;
+            }
+            result = Host::with_backend(backend);
+            if result.is_ok() {
+                break;
+            }
+        }
+        trace::host(None, result)
+    }
+
+    /// Creates a host with a specific backend.
+    ///
+    /// Backends Portaudio supports are opened through it first, and through their native backend
+    /// if that fails. Will return [`Error::BackendUnavailable`] if the backend support was not
+    /// compiled.
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// assert!(Host::with_backend(Backend::Dummy).is_ok(), "The dummy backend should always be available.");
+    /// ```
+    pub fn with_backend(backend: Backend) -> Result<Host> {
+        #[cfg(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        ))]
+        let error = match portaudio::Host::with_backend(backend) {
+            Ok(host) => return trace::host(Some(backend), Ok(Host(HostImpl::Portaudio(host)))),
+            Err(error) => error,
+        };
+        #[cfg(any(
+            not(feature = "portaudio"),
+            target_os = "android",
+            target_arch = "wasm32"
+        ))]
+        let error = Error::BackendUnavailable;
+        let host = match backend {
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            Backend::AAudio => aaudio::Host::new().map(HostImpl::AAudio),
+            #[cfg(all(target_os = "linux", feature = "alsa"))]
+            Backend::Alsa => alsa::Host::new().map(HostImpl::Alsa),
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            Backend::CoreAudio => coreaudio::Host::new().map(HostImpl::CoreAudio),
+            #[cfg(feature = "jack")]
+            Backend::Jack => jack::Host::new().map(HostImpl::Jack),
+            Backend::Dummy => null::Host::new().map(HostImpl::Null),
+            #[cfg(all(target_os = "android", feature = "opensles"))]
+            Backend::OpenSles => opensles::Host::new().map(HostImpl::OpenSles),
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            Backend::PipeWire => pipewire::Host::new().map(HostImpl::PipeWire),
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            Backend::PulseAudio => pulseaudio::Host::new().map(HostImpl::PulseAudio),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            Backend::Wasapi => wasapi::Host::new().map(HostImpl::Wasapi),
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+            Backend::WebAudio => webaudio::Host::new().map(HostImpl::WebAudio),
+            _ => Err(error),
+        };
+        trace::host(Some(backend), host.map(Host))
+    }
+
+    /// Returns the backends compiled into this build, in the order
+    /// [`with_default_backend`](Host::with_default_backend) tries them. [`Backend::Dummy`] is
+    /// always last.
+    ///
+    /// Being available doesn't mean that a backend will initialize: e.g. the PulseAudio backend
+    /// needs a running daemon.
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// for backend in Host::available_backends() {
+    ///     match Host::with_backend(backend) {
+    ///         Ok(host) => println!("{:?}: {}", backend, host.name()),
+    ///         Err(error) => println!("{:?} failed: {:?}", backend, error),
+    ///     }
+    /// }
+    /// assert_eq!(Host::available_backends().last(), Some(&Backend::Dummy));
+    /// ```
+    pub fn available_backends() -> Vec<Backend> {
+        #[cfg(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        ))]
+        let mut backends = portaudio::Host::backends().unwrap_or_default();
+        #[cfg(any(
+            not(feature = "portaudio"),
+            target_os = "android",
+            target_arch = "wasm32"
+        ))]
+        let mut backends = Vec::new();
+        let native = [
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            Backend::AAudio,
+            #[cfg(all(target_os = "android", feature = "opensles"))]
+            Backend::OpenSles,
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            Backend::PipeWire,
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            Backend::PulseAudio,
+            #[cfg(all(target_os = "linux", feature = "alsa"))]
+            Backend::Alsa,
+            #[cfg(feature = "jack")]
+            Backend::Jack,
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            Backend::CoreAudio,
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            Backend::Wasapi,
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+            Backend::WebAudio,
+            Backend::Dummy,
+        ];
+        for backend in native.iter() {
+            if !backends.contains(backend) {
+                backends.push(*backend);
+            }
+        }
+        backends
+    }
+
+    /// Returns Portaudio's host APIs, or none if Portaudio isn't compiled or doesn't initialize.
+    ///
+    /// Portaudio's default is often the wrong one for latency-sensitive programs: e.g. it's MME
+    /// on Windows builds with the `windows-legacy` feature. Hosts on a specific one are opened
+    /// with [`with_backend`](Host::with_backend).
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// let wasapi = Host::host_apis()
+    ///     .into_iter()
+    ///     .find(|host_api| host_api.backend == Backend::Wasapi && host_api.n_devices > 0);
+    /// let host = match wasapi {
+    ///     Some(host_api) => Host::with_backend(host_api.backend)?,
+    ///     None => Host::with_default_backend()?,
+    /// };
+    /// # host;
+    /// # Result::Ok(())
+    /// ```
+    pub fn host_apis() -> Vec<HostApi> {
+        #[cfg(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        ))]
+        let host_apis = portaudio::Host::host_apis().unwrap_or_default();
+        #[cfg(any(
+            not(feature = "portaudio"),
+            target_os = "android",
+            target_arch = "wasm32"
+        ))]
+        let host_apis = Vec::new();
+        host_apis
+    }
+
+    /// Returns the host API's descriptive name (e.g. "CoreAudio").
+    pub fn name(&self) -> &str {
+        dispatch!(&self.0, HostImpl, host => host.name())
+    }
+
+    /// Returns the backend the host runs on.
+    pub fn backend(&self) -> Backend {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            HostImpl::Portaudio(host) => host.backend(),
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            HostImpl::AAudio(_) => Backend::AAudio,
+            #[cfg(all(target_os = "linux", feature = "alsa"))]
+            HostImpl::Alsa(_) => Backend::Alsa,
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            HostImpl::CoreAudio(_) => Backend::CoreAudio,
+            #[cfg(feature = "jack")]
+            HostImpl::Jack(_) => Backend::Jack,
+            HostImpl::Null(_) => Backend::Dummy,
+            #[cfg(all(target_os = "android", feature = "opensles"))]
+            HostImpl::OpenSles(_) => Backend::OpenSles,
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            HostImpl::PipeWire(_) => Backend::PipeWire,
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            HostImpl::PulseAudio(_) => Backend::PulseAudio,
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            HostImpl::Wasapi(_) => Backend::Wasapi,
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+            HostImpl::WebAudio(_) => Backend::WebAudio,
+        }
+    }
+
+    /// Creates and returns the default output device for this host.
+    ///
+    /// This is the recommended device to use for audio playback. It's picked independently of the
+    /// [default input device](Host::default_input_device), and often differs from it: e.g. HDMI
+    /// output with a USB microphone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut host = audiohal::Host::with_default_backend()?;
+    /// match host.default_output_device() {
+    ///     Ok(device) => println!("Default output device name is {}.", device.name()),
+    ///     Err(_) => println!("No devices available."),
+    /// };
+    /// # audiohal::Result::Ok(())
+    /// ```
+    ///
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        trace::device(
+            "default output",
+            dispatch!(&mut self.0, HostImpl, host => host.default_output_device(), map DeviceImpl)
+                .map(Device),
+        )
+    }
+
+    /// Creates and returns the default input device for this host.
+    ///
+    /// This is the recommended device to use for audio capture. It's picked independently of the
+    /// [default output device](Host::default_output_device).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut host = audiohal::Host::with_default_backend()?;
+    /// match host.default_input_device() {
+    ///     Ok(device) => println!("Default input device name is {}.", device.name()),
+    ///     Err(_) => println!("No devices available."),
+    /// };
+    /// # audiohal::Result::Ok(())
+    /// ```
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        trace::device(
+            "default input",
+            dispatch!(&mut self.0, HostImpl, host => host.default_input_device(), map DeviceImpl)
+                .map(Device),
+        )
+    }
+
+    /// Creates and returns all of the host's devices, input and output, for picking one: e.g. in a
+    /// device menu. A device's position in the list is its index, which is stable until devices
+    /// are added or removed.
+    ///
+    /// Portaudio, JACK, and PipeWire hosts list all of their devices. The other backends only
+    /// list their default output and input devices (the ones that exist).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut host = audiohal::Host::with_default_backend()?;
+    /// for (index, device) in host.devices()?.iter().enumerate() {
+    ///     println!("{}: {}", index, device.name());
+    /// }
+    /// # audiohal::Result::Ok(())
+    /// ```
+    pub fn devices(&mut self) -> Result<Vec<Device>> {
+        trace::devices(self.enumerate_devices())
+    }
+
+    /// Creates and returns the first of the host's [`devices`](Host::devices) with the given name.
+    /// Returns [`Error::NoSuchDevice`] if there is none: e.g. if it was unplugged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut host = Host::with_default_backend()?;
+    /// let device = match host.device_by_name("Studio Monitors") {
+    ///     Err(Error::NoSuchDevice) => host.default_output_device()?,
+    ///     device => device?,
+    /// };
+    /// # device;
+    /// # Result::Ok(())
+    /// ```
+    pub fn device_by_name(&mut self, name: &str) -> Result<Device> {
+        let device = self
+            .devices()?
+            .into_iter()
+            .find(|device| device.name() == name)
+            .ok_or(Error::NoSuchDevice);
+        trace::device("named", device)
+    }
+
+    /// Creates and returns the device with the given [`uid`](Device::uid), e.g. to reopen the
+    /// device the user picked last session. Returns [`Error::NoSuchDevice`] if it's gone.
+    pub fn device_by_uid(&mut self, uid: &str) -> Result<Device> {
+        let device = self
+            .devices()?
+            .into_iter()
+            .find(|device| device.uid() == uid)
+            .ok_or(Error::NoSuchDevice);
+        trace::device("by uid", device)
+    }
+
+    /// Creates and returns the device with the given [`id`](Device::id), e.g. one saved in the
+    /// user's preferences. Returns [`Error::NoSuchDevice`] if it's gone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut host = Host::with_default_backend()?;
+    /// let saved = host.default_output_device()?.id().to_string();
+    /// // Restart the application...
+    /// let device = host.device_by_id(&DeviceId::from(saved))?;
+    /// # device;
+    /// # Result::Ok(())
+    /// ```
+    pub fn device_by_id(&mut self, id: &DeviceId) -> Result<Device> {
+        self.device_by_uid(id.as_str())
+    }
+
+    fn enumerate_devices(&mut self) -> Result<Vec<Device>> {
+        let devices = match &mut self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            HostImpl::Portaudio(host) => host
+                .devices()?
+                .into_iter()
+                .map(DeviceImpl::Portaudio)
+                .collect(),
+            #[cfg(feature = "jack")]
+            HostImpl::Jack(host) => {
+                let mut devices = host.output_devices()?;
+                devices.append(&mut host.input_devices()?);
+                devices.into_iter().map(DeviceImpl::Jack).collect()
+            }
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            HostImpl::PipeWire(host) => {
+                let mut devices = host.output_devices()?;
+                devices.append(&mut host.input_devices()?);
+                devices.into_iter().map(DeviceImpl::PipeWire).collect()
+            }
+            _ => {
+                let mut devices = Vec::new();
+                for device in [self.default_output_device(), self.default_input_device()] {
+                    match device {
+                        Ok(device) => devices.push(device.0),
+                        Err(Error::NoSuchDevice) => (),
+                        Err(error) => return Err(error),
+                    }
+                }
+                devices
+            }
+        };
+        Ok(devices.into_iter().map(Device).collect())
+    }
+
+    /// Watches the host's [`devices`](Host::devices), and sends an event whenever one is added or
+    /// removed, so that device lists can be refreshed without polling them. Stops once the
+    /// receiver is dropped. Changes are noticed within a second.
+    ///
+    /// Portaudio only lists its devices once, so Portaudio hosts (and the browser, which has no
+    /// threads to watch on) return [`Error::IncompatibleStreamMode`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let host = Host::with_backend(Backend::PulseAudio)?;
+    /// for event in host.watch_devices()? {
+    ///     match event {
+    ///         DeviceEvent::Added(uid) => println!("{} was plugged in.", uid),
+    ///         DeviceEvent::Removed(uid) => println!("{} is gone.", uid),
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn watch_devices(&self) -> Result<Receiver<DeviceEvent>> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            HostImpl::Portaudio(_) => Err(Error::IncompatibleStreamMode),
+            _ => hotplug::watch(self.backend()),
+        }
+    }
+
+    /// Opens a 5.1 or 7.1 output stream on the default output device, with 6 or 8 channels.
+    ///
+    /// The callback's frames are in the same order on every backend: front left, front right,
+    /// center, LFE, back left, back right, and then side left and side right for 7.1. They are
+    /// reordered for backends whose devices take another order (e.g. ALSA, which puts the back
+    /// channels before the center). Other channel counts return
+    /// [`Error::IncompatibleNChannels`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut host = Host::with_default_backend()?;
+    /// let stream = host.open_default_surround_outstream(StreamOptions::<[f32; 6]> {
+    ///     callback: Box::new(|frames| {
+    ///         // Only play the center channel.
+    ///         frames.iter_mut().for_each(|frame| *frame = [0.0, 0.0, 0.5, 0.0, 0.0, 0.0]);
+    ///     }),
+    ///     ..Default::default()
+    /// });
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_default_surround_outstream<S, const N: usize>(
+        &mut self,
+        options: StreamOptions<[S; N]>,
+    ) -> Result<Stream<[S; N]>>
+    where
+        S: Copy + Send + 'static,
+    {
+        if N != 6 && N != 8 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let mut options = options;
+        if let Some(order) = surround::device_order(self.backend(), N) {
+            let mut callback = options.callback;
+            options.callback = Box::new(move |frames: &mut [[S; N]]| {
+                callback(frames);
+                surround::reorder(frames, order);
+            });
+        }
+        self.default_output_device()?.open_outstream(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_options::Input;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn lists_the_dummy_backend_last() {
+        let backends = Host::available_backends();
+        assert_eq!(backends.last(), Some(&Backend::Dummy));
+        assert_eq!(backends.iter().filter(|&&b| b == Backend::Dummy).count(), 1);
+    }
+
+    #[test]
+    fn opens_the_dummy_backend() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        assert_eq!(host.name(), "Null");
+        host.default_output_device()?
+            .open_outstream(StreamOptions::<[f32; 2]>::default())?
+            .start()
+    }
+
+    #[test]
+    fn rejects_loopback_streams_without_support() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        assert_eq!(
+            device
+                .open_loopback_stream(StreamOptions::<[f32; 2], Input>::default())
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_volume_control_without_a_mixer() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        assert_eq!(device.volume().err(), Some(Error::IncompatibleStreamMode));
+        assert_eq!(
+            device.set_muted(true).err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lists_default_devices() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        assert_eq!(host.devices()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_devices_by_name() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        let device = host.default_output_device()?;
+        assert_eq!(host.device_by_name(device.name())?.name(), device.name());
+        assert_eq!(host.device_by_uid(&device.uid())?.uid(), device.uid());
+        assert_eq!(host.device_by_id(&device.id())?.id(), device.id());
+        assert_eq!(
+            DeviceId::from(device.id().to_string()).as_str(),
+            device.uid()
+        );
+        assert_eq!(
+            host.device_by_name("Unplugged").err(),
+            Some(Error::NoSuchDevice)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn opens_surround_streams() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        assert_eq!(host.backend(), Backend::Dummy);
+        host.open_default_surround_outstream(StreamOptions::<[f32; 8]>::default())?;
+        assert_eq!(
+            host.open_default_surround_outstream(StreamOptions::<[f32; 4]>::default())
+                .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn streams_outlive_their_host() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Dummy)?;
+        let mut output = host.default_output_device()?;
+        let mut input = host.default_input_device()?;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let output_calls = Arc::clone(&calls);
+        let input_calls = Arc::clone(&calls);
+        let mut streams = [
+            output.open_outstream(StreamOptions::<[f32; 2]> {
+                callback: Box::new(move |_| {
+                    output_calls.fetch_add(1, Ordering::Relaxed);
+                }),
+                ..Default::default()
+            })?,
+            output.open_outstream(StreamOptions::<[f32; 2]>::default())?,
+        ];
+        let mut instream = input.open_input_stream(StreamOptions::<[f32; 2], Input> {
+            callback: Box::new(move |_| {
+                input_calls.fetch_add(1, Ordering::Relaxed);
+            }),
+            ..Default::default()
+        })?;
+        drop(host);
+        streams.iter_mut().try_for_each(Stream::start)?;
+        instream.start()?;
+        std::thread::sleep(Duration::from_millis(50));
+        // Both callbacks ran, after the host was gone.
+        assert_ge!(calls.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unavailable_backends() {
+        #[cfg(not(target_arch = "wasm32"))]
+        let backend = Backend::WebAudio;
+        #[cfg(target_arch = "wasm32")]
+        let backend = Backend::Wasapi;
+        assert!(!Host::available_backends().contains(&backend));
+        assert_eq!(
+            Host::with_backend(backend).err(),
+            Some(Error::BackendUnavailable)
+        );
+    }
+}
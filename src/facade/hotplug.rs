@@ -0,0 +1,101 @@
+//! Device hotplug notifications. Backends don't share a way of notifying them, so a thread lists
+//! the devices of its own host of the same backend, and sends what changed.
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+use crate::facade::Host;
+
+/// How often devices are listed.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A change in a host's devices. See [`Host::watch_devices`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// A device was added (e.g. plugged in), with the given [`uid`](crate::Device::uid).
+    Added(String),
+    /// A device was removed, with the given [`uid`](crate::Device::uid).
+    Removed(String),
+}
+
+/// Sends the changes in the backend's devices, until the receiver is dropped.
+pub(super) fn watch(backend: Backend) -> Result<Receiver<DeviceEvent>> {
+    // There are no threads to poll on in the browser.
+    if cfg!(target_arch = "wasm32") {
+        return Err(Error::IncompatibleStreamMode);
+    }
+    let (events, receiver) = mpsc::channel();
+    // The first listing is made here, so that failing to list devices is reported.
+    let uids = list_uids(&mut Host::with_backend(backend)?)?;
+    thread::spawn(move || poll(backend, uids, &events));
+    Ok(receiver)
+}
+
+fn poll(backend: Backend, mut uids: Vec<String>, events: &Sender<DeviceEvent>) {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        // Hosts may only list the devices they found when they were created.
+        let new_uids = match Host::with_backend(backend).and_then(|mut host| list_uids(&mut host)) {
+            Ok(new_uids) => new_uids,
+            // Try again later, e.g. once the server restarted.
+            Err(_) => continue,
+        };
+        for event in diff(&uids, &new_uids) {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+        uids = new_uids;
+    }
+}
+
+fn list_uids(host: &mut Host) -> Result<Vec<String>> {
+    Ok(host.devices()?.iter().map(|device| device.uid()).collect())
+}
+
+/// The events that turn `old` into `new`: removals first, then additions.
+fn diff(old: &[String], new: &[String]) -> Vec<DeviceEvent> {
+    let removed = old
+        .iter()
+        .filter(|uid| !new.contains(uid))
+        .map(|uid| DeviceEvent::Removed(uid.clone()));
+    let added = new
+        .iter()
+        .filter(|uid| !old.contains(uid))
+        .map(|uid| DeviceEvent::Added(uid.clone()));
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uids(uids: &[&str]) -> Vec<String> {
+        uids.iter().map(|uid| uid.to_string()).collect()
+    }
+
+    #[test]
+    fn diffs_devices() {
+        assert_eq!(
+            diff(
+                &uids(&["Speakers", "Headset"]),
+                &uids(&["Headset", "USB Mic"])
+            ),
+            vec![
+                DeviceEvent::Removed("Speakers".to_string()),
+                DeviceEvent::Added("USB Mic".to_string()),
+            ]
+        );
+        assert_eq!(diff(&uids(&["Headset"]), &uids(&["Headset"])), vec![]);
+    }
+
+    #[test]
+    fn watches_the_dummy_backend() -> Result<()> {
+        let events = watch(Backend::Dummy)?;
+        assert_eq!(events.recv_timeout(POLL_INTERVAL * 2).ok(), None);
+        Ok(())
+    }
+}
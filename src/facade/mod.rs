@@ -0,0 +1,126 @@
+//! The crate's root [`Host`]/[`Device`]/[`Stream`], which dispatch to whichever backend the host
+//! was created with.
+//!
+//! Every variant is one of the backend modules' types, and only exists if its backend's feature is
+//! enabled. Portaudio is the default backend where it builds, and the native backends are picked
+//! at runtime through [`Host::with_backend`]. The [`null`](crate::null) backend is always built,
+//! so that there always is one.
+#[cfg(all(target_os = "android", feature = "aaudio"))]
+use crate::aaudio;
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+use crate::alsa;
+#[cfg(all(target_os = "macos", feature = "coreaudio"))]
+use crate::coreaudio;
+#[cfg(feature = "jack")]
+use crate::jack;
+use crate::null;
+#[cfg(all(target_os = "android", feature = "opensles"))]
+use crate::opensles;
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+use crate::pipewire;
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+use crate::portaudio;
+#[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+use crate::pulseaudio;
+#[cfg(all(target_os = "windows", feature = "wasapi"))]
+use crate::wasapi;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+use crate::webaudio;
+
+mod device;
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+mod dyn_stream;
+mod group;
+mod host;
+mod hotplug;
+mod stream;
+
+// Public API exports.
+pub use device::{Device, DeviceId};
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+pub use dyn_stream::{DynBuffer, DynCallback, DynInputBuffer, DynInputCallback, DynStream};
+pub use group::StreamGroup;
+pub use host::Host;
+pub use hotplug::DeviceEvent;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// Declares an enum with a variant per compiled-in backend, each holding that backend's `$ty`.
+macro_rules! backend_enum {
+    ($name:ident $(<$param:ident>)?, $ty:ident) => {
+        enum $name $(<$param>)? {
+            #[cfg(all(feature = "portaudio", not(any(target_os = "android", target_arch = "wasm32"))))]
+            Portaudio(portaudio::$ty $(<$param>)?),
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            AAudio(aaudio::$ty $(<$param>)?),
+            #[cfg(all(target_os = "linux", feature = "alsa"))]
+            Alsa(alsa::$ty $(<$param>)?),
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            CoreAudio(coreaudio::$ty $(<$param>)?),
+            #[cfg(feature = "jack")]
+            Jack(jack::$ty $(<$param>)?),
+            Null(null::$ty $(<$param>)?),
+            #[cfg(all(target_os = "android", feature = "opensles"))]
+            OpenSles(opensles::$ty $(<$param>)?),
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            PipeWire(pipewire::$ty $(<$param>)?),
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            PulseAudio(pulseaudio::$ty $(<$param>)?),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            Wasapi(wasapi::$ty $(<$param>)?),
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+            WebAudio(webaudio::$ty $(<$param>)?),
+        }
+    };
+}
+
+backend_enum!(HostImpl, Host);
+backend_enum!(DeviceImpl, Device);
+backend_enum!(StreamImpl<Frame>, Stream);
+
+/// Evaluates `$body` with `$inner` bound to the backend type held by `$value`. With `map`, wraps
+/// the result's value in the `$target` enum's variant of the same backend.
+macro_rules! dispatch {
+    ($value:expr, $enum:ident, $inner:ident => $body:expr $(, map $target:ident)?) => {
+        match $value {
+            #[cfg(all(feature = "portaudio", not(any(target_os = "android", target_arch = "wasm32"))))]
+            $enum::Portaudio($inner) => dispatch!(@wrap $body $(, $target::Portaudio)?),
+            #[cfg(all(target_os = "android", feature = "aaudio"))]
+            $enum::AAudio($inner) => dispatch!(@wrap $body $(, $target::AAudio)?),
+            #[cfg(all(target_os = "linux", feature = "alsa"))]
+            $enum::Alsa($inner) => dispatch!(@wrap $body $(, $target::Alsa)?),
+            #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+            $enum::CoreAudio($inner) => dispatch!(@wrap $body $(, $target::CoreAudio)?),
+            #[cfg(feature = "jack")]
+            $enum::Jack($inner) => dispatch!(@wrap $body $(, $target::Jack)?),
+            $enum::Null($inner) => dispatch!(@wrap $body $(, $target::Null)?),
+            #[cfg(all(target_os = "android", feature = "opensles"))]
+            $enum::OpenSles($inner) => dispatch!(@wrap $body $(, $target::OpenSles)?),
+            #[cfg(all(target_os = "linux", feature = "pipewire"))]
+            $enum::PipeWire($inner) => dispatch!(@wrap $body $(, $target::PipeWire)?),
+            #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+            $enum::PulseAudio($inner) => dispatch!(@wrap $body $(, $target::PulseAudio)?),
+            #[cfg(all(target_os = "windows", feature = "wasapi"))]
+            $enum::Wasapi($inner) => dispatch!(@wrap $body $(, $target::Wasapi)?),
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+            $enum::WebAudio($inner) => dispatch!(@wrap $body $(, $target::WebAudio)?),
+        }
+    };
+    (@wrap $body:expr) => {
+        $body
+    };
+    (@wrap $body:expr, $variant:path) => {
+        ($body).map($variant)
+    };
+}
+use dispatch;
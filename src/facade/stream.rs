@@ -0,0 +1,404 @@
+use crate::error::{CallbackError, Error, Result};
+use crate::facade::{dispatch, StreamImpl};
+use crate::stream_options::{CallbackMetrics, ClockCorrelation, StreamInfo, StreamStats};
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+use crate::stream_options::{StopMode, StreamState};
+use crate::trace;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// A stream represents the flow of data in and out of an audio device. It's defined by its audio
+/// data format, the number of channels, and whether it is an input stream (e.g. a microphone) or
+/// an output stream (e.g. speakers).
+pub struct Stream<Frame>(pub(super) StreamImpl<Frame>);
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        trace::transition(
+            "start",
+            dispatch!(&mut self.0, StreamImpl, stream => stream.start()),
+        )
+    }
+
+    pub fn close(self) {
+        dispatch!(self.0, StreamImpl, stream => stream.close());
+        trace::closed();
+    }
+
+    /// Stops calling the stream's callback, and returns once the frames it already produced (or
+    /// that were written to it) are played: e.g. when shutting down, so that the end of the
+    /// playback isn't cut off, as it is by dropping the stream. Portaudio streams can be started
+    /// again.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio, ALSA or null stream.
+    pub fn drain(&mut self) -> Result<()> {
+        trace::transition(
+            "drain",
+            match &mut self.0 {
+                #[cfg(all(
+                    feature = "portaudio",
+                    not(any(target_os = "android", target_arch = "wasm32"))
+                ))]
+                StreamImpl::Portaudio(stream) => stream.drain(),
+                #[cfg(all(target_os = "linux", feature = "alsa"))]
+                StreamImpl::Alsa(stream) => stream.drain(),
+                StreamImpl::Null(stream) => stream.drain(),
+                _ => Err(Error::IncompatibleStreamMode),
+            },
+        )
+    }
+
+    /// How many frames the callback gets at a time, as negotiated when the stream was opened.
+    /// Streams without a [`frames_per_buffer`](crate::StreamOptions::frames_per_buffer) get
+    /// whatever their backend delivers, which may vary between callbacks, and some backends can't
+    /// deliver fixed sizes at all: Callbacks should handle any buffer length within the bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// # let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// # let stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+    /// let info = stream.info();
+    /// if let Some(max) = info.max_frames_per_buffer {
+    ///     println!("Preallocating scratch space for {} frames", max);
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn info(&self) -> StreamInfo {
+        dispatch!(&self.0, StreamImpl, stream => stream.info())
+    }
+
+    /// How long captured frames take to reach the callback (or a blocking read), as negotiated
+    /// when the stream was opened: e.g. to compensate for it. `None` for streams without input,
+    /// and for backends that don't report it.
+    pub fn input_latency(&self) -> Option<Duration> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => stream.input_latency(),
+            _ => None,
+        }
+    }
+
+    /// How long the callback's frames (or a blocking write's) take to be played, as negotiated
+    /// when the stream was opened. `None` for streams without output, and for backends that don't
+    /// report it.
+    pub fn output_latency(&self) -> Option<Duration> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => stream.output_latency(),
+            _ => None,
+        }
+    }
+
+    /// The stream's clock, which only goes forward. It's the clock of the
+    /// [`CallbackInfo`](crate::CallbackInfo) times, so that events can be scheduled at precise
+    /// times: e.g. at a buffer's playback time. `None` for backends without one.
+    pub fn time(&self) -> Option<Duration> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => Some(stream.time()),
+            _ => None,
+        }
+    }
+
+    /// Reads the stream's [`time`](Stream::time), and when it was read, to translate between
+    /// stream and wall-clock times: e.g. for A/V sync. The drift between the clocks is estimated
+    /// since the stream's first correlation, so later correlations are more accurate. `None` for
+    /// backends without a stream clock.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use audiohal::*;
+    /// # let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// # let stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+    /// if let Some(correlation) = stream.clock_correlation() {
+    ///     // A second later, on the stream's clock.
+    ///     let stream_time = correlation.stream_time + std::time::Duration::from_secs(1);
+    ///     println!("That's at {:?}", correlation.to_instant(stream_time));
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn clock_correlation(&self) -> Option<ClockCorrelation> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => Some(stream.clock_correlation()),
+            _ => None,
+        }
+    }
+
+    /// The glitches the stream had since it was opened: underflows, overflows, and the frames they
+    /// affected. Lets long-running applications monitor their audio. `None` for backends that
+    /// don't report glitches.
+    pub fn stats(&self) -> Option<StreamStats> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => Some(stream.stats()),
+            _ => None,
+        }
+    }
+
+    /// How long the stream's callbacks took, and how far apart they came, since it was opened:
+    /// e.g. to alert when the audio thread overruns, before users hear it glitch. Counted without
+    /// locking, so reading them doesn't hold up the callback. `None` for backends that don't
+    /// measure them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// # use std::time::Duration;
+    /// # let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// # let stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+    /// if let Some(metrics) = stream.metrics() {
+    ///     if metrics.overruns > 0 || metrics.durations.quantile(0.99) > Some(Duration::from_millis(4)) {
+    ///         println!("The audio thread is falling behind: {:?}", metrics);
+    ///     }
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn metrics(&self) -> Option<CallbackMetrics> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => Some(stream.metrics()),
+            _ => None,
+        }
+    }
+
+    /// The fraction of real time spent in the stream's callback, averaged over its last buffers:
+    /// e.g. to warn users when their processing nears the deadline, past which it glitches. Always
+    /// 0 for blocking streams, and `None` for backends that don't measure it.
+    pub fn cpu_load(&self) -> Option<f64> {
+        match &self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => Some(stream.cpu_load()),
+            _ => None,
+        }
+    }
+
+    /// Takes the receiver of the errors from the stream's callback: the ones it reports with
+    /// [`report_callback_error`](crate::report_callback_error), and its panics, which abort the
+    /// stream. Only Portaudio callback streams have one: Others return `None`, as do streams
+    /// whose receiver was already taken.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let mut stream = device.open_outstream(StreamOptions {
+    ///     callback: Box::new(|buffer: &mut [[f32; 2]]| {
+    ///         # buffer;
+    ///         report_callback_error("Ran out of samples.");
+    ///     }),
+    ///     ..Default::default()
+    /// })?;
+    /// let errors = stream.take_error_receiver();
+    /// # errors;
+    /// # Result::Ok(())
+    /// ```
+    pub fn take_error_receiver(&mut self) -> Option<Receiver<CallbackError>> {
+        match &mut self.0 {
+            #[cfg(all(
+                feature = "portaudio",
+                not(any(target_os = "android", target_arch = "wasm32"))
+            ))]
+            StreamImpl::Portaudio(stream) => stream.take_error_receiver(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+impl<Frame> Stream<Frame> {
+    /// Writes frames to a blocking output stream. Blocks until all frames have been written.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking output stream.
+    pub fn write(&mut self, frames: &[Frame]) -> Result<()> {
+        match &mut self.0 {
+            StreamImpl::Portaudio(stream) => stream.write(frames),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Starts a stopped blocking output stream with the first of `frames` already written, as many
+    /// as its buffer takes without blocking, so that it doesn't underrun (or start with silence)
+    /// before the next write. Returns how many it wrote, which the next write goes on from.
+    /// Callback streams prime their buffers with
+    /// [`StreamOptions::prime_output`](crate::StreamOptions::prime_output) instead.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking output stream.
+    pub fn prime(&mut self, frames: &[Frame]) -> Result<usize> {
+        match &mut self.0 {
+            StreamImpl::Portaudio(stream) => stream.prime(frames),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Reads frames from a blocking input stream. Blocks until the whole buffer has been filled.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking input stream.
+    pub fn read(&mut self, frames: &mut [Frame]) -> Result<()> {
+        match &mut self.0 {
+            StreamImpl::Portaudio(stream) => stream.read(frames),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Whether the stream is calling its callback, or has buffered frames left to play.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio stream.
+    pub fn is_active(&self) -> Result<bool> {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.is_active(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Whether the stream was never started, or was stopped (or aborted).
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio stream.
+    pub fn is_stopped(&self) -> Result<bool> {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.is_stopped(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Where the stream is in its lifecycle, e.g. for supervising code to restart streams whose
+    /// callback ended them.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio stream.
+    pub fn state(&self) -> Result<StreamState> {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.state(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// Stops the stream once the frames that were already buffered are played. The stream can be
+    /// started again. Does nothing if the stream is already stopped.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio stream.
+    pub fn stop(&mut self) -> Result<()> {
+        trace::transition(
+            "stop",
+            match &mut self.0 {
+                StreamImpl::Portaudio(stream) => stream.stop(),
+                _ => Err(Error::IncompatibleStreamMode),
+            },
+        )
+    }
+
+    /// Stops the stream as soon as possible, dropping the frames that weren't played yet.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a Portaudio stream.
+    pub fn abort(&mut self) -> Result<()> {
+        trace::transition(
+            "abort",
+            match &mut self.0 {
+                StreamImpl::Portaudio(stream) => stream.abort(),
+                _ => Err(Error::IncompatibleStreamMode),
+            },
+        )
+    }
+
+    /// Sets how the stream stops when it's dropped: By default, it aborts. Other backends' streams
+    /// always stop the way their backend does.
+    pub fn set_drop_mode(&mut self, mode: StopMode) {
+        if let StreamImpl::Portaudio(stream) = &mut self.0 {
+            stream.set_drop_mode(mode)
+        }
+    }
+
+    /// Stops calling the stream's callback, without stopping the stream: The device stays open,
+    /// and output streams fade out over a buffer, then play silence.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is a blocking stream, or not a Portaudio stream.
+    pub fn pause(&mut self) -> Result<()> {
+        trace::transition(
+            "pause",
+            match &mut self.0 {
+                StreamImpl::Portaudio(stream) => stream.pause(),
+                _ => Err(Error::IncompatibleStreamMode),
+            },
+        )
+    }
+
+    /// Resumes a paused stream. Output streams fade back in over a buffer.
+    pub fn resume(&mut self) -> Result<()> {
+        trace::transition(
+            "resume",
+            match &mut self.0 {
+                StreamImpl::Portaudio(stream) => stream.resume(),
+                _ => Err(Error::IncompatibleStreamMode),
+            },
+        )
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.is_paused(),
+            _ => false,
+        }
+    }
+
+    /// Scales what the stream plays by `volume`, without the callback having to: 1 (the default)
+    /// leaves it as it is, and 0 mutes it. Changes ramp over a buffer, so that they don't click.
+    ///
+    /// Returns [`Error::Invalid`](crate::Error::Invalid) for negative volumes, and
+    /// [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the stream is
+    /// an input or blocking stream, or not a Portaudio stream.
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.set_volume(volume),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+
+    /// The stream's [volume](Stream::set_volume).
+    pub fn volume(&self) -> Result<f32> {
+        match &self.0 {
+            StreamImpl::Portaudio(stream) => stream.volume(),
+            _ => Err(Error::IncompatibleStreamMode),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::{Error, Result};
+use crate::jack::stream::{self, Stream};
+use crate::stream_options::{Input, StreamOptions};
+
+/// A group of physical JACK ports belonging to the same JACK client.
+pub struct Device {
+    name: String,
+    ports: Vec<String>,
+    is_output: bool,
+}
+
+impl Device {
+    pub(super) fn new(name: String, ports: Vec<String>, is_output: bool) -> Device {
+        Device {
+            name,
+            ports,
+            is_output,
+        }
+    }
+
+    /// The name of the JACK client owning the ports (e.g. "system").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The full names of the device's ports (e.g. "system:playback_1"), one per channel.
+    pub fn ports(&self) -> &[String] {
+        &self.ports
+    }
+
+    /// The number of channels, i.e. ports, of the device.
+    pub fn n_channels(&self) -> i32 {
+        self.ports.len() as i32
+    }
+
+    /// Creates an output stream. Its channels are connected to the device's ports in order.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. Its channels are connected to the device's ports in order.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+}
@@ -0,0 +1,69 @@
+//! Hand-written bindings to the subset of libjack used by the backend.
+#![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+
+use std::os::raw::{c_char, c_int, c_ulong, c_void};
+
+pub enum jack_client_t {}
+pub enum jack_port_t {}
+
+pub type jack_nframes_t = u32;
+pub type JackProcessCallback = extern "C" fn(nframes: jack_nframes_t, arg: *mut c_void) -> c_int;
+pub type JackBufferSizeCallback = extern "C" fn(nframes: jack_nframes_t, arg: *mut c_void) -> c_int;
+
+pub const JACK_DEFAULT_AUDIO_TYPE: &[u8] = b"32 bit float mono audio\0";
+
+pub const JackNoStartServer: c_int = 0x01;
+
+pub const JackPortIsInput: c_ulong = 0x1;
+pub const JackPortIsOutput: c_ulong = 0x2;
+pub const JackPortIsPhysical: c_ulong = 0x4;
+
+pub const EEXIST: c_int = 17;
+
+#[link(name = "jack")]
+extern "C" {
+    pub fn jack_client_open(
+        client_name: *const c_char,
+        options: c_int,
+        status: *mut c_int,
+        ...
+    ) -> *mut jack_client_t;
+    pub fn jack_client_close(client: *mut jack_client_t) -> c_int;
+    pub fn jack_activate(client: *mut jack_client_t) -> c_int;
+    pub fn jack_deactivate(client: *mut jack_client_t) -> c_int;
+    pub fn jack_get_sample_rate(client: *mut jack_client_t) -> jack_nframes_t;
+    pub fn jack_get_buffer_size(client: *mut jack_client_t) -> jack_nframes_t;
+    pub fn jack_set_process_callback(
+        client: *mut jack_client_t,
+        process_callback: JackProcessCallback,
+        arg: *mut c_void,
+    ) -> c_int;
+    pub fn jack_set_buffer_size_callback(
+        client: *mut jack_client_t,
+        bufsize_callback: JackBufferSizeCallback,
+        arg: *mut c_void,
+    ) -> c_int;
+
+    pub fn jack_port_register(
+        client: *mut jack_client_t,
+        port_name: *const c_char,
+        port_type: *const c_char,
+        flags: c_ulong,
+        buffer_size: c_ulong,
+    ) -> *mut jack_port_t;
+    pub fn jack_port_name(port: *const jack_port_t) -> *const c_char;
+    pub fn jack_port_get_buffer(port: *mut jack_port_t, nframes: jack_nframes_t) -> *mut c_void;
+    pub fn jack_get_ports(
+        client: *mut jack_client_t,
+        port_name_pattern: *const c_char,
+        type_name_pattern: *const c_char,
+        flags: c_ulong,
+    ) -> *mut *const c_char;
+    pub fn jack_connect(
+        client: *mut jack_client_t,
+        source_port: *const c_char,
+        destination_port: *const c_char,
+    ) -> c_int;
+    pub fn jack_free(ptr: *mut c_void);
+}
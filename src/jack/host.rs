@@ -0,0 +1,98 @@
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::jack::device::Device;
+use crate::jack::{ffi, group_by_client, to_string, Client};
+
+/// The JACK host. Holds a client connection to the running JACK server, used to look up ports.
+pub struct Host {
+    client: Client,
+}
+
+impl Host {
+    /// Connects to the running JACK server. Returns [`Error::BackendUnavailable`] if there is
+    /// none.
+    pub fn new() -> Result<Host> {
+        Ok(Host {
+            client: Client::open()?,
+        })
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "JACK"
+    }
+
+    /// Returns the first group of physical playback ports (usually "system").
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        self.output_devices()?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoSuchDevice)
+    }
+
+    /// Returns the first group of physical capture ports (usually "system").
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        self.input_devices()?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoSuchDevice)
+    }
+
+    /// Lists the physical playback ports, grouped by their JACK client.
+    pub fn output_devices(&mut self) -> Result<Vec<Device>> {
+        self.devices(true)
+    }
+
+    /// Lists the physical capture ports, grouped by their JACK client.
+    pub fn input_devices(&mut self) -> Result<Vec<Device>> {
+        self.devices(false)
+    }
+
+    fn devices(&mut self, is_output: bool) -> Result<Vec<Device>> {
+        // Playback ports are inputs from JACK's point of view, and capture ports are outputs.
+        let direction = if is_output {
+            ffi::JackPortIsInput
+        } else {
+            ffi::JackPortIsOutput
+        };
+        let ports = unsafe {
+            ffi::jack_get_ports(
+                self.client.0,
+                std::ptr::null(),
+                ffi::JACK_DEFAULT_AUDIO_TYPE.as_ptr() as *const _,
+                ffi::JackPortIsPhysical | direction,
+            )
+        };
+        if ports.is_null() {
+            return Ok(Vec::new());
+        }
+        let mut port_names = Vec::new();
+        for i in 0.. {
+            let port = unsafe { *ports.add(i) };
+            if port.is_null() {
+                break;
+            }
+            port_names.push(to_string(port));
+        }
+        unsafe { ffi::jack_free(ports as *mut c_void) };
+        Ok(group_by_client(port_names)
+            .into_iter()
+            .map(|(name, ports)| Device::new(name, ports, is_output))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_system_ports() -> Result<()> {
+        let mut host = Host::new()?;
+        let device = host.default_output_device()?;
+        println!("Default output is {}", device.name());
+        assert_gt!(device.n_channels(), 0);
+        Ok(())
+    }
+}
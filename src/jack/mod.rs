@@ -0,0 +1,103 @@
+//! JACK backend for Linux pro-audio setups.
+//!
+//! Each stream registers its own JACK client, with one port per channel, and connects them to the
+//! device's ports when started. Devices are groups of physical JACK ports, one per JACK client
+//! (e.g. "system"). Exposes the same [`Host`]/[`Device`]/[`Stream`] surface as the crate's default
+//! backend.
+//!
+//! JACK only carries 32-bit float samples at the server's sample rate and buffer size, so streams
+//! must use [`Format::F32`](crate::Format::F32), and cannot request a different rate or buffer
+//! size.
+//!
+//! Stream callbacks run on JACK's real-time thread: They must not block, lock, or allocate.
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// The name clients register with. JACK appends a suffix if it is already taken.
+const CLIENT_NAME: &str = "audiohal";
+
+/// The error of a JACK call that failed with the nonzero `code`.
+fn error(code: c_int, message: &'static str) -> Error {
+    Error::Backend(BackendError::new(
+        Some(Backend::Jack),
+        ErrorCode::Errno(code),
+        message,
+    ))
+}
+
+/// An open JACK client. Closed when dropped.
+struct Client(*mut ffi::jack_client_t);
+
+// JACK's client API is thread-safe.
+unsafe impl Send for Client {}
+unsafe impl Sync for Client {}
+
+impl Client {
+    /// Connects to a running JACK server. Does not start one.
+    fn open() -> Result<Client> {
+        let name = CString::new(CLIENT_NAME).unwrap();
+        let mut status = 0;
+        let client =
+            unsafe { ffi::jack_client_open(name.as_ptr(), ffi::JackNoStartServer, &mut status) };
+        if client.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(Client(client))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        unsafe { ffi::jack_client_close(self.0) };
+    }
+}
+
+/// Copies a C string returned by JACK.
+fn to_string(ptr: *const c_char) -> String {
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Splits JACK's "client:port" names into groups of ports sharing a client name, in order.
+fn group_by_client(port_names: Vec<String>) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for port_name in port_names {
+        let client = port_name.split(':').next().unwrap_or_default().to_owned();
+        match groups.iter_mut().find(|(name, _)| *name == client) {
+            Some((_, ports)) => ports.push(port_name),
+            None => groups.push((client, vec![port_name])),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_ports_by_client() {
+        let ports = ["system:playback_1", "hdmi:playback_1", "system:playback_2"];
+        let groups = group_by_client(ports.iter().map(|port| port.to_string()).collect());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "system");
+        assert_eq!(groups[0].1, ["system:playback_1", "system:playback_2"]);
+        assert_eq!(groups[1].1, ["hdmi:playback_1"]);
+    }
+}
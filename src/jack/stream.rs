@@ -0,0 +1,327 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+
+use crate::convert;
+use crate::error::{Error, Result};
+use crate::jack::device::Device;
+use crate::jack::{error, ffi, to_string, Client};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// A stream backed by its own JACK client.
+pub struct Stream<Frame> {
+    // Declared before the state, so that the client is closed (and the process callback stopped)
+    // before the state it points to is dropped.
+    client: Client,
+    // (source, destination) port pairs to connect once activated.
+    connections: Vec<(CString, CString)>,
+    is_started: bool,
+    _state: Box<dyn Send>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    /// Activates the stream's client and connects its ports to the device's.
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        match unsafe { ffi::jack_activate(self.client.0) } {
+            0 => (),
+            code => return Err(error(code, "Could not activate the JACK client.")),
+        }
+        self.is_started = true;
+        for (source, destination) in &self.connections {
+            match unsafe { ffi::jack_connect(self.client.0, source.as_ptr(), destination.as_ptr()) }
+            {
+                0 | ffi::EEXIST => (),
+                code => return Err(error(code, "Could not connect JACK ports.")),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// The callback gets the server's buffer size every time, until the server changes it.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::fixed(unsafe { ffi::jack_get_buffer_size(self.client.0) } as usize)
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        if self.is_started {
+            unsafe { ffi::jack_deactivate(self.client.0) };
+        }
+    }
+}
+
+/// A port registered by a stream's client.
+struct Port(*mut ffi::jack_port_t);
+
+unsafe impl Send for Port {}
+
+/// What the process callback works with. Interleaves (or deinterleaves) the client's
+/// non-interleaved port buffers through `scratch`, which always holds a whole JACK buffer.
+struct ProcessState<C, Channel> {
+    ports: Vec<Port>,
+    // Stored as u64s so that frames of any alignment can be viewed in it.
+    scratch: Vec<u64>,
+    /// The ports' buffers. Only valid during a callback: Filled before (de)interleaving, and
+    /// cleared after. Allocated up front so that the callback doesn't allocate.
+    channels: Vec<Channel>,
+    callback: C,
+}
+
+type OutputState<Frame> = ProcessState<Callback<Frame>, &'static mut [f32]>;
+type InputState<Frame> = ProcessState<InputCallback<Frame>, &'static [f32]>;
+
+impl<C, Channel> ProcessState<C, Channel> {
+    fn new(ports: Vec<Port>, callback: C) -> ProcessState<C, Channel> {
+        ProcessState {
+            channels: Vec::with_capacity(ports.len()),
+            ports,
+            scratch: Vec::new(),
+            callback,
+        }
+    }
+
+    fn resize(&mut self, frame_count: usize) {
+        let n_samples = frame_count * self.ports.len();
+        self.scratch.resize(n_samples.div_ceil(2), 0);
+    }
+
+    fn samples(&mut self, frame_count: usize) -> &mut [f32] {
+        let n_samples = (frame_count * self.ports.len()).min(self.scratch.len() * 2);
+        unsafe { std::slice::from_raw_parts_mut(self.scratch.as_mut_ptr() as *mut f32, n_samples) }
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let client = Client::open()?;
+    validate_options(&client, device, &options)?;
+    let ports = register_ports(&client, options.n_channels, "out", ffi::JackPortIsOutput)?;
+    let connections = ports
+        .iter()
+        .zip(device.ports())
+        .map(|(port, device_port)| connection(port, device_port))
+        .collect();
+    let mut state = Box::new(OutputState::new(ports, options.callback));
+    state.resize(unsafe { ffi::jack_get_buffer_size(client.0) } as usize);
+    let arg = &mut *state as *mut OutputState<Frame> as *mut c_void;
+    unsafe {
+        ffi::jack_set_process_callback(client.0, outstream_process::<Frame>, arg);
+        ffi::jack_set_buffer_size_callback(
+            client.0,
+            buffer_size_changed::<Callback<Frame>, &mut [f32]>,
+            arg,
+        );
+    }
+    Ok(new_stream(client, connections, state))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let client = Client::open()?;
+    validate_options(&client, device, &options)?;
+    let ports = register_ports(&client, options.n_channels, "in", ffi::JackPortIsInput)?;
+    let connections = ports
+        .iter()
+        .zip(device.ports())
+        .map(|(port, device_port)| {
+            let (ours, theirs) = connection(port, device_port);
+            (theirs, ours)
+        })
+        .collect();
+    let mut state = Box::new(InputState::new(ports, options.callback));
+    state.resize(unsafe { ffi::jack_get_buffer_size(client.0) } as usize);
+    let arg = &mut *state as *mut InputState<Frame> as *mut c_void;
+    unsafe {
+        ffi::jack_set_process_callback(client.0, instream_process::<Frame>, arg);
+        ffi::jack_set_buffer_size_callback(
+            client.0,
+            buffer_size_changed::<InputCallback<Frame>, &[f32]>,
+            arg,
+        );
+    }
+    Ok(new_stream(client, connections, state))
+}
+
+fn new_stream<Frame, State: Send + 'static>(
+    client: Client,
+    connections: Vec<(CString, CString)>,
+    state: Box<State>,
+) -> Stream<Frame> {
+    Stream {
+        client,
+        connections,
+        is_started: false,
+        _state: state,
+        _frame: PhantomData,
+    }
+}
+
+fn validate_options<Frame, Kind: CallbackKind>(
+    client: &Client,
+    device: &Device,
+    options: &StreamOptions<Frame, Kind>,
+) -> Result<()> {
+    if options.format != Format::F32 {
+        return Err(Error::IncompatibleFormat(options.format));
+    }
+    if options.n_channels <= 0 || options.n_channels > device.n_channels() {
+        return Err(Error::IncompatibleNChannels);
+    }
+    let server_rate = unsafe { ffi::jack_get_sample_rate(client.0) } as i32;
+    match options.sample_rate {
+        SampleRate::Exact(rate) if rate != server_rate => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::NearestTo(rate) if rate <= 0 => return Err(Error::IncompatibleSampleRate),
+        _ => (),
+    }
+    let server_buffer_size = unsafe { ffi::jack_get_buffer_size(client.0) } as i32;
+    if !options.frames_per_buffer.accepts(server_buffer_size) {
+        return Err(Error::InvalidFramesPerBuffer);
+    }
+    options.validate_frame_size()
+}
+
+fn register_ports(
+    client: &Client,
+    n_channels: i32,
+    prefix: &str,
+    flags: std::os::raw::c_ulong,
+) -> Result<Vec<Port>> {
+    (1..=n_channels)
+        .map(|channel| {
+            let name = CString::new(format!("{}_{}", prefix, channel)).unwrap();
+            let port = unsafe {
+                ffi::jack_port_register(
+                    client.0,
+                    name.as_ptr(),
+                    ffi::JACK_DEFAULT_AUDIO_TYPE.as_ptr() as *const _,
+                    flags,
+                    0,
+                )
+            };
+            if port.is_null() {
+                Err(Error::Unknown("Could not register a JACK port."))
+            } else {
+                Ok(Port(port))
+            }
+        })
+        .collect()
+}
+
+/// Returns the full names of a stream port and the device port it should be connected to.
+fn connection(port: &Port, device_port: &str) -> (CString, CString) {
+    let own_name = to_string(unsafe { ffi::jack_port_name(port.0) });
+    (
+        CString::new(own_name).unwrap(),
+        CString::new(device_port).unwrap(),
+    )
+}
+
+/// Called by JACK (never concurrently with the process callback) when the server's buffer size
+/// changes. Not real-time, so it may allocate.
+extern "C" fn buffer_size_changed<C, Channel>(
+    frame_count: ffi::jack_nframes_t,
+    arg: *mut c_void,
+) -> c_int {
+    let state = unsafe { (arg as *mut ProcessState<C, Channel>).as_mut() }
+        .expect("Could not get ProcessState from arg.");
+    state.resize(frame_count as usize);
+    0
+}
+
+extern "C" fn outstream_process<Frame>(
+    frame_count: ffi::jack_nframes_t,
+    arg: *mut c_void,
+) -> c_int {
+    let state = unsafe { (arg as *mut OutputState<Frame>).as_mut() }
+        .expect("Could not get ProcessState from arg.");
+    let n_channels = state.ports.len();
+    let samples = state.samples(frame_count as usize);
+    let frame_count = samples.len() / n_channels;
+    let frames =
+        unsafe { std::slice::from_raw_parts_mut(samples.as_mut_ptr() as *mut Frame, frame_count) };
+    (state.callback)(frames);
+
+    for port in &state.ports {
+        state.channels.push(unsafe {
+            std::slice::from_raw_parts_mut(
+                ffi::jack_port_get_buffer(port.0, frame_count as ffi::jack_nframes_t) as *mut f32,
+                frame_count,
+            )
+        });
+    }
+    let samples = unsafe {
+        std::slice::from_raw_parts(
+            state.scratch.as_ptr() as *const f32,
+            frame_count * n_channels,
+        )
+    };
+    convert::deinterleave(samples, &mut state.channels);
+    state.channels.clear();
+    0
+}
+
+extern "C" fn instream_process<Frame>(frame_count: ffi::jack_nframes_t, arg: *mut c_void) -> c_int {
+    let state = unsafe { (arg as *mut InputState<Frame>).as_mut() }
+        .expect("Could not get ProcessState from arg.");
+    let n_channels = state.ports.len();
+    let frame_count = state.samples(frame_count as usize).len() / n_channels;
+    for port in &state.ports {
+        state.channels.push(unsafe {
+            std::slice::from_raw_parts(
+                ffi::jack_port_get_buffer(port.0, frame_count as ffi::jack_nframes_t) as *const f32,
+                frame_count,
+            )
+        });
+    }
+    let samples = unsafe {
+        std::slice::from_raw_parts_mut(
+            state.scratch.as_mut_ptr() as *mut f32,
+            frame_count * n_channels,
+        )
+    };
+    convert::interleave(&state.channels, samples);
+    state.channels.clear();
+    let frames =
+        unsafe { std::slice::from_raw_parts(state.scratch.as_ptr() as *const Frame, frame_count) };
+    (state.callback)(frames);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jack::Host;
+
+    #[test]
+    fn errors_if_format_is_not_f32() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let result = device.open_outstream(StreamOptions::<[i16; 2]>::default());
+        assert_eq!(result.err(), Some(Error::IncompatibleFormat(Format::I16)));
+        Ok(())
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 1]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
@@ -7,22 +7,140 @@
 
 #[macro_use]
 extern crate more_asserts;
-#[cfg(test)]
+// Only used by the Portaudio backend's tests.
+#[cfg(all(
+    test,
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
 #[macro_use]
 extern crate galvanic_assert;
 
+mod aggregate;
 mod backend;
+mod buffered;
+mod capabilities;
 mod error;
+mod facade;
+mod options_builder;
+// Only the backends that run their own callback threads promote them.
+#[cfg(any(
+    all(
+        feature = "portaudio",
+        not(any(target_os = "android", target_arch = "wasm32"))
+    ),
+    all(target_os = "linux", any(feature = "alsa", feature = "pulseaudio")),
+    all(target_os = "windows", feature = "wasapi")
+))]
+mod priority;
+#[cfg(feature = "hound")]
+mod recorder;
 mod stream_options;
+mod surround;
+mod trace;
+mod traits;
 
+// The stream adapters are built on the root Host/Device/Stream. Their tests need Portaudio.
+#[cfg(all(
+    feature = "tokio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+mod async_io;
+#[cfg(all(
+    feature = "futures",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+mod async_stream;
+
+// Every backend is behind its own feature, and only built on the platforms it supports. The
+// facade dispatches to whichever ones are. Portaudio doesn't support Android or the browser,
+// where native backends are the default.
+#[cfg(all(target_os = "android", feature = "aaudio"))]
+mod android;
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
 mod portaudio;
 
+#[cfg(all(target_os = "android", feature = "aaudio"))]
+pub mod aaudio;
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+pub mod alsa;
+pub mod convert;
+#[cfg(all(target_os = "macos", feature = "coreaudio"))]
+pub mod coreaudio;
+#[cfg(feature = "cpal")]
+pub mod cpal;
+#[cfg(feature = "jack")]
+pub mod jack;
+pub mod linux;
+pub mod macos;
+pub mod null;
+#[cfg(all(target_os = "android", feature = "opensles"))]
+pub mod opensles;
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+pub mod pipewire;
+#[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+pub mod pulseaudio;
+pub mod ring;
+#[cfg(feature = "rodio")]
+pub mod rodio;
+#[cfg(feature = "rt-check")]
+pub mod rt;
+pub mod signal;
+#[cfg(feature = "symphonia")]
+pub mod symphonia;
+#[cfg(all(target_os = "windows", feature = "wasapi"))]
+pub mod wasapi;
+#[cfg(feature = "wav")]
+pub mod wav;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "webaudio"))]
+pub mod webaudio;
+pub mod windows;
+
 // Exporting public types.
-pub use backend::Backend;
-pub use error::{Error, Result};
-pub use stream_options::{Callback, Format, SampleRate, StreamOptions};
+pub use aggregate::AggregateInput;
+pub use backend::{Backend, HostApi};
+pub use buffered::{InputReader, OutputWriter};
+pub use capabilities::DeviceCapabilities;
+pub use error::{
+    report_callback_error, BackendError, CallbackError, Error, ErrorCategory, ErrorCode, Result,
+};
+pub use options_builder::StreamOptionsBuilder;
+#[cfg(feature = "hound")]
+pub use recorder::{record_to_wav, WavRecorder};
+pub use stream_options::{
+    BufferSize, Callback, CallbackInfo, CallbackKind, CallbackMetrics, ChannelMap,
+    ChannelMixPolicy, ChannelSelection, ClipPolicy, ClockCorrelation, DitherMode, DuplexCallback,
+    DuplexInfoCallback, DynamicCallback, DynamicInput, DynamicInputCallback, DynamicOutput, Format,
+    Histogram, InfoCallback, InfoInputCallback, Input, InputCallback, InputWithInfo, LatencyHint,
+    NoCallback, Output, OutputWithInfo, PlanarCallback, PlanarInput, PlanarInputCallback,
+    PlanarOutput, RawBuffer, RawCallback, RawInput, RawInputBuffer, RawInputCallback, RawOutput,
+    ReconnectPolicy, ResamplerQuality, SampleRate, StopMode, StreamConfig, StreamFlow, StreamInfo,
+    StreamOptions, StreamState, StreamStats, StreamStatus,
+};
+pub use surround::{ChannelMask, ChannelPosition};
+pub use traits::{DeviceTrait, HostTrait, StreamTrait};
+
+#[cfg(all(
+    feature = "tokio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+pub use async_io::{AsyncInputReader, AsyncOutputWriter};
+#[cfg(all(
+    feature = "futures",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+pub use async_stream::{InputSource, OutputSink};
 
 // Exporting backend types.
-pub use portaudio::Device;
-pub use portaudio::Host;
-pub use portaudio::Stream;
+#[cfg(all(windows, feature = "asio"))]
+pub use portaudio::AsioBufferSizes;
+
+pub use facade::{Device, DeviceEvent, DeviceId, Host, Stream, StreamGroup};
+#[cfg(all(
+    feature = "portaudio",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+pub use facade::{DynBuffer, DynCallback, DynInputBuffer, DynInputCallback, DynStream};
@@ -0,0 +1,69 @@
+//! Settings of ALSA streams, natively or through Portaudio, which streams on other backends
+//! ignore. See [`AlsaOptions`].
+#[cfg(all(target_os = "linux", any(feature = "alsa", feature = "portaudio")))]
+use std::convert::TryFrom;
+
+#[cfg(all(target_os = "linux", any(feature = "alsa", feature = "portaudio")))]
+use crate::error::{Error, Result};
+#[cfg(all(target_os = "linux", any(feature = "alsa", feature = "portaudio")))]
+use crate::stream_options::BufferSize;
+
+/// How a stream sizes and schedules its ALSA buffer, attached to its
+/// [`StreamOptions::alsa`](crate::StreamOptions). ALSA's default buffers are sized for throughput:
+/// Streams with small periods may need more of them, or real-time scheduling, to keep up without
+/// xruns.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// # use audiohal::linux::AlsaOptions;
+/// let options = StreamOptions::<[f32; 2]> {
+///     // Three periods of 64 frames, on a SCHED_FIFO thread.
+///     alsa: Some(AlsaOptions {
+///         periods: Some(3),
+///         period_size: Some(64),
+///         realtime_scheduling: true,
+///     }),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlsaOptions {
+    /// How many periods the stream's buffer has, at least 2. More periods trade latency for
+    /// fewer xruns. `None`, for the device's periods (see `alsa::Device::set_periods`) on native
+    /// streams and Portaudio's 4 on Portaudio's, by default.
+    pub periods: Option<u32>,
+    /// How many frames each period has, i.e. how many the callback gets at a time. Takes
+    /// precedence over [`frames_per_buffer`](crate::StreamOptions::frames_per_buffer). `None` by
+    /// default.
+    pub period_size: Option<u32>,
+    /// Whether the stream's thread is scheduled with `SCHED_FIFO`, even if the stream doesn't ask
+    /// for [real-time priority](crate::StreamOptions::realtime_priority): Native streams promote
+    /// their thread, and Portaudio's ALSA host API schedules its own. `false` by default.
+    pub realtime_scheduling: bool,
+}
+
+#[cfg(all(target_os = "linux", any(feature = "alsa", feature = "portaudio")))]
+impl AlsaOptions {
+    /// Rejects buffers of fewer than two periods, and empty periods.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.periods.is_some_and(|periods| periods < 2) || self.period_size == Some(0) {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        Ok(())
+    }
+
+    /// The frames per buffer of a stream that asked for `frames_per_buffer`.
+    pub(crate) fn frames_per_buffer(&self, frames_per_buffer: BufferSize) -> Result<BufferSize> {
+        match self.period_size {
+            Some(period_size) => match i32::try_from(period_size) {
+                Ok(period_size) => Ok(BufferSize::Exact(period_size)),
+                Err(_) => Err(Error::InvalidFramesPerBuffer),
+            },
+            None => Ok(frames_per_buffer),
+        }
+    }
+}
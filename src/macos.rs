@@ -0,0 +1,56 @@
+//! Settings of native CoreAudio streams, which streams on other backends ignore. See
+//! [`CoreAudioOptions`].
+use std::time::Duration;
+
+/// How a stream uses its CoreAudio device, attached to its
+/// [`StreamOptions::coreaudio`](crate::StreamOptions). For bit-exact, low-latency output, streams
+/// can run the device at their own rate, skip AUHAL's conversions, and shrink the device's
+/// buffer. The device's rate and buffer are the device's, not the stream's: They change for every
+/// application using it, and stay changed after the stream is closed.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// # use audiohal::macos::CoreAudioOptions;
+/// # use std::time::Duration;
+/// let options = StreamOptions::<[f32; 2]> {
+///     sample_rate: SampleRate::Exact(96000),
+///     coreaudio: Some(CoreAudioOptions {
+///         set_nominal_sample_rate: true,
+///         converter: false,
+///         io_buffer_duration: Some(Duration::from_millis(2)),
+///     }),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoreAudioOptions {
+    /// Whether streams that ask for an [exact](crate::SampleRate::Exact) or
+    /// [nearest](crate::SampleRate::NearestTo) sample rate switch the device's nominal rate to it,
+    /// rather than have AUHAL resample them (or, for input streams, fail). `false` by default.
+    pub set_nominal_sample_rate: bool,
+    /// Whether AUHAL converts between the stream's format and the device's. Without its
+    /// converter, streams whose sample rate, channel count, or format differ from the device's
+    /// stream format return [`Error::IncompatibleSampleRate`](crate::Error::IncompatibleSampleRate),
+    /// [`Error::IncompatibleNChannels`](crate::Error::IncompatibleNChannels), or
+    /// [`Error::IncompatibleFormat`](crate::Error::IncompatibleFormat), and samples reach the
+    /// device unconverted. `true` by default.
+    pub converter: bool,
+    /// How long the device's hardware IO buffer is, rounded to frames at the device's rate. Takes
+    /// precedence over [`frames_per_buffer`](crate::StreamOptions::frames_per_buffer). `None`, for
+    /// the device's current buffer, by default.
+    pub io_buffer_duration: Option<Duration>,
+}
+
+impl Default for CoreAudioOptions {
+    fn default() -> CoreAudioOptions {
+        CoreAudioOptions {
+            set_nominal_sample_rate: false,
+            converter: true,
+            io_buffer_duration: None,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::error::{Error, Result};
+use crate::null::stream::{self, Stream};
+use crate::stream_options::{Input, StreamOptions};
+
+/// A null output or input.
+pub struct Device {
+    is_output: bool,
+}
+
+impl Device {
+    pub(super) fn new(is_output: bool) -> Device {
+        Device { is_output }
+    }
+
+    /// "null output" or "null input".
+    pub fn name(&self) -> &str {
+        if self.is_output {
+            "null output"
+        } else {
+            "null input"
+        }
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(options)
+    }
+}
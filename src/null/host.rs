@@ -0,0 +1,26 @@
+use crate::error::Result;
+use crate::null::device::Device;
+
+/// The null host. Always available.
+pub struct Host(());
+
+impl Host {
+    pub fn new() -> Result<Host> {
+        Ok(Host(()))
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "Null"
+    }
+
+    /// Returns an output that discards everything played to it.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new(true))
+    }
+
+    /// Returns an input that captures silence.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new(false))
+    }
+}
@@ -0,0 +1,16 @@
+//! A backend without any hardware, for CI machines and servers without sound cards.
+//!
+//! Output streams consume frames at their sample rate (and discard them), and input streams
+//! capture silence. Callbacks run on a timer thread, with the same buffer sizes and timing as a
+//! real device would use. Any format, channel count, and sample rate is supported.
+
+mod device;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
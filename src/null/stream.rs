@@ -0,0 +1,259 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::stream_options::{CallbackKind, Input, SampleRate, StreamInfo, StreamOptions};
+
+const DEFAULT_SAMPLE_RATE: i32 = 48000;
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 512;
+
+/// Calls the user callback once, with a buffer's worth of frames.
+type Tick = Box<dyn FnMut() + Send>;
+
+/// A null stream. Its timer thread is stopped when dropped.
+pub struct Stream<Frame> {
+    sample_rate: i32,
+    frames_per_buffer: usize,
+    period: Duration,
+    // Moved to the timer thread when started.
+    tick: Option<Tick>,
+    timer: Option<Timer>,
+    _frame: PhantomData<Frame>,
+}
+
+struct Timer {
+    is_stopped: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        let mut tick = self.tick.take().ok_or(Error::StreamAlreadyStarted)?;
+        let period = self.period;
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let is_stopped = is_stopped.clone();
+            std::thread::spawn(move || {
+                // Sleeps until absolute deadlines, so that the timing doesn't drift.
+                let mut deadline = Instant::now();
+                while !is_stopped.load(Ordering::Relaxed) {
+                    // Null streams have nowhere to send violations, which are only counted.
+                    #[cfg(feature = "rt-check")]
+                    crate::rt::checked(&mut tick);
+                    #[cfg(not(feature = "rt-check"))]
+                    tick();
+                    deadline += period;
+                    if let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                        std::thread::sleep(remaining);
+                    }
+                }
+            })
+        };
+        self.timer = Some(Timer { is_stopped, thread });
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// Stops calling the callback. Null streams play their buffers as they're called back, so
+    /// there's nothing left to wait for. The stream can't be started again.
+    pub fn drain(&mut self) -> Result<()> {
+        self.tick = None;
+        if let Some(timer) = self.timer.take() {
+            timer.is_stopped.store(true, Ordering::Relaxed);
+            let _ = timer.thread.join();
+        }
+        Ok(())
+    }
+
+    /// The stream's sample rate. Defaults to 48kHz.
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// Null streams call back with the same number of frames every time: 512 by default.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::fixed(self.frames_per_buffer)
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.is_stopped.store(true, Ordering::Relaxed);
+            let _ = timer.thread.join();
+        }
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let (sample_rate, frames_per_buffer) = validate(&options)?;
+    let mut callback = options.callback;
+    let mut buffer = silence::<Frame>(frames_per_buffer);
+    let tick = Box::new(move || callback(&mut buffer.0));
+    Ok(new_stream(sample_rate, frames_per_buffer, tick))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let (sample_rate, frames_per_buffer) = validate(&options)?;
+    let mut callback = options.callback;
+    let buffer = silence::<Frame>(frames_per_buffer);
+    let tick = Box::new(move || callback(&buffer.0));
+    Ok(new_stream(sample_rate, frames_per_buffer, tick))
+}
+
+/// Returns the sample rate and number of frames per buffer.
+fn validate<Frame, Kind: CallbackKind>(
+    options: &StreamOptions<Frame, Kind>,
+) -> Result<(i32, usize)> {
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+        _ => DEFAULT_SAMPLE_RATE,
+    };
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer,
+        None => DEFAULT_FRAMES_PER_BUFFER,
+    };
+    options.validate_frame_size()?;
+    Ok((sample_rate, frames_per_buffer as usize))
+}
+
+fn new_stream<Frame>(sample_rate: i32, frames_per_buffer: usize, tick: Tick) -> Stream<Frame> {
+    Stream {
+        sample_rate,
+        frames_per_buffer,
+        period: Duration::from_secs_f64(frames_per_buffer as f64 / f64::from(sample_rate)),
+        tick: Some(tick),
+        timer: None,
+        _frame: PhantomData,
+    }
+}
+
+struct Buffer<Frame>(Vec<Frame>);
+
+// Frames are arrays of samples.
+unsafe impl<Frame> Send for Buffer<Frame> {}
+
+/// A buffer of zeroes, i.e. silence in all signed and float formats.
+fn silence<Frame>(n_frames: usize) -> Buffer<Frame> {
+    let mut buffer = Vec::with_capacity(n_frames);
+    unsafe {
+        // Frames are arrays of samples, for which all-zeroes is valid.
+        std::ptr::write_bytes(buffer.as_mut_ptr(), 0, n_frames);
+        buffer.set_len(n_frames);
+    }
+    Buffer(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::null::Host;
+    use crate::stream_options::{BufferSize, ReconnectPolicy};
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn consumes_output_at_sample_rate() -> Result<()> {
+        let n_frames = Arc::new(AtomicUsize::new(0));
+        let mut stream = {
+            let n_frames = n_frames.clone();
+            Host::new()?
+                .default_output_device()?
+                .open_outstream(StreamOptions::<[f32; 2]> {
+                    sample_rate: SampleRate::Exact(10000),
+                    frames_per_buffer: BufferSize::Exact(100),
+                    callback: Box::new(move |buffer| {
+                        n_frames.fetch_add(buffer.len(), Ordering::Relaxed);
+                    }),
+                    ..Default::default()
+                })?
+        };
+        assert!(stream.info().is_fixed_size());
+        assert_eq!(stream.info().max_frames_per_buffer, Some(100));
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(Duration::from_millis(200));
+        stream.close();
+        // 2000 frames were due. Leave room for slow CI machines.
+        let n_frames = n_frames.load(Ordering::Relaxed);
+        assert_ge!(n_frames, 1000);
+        assert_le!(n_frames, 2200);
+        Ok(())
+    }
+
+    #[test]
+    fn captures_silence() -> Result<()> {
+        let is_silent = Arc::new(AtomicBool::new(true));
+        let mut stream = {
+            let is_silent = is_silent.clone();
+            Host::new()?
+                .default_input_device()?
+                .open_input_stream(StreamOptions::<[i16; 1], Input> {
+                    callback: Box::new(move |buffer| {
+                        if buffer.iter().any(|frame| frame[0] != 0) {
+                            is_silent.store(false, Ordering::Relaxed);
+                        }
+                    }),
+                    ..Default::default()
+                })?
+        };
+        stream.start()?;
+        std::thread::sleep(Duration::from_millis(50));
+        stream.close();
+        assert!(is_silent.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[test]
+    fn validates_options() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        assert_eq!(
+            device
+                .open_outstream(StreamOptions::<[f32; 2]> {
+                    frames_per_buffer: BufferSize::Exact(0),
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::InvalidFramesPerBuffer)
+        );
+        assert_eq!(
+            Host::new()?
+                .default_input_device()?
+                .open_outstream(StreamOptions::<[f32; 2]>::default())
+                .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            device
+                .open_outstream(StreamOptions::<[f32; 2]> {
+                    watchdog: Some(Duration::from_secs(1)),
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            device
+                .open_outstream(StreamOptions::<[f32; 2]> {
+                    reconnect: ReconnectPolicy::RetrySameDevice,
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        Ok(())
+    }
+}
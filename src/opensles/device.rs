@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::opensles::stream::{self, Stream};
+use crate::opensles::Engine;
+use crate::stream_options::{Input, StreamOptions};
+
+/// The default OpenSL ES output or input.
+pub struct Device {
+    engine: Arc<Engine>,
+    is_output: bool,
+}
+
+impl Device {
+    pub(super) fn new(engine: Arc<Engine>, is_output: bool) -> Device {
+        Device { engine, is_output }
+    }
+
+    /// Always "default".
+    pub fn name(&self) -> &str {
+        "default"
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(&self.engine, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(&self.engine, options)
+    }
+}
@@ -0,0 +1,207 @@
+//! Hand-written bindings to the subset of OpenSL ES 1.0.1 (and its Android extensions) used by
+//! the backend.
+//!
+//! Interfaces are pointers to pointers to vtables. Only the vtable entries the backend calls are
+//! typed, the others are kept as opaque pointers to preserve the layout.
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use std::os::raw::c_void;
+
+pub type SLresult = u32;
+pub type SLboolean = u32;
+pub type SLuint32 = u32;
+
+#[repr(C)]
+pub struct SLInterfaceID_ {
+    _private: [u8; 0],
+}
+pub type SLInterfaceID = *const SLInterfaceID_;
+
+pub const SL_BOOLEAN_FALSE: SLboolean = 0;
+pub const SL_BOOLEAN_TRUE: SLboolean = 1;
+
+pub const SL_RESULT_SUCCESS: SLresult = 0;
+pub const SL_RESULT_PARAMETER_INVALID: SLresult = 2;
+pub const SL_RESULT_MEMORY_FAILURE: SLresult = 3;
+pub const SL_RESULT_RESOURCE_ERROR: SLresult = 4;
+pub const SL_RESULT_CONTENT_UNSUPPORTED: SLresult = 9;
+pub const SL_RESULT_PERMISSION_DENIED: SLresult = 11;
+pub const SL_RESULT_FEATURE_UNSUPPORTED: SLresult = 12;
+
+pub const SL_PLAYSTATE_STOPPED: SLuint32 = 1;
+pub const SL_PLAYSTATE_PLAYING: SLuint32 = 3;
+pub const SL_RECORDSTATE_STOPPED: SLuint32 = 1;
+pub const SL_RECORDSTATE_RECORDING: SLuint32 = 3;
+
+pub const SL_DATALOCATOR_IODEVICE: SLuint32 = 0x3;
+pub const SL_DATALOCATOR_OUTPUTMIX: SLuint32 = 0x4;
+pub const SL_DATALOCATOR_ANDROIDSIMPLEBUFFERQUEUE: SLuint32 = 0x8000_07BD;
+pub const SL_IODEVICE_AUDIOINPUT: SLuint32 = 1;
+pub const SL_DEFAULTDEVICEID_AUDIOINPUT: SLuint32 = 0xFFFF_FFFF;
+
+pub const SL_DATAFORMAT_PCM: SLuint32 = 0x2;
+/// Android's extended PCM format (API level 21+), which adds float samples.
+pub const SL_ANDROID_DATAFORMAT_PCM_EX: SLuint32 = 0x4;
+pub const SL_ANDROID_PCM_REPRESENTATION_SIGNED_INT: SLuint32 = 1;
+pub const SL_ANDROID_PCM_REPRESENTATION_FLOAT: SLuint32 = 3;
+pub const SL_BYTEORDER_LITTLEENDIAN: SLuint32 = 2;
+
+pub const SL_SPEAKER_FRONT_LEFT: SLuint32 = 0x1;
+pub const SL_SPEAKER_FRONT_RIGHT: SLuint32 = 0x2;
+pub const SL_SPEAKER_FRONT_CENTER: SLuint32 = 0x4;
+
+pub type SLObjectItf = *const *const SLObjectItf_;
+pub type SLEngineItf = *const *const SLEngineItf_;
+pub type SLPlayItf = *const *const SLPlayItf_;
+pub type SLRecordItf = *const *const SLRecordItf_;
+pub type SLAndroidSimpleBufferQueueItf = *const *const SLAndroidSimpleBufferQueueItf_;
+
+#[repr(C)]
+pub struct SLObjectItf_ {
+    pub Realize: unsafe extern "C" fn(self_: SLObjectItf, async_: SLboolean) -> SLresult,
+    pub Resume: *const c_void,
+    pub GetState: *const c_void,
+    pub GetInterface: unsafe extern "C" fn(
+        self_: SLObjectItf,
+        iid: SLInterfaceID,
+        interface: *mut c_void,
+    ) -> SLresult,
+    pub RegisterCallback: *const c_void,
+    pub AbortAsyncOperation: *const c_void,
+    pub Destroy: unsafe extern "C" fn(self_: SLObjectItf),
+    pub SetPriority: *const c_void,
+    pub GetPriority: *const c_void,
+    pub SetLossOfControlInterfaces: *const c_void,
+}
+
+#[repr(C)]
+pub struct SLEngineItf_ {
+    pub CreateLEDDevice: *const c_void,
+    pub CreateVibraDevice: *const c_void,
+    pub CreateAudioPlayer: unsafe extern "C" fn(
+        self_: SLEngineItf,
+        player: *mut SLObjectItf,
+        audio_src: *const SLDataSource,
+        audio_snk: *const SLDataSink,
+        num_interfaces: SLuint32,
+        interface_ids: *const SLInterfaceID,
+        interface_required: *const SLboolean,
+    ) -> SLresult,
+    pub CreateAudioRecorder: unsafe extern "C" fn(
+        self_: SLEngineItf,
+        recorder: *mut SLObjectItf,
+        audio_src: *const SLDataSource,
+        audio_snk: *const SLDataSink,
+        num_interfaces: SLuint32,
+        interface_ids: *const SLInterfaceID,
+        interface_required: *const SLboolean,
+    ) -> SLresult,
+    pub CreateMidiPlayer: *const c_void,
+    pub CreateListener: *const c_void,
+    pub Create3DGroup: *const c_void,
+    pub CreateOutputMix: unsafe extern "C" fn(
+        self_: SLEngineItf,
+        mix: *mut SLObjectItf,
+        num_interfaces: SLuint32,
+        interface_ids: *const SLInterfaceID,
+        interface_required: *const SLboolean,
+    ) -> SLresult,
+    // Followed by entries the backend doesn't use.
+}
+
+#[repr(C)]
+pub struct SLPlayItf_ {
+    pub SetPlayState: unsafe extern "C" fn(self_: SLPlayItf, state: SLuint32) -> SLresult,
+    // Followed by entries the backend doesn't use.
+}
+
+#[repr(C)]
+pub struct SLRecordItf_ {
+    pub SetRecordState: unsafe extern "C" fn(self_: SLRecordItf, state: SLuint32) -> SLresult,
+    // Followed by entries the backend doesn't use.
+}
+
+pub type slAndroidSimpleBufferQueueCallback =
+    unsafe extern "C" fn(caller: SLAndroidSimpleBufferQueueItf, context: *mut c_void);
+
+#[repr(C)]
+pub struct SLAndroidSimpleBufferQueueItf_ {
+    pub Enqueue: unsafe extern "C" fn(
+        self_: SLAndroidSimpleBufferQueueItf,
+        buffer: *const c_void,
+        size: SLuint32,
+    ) -> SLresult,
+    pub Clear: unsafe extern "C" fn(self_: SLAndroidSimpleBufferQueueItf) -> SLresult,
+    pub GetState: *const c_void,
+    pub RegisterCallback: unsafe extern "C" fn(
+        self_: SLAndroidSimpleBufferQueueItf,
+        callback: Option<slAndroidSimpleBufferQueueCallback>,
+        context: *mut c_void,
+    ) -> SLresult,
+}
+
+#[repr(C)]
+pub struct SLDataSource {
+    pub pLocator: *const c_void,
+    pub pFormat: *const c_void,
+}
+
+#[repr(C)]
+pub struct SLDataSink {
+    pub pLocator: *const c_void,
+    pub pFormat: *const c_void,
+}
+
+#[repr(C)]
+pub struct SLDataLocator_AndroidSimpleBufferQueue {
+    pub locatorType: SLuint32,
+    pub numBuffers: SLuint32,
+}
+
+#[repr(C)]
+pub struct SLDataLocator_OutputMix {
+    pub locatorType: SLuint32,
+    pub outputMix: SLObjectItf,
+}
+
+#[repr(C)]
+pub struct SLDataLocator_IODevice {
+    pub locatorType: SLuint32,
+    pub deviceType: SLuint32,
+    pub deviceID: SLuint32,
+    pub device: SLObjectItf,
+}
+
+/// Android's extension of `SLDataFormat_PCM`. Also used for plain `SL_DATAFORMAT_PCM` formats, which
+/// ignore `representation`.
+#[repr(C)]
+pub struct SLAndroidDataFormat_PCM_EX {
+    pub formatType: SLuint32,
+    pub numChannels: SLuint32,
+    /// In milliHertz.
+    pub sampleRate: SLuint32,
+    pub bitsPerSample: SLuint32,
+    pub containerSize: SLuint32,
+    pub channelMask: SLuint32,
+    pub endianness: SLuint32,
+    pub representation: SLuint32,
+}
+
+#[link(name = "OpenSLES")]
+extern "C" {
+    pub static SL_IID_ENGINE: SLInterfaceID;
+    pub static SL_IID_PLAY: SLInterfaceID;
+    pub static SL_IID_RECORD: SLInterfaceID;
+    pub static SL_IID_ANDROIDSIMPLEBUFFERQUEUE: SLInterfaceID;
+
+    pub fn slCreateEngine(
+        engine: *mut SLObjectItf,
+        num_options: SLuint32,
+        engine_options: *const c_void,
+        num_interfaces: SLuint32,
+        interface_ids: *const SLInterfaceID,
+        interface_required: *const SLboolean,
+    ) -> SLresult;
+}
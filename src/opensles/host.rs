@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::opensles::device::Device;
+use crate::opensles::Engine;
+
+/// The OpenSL ES host. OpenSL ES on Android only exposes the default output and input.
+pub struct Host {
+    engine: Arc<Engine>,
+}
+
+impl Host {
+    /// Creates (or shares) the process' OpenSL ES engine.
+    pub fn new() -> Result<Host> {
+        Ok(Host {
+            engine: Engine::get()?,
+        })
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "OpenSL ES"
+    }
+
+    /// Returns the default output (e.g. the speaker, or plugged-in headphones).
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new(self.engine.clone(), true))
+    }
+
+    /// Returns the default input (usually the main microphone).
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new(self.engine.clone(), false))
+    }
+}
@@ -0,0 +1,161 @@
+//! OpenSL ES backend for Android devices too old for AAudio (API level 21 to 26).
+//!
+//! Exposes the same [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend. Streams
+//! are double-buffered through Android's simple buffer queue, so their latency is usually much
+//! higher than AAudio's. Prefer the root [`crate::Host`], which picks the best of the two at
+//! runtime.
+//!
+//! Input streams need the `RECORD_AUDIO` permission.
+use std::sync::{Arc, Weak};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// Converts an OpenSL ES result code into a [`Result`].
+fn check(result: ffi::SLresult) -> Result<()> {
+    match result {
+        ffi::SL_RESULT_SUCCESS => Ok(()),
+        ffi::SL_RESULT_MEMORY_FAILURE => Err(Error::OutOfMemory),
+        ffi::SL_RESULT_PARAMETER_INVALID => Err(Error::Invalid),
+        ffi::SL_RESULT_RESOURCE_ERROR => Err(Error::Backend(BackendError::new(
+            Some(Backend::OpenSles),
+            ErrorCode::OpenSles(result),
+            "OpenSL ES ran out of resources.",
+        ))),
+        ffi::SL_RESULT_PERMISSION_DENIED => Err(Error::Backend(
+            BackendError::new(
+                Some(Backend::OpenSles),
+                ErrorCode::OpenSles(result),
+                "Permission denied. Input streams need the RECORD_AUDIO permission.",
+            )
+            .with_category(ErrorCategory::DeviceUnavailable),
+        )),
+        result => Err(Error::Backend(BackendError::new(
+            Some(Backend::OpenSles),
+            ErrorCode::OpenSles(result),
+            "Unexpected OpenSL ES error.",
+        ))),
+    }
+}
+
+/// An OpenSL ES object. Destroyed when dropped.
+struct Object(ffi::SLObjectItf);
+
+impl Object {
+    /// Realizes the object (synchronously), taking ownership of it.
+    fn realize(object: ffi::SLObjectItf) -> Result<Object> {
+        let object = Object(object);
+        check(unsafe { ((**object.0).Realize)(object.0, ffi::SL_BOOLEAN_FALSE) })?;
+        Ok(object)
+    }
+
+    /// Gets one of the object's interfaces. `Itf` must be the interface type matching `iid`.
+    unsafe fn interface<Itf>(&self, iid: ffi::SLInterfaceID) -> Result<*const Itf> {
+        let mut interface = std::ptr::null();
+        check(((**self.0).GetInterface)(
+            self.0,
+            iid,
+            &mut interface as *mut *const Itf as *mut std::os::raw::c_void,
+        ))?;
+        Ok(interface)
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        unsafe { ((**self.0).Destroy)(self.0) };
+    }
+}
+
+// OpenSL ES objects are thread-safe on Android.
+unsafe impl Send for Object {}
+unsafe impl Sync for Object {}
+
+/// The engine, and the output mix all players play into.
+///
+/// Android only allows a single engine per process, so it is shared by all hosts and streams.
+struct Engine {
+    // Destroyed before the engine.
+    output_mix: Object,
+    engine: ffi::SLEngineItf,
+    _object: Object,
+}
+
+// See Object.
+unsafe impl Send for Engine {}
+unsafe impl Sync for Engine {}
+
+lazy_static! {
+    static ref ENGINE: Mutex<Weak<Engine>> = Mutex::new(Weak::new());
+}
+
+impl Engine {
+    /// Returns the process' engine, creating it if none is alive.
+    fn get() -> Result<Arc<Engine>> {
+        let mut current = ENGINE.lock();
+        if let Some(engine) = current.upgrade() {
+            return Ok(engine);
+        }
+        let mut object = std::ptr::null();
+        check(unsafe {
+            ffi::slCreateEngine(
+                &mut object,
+                0,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        })
+        .map_err(|_| Error::BackendUnavailable)?;
+        let object = Object::realize(object)?;
+        let engine: ffi::SLEngineItf = unsafe { object.interface(ffi::SL_IID_ENGINE)? };
+        let mut output_mix = std::ptr::null();
+        check(unsafe {
+            ((**engine).CreateOutputMix)(
+                engine,
+                &mut output_mix,
+                0,
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        })?;
+        let engine = Arc::new(Engine {
+            output_mix: Object::realize(output_mix)?,
+            engine,
+            _object: object,
+        });
+        *current = Arc::downgrade(&engine);
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_result_codes() {
+        assert_eq!(check(ffi::SL_RESULT_SUCCESS), Ok(()));
+        assert_eq!(
+            check(ffi::SL_RESULT_MEMORY_FAILURE),
+            Err(Error::OutOfMemory)
+        );
+        assert_eq!(check(ffi::SL_RESULT_PARAMETER_INVALID), Err(Error::Invalid));
+    }
+}
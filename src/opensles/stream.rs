@@ -0,0 +1,361 @@
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::opensles::{check, ffi, Engine, Object};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// OpenSL ES resamples to the device's rate, so any rate works. This is the most common native one.
+const DEFAULT_SAMPLE_RATE: i32 = 48000;
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 512;
+/// Streams are double-buffered: The callback fills one buffer while the other is played.
+const N_BUFFERS: usize = 2;
+
+/// An OpenSL ES audio player or recorder. Stopped and destroyed when dropped.
+pub struct Stream<Frame> {
+    // Destroyed before the state it points to is dropped.
+    _object: Object,
+    control: Control,
+    is_started: bool,
+    frames_per_buffer: i32,
+    _state: Box<dyn Send>,
+    _engine: Arc<Engine>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        self.control
+            .set_state(ffi::SL_PLAYSTATE_PLAYING, ffi::SL_RECORDSTATE_RECORDING)?;
+        self.is_started = true;
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// The callback gets one of the stream's buffers every time: 512 frames by default.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::fixed(self.frames_per_buffer as usize)
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        if self.is_started {
+            let _ = self
+                .control
+                .set_state(ffi::SL_PLAYSTATE_STOPPED, ffi::SL_RECORDSTATE_STOPPED);
+        }
+    }
+}
+
+/// The interface that starts and stops the stream.
+enum Control {
+    Play(ffi::SLPlayItf),
+    Record(ffi::SLRecordItf),
+}
+
+impl Control {
+    fn set_state(&self, play_state: ffi::SLuint32, record_state: ffi::SLuint32) -> Result<()> {
+        check(unsafe {
+            match *self {
+                Control::Play(play) => ((**play).SetPlayState)(play, play_state),
+                Control::Record(record) => ((**record).SetRecordState)(record, record_state),
+            }
+        })
+    }
+}
+
+// See Object.
+unsafe impl Send for Control {}
+
+/// What the buffer queue callback works with.
+struct State<Frame, C> {
+    callback: C,
+    buffers: [Vec<Frame>; N_BUFFERS],
+    // The buffer that will be dequeued next.
+    next: usize,
+}
+
+// Frames are arrays of samples.
+unsafe impl<Frame, C: Send> Send for State<Frame, C> {}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    engine: &Arc<Engine>,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    open(engine, options, true, outstream_callback::<Frame>)
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    engine: &Arc<Engine>,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    open(engine, options, false, instream_callback::<Frame>)
+}
+
+/// Creates an audio player (or recorder), and primes its buffer queue with silence.
+fn open<Frame: 'static, Kind: CallbackKind>(
+    engine: &Arc<Engine>,
+    options: StreamOptions<Frame, Kind>,
+    is_output: bool,
+    queue_callback: ffi::slAndroidSimpleBufferQueueCallback,
+) -> Result<Stream<Frame>>
+where
+    Kind::Callback<Frame>: Send + 'static,
+{
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+        _ => DEFAULT_SAMPLE_RATE,
+    };
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer,
+        None => DEFAULT_FRAMES_PER_BUFFER,
+    };
+    options.validate_frame_size()?;
+    let pcm_format = pcm_format(options.format, options.n_channels, sample_rate)?;
+
+    let mut queue_locator = ffi::SLDataLocator_AndroidSimpleBufferQueue {
+        locatorType: ffi::SL_DATALOCATOR_ANDROIDSIMPLEBUFFERQUEUE,
+        numBuffers: N_BUFFERS as u32,
+    };
+    let queue_locator = &mut queue_locator as *mut _ as *const c_void;
+    let format = &pcm_format as *const _ as *const c_void;
+    let mut object = std::ptr::null();
+    let result = unsafe {
+        if is_output {
+            let mix_locator = ffi::SLDataLocator_OutputMix {
+                locatorType: ffi::SL_DATALOCATOR_OUTPUTMIX,
+                outputMix: engine.output_mix.0,
+            };
+            let sink = ffi::SLDataSink {
+                pLocator: &mix_locator as *const _ as *const c_void,
+                pFormat: std::ptr::null(),
+            };
+            let ids = [ffi::SL_IID_ANDROIDSIMPLEBUFFERQUEUE, ffi::SL_IID_PLAY];
+            let required = [ffi::SL_BOOLEAN_TRUE; 2];
+            ((**engine.engine).CreateAudioPlayer)(
+                engine.engine,
+                &mut object,
+                &ffi::SLDataSource {
+                    pLocator: queue_locator,
+                    pFormat: format,
+                },
+                &sink,
+                ids.len() as u32,
+                ids.as_ptr(),
+                required.as_ptr(),
+            )
+        } else {
+            let device_locator = ffi::SLDataLocator_IODevice {
+                locatorType: ffi::SL_DATALOCATOR_IODEVICE,
+                deviceType: ffi::SL_IODEVICE_AUDIOINPUT,
+                deviceID: ffi::SL_DEFAULTDEVICEID_AUDIOINPUT,
+                device: std::ptr::null(),
+            };
+            let source = ffi::SLDataSource {
+                pLocator: &device_locator as *const _ as *const c_void,
+                pFormat: std::ptr::null(),
+            };
+            let ids = [ffi::SL_IID_ANDROIDSIMPLEBUFFERQUEUE, ffi::SL_IID_RECORD];
+            let required = [ffi::SL_BOOLEAN_TRUE; 2];
+            ((**engine.engine).CreateAudioRecorder)(
+                engine.engine,
+                &mut object,
+                &source,
+                &ffi::SLDataSink {
+                    pLocator: queue_locator,
+                    pFormat: format,
+                },
+                ids.len() as u32,
+                ids.as_ptr(),
+                required.as_ptr(),
+            )
+        }
+    };
+    match result {
+        // Float and 32-bit formats need API level 21+.
+        ffi::SL_RESULT_CONTENT_UNSUPPORTED | ffi::SL_RESULT_FEATURE_UNSUPPORTED => {
+            return Err(Error::IncompatibleFormat(options.format))
+        }
+        result => check(result)?,
+    }
+    let object = Object::realize(object)?;
+    let queue: ffi::SLAndroidSimpleBufferQueueItf =
+        unsafe { object.interface(ffi::SL_IID_ANDROIDSIMPLEBUFFERQUEUE)? };
+    let control = unsafe {
+        if is_output {
+            Control::Play(object.interface(ffi::SL_IID_PLAY)?)
+        } else {
+            Control::Record(object.interface(ffi::SL_IID_RECORD)?)
+        }
+    };
+
+    let mut state = Box::new(State {
+        callback: options.callback,
+        buffers: [
+            silence(frames_per_buffer as usize),
+            silence(frames_per_buffer as usize),
+        ],
+        next: 0,
+    });
+    unsafe {
+        check(((**queue).RegisterCallback)(
+            queue,
+            Some(queue_callback),
+            &mut *state as *mut State<Frame, Kind::Callback<Frame>> as *mut c_void,
+        ))?;
+        // Outputs play the silence while the callback fills the next buffer. Inputs record into
+        // the buffers.
+        for buffer in &state.buffers {
+            enqueue(queue, buffer)?;
+        }
+    }
+    Ok(Stream {
+        _object: object,
+        control,
+        is_started: false,
+        frames_per_buffer,
+        _state: state,
+        _engine: engine.clone(),
+        _frame: PhantomData,
+    })
+}
+
+/// Maps the format to a PCM data format. Uses Android's extended format only if needed, since
+/// older devices don't support it.
+fn pcm_format(
+    format: Format,
+    n_channels: i32,
+    sample_rate: i32,
+) -> Result<ffi::SLAndroidDataFormat_PCM_EX> {
+    let channel_mask = match n_channels {
+        1 => ffi::SL_SPEAKER_FRONT_CENTER,
+        2 => ffi::SL_SPEAKER_FRONT_LEFT | ffi::SL_SPEAKER_FRONT_RIGHT,
+        _ => return Err(Error::IncompatibleNChannels),
+    };
+    let (format_type, representation) = match format {
+        // Plain PCM is signed above 8 bits, and unsigned at 8 bits.
+        Format::I16 | Format::U8 => (ffi::SL_DATAFORMAT_PCM, 0),
+        Format::I32 | Format::I24 => (
+            ffi::SL_ANDROID_DATAFORMAT_PCM_EX,
+            ffi::SL_ANDROID_PCM_REPRESENTATION_SIGNED_INT,
+        ),
+        Format::F32 => (
+            ffi::SL_ANDROID_DATAFORMAT_PCM_EX,
+            ffi::SL_ANDROID_PCM_REPRESENTATION_FLOAT,
+        ),
+        _ => return Err(Error::IncompatibleFormat(format)),
+    };
+    let bits_per_sample = format.sample_size() as u32 * 8;
+    Ok(ffi::SLAndroidDataFormat_PCM_EX {
+        formatType: format_type,
+        numChannels: n_channels as u32,
+        sampleRate: sample_rate as u32 * 1000,
+        bitsPerSample: bits_per_sample,
+        containerSize: bits_per_sample,
+        channelMask: channel_mask,
+        endianness: ffi::SL_BYTEORDER_LITTLEENDIAN,
+        representation,
+    })
+}
+
+/// A buffer of zeroes, i.e. silence for all supported formats except U8 (which is briefly played
+/// as a DC offset).
+fn silence<Frame>(n_frames: usize) -> Vec<Frame> {
+    let mut buffer = Vec::with_capacity(n_frames);
+    unsafe {
+        // Frames are arrays of samples, for which all-zeroes is valid.
+        std::ptr::write_bytes(buffer.as_mut_ptr(), 0, n_frames);
+        buffer.set_len(n_frames);
+    }
+    buffer
+}
+
+unsafe fn enqueue<Frame>(
+    queue: ffi::SLAndroidSimpleBufferQueueItf,
+    buffer: &[Frame],
+) -> Result<()> {
+    check(((**queue).Enqueue)(
+        queue,
+        buffer.as_ptr() as *const c_void,
+        std::mem::size_of_val(buffer) as u32,
+    ))
+}
+
+unsafe extern "C" fn outstream_callback<Frame>(
+    queue: ffi::SLAndroidSimpleBufferQueueItf,
+    context: *mut c_void,
+) {
+    let state = (context as *mut State<Frame, Callback<Frame>>)
+        .as_mut()
+        .expect("Could not get State from context.");
+    // The buffer that just finished playing.
+    let buffer = &mut state.buffers[state.next];
+    (state.callback)(buffer);
+    let _ = enqueue(queue, buffer);
+    state.next = (state.next + 1) % N_BUFFERS;
+}
+
+unsafe extern "C" fn instream_callback<Frame>(
+    queue: ffi::SLAndroidSimpleBufferQueueItf,
+    context: *mut c_void,
+) {
+    let state = (context as *mut State<Frame, InputCallback<Frame>>)
+        .as_mut()
+        .expect("Could not get State from context.");
+    // The buffer that was just filled.
+    let buffer = &state.buffers[state.next];
+    (state.callback)(buffer);
+    let _ = enqueue(queue, buffer);
+    state.next = (state.next + 1) % N_BUFFERS;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opensles::Host;
+
+    #[test]
+    fn maps_formats() -> Result<()> {
+        let format = pcm_format(Format::F32, 2, 44100)?;
+        assert_eq!(format.formatType, ffi::SL_ANDROID_DATAFORMAT_PCM_EX);
+        assert_eq!(format.sampleRate, 44_100_000);
+        assert_eq!(format.bitsPerSample, 32);
+        assert_eq!(
+            pcm_format(Format::I16, 1, 44100)?.formatType,
+            ffi::SL_DATAFORMAT_PCM
+        );
+        assert_eq!(
+            pcm_format(Format::I8, 1, 44100).err(),
+            Some(Error::IncompatibleFormat(Format::I8))
+        );
+        assert_eq!(
+            pcm_format(Format::I16, 6, 44100).err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
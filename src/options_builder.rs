@@ -0,0 +1,279 @@
+//! A builder for [`StreamOptions`], for options set one at a time rather than with struct update
+//! syntax.
+use crate::error::{Error, Result};
+use crate::linux::AlsaOptions;
+use crate::macos::CoreAudioOptions;
+use crate::stream_options::{
+    BufferSize, CallbackKind, ChannelMap, ChannelMixPolicy, ChannelSelection, ClipPolicy,
+    DitherMode, FinishedCallback, Format, HasDefaultFormat, HasDefaultNChannels, LatencyHint,
+    Output, ReconnectPolicy, ResamplerQuality, SampleRate, StreamOptions,
+};
+use crate::surround::ChannelMask;
+use crate::windows::WasapiOptions;
+use std::time::Duration;
+
+/// Builds [`StreamOptions`], starting from the default ones. See [`StreamOptions::builder`].
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// let options = StreamOptions::<[f32; 2]>::builder()
+///     .sample_rate(48000)
+///     .frames_per_buffer(256)
+///     .callback(Box::new(|buffer| {
+///         buffer.iter_mut().for_each(|frame| *frame = [0.0, 0.0])
+///     }))
+///     .build()?;
+/// # options;
+/// # Result::Ok(())
+/// ```
+pub struct StreamOptionsBuilder<Frame, Kind: CallbackKind = Output> {
+    options: StreamOptions<Frame, Kind>,
+}
+
+impl<Frame, Sample, Kind> StreamOptions<Frame, Kind>
+where
+    Frame: 'static + sample::Frame<Sample = Sample> + HasDefaultNChannels,
+    Sample: sample::Sample + HasDefaultFormat,
+    Kind: CallbackKind,
+{
+    /// A builder for options, starting from the default ones.
+    pub fn builder() -> StreamOptionsBuilder<Frame, Kind> {
+        StreamOptionsBuilder {
+            options: StreamOptions::default(),
+        }
+    }
+}
+
+impl<Frame, Kind: CallbackKind> StreamOptionsBuilder<Frame, Kind> {
+    /// See [`StreamOptions::format`].
+    pub fn format(mut self, format: Format) -> Self {
+        self.options.format = format;
+        self
+    }
+
+    /// See [`StreamOptions::n_channels`].
+    pub fn n_channels(mut self, n_channels: i32) -> Self {
+        self.options.n_channels = n_channels;
+        self
+    }
+
+    /// See [`StreamOptions::frames_per_buffer`]. Sizes given as integers are
+    /// [`Exact`](BufferSize::Exact).
+    pub fn frames_per_buffer(mut self, frames_per_buffer: impl Into<BufferSize>) -> Self {
+        self.options.frames_per_buffer = frames_per_buffer.into();
+        self
+    }
+
+    /// See [`StreamOptions::sample_rate`]. Rates given as integers are
+    /// [`Exact`](SampleRate::Exact).
+    pub fn sample_rate(mut self, sample_rate: impl Into<SampleRate>) -> Self {
+        self.options.sample_rate = sample_rate.into();
+        self
+    }
+
+    /// See [`StreamOptions::resample_if_needed`].
+    pub fn resample_if_needed(mut self, resample_if_needed: bool) -> Self {
+        self.options.resample_if_needed = resample_if_needed;
+        self
+    }
+
+    /// See [`StreamOptions::resampler_quality`].
+    pub fn resampler_quality(mut self, resampler_quality: ResamplerQuality) -> Self {
+        self.options.resampler_quality = resampler_quality;
+        self
+    }
+
+    /// See [`StreamOptions::channel_mix_policy`].
+    pub fn channel_mix_policy(mut self, channel_mix_policy: ChannelMixPolicy) -> Self {
+        self.options.channel_mix_policy = channel_mix_policy;
+        self
+    }
+
+    /// See [`StreamOptions::channel_map`].
+    pub fn channel_map(mut self, channel_map: ChannelMap) -> Self {
+        self.options.channel_map = Some(channel_map);
+        self
+    }
+
+    /// See [`StreamOptions::channel_mask`].
+    pub fn channel_mask(mut self, channel_mask: ChannelMask) -> Self {
+        self.options.channel_mask = Some(channel_mask);
+        self
+    }
+
+    /// See [`StreamOptions::channels`].
+    pub fn channels(mut self, channels: ChannelSelection) -> Self {
+        self.options.channels = channels;
+        self
+    }
+
+    /// See [`StreamOptions::exclusive`].
+    pub fn exclusive(mut self, exclusive: bool) -> Self {
+        self.options.exclusive = exclusive;
+        self
+    }
+
+    /// See [`StreamOptions::latency`].
+    pub fn latency(mut self, latency: LatencyHint) -> Self {
+        self.options.latency = latency;
+        self
+    }
+
+    /// See [`StreamOptions::realtime_priority`].
+    pub fn realtime_priority(mut self, realtime_priority: bool) -> Self {
+        self.options.realtime_priority = realtime_priority;
+        self
+    }
+
+    /// See [`StreamOptions::prime_output`].
+    pub fn prime_output(mut self, prime_output: bool) -> Self {
+        self.options.prime_output = prime_output;
+        self
+    }
+
+    /// See [`StreamOptions::watchdog`].
+    pub fn watchdog(mut self, timeout: Duration) -> Self {
+        self.options.watchdog = Some(timeout);
+        self
+    }
+
+    /// See [`StreamOptions::reconnect`].
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.options.reconnect = policy;
+        self
+    }
+
+    /// See [`StreamOptions::wasapi`].
+    pub fn wasapi(mut self, wasapi: WasapiOptions) -> Self {
+        self.options.wasapi = Some(wasapi);
+        self
+    }
+
+    /// See [`StreamOptions::coreaudio`].
+    pub fn coreaudio(mut self, coreaudio: CoreAudioOptions) -> Self {
+        self.options.coreaudio = Some(coreaudio);
+        self
+    }
+
+    /// See [`StreamOptions::alsa`].
+    pub fn alsa(mut self, alsa: AlsaOptions) -> Self {
+        self.options.alsa = Some(alsa);
+        self
+    }
+
+    /// See [`StreamOptions::gain`].
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.options.gain = gain;
+        self
+    }
+
+    /// See [`StreamOptions::channel_gains`].
+    pub fn channel_gains(mut self, channel_gains: Vec<f32>) -> Self {
+        self.options.channel_gains = Some(channel_gains);
+        self
+    }
+
+    /// See [`StreamOptions::dither`].
+    pub fn dither(mut self, dither: DitherMode) -> Self {
+        self.options.dither = dither;
+        self
+    }
+
+    /// See [`StreamOptions::clip_policy`].
+    pub fn clip_policy(mut self, clip_policy: ClipPolicy) -> Self {
+        self.options.clip_policy = clip_policy;
+        self
+    }
+
+    /// See [`StreamOptions::on_finished`].
+    pub fn on_finished(mut self, on_finished: FinishedCallback) -> Self {
+        self.options.on_finished = Some(on_finished);
+        self
+    }
+
+    /// See [`StreamOptions::callback`].
+    pub fn callback(mut self, callback: Kind::Callback<Frame>) -> Self {
+        self.options.callback = callback;
+        self
+    }
+
+    /// Checks the options that don't depend on the device, and returns them. Devices still check
+    /// the rest (e.g. the frame size, and what their backend supports) when opening streams.
+    pub fn build(self) -> Result<StreamOptions<Frame, Kind>> {
+        let options = self.options;
+        if options.n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        if matches!(options.frames_per_buffer.frames(), Some(frames) if frames <= 0) {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        if let SampleRate::Exact(rate) | SampleRate::NearestTo(rate) = options.sample_rate {
+            if rate <= 0 {
+                return Err(Error::IncompatibleSampleRate);
+            }
+        }
+        if let Some(mask) = options.channel_mask {
+            if mask.n_channels() != options.n_channels {
+                return Err(Error::IncompatibleNChannels);
+            }
+        }
+        options.channel_gains()?;
+        let has_selection = options.channels != ChannelSelection::All;
+        if has_selection && (options.channel_map.is_some() || options.channel_mask.is_some()) {
+            return Err(Error::IncompatibleStreamMode);
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_options() -> Result<()> {
+        let options = StreamOptions::<[f32; 2]>::builder()
+            .sample_rate(48000)
+            .frames_per_buffer(256)
+            .resample_if_needed(true)
+            .latency(LatencyHint::Low)
+            .build()?;
+        assert_eq!(options.sample_rate, SampleRate::Exact(48000));
+        assert_eq!(options.frames_per_buffer, BufferSize::Exact(256));
+        assert!(options.resample_if_needed);
+        assert_eq!(options.latency, LatencyHint::Low);
+        assert_eq!(options.n_channels, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn validates_options() {
+        let builder = StreamOptions::<[f32; 2]>::builder;
+        assert_eq!(
+            builder().frames_per_buffer(0).build().err(),
+            Some(Error::InvalidFramesPerBuffer)
+        );
+        assert_eq!(
+            builder().sample_rate(-1).build().err(),
+            Some(Error::IncompatibleSampleRate)
+        );
+        assert_eq!(
+            builder().n_channels(0).build().err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            builder()
+                .channels(ChannelSelection::Range(0..2))
+                .channel_map(ChannelMap::new(2))
+                .build()
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            builder().channel_gains(vec![1.0]).build().err(),
+            Some(Error::IncompatibleNChannels)
+        );
+    }
+}
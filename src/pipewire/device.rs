@@ -0,0 +1,82 @@
+use crate::error::{Error, Result};
+use crate::pipewire::stream::{self, Stream};
+use crate::stream_options::{Input, StreamOptions};
+
+/// A PipeWire audio sink (for output) or source (for input) node.
+pub struct Device {
+    // None for the default node.
+    node_name: Option<String>,
+    description: String,
+    is_output: bool,
+}
+
+impl Device {
+    pub(super) fn new(node_name: String, description: String, is_output: bool) -> Device {
+        Device {
+            node_name: Some(node_name),
+            description,
+            is_output,
+        }
+    }
+
+    pub(super) fn default(is_output: bool) -> Device {
+        Device {
+            node_name: None,
+            description: "Default".to_owned(),
+            is_output,
+        }
+    }
+
+    /// The node's name (e.g. "alsa_output.pci-0000_00_1f.3.analog-stereo"), or "default" for the
+    /// default node.
+    pub fn name(&self) -> &str {
+        self.node_name.as_deref().unwrap_or("default")
+    }
+
+    /// The node's human-readable description, as shown in the system's sound settings.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+
+    /// Creates an input stream capturing what this output device plays. See the default
+    /// backend's `Device::open_loopback_stream`.
+    pub fn open_loopback_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_loopback_stream(self, options)
+    }
+
+    pub(super) fn is_output(&self) -> bool {
+        self.is_output
+    }
+
+    pub(super) fn node_name(&self) -> Option<&str> {
+        self.node_name.as_deref()
+    }
+}
@@ -0,0 +1,316 @@
+//! Hand-written bindings to the subset of libpipewire (and SPA) used by the backend.
+//!
+//! SPA's inline helpers (interface method calls, POD building, hook removal) have no exported
+//! symbols, so the structures they work with are declared here and the helpers reimplemented in
+//! Rust.
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+pub enum pw_thread_loop {}
+pub enum pw_loop {}
+pub enum pw_context {}
+pub enum pw_core {}
+pub enum pw_registry {}
+pub enum pw_proxy {}
+pub enum pw_properties {}
+pub enum pw_stream {}
+pub enum spa_pod {}
+
+pub const PW_ID_CORE: u32 = 0;
+pub const PW_ID_ANY: u32 = 0xffff_ffff;
+
+pub const PW_TYPE_INTERFACE_NODE: &str = "PipeWire:Interface:Node";
+pub const PW_VERSION_REGISTRY: u32 = 3;
+
+pub const PW_DIRECTION_INPUT: c_uint = 0;
+pub const PW_DIRECTION_OUTPUT: c_uint = 1;
+
+pub const PW_STREAM_FLAG_AUTOCONNECT: c_uint = 1 << 0;
+pub const PW_STREAM_FLAG_INACTIVE: c_uint = 1 << 1;
+pub const PW_STREAM_FLAG_MAP_BUFFERS: c_uint = 1 << 2;
+pub const PW_STREAM_FLAG_RT_PROCESS: c_uint = 1 << 4;
+
+// POD types.
+pub const SPA_TYPE_ID: u32 = 3;
+pub const SPA_TYPE_INT: u32 = 4;
+pub const SPA_TYPE_OBJECT: u32 = 15;
+pub const SPA_TYPE_OBJECT_FORMAT: u32 = 0x0004_0003;
+pub const SPA_PARAM_ENUM_FORMAT: u32 = 3;
+
+// Format object keys.
+pub const SPA_FORMAT_MEDIA_TYPE: u32 = 1;
+pub const SPA_FORMAT_MEDIA_SUBTYPE: u32 = 2;
+pub const SPA_FORMAT_AUDIO_FORMAT: u32 = 0x0001_0001;
+pub const SPA_FORMAT_AUDIO_RATE: u32 = 0x0001_0003;
+pub const SPA_FORMAT_AUDIO_CHANNELS: u32 = 0x0001_0004;
+
+pub const SPA_MEDIA_TYPE_AUDIO: u32 = 1;
+pub const SPA_MEDIA_SUBTYPE_RAW: u32 = 1;
+
+pub const SPA_AUDIO_FORMAT_S8: u32 = 0x101;
+pub const SPA_AUDIO_FORMAT_U8: u32 = 0x102;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_S16: u32 = 0x103;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_S16: u32 = 0x104;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_U16: u32 = 0x105;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_U16: u32 = 0x106;
+/// 24-bit samples in the low 3 bytes of 4.
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_S24_32: u32 = 0x107;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_S24_32: u32 = 0x108;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_S32: u32 = 0x10b;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_S32: u32 = 0x10c;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_U32: u32 = 0x10d;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_U32: u32 = 0x10e;
+/// Packed 24-bit samples (3 bytes each).
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_S24: u32 = 0x10f;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_S24: u32 = 0x110;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_F32: u32 = 0x11b;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_F32: u32 = 0x11c;
+#[cfg(target_endian = "little")]
+pub const SPA_AUDIO_FORMAT_F64: u32 = 0x11d;
+#[cfg(target_endian = "big")]
+pub const SPA_AUDIO_FORMAT_F64: u32 = 0x11e;
+
+#[repr(C)]
+pub struct spa_list {
+    pub next: *mut spa_list,
+    pub prev: *mut spa_list,
+}
+
+#[repr(C)]
+pub struct spa_callbacks {
+    pub funcs: *const c_void,
+    pub data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct spa_hook {
+    pub link: spa_list,
+    pub cb: spa_callbacks,
+    pub removed: Option<unsafe extern "C" fn(hook: *mut spa_hook)>,
+    pub priv_: *mut c_void,
+}
+
+/// The header of every PipeWire proxy object (e.g. `pw_core`), through which its methods are
+/// called.
+#[repr(C)]
+pub struct spa_interface {
+    pub type_: *const c_char,
+    pub version: u32,
+    pub cb: spa_callbacks,
+}
+
+#[repr(C)]
+pub struct spa_dict_item {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct spa_dict {
+    pub flags: u32,
+    pub n_items: u32,
+    pub items: *const spa_dict_item,
+}
+
+#[repr(C)]
+pub struct spa_chunk {
+    pub offset: u32,
+    pub size: u32,
+    pub stride: i32,
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct spa_data {
+    pub type_: u32,
+    pub flags: u32,
+    pub fd: i64,
+    pub mapoffset: u32,
+    pub maxsize: u32,
+    pub data: *mut c_void,
+    pub chunk: *mut spa_chunk,
+}
+
+#[repr(C)]
+pub struct spa_buffer {
+    pub n_metas: u32,
+    pub n_datas: u32,
+    pub metas: *mut c_void,
+    pub datas: *mut spa_data,
+}
+
+#[repr(C)]
+pub struct pw_buffer {
+    pub buffer: *mut spa_buffer,
+    pub user_data: *mut c_void,
+    pub size: u64,
+    /// The number of frames the graph wants for this cycle (since 0.3.49). 0 if unknown.
+    pub requested: u64,
+}
+
+#[repr(C)]
+pub struct pw_core_methods {
+    pub version: u32,
+    pub add_listener: unsafe extern "C" fn(
+        object: *mut c_void,
+        listener: *mut spa_hook,
+        events: *const pw_core_events,
+        data: *mut c_void,
+    ) -> c_int,
+    pub hello: *const c_void,
+    pub sync: unsafe extern "C" fn(object: *mut c_void, id: u32, seq: c_int) -> c_int,
+    pub pong: *const c_void,
+    pub error: *const c_void,
+    pub get_registry: unsafe extern "C" fn(
+        object: *mut c_void,
+        version: u32,
+        user_data_size: usize,
+    ) -> *mut pw_registry,
+    pub create_object: *const c_void,
+    pub destroy: *const c_void,
+}
+
+#[repr(C)]
+pub struct pw_core_events {
+    pub version: u32,
+    pub info: Option<unsafe extern "C" fn(data: *mut c_void, info: *const c_void)>,
+    pub done: Option<unsafe extern "C" fn(data: *mut c_void, id: u32, seq: c_int)>,
+    pub ping: Option<unsafe extern "C" fn(data: *mut c_void, id: u32, seq: c_int)>,
+    pub error: Option<
+        unsafe extern "C" fn(
+            data: *mut c_void,
+            id: u32,
+            seq: c_int,
+            res: c_int,
+            message: *const c_char,
+        ),
+    >,
+    pub remove_id: Option<unsafe extern "C" fn(data: *mut c_void, id: u32)>,
+    pub bound_id: Option<unsafe extern "C" fn(data: *mut c_void, id: u32, global_id: u32)>,
+    pub add_mem:
+        Option<unsafe extern "C" fn(data: *mut c_void, id: u32, type_: u32, fd: c_int, flags: u32)>,
+    pub remove_mem: Option<unsafe extern "C" fn(data: *mut c_void, id: u32)>,
+}
+
+#[repr(C)]
+pub struct pw_registry_methods {
+    pub version: u32,
+    pub add_listener: unsafe extern "C" fn(
+        object: *mut c_void,
+        listener: *mut spa_hook,
+        events: *const pw_registry_events,
+        data: *mut c_void,
+    ) -> c_int,
+    pub bind: *const c_void,
+    pub destroy: *const c_void,
+}
+
+#[repr(C)]
+pub struct pw_registry_events {
+    pub version: u32,
+    pub global: Option<
+        unsafe extern "C" fn(
+            data: *mut c_void,
+            id: u32,
+            permissions: u32,
+            type_: *const c_char,
+            version: u32,
+            props: *const spa_dict,
+        ),
+    >,
+    pub global_remove: Option<unsafe extern "C" fn(data: *mut c_void, id: u32)>,
+}
+
+#[repr(C)]
+pub struct pw_stream_events {
+    pub version: u32,
+    pub destroy: Option<unsafe extern "C" fn(data: *mut c_void)>,
+    pub state_changed: Option<
+        unsafe extern "C" fn(data: *mut c_void, old: c_int, state: c_int, error: *const c_char),
+    >,
+    pub control_info:
+        Option<unsafe extern "C" fn(data: *mut c_void, id: u32, control: *const c_void)>,
+    pub io_changed:
+        Option<unsafe extern "C" fn(data: *mut c_void, id: u32, area: *mut c_void, size: u32)>,
+    pub param_changed:
+        Option<unsafe extern "C" fn(data: *mut c_void, id: u32, param: *const spa_pod)>,
+    pub add_buffer: Option<unsafe extern "C" fn(data: *mut c_void, buffer: *mut pw_buffer)>,
+    pub remove_buffer: Option<unsafe extern "C" fn(data: *mut c_void, buffer: *mut pw_buffer)>,
+    pub process: Option<unsafe extern "C" fn(data: *mut c_void)>,
+    pub drained: Option<unsafe extern "C" fn(data: *mut c_void)>,
+}
+
+#[link(name = "pipewire-0.3")]
+extern "C" {
+    pub fn pw_init(argc: *mut c_int, argv: *mut *mut *mut c_char);
+
+    pub fn pw_thread_loop_new(name: *const c_char, props: *const spa_dict) -> *mut pw_thread_loop;
+    pub fn pw_thread_loop_destroy(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_start(thread_loop: *mut pw_thread_loop) -> c_int;
+    pub fn pw_thread_loop_stop(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_lock(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_unlock(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_wait(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_signal(thread_loop: *mut pw_thread_loop, wait_for_accept: bool);
+    pub fn pw_thread_loop_get_loop(thread_loop: *mut pw_thread_loop) -> *mut pw_loop;
+
+    pub fn pw_context_new(
+        main_loop: *mut pw_loop,
+        props: *mut pw_properties,
+        user_data_size: usize,
+    ) -> *mut pw_context;
+    pub fn pw_context_destroy(context: *mut pw_context);
+    pub fn pw_context_connect(
+        context: *mut pw_context,
+        props: *mut pw_properties,
+        user_data_size: usize,
+    ) -> *mut pw_core;
+    pub fn pw_core_disconnect(core: *mut pw_core) -> c_int;
+    pub fn pw_proxy_destroy(proxy: *mut pw_proxy);
+
+    pub fn pw_properties_new(key: *const c_char, ...) -> *mut pw_properties;
+    pub fn pw_properties_set(
+        properties: *mut pw_properties,
+        key: *const c_char,
+        value: *const c_char,
+    ) -> c_int;
+
+    pub fn pw_stream_new(
+        core: *mut pw_core,
+        name: *const c_char,
+        props: *mut pw_properties,
+    ) -> *mut pw_stream;
+    pub fn pw_stream_destroy(stream: *mut pw_stream);
+    pub fn pw_stream_add_listener(
+        stream: *mut pw_stream,
+        listener: *mut spa_hook,
+        events: *const pw_stream_events,
+        data: *mut c_void,
+    );
+    pub fn pw_stream_connect(
+        stream: *mut pw_stream,
+        direction: c_uint,
+        target_id: u32,
+        flags: c_uint,
+        params: *mut *const spa_pod,
+        n_params: u32,
+    ) -> c_int;
+    pub fn pw_stream_set_active(stream: *mut pw_stream, active: bool) -> c_int;
+    pub fn pw_stream_dequeue_buffer(stream: *mut pw_stream) -> *mut pw_buffer;
+    pub fn pw_stream_queue_buffer(stream: *mut pw_stream, buffer: *mut pw_buffer) -> c_int;
+}
@@ -0,0 +1,234 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, Result};
+use crate::pipewire::device::Device;
+use crate::pipewire::{error, ffi, lookup, new_hook, remove_hook, Connection};
+
+/// The PipeWire host. Holds a connection to the daemon, used to list its nodes.
+pub struct Host {
+    connection: Connection,
+}
+
+impl Host {
+    /// Connects to the PipeWire daemon. Returns [`Error::BackendUnavailable`] if it isn't
+    /// running.
+    pub fn new() -> Result<Host> {
+        Ok(Host {
+            connection: Connection::new()?,
+        })
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "PipeWire"
+    }
+
+    /// Returns the default sink. Streams on it follow the default as the user changes it.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::default(true))
+    }
+
+    /// Returns the default source.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::default(false))
+    }
+
+    /// Lists the audio sink nodes.
+    pub fn output_devices(&mut self) -> Result<Vec<Device>> {
+        self.devices(true)
+    }
+
+    /// Lists the audio source nodes.
+    pub fn input_devices(&mut self) -> Result<Vec<Device>> {
+        self.devices(false)
+    }
+
+    fn devices(&mut self, is_output: bool) -> Result<Vec<Device>> {
+        Ok(self
+            .list_nodes()?
+            .into_iter()
+            .filter(|node| is_device_class(&node.media_class, is_output))
+            .map(|node| Device::new(node.name, node.description, is_output))
+            .collect())
+    }
+
+    /// Lists every node currently known to the daemon.
+    fn list_nodes(&mut self) -> Result<Vec<Node>> {
+        let lock = self.connection.lock();
+        let core = self.connection.core as *mut ffi::spa_interface;
+        let core_methods = unsafe { &*((*core).cb.funcs as *const ffi::pw_core_methods) };
+        let registry = unsafe {
+            (core_methods.get_registry)((*core).cb.data, ffi::PW_VERSION_REGISTRY, 0)
+                as *mut ffi::spa_interface
+        };
+        if registry.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let registry_methods =
+            unsafe { &*((*registry).cb.funcs as *const ffi::pw_registry_methods) };
+
+        // The callbacks write to the listing while this waits on it, so it's only ever
+        // accessed through this pointer until they're removed.
+        let listing = Box::into_raw(Box::new(Listing {
+            thread_loop: self.connection.thread_loop,
+            nodes: Vec::new(),
+            sync_seq: 0,
+            is_done: AtomicBool::new(false),
+            result: 0,
+        }));
+        let data = listing as *mut c_void;
+        let mut registry_hook = new_hook();
+        let mut core_hook = new_hook();
+        unsafe {
+            (registry_methods.add_listener)(
+                (*registry).cb.data,
+                &mut registry_hook,
+                &REGISTRY_EVENTS,
+                data,
+            );
+            (core_methods.add_listener)((*core).cb.data, &mut core_hook, &CORE_EVENTS, data);
+            // The daemon answers the sync once it has sent every global that existed before it.
+            // Its answer can't come before this returns: The thread loop is locked.
+            (*listing).sync_seq = (core_methods.sync)((*core).cb.data, ffi::PW_ID_CORE, 0);
+            while !(*listing).is_done.load(Ordering::Acquire) {
+                lock.wait();
+            }
+            remove_hook(&mut core_hook);
+            remove_hook(&mut registry_hook);
+            ffi::pw_proxy_destroy(registry as *mut ffi::pw_proxy);
+        }
+        let listing = unsafe { Box::from_raw(listing) };
+        if listing.result < 0 {
+            return Err(error(listing.result, "Could not list PipeWire nodes."));
+        }
+        Ok(listing.nodes)
+    }
+}
+
+/// Whether a node of the given media class can be used as an output (or input) device.
+fn is_device_class(media_class: &str, is_output: bool) -> bool {
+    match media_class {
+        "Audio/Duplex" => true,
+        "Audio/Sink" => is_output,
+        "Audio/Source" | "Audio/Source/Virtual" => !is_output,
+        _ => false,
+    }
+}
+
+struct Node {
+    name: String,
+    description: String,
+    media_class: String,
+}
+
+/// What the registry and core callbacks fill in while listing nodes.
+struct Listing {
+    thread_loop: *mut ffi::pw_thread_loop,
+    nodes: Vec<Node>,
+    sync_seq: c_int,
+    is_done: AtomicBool,
+    /// The negated errno the daemon failed with, if it did.
+    result: c_int,
+}
+
+static REGISTRY_EVENTS: ffi::pw_registry_events = ffi::pw_registry_events {
+    version: 0,
+    global: Some(registry_global),
+    global_remove: None,
+};
+
+static CORE_EVENTS: ffi::pw_core_events = ffi::pw_core_events {
+    version: 0,
+    info: None,
+    done: Some(core_done),
+    ping: None,
+    error: Some(core_error),
+    remove_id: None,
+    bound_id: None,
+    add_mem: None,
+    remove_mem: None,
+};
+
+unsafe extern "C" fn registry_global(
+    data: *mut c_void,
+    _id: u32,
+    _permissions: u32,
+    type_: *const c_char,
+    _version: u32,
+    props: *const ffi::spa_dict,
+) {
+    let listing = (data as *mut Listing)
+        .as_mut()
+        .expect("Could not get Listing from data.");
+    if type_.is_null()
+        || props.is_null()
+        || CStr::from_ptr(type_).to_bytes() != ffi::PW_TYPE_INTERFACE_NODE.as_bytes()
+    {
+        return;
+    }
+    let props = &*props;
+    let name = match lookup(props, "node.name") {
+        Some(name) => name,
+        None => return,
+    };
+    listing.nodes.push(Node {
+        description: lookup(props, "node.description")
+            .or_else(|| lookup(props, "node.nick"))
+            .unwrap_or_else(|| name.clone()),
+        media_class: lookup(props, "media.class").unwrap_or_default(),
+        name,
+    });
+}
+
+unsafe extern "C" fn core_done(data: *mut c_void, id: u32, seq: c_int) {
+    let listing = (data as *mut Listing)
+        .as_mut()
+        .expect("Could not get Listing from data.");
+    if id == ffi::PW_ID_CORE && seq == listing.sync_seq {
+        listing.is_done.store(true, Ordering::Release);
+        ffi::pw_thread_loop_signal(listing.thread_loop, false);
+    }
+}
+
+unsafe extern "C" fn core_error(
+    data: *mut c_void,
+    id: u32,
+    _seq: c_int,
+    res: c_int,
+    _message: *const c_char,
+) {
+    let listing = (data as *mut Listing)
+        .as_mut()
+        .expect("Could not get Listing from data.");
+    if id == ffi::PW_ID_CORE {
+        // Errors are the daemon's negated errnos, which shouldn't be 0.
+        listing.result = res.min(-1);
+        listing.is_done.store(true, Ordering::Release);
+        ffi::pw_thread_loop_signal(listing.thread_loop, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_nodes() {
+        assert!(is_device_class("Audio/Sink", true));
+        assert!(!is_device_class("Audio/Sink", false));
+        assert!(is_device_class("Audio/Source", false));
+        assert!(is_device_class("Audio/Duplex", false));
+        assert!(!is_device_class("Stream/Output/Audio", true));
+    }
+
+    #[test]
+    fn lists_devices() -> Result<()> {
+        let mut host = Host::new()?;
+        for device in host.output_devices()? {
+            println!("Output: {} ({})", device.name(), device.description());
+        }
+        Ok(())
+    }
+}
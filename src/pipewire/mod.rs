@@ -0,0 +1,286 @@
+//! PipeWire backend for modern Linux desktops.
+//!
+//! Talks to libpipewire directly. Devices are the daemon's audio sink and source nodes, and each
+//! stream is a PipeWire stream node of its own, linked to the device by the session manager.
+//! Exposes the same [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend.
+//!
+//! Every host and stream runs its own PipeWire thread loop. Stream callbacks run on PipeWire's
+//! real-time thread: They must not block, lock, or allocate.
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::sync::Once;
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCode, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// The name of the threads running PipeWire loops.
+const LOOP_NAME: &str = "audiohal";
+
+/// Initializes libpipewire, once per process.
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe { ffi::pw_init(std::ptr::null_mut(), std::ptr::null_mut()) });
+}
+
+/// The error of a PipeWire call that failed with `result`, a negated errno.
+fn error(result: c_int, message: &'static str) -> Error {
+    Error::Backend(BackendError::new(
+        Some(Backend::PipeWire),
+        ErrorCode::Errno(-result),
+        message,
+    ))
+}
+
+/// A running thread loop, with a context connected to the PipeWire daemon. Disconnected and torn
+/// down when dropped.
+struct Connection {
+    thread_loop: *mut ffi::pw_thread_loop,
+    context: *mut ffi::pw_context,
+    core: *mut ffi::pw_core,
+}
+
+// PipeWire objects may be used from any thread, as long as the loop is locked.
+unsafe impl Send for Connection {}
+unsafe impl Sync for Connection {}
+
+impl Connection {
+    /// Connects to the PipeWire daemon. Returns [`Error::BackendUnavailable`] if it isn't running.
+    fn new() -> Result<Connection> {
+        init();
+        let name = CString::new(LOOP_NAME).unwrap();
+        let mut connection = Connection {
+            thread_loop: unsafe { ffi::pw_thread_loop_new(name.as_ptr(), std::ptr::null()) },
+            context: std::ptr::null_mut(),
+            core: std::ptr::null_mut(),
+        };
+        if connection.thread_loop.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        connection.context = unsafe {
+            ffi::pw_context_new(
+                ffi::pw_thread_loop_get_loop(connection.thread_loop),
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if connection.context.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        connection.core =
+            unsafe { ffi::pw_context_connect(connection.context, std::ptr::null_mut(), 0) };
+        if connection.core.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        let result = unsafe { ffi::pw_thread_loop_start(connection.thread_loop) };
+        if result < 0 {
+            return Err(error(result, "Could not start the PipeWire loop."));
+        }
+        Ok(connection)
+    }
+
+    /// Locks the loop until the returned guard is dropped. Objects must only be used (and
+    /// callbacks only registered or removed) while the loop is locked.
+    fn lock(&self) -> LoopLock {
+        unsafe { ffi::pw_thread_loop_lock(self.thread_loop) };
+        LoopLock(self.thread_loop)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::pw_thread_loop_stop(self.thread_loop);
+            if !self.core.is_null() {
+                ffi::pw_core_disconnect(self.core);
+            }
+            if !self.context.is_null() {
+                ffi::pw_context_destroy(self.context);
+            }
+            ffi::pw_thread_loop_destroy(self.thread_loop);
+        }
+    }
+}
+
+/// Keeps a thread loop locked. Unlocks it when dropped.
+struct LoopLock(*mut ffi::pw_thread_loop);
+
+impl LoopLock {
+    /// Unlocks the loop until it is signaled (see `pw_thread_loop_signal`).
+    fn wait(&self) {
+        unsafe { ffi::pw_thread_loop_wait(self.0) };
+    }
+}
+
+impl Drop for LoopLock {
+    fn drop(&mut self) {
+        unsafe { ffi::pw_thread_loop_unlock(self.0) };
+    }
+}
+
+/// Returns a zeroed hook, ready to be registered.
+fn new_hook() -> ffi::spa_hook {
+    ffi::spa_hook {
+        link: ffi::spa_list {
+            next: std::ptr::null_mut(),
+            prev: std::ptr::null_mut(),
+        },
+        cb: ffi::spa_callbacks {
+            funcs: std::ptr::null(),
+            data: std::ptr::null_mut(),
+        },
+        removed: None,
+        priv_: std::ptr::null_mut(),
+    }
+}
+
+/// Unregisters a hook. Equivalent to SPA's inline `spa_hook_remove`. The loop must be locked.
+unsafe fn remove_hook(hook: &mut ffi::spa_hook) {
+    if !hook.link.prev.is_null() {
+        (*hook.link.prev).next = hook.link.next;
+        (*hook.link.next).prev = hook.link.prev;
+    }
+    if let Some(removed) = hook.removed {
+        removed(hook);
+    }
+}
+
+/// Returns the value of `key` in a property dictionary, if any. Equivalent to SPA's inline
+/// `spa_dict_lookup`.
+fn lookup(dict: &ffi::spa_dict, key: &str) -> Option<String> {
+    if dict.items.is_null() {
+        return None;
+    }
+    let items = unsafe { std::slice::from_raw_parts(dict.items, dict.n_items as usize) };
+    items
+        .iter()
+        .filter(|item| !item.key.is_null() && !item.value.is_null())
+        .find(|item| unsafe { CStr::from_ptr(item.key) }.to_bytes() == key.as_bytes())
+        .map(|item| to_string(item.value))
+}
+
+/// Copies a C string returned by PipeWire.
+fn to_string(ptr: *const c_char) -> String {
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Builds an `EnumFormat` POD describing raw interleaved audio. Equivalent to SPA's inline
+/// `spa_format_audio_raw_build`. Stored as u64s, as PODs must be 8-byte aligned.
+fn audio_format_pod(format: u32, sample_rate: u32, n_channels: u32) -> Vec<u64> {
+    let properties = [
+        (
+            ffi::SPA_FORMAT_MEDIA_TYPE,
+            ffi::SPA_TYPE_ID,
+            ffi::SPA_MEDIA_TYPE_AUDIO,
+        ),
+        (
+            ffi::SPA_FORMAT_MEDIA_SUBTYPE,
+            ffi::SPA_TYPE_ID,
+            ffi::SPA_MEDIA_SUBTYPE_RAW,
+        ),
+        (ffi::SPA_FORMAT_AUDIO_FORMAT, ffi::SPA_TYPE_ID, format),
+        (ffi::SPA_FORMAT_AUDIO_RATE, ffi::SPA_TYPE_INT, sample_rate),
+        (
+            ffi::SPA_FORMAT_AUDIO_CHANNELS,
+            ffi::SPA_TYPE_INT,
+            n_channels,
+        ),
+    ];
+    // Each property is its key and flags, followed by a 4-byte value POD padded to 8 bytes.
+    let body_size = 8 + properties.len() as u32 * 24;
+    let mut words = vec![
+        body_size,
+        ffi::SPA_TYPE_OBJECT,
+        ffi::SPA_TYPE_OBJECT_FORMAT,
+        ffi::SPA_PARAM_ENUM_FORMAT,
+    ];
+    for &(key, value_type, value) in &properties {
+        words.extend_from_slice(&[key, 0, 4, value_type, value, 0]);
+    }
+    words
+        .chunks(2)
+        .map(|pair| {
+            let mut bytes = [0; 8];
+            bytes[..4].copy_from_slice(&pair[0].to_ne_bytes());
+            bytes[4..].copy_from_slice(&pair[1].to_ne_bytes());
+            u64::from_ne_bytes(bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_audio_format_pods() {
+        let pod = audio_format_pod(ffi::SPA_AUDIO_FORMAT_F32, 44_100, 2);
+        let words =
+            unsafe { std::slice::from_raw_parts(pod.as_ptr() as *const u32, pod.len() * 2) };
+        // The header's size doesn't include the header itself.
+        assert_eq!(words[0] as usize, words.len() * 4 - 8);
+        assert_eq!(words[1], ffi::SPA_TYPE_OBJECT);
+        assert_eq!(
+            &words[16..22],
+            &[
+                ffi::SPA_FORMAT_AUDIO_FORMAT,
+                0,
+                4,
+                ffi::SPA_TYPE_ID,
+                ffi::SPA_AUDIO_FORMAT_F32,
+                0
+            ]
+        );
+        assert_eq!(
+            &words[22..28],
+            &[
+                ffi::SPA_FORMAT_AUDIO_RATE,
+                0,
+                4,
+                ffi::SPA_TYPE_INT,
+                44_100,
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn looks_up_properties() {
+        let keys = [
+            CString::new("node.name").unwrap(),
+            CString::new("media.class").unwrap(),
+        ];
+        let values = [
+            CString::new("speakers").unwrap(),
+            CString::new("Audio/Sink").unwrap(),
+        ];
+        let items: Vec<_> = keys
+            .iter()
+            .zip(&values)
+            .map(|(key, value)| ffi::spa_dict_item {
+                key: key.as_ptr(),
+                value: value.as_ptr(),
+            })
+            .collect();
+        let dict = ffi::spa_dict {
+            flags: 0,
+            n_items: items.len() as u32,
+            items: items.as_ptr(),
+        };
+        assert_eq!(lookup(&dict, "media.class").as_deref(), Some("Audio/Sink"));
+        assert_eq!(lookup(&dict, "node.description"), None);
+    }
+}
@@ -0,0 +1,332 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::pipewire::device::Device;
+use crate::pipewire::{audio_format_pod, error, ffi, new_hook, Connection};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// The rate used when the options ask for the device's default. The daemon resamples as needed.
+const FALLBACK_SAMPLE_RATE: u32 = 48_000;
+
+/// A stream node of its own. The callback runs on the stream's PipeWire thread.
+pub struct Stream<Frame> {
+    // Declared first, so that the loop is stopped before the listener and state are dropped.
+    connection: Connection,
+    stream: StreamPtr,
+    is_started: bool,
+    _listener: Box<Listener>,
+    _state: Box<dyn Send>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    /// Activates the stream. It has been linked to the device, inactive, since it was opened.
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        let _lock = self.connection.lock();
+        let result = unsafe { ffi::pw_stream_set_active(self.stream.0, true) };
+        if result < 0 {
+            return Err(error(result, "Could not activate the PipeWire stream."));
+        }
+        self.is_started = true;
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// PipeWire's graph runs at a quantum that changes as clients come and go, so the callback's
+    /// buffers aren't bounded.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::default()
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        // Also removes the stream's listener.
+        let _lock = self.connection.lock();
+        unsafe { ffi::pw_stream_destroy(self.stream.0) };
+    }
+}
+
+#[derive(Copy, Clone)]
+struct StreamPtr(*mut ffi::pw_stream);
+
+// Only used with the stream's loop locked, or from the loop's own thread.
+unsafe impl Send for StreamPtr {}
+
+/// The stream's listener registration. Must outlive the stream.
+struct Listener {
+    hook: ffi::spa_hook,
+    events: ffi::pw_stream_events,
+}
+
+// Only touched by the loop.
+unsafe impl Send for Listener {}
+
+/// What the process callback works with.
+struct ProcessState<C> {
+    stream: StreamPtr,
+    callback: C,
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    connect(
+        device,
+        options,
+        ffi::PW_DIRECTION_OUTPUT,
+        outstream_process::<Frame>,
+    )
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    connect(
+        device,
+        options,
+        ffi::PW_DIRECTION_INPUT,
+        instream_process::<Frame>,
+    )
+}
+
+/// Captures what an output device plays, through its sink's monitor ports.
+pub(super) fn new_loopback_stream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    connect(
+        device,
+        options,
+        ffi::PW_DIRECTION_INPUT,
+        instream_process::<Frame>,
+    )
+}
+
+/// Creates a stream node and links it, inactive, to the device.
+fn connect<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: StreamOptions<Frame, Kind>,
+    direction: std::os::raw::c_uint,
+    process: unsafe extern "C" fn(*mut c_void),
+) -> Result<Stream<Frame>>
+where
+    Kind::Callback<Frame>: Send + 'static,
+{
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate as u32,
+        _ => FALLBACK_SAMPLE_RATE,
+    };
+    if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+    }
+    options.validate_frame_size()?;
+    let pod = audio_format_pod(
+        pipewire_format(options.format)?,
+        sample_rate,
+        options.n_channels as u32,
+    );
+
+    let connection = Connection::new()?;
+    let lock = connection.lock();
+    let properties = unsafe { ffi::pw_properties_new(std::ptr::null()) };
+    if properties.is_null() {
+        return Err(Error::OutOfMemory);
+    }
+    let category = if direction == ffi::PW_DIRECTION_OUTPUT {
+        "Playback"
+    } else {
+        "Capture"
+    };
+    let mut entries = vec![("media.type", "Audio".to_owned())];
+    entries.push(("media.category", category.to_owned()));
+    if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+        // Asks the graph for the given quantum. The daemon may still pick another one.
+        entries.push((
+            "node.latency",
+            format!("{}/{}", frames_per_buffer, sample_rate),
+        ));
+    }
+    if let Some(node_name) = device.node_name() {
+        entries.push(("target.object", node_name.to_owned()));
+    }
+    if direction == ffi::PW_DIRECTION_INPUT && device.is_output() {
+        entries.push(("stream.capture.sink", "true".to_owned()));
+    }
+    for (key, value) in entries {
+        let key = CString::new(key).unwrap();
+        let value = CString::new(value).or(Err(Error::Invalid))?;
+        unsafe { ffi::pw_properties_set(properties, key.as_ptr(), value.as_ptr()) };
+    }
+
+    let name = CString::new("audiohal").unwrap();
+    // Takes ownership of the properties.
+    let stream = unsafe { ffi::pw_stream_new(connection.core, name.as_ptr(), properties) };
+    if stream.is_null() {
+        return Err(Error::OutOfMemory);
+    }
+    let stream = StreamPtr(stream);
+    let mut state = Box::new(ProcessState {
+        stream,
+        callback: options.callback,
+    });
+    let mut listener = Box::new(Listener {
+        hook: new_hook(),
+        events: ffi::pw_stream_events {
+            version: 0,
+            destroy: None,
+            state_changed: None,
+            control_info: None,
+            io_changed: None,
+            param_changed: None,
+            add_buffer: None,
+            remove_buffer: None,
+            process: Some(process),
+            drained: None,
+        },
+    });
+    let mut params = [pod.as_ptr() as *const ffi::spa_pod];
+    let result = unsafe {
+        ffi::pw_stream_add_listener(
+            stream.0,
+            &mut listener.hook,
+            &listener.events,
+            &mut *state as *mut ProcessState<Kind::Callback<Frame>> as *mut c_void,
+        );
+        ffi::pw_stream_connect(
+            stream.0,
+            direction,
+            ffi::PW_ID_ANY,
+            ffi::PW_STREAM_FLAG_AUTOCONNECT
+                | ffi::PW_STREAM_FLAG_INACTIVE
+                | ffi::PW_STREAM_FLAG_MAP_BUFFERS
+                | ffi::PW_STREAM_FLAG_RT_PROCESS,
+            params.as_mut_ptr(),
+            params.len() as u32,
+        )
+    };
+    drop(lock);
+    let stream = Stream {
+        connection,
+        stream,
+        is_started: false,
+        _listener: listener,
+        _state: state,
+        _frame: PhantomData,
+    };
+    if result < 0 {
+        return Err(error(result, "Could not connect the PipeWire stream."));
+    }
+    Ok(stream)
+}
+
+fn pipewire_format(format: Format) -> Result<u32> {
+    Ok(match format {
+        Format::F64 => ffi::SPA_AUDIO_FORMAT_F64,
+        Format::F32 => ffi::SPA_AUDIO_FORMAT_F32,
+        Format::I32 => ffi::SPA_AUDIO_FORMAT_S32,
+        Format::I24 => ffi::SPA_AUDIO_FORMAT_S24,
+        Format::I24In32 => ffi::SPA_AUDIO_FORMAT_S24_32,
+        Format::I16 => ffi::SPA_AUDIO_FORMAT_S16,
+        Format::I8 => ffi::SPA_AUDIO_FORMAT_S8,
+        Format::U32 => ffi::SPA_AUDIO_FORMAT_U32,
+        Format::U16 => ffi::SPA_AUDIO_FORMAT_U16,
+        Format::U8 => ffi::SPA_AUDIO_FORMAT_U8,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    })
+}
+
+unsafe extern "C" fn outstream_process<Frame>(data: *mut c_void) {
+    let state = (data as *mut ProcessState<Callback<Frame>>)
+        .as_mut()
+        .expect("Could not get ProcessState from data.");
+    let buffer = ffi::pw_stream_dequeue_buffer(state.stream.0);
+    if buffer.is_null() {
+        // Out of buffers. Nothing to fill this cycle.
+        return;
+    }
+    let spa_data = &mut *(*(*buffer).buffer).datas;
+    if !spa_data.data.is_null() {
+        let stride = std::mem::size_of::<Frame>();
+        let mut frame_count = spa_data.maxsize as usize / stride;
+        if (*buffer).requested > 0 {
+            frame_count = frame_count.min((*buffer).requested as usize);
+        }
+        let frames = std::slice::from_raw_parts_mut(spa_data.data as *mut Frame, frame_count);
+        (state.callback)(frames);
+        let chunk = &mut *spa_data.chunk;
+        chunk.offset = 0;
+        chunk.stride = stride as i32;
+        chunk.size = (frame_count * stride) as u32;
+    }
+    ffi::pw_stream_queue_buffer(state.stream.0, buffer);
+}
+
+unsafe extern "C" fn instream_process<Frame>(data: *mut c_void) {
+    let state = (data as *mut ProcessState<InputCallback<Frame>>)
+        .as_mut()
+        .expect("Could not get ProcessState from data.");
+    let buffer = ffi::pw_stream_dequeue_buffer(state.stream.0);
+    if buffer.is_null() {
+        return;
+    }
+    let spa_data = &*(*(*buffer).buffer).datas;
+    if !spa_data.data.is_null() && spa_data.maxsize > 0 {
+        let chunk = &*spa_data.chunk;
+        let offset = chunk.offset % spa_data.maxsize;
+        let size = chunk.size.min(spa_data.maxsize - offset) as usize;
+        let frames = std::slice::from_raw_parts(
+            (spa_data.data as *const u8).add(offset as usize) as *const Frame,
+            size / std::mem::size_of::<Frame>(),
+        );
+        (state.callback)(frames);
+    }
+    ffi::pw_stream_queue_buffer(state.stream.0, buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::Host;
+
+    #[test]
+    fn maps_formats() {
+        assert_eq!(pipewire_format(Format::I8), Ok(ffi::SPA_AUDIO_FORMAT_S8));
+        assert_eq!(pipewire_format(Format::F32), Ok(ffi::SPA_AUDIO_FORMAT_F32));
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+
+    #[test]
+    fn can_start_input_stream() -> Result<()> {
+        let mut device = Host::new()?.default_input_device()?;
+        let mut stream = device.open_input_stream(StreamOptions::<[i16; 1], Input>::default())?;
+        stream.start()?;
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
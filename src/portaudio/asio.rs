@@ -0,0 +1,116 @@
+//! Extensions for Portaudio's ASIO host API.
+use libportaudio_sys as ffi;
+use std::os::raw::c_long;
+
+use crate::error::Result;
+use crate::portaudio::error::PaErrorIntoResult as _;
+use crate::portaudio::LockGuard;
+
+/// The buffer sizes, in frames, that an ASIO driver can run at.
+///
+/// ASIO drivers only run at these sizes. Streams opened on an ASIO device use `preferred` if their
+/// `frames_per_buffer` allows it, or else the nearest size to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AsioBufferSizes {
+    pub min: i32,
+    pub max: i32,
+    /// The size the driver is configured for, usually in its control panel.
+    pub preferred: i32,
+    /// The step between legal sizes. -1 if only powers of two are legal, and 0 if only
+    /// `preferred` is.
+    pub granularity: i32,
+}
+
+impl AsioBufferSizes {
+    /// Asks the device's driver for its buffer sizes.
+    pub(super) fn of_device(device_index: i32, _guard: &LockGuard) -> Result<AsioBufferSizes> {
+        let (mut min, mut max, mut preferred, mut granularity): (c_long, c_long, c_long, c_long) =
+            (0, 0, 0, 0);
+        unsafe {
+            ffi::PaAsio_GetAvailableBufferSizes(
+                device_index,
+                &mut min,
+                &mut max,
+                &mut preferred,
+                &mut granularity,
+            )
+        }
+        .into_result()?;
+        Ok(AsioBufferSizes {
+            min,
+            max,
+            preferred,
+            granularity,
+        })
+    }
+
+    /// Returns the legal buffer size closest to `frames_per_buffer`. Picks the smaller one on
+    /// ties.
+    pub fn nearest(&self, frames_per_buffer: i32) -> i32 {
+        let clamped = frames_per_buffer.max(self.min).min(self.max);
+        match self.granularity {
+            -1 => {
+                let upper = (clamped.max(1) as u32).next_power_of_two() as i32;
+                let lower = if upper == clamped { upper } else { upper / 2 };
+                if upper > self.max || (lower >= self.min && clamped - lower <= upper - clamped) {
+                    lower
+                } else {
+                    upper
+                }
+            }
+            granularity if granularity > 0 => {
+                let steps = (clamped - self.min + granularity / 2) / granularity;
+                let size = self.min + steps * granularity;
+                if size > self.max {
+                    size - granularity
+                } else {
+                    size
+                }
+            }
+            _ => self.preferred,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_to_granularity() {
+        let sizes = AsioBufferSizes {
+            min: 64,
+            max: 2048,
+            preferred: 256,
+            granularity: 32,
+        };
+        assert_eq!(sizes.nearest(100), 96);
+        assert_eq!(sizes.nearest(10), 64);
+        assert_eq!(sizes.nearest(10_000), 2048);
+    }
+
+    #[test]
+    fn snaps_to_powers_of_two() {
+        let sizes = AsioBufferSizes {
+            min: 32,
+            max: 1024,
+            preferred: 128,
+            granularity: -1,
+        };
+        assert_eq!(sizes.nearest(100), 128);
+        assert_eq!(sizes.nearest(90), 64);
+        assert_eq!(sizes.nearest(96), 64);
+        assert_eq!(sizes.nearest(5_000), 1024);
+    }
+
+    #[test]
+    fn uses_preferred_size_without_granularity() {
+        let sizes = AsioBufferSizes {
+            min: 512,
+            max: 512,
+            preferred: 512,
+            granularity: 0,
+        };
+        assert_eq!(sizes.nearest(64), 512);
+    }
+}
@@ -1,10 +1,19 @@
 use std::sync::Arc;
 
+use crate::capabilities::DeviceCapabilities;
 use crate::error::Result;
+#[cfg(all(windows, feature = "asio"))]
+use crate::portaudio::asio::AsioBufferSizes;
 use crate::portaudio::host::HostHandle;
+use crate::portaudio::stream::Stream;
 use crate::portaudio::LockGuard;
-use crate::stream_options::StreamOptions;
-use crate::Stream;
+use crate::stream_options::{
+    CallbackKind, DuplexCallback, DuplexInfoCallback, DynamicInput, DynamicOutput, Input,
+    InputWithInfo, NoCallback, OutputWithInfo, PlanarInput, PlanarOutput, RawInput, RawOutput,
+    StreamOptions,
+};
+use crate::surround::ChannelPosition;
+use crate::Format;
 
 use crate::portaudio::internal::device as internal;
 
@@ -17,38 +26,173 @@ impl Device {
         &self.0.name
     }
 
+    /// Portaudio's index for the device, among all of its host APIs' devices.
+    pub fn index(&self) -> i32 {
+        self.0.index()
+    }
+
+    /// The sample rates and formats the device supports, and its channel counts.
+    pub fn capabilities(&self) -> DeviceCapabilities {
+        self.0.capabilities()
+    }
+
+    /// Whether Portaudio takes streams with the options' format, channel count, and sample rate,
+    /// without opening one.
+    pub fn supports<F, K: CallbackKind>(&self, options: &StreamOptions<F, K>) -> bool {
+        self.0.supports(options)
+    }
+
+    /// Whether Portaudio takes output or input streams in the format, without opening one.
+    pub fn supports_config(&self, format: Format, n_channels: i32, sample_rate: i32) -> bool {
+        self.0.supports_config(format, n_channels, sample_rate)
+    }
+
+    /// The positions of the device's output channels, or of its input channels, in their order.
+    /// See [`StreamOptions::channel_mask`].
+    pub fn channel_positions(&self, is_output: bool) -> Option<Vec<ChannelPosition>> {
+        self.0.channel_positions(is_output)
+    }
+
     /// Creates an output stream.
-    ///
-    /// `Frame` is the stream's frame type, and is inferred from the stream callback.
-    ///
-    /// Output streams stream digital audio (in the form of frames) to a system's output device.
-    /// The callback in  [`StreamOptions`] is called multiple times per second (depending on how you
-    /// setup frames_per_buffer) in order to satisfy the requested sample-rate. See
-    /// [`Stream`] for more details.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use audiohal::*;
-    /// fn callback(buffer: &mut [[f32; 2]]) {
-    ///     # buffer;
-    /// }
-    /// let mut device = Host::with_default_backend()?.default_output_device()?;
-    /// let stream = device.open_outstream(
-    ///     StreamOptions {
-    ///         callback: Box::new(callback),
-    ///         // The rest of the parameters will be set to device defaults.
-    ///         ..Default::default()
-    ///     });
-    /// assert!(stream.is_ok());
-    /// # Result::Ok(())
-    /// ```
-    pub fn open_outstream<Frame>(
+    pub fn open_outstream<Frame: 'static>(
         &mut self,
         options: StreamOptions<Frame>,
     ) -> Result<Stream<Frame>> {
         self.0.open_outstream(options, Arc::clone(&self.0))
     }
+
+    /// Creates an input stream.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        self.0.open_input_stream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a planar output stream, whose callback fills a buffer per channel.
+    pub fn open_planar_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, PlanarOutput>,
+    ) -> Result<Stream<Sample>> {
+        self.0.open_planar_outstream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a planar input stream, whose callback receives a buffer per channel.
+    pub fn open_planar_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, PlanarInput>,
+    ) -> Result<Stream<Sample>> {
+        self.0
+            .open_planar_input_stream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates an output stream whose callback gets a [`CallbackInfo`](crate::CallbackInfo).
+    pub fn open_outstream_with_info<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, OutputWithInfo>,
+    ) -> Result<Stream<Frame>> {
+        self.0
+            .open_outstream_with_info(options, Arc::clone(&self.0))
+    }
+
+    /// Creates an input stream whose callback gets a [`CallbackInfo`](crate::CallbackInfo).
+    pub fn open_input_stream_with_info<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, InputWithInfo>,
+    ) -> Result<Stream<Frame>> {
+        self.0
+            .open_input_stream_with_info(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a dynamic output stream, whose callback fills a buffer of interleaved samples for
+    /// the options' channel count.
+    pub fn open_dynamic_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, DynamicOutput>,
+    ) -> Result<Stream<Sample>> {
+        self.0.open_dynamic_outstream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a dynamic input stream, whose callback receives interleaved samples for the
+    /// options' channel count.
+    pub fn open_dynamic_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, DynamicInput>,
+    ) -> Result<Stream<Sample>> {
+        self.0
+            .open_dynamic_input_stream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a raw output stream, whose callback fills Portaudio's own buffer in the options'
+    /// format, which must be one the device takes without conversion.
+    pub fn open_raw_outstream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, RawOutput>,
+    ) -> Result<Stream<Sample>> {
+        self.0.open_raw_outstream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a raw input stream, whose callback reads Portaudio's own buffer of captured
+    /// samples in the options' format.
+    pub fn open_raw_input_stream<Sample: 'static>(
+        &mut self,
+        options: StreamOptions<Sample, RawInput>,
+    ) -> Result<Stream<Sample>> {
+        self.0.open_raw_input_stream(options, Arc::clone(&self.0))
+    }
+
+    /// Creates a blocking output stream.
+    pub fn open_blocking_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+    ) -> Result<Stream<Frame>> {
+        self.0
+            .open_blocking_stream(options, true, Arc::clone(&self.0))
+    }
+
+    /// Creates a blocking input stream.
+    pub fn open_blocking_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, NoCallback>,
+    ) -> Result<Stream<Frame>> {
+        self.0
+            .open_blocking_stream(options, false, Arc::clone(&self.0))
+    }
+
+    /// Creates a full-duplex stream.
+    pub fn open_duplex_stream<InFrame: 'static, OutFrame: 'static>(
+        &mut self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexCallback<InFrame, OutFrame>,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        self.0
+            .open_duplex_stream(input, output, callback, Arc::clone(&self.0))
+    }
+
+    /// Creates a full-duplex stream whose callback gets a [`CallbackInfo`](crate::CallbackInfo).
+    pub fn open_duplex_stream_with_info<InFrame: 'static, OutFrame: 'static>(
+        &mut self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexInfoCallback<InFrame, OutFrame>,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        self.0
+            .open_duplex_stream_with_info(input, output, callback, Arc::clone(&self.0))
+    }
+}
+
+#[cfg(all(windows, feature = "asio"))]
+impl Device {
+    /// Returns the buffer sizes the device's ASIO driver supports.
+    pub fn asio_buffer_sizes(&self) -> Result<AsioBufferSizes> {
+        self.0.asio_buffer_sizes()
+    }
+
+    /// Opens the ASIO driver's control panel, where users usually pick its preferred buffer size.
+    pub fn show_asio_control_panel(&mut self) -> Result<()> {
+        self.0.show_asio_control_panel()
+    }
 }
 
 pub fn from_device_index(
@@ -70,6 +214,22 @@ mod tests {
         assert_send::<Device>();
     }
 
+    #[test]
+    fn lists_capabilities() -> Result<()> {
+        begin!();
+        let device = Host::with_default_backend()?.default_output_device()?;
+        let capabilities = device.capabilities();
+        assert_gt!(capabilities.max_output_channels, 0);
+        assert!(capabilities
+            .sample_rates
+            .contains(&device.0.default_sample_rate()));
+        assert!(!capabilities.formats.is_empty());
+        assert!(device.supports_config(capabilities.formats[0], 1, capabilities.sample_rates[0]));
+        assert!(device.supports(&StreamOptions::<[f32; 1]>::default()));
+        assert!(!device.supports_config(Format::F32, 10_000, 48000));
+        Ok(())
+    }
+
     #[test]
     fn device_holds_host_ref() -> Result<()> {
         begin!();
@@ -81,4 +241,25 @@ mod tests {
         assert!(is_initialized());
         Ok(())
     }
+
+    #[test]
+    fn streams_hold_host_ref() -> Result<()> {
+        begin!();
+        let mut streams = {
+            let mut host = Host::with_default_backend()?;
+            let mut device = host.default_output_device()?;
+            vec![
+                device.open_outstream(StreamOptions::<[f32; 2]>::default())?,
+                device.open_outstream(StreamOptions::<[f32; 2]>::default())?,
+            ]
+        };
+        // The host and device are gone, but the streams still run.
+        for stream in &mut streams {
+            stream.start()?;
+            assert!(stream.is_active()?);
+        }
+        drop(streams);
+        assert!(!is_initialized());
+        Ok(())
+    }
 }
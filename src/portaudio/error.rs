@@ -1,7 +1,10 @@
 use libportaudio_sys as ffi;
+use std::ffi::CStr;
 use std::os::raw::c_int;
 
-use crate::error::{Error, Result};
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+use crate::portaudio::host::backend_of;
 
 impl From<ffi::PaErrorCode> for Error {
     fn from(error: ffi::PaErrorCode) -> Error {
@@ -15,23 +18,139 @@ impl From<ffi::PaErrorCode> for Error {
             paHostApiNotFound => BackendUnavailable,
             paInvalidSampleRate => IncompatibleSampleRate,
             paInvalidChannelCount => IncompatibleNChannels,
+            paInvalidDevice => NoSuchDevice,
+            paStreamIsNotStopped => StreamAlreadyStarted,
             // Not actually sure how to handle paNotInitialized. Should never happen
             // under normal circumstances.
             paNotInitialized => Unknown("Portaudio not initialized."),
-            _ => panic!("Figure out error mapping for {:?}", error),
+            paCanNotReadFromACallbackStream
+            | paCanNotWriteToACallbackStream
+            | paCanNotReadFromAnOutputOnlyStream
+            | paCanNotWriteToAnInputOnlyStream => IncompatibleStreamMode,
+            paUnanticipatedHostError => Backend(host_error()),
+            _ => {
+                let backend_error =
+                    BackendError::new(None, ErrorCode::PaError(error as i32), error_text(error));
+                Backend(match error_category(error) {
+                    Some(category) => backend_error.with_category(category),
+                    None => backend_error,
+                })
+            }
         }
     }
 }
 
-pub trait PaErrorAsResult: Sized {
-    fn as_result(self) -> Result<c_int>;
+/// Converts the error of an open stream, whose device may have disappeared since: Host API errors
+/// that say so become [`Error::DeviceLost`].
+pub fn stream_error(error: ffi::PaErrorCode) -> Error {
+    match Error::from(error) {
+        error if is_device_lost(&error) => Error::DeviceLost,
+        error => error,
+    }
+}
+
+/// Whether the error is how Portaudio, or its host API, says that the device is gone.
+fn is_device_lost(error: &Error) -> bool {
+    /// WASAPI's `AUDCLNT_E_DEVICE_INVALIDATED`.
+    const DEVICE_INVALIDATED: i32 = 0x8889_0004_u32 as i32;
+    /// CoreAudio's `kAudioHardwareBadDeviceError`, i.e. `'!dev'`.
+    const BAD_DEVICE: i32 = 0x2164_6576;
+    const ENODEV: i32 = 19;
+    match error.code() {
+        Some(ErrorCode::PaError(code)) => code == ffi::PaErrorCode::paDeviceUnavailable as i32,
+        Some(ErrorCode::HResult(code)) => code == DEVICE_INVALIDATED,
+        Some(ErrorCode::OsStatus(code)) => code == BAD_DEVICE,
+        Some(ErrorCode::Errno(code)) => code == ENODEV,
+        _ => false,
+    }
 }
 
-impl PaErrorAsResult for ffi::PaError {
-    fn as_result(self) -> Result<c_int> {
+/// The category of the Portaudio errors that aren't mapped to one of our own.
+fn error_category(error: ffi::PaErrorCode) -> Option<ErrorCategory> {
+    use ffi::PaErrorCode::*;
+    match error {
+        paDeviceUnavailable => Some(ErrorCategory::DeviceUnavailable),
+        paSampleFormatNotSupported | paBufferTooBig | paBufferTooSmall => {
+            Some(ErrorCategory::FormatUnsupported)
+        }
+        paInvalidFlag
+        | paBadIODeviceCombination
+        | paNullCallback
+        | paBadStreamPtr
+        | paIncompatibleHostApiSpecificStreamInfo
+        | paStreamIsStopped
+        | paIncompatibleStreamHostApi
+        | paBadBufferPtr => Some(ErrorCategory::InvalidUsage),
+        _ => None,
+    }
+}
+
+/// Portaudio's description of the error.
+fn error_text(error: ffi::PaErrorCode) -> &'static str {
+    // Portaudio's texts are string literals.
+    let text = unsafe { CStr::from_ptr(ffi::Pa_GetErrorText(ffi::PaError(error as c_int))) };
+    text.to_str().unwrap_or("Unexpected Portaudio error.")
+}
+
+/// The error of Portaudio's host API behind a `paUnanticipatedHostError`, with the host API's own
+/// code.
+fn host_error() -> BackendError {
+    let info = unsafe { *ffi::Pa_GetLastHostErrorInfo() };
+    let backend = backend_of(info.hostApiType);
+    let code = info.errorCode as i32;
+    let code = match backend {
+        Some(Backend::CoreAudio) => ErrorCode::OsStatus(code),
+        Some(Backend::Wasapi) => ErrorCode::HResult(code),
+        // ALSA returns negated errnos.
+        Some(Backend::Alsa) | Some(Backend::LinuxFallback) => ErrorCode::Errno(code.abs()),
+        _ => ErrorCode::PaError(ffi::PaErrorCode::paUnanticipatedHostError as i32),
+    };
+    BackendError::new(backend, code, "Portaudio's host API failed.")
+}
+
+pub trait PaErrorIntoResult: Sized {
+    fn into_result(self) -> Result<c_int>;
+
+    /// Like [`into_result`](PaErrorIntoResult::into_result), for the errors of open streams. See
+    /// [`stream_error`].
+    fn into_stream_result(self) -> Result<c_int>;
+}
+
+impl PaErrorIntoResult for ffi::PaError {
+    fn into_result(self) -> Result<c_int> {
         match self.into() {
             Err(err) => Err(err.into()),
             Ok(val) => Ok(val),
         }
     }
+
+    fn into_stream_result(self) -> Result<c_int> {
+        std::result::Result::<c_int, ffi::PaErrorCode>::from(self).map_err(stream_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_lost_devices() {
+        let error = |backend, code| Error::Backend(BackendError::new(backend, code, ""));
+        let unavailable = ErrorCode::PaError(ffi::PaErrorCode::paDeviceUnavailable as i32);
+        assert!(is_device_lost(&error(None, unavailable)));
+        assert!(is_device_lost(&error(
+            Some(Backend::Wasapi),
+            ErrorCode::HResult(0x8889_0004_u32 as i32)
+        )));
+        assert!(is_device_lost(&error(
+            Some(Backend::Alsa),
+            ErrorCode::Errno(19)
+        )));
+        // Busy devices aren't lost.
+        assert!(!is_device_lost(&error(
+            Some(Backend::Alsa),
+            ErrorCode::Errno(16)
+        )));
+        assert!(!is_device_lost(&Error::NoSuchDevice));
+    }
 }
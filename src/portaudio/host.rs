@@ -1,10 +1,10 @@
 use libportaudio_sys as ffi;
 use std::convert::{TryFrom, TryInto as _};
 
-use crate::backend::Backend;
+use crate::backend::{Backend, HostApi};
 use crate::error::{Error, Result};
 use crate::portaudio::device;
-use crate::portaudio::error::PaErrorAsResult as _;
+use crate::portaudio::error::PaErrorIntoResult as _;
 use crate::portaudio::{global_lock, LockGuard, RawPtr};
 
 pub type HostHandle = std::sync::Arc<HostImpl>;
@@ -21,13 +21,36 @@ impl TryFrom<Backend> for ffi::PaHostApiTypeId {
             Alsa => Ok(paALSA),
             CoreAudio => Ok(paCoreAudio),
             Wasapi => Ok(paWASAPI),
+            Asio => Ok(paASIO),
+            DirectSound => Ok(paDirectSound),
+            Mme => Ok(paMME),
+            WdmKs => Ok(paWDMKS),
             LinuxFallback => Ok(paOSS),
-            Dummy => Ok(paInDevelopment),
+            Dummy | AAudio | OpenSles | PipeWire | PulseAudio | WebAudio => {
+                Err(Error::BackendUnavailable)
+            }
             _ => panic!("Backend pattern is not exhaustive."),
         }
     }
 }
 
+/// The backend of a Portaudio host API, if it is one of ours.
+pub(super) fn backend_of(pa_backend: ffi::PaHostApiTypeId) -> Option<Backend> {
+    use ffi::PaHostApiTypeId::*;
+    match pa_backend {
+        paJACK => Some(Backend::Jack),
+        paALSA => Some(Backend::Alsa),
+        paCoreAudio => Some(Backend::CoreAudio),
+        paWASAPI => Some(Backend::Wasapi),
+        paASIO => Some(Backend::Asio),
+        paDirectSound => Some(Backend::DirectSound),
+        paMME => Some(Backend::Mme),
+        paWDMKS => Some(Backend::WdmKs),
+        paOSS => Some(Backend::LinuxFallback),
+        _ => None,
+    }
+}
+
 pub struct HostImpl {
     name: String,
     host_index: ffi::PaHostApiIndex,
@@ -38,62 +61,115 @@ impl Host {
     /// Creates a host with the default system backend.
     pub fn with_default_backend() -> Result<Host> {
         let _guard = global_lock();
-        unsafe { ffi::Pa_Initialize() }.as_result()?;
+        unsafe { ffi::Pa_Initialize() }.into_result()?;
         let mut host = HostImpl::new();
         // TODO: Expose default backend.
         let host_index = unsafe { ffi::Pa_GetDefaultHostApi() };
         if host_index < 0 {
-            return Err(ffi::PaError::from(host_index).as_result().unwrap_err());
+            return Err(ffi::PaError::from(host_index).into_result().unwrap_err());
         }
         host.init_with_pa_host_index(host_index, _guard)?;
         Ok(Host(HostHandle::new(host)))
     }
 
-    /// Creates a host with a specific backend.
-    ///
-    /// Will return [`Error::BackendUnavailable`] if the backend support was not
-    /// compiled.
-    ///
-    /// # Examples
-    /// ```
-    /// # use audiohal::*;
-    /// assert!(Host::with_backend(Backend::Dummy).is_ok(), "The dummy backend should always be available.");
-    /// ```
+    /// Creates a host with a specific backend. Returns [`Error::BackendUnavailable`] if the
+    /// backend support was not compiled.
     pub fn with_backend(backend: Backend) -> Result<Host> {
         let _guard = global_lock();
         // Initialize Pa.
-        unsafe { ffi::Pa_Initialize() }.as_result()?;
+        unsafe { ffi::Pa_Initialize() }.into_result()?;
         let mut host = HostImpl::new();
         let pa_backend = backend.try_into()?;
         host.init_with_pa_host_type(pa_backend, _guard)?;
         Ok(Host(HostHandle::new(host)))
     }
 
+    /// Returns the backends Portaudio was compiled with, starting with its default.
+    pub fn backends() -> Result<Vec<Backend>> {
+        let _guard = global_lock();
+        unsafe { ffi::Pa_Initialize() }.into_result()?;
+        let default_index = unsafe { ffi::Pa_GetDefaultHostApi() };
+        let mut backends = Vec::new();
+        for host_index in 0..unsafe { ffi::Pa_GetHostApiCount() } {
+            let host_info = match unsafe { ffi::Pa_GetHostApiInfo(host_index).as_ref() } {
+                Some(host_info) => host_info,
+                None => continue,
+            };
+            if let Some(backend) = backend_of(host_info.type_) {
+                if host_index == default_index {
+                    backends.insert(0, backend);
+                } else {
+                    backends.push(backend);
+                }
+            }
+        }
+        unsafe { ffi::Pa_Terminate() }.into_result()?;
+        Ok(backends)
+    }
+
+    /// Returns every host API Portaudio was compiled with, in Portaudio's order: Including those
+    /// that aren't one of our backends.
+    pub fn host_apis() -> Result<Vec<HostApi>> {
+        let _guard = global_lock();
+        unsafe { ffi::Pa_Initialize() }.into_result()?;
+        let default_index = unsafe { ffi::Pa_GetDefaultHostApi() };
+        let host_apis = (0..unsafe { ffi::Pa_GetHostApiCount() })
+            .filter_map(|host_index| {
+                let host_info = unsafe { ffi::Pa_GetHostApiInfo(host_index).as_ref() }?;
+                let name = unsafe { std::ffi::CStr::from_ptr(host_info.name) };
+                Some(HostApi {
+                    name: name.to_string_lossy().into_owned(),
+                    backend: backend_of(host_info.type_).unwrap_or(Backend::None),
+                    n_devices: usize::try_from(host_info.deviceCount).unwrap_or(0),
+                    is_default: host_index == default_index,
+                })
+            })
+            .collect();
+        unsafe { ffi::Pa_Terminate() }.into_result()?;
+        Ok(host_apis)
+    }
+
     /// Returns the host API's descriptive name (e.g. "CoreAudio").
     pub fn name(&self) -> &str {
         &self.0.name
     }
 
+    /// Returns the host API's backend, or [`Backend::None`] if it isn't one of ours.
+    pub fn backend(&self) -> Backend {
+        let host_info = unsafe { self.0.host_info.as_ref().unwrap() };
+        backend_of(host_info.type_).unwrap_or(Backend::None)
+    }
+
     /// Creates and returns the default output device for this host.
-    ///
-    /// This is the recommended device to use for audio playback.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut host = audiohal::Host::with_default_backend()?;
-    /// match host.default_output_device() {
-    ///     Ok(device) => println!("Default output device name is {}.", device.name()),
-    ///     Err(_) => println!("No devices available."),
-    /// };
-    /// # audiohal::Result::Ok(())
-    /// ```
-    ///
     pub fn default_output_device(&mut self) -> Result<device::Device> {
         let guard = global_lock();
-        let device_index = self.0.default_output_device_index(&guard)?;
+        let device_index = self.0.default_device_index(true, &guard)?;
+        device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
+    }
+
+    /// Creates and returns the default input device for this host.
+    pub fn default_input_device(&mut self) -> Result<device::Device> {
+        let guard = global_lock();
+        let device_index = self.0.default_device_index(false, &guard)?;
         device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
     }
+
+    /// Creates and returns all of the host's devices, input and output, in Portaudio's order.
+    pub fn devices(&mut self) -> Result<Vec<device::Device>> {
+        let guard = global_lock();
+        let host_info = unsafe { self.0.host_info.as_ref().unwrap() };
+        (0..host_info.deviceCount)
+            .map(|host_device_index| {
+                let device_index = unsafe {
+                    ffi::Pa_HostApiDeviceIndexToDeviceIndex(self.0.host_index, host_device_index)
+                };
+                if device_index < 0 {
+                    return Err(ffi::PaError::from(device_index).into_result().unwrap_err());
+                }
+                device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
+            })
+            .collect()
+    }
 }
 
 impl HostImpl {
@@ -126,13 +202,20 @@ impl HostImpl {
             .ok_or(Error::BackendUnavailable)?;
         self.name = unsafe { std::ffi::CStr::from_ptr(self.host_info.as_ref().unwrap().name) }
             .to_str()
-            .or(Err(Error::Unknown("Could not convert host name to UTF-8.")))?
+            .map_err(|error| {
+                Error::from_source(None, "Could not convert host name to UTF-8.", error)
+            })?
             .to_string();
         Ok(())
     }
 
-    fn default_output_device_index(&self, _guard: &LockGuard) -> Result<i32> {
-        let host_device_index = unsafe { self.host_info.as_ref().unwrap() }.defaultOutputDevice;
+    fn default_device_index(&self, is_output: bool, _guard: &LockGuard) -> Result<i32> {
+        let host_info = unsafe { self.host_info.as_ref().unwrap() };
+        let host_device_index = if is_output {
+            host_info.defaultOutputDevice
+        } else {
+            host_info.defaultInputDevice
+        };
         if host_device_index == ffi::paNoDevice {
             return Err(Error::NoSuchDevice);
         }
@@ -140,16 +223,19 @@ impl HostImpl {
         let device_index =
             unsafe { ffi::Pa_HostApiDeviceIndexToDeviceIndex(self.host_index, host_device_index) };
         if device_index < 0 {
-            return Err(ffi::PaError::from(device_index).as_result().unwrap_err());
+            return Err(ffi::PaError::from(device_index).into_result().unwrap_err());
         }
         Ok(device_index)
     }
 }
 
+/// Devices and streams hold a handle to their host, so it's only dropped once they all are, and
+/// Portaudio is never terminated under an open stream.
 impl Drop for HostImpl {
     fn drop(&mut self) {
         let _guard = global_lock();
-        unsafe { ffi::Pa_Terminate() }.as_result().unwrap();
+        // Nothing's left to report a failure to, and panicking in a drop could abort.
+        unsafe { ffi::Pa_Terminate() }.into_result().ok();
     }
 }
 
@@ -171,6 +257,32 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn lists_devices() -> Result<()> {
+        begin!();
+        let mut host = Host::with_default_backend()?;
+        let devices = host.devices()?;
+        let default_name = host.default_output_device()?.name().to_string();
+        assert!(devices.iter().any(|device| device.name() == default_name));
+        Ok(())
+    }
+
+    #[test]
+    fn lists_host_apis() -> Result<()> {
+        begin!();
+        let host_apis = Host::host_apis()?;
+        let default = host_apis
+            .iter()
+            .find(|host_api| host_api.is_default)
+            .ok_or(Error::BackendUnavailable)?;
+        assert_eq!(Host::with_default_backend()?.name(), default.name);
+        assert_eq!(
+            Host::with_backend(default.backend)?.backend(),
+            default.backend
+        );
+        Ok(())
+    }
+
     #[test]
     fn handles_invalid_backend() {
         begin!();
@@ -189,7 +301,7 @@ mod tests {
     fn internal_handles_invalid_host_index() {
         begin!();
         let _guard = global_lock();
-        unsafe { ffi::Pa_Initialize() }.as_result().unwrap();
+        unsafe { ffi::Pa_Initialize() }.into_result().unwrap();
         let mut host = HostImpl::new();
         assert_eq!(
             host.init_with_pa_host_index(100_000, _guard),
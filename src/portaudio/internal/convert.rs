@@ -0,0 +1,427 @@
+//! Formats the device doesn't take. Their streams run the device in a format it does take, and
+//! their callbacks convert.
+use libportaudio_sys as ffi;
+use sample::conv::Duplex;
+use sample::Sample;
+use std::any::TypeId;
+use std::marker::PhantomData;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::portaudio::internal::dither::Dither;
+use crate::portaudio::internal::simd;
+use crate::stream_options::{Callback, DitherMode, Format, InputCallback};
+
+/// The format the device runs in for streams of `format`, for the formats Portaudio doesn't have.
+pub fn device_format(format: Format) -> Format {
+    match format {
+        Format::F64 => Format::F32,
+        Format::I24In32 => Format::I32,
+        Format::U16 => Format::I16,
+        Format::U32 => Format::I32,
+        format => format,
+    }
+}
+
+/// The formats the device may run in for streams of `format`, in order of preference: Its
+/// [`device_format`], and then the formats most devices take. Packed 24-bit samples have no Rust
+/// type to convert from.
+pub fn device_formats(format: Format) -> Vec<Format> {
+    let mut formats = vec![device_format(format)];
+    if format != Format::I24 && format != Format::I24In32 {
+        for &fallback in &[Format::F32, Format::I32, Format::I16] {
+            if !formats.contains(&fallback) {
+                formats.push(fallback);
+            }
+        }
+    }
+    formats
+}
+
+/// Converts samples of the stream's format to and from the device's format.
+pub trait Conversion: 'static {
+    type Sample: Copy + Default + Send;
+    type DeviceSample: Copy;
+
+    fn to_device(sample: Self::Sample) -> Self::DeviceSample;
+    fn from_device(sample: Self::DeviceSample) -> Self::Sample;
+
+    /// Converts `samples` into `output`, which is as long. Conversions with SIMD loops (see
+    /// [`simd`]) run them.
+    fn to_device_slice(samples: &[Self::Sample], output: &mut [Self::DeviceSample]) {
+        for (output, &sample) in output.iter_mut().zip(samples) {
+            *output = Self::to_device(sample);
+        }
+    }
+
+    /// Converts `input` into `samples`, which is as long.
+    fn from_device_slice(input: &[Self::DeviceSample], samples: &mut [Self::Sample]) {
+        for (sample, &input) in samples.iter_mut().zip(input) {
+            *sample = Self::from_device(input);
+        }
+    }
+
+    /// The device sample's step, in the stream's units, if converting to it drops bits that
+    /// should be dithered.
+    fn dither_step() -> Option<f64> {
+        None
+    }
+
+    /// Rounds the interleaved `samples` to device steps with `dither`, so that they convert
+    /// exactly with [`to_device_slice`](Conversion::to_device_slice).
+    fn dither_slice(_samples: &mut [Self::Sample], _n_channels: usize, _dither: &mut Dither) {}
+}
+
+/// Converts between two sample types, scaling to the device type's range (e.g. `f32`s in
+/// `-1.0..1.0` to the whole range of `i16`s). Unsigned samples are centered on their midpoint.
+pub struct Scale<S, D>(PhantomData<fn() -> (S, D)>);
+
+impl<S, D> Conversion for Scale<S, D>
+where
+    S: Sample + Duplex<D> + Duplex<f64> + Default + Send + 'static,
+    D: Sample + Duplex<f64> + 'static,
+{
+    type Sample = S;
+    type DeviceSample = D;
+
+    fn to_device(sample: S) -> D {
+        sample.to_sample()
+    }
+
+    fn from_device(sample: D) -> S {
+        S::from_sample(sample)
+    }
+
+    fn to_device_slice(samples: &[S], output: &mut [D]) {
+        let n_converted = convert_simd(samples, output);
+        for (output, &sample) in output[n_converted..]
+            .iter_mut()
+            .zip(&samples[n_converted..])
+        {
+            *output = sample.to_sample();
+        }
+    }
+
+    fn from_device_slice(input: &[D], samples: &mut [S]) {
+        let n_converted = convert_simd(input, samples);
+        for (sample, &input) in samples[n_converted..].iter_mut().zip(&input[n_converted..]) {
+            *sample = S::from_sample(input);
+        }
+    }
+
+    /// Only float samples are finer than the 16-bit devices converted streams fall back to.
+    fn dither_step() -> Option<f64> {
+        let is_float =
+            TypeId::of::<S>() == TypeId::of::<f32>() || TypeId::of::<S>() == TypeId::of::<f64>();
+        if is_float && TypeId::of::<D>() == TypeId::of::<i16>() {
+            Some(1.0 / 32768.0)
+        } else {
+            None
+        }
+    }
+
+    fn dither_slice(samples: &mut [S], n_channels: usize, dither: &mut Dither) {
+        // Each channel's noise (and shaped error) follows from its last sample's, so this can't
+        // run in SIMD lanes like the conversion after it.
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = S::from_sample(dither.quantize((*sample).to_sample(), i % n_channels));
+        }
+    }
+}
+
+/// Converts the first of `samples` into `output` with SIMD, if there's a loop for their types, and
+/// returns how many it converted.
+fn convert_simd<S: 'static, D: 'static>(samples: &[S], output: &mut [D]) -> usize {
+    if let (Some(samples), Some(output)) = (cast::<_, f32>(samples), cast_mut::<_, i16>(output)) {
+        return simd::f32_to_i16(samples, output);
+    }
+    if let (Some(samples), Some(output)) = (cast::<_, i16>(samples), cast_mut::<_, f32>(output)) {
+        return simd::i16_to_f32(samples, output);
+    }
+    if let (Some(samples), Some(output)) = (cast::<_, f32>(samples), cast_mut::<_, i32>(output)) {
+        return simd::f32_to_i32(samples, output);
+    }
+    if let (Some(samples), Some(output)) = (cast::<_, i32>(samples), cast_mut::<_, f32>(output)) {
+        return simd::i32_to_f32(samples, output);
+    }
+    0
+}
+
+/// `slice`, if it's a slice of `T`s.
+fn cast<S: 'static, T: 'static>(slice: &[S]) -> Option<&[T]> {
+    if TypeId::of::<S>() == TypeId::of::<T>() {
+        Some(unsafe { std::slice::from_raw_parts(slice.as_ptr().cast(), slice.len()) })
+    } else {
+        None
+    }
+}
+
+fn cast_mut<S: 'static, T: 'static>(slice: &mut [S]) -> Option<&mut [T]> {
+    if TypeId::of::<S>() == TypeId::of::<T>() {
+        Some(unsafe { std::slice::from_raw_parts_mut(slice.as_mut_ptr().cast(), slice.len()) })
+    } else {
+        None
+    }
+}
+
+/// [`Format::I24In32`] to [`Format::I32`], by moving the sample to the high 3 bytes.
+pub enum I24In32ToI32 {}
+
+impl Conversion for I24In32ToI32 {
+    type Sample = i32;
+    type DeviceSample = i32;
+
+    fn to_device(sample: i32) -> i32 {
+        sample << 8
+    }
+
+    fn from_device(sample: i32) -> i32 {
+        sample >> 8
+    }
+
+    fn to_device_slice(samples: &[i32], output: &mut [i32]) {
+        let n_converted = simd::shift_left_8(samples, output);
+        for (output, &sample) in output[n_converted..]
+            .iter_mut()
+            .zip(&samples[n_converted..])
+        {
+            *output = Self::to_device(sample);
+        }
+    }
+
+    fn from_device_slice(input: &[i32], samples: &mut [i32]) {
+        let n_converted = simd::shift_right_8(input, samples);
+        for (sample, &input) in samples[n_converted..].iter_mut().zip(&input[n_converted..]) {
+            *sample = Self::from_device(input);
+        }
+    }
+}
+
+/// Wraps the callback of a converted stream. The callback sees `buffer`, which is converted from
+/// or to the device's buffer.
+pub struct ConvertingWrapper<C, Conv: Conversion> {
+    callback: C,
+    buffer: Vec<Conv::Sample>,
+    n_channels: usize,
+    /// `None` for input streams, and streams that don't dither.
+    dither: Option<Dither>,
+}
+
+impl<C, Conv: Conversion> ConvertingWrapper<C, Conv> {
+    pub fn new(
+        callback: C,
+        frames_per_buffer: Option<i32>,
+        n_channels: i32,
+    ) -> ConvertingWrapper<C, Conv> {
+        ConvertingWrapper {
+            callback,
+            buffer: Vec::with_capacity(reserved_frames(frames_per_buffer) * n_channels as usize),
+            n_channels: n_channels as usize,
+            dither: None,
+        }
+    }
+
+    /// Dithers the converted output, if the device format has fewer bits.
+    pub fn dithered(self, mode: DitherMode) -> ConvertingWrapper<C, Conv> {
+        ConvertingWrapper {
+            dither: Conv::dither_step().and_then(|step| Dither::new(mode, step, self.n_channels)),
+            ..self
+        }
+    }
+}
+
+/// How many frames callbacks' buffers are allocated for when Portaudio picks the buffer size,
+/// which is more than it picks on the usual host APIs. Buffers only grow in the callback (once)
+/// if it asks for more.
+const RESERVED_FRAMES: usize = 8192;
+
+/// How many frames to allocate callbacks' buffers for when the stream opens, so that the callback
+/// doesn't have to.
+pub fn reserved_frames(frames_per_buffer: Option<i32>) -> usize {
+    frames_per_buffer.map_or(RESERVED_FRAMES, |frames| frames as usize)
+}
+
+/// Resizes `buffer` to hold `frame_count` frames, and returns it as frames.
+pub fn resize_as_frames<Frame, Sample: Copy + Default>(
+    buffer: &mut Vec<Sample>,
+    frame_count: usize,
+) -> &mut [Frame] {
+    let n_samples = frame_count * std::mem::size_of::<Frame>() / std::mem::size_of::<Sample>();
+    buffer.resize(n_samples, Sample::default());
+    // Frames are arrays of samples (see validate_frame_size).
+    unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut Frame, frame_count) }
+}
+
+pub extern "C-unwind" fn outstream_callback<Frame, Conv: Conversion>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut ConvertingWrapper<Callback<Frame>, Conv>).as_mut() }
+        .expect("Could not create ConvertingWrapper from user_data.");
+
+    (wrapper.callback)(resize_as_frames(&mut wrapper.buffer, frame_count as usize));
+    let output = unsafe {
+        std::slice::from_raw_parts_mut(output as *mut Conv::DeviceSample, wrapper.buffer.len())
+    };
+    if let Some(dither) = &mut wrapper.dither {
+        Conv::dither_slice(&mut wrapper.buffer, wrapper.n_channels, dither);
+    }
+    Conv::to_device_slice(&wrapper.buffer, output);
+    0
+}
+
+pub extern "C-unwind" fn instream_callback<Frame, Conv: Conversion>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper =
+        unsafe { (user_data as *mut ConvertingWrapper<InputCallback<Frame>, Conv>).as_mut() }
+            .expect("Could not create ConvertingWrapper from user_data.");
+
+    resize_as_frames::<Frame, _>(&mut wrapper.buffer, frame_count as usize);
+    let input = unsafe {
+        std::slice::from_raw_parts(input as *const Conv::DeviceSample, wrapper.buffer.len())
+    };
+    Conv::from_device_slice(input, &mut wrapper.buffer);
+    (wrapper.callback)(resize_as_frames(&mut wrapper.buffer, frame_count as usize));
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn converts_f64_frames() {
+        let callback: Callback<[f64; 2]> = Box::new(|frames| {
+            for (i, frame) in frames.iter_mut().enumerate() {
+                *frame = [i as f64, -0.5];
+            }
+        });
+        let mut wrapper = ConvertingWrapper::<_, Scale<f64, f32>>::new(callback, Some(2), 2);
+        let mut output = [0.0f32; 6];
+        outstream_callback::<[f64; 2], Scale<f64, f32>>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            3,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(output, [0.0, -0.5, 1.0, -0.5, 2.0, -0.5]);
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let callback: InputCallback<[f64; 1]> = Box::new({
+            let captured = Arc::clone(&captured);
+            move |frames| captured.lock().unwrap().extend_from_slice(frames)
+        });
+        let mut wrapper = ConvertingWrapper::<_, Scale<f64, f32>>::new(callback, None, 1);
+        let input = [0.25f32, 1.0];
+        instream_callback::<[f64; 1], Scale<f64, f32>>(
+            input.as_ptr() as *const c_void,
+            std::ptr::null_mut(),
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(*captured.lock().unwrap(), vec![[0.25], [1.0]]);
+    }
+
+    #[test]
+    fn converts_16_bit_output() {
+        let callback: Callback<[f32; 2]> = Box::new(|frames| {
+            for (i, frame) in frames.iter_mut().enumerate() {
+                *frame = [i as f32 / 32.0 - 1.0, 1.5];
+            }
+        });
+        let mut wrapper = ConvertingWrapper::<_, Scale<f32, i16>>::new(callback, None, 2)
+            .dithered(DitherMode::Off);
+        // Long enough for the SIMD loop, with a scalar tail.
+        let mut output = [0i16; 2 * 67];
+        outstream_callback::<[f32; 2], Scale<f32, i16>>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            67,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        for (i, frame) in output.chunks(2).enumerate() {
+            let sample = Scale::<f32, i16>::to_device(i as f32 / 32.0 - 1.0);
+            assert_eq!(frame, [sample, i16::MAX]);
+        }
+    }
+
+    #[test]
+    fn dithers_16_bit_output() {
+        let callback: Callback<[f32; 1]> =
+            Box::new(|frames| frames.iter_mut().for_each(|frame| *frame = [0.1 / 32768.0]));
+        let mut wrapper = ConvertingWrapper::<_, Scale<f32, i16>>::new(callback, None, 1)
+            .dithered(DitherMode::Tpdf);
+        let mut output = [0i16; 64];
+        outstream_callback::<[f32; 1], Scale<f32, i16>>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            64,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        // Truncating would have silenced the whole buffer.
+        assert!(output.iter().any(|&sample| sample != 0));
+        assert!(output.iter().all(|&sample| sample.abs() <= 1));
+        assert_eq!(Scale::<i16, i32>::dither_step(), None);
+    }
+
+    #[test]
+    fn flips_unsigned_sign_bits() {
+        assert_eq!(Scale::<u16, i16>::to_device(0x8000), 0);
+        assert_eq!(Scale::<u16, i16>::to_device(0), i16::MIN);
+        assert_eq!(Scale::<u16, i16>::from_device(i16::MAX), u16::MAX);
+        assert_eq!(Scale::<u32, i32>::to_device(0x8000_0000), 0);
+        assert_eq!(Scale::<u32, i32>::from_device(i32::MIN), 0);
+    }
+
+    #[test]
+    fn scales_samples() {
+        assert_eq!(Scale::<f32, i16>::to_device(0.5), 0x4000);
+        assert_eq!(Scale::<f32, i16>::to_device(-1.0), i16::MIN);
+        // Saturates instead of wrapping around.
+        assert_eq!(Scale::<f32, i16>::to_device(2.0), i16::MAX);
+        assert_eq!(Scale::<f32, i16>::from_device(i16::MIN), -1.0);
+        assert_eq!(Scale::<i16, f32>::to_device(0x4000), 0.5);
+        assert_eq!(Scale::<u8, i32>::to_device(0x80), 0);
+        assert_eq!(Scale::<i16, i32>::from_device(0x1234_5678), 0x1234);
+    }
+
+    #[test]
+    fn prefers_the_native_device_format() {
+        assert_eq!(
+            device_formats(Format::F64),
+            vec![Format::F32, Format::I32, Format::I16]
+        );
+        assert_eq!(
+            device_formats(Format::I16),
+            vec![Format::I16, Format::F32, Format::I32]
+        );
+        assert_eq!(device_formats(Format::I24), vec![Format::I24]);
+        assert_eq!(device_formats(Format::I24In32), vec![Format::I32]);
+    }
+
+    #[test]
+    fn aligns_24_bit_samples() {
+        assert_eq!(I24In32ToI32::to_device(-0x80_0000), i32::MIN);
+        assert_eq!(I24In32ToI32::to_device(0x7f_ffff), 0x7fff_ff00);
+        assert_eq!(I24In32ToI32::from_device(-1), -1);
+        assert_eq!(I24In32ToI32::from_device(0x1234_5678), 0x12_3456);
+    }
+}
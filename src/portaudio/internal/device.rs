@@ -1,14 +1,30 @@
 use libportaudio_sys as ffi;
 use std::convert::TryInto;
 
+use crate::capabilities::{DeviceCapabilities, STANDARD_SAMPLE_RATES};
 use crate::error::{Error, Result};
+#[cfg(all(windows, feature = "asio"))]
+use crate::portaudio::asio::AsioBufferSizes;
 use crate::portaudio::device::DeviceHandle;
-use crate::portaudio::host::HostHandle;
+use crate::portaudio::error::PaErrorIntoResult as _;
+use crate::portaudio::global_lock;
+use crate::portaudio::host::{backend_of, HostHandle};
+use crate::portaudio::internal::convert;
 use crate::portaudio::internal::stream::StreamOpenParams;
-use crate::portaudio::stream::{new_outstream, Stream};
+use crate::portaudio::stream::{
+    new_blocking_stream, new_duplex_stream, new_duplex_stream_with_info, new_dynamic_instream,
+    new_dynamic_outstream, new_instream, new_instream_with_info, new_outstream,
+    new_outstream_with_info, new_planar_instream, new_planar_outstream, new_raw_instream,
+    new_raw_outstream, Stream,
+};
 use crate::portaudio::{LockGuard, RawPtr};
-use crate::stream_options::StreamOptions;
-use crate::SampleRate;
+use crate::stream_options::{
+    BufferSize, CallbackKind, DuplexCallback, DuplexInfoCallback, DynamicInput, DynamicOutput,
+    Input, InputWithInfo, LatencyHint, NoCallback, OutputWithInfo, PlanarInput, PlanarOutput,
+    RawInput, RawOutput, StreamOptions,
+};
+use crate::surround::{self, ChannelPosition};
+use crate::{Backend, Format, SampleRate};
 
 pub struct Device {
     pub name: String,
@@ -34,9 +50,9 @@ impl Device {
         };
         let name = unsafe { std::ffi::CStr::from_ptr(device_info.name) }
             .to_str()
-            .or(Err(Error::Unknown(
-                "Could not convert device name to UTF-8.",
-            )))?
+            .map_err(|error| {
+                Error::from_source(None, "Could not convert device name to UTF-8.", error)
+            })?
             .to_string();
         Ok(Device {
             name,
@@ -46,25 +62,387 @@ impl Device {
         })
     }
 
-    pub fn open_outstream<Frame>(
+    pub fn index(&self) -> i32 {
+        self.index
+    }
+
+    /// The sample rate the device runs at when streams don't ask for one.
+    pub fn default_sample_rate(&self) -> i32 {
+        unsafe { self.info.as_ref().unwrap() }.defaultSampleRate as i32
+    }
+
+    /// How many channels the device has for output streams, or for input streams.
+    pub fn max_channels(&self, is_output: bool) -> i32 {
+        let info = unsafe { self.info.as_ref().unwrap() };
+        if is_output {
+            info.maxOutputChannels
+        } else {
+            info.maxInputChannels
+        }
+    }
+
+    /// The positions of the device's channels for output streams, or for input streams, in their
+    /// order. Portaudio doesn't report them, so they are the usual layout for the device's channel
+    /// count on its host API. `None` if there isn't one.
+    pub fn channel_positions(&self, is_output: bool) -> Option<Vec<ChannelPosition>> {
+        let info = unsafe { self.info.as_ref().unwrap() };
+        let backend = unsafe { ffi::Pa_GetHostApiInfo(info.hostApi).as_ref() }
+            .and_then(|host_info| backend_of(host_info.type_))
+            .unwrap_or(Backend::None);
+        surround::device_positions(backend, self.max_channels(is_output))
+    }
+
+    /// Checks the standard sample rates, and the formats Portaudio takes, with each of them. Output
+    /// streams are checked on devices that have any, and input streams on the others.
+    pub fn capabilities(&self) -> DeviceCapabilities {
+        let guard = global_lock();
+        let is_output = self.max_channels(true) > 0;
+        let n_channels = self.max_channels(is_output);
+        let mut capabilities = DeviceCapabilities {
+            max_output_channels: self.max_channels(true),
+            max_input_channels: self.max_channels(false),
+            ..Default::default()
+        };
+        if n_channels == 0 {
+            return capabilities;
+        }
+        let mut sample_rates = STANDARD_SAMPLE_RATES.to_vec();
+        if !sample_rates.contains(&self.default_sample_rate()) {
+            sample_rates.push(self.default_sample_rate());
+            sample_rates.sort_unstable();
+        }
+        let formats = [
+            Format::F32,
+            Format::I32,
+            Format::I24,
+            Format::I16,
+            Format::I8,
+            Format::U8,
+        ];
+        let supported = |format, sample_rate| {
+            self.is_supported(is_output, format, n_channels, sample_rate, &guard)
+        };
+        capabilities.sample_rates = sample_rates
+            .into_iter()
+            .filter(|&sample_rate| formats.iter().any(|&format| supported(format, sample_rate)))
+            .collect();
+        capabilities.formats = formats
+            .iter()
+            .copied()
+            .filter(|&format| {
+                (capabilities.sample_rates.iter())
+                    .any(|&sample_rate| supported(format, sample_rate))
+            })
+            .collect();
+        capabilities
+    }
+
+    /// Whether Portaudio takes the options as they are, for either direction if `K` doesn't tell.
+    pub fn supports<F, K: CallbackKind>(&self, options: &StreamOptions<F, K>) -> bool {
+        let guard = global_lock();
+        let directions = match K::IS_OUTPUT {
+            Some(is_output) => vec![is_output],
+            None => vec![true, false],
+        };
+        directions.into_iter().any(|is_output| {
+            match self.options_to_stream_params(options, is_output) {
+                Ok((params, sample_rate)) => {
+                    is_params_supported(&params, is_output, sample_rate, &guard)
+                }
+                Err(_) => false,
+            }
+        })
+    }
+
+    /// Whether Portaudio takes streams in the format, for either direction.
+    pub fn supports_config(&self, format: Format, n_channels: i32, sample_rate: i32) -> bool {
+        let guard = global_lock();
+        [true, false]
+            .iter()
+            .any(|&is_output| self.is_supported(is_output, format, n_channels, sample_rate, &guard))
+    }
+
+    /// Whether Portaudio can open streams in the format, without opening one.
+    fn is_supported(
+        &self,
+        is_output: bool,
+        format: Format,
+        n_channels: i32,
+        sample_rate: i32,
+        guard: &LockGuard,
+    ) -> bool {
+        let params = ffi::PaStreamParameters {
+            device: self.index,
+            channelCount: n_channels,
+            sampleFormat: match format.try_into() {
+                Ok(format) => format,
+                Err(_) => return false,
+            },
+            suggestedLatency: 0.0,
+            hostApiSpecificStreamInfo: std::ptr::null_mut(),
+        };
+        is_params_supported(&params, is_output, sample_rate, guard)
+    }
+
+    pub fn open_outstream<Frame: 'static>(
         &self,
         options: StreamOptions<Frame>,
         device_handle: DeviceHandle,
     ) -> Result<Stream<Frame>> {
         // Early-out if the stream spec is not supported?
         // self.is_stream_spec_supported(&options, true, &global_lock())?;
-        let (params, sample_rate) = self.options_to_stream_params(&options)?;
-        let open_params = StreamOpenParams {
-            user_options: options,
-            pa_params: params,
-            sample_rate,
-        };
+        let mut options = options;
+        options.frames_per_buffer = self.negotiate_frames_per_buffer(options.frames_per_buffer)?;
+        let (params, sample_rate) = self.options_to_stream_params(&options, true)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
         new_outstream(open_params, device_handle)
     }
 
-    fn options_to_stream_params<F>(
+    pub fn open_input_stream<Frame: 'static>(
+        &self,
+        options: StreamOptions<Frame, Input>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Frame>> {
+        let mut options = options;
+        options.frames_per_buffer = self.negotiate_frames_per_buffer(options.frames_per_buffer)?;
+        let (params, sample_rate) = self.options_to_stream_params(&options, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_instream(open_params, device_handle)
+    }
+
+    pub fn open_planar_outstream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, PlanarOutput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, true, true)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_planar_outstream(open_params, device_handle)
+    }
+
+    pub fn open_planar_input_stream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, PlanarInput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, false, true)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_planar_instream(open_params, device_handle)
+    }
+
+    pub fn open_dynamic_outstream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, DynamicOutput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, true, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_dynamic_outstream(open_params, device_handle)
+    }
+
+    pub fn open_dynamic_input_stream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, DynamicInput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, false, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_dynamic_instream(open_params, device_handle)
+    }
+
+    pub fn open_raw_outstream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, RawOutput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, true, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_raw_outstream(open_params, device_handle)
+    }
+
+    pub fn open_raw_input_stream<Sample: 'static>(
+        &self,
+        options: StreamOptions<Sample, RawInput>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Sample>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, false, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_raw_instream(open_params, device_handle)
+    }
+
+    pub fn open_outstream_with_info<Frame: 'static>(
+        &self,
+        options: StreamOptions<Frame, OutputWithInfo>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Frame>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, true, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_outstream_with_info(open_params, device_handle)
+    }
+
+    pub fn open_input_stream_with_info<Frame: 'static>(
+        &self,
+        options: StreamOptions<Frame, InputWithInfo>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Frame>> {
+        let (params, sample_rate, options) = self.sample_stream_params(options, false, false)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_instream_with_info(open_params, device_handle)
+    }
+
+    pub fn open_blocking_stream<Frame: 'static>(
+        &self,
+        options: StreamOptions<Frame, NoCallback>,
+        is_output: bool,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Frame>> {
+        // Only callbacks convert, route, and lay out channels.
+        options.validate_layout(false)?;
+        if convert::device_format(options.format) != options.format {
+            return Err(Error::IncompatibleFormat(options.format));
+        }
+        let mut options = options;
+        options.frames_per_buffer = self.negotiate_frames_per_buffer(options.frames_per_buffer)?;
+        let (params, sample_rate) = self.options_to_stream_params(&options, is_output)?;
+        let open_params = StreamOpenParams::new(options, params, sample_rate)?;
+        new_blocking_stream(open_params, is_output, device_handle)
+    }
+
+    pub fn open_duplex_stream<InFrame: 'static, OutFrame: 'static>(
+        &self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexCallback<InFrame, OutFrame>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        let (input, output) = self.duplex_open_params(input, output)?;
+        new_duplex_stream(input, output, callback, device_handle)
+    }
+
+    pub fn open_duplex_stream_with_info<InFrame: 'static, OutFrame: 'static>(
         &self,
-        options: &StreamOptions<F>,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+        callback: DuplexInfoCallback<InFrame, OutFrame>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<(InFrame, OutFrame)>> {
+        let (input, output) = self.duplex_open_params(input, output)?;
+        new_duplex_stream_with_info(input, output, callback, device_handle)
+    }
+
+    /// Checks the halves of a duplex stream against each other, and resolves their parameters.
+    #[allow(clippy::type_complexity)]
+    fn duplex_open_params<InFrame, OutFrame>(
+        &self,
+        input: StreamOptions<InFrame, NoCallback>,
+        output: StreamOptions<OutFrame, NoCallback>,
+    ) -> Result<(
+        StreamOpenParams<InFrame, NoCallback>,
+        StreamOpenParams<OutFrame, NoCallback>,
+    )> {
+        // Portaudio runs both halves of the stream with a single clock and buffer size.
+        if input.frames_per_buffer != output.frames_per_buffer {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        input.validate_layout(false)?;
+        let (mut input, mut output) = (input, output);
+        // The stream is watched and reconnected by its output's options.
+        let watchdog = output.watchdog.take();
+        let reconnect = std::mem::take(&mut output.reconnect);
+        output.validate_layout(false)?;
+        for format in &[input.format, output.format] {
+            if convert::device_format(*format) != *format {
+                return Err(Error::IncompatibleFormat(*format));
+            }
+        }
+        input.frames_per_buffer = self.negotiate_frames_per_buffer(input.frames_per_buffer)?;
+        output.frames_per_buffer = input.frames_per_buffer;
+        let (in_params, in_sample_rate) = self.options_to_stream_params(&input, false)?;
+        let (out_params, out_sample_rate) = self.options_to_stream_params(&output, true)?;
+        if in_sample_rate != out_sample_rate {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        let input = StreamOpenParams::new(input, in_params, in_sample_rate)?;
+        let mut output = StreamOpenParams::new(output, out_params, out_sample_rate)?;
+        output.open.watchdog = watchdog;
+        output.open.reconnect = reconnect;
+        Ok((input, output))
+    }
+
+    /// Returns the buffer sizes supported by the device's ASIO driver.
+    #[cfg(all(windows, feature = "asio"))]
+    pub fn asio_buffer_sizes(&self) -> Result<AsioBufferSizes> {
+        let guard = global_lock();
+        if !self.is_asio(&guard) {
+            return Err(Error::BackendUnavailable);
+        }
+        AsioBufferSizes::of_device(self.index, &guard)
+    }
+
+    /// Opens the device's ASIO driver control panel.
+    #[cfg(all(windows, feature = "asio"))]
+    pub fn show_asio_control_panel(&self) -> Result<()> {
+        let guard = global_lock();
+        if !self.is_asio(&guard) {
+            return Err(Error::BackendUnavailable);
+        }
+        unsafe { ffi::PaAsio_ShowControlPanel(self.index, std::ptr::null_mut()) }.into_result()?;
+        Ok(())
+    }
+
+    #[cfg(all(windows, feature = "asio"))]
+    fn is_asio(&self, _guard: &LockGuard) -> bool {
+        let info = unsafe { self.info.as_ref().unwrap() };
+        unsafe { ffi::Pa_GetHostApiInfo(info.hostApi).as_ref() }
+            .is_some_and(|host_info| host_info.type_ == ffi::PaHostApiTypeId::paASIO)
+    }
+
+    /// ASIO drivers only run at a handful of buffer sizes. On ASIO devices, picks the driver's
+    /// preferred size if the request allows it, or else snaps the requested size to the nearest
+    /// supported one. Other devices get the request unchanged.
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn negotiate_frames_per_buffer(&self, requested: BufferSize) -> Result<BufferSize> {
+        #[cfg(all(windows, feature = "asio"))]
+        {
+            let guard = global_lock();
+            if self.is_asio(&guard) {
+                let sizes = AsioBufferSizes::of_device(self.index, &guard)?;
+                return Ok(match requested.frames_preferring(sizes.preferred) {
+                    // Leave invalid sizes for options_to_stream_params to reject.
+                    Some(frames_per_buffer) if frames_per_buffer <= 0 => requested,
+                    Some(frames_per_buffer) => BufferSize::Exact(sizes.nearest(frames_per_buffer)),
+                    None => BufferSize::Exact(sizes.preferred),
+                });
+            }
+        }
+        Ok(requested)
+    }
+
+    /// Like options_to_stream_params, but for planar, dynamic, raw, and info streams, whose callbacks
+    /// don't convert. Also returns the options, with the negotiated frames_per_buffer.
+    #[allow(clippy::type_complexity)]
+    fn sample_stream_params<Sample, K: CallbackKind>(
+        &self,
+        options: StreamOptions<Sample, K>,
+        is_output: bool,
+        is_planar: bool,
+    ) -> Result<(ffi::PaStreamParameters, i32, StreamOptions<Sample, K>)> {
+        if convert::device_format(options.format) != options.format {
+            return Err(Error::IncompatibleFormat(options.format));
+        }
+        let mut options = options;
+        options.frames_per_buffer = self.negotiate_frames_per_buffer(options.frames_per_buffer)?;
+        let (mut params, sample_rate) = self.options_to_stream_params(&options, is_output)?;
+        if is_planar {
+            params.sampleFormat |= ffi::PaSampleFormat::paNonInterleaved;
+        }
+        Ok((params, sample_rate, options))
+    }
+
+    fn options_to_stream_params<F, K: CallbackKind>(
+        &self,
+        options: &StreamOptions<F, K>,
+        is_output: bool,
     ) -> Result<(ffi::PaStreamParameters, i32)> {
         let info = unsafe { self.info.as_ref().unwrap() };
         let sample_rate = match options.sample_rate {
@@ -72,19 +450,27 @@ impl Device {
             SampleRate::DeviceDefault | SampleRate::NearestTo(_) => info.defaultSampleRate as i32,
             _ => panic!("Non-exhaustive sample rate."),
         };
-        let latency = if let Some(frames_per_buffer) = options.frames_per_buffer {
+        if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
             if frames_per_buffer <= 0 {
                 return Err(Error::InvalidFramesPerBuffer);
             }
-            frames_per_buffer_to_latency(frames_per_buffer, sample_rate)
-        } else {
-            info.defaultHighOutputLatency
+        }
+        let latency = match (options.latency, options.frames_per_buffer.frames()) {
+            (LatencyHint::Exact(latency), _) => latency.as_secs_f64(),
+            (LatencyHint::Low, _) if is_output => info.defaultLowOutputLatency,
+            (LatencyHint::Low, _) => info.defaultLowInputLatency,
+            (_, Some(frames_per_buffer)) => {
+                frames_per_buffer_to_latency(frames_per_buffer, sample_rate)
+            }
+            (_, None) if is_output => info.defaultHighOutputLatency,
+            (_, None) => info.defaultHighInputLatency,
         };
+        let format = convert::device_format(options.format);
         Ok((
             ffi::PaStreamParameters {
                 device: self.index,
                 channelCount: options.n_channels,
-                sampleFormat: options.format.try_into()?,
+                sampleFormat: format.try_into()?,
                 suggestedLatency: latency,
                 hostApiSpecificStreamInfo: std::ptr::null_mut(),
             },
@@ -93,6 +479,22 @@ impl Device {
     }
 }
 
+fn is_params_supported(
+    params: &ffi::PaStreamParameters,
+    is_output: bool,
+    sample_rate: i32,
+    _guard: &LockGuard,
+) -> bool {
+    let (input_params, output_params) = if is_output {
+        (std::ptr::null(), params as *const _)
+    } else {
+        (params as *const _, std::ptr::null())
+    };
+    unsafe { ffi::Pa_IsFormatSupported(input_params, output_params, sample_rate.into()) }
+        .into_result()
+        .is_ok()
+}
+
 fn frames_per_buffer_to_latency(frames_per_buffer: i32, sample_rate: i32) -> f64 {
     f64::from(frames_per_buffer) / f64::from(sample_rate)
 }
@@ -0,0 +1,72 @@
+//! Dither for streams converted to devices with fewer bits, so that quiet material fades into
+//! noise instead of the distortion truncating it would add.
+use crate::stream_options::DitherMode;
+
+/// Rounds samples to the device's steps, adding noise first.
+pub struct Dither {
+    mode: DitherMode,
+    /// The device sample's step, in the stream's units.
+    step: f64,
+    /// A xorshift generator's state. The callback can't wait on a system generator.
+    state: u32,
+    /// The last rounding error of each channel, which shaped dither subtracts from the next
+    /// sample. Always 0 for TPDF dither.
+    errors: Vec<f64>,
+}
+
+impl Dither {
+    /// `None` for [`DitherMode::Off`].
+    pub fn new(mode: DitherMode, step: f64, n_channels: usize) -> Option<Dither> {
+        if mode == DitherMode::Off {
+            return None;
+        }
+        Some(Dither {
+            mode,
+            step,
+            state: 0x9e37_79b9,
+            errors: vec![0.0; n_channels],
+        })
+    }
+
+    /// Uniform in `0.0..1.0`.
+    fn random(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        f64::from(self.state) / (f64::from(u32::MAX) + 1.0)
+    }
+
+    /// Rounds `sample` to a device step, after adding triangular noise of a step either way.
+    pub fn quantize(&mut self, sample: f64, channel: usize) -> f64 {
+        let target = sample - self.errors[channel];
+        let noise = self.random() - self.random();
+        let quantized = (target / self.step + noise).round() * self.step;
+        if self.mode == DitherMode::Shaped {
+            // Pushes the rounding error up in frequency, where it's harder to hear.
+            self.errors[channel] = quantized - target;
+        }
+        quantized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantize(mode: DitherMode, sample: f64) -> Vec<f64> {
+        let mut dither = Dither::new(mode, 0.25, 1).unwrap();
+        (0..1000).map(|_| dither.quantize(sample, 0)).collect()
+    }
+
+    #[test]
+    fn rounds_to_device_steps() {
+        for &mode in &[DitherMode::Tpdf, DitherMode::Shaped] {
+            let samples = quantize(mode, 0.1);
+            assert!(samples.iter().all(|sample| (sample * 4.0).fract() == 0.0));
+            // Noise averages out to the sample, which is between steps.
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+            assert!((mean - 0.1).abs() < 0.01, "{:?}: {}", mode, mean);
+        }
+        assert!(Dither::new(DitherMode::Off, 0.25, 1).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+//! Interleaved streams whose channel count is only known at runtime. Their callbacks get the
+//! device's buffer as samples, along with the channel count.
+use libportaudio_sys as ffi;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::stream_options::{DynamicCallback, DynamicInputCallback};
+
+/// Wraps the callback of a dynamic stream.
+pub struct DynamicWrapper<C> {
+    callback: C,
+    n_channels: usize,
+}
+
+impl<C> DynamicWrapper<C> {
+    pub fn new(callback: C, n_channels: i32) -> DynamicWrapper<C> {
+        DynamicWrapper {
+            callback,
+            n_channels: n_channels as usize,
+        }
+    }
+}
+
+pub extern "C-unwind" fn outstream_callback<Sample>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut DynamicWrapper<DynamicCallback<Sample>>).as_mut() }
+        .expect("Could not create DynamicWrapper from user_data.");
+
+    let output = unsafe {
+        std::slice::from_raw_parts_mut(
+            output as *mut Sample,
+            frame_count as usize * wrapper.n_channels,
+        )
+    };
+    (wrapper.callback)(output, wrapper.n_channels);
+    0
+}
+
+pub extern "C-unwind" fn instream_callback<Sample>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper =
+        unsafe { (user_data as *mut DynamicWrapper<DynamicInputCallback<Sample>>).as_mut() }
+            .expect("Could not create DynamicWrapper from user_data.");
+
+    let input = unsafe {
+        std::slice::from_raw_parts(
+            input as *const Sample,
+            frame_count as usize * wrapper.n_channels,
+        )
+    };
+    (wrapper.callback)(input, wrapper.n_channels);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_the_channel_count() {
+        let callback: DynamicCallback<i16> = Box::new(|buffer, n_channels| {
+            for frame in buffer.chunks_exact_mut(n_channels) {
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    *sample = channel as i16;
+                }
+            }
+        });
+        let mut wrapper = DynamicWrapper::new(callback, 3);
+        let mut output = [-1i16; 6];
+        outstream_callback::<i16>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(output, [0, 1, 2, 0, 1, 2]);
+    }
+}
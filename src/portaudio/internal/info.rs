@@ -0,0 +1,223 @@
+//! Streams whose callbacks get a [`CallbackInfo`] along with their frames, from what Portaudio
+//! passes its own callbacks.
+use libportaudio_sys as ffi;
+use std::os::raw::{c_ulong, c_void};
+use std::time::Duration;
+
+use crate::stream_options::{
+    CallbackInfo, DuplexInfoCallback, InfoCallback, InfoInputCallback, StreamFlow, StreamStatus,
+};
+
+/// Wraps the callback of a stream with info into a thin pointer.
+pub struct InfoWrapper<C>(pub C);
+
+/// Converts a Portaudio time, in seconds. Some host APIs don't report times, and leave them at 0.
+pub fn to_duration(time: ffi::PaTime) -> Duration {
+    Duration::from_secs_f64(time.max(0.0))
+}
+
+/// The Portaudio callback result for a flow.
+fn callback_result(flow: StreamFlow) -> i32 {
+    use ffi::PaStreamCallbackResult::*;
+    (match flow {
+        StreamFlow::Continue => paContinue,
+        StreamFlow::Complete => paComplete,
+        StreamFlow::Abort => paAbort,
+        _ => paContinue,
+    }) as i32
+}
+
+pub fn stream_status(flags: ffi::PaStreamCallbackFlags) -> StreamStatus {
+    use ffi::PaStreamCallbackFlags as Flags;
+    StreamStatus {
+        input_underflow: flags.contains(Flags::PaInputUnderflow),
+        input_overflow: flags.contains(Flags::PaInputOverflow),
+        output_underflow: flags.contains(Flags::PaOutputUnderflow),
+        output_overflow: flags.contains(Flags::PaOutputOverflow),
+        priming_output: flags.contains(Flags::PaPrimingOutput),
+    }
+}
+
+/// The info of a buffer that's captured, played, or both.
+fn callback_info(
+    time_info: *const ffi::PaStreamCallbackTimeInfo,
+    status_flags: ffi::PaStreamCallbackFlags,
+    is_input: bool,
+    is_output: bool,
+) -> CallbackInfo {
+    let time_info = unsafe { time_info.as_ref() };
+    let time = |time: fn(&ffi::PaStreamCallbackTimeInfo) -> ffi::PaTime| {
+        time_info.map_or(Duration::default(), |time_info| {
+            to_duration(time(time_info))
+        })
+    };
+    CallbackInfo {
+        current_time: time(|time_info| time_info.currentTime),
+        capture_time: if is_input {
+            Some(time(|time_info| time_info.inputBufferAdcTime))
+        } else {
+            None
+        },
+        playback_time: if is_output {
+            Some(time(|time_info| time_info.outputBufferDacTime))
+        } else {
+            None
+        },
+        status: stream_status(status_flags),
+    }
+}
+
+pub extern "C-unwind" fn outstream_callback<Frame>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    time_info: *const ffi::PaStreamCallbackTimeInfo,
+    status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut InfoWrapper<InfoCallback<Frame>>).as_mut() }
+        .expect("Could not create InfoWrapper from user_data.");
+
+    let output =
+        unsafe { std::slice::from_raw_parts_mut(output as *mut Frame, frame_count as usize) };
+    callback_result((wrapper.0)(
+        output,
+        &callback_info(time_info, status_flags, false, true),
+    ))
+}
+
+pub extern "C-unwind" fn instream_callback<Frame>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    time_info: *const ffi::PaStreamCallbackTimeInfo,
+    status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut InfoWrapper<InfoInputCallback<Frame>>).as_mut() }
+        .expect("Could not create InfoWrapper from user_data.");
+
+    let input = unsafe { std::slice::from_raw_parts(input as *const Frame, frame_count as usize) };
+    callback_result((wrapper.0)(
+        input,
+        &callback_info(time_info, status_flags, true, false),
+    ))
+}
+
+pub extern "C-unwind" fn duplex_stream_callback<InFrame, OutFrame>(
+    input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    time_info: *const ffi::PaStreamCallbackTimeInfo,
+    status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper =
+        unsafe { (user_data as *mut InfoWrapper<DuplexInfoCallback<InFrame, OutFrame>>).as_mut() }
+            .expect("Could not create InfoWrapper from user_data.");
+
+    let input =
+        unsafe { std::slice::from_raw_parts(input as *const InFrame, frame_count as usize) };
+    let output =
+        unsafe { std::slice::from_raw_parts_mut(output as *mut OutFrame, frame_count as usize) };
+    (wrapper.0)(
+        input,
+        output,
+        &callback_info(time_info, status_flags, true, true),
+    );
+    callback_result(StreamFlow::Continue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn passes_the_buffer_times_and_status() {
+        let infos = Arc::new(Mutex::new(Vec::new()));
+        let callback: InfoInputCallback<[i16; 2]> = Box::new({
+            let infos = Arc::clone(&infos);
+            move |_, info| {
+                infos.lock().unwrap().push(*info);
+                StreamFlow::Continue
+            }
+        });
+        let mut wrapper = InfoWrapper(callback);
+        let input = [0i16; 4];
+        let time_info = ffi::PaStreamCallbackTimeInfo {
+            inputBufferAdcTime: 1.5,
+            currentTime: 1.75,
+            outputBufferDacTime: 0.0,
+        };
+        let result = instream_callback::<[i16; 2]>(
+            input.as_ptr() as *const c_void,
+            std::ptr::null_mut(),
+            2,
+            &time_info,
+            ffi::PaStreamCallbackFlags::PaInputOverflow,
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(result, ffi::PaStreamCallbackResult::paContinue as i32);
+        assert_eq!(
+            *infos.lock().unwrap(),
+            vec![CallbackInfo {
+                current_time: Duration::from_millis(1750),
+                capture_time: Some(Duration::from_millis(1500)),
+                playback_time: None,
+                status: StreamStatus {
+                    input_overflow: true,
+                    ..Default::default()
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn passes_duplex_buffers_both_times() {
+        let callback: DuplexInfoCallback<[i16; 1], [i16; 1]> = Box::new(|input, output, info| {
+            assert_eq!(input.len(), output.len());
+            output.copy_from_slice(input);
+            assert_eq!(info.capture_time, Some(Duration::from_secs(1)));
+            assert_eq!(info.playback_time, Some(Duration::from_secs(2)));
+        });
+        let mut wrapper = InfoWrapper(callback);
+        let input = [[1i16], [2]];
+        let mut output = [[0i16]; 2];
+        let time_info = ffi::PaStreamCallbackTimeInfo {
+            inputBufferAdcTime: 1.0,
+            currentTime: 1.5,
+            outputBufferDacTime: 2.0,
+        };
+        let result = duplex_stream_callback::<[i16; 1], [i16; 1]>(
+            input.as_ptr() as *const c_void,
+            output.as_mut_ptr() as *mut c_void,
+            2,
+            &time_info,
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(result, ffi::PaStreamCallbackResult::paContinue as i32);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn returns_the_callback_flow() {
+        let callback: InfoCallback<[f32; 1]> = Box::new(|buffer, _| {
+            buffer.iter_mut().for_each(|frame| *frame = [0.0]);
+            StreamFlow::Complete
+        });
+        let mut wrapper = InfoWrapper(callback);
+        let mut output = [[1.0f32]; 2];
+        let result = outstream_callback::<[f32; 1]>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(result, ffi::PaStreamCallbackResult::paComplete as i32);
+        assert_eq!(output, [[0.0]; 2]);
+    }
+}
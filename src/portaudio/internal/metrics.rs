@@ -0,0 +1,129 @@
+//! Times a stream's callbacks, and how regularly they come, as its callback sees them.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::stream_options::{CallbackMetrics, Histogram, HISTOGRAM_BUCKETS};
+
+/// Shared by the stream and its callback. The callback only ever adds to it, without locking.
+#[derive(Default)]
+pub struct Metrics {
+    /// 0 if the rate isn't known, in which case overruns and jitter aren't counted.
+    sample_rate: u32,
+    durations: [AtomicU64; HISTOGRAM_BUCKETS],
+    jitter: [AtomicU64; HISTOGRAM_BUCKETS],
+    overruns: AtomicU64,
+    /// In nanoseconds.
+    max_duration: AtomicU64,
+    /// When the last callback came, in nanoseconds on the stream's clock, plus one: 0 before the
+    /// first callback.
+    last_callback: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new(sample_rate: i32) -> Metrics {
+        Metrics {
+            sample_rate: sample_rate.max(0) as u32,
+            ..Default::default()
+        }
+    }
+
+    /// How long `n_frames` frames play for. `None` if the rate isn't known.
+    fn buffer_duration(&self, n_frames: usize) -> Option<Duration> {
+        match self.sample_rate {
+            0 => None,
+            rate => Some(Duration::from_secs_f64(n_frames as f64 / f64::from(rate))),
+        }
+    }
+
+    /// Counts how far a callback for `n_frames` frames, at `time` on the stream's clock, came
+    /// from a buffer after the last one.
+    pub fn record_arrival(&self, n_frames: usize, time: Duration) {
+        let last = self
+            .last_callback
+            .swap(time.as_nanos() as u64 + 1, Ordering::Relaxed);
+        if let (Some(last), Some(expected)) = (last.checked_sub(1), self.buffer_duration(n_frames))
+        {
+            let interval = time.saturating_sub(Duration::from_nanos(last));
+            self.jitter[Histogram::bucket_of(interval.abs_diff(expected))]
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a callback for `n_frames` frames that took `duration`.
+    pub fn record_duration(&self, n_frames: usize, duration: Duration) {
+        self.durations[Histogram::bucket_of(duration)].fetch_add(1, Ordering::Relaxed);
+        self.max_duration
+            .fetch_max(duration.as_nanos() as u64, Ordering::Relaxed);
+        if self
+            .buffer_duration(n_frames)
+            .is_some_and(|deadline| duration > deadline)
+        {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> CallbackMetrics {
+        let load = |counts: &[AtomicU64; HISTOGRAM_BUCKETS]| {
+            let mut loaded = [0; HISTOGRAM_BUCKETS];
+            for (loaded, count) in loaded.iter_mut().zip(counts) {
+                *loaded = count.load(Ordering::Relaxed);
+            }
+            Histogram::from_counts(loaded)
+        };
+        CallbackMetrics {
+            durations: load(&self.durations),
+            jitter: load(&self.jitter),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            max_duration: Duration::from_nanos(self.max_duration.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_callbacks() {
+        let metrics = Metrics::new(48000);
+        let buffer = Duration::from_millis(10);
+        metrics.record_arrival(480, Duration::from_secs(1));
+        metrics.record_duration(480, Duration::from_millis(2));
+        // 100 µs late, and over its deadline.
+        metrics.record_arrival(
+            480,
+            Duration::from_secs(1) + buffer + Duration::from_micros(100),
+        );
+        metrics.record_duration(480, Duration::from_millis(12));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.durations.count(), 2);
+        assert_eq!(
+            snapshot.durations.quantile(0.5),
+            Some(Duration::from_micros(2048))
+        );
+        assert_eq!(
+            snapshot.durations.quantile(1.0),
+            Some(Duration::from_micros(16384))
+        );
+        assert_eq!(snapshot.overruns, 1);
+        assert_eq!(snapshot.max_duration, Duration::from_millis(12));
+        // The first callback has no interval.
+        assert_eq!(snapshot.jitter.count(), 1);
+        assert_eq!(
+            snapshot.jitter.quantile(1.0),
+            Some(Duration::from_micros(128))
+        );
+    }
+
+    #[test]
+    fn skips_deadlines_without_a_rate() {
+        let metrics = Metrics::default();
+        metrics.record_arrival(480, Duration::from_secs(1));
+        metrics.record_arrival(480, Duration::from_secs(2));
+        metrics.record_duration(480, Duration::from_secs(1));
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.overruns, 0);
+        assert_eq!(snapshot.jitter, Histogram::default());
+    }
+}
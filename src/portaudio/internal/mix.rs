@@ -0,0 +1,231 @@
+//! Streams whose device doesn't take the callback's channel count. Their callbacks mix between
+//! the callback's channels and the device's, and convert to and from the device's format.
+use libportaudio_sys as ffi;
+use sample::conv::Duplex;
+use sample::Sample;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::portaudio::internal::convert::{reserved_frames, resize_as_frames, Conversion};
+use crate::stream_options::{Callback, InputCallback};
+
+/// The weights that mix `n_channels` channels into `mixed_n_channels`: Mixed channel `i` is the
+/// sum of the channels weighted by row `i`. Channels are in the usual (WAVE) order, so 5.1 is
+/// left, right, center, LFE, left surround and right surround.
+///
+/// Mono is played on both front channels, and downmixed to by averaging every channel but the
+/// LFE. 5.1 is downmixed to stereo by mixing the center and surround channels into the front
+/// ones. Other mixes keep the channels both sides have, and drop or silence the others.
+pub fn mix_matrix(n_channels: usize, mixed_n_channels: usize) -> Vec<Vec<f64>> {
+    let mut matrix = vec![vec![0.0; n_channels]; mixed_n_channels];
+    match (n_channels, mixed_n_channels) {
+        (n, m) if n == m => (),
+        (1, _) => {
+            matrix[0][0] = 1.0;
+            matrix[1][0] = 1.0;
+            return matrix;
+        }
+        (n, 1) => {
+            let lfe = if n == 6 { Some(3) } else { None };
+            let weight = 1.0 / (n - lfe.iter().count()) as f64;
+            for channel in (0..n).filter(|&channel| Some(channel) != lfe) {
+                matrix[0][channel] = weight;
+            }
+            return matrix;
+        }
+        (6, 2) => {
+            use std::f64::consts::FRAC_1_SQRT_2;
+            // Scaled down so that the mix doesn't clip.
+            let scale = 1.0 / (1.0 + 2.0 * FRAC_1_SQRT_2);
+            matrix[0][0] = scale;
+            matrix[0][2] = FRAC_1_SQRT_2 * scale;
+            matrix[0][4] = FRAC_1_SQRT_2 * scale;
+            matrix[1][1] = scale;
+            matrix[1][2] = FRAC_1_SQRT_2 * scale;
+            matrix[1][5] = FRAC_1_SQRT_2 * scale;
+            return matrix;
+        }
+        _ => (),
+    }
+    for (channel, row) in matrix.iter_mut().enumerate().take(n_channels) {
+        row[channel] = 1.0;
+    }
+    matrix
+}
+
+/// Wraps the callback of a mixed stream.
+pub struct MixingWrapper<C, Conv: Conversion> {
+    callback: C,
+    matrix: Vec<Vec<f64>>,
+    n_channels: usize,
+    device_n_channels: usize,
+    /// Frames with the callback's channels.
+    frames: Vec<Conv::Sample>,
+}
+
+impl<C, Conv: Conversion> MixingWrapper<C, Conv> {
+    /// `matrix` mixes the callback's channels into the device's for output streams, and the
+    /// other way around for input streams (see [`mix_matrix`]).
+    pub fn new(
+        callback: C,
+        matrix: Vec<Vec<f64>>,
+        n_channels: i32,
+        device_n_channels: i32,
+        frames_per_buffer: Option<i32>,
+    ) -> MixingWrapper<C, Conv> {
+        MixingWrapper {
+            callback,
+            matrix,
+            n_channels: n_channels as usize,
+            device_n_channels: device_n_channels as usize,
+            frames: Vec::with_capacity(reserved_frames(frames_per_buffer) * n_channels as usize),
+        }
+    }
+}
+
+/// Mixes a frame with `weights`, a row of a [`mix_matrix`].
+fn mix<S: Sample + Duplex<f64>>(weights: &[f64], frame: impl Iterator<Item = S>) -> S {
+    S::from_sample(
+        weights
+            .iter()
+            .zip(frame)
+            .map(|(&weight, sample)| weight * sample.to_sample::<f64>())
+            .sum::<f64>(),
+    )
+}
+
+pub extern "C-unwind" fn outstream_callback<Frame, Conv: Conversion>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32
+where
+    Conv::Sample: Sample + Duplex<f64>,
+{
+    let wrapper = unsafe { (user_data as *mut MixingWrapper<Callback<Frame>, Conv>).as_mut() }
+        .expect("Could not create MixingWrapper from user_data.");
+
+    (wrapper.callback)(resize_as_frames(&mut wrapper.frames, frame_count as usize));
+    let output = unsafe {
+        std::slice::from_raw_parts_mut(
+            output as *mut Conv::DeviceSample,
+            frame_count as usize * wrapper.device_n_channels,
+        )
+    };
+    for (frame, output) in wrapper
+        .frames
+        .chunks_exact(wrapper.n_channels)
+        .zip(output.chunks_exact_mut(wrapper.device_n_channels))
+    {
+        for (output, weights) in output.iter_mut().zip(&wrapper.matrix) {
+            *output = Conv::to_device(mix(weights, frame.iter().copied()));
+        }
+    }
+    0
+}
+
+pub extern "C-unwind" fn instream_callback<Frame, Conv: Conversion>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32
+where
+    Conv::Sample: Sample + Duplex<f64>,
+{
+    let wrapper = unsafe { (user_data as *mut MixingWrapper<InputCallback<Frame>, Conv>).as_mut() }
+        .expect("Could not create MixingWrapper from user_data.");
+
+    let input = unsafe {
+        std::slice::from_raw_parts(
+            input as *const Conv::DeviceSample,
+            frame_count as usize * wrapper.device_n_channels,
+        )
+    };
+    resize_as_frames::<Frame, _>(&mut wrapper.frames, frame_count as usize);
+    for (frame, input) in wrapper
+        .frames
+        .chunks_exact_mut(wrapper.n_channels)
+        .zip(input.chunks_exact(wrapper.device_n_channels))
+    {
+        for (sample, weights) in frame.iter_mut().zip(&wrapper.matrix) {
+            *sample = mix(
+                weights,
+                input.iter().map(|&sample| Conv::from_device(sample)),
+            );
+        }
+    }
+    (wrapper.callback)(resize_as_frames(&mut wrapper.frames, frame_count as usize));
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portaudio::internal::convert::Scale;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn mixes_common_layouts() {
+        assert_eq!(mix_matrix(2, 2), vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(mix_matrix(1, 2), vec![vec![1.0], vec![1.0]]);
+        assert_eq!(mix_matrix(2, 1), vec![vec![0.5, 0.5]]);
+        assert_eq!(mix_matrix(6, 1), vec![vec![0.2, 0.2, 0.2, 0.0, 0.2, 0.2]]);
+        // Surround channels into the front ones, and the LFE dropped.
+        let matrix = mix_matrix(6, 2);
+        assert!(matrix[0][2] > 0.0 && matrix[0][4] > 0.0 && matrix[0][5] == 0.0);
+        assert!(matrix.iter().all(|row| row[3] == 0.0));
+        assert!(matrix
+            .iter()
+            .all(|row| (row.iter().sum::<f64>() - 1.0).abs() < 1e-9));
+        // Stereo on the front channels of 5.1.
+        assert_eq!(
+            mix_matrix(2, 6)[..3],
+            [vec![1.0, 0.0], vec![0.0, 1.0], vec![0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn downmixes_output_frames() {
+        let callback: Callback<[f32; 2]> = Box::new(|frames| {
+            frames.iter_mut().for_each(|frame| *frame = [0.5, 0.25]);
+        });
+        let mut wrapper =
+            MixingWrapper::<_, Scale<f32, f32>>::new(callback, mix_matrix(2, 1), 2, 1, None);
+        let mut output = [0.0f32; 3];
+        outstream_callback::<[f32; 2], Scale<f32, f32>>(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            3,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(output, [0.375; 3]);
+    }
+
+    #[test]
+    fn upmixes_input_frames() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let callback: InputCallback<[i16; 2]> = Box::new({
+            let captured = Arc::clone(&captured);
+            move |frames| captured.lock().unwrap().extend_from_slice(frames)
+        });
+        let mut wrapper =
+            MixingWrapper::<_, Scale<i16, i16>>::new(callback, mix_matrix(1, 2), 2, 1, None);
+        let input = [100i16, -100];
+        instream_callback::<[i16; 2], Scale<i16, i16>>(
+            input.as_ptr() as *const c_void,
+            std::ptr::null_mut(),
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(*captured.lock().unwrap(), vec![[100, 100], [-100, -100]]);
+    }
+}
@@ -1,2 +1,17 @@
+pub mod convert;
 pub mod device;
+pub mod dither;
+pub mod dynamic;
+pub mod info;
+pub mod metrics;
+pub mod mix;
+pub mod pause;
+pub mod planar;
+pub mod raw;
+pub mod reconnect;
+pub mod resample;
+pub mod simd;
+pub mod stats;
 pub mod stream;
+pub mod volume;
+pub mod watchdog;
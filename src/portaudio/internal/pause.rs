@@ -0,0 +1,334 @@
+//! Pausing streams without stopping them. Paused streams keep running, but don't call their
+//! callback: Output streams play silence instead. The buffers around a pause fade out and back in,
+//! so that it doesn't click.
+use libportaudio_sys as ffi;
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const RUNNING: u8 = 0;
+/// Fading out, in the next buffer.
+const PAUSING: u8 = 1;
+const PAUSED: u8 = 2;
+/// Fading in, in the next buffer.
+const RESUMING: u8 = 3;
+
+/// Whether a stream is paused. Shared by the stream and its callback.
+#[derive(Default)]
+pub struct Pause(AtomicU8);
+
+/// What the callback does with a buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Run,
+    FadeOut,
+    Skip,
+    FadeIn,
+}
+
+impl Pause {
+    pub fn pause(&self) {
+        // Resuming streams haven't played anything yet, so there is nothing to fade out.
+        self.transition(&[(RUNNING, PAUSING), (RESUMING, PAUSED)]);
+    }
+
+    pub fn resume(&self) {
+        self.transition(&[(PAUSED, RESUMING), (PAUSING, RUNNING)]);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.0.load(Ordering::Acquire), PAUSING | PAUSED)
+    }
+
+    /// What to do with the next buffer. Moves fading streams on to their next state.
+    pub fn next_action(&self) -> Action {
+        match self.0.load(Ordering::Acquire) {
+            RUNNING => Action::Run,
+            PAUSING => {
+                self.transition(&[(PAUSING, PAUSED)]);
+                Action::FadeOut
+            }
+            PAUSED => Action::Skip,
+            _ => {
+                self.transition(&[(RESUMING, RUNNING)]);
+                Action::FadeIn
+            }
+        }
+    }
+
+    fn transition(&self, transitions: &[(u8, u8)]) {
+        for &(from, to) in transitions {
+            if self
+                .0
+                .compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// How the device's output buffer is laid out.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputLayout {
+    format: ffi::PaSampleFormat,
+    n_channels: usize,
+}
+
+impl OutputLayout {
+    pub fn new(params: &ffi::PaStreamParameters) -> OutputLayout {
+        OutputLayout {
+            format: params.sampleFormat,
+            n_channels: params.channelCount as usize,
+        }
+    }
+
+    fn is_planar(&self) -> bool {
+        self.format.contains(ffi::PaSampleFormat::paNonInterleaved)
+    }
+
+    /// Calls `f` with each of the buffer's channels (or with the whole buffer, if interleaved), the
+    /// number of samples in it, and the channel of its first sample.
+    unsafe fn for_each_buffer(
+        &self,
+        output: *mut c_void,
+        frame_count: usize,
+        f: impl Fn(*mut c_void, usize, usize),
+    ) {
+        if self.is_planar() {
+            let buffers = output as *const *mut c_void;
+            for channel in 0..self.n_channels {
+                f(*buffers.add(channel), frame_count, channel);
+            }
+        } else {
+            f(output, frame_count * self.n_channels, 0);
+        }
+    }
+
+    /// Fills the buffer with silence.
+    pub unsafe fn silence(&self, output: *mut c_void, frame_count: usize) {
+        let format = self.format & !ffi::PaSampleFormat::paNonInterleaved;
+        self.for_each_buffer(output, frame_count, |buffer, n_samples, _| {
+            use ffi::PaSampleFormat as F;
+            let (size, silence) = match format {
+                F::paFloat32 | F::paInt32 => (4, 0),
+                F::paInt24 => (3, 0),
+                F::paInt16 => (2, 0),
+                F::paInt8 => (1, 0),
+                F::paUInt8 => (1, 0x80),
+                _ => return,
+            };
+            std::ptr::write_bytes(buffer as *mut u8, silence, n_samples * size);
+        });
+    }
+
+    /// Fades the buffer in or out, linearly.
+    pub unsafe fn fade(&self, output: *mut c_void, frame_count: usize, fade_in: bool) {
+        if fade_in {
+            self.ramp(output, frame_count, 0.0, 1.0);
+        } else {
+            self.ramp(output, frame_count, 1.0, 0.0);
+        }
+    }
+
+    /// Scales the buffer by a gain that goes linearly from `from`, on its first frame, towards
+    /// `to`. Formats it can't scale are left as they are.
+    pub unsafe fn ramp(&self, output: *mut c_void, frame_count: usize, from: f32, to: f32) {
+        self.scale_by(output, frame_count, |frame, _| {
+            from + (to - from) * (frame as f32 / frame_count as f32)
+        });
+    }
+
+    /// Clamps float samples to `-1.0..=1.0` (and NaNs to 0), and returns how many were out of it.
+    /// Integer samples can't be.
+    pub unsafe fn clip(&self, output: *mut c_void, frame_count: usize) -> usize {
+        if self.format & !ffi::PaSampleFormat::paNonInterleaved != ffi::PaSampleFormat::paFloat32 {
+            return 0;
+        }
+        let clipped = Cell::new(0);
+        self.for_each_buffer(output, frame_count, |buffer, n_samples, _| {
+            let samples = std::slice::from_raw_parts_mut(buffer as *mut f32, n_samples);
+            for sample in samples
+                .iter_mut()
+                .filter(|sample| !(-1.0..=1.0).contains(*sample))
+            {
+                *sample = if sample.is_nan() {
+                    0.0
+                } else {
+                    sample.clamp(-1.0, 1.0)
+                };
+                clipped.set(clipped.get() + 1);
+            }
+        });
+        clipped.get()
+    }
+
+    /// Scales each of the buffer's channels by its gain.
+    pub unsafe fn scale(&self, output: *mut c_void, frame_count: usize, gains: &[f32]) {
+        debug_assert_eq!(gains.len(), self.n_channels);
+        self.scale_by(output, frame_count, |_, channel| gains[channel]);
+    }
+
+    /// Scales each sample by `gain(frame, channel)`.
+    unsafe fn scale_by(
+        &self,
+        output: *mut c_void,
+        frame_count: usize,
+        gain: impl Fn(usize, usize) -> f32,
+    ) {
+        let format = self.format & !ffi::PaSampleFormat::paNonInterleaved;
+        let stride = if self.is_planar() { 1 } else { self.n_channels };
+        self.for_each_buffer(output, frame_count, |buffer, n_samples, first_channel| {
+            let gain = |sample: usize| gain(sample / stride, first_channel + sample % stride);
+            use ffi::PaSampleFormat as F;
+            match format {
+                F::paFloat32 => {
+                    let samples = std::slice::from_raw_parts_mut(buffer as *mut f32, n_samples);
+                    for (i, sample) in samples.iter_mut().enumerate() {
+                        *sample *= gain(i);
+                    }
+                }
+                F::paInt32 => {
+                    let samples = std::slice::from_raw_parts_mut(buffer as *mut i32, n_samples);
+                    for (i, sample) in samples.iter_mut().enumerate() {
+                        *sample = (f64::from(*sample) * f64::from(gain(i))) as i32;
+                    }
+                }
+                F::paInt24 => {
+                    let bytes = std::slice::from_raw_parts_mut(buffer as *mut u8, n_samples * 3);
+                    for (i, sample) in bytes.chunks_exact_mut(3).enumerate() {
+                        // Little-endian, sign-extended through the top byte.
+                        let value = i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) >> 8;
+                        let scaled = (value as f32 * gain(i)) as i32;
+                        sample.copy_from_slice(&scaled.to_le_bytes()[..3]);
+                    }
+                }
+                F::paInt16 => {
+                    let samples = std::slice::from_raw_parts_mut(buffer as *mut i16, n_samples);
+                    for (i, sample) in samples.iter_mut().enumerate() {
+                        *sample = (f32::from(*sample) * gain(i)) as i16;
+                    }
+                }
+                F::paInt8 => {
+                    let samples = std::slice::from_raw_parts_mut(buffer as *mut i8, n_samples);
+                    for (i, sample) in samples.iter_mut().enumerate() {
+                        *sample = (f32::from(*sample) * gain(i)) as i8;
+                    }
+                }
+                F::paUInt8 => {
+                    let samples = std::slice::from_raw_parts_mut(buffer as *mut u8, n_samples);
+                    for (i, sample) in samples.iter_mut().enumerate() {
+                        *sample = ((f32::from(*sample) - 128.0) * gain(i) + 128.0) as u8;
+                    }
+                }
+                _ => (),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(format: ffi::PaSampleFormat, n_channels: usize) -> OutputLayout {
+        OutputLayout { format, n_channels }
+    }
+
+    #[test]
+    fn fades_around_pauses() {
+        let pause = Pause::default();
+        assert_eq!(pause.next_action(), Action::Run);
+        pause.pause();
+        assert!(pause.is_paused());
+        assert_eq!(pause.next_action(), Action::FadeOut);
+        assert_eq!(pause.next_action(), Action::Skip);
+        pause.resume();
+        assert!(!pause.is_paused());
+        assert_eq!(pause.next_action(), Action::FadeIn);
+        assert_eq!(pause.next_action(), Action::Run);
+        // Resuming before the fade out doesn't fade at all.
+        pause.pause();
+        pause.resume();
+        assert_eq!(pause.next_action(), Action::Run);
+    }
+
+    #[test]
+    fn fades_and_silences_buffers() {
+        let stereo = layout(ffi::PaSampleFormat::paFloat32, 2);
+        let mut output = [1.0f32; 8];
+        unsafe { stereo.fade(output.as_mut_ptr() as *mut c_void, 4, false) };
+        assert_eq!(output, [1.0, 1.0, 0.75, 0.75, 0.5, 0.5, 0.25, 0.25]);
+        unsafe { stereo.silence(output.as_mut_ptr() as *mut c_void, 4) };
+        assert_eq!(output, [0.0; 8]);
+
+        let unsigned = layout(ffi::PaSampleFormat::paUInt8, 1);
+        let mut output = [0u8; 4];
+        unsafe { unsigned.silence(output.as_mut_ptr() as *mut c_void, 4) };
+        assert_eq!(output, [0x80; 4]);
+
+        let planar = layout(
+            ffi::PaSampleFormat::paInt16 | ffi::PaSampleFormat::paNonInterleaved,
+            2,
+        );
+        let (mut left, mut right) = ([1000i16; 2], [-1000i16; 2]);
+        let mut buffers = [left.as_mut_ptr(), right.as_mut_ptr()];
+        unsafe { planar.fade(buffers.as_mut_ptr() as *mut c_void, 2, true) };
+        assert_eq!((left, right), ([0, 500], [0, -500]));
+    }
+
+    #[test]
+    fn ramps_buffers() {
+        let mono = layout(ffi::PaSampleFormat::paFloat32, 1);
+        let mut output = [1.0f32; 4];
+        unsafe { mono.ramp(output.as_mut_ptr() as *mut c_void, 4, 0.5, 0.5) };
+        assert_eq!(output, [0.5; 4]);
+
+        let packed = layout(ffi::PaSampleFormat::paInt24, 1);
+        // -1000 and 1000, as little-endian 24-bit samples.
+        let mut output = [0x18u8, 0xfc, 0xff, 0xe8, 0x03, 0x00];
+        unsafe { packed.ramp(output.as_mut_ptr() as *mut c_void, 2, 0.5, 0.5) };
+        assert_eq!(output, [0x0c, 0xfe, 0xff, 0xf4, 0x01, 0x00]);
+
+        let unsigned = layout(ffi::PaSampleFormat::paUInt8, 1);
+        let mut output = [0xffu8, 0x00];
+        unsafe { unsigned.ramp(output.as_mut_ptr() as *mut c_void, 2, 0.5, 0.5) };
+        assert_eq!(output, [0xbf, 0x40]);
+    }
+
+    #[test]
+    fn clips_float_samples() {
+        let stereo = layout(ffi::PaSampleFormat::paFloat32, 2);
+        let mut output = [0.5f32, 1.5, -2.0, f32::NAN];
+        assert_eq!(
+            unsafe { stereo.clip(output.as_mut_ptr() as *mut c_void, 2) },
+            3
+        );
+        assert_eq!(output, [0.5, 1.0, -1.0, 0.0]);
+
+        let ints = layout(ffi::PaSampleFormat::paInt16, 1);
+        let mut output = [i16::MAX];
+        assert_eq!(
+            unsafe { ints.clip(output.as_mut_ptr() as *mut c_void, 1) },
+            0
+        );
+    }
+
+    #[test]
+    fn scales_channels() {
+        let stereo = layout(ffi::PaSampleFormat::paInt16, 2);
+        let mut output = [1000i16, 1000, -1000, -1000];
+        unsafe { stereo.scale(output.as_mut_ptr() as *mut c_void, 2, &[0.5, 2.0]) };
+        assert_eq!(output, [500, 2000, -500, -2000]);
+
+        let planar = layout(
+            ffi::PaSampleFormat::paFloat32 | ffi::PaSampleFormat::paNonInterleaved,
+            2,
+        );
+        let (mut left, mut right) = ([1.0f32; 2], [1.0f32; 2]);
+        let mut buffers = [left.as_mut_ptr(), right.as_mut_ptr()];
+        unsafe { planar.scale(buffers.as_mut_ptr() as *mut c_void, 2, &[0.5, 0.25]) };
+        assert_eq!((left, right), ([0.5; 2], [0.25; 2]));
+    }
+}
@@ -0,0 +1,125 @@
+//! Planar (non-interleaved) streams. Portaudio passes their callbacks an array of pointers, one per
+//! channel, which the wrappers here turn into slices.
+use libportaudio_sys as ffi;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::stream_options::{PlanarCallback, PlanarInputCallback};
+
+/// Wraps the callback of a planar stream.
+pub struct PlanarWrapper<C, Channel> {
+    callback: C,
+    n_channels: usize,
+    /// The channels' buffers. Only valid during a callback: Filled with the device's buffers before
+    /// calling it, and cleared after. Allocated up front so that the callback doesn't allocate.
+    channels: Vec<Channel>,
+}
+
+// The channels' buffers are empty outside of callbacks, so only the callback is sent.
+unsafe impl<C: Send, Channel> Send for PlanarWrapper<C, Channel> {}
+
+impl<C, Channel> PlanarWrapper<C, Channel> {
+    pub fn new(callback: C, n_channels: i32) -> PlanarWrapper<C, Channel> {
+        PlanarWrapper {
+            callback,
+            n_channels: n_channels as usize,
+            channels: Vec::with_capacity(n_channels as usize),
+        }
+    }
+}
+
+pub extern "C-unwind" fn outstream_callback<Sample: 'static>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe {
+        (user_data as *mut PlanarWrapper<PlanarCallback<Sample>, &'static mut [Sample]>).as_mut()
+    }
+    .expect("Could not create PlanarWrapper from user_data.");
+
+    let buffers = output as *const *mut Sample;
+    for channel in 0..wrapper.n_channels {
+        wrapper.channels.push(unsafe {
+            std::slice::from_raw_parts_mut(*buffers.add(channel), frame_count as usize)
+        });
+    }
+    (wrapper.callback)(&mut wrapper.channels);
+    wrapper.channels.clear();
+    0
+}
+
+pub extern "C-unwind" fn instream_callback<Sample: 'static>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe {
+        (user_data as *mut PlanarWrapper<PlanarInputCallback<Sample>, &'static [Sample]>).as_mut()
+    }
+    .expect("Could not create PlanarWrapper from user_data.");
+
+    let buffers = input as *const *const Sample;
+    for channel in 0..wrapper.n_channels {
+        wrapper.channels.push(unsafe {
+            std::slice::from_raw_parts(*buffers.add(channel), frame_count as usize)
+        });
+    }
+    (wrapper.callback)(&wrapper.channels);
+    wrapper.channels.clear();
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn passes_a_buffer_per_channel() {
+        let callback: PlanarCallback<f32> = Box::new(|channels| {
+            for (i, channel) in channels.iter_mut().enumerate() {
+                channel.iter_mut().for_each(|sample| *sample = i as f32);
+            }
+        });
+        let mut wrapper = PlanarWrapper::<_, &mut [f32]>::new(callback, 2);
+        let (mut left, mut right) = ([-1.0f32; 3], [-1.0f32; 3]);
+        let buffers = [left.as_mut_ptr(), right.as_mut_ptr()];
+        outstream_callback::<f32>(
+            std::ptr::null(),
+            buffers.as_ptr() as *mut c_void,
+            3,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!((left, right), ([0.0; 3], [1.0; 3]));
+        assert!(wrapper.channels.is_empty());
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let callback: PlanarInputCallback<i16> = Box::new({
+            let captured = Arc::clone(&captured);
+            move |channels| {
+                let mut captured = captured.lock().unwrap();
+                captured.extend(channels.iter().map(|channel| channel.to_vec()));
+            }
+        });
+        let mut wrapper = PlanarWrapper::<_, &[i16]>::new(callback, 2);
+        let (left, right) = ([1i16, 2], [3i16, 4]);
+        let buffers = [left.as_ptr(), right.as_ptr()];
+        instream_callback::<i16>(
+            buffers.as_ptr() as *const c_void,
+            std::ptr::null_mut(),
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(*captured.lock().unwrap(), vec![vec![1, 2], vec![3, 4]]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Raw streams, whose callbacks get Portaudio's own buffers in the stream's format, without copying
+//! or converting them.
+use libportaudio_sys as ffi;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::stream_options::{Format, RawBuffer, RawCallback, RawInputBuffer, RawInputCallback};
+
+/// Wraps the callback of a raw stream.
+pub struct RawWrapper<C> {
+    callback: C,
+    n_channels: usize,
+    format: Format,
+}
+
+impl<C> RawWrapper<C> {
+    pub fn new(callback: C, n_channels: i32, format: Format) -> RawWrapper<C> {
+        RawWrapper {
+            callback,
+            n_channels: n_channels as usize,
+            format,
+        }
+    }
+}
+
+pub extern "C-unwind" fn outstream_callback(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut RawWrapper<RawCallback>).as_mut() }
+        .expect("Could not create RawWrapper from user_data.");
+
+    let output = unsafe {
+        RawBuffer::new(
+            output,
+            frame_count as usize,
+            wrapper.n_channels,
+            wrapper.format,
+        )
+    };
+    (wrapper.callback)(output);
+    0
+}
+
+pub extern "C-unwind" fn instream_callback(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let wrapper = unsafe { (user_data as *mut RawWrapper<RawInputCallback>).as_mut() }
+        .expect("Could not create RawWrapper from user_data.");
+
+    let input = unsafe {
+        RawInputBuffer::new(
+            input,
+            frame_count as usize,
+            wrapper.n_channels,
+            wrapper.format,
+        )
+    };
+    (wrapper.callback)(input);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_the_buffer_in_place() {
+        let callback: RawCallback = Box::new(|mut buffer| {
+            assert_eq!(
+                (buffer.n_frames(), buffer.n_channels(), buffer.len_bytes()),
+                (2, 3, 12)
+            );
+            assert_eq!(buffer.format(), Format::I16);
+            for (i, sample) in unsafe { buffer.samples_mut::<i16>() }
+                .iter_mut()
+                .enumerate()
+            {
+                *sample = i as i16;
+            }
+        });
+        let mut wrapper = RawWrapper::new(callback, 3, Format::I16);
+        let mut output = [-1i16; 6];
+        outstream_callback(
+            std::ptr::null(),
+            output.as_mut_ptr() as *mut c_void,
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(output, [0, 1, 2, 3, 4, 5]);
+    }
+}
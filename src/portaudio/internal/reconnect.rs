@@ -0,0 +1,237 @@
+//! Reopens streams that lost their device, on the same device or the default one, with the same
+//! callback: The callback's user data outlives the Portaudio streams it's passed to.
+use libportaudio_sys as ffi;
+use std::os::raw::{c_ulong, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::error::{CallbackError, Error, Result};
+use crate::portaudio::error::PaErrorIntoResult as _;
+use crate::portaudio::internal::watchdog::Heartbeat;
+use crate::portaudio::{global_lock, RawPtr};
+use crate::stream_options::ReconnectPolicy;
+
+/// How long the supervisor waits before trying to reopen a stream again.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What a stream was opened with, to open it again.
+pub struct Reopen {
+    pub input_params: Option<ffi::PaStreamParameters>,
+    pub output_params: Option<ffi::PaStreamParameters>,
+    pub sample_rate: i32,
+    pub frames_per_buffer: c_ulong,
+    pub flags: ffi::PaStreamFlags,
+    pub pa_callback: ffi::PaStreamCallback,
+    pub finished_callback: ffi::PaStreamFinishedCallback,
+    pub user_data: *mut c_void,
+    #[cfg(target_os = "linux")]
+    pub alsa_realtime: bool,
+}
+
+// The user data is only freed after the supervisor is joined.
+unsafe impl Send for Reopen {}
+
+impl Reopen {
+    /// The parameters to reopen with, on the default devices if `policy` falls back to them.
+    fn params(
+        &self,
+        policy: ReconnectPolicy,
+    ) -> (
+        Option<ffi::PaStreamParameters>,
+        Option<ffi::PaStreamParameters>,
+    ) {
+        let mut input_params = self.input_params.as_ref().map(copy_params);
+        let mut output_params = self.output_params.as_ref().map(copy_params);
+        if policy == ReconnectPolicy::FallbackToDefault {
+            if let Some(params) = &mut input_params {
+                params.device = unsafe { ffi::Pa_GetDefaultInputDevice() };
+            }
+            if let Some(params) = &mut output_params {
+                params.device = unsafe { ffi::Pa_GetDefaultOutputDevice() };
+            }
+        }
+        (input_params, output_params)
+    }
+}
+
+/// Copies `params`, without their host API specific info: It only lives until the stream is first
+/// opened.
+pub fn copy_params(params: &ffi::PaStreamParameters) -> ffi::PaStreamParameters {
+    ffi::PaStreamParameters {
+        device: params.device,
+        channelCount: params.channelCount,
+        sampleFormat: params.sampleFormat,
+        suggestedLatency: params.suggestedLatency,
+        hostApiSpecificStreamInfo: std::ptr::null_mut(),
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether the stream's device was lost, and the stream wasn't reopened yet.
+    lost: bool,
+    closed: bool,
+}
+
+/// Shared by the stream's finished callback, which tells the supervisor when its device is lost.
+#[derive(Default)]
+pub struct Signal {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Signal {
+    pub fn lose(&self) {
+        self.state.lock().unwrap().lost = true;
+        self.wake.notify_one();
+    }
+}
+
+/// What the supervisor shares with the stream it reopens.
+pub struct Supervised {
+    /// The stream's Portaudio stream, which the supervisor replaces. Null while it's lost and
+    /// couldn't be reopened.
+    pub pa_stream: Arc<Mutex<RawPtr<ffi::PaStream>>>,
+    /// Whether the owner stopped the stream, which then reopens stopped.
+    pub stopping: Arc<AtomicBool>,
+    pub heartbeat: Option<Arc<Heartbeat>>,
+    pub errors: SyncSender<CallbackError>,
+}
+
+/// Owns the supervisor's thread, which it stops when dropped.
+pub struct Supervisor {
+    signal: Arc<Signal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    /// Spawns a supervisor, which reopens the stream with `reopen` by `policy` whenever `signal`
+    /// is lost, and sends [`CallbackError::Reconnected`] once it's reopened.
+    pub fn spawn(
+        policy: ReconnectPolicy,
+        reopen: Reopen,
+        signal: Arc<Signal>,
+        supervised: Supervised,
+    ) -> Result<Supervisor> {
+        let watched = Arc::clone(&signal);
+        let thread = std::thread::Builder::new()
+            .name("audiohal-reconnect".into())
+            .spawn(move || supervise(&watched, policy, &reopen, &supervised))
+            .map_err(|error| {
+                Error::from_source(None, "Could not spawn the reconnecting thread.", error)
+            })?;
+        Ok(Supervisor {
+            signal,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.signal.state.lock().unwrap().closed = true;
+        self.signal.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Reopens the stream each time it's lost, retrying until it opens, until the supervisor is
+/// dropped.
+fn supervise(signal: &Signal, policy: ReconnectPolicy, reopen: &Reopen, supervised: &Supervised) {
+    let mut state = signal.state.lock().unwrap();
+    while !state.closed {
+        if !state.lost {
+            state = signal.wake.wait(state).unwrap();
+            continue;
+        }
+        // Streams lost again while they reopen are reopened again.
+        state.lost = false;
+        drop(state);
+        let reopened = reopen_stream(policy, reopen, supervised);
+        state = signal.state.lock().unwrap();
+        match reopened {
+            Ok(()) => {
+                supervised.errors.try_send(CallbackError::Reconnected).ok();
+            }
+            Err(_) => {
+                state.lost = true;
+                state = signal.wake.wait_timeout(state, RETRY_INTERVAL).unwrap().0;
+            }
+        }
+    }
+}
+
+/// Closes the lost Portaudio stream, then opens and starts its replacement. Owners calling into
+/// the stream wait until it's done (or see a null stream, if it failed).
+fn reopen_stream(policy: ReconnectPolicy, reopen: &Reopen, supervised: &Supervised) -> Result<()> {
+    let _guard = global_lock();
+    let mut pa_stream = supervised.pa_stream.lock().unwrap();
+    if !pa_stream.is_null() {
+        // Its device is gone, so closing it may fail. It's freed all the same.
+        unsafe { ffi::Pa_CloseStream(pa_stream.as_ptr_mut()) };
+        *pa_stream = RawPtr::dangling();
+    }
+    let (input_params, output_params) = reopen.params(policy);
+    let mut opened = RawPtr::dangling();
+    unsafe {
+        ffi::Pa_OpenStream(
+            &mut opened as *const _ as *mut _,
+            input_params
+                .as_ref()
+                .map_or(std::ptr::null(), |params| params as *const _),
+            output_params
+                .as_ref()
+                .map_or(std::ptr::null(), |params| params as *const _),
+            reopen.sample_rate.into(),
+            reopen.frames_per_buffer,
+            reopen.flags,
+            reopen.pa_callback,
+            reopen.user_data,
+        )
+    }
+    .into_result()?;
+    *pa_stream = opened;
+    #[cfg(target_os = "linux")]
+    if reopen.alsa_realtime {
+        unsafe { ffi::PaAlsa_EnableRealtimeScheduling(pa_stream.as_ptr_mut(), 1) };
+    }
+    unsafe { ffi::Pa_SetStreamFinishedCallback(pa_stream.as_ptr_mut(), reopen.finished_callback) }
+        .into_result()?;
+    if supervised.stopping.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if let Some(heartbeat) = &supervised.heartbeat {
+        heartbeat.restart();
+    }
+    unsafe { ffi::Pa_StartStream(pa_stream.as_ptr_mut()) }.into_result()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_params_without_host_api_info() {
+        let mut info = 0u8;
+        let params = ffi::PaStreamParameters {
+            device: 3,
+            channelCount: 2,
+            sampleFormat: ffi::PaSampleFormat::paFloat32,
+            suggestedLatency: 0.01,
+            hostApiSpecificStreamInfo: &mut info as *mut u8 as *mut c_void,
+        };
+        let copy = copy_params(&params);
+        assert_eq!(
+            (copy.device, copy.channelCount, copy.suggestedLatency),
+            (3, 2, 0.01)
+        );
+        assert_eq!(copy.sampleFormat, ffi::PaSampleFormat::paFloat32);
+        assert!(copy.hostApiSpecificStreamInfo.is_null());
+    }
+}
@@ -0,0 +1,562 @@
+//! Streams whose device doesn't run at the callback's sample rate. Their callbacks resample
+//! between the two rates, and convert to and from the device's format.
+use libportaudio_sys as ffi;
+use sample::conv::Duplex;
+use sample::Sample;
+use std::os::raw::{c_ulong, c_void};
+
+use crate::portaudio::internal::convert::{reserved_frames, resize_as_frames, Conversion};
+use crate::stream_options::{Callback, InputCallback, ResamplerQuality};
+
+/// Resamples interleaved frames at the stream's [`ResamplerQuality`].
+pub enum Resampler {
+    Linear(LinearResampler),
+    #[cfg(feature = "rubato")]
+    Sinc(SincResampler),
+}
+
+impl Resampler {
+    /// Without the `rubato` feature, every quality resamples linearly.
+    pub fn new(
+        quality: ResamplerQuality,
+        n_channels: usize,
+        source_rate: i32,
+        rate: i32,
+    ) -> Resampler {
+        match quality {
+            #[cfg(feature = "rubato")]
+            ResamplerQuality::SincFast | ResamplerQuality::SincBest => {
+                Resampler::Sinc(SincResampler::new(quality, n_channels, source_rate, rate))
+            }
+            _ => Resampler::Linear(LinearResampler::new(n_channels, source_rate, rate)),
+        }
+    }
+
+    /// See [`LinearResampler::resample`].
+    pub fn resample<S: Sample + Duplex<f64>>(
+        &mut self,
+        source: &[S],
+        output: &mut [S],
+    ) -> (usize, usize) {
+        match self {
+            Resampler::Linear(resampler) => resampler.resample(source, output),
+            #[cfg(feature = "rubato")]
+            Resampler::Sinc(resampler) => resampler.resample(source, output),
+        }
+    }
+
+    fn max_written(&self, source_frames: usize) -> usize {
+        match self {
+            Resampler::Linear(resampler) => resampler.max_written(source_frames),
+            #[cfg(feature = "rubato")]
+            Resampler::Sinc(resampler) => resampler.max_written(source_frames),
+        }
+    }
+
+    fn max_consumed(&self, frames: usize) -> usize {
+        match self {
+            Resampler::Linear(resampler) => resampler.max_consumed(frames),
+            #[cfg(feature = "rubato")]
+            Resampler::Sinc(resampler) => resampler.max_consumed(frames),
+        }
+    }
+
+    /// Bounds [`max_written`](Resampler::max_written), whatever the resampler's state.
+    fn written_bound(&self, source_frames: usize) -> usize {
+        match self {
+            Resampler::Linear(resampler) => resampler.max_written(source_frames),
+            #[cfg(feature = "rubato")]
+            Resampler::Sinc(resampler) => resampler.written_bound(source_frames),
+        }
+    }
+
+    /// Bounds [`max_consumed`](Resampler::max_consumed), whatever the resampler's state.
+    fn consumed_bound(&self, frames: usize) -> usize {
+        match self {
+            Resampler::Linear(resampler) => resampler.max_consumed(frames),
+            #[cfg(feature = "rubato")]
+            Resampler::Sinc(resampler) => resampler.consumed_bound(frames),
+        }
+    }
+}
+
+/// Linearly interpolates interleaved frames at another rate. Keeps its position across buffers,
+/// so that a stream can be resampled a buffer at a time.
+pub struct LinearResampler {
+    n_channels: usize,
+    /// Source frames per resampled frame.
+    step: f64,
+    /// Where the next resampled frame is between `previous` and `current`. At 1 or more, the
+    /// next source frame is needed first.
+    position: f64,
+    previous: Vec<f64>,
+    current: Vec<f64>,
+}
+
+impl LinearResampler {
+    pub fn new(n_channels: usize, source_rate: i32, rate: i32) -> LinearResampler {
+        LinearResampler {
+            n_channels,
+            step: f64::from(source_rate) / f64::from(rate),
+            position: 1.0,
+            previous: vec![0.0; n_channels],
+            current: vec![0.0; n_channels],
+        }
+    }
+
+    /// Resamples `source` into `output`, until either runs out. Returns the number of source
+    /// frames consumed and of frames written.
+    pub fn resample<S: Sample + Duplex<f64>>(
+        &mut self,
+        source: &[S],
+        output: &mut [S],
+    ) -> (usize, usize) {
+        let mut source = source.chunks_exact(self.n_channels);
+        let (mut consumed, mut written) = (0, 0);
+        for frame in output.chunks_exact_mut(self.n_channels) {
+            while self.position >= 1.0 {
+                let next = match source.next() {
+                    Some(next) => next,
+                    None => return (consumed, written),
+                };
+                std::mem::swap(&mut self.previous, &mut self.current);
+                for (current, &sample) in self.current.iter_mut().zip(next) {
+                    *current = sample.to_sample();
+                }
+                self.position -= 1.0;
+                consumed += 1;
+            }
+            for (sample, (&previous, &current)) in frame
+                .iter_mut()
+                .zip(self.previous.iter().zip(&self.current))
+            {
+                *sample = S::from_sample(previous + (current - previous) * self.position);
+            }
+            self.position += self.step;
+            written += 1;
+        }
+        (consumed, written)
+    }
+
+    /// About the number of frames resampling `source_frames` frames writes.
+    fn max_written(&self, source_frames: usize) -> usize {
+        ((source_frames + 1) as f64 / self.step).ceil() as usize + 1
+    }
+
+    /// An upper bound on the number of source frames writing `frames` frames consumes.
+    fn max_consumed(&self, frames: usize) -> usize {
+        (frames as f64 * self.step).ceil() as usize + 1
+    }
+}
+
+/// Resamples through one of rubato's windowed sinc filters. These take fixed-size chunks of
+/// planar frames, so source frames are buffered until there is a whole chunk, and resampled
+/// frames until they are written.
+#[cfg(feature = "rubato")]
+pub struct SincResampler {
+    resampler: rubato::SincFixedIn<f64>,
+    /// Source frames per resampled frame.
+    step: f64,
+    /// A buffer per channel, of the source frames of the next chunk.
+    chunk: Vec<Vec<f64>>,
+    /// A buffer per channel, of the last chunk's resampled frames. The ones from `unwritten` to
+    /// `n_resampled` haven't been written yet.
+    resampled: Vec<Vec<f64>>,
+    n_resampled: usize,
+    unwritten: usize,
+}
+
+#[cfg(feature = "rubato")]
+impl SincResampler {
+    /// Source frames per chunk.
+    const CHUNK_FRAMES: usize = 256;
+
+    pub fn new(
+        quality: ResamplerQuality,
+        n_channels: usize,
+        source_rate: i32,
+        rate: i32,
+    ) -> SincResampler {
+        use rubato::{
+            Resampler, SincInterpolationParameters, SincInterpolationType, WindowFunction,
+        };
+        let window = WindowFunction::BlackmanHarris2;
+        let (sinc_len, oversampling_factor, interpolation) = match quality {
+            ResamplerQuality::SincBest => (256, 256, SincInterpolationType::Cubic),
+            _ => (64, 128, SincInterpolationType::Linear),
+        };
+        let parameters = SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: rubato::calculate_cutoff(sinc_len, window),
+            oversampling_factor,
+            interpolation,
+            window,
+        };
+        let resampler = rubato::SincFixedIn::new(
+            f64::from(rate) / f64::from(source_rate),
+            1.0,
+            parameters,
+            Self::CHUNK_FRAMES,
+            n_channels,
+        )
+        .expect("Sample rates should be positive.");
+        SincResampler {
+            step: f64::from(source_rate) / f64::from(rate),
+            chunk: vec![Vec::with_capacity(Self::CHUNK_FRAMES); n_channels],
+            resampled: vec![vec![0.0; resampler.output_frames_max()]; n_channels],
+            resampler,
+            n_resampled: 0,
+            unwritten: 0,
+        }
+    }
+
+    /// See [`LinearResampler::resample`].
+    pub fn resample<S: Sample + Duplex<f64>>(
+        &mut self,
+        source: &[S],
+        output: &mut [S],
+    ) -> (usize, usize) {
+        use rubato::Resampler;
+        let n_channels = self.chunk.len();
+        let mut source = source.chunks_exact(n_channels);
+        let (mut consumed, mut written) = (0, 0);
+        for frame in output.chunks_exact_mut(n_channels) {
+            while self.unwritten == self.n_resampled {
+                if self.chunk[0].len() == Self::CHUNK_FRAMES {
+                    let (_, n_resampled) = self
+                        .resampler
+                        .process_into_buffer(&self.chunk, &mut self.resampled, None)
+                        .expect("Buffers should fit their chunk.");
+                    self.chunk.iter_mut().for_each(Vec::clear);
+                    self.n_resampled = n_resampled;
+                    self.unwritten = 0;
+                    continue;
+                }
+                let next = match source.next() {
+                    Some(next) => next,
+                    None => return (consumed, written),
+                };
+                for (channel, &sample) in self.chunk.iter_mut().zip(next) {
+                    channel.push(sample.to_sample());
+                }
+                consumed += 1;
+            }
+            for (sample, channel) in frame.iter_mut().zip(&self.resampled) {
+                *sample = S::from_sample(channel[self.unwritten]);
+            }
+            self.unwritten += 1;
+            written += 1;
+        }
+        (consumed, written)
+    }
+
+    /// About the number of frames resampling `source_frames` frames writes.
+    fn max_written(&self, source_frames: usize) -> usize {
+        let source_frames = source_frames + self.chunk[0].len();
+        self.n_resampled - self.unwritten + (source_frames as f64 / self.step).ceil() as usize + 1
+    }
+
+    /// About the number of source frames writing `frames` frames consumes: At least enough to
+    /// finish the next chunk.
+    fn max_consumed(&self, frames: usize) -> usize {
+        let unwritten = self.n_resampled - self.unwritten;
+        let frames = (frames.saturating_sub(unwritten) as f64 * self.step).ceil() as usize + 1;
+        frames.max(Self::CHUNK_FRAMES - self.chunk[0].len())
+    }
+
+    /// [`max_written`](SincResampler::max_written) with a whole chunk buffered, and a whole
+    /// chunk unwritten.
+    fn written_bound(&self, source_frames: usize) -> usize {
+        let source_frames = source_frames + Self::CHUNK_FRAMES;
+        self.resampled[0].len() + (source_frames as f64 / self.step).ceil() as usize + 1
+    }
+
+    /// [`max_consumed`](SincResampler::max_consumed) with nothing buffered or unwritten.
+    fn consumed_bound(&self, frames: usize) -> usize {
+        ((frames as f64 * self.step).ceil() as usize + 1).max(Self::CHUNK_FRAMES)
+    }
+}
+
+/// Wraps the callback of a resampled stream.
+pub struct ResamplingWrapper<C, Conv: Conversion> {
+    callback: C,
+    resampler: Resampler,
+    n_channels: usize,
+    /// Samples at the callback's rate. For output streams, the ones from `unread` on haven't been
+    /// resampled yet.
+    frames: Vec<Conv::Sample>,
+    unread: usize,
+    /// Samples at the device's rate, in the callback's format.
+    device_frames: Vec<Conv::Sample>,
+}
+
+impl<C, Conv: Conversion> ResamplingWrapper<C, Conv> {
+    /// `rate` is the callback's sample rate, and `device_rate` the device's.
+    pub fn new(
+        callback: C,
+        n_channels: i32,
+        rate: i32,
+        device_rate: i32,
+        quality: ResamplerQuality,
+        is_output: bool,
+        frames_per_buffer: Option<i32>,
+    ) -> ResamplingWrapper<C, Conv> {
+        let n_channels = n_channels as usize;
+        let resampler = if is_output {
+            Resampler::new(quality, n_channels, rate, device_rate)
+        } else {
+            Resampler::new(quality, n_channels, device_rate, rate)
+        };
+        let device_frames = reserved_frames(frames_per_buffer);
+        // As many frames as the callback takes to fill (or is given from) the device's buffer.
+        let frames = if is_output {
+            resampler.consumed_bound(device_frames)
+        } else {
+            resampler.written_bound(device_frames)
+        };
+        ResamplingWrapper {
+            callback,
+            resampler,
+            n_channels,
+            frames: Vec::with_capacity(frames * n_channels),
+            unread: 0,
+            device_frames: Vec::with_capacity(device_frames * n_channels),
+        }
+    }
+}
+
+pub extern "C-unwind" fn outstream_callback<Frame, Conv: Conversion>(
+    _input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32
+where
+    Conv::Sample: Sample + Duplex<f64>,
+{
+    let wrapper = unsafe { (user_data as *mut ResamplingWrapper<Callback<Frame>, Conv>).as_mut() }
+        .expect("Could not create ResamplingWrapper from user_data.");
+
+    let n_channels = wrapper.n_channels;
+    let n_samples = frame_count as usize * n_channels;
+    wrapper
+        .device_frames
+        .resize(n_samples, Conv::Sample::default());
+    let mut written = 0;
+    loop {
+        let (consumed, n_written) = wrapper.resampler.resample(
+            &wrapper.frames[wrapper.unread..],
+            &mut wrapper.device_frames[written..],
+        );
+        wrapper.unread += consumed * n_channels;
+        written += n_written * n_channels;
+        if written == n_samples {
+            break;
+        }
+        // Out of frames: Ask the callback for enough to fill the rest of the buffer.
+        let frame_count = wrapper
+            .resampler
+            .max_consumed((n_samples - written) / n_channels);
+        (wrapper.callback)(resize_as_frames(&mut wrapper.frames, frame_count));
+        wrapper.unread = 0;
+    }
+    let output =
+        unsafe { std::slice::from_raw_parts_mut(output as *mut Conv::DeviceSample, n_samples) };
+    Conv::to_device_slice(&wrapper.device_frames, output);
+    0
+}
+
+pub extern "C-unwind" fn instream_callback<Frame, Conv: Conversion>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32
+where
+    Conv::Sample: Sample + Duplex<f64>,
+{
+    let wrapper =
+        unsafe { (user_data as *mut ResamplingWrapper<InputCallback<Frame>, Conv>).as_mut() }
+            .expect("Could not create ResamplingWrapper from user_data.");
+
+    let n_channels = wrapper.n_channels;
+    let n_samples = frame_count as usize * n_channels;
+    let input =
+        unsafe { std::slice::from_raw_parts(input as *const Conv::DeviceSample, n_samples) };
+    wrapper
+        .device_frames
+        .resize(n_samples, Conv::Sample::default());
+    Conv::from_device_slice(input, &mut wrapper.device_frames);
+    let max_written = wrapper.resampler.max_written(frame_count as usize);
+    resize_as_frames::<Frame, _>(&mut wrapper.frames, max_written);
+    let mut read = 0;
+    while read < n_samples {
+        let (consumed, written) = wrapper
+            .resampler
+            .resample(&wrapper.device_frames[read..], &mut wrapper.frames);
+        read += consumed * n_channels;
+        if written > 0 {
+            (wrapper.callback)(&resize_as_frames(&mut wrapper.frames, max_written)[..written]);
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portaudio::internal::convert::Scale;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn interpolates_between_frames() {
+        // Doubles the rate. The first frame is interpolated from silence.
+        let mut resampler = LinearResampler::new(1, 24_000, 48_000);
+        let mut output = [0.0f32; 6];
+        assert_eq!(resampler.resample(&[1.0f32, 2.0, 3.0], &mut output), (3, 6));
+        assert_eq!(output, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+    }
+
+    #[test]
+    fn resumes_across_buffers() {
+        let mut resampler = LinearResampler::new(2, 48_000, 24_000);
+        let mut output = [0i16; 4];
+        assert_eq!(
+            resampler.resample(&[10i16, -10, 20, -20, 30, -30], &mut output),
+            (3, 2)
+        );
+        assert_eq!(output, [0, 0, 20, -20]);
+        // The next frame is between the next two.
+        assert_eq!(resampler.resample(&[40i16, -40], &mut output), (1, 0));
+        assert_eq!(resampler.resample(&[50i16, -50], &mut output), (1, 1));
+        assert_eq!(output[..2], [40, -40]);
+    }
+
+    #[test]
+    fn picks_the_resampler_for_the_quality() {
+        let resampler = Resampler::new(ResamplerQuality::SincBest, 2, 44_100, 48_000);
+        #[cfg(feature = "rubato")]
+        assert!(matches!(resampler, Resampler::Sinc(_)));
+        #[cfg(not(feature = "rubato"))]
+        assert!(matches!(resampler, Resampler::Linear(_)));
+        let resampler = Resampler::new(ResamplerQuality::Linear, 2, 44_100, 48_000);
+        assert!(matches!(resampler, Resampler::Linear(_)));
+    }
+
+    #[cfg(feature = "rubato")]
+    #[test]
+    fn sinc_filters_keep_the_signal() {
+        let mut resampler = SincResampler::new(ResamplerQuality::SincFast, 1, 48_000, 44_100);
+        let mut output = vec![0.0f32; 4800];
+        let (consumed, written) = resampler.resample(&[0.5f32; 4800], &mut output);
+        assert_eq!(consumed, 4800);
+        // All but the last partial chunk is resampled.
+        assert!((4410 - 256..=4410).contains(&written), "{}", written);
+        assert!(
+            (output[written - 1] - 0.5).abs() < 0.01,
+            "{}",
+            output[written - 1]
+        );
+    }
+
+    #[test]
+    fn fills_device_buffers() {
+        let n_frames = Arc::new(Mutex::new(0));
+        let callback: Callback<[f32; 1]> = Box::new({
+            let n_frames = Arc::clone(&n_frames);
+            move |frames| {
+                *n_frames.lock().unwrap() += frames.len();
+                frames.iter_mut().for_each(|frame| *frame = [1.0]);
+            }
+        });
+        let mut wrapper = ResamplingWrapper::<_, Scale<f32, i16>>::new(
+            callback,
+            1,
+            44_100,
+            48_000,
+            ResamplerQuality::Linear,
+            true,
+            Some(480),
+        );
+        let mut output = [0i16; 480];
+        for _ in 0..10 {
+            outstream_callback::<[f32; 1], Scale<f32, i16>>(
+                std::ptr::null(),
+                output.as_mut_ptr() as *mut c_void,
+                output.len() as c_ulong,
+                std::ptr::null(),
+                ffi::PaStreamCallbackFlags::empty(),
+                &mut wrapper as *mut _ as *mut c_void,
+            );
+        }
+        assert_eq!(output[479], i16::MAX);
+        // 4800 frames at 48kHz take 4410 at 44.1kHz, give or take a buffer.
+        let n_frames = *n_frames.lock().unwrap();
+        assert!((4410..4410 + 480).contains(&n_frames), "{}", n_frames);
+    }
+
+    #[test]
+    fn passes_every_captured_frame() {
+        let n_frames = Arc::new(Mutex::new(0));
+        let callback: InputCallback<[i16; 2]> = Box::new({
+            let n_frames = Arc::clone(&n_frames);
+            move |frames| *n_frames.lock().unwrap() += frames.len()
+        });
+        let mut wrapper = ResamplingWrapper::<_, Scale<i16, f32>>::new(
+            callback,
+            2,
+            64_000,
+            8_000,
+            ResamplerQuality::Linear,
+            false,
+            Some(80),
+        );
+        let input = [0.25f32; 2 * 80];
+        instream_callback::<[i16; 2], Scale<i16, f32>>(
+            input.as_ptr() as *const c_void,
+            std::ptr::null_mut(),
+            80,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            &mut wrapper as *mut _ as *mut c_void,
+        );
+        assert_eq!(*n_frames.lock().unwrap(), 80 * 8);
+    }
+
+    #[test]
+    fn preallocates_buffers() {
+        let callback: Callback<[f32; 2]> = Box::new(|frames| {
+            frames.iter_mut().for_each(|frame| *frame = [0.5, -0.5]);
+        });
+        let mut wrapper = ResamplingWrapper::<_, Scale<f32, f32>>::new(
+            callback,
+            2,
+            44_100,
+            48_000,
+            ResamplerQuality::Linear,
+            true,
+            None,
+        );
+        let buffers = (wrapper.frames.as_ptr(), wrapper.device_frames.as_ptr());
+        // Portaudio picks buffers of any size, up to the reserved frames.
+        let mut output = vec![0.0f32; 2 * 8192];
+        for &frame_count in &[8192, 1, 4096, 8192] {
+            outstream_callback::<[f32; 2], Scale<f32, f32>>(
+                std::ptr::null(),
+                output.as_mut_ptr() as *mut c_void,
+                frame_count,
+                std::ptr::null(),
+                ffi::PaStreamCallbackFlags::empty(),
+                &mut wrapper as *mut _ as *mut c_void,
+            );
+        }
+        assert_eq!(
+            (wrapper.frames.as_ptr(), wrapper.device_frames.as_ptr()),
+            buffers
+        );
+    }
+}
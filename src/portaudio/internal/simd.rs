@@ -0,0 +1,317 @@
+//! SIMD loops for the conversions converted streams run most: between `f32` and `i16` or `i32`
+//! samples, and the shifts of 24-bit samples in 32 bits. They use SSE2 on x86_64 and NEON on
+//! aarch64, which both always have them.
+//!
+//! Each loop converts as many samples as fill whole vectors, and returns how many. Callers convert
+//! the rest one by one, as they do everything on other architectures. The loops convert exactly
+//! like the `sample` crate does: Floats are truncated towards zero, saturated, and NaNs are 0.
+
+#[cfg(target_arch = "x86_64")]
+pub use sse2::*;
+
+#[cfg(target_arch = "aarch64")]
+pub use neon::*;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use fallback::*;
+
+/// How many samples of `samples` and `output` fill whole vectors of `lanes` samples.
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    allow(dead_code)
+)]
+fn n_vectorized<S, D>(samples: &[S], output: &[D], lanes: usize) -> usize {
+    samples.len().min(output.len()) / lanes * lanes
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use super::n_vectorized;
+
+    /// Truncates each lane to an i32, as `as i16` would: saturated to i16's range, and 0 for NaN.
+    unsafe fn truncate_to_i16(x: __m128) -> __m128i {
+        let x = _mm_and_ps(x, _mm_cmpord_ps(x, x));
+        let x = _mm_min_ps(_mm_max_ps(x, _mm_set1_ps(-32768.0)), _mm_set1_ps(32767.0));
+        _mm_cvttps_epi32(x)
+    }
+
+    pub fn f32_to_i16(samples: &[f32], output: &mut [i16]) -> usize {
+        let n_converted = n_vectorized(samples, output, 8);
+        unsafe {
+            let scale = _mm_set1_ps(32768.0);
+            for i in (0..n_converted).step_by(8) {
+                let samples = samples.as_ptr().add(i);
+                let low = truncate_to_i16(_mm_mul_ps(_mm_loadu_ps(samples), scale));
+                let high = truncate_to_i16(_mm_mul_ps(_mm_loadu_ps(samples.add(4)), scale));
+                _mm_storeu_si128(
+                    output.as_mut_ptr().add(i).cast(),
+                    _mm_packs_epi32(low, high),
+                );
+            }
+        }
+        n_converted
+    }
+
+    pub fn i16_to_f32(samples: &[i16], output: &mut [f32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 8);
+        unsafe {
+            let scale = _mm_set1_ps(1.0 / 32768.0);
+            for i in (0..n_converted).step_by(8) {
+                let samples = _mm_loadu_si128(samples.as_ptr().add(i).cast());
+                // Each sample, doubled into a 32-bit lane, and shifted back down to sign-extend it.
+                let low = _mm_srai_epi32::<16>(_mm_unpacklo_epi16(samples, samples));
+                let high = _mm_srai_epi32::<16>(_mm_unpackhi_epi16(samples, samples));
+                let output = output.as_mut_ptr().add(i);
+                _mm_storeu_ps(output, _mm_mul_ps(_mm_cvtepi32_ps(low), scale));
+                _mm_storeu_ps(output.add(4), _mm_mul_ps(_mm_cvtepi32_ps(high), scale));
+            }
+        }
+        n_converted
+    }
+
+    pub fn f32_to_i32(samples: &[f32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            let scale = _mm_set1_ps(2_147_483_648.0);
+            for i in (0..n_converted).step_by(4) {
+                let x = _mm_mul_ps(_mm_loadu_ps(samples.as_ptr().add(i)), scale);
+                // Out of range lanes truncate to i32::MIN, which positive ones flip to i32::MAX.
+                let truncated = _mm_xor_si128(
+                    _mm_cvttps_epi32(x),
+                    _mm_castps_si128(_mm_cmpge_ps(x, scale)),
+                );
+                let ordered = _mm_castps_si128(_mm_cmpord_ps(x, x));
+                _mm_storeu_si128(
+                    output.as_mut_ptr().add(i).cast(),
+                    _mm_and_si128(truncated, ordered),
+                );
+            }
+        }
+        n_converted
+    }
+
+    pub fn i32_to_f32(samples: &[i32], output: &mut [f32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            let scale = _mm_set1_ps(1.0 / 2_147_483_648.0);
+            for i in (0..n_converted).step_by(4) {
+                let samples = _mm_loadu_si128(samples.as_ptr().add(i).cast());
+                _mm_storeu_ps(
+                    output.as_mut_ptr().add(i),
+                    _mm_mul_ps(_mm_cvtepi32_ps(samples), scale),
+                );
+            }
+        }
+        n_converted
+    }
+
+    /// Moves 24-bit samples to the high 3 bytes of their 32 bits.
+    pub fn shift_left_8(samples: &[i32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let samples = _mm_loadu_si128(samples.as_ptr().add(i).cast());
+                _mm_storeu_si128(
+                    output.as_mut_ptr().add(i).cast(),
+                    _mm_slli_epi32::<8>(samples),
+                );
+            }
+        }
+        n_converted
+    }
+
+    /// Moves 32-bit samples to the low 3 bytes, sign-extended.
+    pub fn shift_right_8(samples: &[i32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let samples = _mm_loadu_si128(samples.as_ptr().add(i).cast());
+                _mm_storeu_si128(
+                    output.as_mut_ptr().add(i).cast(),
+                    _mm_srai_epi32::<8>(samples),
+                );
+            }
+        }
+        n_converted
+    }
+}
+
+/// NEON's float to integer conversions already truncate, saturate, and turn NaNs into 0.
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::n_vectorized;
+
+    pub fn f32_to_i16(samples: &[f32], output: &mut [i16]) -> usize {
+        let n_converted = n_vectorized(samples, output, 8);
+        unsafe {
+            for i in (0..n_converted).step_by(8) {
+                let samples = samples.as_ptr().add(i);
+                let low = vcvtq_s32_f32(vmulq_n_f32(vld1q_f32(samples), 32768.0));
+                let high = vcvtq_s32_f32(vmulq_n_f32(vld1q_f32(samples.add(4)), 32768.0));
+                vst1q_s16(
+                    output.as_mut_ptr().add(i),
+                    vcombine_s16(vqmovn_s32(low), vqmovn_s32(high)),
+                );
+            }
+        }
+        n_converted
+    }
+
+    pub fn i16_to_f32(samples: &[i16], output: &mut [f32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 8);
+        unsafe {
+            for i in (0..n_converted).step_by(8) {
+                let samples = vld1q_s16(samples.as_ptr().add(i));
+                let low = vcvtq_f32_s32(vmovl_s16(vget_low_s16(samples)));
+                let high = vcvtq_f32_s32(vmovl_s16(vget_high_s16(samples)));
+                let output = output.as_mut_ptr().add(i);
+                vst1q_f32(output, vmulq_n_f32(low, 1.0 / 32768.0));
+                vst1q_f32(output.add(4), vmulq_n_f32(high, 1.0 / 32768.0));
+            }
+        }
+        n_converted
+    }
+
+    pub fn f32_to_i32(samples: &[f32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let x = vmulq_n_f32(vld1q_f32(samples.as_ptr().add(i)), 2_147_483_648.0);
+                vst1q_s32(output.as_mut_ptr().add(i), vcvtq_s32_f32(x));
+            }
+        }
+        n_converted
+    }
+
+    pub fn i32_to_f32(samples: &[i32], output: &mut [f32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let samples = vcvtq_f32_s32(vld1q_s32(samples.as_ptr().add(i)));
+                vst1q_f32(
+                    output.as_mut_ptr().add(i),
+                    vmulq_n_f32(samples, 1.0 / 2_147_483_648.0),
+                );
+            }
+        }
+        n_converted
+    }
+
+    /// Moves 24-bit samples to the high 3 bytes of their 32 bits.
+    pub fn shift_left_8(samples: &[i32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let samples = vld1q_s32(samples.as_ptr().add(i));
+                vst1q_s32(output.as_mut_ptr().add(i), vshlq_n_s32::<8>(samples));
+            }
+        }
+        n_converted
+    }
+
+    /// Moves 32-bit samples to the low 3 bytes, sign-extended.
+    pub fn shift_right_8(samples: &[i32], output: &mut [i32]) -> usize {
+        let n_converted = n_vectorized(samples, output, 4);
+        unsafe {
+            for i in (0..n_converted).step_by(4) {
+                let samples = vld1q_s32(samples.as_ptr().add(i));
+                vst1q_s32(output.as_mut_ptr().add(i), vshrq_n_s32::<8>(samples));
+            }
+        }
+        n_converted
+    }
+}
+
+/// Elsewhere, every sample is converted one by one.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod fallback {
+    pub fn f32_to_i16(_: &[f32], _: &mut [i16]) -> usize {
+        0
+    }
+
+    pub fn i16_to_f32(_: &[i16], _: &mut [f32]) -> usize {
+        0
+    }
+
+    pub fn f32_to_i32(_: &[f32], _: &mut [i32]) -> usize {
+        0
+    }
+
+    pub fn i32_to_f32(_: &[i32], _: &mut [f32]) -> usize {
+        0
+    }
+
+    pub fn shift_left_8(_: &[i32], _: &mut [i32]) -> usize {
+        0
+    }
+
+    pub fn shift_right_8(_: &[i32], _: &mut [i32]) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sample::Sample;
+
+    /// Converts `samples` with `simd`, and checks it against `scalar`, tail included.
+    fn converts_like<S: Copy, D: Copy + Default + PartialEq + std::fmt::Debug>(
+        samples: &[S],
+        simd: fn(&[S], &mut [D]) -> usize,
+        scalar: fn(S) -> D,
+    ) {
+        let mut output = vec![D::default(); samples.len()];
+        let n_converted = simd(samples, &mut output);
+        assert!(n_converted <= samples.len());
+        for (output, &sample) in output[n_converted..]
+            .iter_mut()
+            .zip(&samples[n_converted..])
+        {
+            *output = scalar(sample);
+        }
+        let expected: Vec<D> = samples.iter().map(|&sample| scalar(sample)).collect();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn converts_like_scalar_samples() {
+        let floats = [
+            0.0,
+            -0.0,
+            0.5,
+            -0.5,
+            1.0,
+            -1.0,
+            0.999_999_9,
+            -1.5,
+            2.0,
+            1e-9,
+            -3.051_757_8e-5,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MAX,
+            0.123_456_79,
+            -0.987_654_3,
+            0.25,
+            -0.75,
+        ];
+        converts_like(&floats, f32_to_i16, |sample| sample.to_sample::<i16>());
+        converts_like(&floats, f32_to_i32, |sample| sample.to_sample::<i32>());
+
+        let shorts: Vec<i16> = (0..19).map(|i| (i * 3449 - 32768) as i16).collect();
+        converts_like(&shorts, i16_to_f32, |sample| sample.to_sample::<f32>());
+        let ints: Vec<i32> = (0..19)
+            .map(|i| (i * 226_050_910 - 2_147_483_648i64) as i32)
+            .chain([i32::MAX, -1, 1, 0x12_3456])
+            .collect();
+        converts_like(&ints, i32_to_f32, |sample| sample.to_sample::<f32>());
+        converts_like(&ints, shift_left_8, |sample| sample << 8);
+        converts_like(&ints, shift_right_8, |sample| sample >> 8);
+    }
+}
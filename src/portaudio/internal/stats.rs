@@ -0,0 +1,109 @@
+//! Counts a stream's glitches, as its callback (or its blocking reads and writes) see them.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::stream_options::{StreamStats, StreamStatus};
+
+/// Shared by the stream and its callback. The callback only ever adds to it.
+#[derive(Default)]
+pub struct Stats {
+    input_underflows: AtomicU64,
+    input_overflows: AtomicU64,
+    output_underflows: AtomicU64,
+    output_overflows: AtomicU64,
+    glitched_frames: AtomicU64,
+    /// In nanoseconds on the stream's clock, plus one: 0 if the stream never glitched.
+    last_glitch: AtomicU64,
+    clipped_samples: AtomicU64,
+}
+
+impl Stats {
+    /// Counts the glitches of a buffer of `n_frames` frames, at `time` on the stream's clock.
+    pub fn record(&self, status: StreamStatus, n_frames: usize, time: Duration) {
+        if status.is_ok() {
+            return;
+        }
+        for (glitched, count) in [
+            (status.input_underflow, &self.input_underflows),
+            (status.input_overflow, &self.input_overflows),
+            (status.output_underflow, &self.output_underflows),
+            (status.output_overflow, &self.output_overflows),
+        ] {
+            if glitched {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.glitched_frames
+            .fetch_add(n_frames as u64, Ordering::Relaxed);
+        self.last_glitch
+            .store(time.as_nanos() as u64 + 1, Ordering::Relaxed);
+    }
+
+    pub fn record_clipping(&self, n_samples: usize) {
+        self.clipped_samples
+            .fetch_add(n_samples as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StreamStats {
+        let load = |count: &AtomicU64| count.load(Ordering::Relaxed);
+        StreamStats {
+            input_underflows: load(&self.input_underflows),
+            input_overflows: load(&self.input_overflows),
+            output_underflows: load(&self.output_underflows),
+            output_overflows: load(&self.output_overflows),
+            glitched_frames: load(&self.glitched_frames),
+            last_glitch: match load(&self.last_glitch) {
+                0 => None,
+                time => Some(Duration::from_nanos(time - 1)),
+            },
+            clipped_samples: load(&self.clipped_samples),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_glitches() {
+        let stats = Stats::default();
+        stats.record(StreamStatus::default(), 256, Duration::from_secs(1));
+        assert_eq!(stats.snapshot(), StreamStats::default());
+
+        let underflow = StreamStatus {
+            output_underflow: true,
+            ..Default::default()
+        };
+        stats.record(underflow, 256, Duration::from_secs(2));
+        stats.record(
+            StreamStatus {
+                output_overflow: true,
+                ..underflow
+            },
+            128,
+            Duration::from_secs(3),
+        );
+        // Priming isn't a glitch.
+        stats.record(
+            StreamStatus {
+                priming_output: true,
+                ..Default::default()
+            },
+            64,
+            Duration::from_secs(4),
+        );
+        stats.record_clipping(3);
+        assert_eq!(
+            stats.snapshot(),
+            StreamStats {
+                output_underflows: 2,
+                output_overflows: 1,
+                glitched_frames: 384,
+                last_glitch: Some(Duration::from_secs(3)),
+                clipped_samples: 3,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,112 +1,1832 @@
 use libportaudio_sys as ffi;
-use std::os::raw::{c_ulong, c_void};
+use sample::conv::Duplex;
+use sample::Sample;
+use std::convert::TryInto;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_ulong, c_void};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
-use crate::error::{Error, Result};
+use crate::error::{with_callback_errors, CallbackError, Error, Result};
 use crate::portaudio::device::DeviceHandle;
-use crate::portaudio::error::PaErrorAsResult as _;
-use crate::portaudio::{global_lock, LockGuard, RawPtr};
-use crate::stream_options::{Callback, StreamOptions};
+use crate::portaudio::error::{self, PaErrorIntoResult as _};
+use crate::portaudio::internal::convert::{
+    self, Conversion, ConvertingWrapper, I24In32ToI32, Scale,
+};
+use crate::portaudio::internal::device::Device;
+use crate::portaudio::internal::dynamic::{self, DynamicWrapper};
+use crate::portaudio::internal::info::{self, InfoWrapper};
+use crate::portaudio::internal::metrics::Metrics;
+use crate::portaudio::internal::mix::{self, MixingWrapper};
+use crate::portaudio::internal::pause::{Action, OutputLayout, Pause};
+use crate::portaudio::internal::planar::{self, PlanarWrapper};
+use crate::portaudio::internal::raw::{self, RawWrapper};
+use crate::portaudio::internal::reconnect::{copy_params, Reopen, Signal, Supervised, Supervisor};
+use crate::portaudio::internal::resample::{self, ResamplingWrapper};
+use crate::portaudio::internal::stats::Stats;
+use crate::portaudio::internal::volume::Volume;
+use crate::portaudio::internal::watchdog::{Heartbeat, Watchdog};
+use crate::portaudio::{global_lock, stream_lock, LockGuard, RawPtr};
+use crate::priority;
+use crate::stream_options::{
+    Callback, CallbackKind, CallbackMetrics, ChannelMap, ChannelMixPolicy, ChannelSelection,
+    ClipPolicy, ClockCorrelation, DitherMode, DuplexCallback, DuplexInfoCallback, DynamicInput,
+    DynamicOutput, FinishedCallback, Format, Input, InputCallback, InputWithInfo, NoCallback,
+    Output, OutputWithInfo, PlanarInput, PlanarOutput, RawInput, RawOutput, ReconnectPolicy,
+    StopMode, StreamInfo, StreamOptions, StreamState, StreamStats, StreamStatus,
+};
+use crate::surround::ChannelMask;
+
+/// Turns a stream's channel selection into a channel map onto all of the device's channels.
+fn map_channel_selection<Frame, K: CallbackKind>(
+    options: &mut StreamOptions<Frame, K>,
+    device: &Device,
+    is_output: bool,
+) -> Result<()> {
+    let range = match std::mem::take(&mut options.channels) {
+        ChannelSelection::All => return Ok(()),
+        ChannelSelection::Range(range) => range,
+    };
+    if options.channel_map.is_some() {
+        return Err(Error::IncompatibleStreamMode);
+    }
+    if range.end - range.start != options.n_channels {
+        return Err(Error::IncompatibleNChannels);
+    }
+    // Channels past the device's are caught when the map is turned into weights.
+    options.channel_map = Some(range.enumerate().fold(
+        ChannelMap::new(device.max_channels(is_output)),
+        |map, (channel, device_channel)| map.route(channel as i32, device_channel),
+    ));
+    Ok(())
+}
+
+/// Turns a stream's channel mask into a channel map that routes each of its channels to the
+/// device's channel for the same position. Channels the device has no speaker for are dropped (or
+/// silent, for input streams). Masks with the usual layout are left to the device.
+fn map_channel_mask<Frame, K: CallbackKind>(
+    options: &mut StreamOptions<Frame, K>,
+    device: &Device,
+    is_output: bool,
+) -> Result<()> {
+    let mask = match options.channel_mask {
+        Some(mask) => mask,
+        None => return Ok(()),
+    };
+    // Streams can't have both a mask and a map.
+    options.validate_layout(true)?;
+    options.channel_mask = None;
+    if ChannelMask::default_for(options.n_channels) == Some(mask) {
+        return Ok(());
+    }
+    let device_positions = device
+        .channel_positions(is_output)
+        .ok_or(Error::IncompatibleStreamMode)?;
+    let mut map = ChannelMap::new(device_positions.len() as i32);
+    for (channel, position) in mask.positions().into_iter().enumerate() {
+        if let Some(device_channel) = device_positions.iter().position(|&p| p == position) {
+            map = map.route(channel as i32, device_channel as i32);
+        }
+    }
+    options.channel_map = Some(map);
+    Ok(())
+}
 
 /// Convenience structure to collect data needed for stream creation.
-pub struct StreamOpenParams<Frame> {
-    pub user_options: StreamOptions<Frame>,
+pub struct StreamOpenParams<Frame, Kind: CallbackKind = Output> {
+    pub user_options: StreamOptions<Frame, Kind>,
     pub pa_params: ffi::PaStreamParameters,
+    pub open: OpenParams,
+    /// What `pa_params` points WASAPI devices at, which has to live until the stream is opened.
+    #[cfg(windows)]
+    pub _wasapi_info: Option<Box<ffi::PaWasapiStreamInfo>>,
+}
+
+/// What [`StreamImpl::open`] opens every stream with, taken out of its options once.
+pub struct OpenParams {
     pub sample_rate: i32,
+    pub frames_per_buffer: Option<i32>,
+    /// Taken out of the options, which streams check like other backends do.
+    pub on_finished: Option<FinishedCallback>,
+    /// The gain of each of the stream's channels, taken out of the options like `on_finished`.
+    pub gains: Option<Vec<f32>>,
+    /// How Portaudio converts the stream's samples to the device's.
+    pub flags: ffi::PaStreamFlags,
+    /// What the callback does with the samples out of range.
+    pub clip_policy: ClipPolicy,
+    /// How the callback's thread is scheduled.
+    pub priority: Priority,
+    /// How long the callback can go uncalled before the stream is reported stalled.
+    pub watchdog: Option<Duration>,
+    /// Whether the stream reopens after losing its device.
+    pub reconnect: ReconnectPolicy,
+}
+
+impl<Frame, Kind: CallbackKind> StreamOpenParams<Frame, Kind> {
+    pub fn new(
+        user_options: StreamOptions<Frame, Kind>,
+        pa_params: ffi::PaStreamParameters,
+        sample_rate: i32,
+    ) -> Result<StreamOpenParams<Frame, Kind>> {
+        let mut user_options = user_options;
+        #[cfg(windows)]
+        let mut pa_params = pa_params;
+        let gains = user_options.channel_gains()?;
+        user_options.gain = 1.0;
+        user_options.channel_gains = None;
+        #[cfg(windows)]
+        let wasapi_info = wasapi_stream_info(&mut user_options, &mut pa_params);
+        #[cfg(target_os = "linux")]
+        let alsa_realtime = apply_alsa_options(&mut user_options, &pa_params)?;
+        #[cfg(not(target_os = "linux"))]
+        let alsa_realtime = false;
+        Ok(StreamOpenParams {
+            open: OpenParams {
+                sample_rate,
+                frames_per_buffer: user_options.frames_per_buffer.frames(),
+                on_finished: user_options.on_finished.take(),
+                gains,
+                flags: stream_flags(&user_options),
+                clip_policy: user_options.clip_policy,
+                priority: Priority::new(user_options.realtime_priority, alsa_realtime),
+                watchdog: user_options.watchdog.take(),
+                reconnect: std::mem::take(&mut user_options.reconnect),
+            },
+            #[cfg(windows)]
+            _wasapi_info: wasapi_info,
+            user_options,
+            pa_params,
+        })
+    }
+}
+
+/// How the callback's thread is scheduled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Normal,
+    /// Promoted to real-time priority once the callback runs.
+    Realtime,
+    /// Also scheduled with `SCHED_FIFO` by Portaudio's ALSA host API, from the moment it starts.
+    AlsaRealtime,
+}
+
+impl Priority {
+    fn new(realtime_priority: bool, alsa_realtime: bool) -> Priority {
+        match (realtime_priority, alsa_realtime) {
+            (_, true) => Priority::AlsaRealtime,
+            (true, false) => Priority::Realtime,
+            (false, false) => Priority::Normal,
+        }
+    }
+}
+
+/// The type of the host API that the device is on.
+#[cfg(any(windows, target_os = "linux"))]
+fn host_api_type(device: ffi::PaDeviceIndex) -> Option<ffi::PaHostApiTypeId> {
+    let info = unsafe { ffi::Pa_GetDeviceInfo(device).as_ref() }?;
+    let host_info = unsafe { ffi::Pa_GetHostApiInfo(info.hostApi).as_ref() }?;
+    Some(host_info.type_)
+}
+
+/// How many periods Portaudio's ALSA streams have, unless they're told otherwise.
+#[cfg(target_os = "linux")]
+const DEFAULT_ALSA_PERIODS: u32 = 4;
+
+/// Applies the options' ALSA settings to streams on ALSA devices: Sizes their periods, and sets
+/// how many of them Portaudio gives their buffers, which it has one setting for, for every stream
+/// opened from then on. Returns whether the stream's thread is scheduled with `SCHED_FIFO`.
+#[cfg(target_os = "linux")]
+fn apply_alsa_options<Frame, Kind: CallbackKind>(
+    options: &mut StreamOptions<Frame, Kind>,
+    pa_params: &ffi::PaStreamParameters,
+) -> Result<bool> {
+    if host_api_type(pa_params.device) != Some(ffi::PaHostApiTypeId::paALSA) {
+        return Ok(false);
+    }
+    let alsa = options.alsa.unwrap_or_default();
+    alsa.validate()?;
+    options.frames_per_buffer = alsa.frames_per_buffer(options.frames_per_buffer)?;
+    let periods = alsa
+        .periods
+        .unwrap_or(DEFAULT_ALSA_PERIODS)
+        .try_into()
+        .or(Err(Error::InvalidFramesPerBuffer))?;
+    unsafe { ffi::PaAlsa_SetNumPeriods(periods) }.into_result()?;
+    Ok(alsa.realtime_scheduling)
+}
+
+/// Points the parameters of WASAPI devices at the options' WASAPI settings. Takes the settings
+/// that Portaudio's WASAPI host API applies out of the options: Exclusive mode, which other
+/// Portaudio streams reject, and real-time priority, as its thread joins its MMCSS task itself.
+#[cfg(windows)]
+fn wasapi_stream_info<Frame, Kind: CallbackKind>(
+    options: &mut StreamOptions<Frame, Kind>,
+    pa_params: &mut ffi::PaStreamParameters,
+) -> Option<Box<ffi::PaWasapiStreamInfo>> {
+    use crate::windows::ThreadPriority;
+
+    let is_wasapi = host_api_type(pa_params.device) == Some(ffi::PaHostApiTypeId::paWASAPI);
+    if !is_wasapi || (options.wasapi.is_none() && !options.exclusive) {
+        return None;
+    }
+    let wasapi = options.wasapi.unwrap_or_default();
+    let mut flags = ffi::paWinWasapiThreadPriority;
+    if std::mem::take(&mut options.exclusive) || wasapi.exclusive {
+        flags |= ffi::paWinWasapiExclusive;
+    }
+    if wasapi.auto_convert_pcm {
+        flags |= ffi::paWinWasapiAutoConvert;
+    }
+    if !wasapi.event_driven {
+        flags |= ffi::paWinWasapiPolling;
+    }
+    let thread_priority = match wasapi.thread_priority {
+        _ if !std::mem::take(&mut options.realtime_priority) => ffi::eThreadPriorityNone,
+        ThreadPriority::None => ffi::eThreadPriorityNone,
+        ThreadPriority::Audio => ffi::eThreadPriorityAudio,
+        ThreadPriority::Capture => ffi::eThreadPriorityCapture,
+        ThreadPriority::Distribution => ffi::eThreadPriorityDistribution,
+        ThreadPriority::Games => ffi::eThreadPriorityGames,
+        ThreadPriority::Playback => ffi::eThreadPriorityPlayback,
+        ThreadPriority::ProAudio => ffi::eThreadPriorityProAudio,
+        ThreadPriority::WindowManager => ffi::eThreadPriorityWindowManager,
+    };
+    let mut info = Box::new(ffi::PaWasapiStreamInfo {
+        size: std::mem::size_of::<ffi::PaWasapiStreamInfo>() as c_ulong,
+        hostApiType: ffi::PaHostApiTypeId::paWASAPI,
+        version: 1,
+        flags,
+        channelMask: 0,
+        hostProcessorOutput: None,
+        hostProcessorInput: None,
+        threadPriority: thread_priority,
+        streamCategory: ffi::eAudioCategoryOther,
+        streamOption: ffi::eStreamOptionNone,
+    });
+    pa_params.hostApiSpecificStreamInfo = &mut *info as *mut ffi::PaWasapiStreamInfo as *mut c_void;
+    Some(info)
+}
+
+/// The flags for Portaudio's own conversions, which clip and dither unless they're told not to.
+fn stream_flags<Frame, Kind: CallbackKind>(
+    options: &StreamOptions<Frame, Kind>,
+) -> ffi::PaStreamFlags {
+    let mut flags = ffi::PaStreamFlags::PaNoFlag;
+    if options.dither == DitherMode::Off {
+        flags |= ffi::PaStreamFlags::PaDitherOff;
+    }
+    if options.clip_policy == ClipPolicy::Wrap {
+        flags |= ffi::PaStreamFlags::PaClipOff;
+    }
+    if options.prime_output {
+        flags |= ffi::PaStreamFlags::PaPrimeOutputBuffersUsingStreamCallback;
+    }
+    flags
+}
+
+/// Spreads the stream's channel gains over the device's channels. Streams routed or mixed onto a
+/// different number of channels can only have the same gain on every channel.
+fn device_gains(gains: Vec<f32>, device_n_channels: usize) -> Result<Vec<f32>> {
+    if gains.len() == device_n_channels {
+        return Ok(gains);
+    }
+    match gains.split_first() {
+        Some((&gain, rest)) if rest.iter().all(|&other| other == gain) => {
+            Ok(vec![gain; device_n_channels])
+        }
+        _ => Err(Error::IncompatibleStreamMode),
+    }
 }
 
 /// Internal stream implementation. Deals with the Portaudio boilerplate.
 pub struct StreamImpl<Frame> {
-    pa_stream: RawPtr<ffi::PaStream>,
-    cb_wrapper: Box<CallbackWrapper<Frame>>,
+    /// Shared with the supervisor, which replaces it when the stream reconnects. Null once the
+    /// stream's device was lost, until it's reopened.
+    pa_stream: Arc<Mutex<RawPtr<ffi::PaStream>>>,
+    /// The user callback. Only ever accessed by the Portaudio callback through its user data, so its
+    /// type is erased. Must outlive pa_stream.
+    _cb_wrapper: Box<dyn Send>,
+    /// Where the callback's errors go. `None` for blocking streams, and once taken.
+    error_receiver: Option<Receiver<CallbackError>>,
+    /// Shared with the callback. `None` for blocking streams, which have no callback to pause.
+    pause: Option<Arc<Pause>>,
+    /// Shared with the callback. `None` for blocking and input streams, which have no output for
+    /// the callback to scale.
+    volume: Option<Arc<Volume>>,
+    /// How the stream stops when it's dropped.
+    drop_mode: StopMode,
     _sample_rate: i32,
+    /// The latencies Portaudio settled on. `None` for streams without input, or output.
+    input_latency: Option<Duration>,
+    output_latency: Option<Duration>,
+    info: StreamInfo,
+    /// The first clock correlation, which the others' drift is estimated from.
+    clock_reference: Mutex<Option<(Duration, Instant)>>,
+    /// Shared with the callback.
+    stats: Arc<Stats>,
+    /// Shared with the callback.
+    metrics: Arc<Metrics>,
+    /// `None` for streams that aren't watched.
+    watchdog: Option<Watchdog>,
+    /// `None` for streams that don't reconnect.
+    supervisor: Option<Supervisor>,
+    /// Shared with the callback.
+    stopping: Arc<AtomicBool>,
     /// Handle back to the parent device.
     _parent_device: DeviceHandle,
+    _frame: PhantomData<Frame>,
 }
 
-impl<Frame> StreamImpl<Frame> {
+/// Opens a converted stream with `$open`, generic over the conversion from `$format` to
+/// `$device_format`.
+macro_rules! open_converted {
+    ($open:ident($($arg:expr),*), $format:expr, $device_format:expr) => {
+        match $format {
+            Format::F64 => open_converted!(@scale $open($($arg),*), f64, $device_format),
+            Format::F32 => open_converted!(@scale $open($($arg),*), f32, $device_format),
+            Format::I32 => open_converted!(@scale $open($($arg),*), i32, $device_format),
+            Format::I24In32 if $device_format == Format::I32 => {
+                StreamImpl::$open::<I24In32ToI32>($($arg),*)
+            }
+            Format::I16 => open_converted!(@scale $open($($arg),*), i16, $device_format),
+            Format::I8 => open_converted!(@scale $open($($arg),*), i8, $device_format),
+            Format::U32 => open_converted!(@scale $open($($arg),*), u32, $device_format),
+            Format::U16 => open_converted!(@scale $open($($arg),*), u16, $device_format),
+            Format::U8 => open_converted!(@scale $open($($arg),*), u8, $device_format),
+            format => Err(Error::IncompatibleFormat(format)),
+        }
+    };
+    (@scale $open:ident($($arg:expr),*), $sample:ty, $device_format:expr) => {
+        match $device_format {
+            Format::F32 => StreamImpl::$open::<Scale<$sample, f32>>($($arg),*),
+            Format::I32 => StreamImpl::$open::<Scale<$sample, i32>>($($arg),*),
+            Format::I16 => StreamImpl::$open::<Scale<$sample, i16>>($($arg),*),
+            format => Err(Error::IncompatibleFormat(format)),
+        }
+    };
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
     pub fn new_outstream(
         params: StreamOpenParams<Frame>,
         device: DeviceHandle,
     ) -> Result<StreamImpl<Frame>> {
         let _guard = global_lock();
-        // Verify stream spec.
-        is_stream_spec_supported(&params, true, &_guard)?;
-        // Wrap the callback into a thin pointer.
-        let callback = Box::new(CallbackWrapper(params.user_options.callback));
+        let mut params = params;
+        map_channel_selection(&mut params.user_options, &device, true)?;
+        map_channel_mask(&mut params.user_options, &device, true)?;
+        let map = params.user_options.channel_map.take();
+        // Whatever the stream didn't take out of the options by now is for other backends.
+        params.user_options.validate_layout(false)?;
+        if let Some(map) = map {
+            let matrix = map.matrix(params.user_options.n_channels, true)?;
+            let device_n_channels = map.device_n_channels();
+            return StreamImpl::new_mapped_outstream(
+                params,
+                matrix,
+                device_n_channels,
+                device,
+                &_guard,
+            );
+        }
+        let device_format = match negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            true,
+            params.open.sample_rate,
+            &_guard,
+        ) {
+            Err(Error::IncompatibleSampleRate) if params.user_options.resample_if_needed => {
+                return StreamImpl::new_resampled_outstream(params, device, &_guard);
+            }
+            Err(Error::IncompatibleNChannels)
+                if params.user_options.channel_mix_policy == ChannelMixPolicy::Mix =>
+            {
+                return StreamImpl::new_mixed_outstream(params, device, &_guard);
+            }
+            result => result?,
+        };
+        if device_format != params.user_options.format {
+            return open_converted!(
+                new_converted_outstream(params, device, &_guard),
+                params.user_options.format,
+                device_format
+            );
+        }
+        let callback = CallbackWrapper(params.user_options.callback);
+        let stream = StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(outstream_callback::<Frame>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )?;
+        // Verify the frame size.
+        is_frame_size_valid::<Frame>(
+            params.pa_params.sampleFormat,
+            params.user_options.n_channels,
+            &_guard,
+        )?;
+        Ok(stream)
+    }
+
+    pub fn new_instream(
+        params: StreamOpenParams<Frame, Input>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Frame>> {
+        let _guard = global_lock();
+        let mut params = params;
+        map_channel_selection(&mut params.user_options, &device, false)?;
+        map_channel_mask(&mut params.user_options, &device, false)?;
+        let map = params.user_options.channel_map.take();
+        // Whatever the stream didn't take out of the options by now is for other backends.
+        params.user_options.validate_layout(false)?;
+        if let Some(map) = map {
+            let matrix = map.matrix(params.user_options.n_channels, false)?;
+            let device_n_channels = map.device_n_channels();
+            return StreamImpl::new_mapped_instream(
+                params,
+                matrix,
+                device_n_channels,
+                device,
+                &_guard,
+            );
+        }
+        let device_format = match negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            false,
+            params.open.sample_rate,
+            &_guard,
+        ) {
+            Err(Error::IncompatibleSampleRate) if params.user_options.resample_if_needed => {
+                return StreamImpl::new_resampled_instream(params, device, &_guard);
+            }
+            Err(Error::IncompatibleNChannels)
+                if params.user_options.channel_mix_policy == ChannelMixPolicy::Mix =>
+            {
+                return StreamImpl::new_mixed_instream(params, device, &_guard);
+            }
+            result => result?,
+        };
+        if device_format != params.user_options.format {
+            return open_converted!(
+                new_converted_instream(params, device, &_guard),
+                params.user_options.format,
+                device_format
+            );
+        }
+        let callback = CallbackWrapper(params.user_options.callback);
+        let stream = StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(instream_callback::<Frame>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )?;
+        is_frame_size_valid::<Frame>(
+            params.pa_params.sampleFormat,
+            params.user_options.n_channels,
+            &_guard,
+        )?;
+        Ok(stream)
+    }
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
+    /// Opens an output stream whose device runs in the format `Conv` converts to.
+    fn new_converted_outstream<Conv: Conversion>(
+        params: StreamOpenParams<Frame>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        params.user_options.validate_frame_size()?;
+        let dither = params.user_options.dither;
+        let callback = ConvertingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            params.user_options.frames_per_buffer.frames(),
+            params.user_options.n_channels,
+        )
+        .dithered(dither);
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(convert::outstream_callback::<Frame, Conv>),
+            callback,
+            params.open,
+            device,
+            guard,
+        )
+    }
+
+    /// Opens an input stream whose device runs in the format `Conv` converts from.
+    fn new_converted_instream<Conv: Conversion>(
+        params: StreamOpenParams<Frame, Input>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        params.user_options.validate_frame_size()?;
+        let callback = ConvertingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            params.user_options.frames_per_buffer.frames(),
+            params.user_options.n_channels,
+        );
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(convert::instream_callback::<Frame, Conv>),
+            callback,
+            params.open,
+            device,
+            guard,
+        )
+    }
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
+    /// Opens an output stream whose device runs at its default sample rate, instead of the
+    /// stream's.
+    fn new_resampled_outstream(
+        params: StreamOpenParams<Frame>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let device_rate = device.default_sample_rate();
+        if device_rate == params.open.sample_rate {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        let mut params = params;
+        let device_format = negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            true,
+            device_rate,
+            guard,
+        )?;
+        open_converted!(
+            open_resampled_outstream(params, device_rate, device, guard),
+            params.user_options.format,
+            device_format
+        )
+    }
+
+    /// Opens an input stream whose device runs at its default sample rate, instead of the
+    /// stream's.
+    fn new_resampled_instream(
+        params: StreamOpenParams<Frame, Input>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let device_rate = device.default_sample_rate();
+        if device_rate == params.open.sample_rate {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        let mut params = params;
+        let device_format = negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            false,
+            device_rate,
+            guard,
+        )?;
+        open_converted!(
+            open_resampled_instream(params, device_rate, device, guard),
+            params.user_options.format,
+            device_format
+        )
+    }
+
+    fn open_resampled_outstream<Conv: Conversion>(
+        params: StreamOpenParams<Frame>,
+        device_rate: i32,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Conv::Sample: Sample + Duplex<f64>,
+    {
+        params.user_options.validate_frame_size()?;
+        let callback = ResamplingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+            params.open.sample_rate,
+            device_rate,
+            params.user_options.resampler_quality,
+            true,
+            params.user_options.frames_per_buffer.frames(),
+        );
+        let mut stream = StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(resample::outstream_callback::<Frame, Conv>),
+            callback,
+            OpenParams {
+                sample_rate: device_rate,
+                ..params.open
+            },
+            device,
+            guard,
+        )?;
+        // Resamplers call back for as many frames as each device buffer takes, which varies.
+        stream.info = StreamInfo::default();
+        Ok(stream)
+    }
+
+    fn open_resampled_instream<Conv: Conversion>(
+        params: StreamOpenParams<Frame, Input>,
+        device_rate: i32,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Conv::Sample: Sample + Duplex<f64>,
+    {
+        params.user_options.validate_frame_size()?;
+        let callback = ResamplingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+            params.open.sample_rate,
+            device_rate,
+            params.user_options.resampler_quality,
+            false,
+            params.user_options.frames_per_buffer.frames(),
+        );
+        let mut stream = StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(resample::instream_callback::<Frame, Conv>),
+            callback,
+            OpenParams {
+                sample_rate: device_rate,
+                ..params.open
+            },
+            device,
+            guard,
+        )?;
+        // Resamplers call back for as many frames as each device buffer takes, which varies.
+        stream.info = StreamInfo::default();
+        Ok(stream)
+    }
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
+    /// Opens an output stream whose device runs with all of its channels, instead of the
+    /// stream's.
+    fn new_mixed_outstream(
+        params: StreamOpenParams<Frame>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let device_n_channels = device.max_channels(true);
+        if device_n_channels == params.user_options.n_channels || device_n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let matrix = mix::mix_matrix(
+            params.user_options.n_channels as usize,
+            device_n_channels as usize,
+        );
+        StreamImpl::new_mapped_outstream(params, matrix, device_n_channels, device, guard)
+    }
+
+    /// Opens an output stream whose device runs with `device_n_channels` channels, which `matrix`
+    /// mixes the callback's channels into.
+    fn new_mapped_outstream(
+        params: StreamOpenParams<Frame>,
+        matrix: Vec<Vec<f64>>,
+        device_n_channels: i32,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let mut params = params;
+        params.pa_params.channelCount = device_n_channels;
+        let device_format = negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            true,
+            params.open.sample_rate,
+            guard,
+        )?;
+        open_converted!(
+            open_mixed_outstream(params, matrix, device, guard),
+            params.user_options.format,
+            device_format
+        )
+    }
+
+    /// Opens an input stream whose device runs with all of its channels, instead of the
+    /// stream's.
+    fn new_mixed_instream(
+        params: StreamOpenParams<Frame, Input>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let device_n_channels = device.max_channels(false);
+        if device_n_channels == params.user_options.n_channels || device_n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let matrix = mix::mix_matrix(
+            device_n_channels as usize,
+            params.user_options.n_channels as usize,
+        );
+        StreamImpl::new_mapped_instream(params, matrix, device_n_channels, device, guard)
+    }
+
+    /// Opens an input stream whose device runs with `device_n_channels` channels, which `matrix`
+    /// mixes into the callback's channels.
+    fn new_mapped_instream(
+        params: StreamOpenParams<Frame, Input>,
+        matrix: Vec<Vec<f64>>,
+        device_n_channels: i32,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let mut params = params;
+        params.pa_params.channelCount = device_n_channels;
+        let device_format = negotiate_format(
+            params.user_options.format,
+            &mut params.pa_params,
+            false,
+            params.open.sample_rate,
+            guard,
+        )?;
+        open_converted!(
+            open_mixed_instream(params, matrix, device, guard),
+            params.user_options.format,
+            device_format
+        )
+    }
+
+    fn open_mixed_outstream<Conv: Conversion>(
+        params: StreamOpenParams<Frame>,
+        matrix: Vec<Vec<f64>>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Conv::Sample: Sample + Duplex<f64>,
+    {
+        params.user_options.validate_frame_size()?;
+        let callback = MixingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            matrix,
+            params.user_options.n_channels,
+            params.pa_params.channelCount,
+            params.user_options.frames_per_buffer.frames(),
+        );
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(mix::outstream_callback::<Frame, Conv>),
+            callback,
+            params.open,
+            device,
+            guard,
+        )
+    }
+
+    fn open_mixed_instream<Conv: Conversion>(
+        params: StreamOpenParams<Frame, Input>,
+        matrix: Vec<Vec<f64>>,
+        device: DeviceHandle,
+        guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Conv::Sample: Sample + Duplex<f64>,
+    {
+        params.user_options.validate_frame_size()?;
+        let callback = MixingWrapper::<_, Conv>::new(
+            params.user_options.callback,
+            matrix,
+            params.user_options.n_channels,
+            params.pa_params.channelCount,
+            params.user_options.frames_per_buffer.frames(),
+        );
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(mix::instream_callback::<Frame, Conv>),
+            callback,
+            params.open,
+            device,
+            guard,
+        )
+    }
+}
+
+impl<Sample: 'static> StreamImpl<Sample> {
+    pub fn new_planar_outstream(
+        params: StreamOpenParams<Sample, PlanarOutput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        is_stream_spec_supported(
+            None,
+            Some(&params.pa_params),
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback = PlanarWrapper::<_, &'static mut [Sample]>::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+        );
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(planar::outstream_callback::<Sample>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+
+    pub fn new_planar_instream(
+        params: StreamOpenParams<Sample, PlanarInput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        is_stream_spec_supported(
+            Some(&params.pa_params),
+            None,
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback = PlanarWrapper::<_, &'static [Sample]>::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+        );
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(planar::instream_callback::<Sample>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+}
+
+impl<Sample: 'static> StreamImpl<Sample> {
+    pub fn new_dynamic_outstream(
+        params: StreamOpenParams<Sample, DynamicOutput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        is_stream_spec_supported(
+            None,
+            Some(&params.pa_params),
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback =
+            DynamicWrapper::new(params.user_options.callback, params.user_options.n_channels);
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(dynamic::outstream_callback::<Sample>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+
+    pub fn new_dynamic_instream(
+        params: StreamOpenParams<Sample, DynamicInput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        is_stream_spec_supported(
+            Some(&params.pa_params),
+            None,
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback =
+            DynamicWrapper::new(params.user_options.callback, params.user_options.n_channels);
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(dynamic::instream_callback::<Sample>),
+            callback,
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+}
+
+impl<Sample: 'static> StreamImpl<Sample> {
+    /// Raw streams hand the callback Portaudio's own buffer, so they don't apply gains either.
+    pub fn new_raw_outstream(
+        params: StreamOpenParams<Sample, RawOutput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        if params.open.gains.is_some() {
+            return Err(Error::IncompatibleStreamMode);
+        }
+        is_stream_spec_supported(
+            None,
+            Some(&params.pa_params),
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback = RawWrapper::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+            params.user_options.format,
+        );
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(raw::outstream_callback),
+            callback,
+            OpenParams {
+                gains: None,
+                ..params.open
+            },
+            device,
+            &_guard,
+        )
+    }
+
+    pub fn new_raw_instream(
+        params: StreamOpenParams<Sample, RawInput>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Sample>> {
+        let _guard = global_lock();
+        params.user_options.validate_sample_size()?;
+        if params.open.gains.is_some() {
+            return Err(Error::IncompatibleStreamMode);
+        }
+        is_stream_spec_supported(
+            Some(&params.pa_params),
+            None,
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        let callback = RawWrapper::new(
+            params.user_options.callback,
+            params.user_options.n_channels,
+            params.user_options.format,
+        );
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(raw::instream_callback),
+            callback,
+            OpenParams {
+                gains: None,
+                ..params.open
+            },
+            device,
+            &_guard,
+        )
+    }
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
+    pub fn new_outstream_with_info(
+        params: StreamOpenParams<Frame, OutputWithInfo>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Frame>> {
+        let _guard = global_lock();
+        params.user_options.validate_frame_size()?;
+        is_stream_spec_supported(
+            None,
+            Some(&params.pa_params),
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        StreamImpl::open(
+            None,
+            Some(&params.pa_params),
+            Some(info::outstream_callback::<Frame>),
+            InfoWrapper(params.user_options.callback),
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+
+    pub fn new_instream_with_info(
+        params: StreamOpenParams<Frame, InputWithInfo>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Frame>> {
+        let _guard = global_lock();
+        params.user_options.validate_frame_size()?;
+        is_stream_spec_supported(
+            Some(&params.pa_params),
+            None,
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        StreamImpl::open(
+            Some(&params.pa_params),
+            None,
+            Some(info::instream_callback::<Frame>),
+            InfoWrapper(params.user_options.callback),
+            params.open,
+            device,
+            &_guard,
+        )
+    }
+}
+
+impl<Frame: 'static> StreamImpl<Frame> {
+    pub fn new_blocking_stream(
+        params: StreamOpenParams<Frame, NoCallback>,
+        is_output: bool,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Frame>> {
+        let _guard = global_lock();
+        let (input_params, output_params) = if is_output {
+            (None, Some(&params.pa_params))
+        } else {
+            (Some(&params.pa_params), None)
+        };
+        is_stream_spec_supported(
+            input_params,
+            output_params,
+            params.open.sample_rate,
+            &_guard,
+        )?;
+        // Blocking streams have no callback.
+        let stream = StreamImpl::open(
+            input_params,
+            output_params,
+            None,
+            (),
+            params.open,
+            device,
+            &_guard,
+        )?;
+        is_frame_size_valid::<Frame>(
+            params.pa_params.sampleFormat,
+            params.user_options.n_channels,
+            &_guard,
+        )?;
+        Ok(stream)
+    }
+}
+
+impl<InFrame: 'static, OutFrame: 'static> StreamImpl<(InFrame, OutFrame)> {
+    pub fn new_duplex_stream(
+        input: StreamOpenParams<InFrame, NoCallback>,
+        output: StreamOpenParams<OutFrame, NoCallback>,
+        callback: DuplexCallback<InFrame, OutFrame>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<(InFrame, OutFrame)>> {
+        StreamImpl::open_duplex(
+            input,
+            output,
+            duplex_stream_callback::<InFrame, OutFrame>,
+            CallbackWrapper(callback),
+            device,
+        )
+    }
+
+    pub fn new_duplex_stream_with_info(
+        input: StreamOpenParams<InFrame, NoCallback>,
+        output: StreamOpenParams<OutFrame, NoCallback>,
+        callback: DuplexInfoCallback<InFrame, OutFrame>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<(InFrame, OutFrame)>> {
+        StreamImpl::open_duplex(
+            input,
+            output,
+            info::duplex_stream_callback::<InFrame, OutFrame>,
+            InfoWrapper(callback),
+            device,
+        )
+    }
+
+    /// Opens a duplex stream whose `pa_callback` is passed a pointer to `cb_wrapper`.
+    fn open_duplex<W: Send + 'static>(
+        input: StreamOpenParams<InFrame, NoCallback>,
+        output: StreamOpenParams<OutFrame, NoCallback>,
+        pa_callback: StreamCallback,
+        cb_wrapper: W,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<(InFrame, OutFrame)>> {
+        let _guard = global_lock();
+        debug_assert_eq!(input.open.sample_rate, output.open.sample_rate);
+        is_stream_spec_supported(
+            Some(&input.pa_params),
+            Some(&output.pa_params),
+            output.open.sample_rate,
+            &_guard,
+        )?;
+        let stream = StreamImpl::open(
+            Some(&input.pa_params),
+            Some(&output.pa_params),
+            Some(pa_callback),
+            cb_wrapper,
+            OpenParams {
+                on_finished: None,
+                gains: None,
+                ..output.open
+            },
+            device,
+            &_guard,
+        )?;
+        is_frame_size_valid::<InFrame>(
+            input.pa_params.sampleFormat,
+            input.user_options.n_channels,
+            &_guard,
+        )?;
+        is_frame_size_valid::<OutFrame>(
+            output.pa_params.sampleFormat,
+            output.user_options.n_channels,
+            &_guard,
+        )?;
+        Ok(stream)
+    }
+}
+
+impl<Frame> StreamImpl<Frame> {
+    /// Opens the Portaudio stream. `pa_callback` is passed a pointer to `cb_wrapper` as its user
+    /// data.
+    fn open<W: Send + 'static>(
+        input_params: Option<&ffi::PaStreamParameters>,
+        output_params: Option<&ffi::PaStreamParameters>,
+        pa_callback: Option<StreamCallback>,
+        cb_wrapper: W,
+        params: OpenParams,
+        device: DeviceHandle,
+        _guard: &LockGuard,
+    ) -> Result<StreamImpl<Frame>> {
+        let OpenParams {
+            sample_rate,
+            frames_per_buffer,
+            on_finished,
+            gains,
+            flags,
+            clip_policy,
+            priority,
+            watchdog,
+            reconnect,
+        } = params;
+        // Only the callback's output is scaled on its way to the device.
+        let gains = match (gains, output_params) {
+            (None, _) => None,
+            (Some(gains), Some(params)) if pa_callback.is_some() => {
+                Some(device_gains(gains, params.channelCount as usize)?)
+            }
+            _ => return Err(Error::IncompatibleStreamMode),
+        };
+        // Callback streams that finish on their own have lost their device.
+        let has_finished_callback = on_finished.is_some() || pa_callback.is_some();
+        let stopping = Arc::new(AtomicBool::new(false));
+        let (errors, error_receiver) = mpsc::sync_channel(MAX_PENDING_ERRORS);
+        let pause = Arc::new(Pause::default());
+        let stats = Arc::new(Stats::default());
+        let metrics = Arc::new(Metrics::new(sample_rate));
+        let volume = Arc::new(Volume::default());
+        // Blocking streams have no callback to watch.
+        let heartbeat = watchdog
+            .and(pa_callback)
+            .map(|_| Arc::new(Heartbeat::default()));
+        // Only callback streams can tell when their device is lost.
+        let signal = match reconnect {
+            ReconnectPolicy::None => None,
+            _ => pa_callback.map(|_| Arc::new(Signal::default())),
+        };
+        let watchdog = match (watchdog, &heartbeat) {
+            (Some(timeout), Some(heartbeat)) => Some(Watchdog::spawn(
+                timeout,
+                Arc::clone(heartbeat),
+                errors.clone(),
+            )?),
+            _ => None,
+        };
+        let supervised_errors = errors.clone();
+        let user_data = Box::new(UserData {
+            cb_wrapper,
+            on_finished,
+            pa_callback,
+            errors,
+            pause: Arc::clone(&pause),
+            stats: Arc::clone(&stats),
+            metrics: Arc::clone(&metrics),
+            heartbeat: heartbeat.clone(),
+            signal: signal.clone(),
+            stopping: Arc::clone(&stopping),
+            volume: Arc::clone(&volume),
+            gains,
+            clip_policy,
+            realtime_rate: if priority == Priority::Normal {
+                None
+            } else {
+                Some(sample_rate)
+            },
+            output_layout: output_params.map(OutputLayout::new),
+        });
+        let user_data_ptr = Box::as_ref(&user_data) as *const UserData<W> as *mut c_void;
         // Create the Portaudio stream.
         let mut stream = StreamImpl {
-            pa_stream: RawPtr::dangling(),
+            pa_stream: Arc::new(Mutex::new(RawPtr::dangling())),
             _sample_rate: 0,
-            cb_wrapper: callback,
+            input_latency: None,
+            output_latency: None,
+            // Portaudio adapts the host's buffers to exactly the requested size, if there's one.
+            info: frames_per_buffer.map_or_else(StreamInfo::default, |frames| {
+                StreamInfo::fixed(frames as usize)
+            }),
+            clock_reference: Mutex::default(),
+            stats: Arc::clone(&stats),
+            metrics,
+            watchdog,
+            supervisor: None,
+            stopping,
+            _cb_wrapper: user_data,
+            error_receiver: pa_callback.map(|_| error_receiver),
+            pause: pa_callback.map(|_| pause),
+            volume: pa_callback.and(output_params).map(|_| volume),
+            drop_mode: StopMode::default(),
             _parent_device: device,
+            _frame: PhantomData,
         };
+        let mut pa_stream = RawPtr::dangling();
         unsafe {
             ffi::Pa_OpenStream(
-                &mut stream.pa_stream as *const _ as *mut _,
-                std::ptr::null(),
-                &params.pa_params,
-                params.sample_rate.into(),
-                params
-                    .user_options
-                    .frames_per_buffer
-                    .unwrap_or(ffi::paFramesPerBufferUnspecified as i32) as c_ulong,
-                ffi::PaStreamFlags::PaNoFlag, // No flags
-                Some(outstream_callback::<Frame>),
-                Box::as_ref(&stream.cb_wrapper) as *const _ as *mut _,
+                &mut pa_stream as *const _ as *mut _,
+                input_params.map_or(std::ptr::null(), |params| params as *const _),
+                output_params.map_or(std::ptr::null(), |params| params as *const _),
+                sample_rate.into(),
+                frames_per_buffer.unwrap_or(ffi::paFramesPerBufferUnspecified as i32) as c_ulong,
+                flags,
+                pa_callback.map(|_| guarded_callback::<W> as _),
+                user_data_ptr,
             )
         }
-        .as_result()?;
-        debug_assert!(!stream.pa_stream.is_null());
-        // Verify the frame size.
-        is_frame_size_valid::<Frame>(
-            params.pa_params.sampleFormat,
-            params.user_options.n_channels,
-            &_guard,
-        )?;
+        .into_result()?;
+        debug_assert!(!pa_stream.is_null());
+        *stream.pa_stream() = pa_stream;
+        let pa_stream = stream.pa_stream().as_ptr_mut();
+        #[cfg(target_os = "linux")]
+        if priority == Priority::AlsaRealtime {
+            unsafe { ffi::PaAlsa_EnableRealtimeScheduling(pa_stream, 1) };
+        }
+        if has_finished_callback {
+            unsafe { ffi::Pa_SetStreamFinishedCallback(pa_stream, Some(finished_callback::<W>)) }
+                .into_result()?;
+        }
         // Get the stream info.
-        let stream_info =
-            *(unsafe { ffi::Pa_GetStreamInfo(stream.pa_stream.as_ptr_mut()).as_ref() }
-                .ok_or(Error::Unknown("Could not get stream info after creation."))?);
+        let stream_info = *(unsafe { ffi::Pa_GetStreamInfo(pa_stream).as_ref() }
+            .ok_or(Error::Unknown("Could not get stream info after creation."))?);
         // TODO: Do something with this sample rate.
         stream._sample_rate = stream_info.sampleRate as i32;
+        stream.input_latency = input_params.map(|_| info::to_duration(stream_info.inputLatency));
+        stream.output_latency = output_params.map(|_| info::to_duration(stream_info.outputLatency));
+        if let Some(signal) = signal {
+            let reopen = Reopen {
+                input_params: input_params.map(copy_params),
+                output_params: output_params.map(copy_params),
+                sample_rate,
+                frames_per_buffer: frames_per_buffer
+                    .unwrap_or(ffi::paFramesPerBufferUnspecified as i32)
+                    as c_ulong,
+                flags,
+                pa_callback: Some(guarded_callback::<W>),
+                finished_callback: Some(finished_callback::<W>),
+                user_data: user_data_ptr,
+                #[cfg(target_os = "linux")]
+                alsa_realtime: priority == Priority::AlsaRealtime,
+            };
+            stream.supervisor = Some(Supervisor::spawn(
+                reconnect,
+                reopen,
+                signal,
+                Supervised {
+                    pa_stream: Arc::clone(&stream.pa_stream),
+                    stopping: Arc::clone(&stream.stopping),
+                    heartbeat,
+                    errors: supervised_errors,
+                },
+            )?);
+        }
         Ok(stream)
     }
 
+    /// The Portaudio stream, which the supervisor can't replace while it's locked.
+    fn pa_stream(&self) -> MutexGuard<'_, RawPtr<ffi::PaStream>> {
+        self.pa_stream.lock().unwrap()
+    }
+
+    /// The Portaudio stream, unless its device was lost and it wasn't reopened.
+    fn live_stream(&self) -> Result<MutexGuard<'_, RawPtr<ffi::PaStream>>> {
+        let pa_stream = self.pa_stream();
+        if pa_stream.is_null() {
+            return Err(Error::DeviceLost);
+        }
+        Ok(pa_stream)
+    }
+
+    pub fn input_latency(&self) -> Option<Duration> {
+        self.input_latency
+    }
+
+    pub fn output_latency(&self) -> Option<Duration> {
+        self.output_latency
+    }
+
+    pub fn info(&self) -> StreamInfo {
+        self.info
+    }
+
+    /// The stream's clock, which the callback info's times are on. 0 if the stream can't tell.
+    pub fn time(&self) -> Duration {
+        info::to_duration(unsafe { ffi::Pa_GetStreamTime(self.pa_stream().as_ptr() as *mut _) })
+    }
+
+    pub fn clock_correlation(&self) -> ClockCorrelation {
+        // The stream time is read halfway between the instants around it.
+        let before = Instant::now();
+        let stream_time = self.time();
+        let after = Instant::now();
+        let instant = before + (after - before) / 2;
+        let mut reference = self.clock_reference.lock().unwrap();
+        let correlation = ClockCorrelation::new(stream_time, instant, *reference);
+        // Streams that can't tell the time have nothing to drift from.
+        if reference.is_none() && stream_time > Duration::default() {
+            *reference = Some((stream_time, instant));
+        }
+        correlation
+    }
+
+    /// The fraction of real time spent in the callback, averaged over the last buffers. Always 0
+    /// for blocking streams.
+    pub fn cpu_load(&self) -> f64 {
+        let _guard = stream_lock();
+        unsafe { ffi::Pa_GetStreamCpuLoad(self.pa_stream().as_ptr() as *mut _) }
+    }
+
+    pub fn take_error_receiver(&mut self) -> Option<Receiver<CallbackError>> {
+        self.error_receiver.take()
+    }
+
+    /// Stops calling the callback, without stopping the stream. Output streams fade out, then play
+    /// silence.
+    pub fn pause(&mut self) -> Result<()> {
+        self.pause
+            .as_ref()
+            .ok_or(Error::IncompatibleStreamMode)?
+            .pause();
+        Ok(())
+    }
+
+    /// Calls the callback again, fading output streams back in.
+    pub fn resume(&mut self) -> Result<()> {
+        self.pause
+            .as_ref()
+            .ok_or(Error::IncompatibleStreamMode)?
+            .resume();
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.as_ref().is_some_and(|pause| pause.is_paused())
+    }
+
+    /// Scales the callback's output from the next buffer on, ramping to the new volume over it.
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        if !(volume.is_finite() && volume >= 0.0) {
+            return Err(Error::Invalid);
+        }
+        self.volume
+            .as_ref()
+            .ok_or(Error::IncompatibleStreamMode)?
+            .set(volume);
+        Ok(())
+    }
+
+    pub fn volume(&self) -> Result<f32> {
+        Ok(self
+            .volume
+            .as_ref()
+            .ok_or(Error::IncompatibleStreamMode)?
+            .get())
+    }
+
+    /// Whether the stream is calling its callback, or has buffered frames left to play.
+    pub fn is_active(&self) -> Result<bool> {
+        let _guard = stream_lock();
+        let pa_stream = self.live_stream()?;
+        Ok(unsafe { ffi::Pa_IsStreamActive(pa_stream.as_ptr() as *mut _) }.into_result()? != 0)
+    }
+
+    /// Whether the stream was never started, or was stopped (or aborted).
+    pub fn is_stopped(&self) -> Result<bool> {
+        let _guard = stream_lock();
+        let pa_stream = self.live_stream()?;
+        Ok(unsafe { ffi::Pa_IsStreamStopped(pa_stream.as_ptr() as *mut _) }.into_result()? != 0)
+    }
+
+    pub fn state(&self) -> Result<StreamState> {
+        if self.is_stopped()? {
+            Ok(StreamState::Stopped)
+        } else if self.is_active()? {
+            Ok(StreamState::Running)
+        } else {
+            Ok(StreamState::Finished)
+        }
+    }
+
     /// Stream is inactive (i.e. no callback) until this method is called.
     pub fn start(&mut self) -> Result<()> {
-        let _guard = global_lock();
+        let _guard = stream_lock();
+        let pa_stream = self.live_stream()?;
         // Make sure the stream isn't actually running.
-        match unsafe { ffi::Pa_IsStreamStopped(self.pa_stream.as_ptr() as *mut _) }.into() {
-            Ok(0) => Err(Error::StreamAlreadyStarted),
+        match unsafe { ffi::Pa_IsStreamStopped(pa_stream.as_ptr() as *mut _) }.into() {
+            // Streams whose callback ended them are inactive, but still have to be stopped.
+            Ok(0) => match unsafe { ffi::Pa_IsStreamActive(pa_stream.as_ptr() as *mut _) }.into() {
+                Ok(0) => unsafe { ffi::Pa_StopStream(pa_stream.as_ptr() as *mut _) }
+                    .into_result()
+                    .and(Ok(())),
+                Ok(_) => Err(Error::StreamAlreadyStarted),
+                Err(error) => Err(error.into()),
+            },
             Ok(_) => Ok(()),
             Err(error) => Err(error.into()),
         }?;
         // Now, open the stream.
-        unsafe { ffi::Pa_StartStream(self.pa_stream.as_ptr() as *mut _) }
-            .as_result()
-            .and(Ok(()))
+        self.stopping.store(false, Ordering::Relaxed);
+        unsafe { ffi::Pa_StartStream(pa_stream.as_ptr() as *mut _) }.into_stream_result()?;
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.arm();
+        }
+        Ok(())
+    }
+
+    // The blocking read/write calls intentionally do not hold the global lock: they block until the
+    // device is done with the data, and Portaudio only requires that they are not called
+    // concurrently on the same stream (which &mut self guarantees).
+
+    /// Writes frames to a blocking output stream.
+    pub fn write(&mut self, frames: &[Frame]) -> Result<()> {
+        // The stream isn't locked while blocked, nor while a glitch is timed, which locks it again.
+        let pa_stream = self.pa_stream().as_ptr_mut();
+        match unsafe {
+            ffi::Pa_WriteStream(
+                pa_stream,
+                frames.as_ptr() as *const c_void,
+                frames.len() as c_ulong,
+            )
+        }
+        .into()
+        {
+            Ok(_) => Ok(()),
+            // An underflow means there was a glitch before this write. The frames were still
+            // written.
+            Err(ffi::PaErrorCode::paOutputUnderflowed) => {
+                self.record_glitch(
+                    StreamStatus {
+                        output_underflow: true,
+                        ..Default::default()
+                    },
+                    frames.len(),
+                );
+                Ok(())
+            }
+            Err(error) => Err(error::stream_error(error)),
+        }
+    }
+
+    /// Starts a stopped blocking output stream with the first of `frames` written, as many as fit
+    /// in its buffer without blocking. Returns how many it wrote.
+    pub fn prime(&mut self, frames: &[Frame]) -> Result<usize> {
+        if !self.is_stopped()? {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        self.start()?;
+        let available = unsafe { ffi::Pa_GetStreamWriteAvailable(self.pa_stream().as_ptr_mut()) };
+        let n_frames = frames
+            .len()
+            .min(ffi::PaError(available as c_int).into_stream_result()? as usize);
+        self.write(&frames[..n_frames])?;
+        Ok(n_frames)
+    }
+
+    /// Reads frames from a blocking input stream.
+    pub fn read(&mut self, frames: &mut [Frame]) -> Result<()> {
+        // As in write, the stream isn't locked while blocked.
+        let pa_stream = self.pa_stream().as_ptr_mut();
+        match unsafe {
+            ffi::Pa_ReadStream(
+                pa_stream,
+                frames.as_mut_ptr() as *mut c_void,
+                frames.len() as c_ulong,
+            )
+        }
+        .into()
+        {
+            Ok(_) => Ok(()),
+            // An overflow means some data was dropped before this read. The buffer was still
+            // filled.
+            Err(ffi::PaErrorCode::paInputOverflowed) => {
+                self.record_glitch(
+                    StreamStatus {
+                        input_overflow: true,
+                        ..Default::default()
+                    },
+                    frames.len(),
+                );
+                Ok(())
+            }
+            Err(error) => Err(error::stream_error(error)),
+        }
+    }
+
+    fn record_glitch(&self, status: StreamStatus, n_frames: usize) {
+        self.stats.record(status, n_frames, self.time());
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        self.stats.snapshot()
+    }
+
+    pub fn metrics(&self) -> CallbackMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Stops the stream once the frames that were already buffered are played. Does nothing if the
+    /// stream is already stopped.
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_with(StopMode::Stop)
+    }
+
+    /// Stops the stream as soon as possible, dropping the frames that weren't played yet. Does
+    /// nothing if the stream is already stopped.
+    pub fn abort(&mut self) -> Result<()> {
+        self.stop_with(StopMode::Abort)
+    }
+
+    pub fn set_drop_mode(&mut self, mode: StopMode) {
+        self.drop_mode = mode;
+    }
+
+    fn stop_with(&mut self, mode: StopMode) -> Result<()> {
+        self.stopping.store(true, Ordering::Relaxed);
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.disarm();
+        }
+        let _guard = stream_lock();
+        let pa_stream = self.pa_stream();
+        // Streams that lost their device, and weren't reopened, have nothing left to stop.
+        if pa_stream.is_null() {
+            return Ok(());
+        }
+        match match mode {
+            StopMode::Stop => unsafe { ffi::Pa_StopStream(pa_stream.as_ptr_mut()) },
+            _ => unsafe { ffi::Pa_AbortStream(pa_stream.as_ptr_mut()) },
+        }
+        .into()
+        {
+            Err(ffi::PaErrorCode::paStreamIsStopped) => Ok(()),
+            Err(code) => Err(error::stream_error(code)),
+            _ => Ok(()),
+        }
     }
 
     /// Closes the stream and deallocates any associated data.
     pub fn close(&mut self) -> Result<()> {
+        self.stopping.store(true, Ordering::Relaxed);
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.disarm();
+        }
+        // The supervisor takes the global lock to reopen the stream.
+        self.supervisor.take();
         let _guard = global_lock();
-        unsafe { ffi::Pa_CloseStream(self.pa_stream.as_ptr_mut() as *mut _) }.as_result()?;
+        let mut pa_stream = self.pa_stream();
+        if !pa_stream.is_null() {
+            unsafe { ffi::Pa_CloseStream(pa_stream.as_ptr_mut()) }.into_result()?;
+            *pa_stream = RawPtr::dangling();
+        }
         Ok(())
     }
 }
 
 impl<Frame> Drop for StreamImpl<Frame> {
     fn drop(&mut self) {
-        // Immediately stop execution and close.
+        // Closing aborts the stream, so it only has to be stopped first to play out its buffers.
+        if self.drop_mode == StopMode::Stop {
+            self.stop().expect("Could not stop stream while dropping.");
+        }
         self.close()
             .expect("Could not close stream while dropping.");
     }
 }
 
-/// Wraps Callback in order to avoid dealing with fat closure pointers.
-struct CallbackWrapper<Frame>(Callback<Frame>);
+/// How many callback errors a stream holds for its receiver. Channels with a bound are allocated
+/// when the stream opens, so callbacks never allocate to send errors (or wait to): Errors past the
+/// bound are dropped until the receiver catches up.
+const MAX_PENDING_ERRORS: usize = 64;
+
+/// Wraps a callback in order to avoid dealing with fat closure pointers.
+struct CallbackWrapper<C>(C);
 
-extern "C" fn outstream_callback<Frame>(
+/// A stream's callback, as Portaudio would call it. Unwinds, so that [`guarded_callback`] can catch
+/// the user callback's panics.
+pub type StreamCallback = unsafe extern "C-unwind" fn(
+    *const c_void,
+    *mut c_void,
+    c_ulong,
+    *const ffi::PaStreamCallbackTimeInfo,
+    ffi::PaStreamCallbackFlags,
+    *mut c_void,
+) -> i32;
+
+/// What Portaudio passes the stream's callbacks. The callback's wrapper goes first, so that they
+/// can take the user data as their wrapper.
+#[repr(C)]
+struct UserData<W> {
+    cb_wrapper: W,
+    on_finished: Option<FinishedCallback>,
+    /// The stream's own callback, which [`guarded_callback`] calls.
+    pa_callback: Option<StreamCallback>,
+    errors: SyncSender<CallbackError>,
+    pause: Arc<Pause>,
+    stats: Arc<Stats>,
+    metrics: Arc<Metrics>,
+    /// `None` for streams that aren't watched.
+    heartbeat: Option<Arc<Heartbeat>>,
+    /// `None` for streams that don't reconnect.
+    signal: Option<Arc<Signal>>,
+    /// Whether the stream is being stopped, by its owner or its callback, so that it's expected to
+    /// finish.
+    stopping: Arc<AtomicBool>,
+    volume: Arc<Volume>,
+    /// The gain of each of the device's channels. `None` at unity gain, and for input streams.
+    gains: Option<Vec<f32>>,
+    clip_policy: ClipPolicy,
+    /// The stream's sample rate, if the callback's thread is promoted to real-time priority.
+    realtime_rate: Option<i32>,
+    /// `None` for input streams.
+    output_layout: Option<OutputLayout>,
+}
+
+/// Runs the stream's callback, sending the errors it reports, and aborting the stream if it
+/// panics: Panics can't unwind into Portaudio. Paused streams skip their callback.
+extern "C" fn guarded_callback<W>(
+    input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    time_info: *const ffi::PaStreamCallbackTimeInfo,
+    status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let started = Instant::now();
+    let data = user_data as *mut UserData<W>;
+    // The callback borrows the wrapper, so only the other fields are borrowed here.
+    if let Some(rate) = unsafe { (*data).realtime_rate } {
+        // Portaudio may rerun the callback on another thread, e.g. once the stream is restarted.
+        priority::promote_current_thread(Some(Duration::from_secs_f64(
+            frame_count as f64 / f64::from(rate),
+        )));
+    }
+    let (
+        pa_callback,
+        errors,
+        pause,
+        stats,
+        metrics,
+        heartbeat,
+        stopping,
+        volume,
+        gains,
+        clip_policy,
+        output_layout,
+    ) = unsafe {
+        (
+            (*data).pa_callback,
+            &*std::ptr::addr_of!((*data).errors),
+            &*std::ptr::addr_of!((*data).pause),
+            &*std::ptr::addr_of!((*data).stats),
+            &*std::ptr::addr_of!((*data).metrics),
+            &*std::ptr::addr_of!((*data).heartbeat),
+            &*std::ptr::addr_of!((*data).stopping),
+            &*std::ptr::addr_of!((*data).volume),
+            &*std::ptr::addr_of!((*data).gains),
+            (*data).clip_policy,
+            (*data).output_layout,
+        )
+    };
+    let pa_callback = pa_callback.expect("Guarded streams have a callback.");
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.beat();
+    }
+    let now =
+        unsafe { time_info.as_ref() }.map(|time_info| info::to_duration(time_info.currentTime));
+    stats.record(
+        info::stream_status(status_flags),
+        frame_count as usize,
+        now.unwrap_or_default(),
+    );
+    if let Some(now) = now {
+        metrics.record_arrival(frame_count as usize, now);
+    }
+    let action = pause.next_action();
+    // Input streams have nothing to fade out.
+    if action == Action::Skip || (action == Action::FadeOut && output_layout.is_none()) {
+        if let Some(layout) = output_layout {
+            unsafe { layout.silence(output, frame_count as usize) };
+        }
+        return ffi::PaStreamCallbackResult::paContinue as i32;
+    }
+    let call = || {
+        panic::catch_unwind(|| unsafe {
+            pa_callback(
+                input,
+                output,
+                frame_count,
+                time_info,
+                status_flags,
+                user_data,
+            )
+        })
+    };
+    #[cfg(feature = "rt-check")]
+    let call = || {
+        let (result, violation) = crate::rt::checked(call);
+        if let Some((first, count)) = violation {
+            errors
+                .try_send(CallbackError::NotRealTime(first, count))
+                .ok();
+        }
+        result
+    };
+    let result = with_callback_errors(errors, call).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        errors.try_send(CallbackError::Panicked(message)).ok();
+        ffi::PaStreamCallbackResult::paAbort as i32
+    });
+    if let Some(layout) = output_layout {
+        if let Some(gains) = gains {
+            unsafe { layout.scale(output, frame_count as usize, gains) };
+        }
+        match action {
+            Action::FadeOut => unsafe { layout.fade(output, frame_count as usize, false) },
+            Action::FadeIn => unsafe { layout.fade(output, frame_count as usize, true) },
+            _ => (),
+        }
+        if let Some((from, to)) = volume.next_ramp() {
+            unsafe { layout.ramp(output, frame_count as usize, from, to) };
+        }
+        if clip_policy != ClipPolicy::Wrap {
+            let clipped = unsafe { layout.clip(output, frame_count as usize) };
+            if clipped > 0 && clip_policy == ClipPolicy::ErrorOnClip {
+                stats.record_clipping(clipped);
+                errors.try_send(CallbackError::Clipped(clipped)).ok();
+            }
+        }
+    }
+    metrics.record_duration(frame_count as usize, started.elapsed());
+    if result != ffi::PaStreamCallbackResult::paContinue as i32 {
+        stopping.store(true, Ordering::Relaxed);
+        if let Some(heartbeat) = heartbeat {
+            heartbeat.finish();
+        }
+    }
+    result
+}
+
+extern "C" fn finished_callback<W>(user_data: *mut c_void) {
+    let user_data = unsafe { (user_data as *mut UserData<W>).as_mut() }
+        .expect("Could not create UserData from user_data.");
+    if user_data.pa_callback.is_some() && !user_data.stopping.load(Ordering::Relaxed) {
+        if let Some(heartbeat) = &user_data.heartbeat {
+            heartbeat.finish();
+        }
+        user_data.errors.try_send(CallbackError::DeviceLost).ok();
+        if let Some(signal) = &user_data.signal {
+            signal.lose();
+        }
+    }
+    if let Some(on_finished) = &mut user_data.on_finished {
+        on_finished();
+    }
+}
+
+extern "C-unwind" fn outstream_callback<Frame>(
     _input: *const c_void,
     output: *mut c_void,
     frame_count: c_ulong,
@@ -114,7 +1834,7 @@ extern "C" fn outstream_callback<Frame>(
     _status_flags: ffi::PaStreamCallbackFlags,
     user_data: *mut c_void,
 ) -> i32 {
-    let callback = unsafe { (user_data as *mut CallbackWrapper<Frame>).as_mut() }
+    let callback = unsafe { (user_data as *mut CallbackWrapper<Callback<Frame>>).as_mut() }
         .expect("Could not create CallbackWrapper from user_data.");
 
     let output =
@@ -123,6 +1843,42 @@ extern "C" fn outstream_callback<Frame>(
     0
 }
 
+extern "C-unwind" fn instream_callback<Frame>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let callback = unsafe { (user_data as *mut CallbackWrapper<InputCallback<Frame>>).as_mut() }
+        .expect("Could not create CallbackWrapper from user_data.");
+
+    let input = unsafe { std::slice::from_raw_parts(input as *const Frame, frame_count as usize) };
+    (callback.0)(input);
+    0
+}
+
+extern "C-unwind" fn duplex_stream_callback<InFrame, OutFrame>(
+    input: *const c_void,
+    output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let callback =
+        unsafe { (user_data as *mut CallbackWrapper<DuplexCallback<InFrame, OutFrame>>).as_mut() }
+            .expect("Could not create CallbackWrapper from user_data.");
+
+    let input =
+        unsafe { std::slice::from_raw_parts(input as *const InFrame, frame_count as usize) };
+    let output =
+        unsafe { std::slice::from_raw_parts_mut(output as *mut OutFrame, frame_count as usize) };
+    (callback.0)(input, output);
+    0
+}
+
 #[must_use]
 fn is_frame_size_valid<Frame>(
     pa_format: ffi::PaSampleFormat,
@@ -132,7 +1888,7 @@ fn is_frame_size_valid<Frame>(
     if n_channels <= 0 {
         return Err(Error::IncompatibleNChannels);
     }
-    let pa_sample_size = unsafe { ffi::Pa_GetSampleSize(pa_format) }.as_result()?;
+    let pa_sample_size = unsafe { ffi::Pa_GetSampleSize(pa_format) }.into_result()?;
     let pa_frame_size = (pa_sample_size * n_channels) as usize;
     if std::mem::size_of::<Frame>() != pa_frame_size {
         return Err(Error::InvalidFrameSize {
@@ -143,29 +1899,118 @@ fn is_frame_size_valid<Frame>(
     Ok(())
 }
 
-#[must_use]
-fn is_stream_spec_supported<F>(
-    params: &StreamOpenParams<F>,
+/// Picks the format the device runs in: The first of the stream format's
+/// [device formats](convert::device_formats) the device takes. Sets it in `pa_params`, and returns
+/// it.
+fn negotiate_format(
+    format: Format,
+    pa_params: &mut ffi::PaStreamParameters,
     is_output: bool,
+    sample_rate: i32,
+    _guard: &LockGuard,
+) -> Result<Format> {
+    for device_format in convert::device_formats(format) {
+        pa_params.sampleFormat = device_format.try_into()?;
+        let (input_params, output_params) = if is_output {
+            (std::ptr::null(), pa_params as *const _)
+        } else {
+            (pa_params as *const _, std::ptr::null())
+        };
+        match unsafe { ffi::Pa_IsFormatSupported(input_params, output_params, sample_rate.into()) }
+            .into()
+        {
+            Ok(_) => return Ok(device_format),
+            // Try the next one.
+            Err(ffi::PaErrorCode::paSampleFormatNotSupported) => (),
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Err(Error::IncompatibleFormat(format))
+}
+
+#[must_use]
+fn is_stream_spec_supported(
+    input_params: Option<&ffi::PaStreamParameters>,
+    output_params: Option<&ffi::PaStreamParameters>,
+    sample_rate: i32,
     _guard: &LockGuard,
 ) -> Result<()> {
-    if is_output {
-        unsafe {
-            ffi::Pa_IsFormatSupported(
-                std::ptr::null(),
-                &params.pa_params,
-                params.sample_rate.into(),
-            )
+    unsafe {
+        ffi::Pa_IsFormatSupported(
+            input_params.map_or(std::ptr::null(), |params| params as *const _),
+            output_params.map_or(std::ptr::null(), |params| params as *const _),
+            sample_rate.into(),
+        )
+    }
+    .into_result()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C-unwind" fn failing_callback(
+        _input: *const c_void,
+        _output: *mut c_void,
+        _frame_count: c_ulong,
+        _time_info: *const ffi::PaStreamCallbackTimeInfo,
+        _status_flags: ffi::PaStreamCallbackFlags,
+        user_data: *mut c_void,
+    ) -> i32 {
+        let n_calls = unsafe { (user_data as *mut i32).as_mut() }.unwrap();
+        *n_calls += 1;
+        assert!(crate::report_callback_error("Out of frames."));
+        if *n_calls > 1 {
+            panic!("Out of ideas.");
         }
-    } else {
-        unsafe {
-            ffi::Pa_IsFormatSupported(
-                &params.pa_params,
+        0
+    }
+
+    #[test]
+    fn sends_callback_errors() {
+        let (errors, receiver) = mpsc::sync_channel(MAX_PENDING_ERRORS);
+        let mut user_data = UserData {
+            cb_wrapper: 0i32,
+            on_finished: None,
+            pa_callback: Some(failing_callback),
+            errors,
+            pause: Arc::default(),
+            stats: Arc::default(),
+            metrics: Arc::default(),
+            heartbeat: None,
+            signal: None,
+            stopping: Arc::default(),
+            volume: Arc::default(),
+            gains: None,
+            clip_policy: ClipPolicy::default(),
+            realtime_rate: None,
+            output_layout: None,
+        };
+        let call = |user_data: &mut UserData<i32>| {
+            guarded_callback::<i32>(
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                0,
                 std::ptr::null(),
-                params.sample_rate.into(),
+                ffi::PaStreamCallbackFlags::empty(),
+                user_data as *mut _ as *mut c_void,
             )
-        }
+        };
+        assert_eq!(call(&mut user_data), 0);
+        assert_eq!(
+            call(&mut user_data),
+            ffi::PaStreamCallbackResult::paAbort as i32
+        );
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                CallbackError::Reported("Out of frames.".to_string()),
+                CallbackError::Reported("Out of frames.".to_string()),
+                CallbackError::Panicked("Out of ideas.".to_string()),
+            ]
+        );
+        // Outside of callbacks, there is nowhere to report to.
+        assert!(!crate::report_callback_error("Too late."));
     }
-    .as_result()?;
-    Ok(())
 }
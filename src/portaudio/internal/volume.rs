@@ -0,0 +1,59 @@
+//! Stream volume, applied to output buffers after the callback fills them. Changes ramp over a
+//! buffer, so that they don't click.
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Shared by the stream, which sets the volume, and its callback, which ramps to it.
+pub struct Volume {
+    /// The volume set on the stream, as f32 bits.
+    target: AtomicU32,
+    /// The volume the callback last ramped to. Only the callback writes it.
+    current: AtomicU32,
+}
+
+impl Default for Volume {
+    fn default() -> Volume {
+        Volume {
+            target: AtomicU32::new(1.0_f32.to_bits()),
+            current: AtomicU32::new(1.0_f32.to_bits()),
+        }
+    }
+}
+
+impl Volume {
+    pub fn set(&self, volume: f32) {
+        self.target.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.target.load(Ordering::Relaxed))
+    }
+
+    /// The gains at the start and end of the next buffer. `None` at unity gain, where buffers are
+    /// left as they are.
+    pub fn next_ramp(&self) -> Option<(f32, f32)> {
+        let target = self.target.load(Ordering::Relaxed);
+        let current = self.current.swap(target, Ordering::Relaxed);
+        if current == target && f32::from_bits(target) == 1.0 {
+            return None;
+        }
+        Some((f32::from_bits(current), f32::from_bits(target)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_to_new_volumes() {
+        let volume = Volume::default();
+        assert_eq!(volume.next_ramp(), None);
+        volume.set(0.5);
+        assert_eq!(volume.get(), 0.5);
+        assert_eq!(volume.next_ramp(), Some((1.0, 0.5)));
+        assert_eq!(volume.next_ramp(), Some((0.5, 0.5)));
+        volume.set(1.0);
+        assert_eq!(volume.next_ramp(), Some((0.5, 1.0)));
+        assert_eq!(volume.next_ramp(), None);
+    }
+}
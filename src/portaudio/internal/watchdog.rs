@@ -0,0 +1,162 @@
+//! Watches that a running stream's callback keeps being called, and reports when it stops: e.g.
+//! when the device stalls, or its driver hangs.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{CallbackError, Error, Result};
+
+/// Shared by the callback, which beats it, and the watchdog.
+#[derive(Default)]
+pub struct Heartbeat {
+    beats: AtomicU64,
+    /// Whether the callback ended the stream, which then stops calling it.
+    finished: AtomicBool,
+}
+
+impl Heartbeat {
+    /// Called at the start of every callback.
+    pub fn beat(&self) {
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called once the callback ends the stream.
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    /// Called once the stream starts again, and its callback with it.
+    pub fn restart(&self) {
+        self.finished.store(false, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether the stream is started, so that its callback should be called.
+    armed: bool,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    heartbeat: Arc<Heartbeat>,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+/// Owns the watchdog's thread, which it stops when dropped.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Spawns a disarmed watchdog, which sends [`CallbackError::StreamStalled`] to `errors` once
+    /// armed and `heartbeat` doesn't beat for `timeout`.
+    pub fn spawn(
+        timeout: Duration,
+        heartbeat: Arc<Heartbeat>,
+        errors: SyncSender<CallbackError>,
+    ) -> Result<Watchdog> {
+        if timeout == Duration::default() {
+            return Err(Error::Invalid);
+        }
+        let shared = Arc::new(Shared {
+            heartbeat,
+            ..Default::default()
+        });
+        let watched = Arc::clone(&shared);
+        let thread = std::thread::Builder::new()
+            .name("audiohal-watchdog".into())
+            .spawn(move || watch(&watched, timeout, &errors))
+            .map_err(|error| {
+                Error::from_source(None, "Could not spawn the watchdog thread.", error)
+            })?;
+        Ok(Watchdog {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Watches the stream, once it's started.
+    pub fn arm(&self) {
+        self.shared.heartbeat.restart();
+        self.shared.state.lock().unwrap().armed = true;
+    }
+
+    /// Stops watching the stream, once it's stopped.
+    pub fn disarm(&self) {
+        self.shared.state.lock().unwrap().armed = false;
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Checks the heartbeat twice per `timeout`, until the watchdog is dropped.
+fn watch(shared: &Shared, timeout: Duration, errors: &SyncSender<CallbackError>) {
+    let mut last_beat = (
+        shared.heartbeat.beats.load(Ordering::Relaxed),
+        Instant::now(),
+    );
+    let mut reported = false;
+    let mut state = shared.state.lock().unwrap();
+    while !state.closed {
+        state = shared.wake.wait_timeout(state, timeout / 2).unwrap().0;
+        let beats = shared.heartbeat.beats.load(Ordering::Relaxed);
+        if !state.armed || shared.heartbeat.finished.load(Ordering::Relaxed) || beats != last_beat.0
+        {
+            last_beat = (beats, Instant::now());
+            reported = false;
+            continue;
+        }
+        let stalled = last_beat.1.elapsed();
+        if stalled >= timeout && !reported {
+            errors.try_send(CallbackError::StreamStalled(stalled)).ok();
+            reported = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn reports_stalls_once() {
+        let timeout = Duration::from_millis(20);
+        let heartbeat = Arc::new(Heartbeat::default());
+        let (errors, receiver) = mpsc::sync_channel(4);
+        let watchdog = Watchdog::spawn(timeout, Arc::clone(&heartbeat), errors).unwrap();
+        // Stopped streams don't stall.
+        std::thread::sleep(timeout * 2);
+        assert!(receiver.try_recv().is_err());
+
+        watchdog.arm();
+        heartbeat.beat();
+        match receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(CallbackError::StreamStalled(stalled)) => assert!(stalled >= timeout),
+            other => panic!("Expected a stall, got {:?}.", other),
+        }
+        std::thread::sleep(timeout * 2);
+        assert!(receiver.try_recv().is_err());
+
+        // Nor do streams that their callback ended.
+        heartbeat.finish();
+        heartbeat.beat();
+        std::thread::sleep(timeout * 2);
+        assert!(receiver.try_recv().is_err());
+        drop(watchdog);
+    }
+}
@@ -1,7 +1,10 @@
 use lazy_static::lazy_static;
 use libportaudio_sys as ffi;
-use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::cell::Cell;
 
+#[cfg(all(windows, feature = "asio"))]
+mod asio;
 mod device;
 mod error;
 mod host;
@@ -11,18 +14,86 @@ mod stream_options;
 mod internal;
 
 // Public API exports.
+#[cfg(all(windows, feature = "asio"))]
+pub use asio::AsioBufferSizes;
 pub use device::Device;
 pub use host::Host;
 pub use stream::Stream;
 
+crate::traits::impl_traits!(Host);
+
+// Portaudio isn't thread-safe, and its state (initialization, the device list, open streams) is
+// shared by every host in the process, so hosts can't have locks of their own. Instead, calls that
+// touch the shared state hold API_LOCK, and calls to a single stream only share STREAM_LOCK with
+// each other: e.g. starting or stopping one stream doesn't wait for another's. Streams keep their
+// host, and so Portaudio, alive, and can't be called into concurrently, as they're not Sync.
+//
+// Neither lock is poisoned by panics, so one panicking thread doesn't break every other caller.
 lazy_static! {
-    static ref GLOBAL_LOCK: ReentrantMutex<()> = ReentrantMutex::new(());
+    static ref API_LOCK: ReentrantMutex<()> = ReentrantMutex::new(());
+    static ref STREAM_LOCK: RwLock<()> = RwLock::new(());
+}
+
+thread_local! {
+    /// How many LockGuards the thread holds. Only the outermost one write-locks STREAM_LOCK.
+    static LOCK_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// How many StreamGuards the thread holds.
+    static STREAM_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Exclusive access to Portaudio. Reentrant.
+pub(crate) struct LockGuard {
+    _streams: Option<RwLockWriteGuard<'static, ()>>,
+    _api: ReentrantMutexGuard<'static, ()>,
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        LOCK_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+pub(crate) fn global_lock() -> LockGuard {
+    #[cfg(feature = "rt-check")]
+    crate::rt::may_block();
+    let api = API_LOCK.lock();
+    let outermost = LOCK_DEPTH.with(|depth| depth.replace(depth.get() + 1)) == 0;
+    // Threads calling into Portaudio from a stream call (e.g. from a finished callback called by
+    // Pa_StopStream) can't wait for the other streams' calls, which may wait for theirs.
+    LockGuard {
+        _streams: if outermost && STREAM_DEPTH.with(Cell::get) == 0 {
+            Some(STREAM_LOCK.write())
+        } else {
+            None
+        },
+        _api: api,
+    }
 }
 
-type LockGuard = ReentrantMutexGuard<'static, ()>;
+/// Access to a single stream, shared with calls to other streams.
+struct StreamGuard {
+    _shared: Option<RwLockReadGuard<'static, ()>>,
+}
 
-fn global_lock() -> LockGuard {
-    GLOBAL_LOCK.lock()
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        STREAM_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Threads that already hold a [`LockGuard`] (e.g. when closing a stream that failed to open)
+/// don't lock again.
+fn stream_lock() -> StreamGuard {
+    #[cfg(feature = "rt-check")]
+    crate::rt::may_block();
+    STREAM_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    if LOCK_DEPTH.with(Cell::get) > 0 {
+        StreamGuard { _shared: None }
+    } else {
+        StreamGuard {
+            _shared: Some(STREAM_LOCK.read_recursive()),
+        }
+    }
 }
 
 impl std::convert::TryFrom<crate::Format> for ffi::PaSampleFormat {
@@ -34,6 +105,7 @@ impl std::convert::TryFrom<crate::Format> for ffi::PaSampleFormat {
         Ok(match format {
             F32 => PaSampleFormat::paFloat32,
             I32 => PaSampleFormat::paInt32,
+            // paInt24 is packed. I24In32 streams run the device in I32 (see internal::convert).
             I24 => PaSampleFormat::paInt24,
             I16 => PaSampleFormat::paInt16,
             I8 => PaSampleFormat::paInt8,
@@ -81,8 +153,9 @@ unsafe impl<T> Send for RawPtr<T> {}
 unsafe impl<T> Sync for RawPtr<T> {}
 
 #[cfg(test)]
-mod test_prelude {
+pub(crate) mod test_prelude {
     pub use super::*;
+    pub use super::{Device, Host};
     pub use crate::*;
     pub use galvanic_assert::matchers::variant::*;
     pub use galvanic_assert::matchers::*;
@@ -105,7 +178,36 @@ mod test_prelude {
         static ref TEST_LOCK: ReentrantMutex<()> = ReentrantMutex::new(());
     }
 
-    pub fn test_lock() -> LockGuard {
+    pub fn test_lock() -> ReentrantMutexGuard<'static, ()> {
         TEST_LOCK.lock()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn shares_stream_calls() {
+        let _guard = stream_lock();
+        // Another stream's call doesn't wait for this one.
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _guard = stream_lock();
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn nests_locks() {
+        let _outer = global_lock();
+        let _inner = global_lock();
+        let _stream = stream_lock();
+        drop(_outer);
+        drop(_inner);
+        let _guard = global_lock();
+    }
+}
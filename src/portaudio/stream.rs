@@ -1,5 +1,12 @@
-use crate::error::Result;
+use crate::error::{CallbackError, Result};
 use crate::portaudio::device::DeviceHandle;
+use crate::stream_options::{
+    CallbackMetrics, ClockCorrelation, DuplexCallback, DuplexInfoCallback, DynamicInput,
+    DynamicOutput, Input, InputWithInfo, NoCallback, OutputWithInfo, PlanarInput, PlanarOutput,
+    RawInput, RawOutput, StopMode, StreamInfo, StreamState, StreamStats,
+};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 
 use crate::portaudio::internal::stream as internal;
 
@@ -8,26 +15,154 @@ use crate::portaudio::internal::stream as internal;
 /// an output stream (e.g. speakers).
 pub struct Stream<Frame>(internal::StreamImpl<Frame>);
 
-// impl<Frame> StreamImpl<Frame> {
-
-//     /// Aborts the execution of the stream, throwing away any data already buffered. Does nothing
-//     /// if the stream is not running.
-//     fn abort(&mut self) -> Result<()> {
-//         let _guard = global_lock();
-//         match unsafe { ffi::Pa_AbortStream(self.pa_stream.as_ptr() as *mut _) }.into() {
-//             Err(ffi::PaErrorCode::paStreamIsStopped) => Ok(()),
-//             Err(code) => Err(code.into()),
-//             _ => Ok(()),
-//         }
-//     }
-
-// }
-
 impl<Frame> Stream<Frame> {
     pub fn start(&mut self) -> Result<()> {
         self.0.start()
     }
 
+    /// Writes frames to a blocking output stream. Blocks until all frames have been written.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking output stream.
+    pub fn write(&mut self, frames: &[Frame]) -> Result<()> {
+        self.0.write(frames)
+    }
+
+    /// Starts a stopped blocking output stream with the first of `frames` already written, as many
+    /// as its buffer takes without blocking, so that it doesn't underrun before the next write.
+    /// Returns how many it wrote.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking output stream.
+    pub fn prime(&mut self, frames: &[Frame]) -> Result<usize> {
+        self.0.prime(frames)
+    }
+
+    /// Reads frames from a blocking input stream. Blocks until the whole buffer has been filled.
+    ///
+    /// Returns [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) if the
+    /// stream is not a blocking input stream.
+    pub fn read(&mut self, frames: &mut [Frame]) -> Result<()> {
+        self.0.read(frames)
+    }
+
+    /// How long captured frames take to reach the callback, as negotiated when the stream was
+    /// opened. `None` for streams without input.
+    pub fn input_latency(&self) -> Option<Duration> {
+        self.0.input_latency()
+    }
+
+    /// How long the callback's frames take to be played, as negotiated when the stream was
+    /// opened. `None` for streams without output.
+    pub fn output_latency(&self) -> Option<Duration> {
+        self.0.output_latency()
+    }
+
+    /// Streams with a `frames_per_buffer` call back with exactly that many frames, which
+    /// Portaudio adapts the host's buffers to. Others get whatever the host API delivers, as do
+    /// resampled streams.
+    pub fn info(&self) -> StreamInfo {
+        self.0.info()
+    }
+
+    /// The stream's clock, which only goes forward. It's the clock of the
+    /// [`CallbackInfo`](crate::CallbackInfo) times.
+    pub fn time(&self) -> Duration {
+        self.0.time()
+    }
+
+    /// Reads the stream's time, and when it was read, to translate between stream and wall-clock
+    /// times. The drift is estimated since the stream's first correlation.
+    pub fn clock_correlation(&self) -> ClockCorrelation {
+        self.0.clock_correlation()
+    }
+
+    /// The glitches the stream had since it was opened.
+    pub fn stats(&self) -> StreamStats {
+        self.0.stats()
+    }
+
+    /// How long the stream's callbacks took, and how regularly they came. Blocking streams have
+    /// none.
+    pub fn metrics(&self) -> CallbackMetrics {
+        self.0.metrics()
+    }
+
+    /// The fraction of real time spent in the callback: Glitches are likely once it nears 1.
+    /// Always 0 for blocking streams.
+    pub fn cpu_load(&self) -> f64 {
+        self.0.cpu_load()
+    }
+
+    /// Takes the receiver of the errors from the stream's callback. `None` for blocking streams,
+    /// and once taken.
+    pub fn take_error_receiver(&mut self) -> Option<Receiver<CallbackError>> {
+        self.0.take_error_receiver()
+    }
+
+    /// Whether the stream is calling its callback, or has buffered frames left to play.
+    pub fn is_active(&self) -> Result<bool> {
+        self.0.is_active()
+    }
+
+    /// Whether the stream was never started, or was stopped (or aborted).
+    pub fn is_stopped(&self) -> Result<bool> {
+        self.0.is_stopped()
+    }
+
+    pub fn state(&self) -> Result<StreamState> {
+        self.0.state()
+    }
+
+    /// Stops the stream once the frames that were already buffered are played. The stream can be
+    /// started again.
+    pub fn stop(&mut self) -> Result<()> {
+        self.0.stop()
+    }
+
+    /// Stops calling the callback, and returns once the frames it already produced (or that were
+    /// written) are played: Portaudio's stop waits for them. The stream can be started again.
+    pub fn drain(&mut self) -> Result<()> {
+        self.0.stop()
+    }
+
+    /// Stops the stream as soon as possible, dropping the frames that weren't played yet.
+    pub fn abort(&mut self) -> Result<()> {
+        self.0.abort()
+    }
+
+    /// Sets how the stream stops when it's dropped. Defaults to [`StopMode::Abort`].
+    pub fn set_drop_mode(&mut self, mode: StopMode) {
+        self.0.set_drop_mode(mode)
+    }
+
+    /// Stops calling the callback, without stopping the stream: The device stays open, and output
+    /// streams fade out over a buffer, then play silence. Returns
+    /// [`Error::IncompatibleStreamMode`](crate::Error::IncompatibleStreamMode) for blocking
+    /// streams.
+    pub fn pause(&mut self) -> Result<()> {
+        self.0.pause()
+    }
+
+    /// Resumes a paused stream. Output streams fade back in over a buffer.
+    pub fn resume(&mut self) -> Result<()> {
+        self.0.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.is_paused()
+    }
+
+    /// Scales the stream's output by `volume` (1 by default), ramping to it over a buffer. See
+    /// the default backend's `Stream::set_volume`.
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        self.0.set_volume(volume)
+    }
+
+    pub fn volume(&self) -> Result<f32> {
+        self.0.volume()
+    }
+
     pub fn close(mut self) {
         self.0
             .close()
@@ -35,19 +170,131 @@ impl<Frame> Stream<Frame> {
     }
 }
 
-pub fn new_outstream<Frame>(
+pub fn new_outstream<Frame: 'static>(
     params: internal::StreamOpenParams<Frame>,
     device: DeviceHandle,
 ) -> Result<Stream<Frame>> {
     Ok(Stream(internal::StreamImpl::new_outstream(params, device)?))
 }
 
+pub fn new_instream<Frame: 'static>(
+    params: internal::StreamOpenParams<Frame, Input>,
+    device: DeviceHandle,
+) -> Result<Stream<Frame>> {
+    Ok(Stream(internal::StreamImpl::new_instream(params, device)?))
+}
+
+pub fn new_planar_outstream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, PlanarOutput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_planar_outstream(
+        params, device,
+    )?))
+}
+
+pub fn new_planar_instream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, PlanarInput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_planar_instream(
+        params, device,
+    )?))
+}
+
+pub fn new_dynamic_outstream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, DynamicOutput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_dynamic_outstream(
+        params, device,
+    )?))
+}
+
+pub fn new_dynamic_instream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, DynamicInput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_dynamic_instream(
+        params, device,
+    )?))
+}
+
+pub fn new_raw_outstream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, RawOutput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_raw_outstream(
+        params, device,
+    )?))
+}
+
+pub fn new_raw_instream<Sample: 'static>(
+    params: internal::StreamOpenParams<Sample, RawInput>,
+    device: DeviceHandle,
+) -> Result<Stream<Sample>> {
+    Ok(Stream(internal::StreamImpl::new_raw_instream(
+        params, device,
+    )?))
+}
+
+pub fn new_outstream_with_info<Frame: 'static>(
+    params: internal::StreamOpenParams<Frame, OutputWithInfo>,
+    device: DeviceHandle,
+) -> Result<Stream<Frame>> {
+    Ok(Stream(internal::StreamImpl::new_outstream_with_info(
+        params, device,
+    )?))
+}
+
+pub fn new_instream_with_info<Frame: 'static>(
+    params: internal::StreamOpenParams<Frame, InputWithInfo>,
+    device: DeviceHandle,
+) -> Result<Stream<Frame>> {
+    Ok(Stream(internal::StreamImpl::new_instream_with_info(
+        params, device,
+    )?))
+}
+
+pub fn new_blocking_stream<Frame: 'static>(
+    params: internal::StreamOpenParams<Frame, NoCallback>,
+    is_output: bool,
+    device: DeviceHandle,
+) -> Result<Stream<Frame>> {
+    Ok(Stream(internal::StreamImpl::new_blocking_stream(
+        params, is_output, device,
+    )?))
+}
+
+pub fn new_duplex_stream<InFrame: 'static, OutFrame: 'static>(
+    input: internal::StreamOpenParams<InFrame, NoCallback>,
+    output: internal::StreamOpenParams<OutFrame, NoCallback>,
+    callback: DuplexCallback<InFrame, OutFrame>,
+    device: DeviceHandle,
+) -> Result<Stream<(InFrame, OutFrame)>> {
+    Ok(Stream(internal::StreamImpl::new_duplex_stream(
+        input, output, callback, device,
+    )?))
+}
+
+pub fn new_duplex_stream_with_info<InFrame: 'static, OutFrame: 'static>(
+    input: internal::StreamOpenParams<InFrame, NoCallback>,
+    output: internal::StreamOpenParams<OutFrame, NoCallback>,
+    callback: DuplexInfoCallback<InFrame, OutFrame>,
+    device: DeviceHandle,
+) -> Result<Stream<(InFrame, OutFrame)>> {
+    Ok(Stream(internal::StreamImpl::new_duplex_stream_with_info(
+        input, output, callback, device,
+    )?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::Error;
     use crate::portaudio::test_prelude::*;
-    use crate::SampleRate;
+    use crate::portaudio::Stream;
+    use crate::{BufferSize, ChannelMixPolicy, ChannelSelection, ResamplerQuality, SampleRate};
     use std::sync::Arc;
     use std::sync::{Condvar, Mutex};
     use std::thread;
@@ -58,6 +305,11 @@ mod tests {
         device.open_outstream(options)
     }
 
+    fn make_instream_with(options: StreamOptions<[f32; 1], Input>) -> Result<Stream<[f32; 1]>> {
+        let mut device = Host::with_default_backend()?.default_input_device()?;
+        device.open_input_stream(options)
+    }
+
     #[test]
     fn stream_is_send() {
         assert_send::<Stream<[f32; 2]>>();
@@ -71,6 +323,103 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn creates_planar_outstream() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        device.open_planar_outstream(StreamOptions::<f32, PlanarOutput> {
+            format: Format::F32,
+            n_channels: 2,
+            frames_per_buffer: BufferSize::Default,
+            sample_rate: SampleRate::DeviceDefault,
+            resample_if_needed: false,
+            resampler_quality: ResamplerQuality::Linear,
+            channel_mix_policy: ChannelMixPolicy::Exact,
+            channel_map: None,
+            channel_mask: None,
+            channels: ChannelSelection::All,
+            exclusive: false,
+            latency: LatencyHint::High,
+            realtime_priority: true,
+            prime_output: false,
+            watchdog: None,
+            reconnect: ReconnectPolicy::None,
+            wasapi: None,
+            coreaudio: None,
+            alsa: None,
+            gain: 1.0,
+            channel_gains: None,
+            dither: DitherMode::Tpdf,
+            clip_policy: ClipPolicy::Clamp,
+            on_finished: None,
+            callback: Box::new(|_| {}),
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn creates_instream() -> Result<()> {
+        begin!();
+        make_instream_with(StreamOptions::default())?;
+        Ok(())
+    }
+
+    // Portaudio's WASAPI streams, which the default devices can be on, open in exclusive mode.
+    #[test]
+    #[cfg(not(windows))]
+    fn errors_if_stream_is_exclusive() {
+        begin!();
+        let stream = make_stream_with(StreamOptions {
+            exclusive: true,
+            ..Default::default()
+        });
+        assert_that!(&stream, maybe_err(eq(Error::IncompatibleStreamMode)));
+        let stream = make_instream_with(StreamOptions {
+            exclusive: true,
+            ..Default::default()
+        });
+        assert_that!(&stream, maybe_err(eq(Error::IncompatibleStreamMode)));
+    }
+
+    #[test]
+    fn errors_if_duplex_sample_rates_differ() {
+        begin!();
+        let stream = Host::with_default_backend()
+            .and_then(|mut host| host.default_input_device())
+            .and_then(|mut device| {
+                device.open_duplex_stream(
+                    StreamOptions::<[f32; 1], NoCallback> {
+                        sample_rate: SampleRate::Exact(44_100),
+                        ..Default::default()
+                    },
+                    StreamOptions::<[f32; 1], NoCallback> {
+                        sample_rate: SampleRate::Exact(48_000),
+                        ..Default::default()
+                    },
+                    Box::new(|_, _| {}),
+                )
+            });
+        assert_that!(&stream, maybe_err(eq(Error::IncompatibleSampleRate)));
+    }
+
+    #[test]
+    fn errors_if_duplex_frames_per_buffer_differ() {
+        begin!();
+        let stream = Host::with_default_backend()
+            .and_then(|mut host| host.default_input_device())
+            .and_then(|mut device| {
+                device.open_duplex_stream(
+                    StreamOptions::<[f32; 1], NoCallback> {
+                        frames_per_buffer: BufferSize::Exact(256),
+                        ..Default::default()
+                    },
+                    StreamOptions::<[f32; 1], NoCallback>::default(),
+                    Box::new(|_, _| {}),
+                )
+            });
+        assert_that!(&stream, maybe_err(eq(Error::InvalidFramesPerBuffer)));
+    }
+
     #[test]
     fn can_start_stream() -> Result<()> {
         begin!();
@@ -96,7 +445,233 @@ mod tests {
         let (guard, _) = cvar
             .wait_timeout(lock.lock().unwrap(), Duration::from_secs(20))
             .unwrap();
-        assert_eq!(*guard, true);
+        assert!(*guard);
+        Ok(())
+    }
+
+    #[test]
+    fn calls_on_finished_once_complete() -> Result<()> {
+        begin!();
+        let pair = Arc::new((Mutex::new(false), Condvar::new()));
+        let pair2 = Arc::clone(&pair);
+        let mut stream = Host::with_default_backend()?
+            .default_output_device()?
+            .open_outstream_with_info(StreamOptions::<[f32; 2], OutputWithInfo> {
+                on_finished: Some(Box::new(move || {
+                    let (lock, cvar) = &*pair2;
+                    *lock.lock().unwrap() = true;
+                    cvar.notify_one();
+                })),
+                callback: Box::new(|buffer, _| {
+                    buffer.iter_mut().for_each(|frame| *frame = [0.0, 0.0]);
+                    StreamFlow::Complete
+                }),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        let (lock, cvar) = &*pair;
+        let (guard, _) = cvar
+            .wait_timeout_while(lock.lock().unwrap(), Duration::from_secs(20), |done| !*done)
+            .unwrap();
+        assert!(*guard);
+        Ok(())
+    }
+
+    #[test]
+    fn stops_and_aborts_streams() -> Result<()> {
+        begin!();
+        let mut stream = make_stream_with(StreamOptions {
+            callback: Box::new(|buffer: &mut [[f32; 2]]| {
+                buffer.iter_mut().for_each(|frame| *frame = [0.0, 0.0])
+            }),
+            ..Default::default()
+        })?;
+        // Streams that aren't running are already stopped.
+        stream.stop()?;
+        stream.start()?;
+        stream.stop()?;
+        stream.start()?;
+        stream.abort()?;
+        stream.abort()?;
+        stream.start()?;
+        stream.set_drop_mode(StopMode::Stop);
+        Ok(())
+    }
+
+    #[test]
+    fn drains_streams() -> Result<()> {
+        begin!();
+        let mut stream = make_stream_with(StreamOptions {
+            callback: Box::new(|buffer: &mut [[f32; 2]]| {
+                buffer.iter_mut().for_each(|frame| *frame = [0.0, 0.0])
+            }),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        stream.drain()?;
+        assert!(stream.is_stopped()?);
+        // Drained streams can start again.
+        stream.start()?;
+        Ok(())
+    }
+
+    #[test]
+    fn sets_stream_volume() -> Result<()> {
+        begin!();
+        let mut stream = make_stream_with(StreamOptions {
+            callback: Box::new(|buffer: &mut [[f32; 2]]| {
+                buffer.iter_mut().for_each(|frame| *frame = [0.5, 0.5])
+            }),
+            ..Default::default()
+        })?;
+        assert_eq!(stream.volume()?, 1.0);
+        stream.set_volume(0.25)?;
+        stream.start()?;
+        assert_eq!(stream.volume()?, 0.25);
+        assert_eq!(stream.set_volume(-1.0), Err(Error::Invalid));
+        Ok(())
+    }
+
+    #[test]
+    fn reports_stream_state() -> Result<()> {
+        begin!();
+        let mut stream = Host::with_default_backend()?
+            .default_output_device()?
+            .open_outstream_with_info(StreamOptions::<[f32; 2], OutputWithInfo> {
+                callback: Box::new(|buffer, _| {
+                    buffer.iter_mut().for_each(|frame| *frame = [0.0, 0.0]);
+                    StreamFlow::Complete
+                }),
+                ..Default::default()
+            })?;
+        assert_eq!(stream.state()?, StreamState::Stopped);
+        assert!(stream.output_latency().is_some());
+        assert_eq!(stream.input_latency(), None);
+        assert_eq!(stream.cpu_load(), 0.0);
+        assert_eq!(stream.stats().last_glitch, None);
+        stream.start()?;
+        assert!(!stream.is_stopped()?);
+        let time = stream.time();
+        thread::sleep(Duration::from_millis(10));
+        assert_gt!(stream.time(), time);
+        // The stream finishes once its only buffer is played.
+        let start = std::time::Instant::now();
+        while stream.is_active()? && start.elapsed() < Duration::from_secs(20) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(stream.state()?, StreamState::Finished);
+        stream.stop()?;
+        assert_eq!(stream.state()?, StreamState::Stopped);
+        Ok(())
+    }
+
+    #[test]
+    fn can_start_instream() -> Result<()> {
+        begin!();
+        let pair = Arc::new((Mutex::new(0_usize), Condvar::new()));
+        let pair2 = Arc::clone(&pair);
+        let cb = move |captured: &[[f32; 1]]| {
+            let (lock, cvar) = &*pair2;
+            *lock.lock().unwrap() += captured.len();
+            cvar.notify_one();
+        };
+        let mut stream = make_instream_with(StreamOptions {
+            callback: Box::new(cb),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        let (lock, cvar) = &*pair;
+        let (guard, _) = cvar
+            .wait_timeout_while(lock.lock().unwrap(), Duration::from_secs(20), |n| *n == 0)
+            .unwrap();
+        assert_gt!(*guard, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn can_write_blocking_outstream() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut stream = device.open_blocking_outstream(StreamOptions::<[f32; 2], _>::default())?;
+        stream.start()?;
+        stream.write(&[[0.0; 2]; 1024])?;
+        Ok(())
+    }
+
+    #[test]
+    fn primes_blocking_outstream() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut stream = device.open_blocking_outstream(StreamOptions::<[f32; 2], _>::default())?;
+        let primed = stream.prime(&[[0.0; 2]; 1 << 16])?;
+        assert_gt!(primed, 0);
+        assert_that!(
+            &stream.prime(&[]),
+            maybe_err(eq(Error::StreamAlreadyStarted))
+        );
+        stream.write(&[[0.0; 2]; 1024])?;
+        Ok(())
+    }
+
+    #[test]
+    fn can_read_blocking_instream() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_input_device()?;
+        let mut stream =
+            device.open_blocking_input_stream(StreamOptions::<[f32; 1], _>::default())?;
+        stream.start()?;
+        stream.read(&mut [[0.0; 1]; 1024])?;
+        Ok(())
+    }
+
+    #[test]
+    fn records_blocking_underflows() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut stream = device.open_blocking_outstream(StreamOptions::<[f32; 2], _>::default())?;
+        stream.start()?;
+        stream.write(&[[0.0; 2]; 1024])?;
+        // The device plays out the frames, and underflows before the next write.
+        thread::sleep(Duration::from_millis(500));
+        stream.write(&[[0.0; 2]; 1024])?;
+        assert_gt!(stream.stats().output_underflows, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn records_blocking_overflows() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_input_device()?;
+        let mut stream =
+            device.open_blocking_input_stream(StreamOptions::<[f32; 1], _>::default())?;
+        stream.start()?;
+        // The device captures more frames than the stream buffers before the first read.
+        thread::sleep(Duration::from_millis(500));
+        stream.read(&mut [[0.0; 1]; 1024])?;
+        assert_gt!(stream.stats().input_overflows, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_writing_to_callback_stream() -> Result<()> {
+        begin!();
+        let mut stream = make_stream_with(StreamOptions::default())?;
+        assert_that!(
+            &stream.write(&[[0.0; 2]; 16]),
+            maybe_err(eq(Error::IncompatibleStreamMode))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_reading_from_blocking_outstream() -> Result<()> {
+        begin!();
+        let mut device = Host::with_default_backend()?.default_output_device()?;
+        let mut stream = device.open_blocking_outstream(StreamOptions::<[f32; 2], _>::default())?;
+        assert_that!(
+            &stream.read(&mut [[0.0; 2]; 16]),
+            maybe_err(eq(Error::IncompatibleStreamMode))
+        );
         Ok(())
     }
 
@@ -127,7 +702,7 @@ mod tests {
         begin!();
         assert_that!(
             &make_stream_with(StreamOptions {
-                frames_per_buffer: Some(-100),
+                frames_per_buffer: BufferSize::Exact(-100),
                 ..Default::default()
             }),
             maybe_err(eq(Error::InvalidFramesPerBuffer))
@@ -152,4 +727,23 @@ mod tests {
             maybe_err(eq(Error::IncompatibleNChannels))
         );
     }
+
+    #[test]
+    fn errors_if_invalid_channel_selection() {
+        begin!();
+        assert_that!(
+            &make_stream_with(StreamOptions {
+                channels: ChannelSelection::Range(0..3),
+                ..Default::default()
+            }),
+            maybe_err(eq(Error::IncompatibleNChannels))
+        );
+        assert_that!(
+            &make_stream_with(StreamOptions {
+                channels: ChannelSelection::Range(100_000..100_002),
+                ..Default::default()
+            }),
+            maybe_err(eq(Error::IncompatibleNChannels))
+        );
+    }
 }
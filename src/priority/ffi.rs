@@ -0,0 +1,153 @@
+//! Hand-written bindings to the subset of libdbus used to ask rtkit for real-time priority.
+//!
+//! libdbus is loaded at runtime rather than linked, so that programs still load on systems
+//! without it. The functions panic if called before [`load`] succeeded.
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+use lazy_static::lazy_static;
+
+pub enum DBusConnection {}
+pub enum DBusMessage {}
+
+pub type dbus_bool_t = c_uint;
+pub type DBusBusType = c_int;
+
+pub const DBUS_BUS_SYSTEM: DBusBusType = 1;
+
+pub const DBUS_TYPE_INT32: c_int = b'i' as c_int;
+pub const DBUS_TYPE_INT64: c_int = b'x' as c_int;
+pub const DBUS_TYPE_UINT32: c_int = b'u' as c_int;
+pub const DBUS_TYPE_UINT64: c_int = b't' as c_int;
+pub const DBUS_TYPE_STRING: c_int = b's' as c_int;
+pub const DBUS_TYPE_VARIANT: c_int = b'v' as c_int;
+
+#[repr(C)]
+pub struct DBusError {
+    pub name: *const c_char,
+    pub message: *const c_char,
+    /// Five one-bit fields.
+    dummy: c_uint,
+    padding: *mut c_void,
+}
+
+impl DBusError {
+    /// Still needs [`dbus_error_init`].
+    pub fn new() -> DBusError {
+        DBusError {
+            name: std::ptr::null(),
+            message: std::ptr::null(),
+            dummy: 0,
+            padding: std::ptr::null_mut(),
+        }
+    }
+}
+
+/// Opaque to its users, who only allocate it. Larger than libdbus's on every platform.
+#[repr(C)]
+pub struct DBusMessageIter([*mut c_void; 16]);
+
+impl DBusMessageIter {
+    pub fn new() -> DBusMessageIter {
+        DBusMessageIter([std::ptr::null_mut(); 16])
+    }
+}
+
+/// Declares the library's functions: A table of function pointers filled in by `dlsym`, and a
+/// wrapper for each that calls through it.
+macro_rules! dynamic_functions {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        struct Library {
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl Library {
+            unsafe fn load(handle: *mut c_void) -> Option<Library> {
+                Some(Library {
+                    $($name: {
+                        let symbol = dlsym(handle, concat!(stringify!($name), "\0").as_ptr() as *const c_char);
+                        if symbol.is_null() {
+                            return None;
+                        }
+                        std::mem::transmute::<*mut c_void, unsafe extern "C" fn($($ty),*) $(-> $ret)?>(
+                            symbol,
+                        )
+                    },)*
+                })
+            }
+        }
+
+        $(pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+            (LIBRARY.as_ref().expect("libdbus is not loaded.").$name)($($arg),*)
+        })*
+    };
+}
+
+dynamic_functions! {
+    pub fn dbus_error_init(error: *mut DBusError);
+    pub fn dbus_error_free(error: *mut DBusError);
+
+    pub fn dbus_bus_get_private(
+        bus_type: DBusBusType,
+        error: *mut DBusError,
+    ) -> *mut DBusConnection;
+    pub fn dbus_connection_set_exit_on_disconnect(
+        connection: *mut DBusConnection,
+        exit_on_disconnect: dbus_bool_t,
+    );
+    pub fn dbus_connection_send_with_reply_and_block(
+        connection: *mut DBusConnection,
+        message: *mut DBusMessage,
+        timeout_milliseconds: c_int,
+        error: *mut DBusError,
+    ) -> *mut DBusMessage;
+    pub fn dbus_connection_close(connection: *mut DBusConnection);
+    pub fn dbus_connection_unref(connection: *mut DBusConnection);
+
+    pub fn dbus_message_new_method_call(
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        method: *const c_char,
+    ) -> *mut DBusMessage;
+    pub fn dbus_message_unref(message: *mut DBusMessage);
+
+    pub fn dbus_message_iter_init_append(message: *mut DBusMessage, iter: *mut DBusMessageIter);
+    pub fn dbus_message_iter_append_basic(
+        iter: *mut DBusMessageIter,
+        arg_type: c_int,
+        value: *const c_void,
+    ) -> dbus_bool_t;
+    pub fn dbus_message_iter_init(
+        message: *mut DBusMessage,
+        iter: *mut DBusMessageIter,
+    ) -> dbus_bool_t;
+    pub fn dbus_message_iter_get_arg_type(iter: *mut DBusMessageIter) -> c_int;
+    pub fn dbus_message_iter_recurse(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter);
+    pub fn dbus_message_iter_get_basic(iter: *mut DBusMessageIter, value: *mut c_void);
+}
+
+lazy_static! {
+    static ref LIBRARY: Option<Library> = unsafe {
+        let handle = dlopen(b"libdbus-1.so.3\0".as_ptr() as *const c_char, RTLD_NOW);
+        if handle.is_null() {
+            None
+        } else {
+            Library::load(handle)
+        }
+    };
+}
+
+/// Loads libdbus, once. Returns whether it is installed.
+pub fn load() -> bool {
+    LIBRARY.is_some()
+}
+
+const RTLD_NOW: c_int = 2;
+
+#[link(name = "dl")]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
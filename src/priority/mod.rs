@@ -0,0 +1,217 @@
+//! Promotes the threads that run stream callbacks to real-time priority (see
+//! [`StreamOptions::realtime_priority`](crate::StreamOptions)), so that other work doesn't delay
+//! them past the device's deadline.
+//!
+//! Backends whose callbacks run on the system's own audio threads (e.g. CoreAudio, JACK, and
+//! PipeWire) are already real-time, and don't promote them.
+use std::cell::Cell;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+mod ffi;
+#[cfg(target_os = "linux")]
+mod rtkit;
+
+thread_local! {
+    /// Whether the thread was promoted, once it tried to be.
+    static PROMOTED: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// How often callbacks run when their backend doesn't say, for the schedulers that plan by it.
+#[cfg(target_os = "macos")]
+const DEFAULT_PERIOD: Duration = Duration::from_millis(10);
+
+/// Promotes the current thread to real-time priority, if it wasn't already, and returns whether it
+/// is. `period` is how often the thread runs its callback, if the backend knows. Only tries once
+/// per thread, so that callbacks can call this every time they run: Threads the system won't
+/// promote (e.g. for lack of privileges) keep their priority.
+pub(crate) fn promote_current_thread(period: Option<Duration>) -> bool {
+    promote_once(|| promote(period))
+}
+
+/// Like [`promote_current_thread`], but joins MMCSS's `task` (e.g. "Audio") rather than "Pro
+/// Audio".
+#[cfg(all(target_os = "windows", feature = "wasapi"))]
+pub(crate) fn join_mmcss_task(task: &str) -> bool {
+    promote_once(|| windows::join_task(task))
+}
+
+fn promote_once(promote: impl FnOnce() -> bool) -> bool {
+    PROMOTED
+        .try_with(|promoted| match promoted.get() {
+            Some(is_promoted) => is_promoted,
+            None => {
+                let is_promoted = promote();
+                promoted.set(Some(is_promoted));
+                is_promoted
+            }
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+fn promote(_period: Option<Duration>) -> bool {
+    windows::join_task("Pro Audio")
+}
+
+#[cfg(target_os = "macos")]
+fn promote(period: Option<Duration>) -> bool {
+    macos::set_time_constraint(period.unwrap_or(DEFAULT_PERIOD))
+}
+
+/// Unprivileged processes can't set `SCHED_FIFO` themselves, but desktops let rtkit set it for
+/// them.
+#[cfg(target_os = "linux")]
+fn promote(_period: Option<Duration>) -> bool {
+    linux::set_fifo() || rtkit::make_thread_realtime(linux::PRIORITY)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn promote(_period: Option<Duration>) -> bool {
+    false
+}
+
+/// Windows schedules audio threads through MMCSS, which boosts the threads of its tasks.
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::cell::Cell;
+    use std::os::raw::c_void;
+
+    #[link(name = "avrt")]
+    extern "system" {
+        fn AvSetMmThreadCharacteristicsW(
+            task_name: *const u16,
+            task_index: *mut u32,
+        ) -> *mut c_void;
+        fn AvRevertMmThreadCharacteristics(task: *mut c_void) -> i32;
+    }
+
+    /// The thread's MMCSS task, which it leaves when it exits.
+    struct Task(Cell<*mut c_void>);
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            if !self.0.get().is_null() {
+                unsafe { AvRevertMmThreadCharacteristics(self.0.get()) };
+            }
+        }
+    }
+
+    thread_local! {
+        static TASK: Task = const { Task(Cell::new(std::ptr::null_mut())) };
+    }
+
+    /// Joins the task, by its name in MMCSS's registry.
+    pub fn join_task(task: &str) -> bool {
+        let name: Vec<u16> = task.encode_utf16().chain(Some(0)).collect();
+        let mut index = 0;
+        let task = unsafe { AvSetMmThreadCharacteristicsW(name.as_ptr(), &mut index) };
+        !task.is_null() && TASK.try_with(|current| current.0.set(task)).is_ok()
+    }
+}
+
+/// macOS gives real-time threads a share of every period, which they have to be done in.
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::os::raw::{c_int, c_void};
+    use std::time::Duration;
+
+    const THREAD_TIME_CONSTRAINT_POLICY: u32 = 2;
+    const KERN_SUCCESS: c_int = 0;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct MachTimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    #[repr(C)]
+    struct ThreadTimeConstraintPolicy {
+        period: u32,
+        computation: u32,
+        constraint: u32,
+        preemptible: c_int,
+    }
+
+    extern "C" {
+        fn pthread_self() -> *mut c_void;
+        fn pthread_mach_thread_np(thread: *mut c_void) -> u32;
+        fn mach_timebase_info(info: *mut MachTimebaseInfo) -> c_int;
+        fn thread_policy_set(thread: u32, flavor: u32, policy: *mut c_int, count: u32) -> c_int;
+    }
+
+    /// Asks for half of every `period` to run in, done by the end of it.
+    pub fn set_time_constraint(period: Duration) -> bool {
+        let mut timebase = MachTimebaseInfo::default();
+        if unsafe { mach_timebase_info(&mut timebase) } != KERN_SUCCESS || timebase.numer == 0 {
+            return false;
+        }
+        // Mach counts in ticks of its own.
+        let ticks = |duration: Duration| {
+            (duration.as_nanos() * u128::from(timebase.denom) / u128::from(timebase.numer)) as u32
+        };
+        let mut policy = ThreadTimeConstraintPolicy {
+            period: ticks(period),
+            computation: ticks(period / 2),
+            constraint: ticks(period),
+            preemptible: 1,
+        };
+        let count = (std::mem::size_of::<ThreadTimeConstraintPolicy>()
+            / std::mem::size_of::<c_int>()) as u32;
+        unsafe {
+            thread_policy_set(
+                pthread_mach_thread_np(pthread_self()),
+                THREAD_TIME_CONSTRAINT_POLICY,
+                &mut policy as *mut _ as *mut c_int,
+                count,
+            ) == KERN_SUCCESS
+        }
+    }
+}
+
+/// Linux runs `SCHED_FIFO` threads ahead of every normal thread, for processes allowed to.
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::os::raw::{c_int, c_ulong};
+
+    const SCHED_FIFO: c_int = 1;
+    /// Below the kernel's own real-time threads, which run at 99.
+    pub const PRIORITY: c_int = 70;
+
+    #[repr(C)]
+    struct SchedParam {
+        sched_priority: c_int,
+    }
+
+    extern "C" {
+        fn pthread_self() -> c_ulong;
+        fn pthread_setschedparam(thread: c_ulong, policy: c_int, param: *const SchedParam)
+            -> c_int;
+        fn sched_get_priority_max(policy: c_int) -> c_int;
+    }
+
+    pub fn set_fifo() -> bool {
+        let param = SchedParam {
+            sched_priority: PRIORITY.min(unsafe { sched_get_priority_max(SCHED_FIFO) }),
+        };
+        unsafe { pthread_setschedparam(pthread_self(), SCHED_FIFO, &param) == 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promotes_threads_once() {
+        std::thread::spawn(|| {
+            // Unprivileged tests may not be promoted, but either way the thread only tries once.
+            let promoted = promote_current_thread(Some(Duration::from_millis(10)));
+            assert_eq!(PROMOTED.with(Cell::get), Some(promoted));
+            assert_eq!(promote_current_thread(None), promoted);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Asks RealtimeKit (rtkit), the system service that desktop Linux grants real-time priority
+//! through, to promote threads of processes that aren't allowed to promote themselves.
+//!
+//! rtkit only promotes threads of processes that limit their real-time CPU time
+//! (`RLIMIT_RTTIME`) to at most its own limit, so that runaway threads are killed rather than
+//! hanging the system. Promoting a thread sets that limit for the whole process.
+use std::os::raw::{c_char, c_int, c_ulong, c_void};
+
+use super::ffi;
+
+const RTKIT: &[u8] = b"org.freedesktop.RealtimeKit1\0";
+const RTKIT_PATH: &[u8] = b"/org/freedesktop/RealtimeKit1\0";
+const PROPERTIES: &[u8] = b"org.freedesktop.DBus.Properties\0";
+
+/// How long to wait for rtkit's replies. Promotion runs on the thread itself, which is meant to
+/// be busy with audio.
+const TIMEOUT_MS: c_int = 500;
+
+const RLIMIT_RTTIME: c_int = 15;
+
+#[repr(C)]
+struct Rlimit {
+    rlim_cur: c_ulong,
+    rlim_max: c_ulong,
+}
+
+extern "C" {
+    fn gettid() -> c_int;
+    fn getrlimit(resource: c_int, rlim: *mut Rlimit) -> c_int;
+    fn setrlimit(resource: c_int, rlim: *const Rlimit) -> c_int;
+}
+
+/// Asks rtkit to run the current thread at `SCHED_FIFO` `priority`, or at its highest if that's
+/// lower. Returns whether it did: Systems without D-Bus or rtkit don't.
+pub fn make_thread_realtime(priority: c_int) -> bool {
+    if !ffi::load() {
+        return false;
+    }
+    let connection = match Connection::system() {
+        Some(connection) => connection,
+        None => return false,
+    };
+    let max_priority = connection.property(b"MaxRealtimePriority\0");
+    let max_rttime = connection.property(b"RTTimeUSecMax\0");
+    let (max_priority, max_rttime) = match (max_priority, max_rttime) {
+        (Some(max_priority), Some(max_rttime)) if max_priority > 0 && max_rttime > 0 => {
+            (max_priority, max_rttime as c_ulong)
+        }
+        _ => return false,
+    };
+    if !limit_rttime(max_rttime) {
+        return false;
+    }
+    let thread = unsafe { gettid() } as u64;
+    let priority = priority.min(max_priority as c_int) as u32;
+    connection
+        .call(
+            RTKIT,
+            b"MakeThreadRealtime\0",
+            &[Arg::U64(thread), Arg::U32(priority)],
+        )
+        .is_some()
+}
+
+/// Limits the process's real-time CPU time to at most `max` microseconds.
+fn limit_rttime(max: c_ulong) -> bool {
+    let mut limit = Rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { getrlimit(RLIMIT_RTTIME, &mut limit) } != 0 {
+        return false;
+    }
+    if limit.rlim_max <= max {
+        return true;
+    }
+    // Reaching the soft limit also kills the process (unless it handles SIGXCPU).
+    limit.rlim_cur = limit.rlim_cur.min(max);
+    limit.rlim_max = max;
+    unsafe { setrlimit(RLIMIT_RTTIME, &limit) == 0 }
+}
+
+/// An argument of a method call.
+enum Arg<'a> {
+    U32(u32),
+    U64(u64),
+    /// Nul-terminated.
+    Str(&'a [u8]),
+}
+
+/// A private connection to the system bus.
+struct Connection(*mut ffi::DBusConnection);
+
+impl Connection {
+    fn system() -> Option<Connection> {
+        unsafe {
+            let mut error = Error::new();
+            let connection = ffi::dbus_bus_get_private(ffi::DBUS_BUS_SYSTEM, &mut error.0);
+            if connection.is_null() {
+                return None;
+            }
+            // By default, libdbus exits the process when the bus goes away.
+            ffi::dbus_connection_set_exit_on_disconnect(connection, 0);
+            Some(Connection(connection))
+        }
+    }
+
+    /// Calls rtkit's `method` of `interface` (both nul-terminated), and returns its reply.
+    fn call(&self, interface: &[u8], method: &[u8], args: &[Arg]) -> Option<Message> {
+        unsafe {
+            let message = ffi::dbus_message_new_method_call(
+                RTKIT.as_ptr() as *const c_char,
+                RTKIT_PATH.as_ptr() as *const c_char,
+                interface.as_ptr() as *const c_char,
+                method.as_ptr() as *const c_char,
+            );
+            if message.is_null() {
+                return None;
+            }
+            let message = Message(message);
+            let mut iter = ffi::DBusMessageIter::new();
+            ffi::dbus_message_iter_init_append(message.0, &mut iter);
+            for arg in args {
+                let appended = match arg {
+                    Arg::U32(value) => ffi::dbus_message_iter_append_basic(
+                        &mut iter,
+                        ffi::DBUS_TYPE_UINT32,
+                        value as *const u32 as *const c_void,
+                    ),
+                    Arg::U64(value) => ffi::dbus_message_iter_append_basic(
+                        &mut iter,
+                        ffi::DBUS_TYPE_UINT64,
+                        value as *const u64 as *const c_void,
+                    ),
+                    Arg::Str(value) => {
+                        let value = value.as_ptr() as *const c_char;
+                        ffi::dbus_message_iter_append_basic(
+                            &mut iter,
+                            ffi::DBUS_TYPE_STRING,
+                            &value as *const *const c_char as *const c_void,
+                        )
+                    }
+                };
+                if appended == 0 {
+                    return None;
+                }
+            }
+            let mut error = Error::new();
+            let reply = ffi::dbus_connection_send_with_reply_and_block(
+                self.0,
+                message.0,
+                TIMEOUT_MS,
+                &mut error.0,
+            );
+            if reply.is_null() {
+                None
+            } else {
+                Some(Message(reply))
+            }
+        }
+    }
+
+    /// One of rtkit's integer properties (nul-terminated).
+    fn property(&self, name: &[u8]) -> Option<i64> {
+        let reply = self.call(PROPERTIES, b"Get\0", &[Arg::Str(RTKIT), Arg::Str(name)])?;
+        unsafe {
+            let mut iter = ffi::DBusMessageIter::new();
+            if ffi::dbus_message_iter_init(reply.0, &mut iter) == 0
+                || ffi::dbus_message_iter_get_arg_type(&mut iter) != ffi::DBUS_TYPE_VARIANT
+            {
+                return None;
+            }
+            let mut variant = ffi::DBusMessageIter::new();
+            ffi::dbus_message_iter_recurse(&mut iter, &mut variant);
+            match ffi::dbus_message_iter_get_arg_type(&mut variant) {
+                ffi::DBUS_TYPE_INT32 => {
+                    let mut value = 0i32;
+                    ffi::dbus_message_iter_get_basic(
+                        &mut variant,
+                        &mut value as *mut i32 as *mut c_void,
+                    );
+                    Some(i64::from(value))
+                }
+                ffi::DBUS_TYPE_INT64 => {
+                    let mut value = 0i64;
+                    ffi::dbus_message_iter_get_basic(
+                        &mut variant,
+                        &mut value as *mut i64 as *mut c_void,
+                    );
+                    Some(value)
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::dbus_connection_close(self.0);
+            ffi::dbus_connection_unref(self.0);
+        }
+    }
+}
+
+struct Message(*mut ffi::DBusMessage);
+
+impl Drop for Message {
+    fn drop(&mut self) {
+        unsafe { ffi::dbus_message_unref(self.0) };
+    }
+}
+
+/// Freed once it was reported, or not.
+struct Error(ffi::DBusError);
+
+impl Error {
+    fn new() -> Error {
+        let mut error = ffi::DBusError::new();
+        unsafe { ffi::dbus_error_init(&mut error) };
+        Error(error)
+    }
+}
+
+impl Drop for Error {
+    fn drop(&mut self) {
+        unsafe { ffi::dbus_error_free(&mut self.0) };
+    }
+}
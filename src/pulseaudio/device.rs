@@ -0,0 +1,124 @@
+use crate::error::{Error, Result};
+use crate::pulseaudio::stream::{self, Stream};
+use crate::stream_options::{Input, StreamOptions};
+
+/// The stream name shown in the system's sound settings when none is given.
+const DEFAULT_STREAM_NAME: &str = "Audio";
+/// The server's name for the default sink's monitor source.
+const DEFAULT_MONITOR_NAME: &str = "@DEFAULT_MONITOR@";
+
+/// A PulseAudio sink (for output) or source (for input).
+pub struct Device {
+    application_name: String,
+    // None for the user's default.
+    pa_name: Option<String>,
+    is_output: bool,
+    stream_name: String,
+}
+
+impl Device {
+    pub(super) fn new(application_name: &str, pa_name: Option<&str>, is_output: bool) -> Device {
+        Device {
+            application_name: application_name.to_owned(),
+            pa_name: pa_name.map(str::to_owned),
+            is_output,
+            stream_name: DEFAULT_STREAM_NAME.to_owned(),
+        }
+    }
+
+    /// The sink or source name, or "default" for the user's default.
+    pub fn name(&self) -> &str {
+        self.pa_name.as_deref().unwrap_or("default")
+    }
+
+    /// Sets the name that streams opened from now on show in the system's sound settings (e.g.
+    /// "Music" or "Voice chat").
+    pub fn set_stream_name(&mut self, stream_name: &str) {
+        self.stream_name = stream_name.to_owned();
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+
+    /// Creates an input stream capturing what this output device plays, from its sink's monitor
+    /// source. See the default backend's `Device::open_loopback_stream`.
+    pub fn open_loopback_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(&self.monitor(), options)
+    }
+
+    /// The source that carries what the sink plays.
+    fn monitor(&self) -> Device {
+        let monitor_name = match &self.pa_name {
+            Some(pa_name) => format!("{}.monitor", pa_name),
+            None => DEFAULT_MONITOR_NAME.to_owned(),
+        };
+        Device {
+            application_name: self.application_name.clone(),
+            pa_name: Some(monitor_name),
+            is_output: false,
+            stream_name: self.stream_name.clone(),
+        }
+    }
+
+    pub(super) fn application_name(&self) -> &str {
+        &self.application_name
+    }
+
+    pub(super) fn pa_name(&self) -> Option<&str> {
+        self.pa_name.as_deref()
+    }
+
+    pub(super) fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulseaudio::Host;
+
+    #[test]
+    fn names_default_devices() -> Result<()> {
+        let mut host = Host::new()?;
+        assert_eq!(host.default_output_device()?.name(), "default");
+        assert_eq!(
+            host.output_device("alsa_output.pci")?.name(),
+            "alsa_output.pci"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn names_monitor_sources() {
+        let sink = Device::new("audiohal", Some("alsa_output.pci"), true);
+        assert_eq!(sink.monitor().name(), "alsa_output.pci.monitor");
+        let default_sink = Device::new("audiohal", None, true);
+        assert_eq!(default_sink.monitor().name(), "@DEFAULT_MONITOR@");
+    }
+}
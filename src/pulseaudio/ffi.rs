@@ -0,0 +1,90 @@
+//! Hand-written bindings to libpulse-simple.
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_int, c_void};
+
+pub enum pa_simple {}
+
+pub type pa_sample_format_t = c_int;
+pub type pa_stream_direction_t = c_int;
+
+pub const PA_STREAM_PLAYBACK: pa_stream_direction_t = 1;
+pub const PA_STREAM_RECORD: pa_stream_direction_t = 2;
+
+pub const PA_SAMPLE_U8: pa_sample_format_t = 0;
+#[cfg(target_endian = "little")]
+pub const PA_SAMPLE_S16NE: pa_sample_format_t = 3;
+#[cfg(target_endian = "big")]
+pub const PA_SAMPLE_S16NE: pa_sample_format_t = 4;
+#[cfg(target_endian = "little")]
+pub const PA_SAMPLE_FLOAT32NE: pa_sample_format_t = 5;
+#[cfg(target_endian = "big")]
+pub const PA_SAMPLE_FLOAT32NE: pa_sample_format_t = 6;
+#[cfg(target_endian = "little")]
+pub const PA_SAMPLE_S32NE: pa_sample_format_t = 7;
+#[cfg(target_endian = "big")]
+pub const PA_SAMPLE_S32NE: pa_sample_format_t = 8;
+/// Packed 24-bit samples (3 bytes each).
+#[cfg(target_endian = "little")]
+pub const PA_SAMPLE_S24NE: pa_sample_format_t = 9;
+#[cfg(target_endian = "big")]
+pub const PA_SAMPLE_S24NE: pa_sample_format_t = 10;
+/// 24-bit samples in the low 3 bytes of 4.
+#[cfg(target_endian = "little")]
+pub const PA_SAMPLE_S24_32NE: pa_sample_format_t = 11;
+#[cfg(target_endian = "big")]
+pub const PA_SAMPLE_S24_32NE: pa_sample_format_t = 12;
+
+pub const PA_ERR_INVALID: c_int = 3;
+pub const PA_ERR_NOENTITY: c_int = 5;
+pub const PA_ERR_CONNECTIONREFUSED: c_int = 6;
+pub const PA_ERR_NOTSUPPORTED: c_int = 19;
+
+#[repr(C)]
+pub struct pa_sample_spec {
+    pub format: pa_sample_format_t,
+    pub rate: u32,
+    pub channels: u8,
+}
+
+/// Fields set to `u32::MAX` are left to the server.
+#[repr(C)]
+pub struct pa_buffer_attr {
+    pub maxlength: u32,
+    pub tlength: u32,
+    pub prebuf: u32,
+    pub minreq: u32,
+    pub fragsize: u32,
+}
+
+pub enum pa_channel_map {}
+
+#[link(name = "pulse-simple")]
+#[link(name = "pulse")]
+extern "C" {
+    pub fn pa_simple_new(
+        server: *const c_char,
+        name: *const c_char,
+        dir: pa_stream_direction_t,
+        dev: *const c_char,
+        stream_name: *const c_char,
+        ss: *const pa_sample_spec,
+        map: *const pa_channel_map,
+        attr: *const pa_buffer_attr,
+        error: *mut c_int,
+    ) -> *mut pa_simple;
+    pub fn pa_simple_free(s: *mut pa_simple);
+    pub fn pa_simple_write(
+        s: *mut pa_simple,
+        data: *const c_void,
+        bytes: usize,
+        error: *mut c_int,
+    ) -> c_int;
+    pub fn pa_simple_read(
+        s: *mut pa_simple,
+        data: *mut c_void,
+        bytes: usize,
+        error: *mut c_int,
+    ) -> c_int;
+    pub fn pa_simple_flush(s: *mut pa_simple, error: *mut c_int) -> c_int;
+}
@@ -0,0 +1,53 @@
+use crate::error::Result;
+use crate::pulseaudio::device::Device;
+
+/// The name streams are attributed to when no application name is given.
+const DEFAULT_APPLICATION_NAME: &str = "audiohal";
+
+/// The PulseAudio host. Connections to the server are made per stream.
+pub struct Host {
+    application_name: String,
+}
+
+impl Host {
+    pub fn new() -> Result<Host> {
+        Host::with_application_name(DEFAULT_APPLICATION_NAME)
+    }
+
+    /// Creates a host whose streams are attributed to `application_name` in the system's sound
+    /// settings.
+    pub fn with_application_name(application_name: &str) -> Result<Host> {
+        Ok(Host {
+            application_name: application_name.to_owned(),
+        })
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "PulseAudio"
+    }
+
+    /// Returns the user's default sink. Streams on it follow the default as the user changes it.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new(&self.application_name, None, true))
+    }
+
+    /// Returns the user's default source.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new(&self.application_name, None, false))
+    }
+
+    /// Returns the sink with the given name, as listed by e.g. `pactl list short sinks`.
+    pub fn output_device(&mut self, sink_name: &str) -> Result<Device> {
+        Ok(Device::new(&self.application_name, Some(sink_name), true))
+    }
+
+    /// Returns the source with the given name, as listed by e.g. `pactl list short sources`.
+    pub fn input_device(&mut self, source_name: &str) -> Result<Device> {
+        Ok(Device::new(
+            &self.application_name,
+            Some(source_name),
+            false,
+        ))
+    }
+}
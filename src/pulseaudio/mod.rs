@@ -0,0 +1,56 @@
+//! PulseAudio backend for desktop Linux.
+//!
+//! Streams are regular PulseAudio client streams, so they show up (with their application and
+//! stream names) in the system's sound settings, get their own volume control, and follow the
+//! user's default device unless a specific sink or source is requested. Exposes the same
+//! [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend.
+use std::os::raw::c_int;
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCode};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// Converts a PulseAudio error code.
+fn to_error(code: c_int) -> Error {
+    match code {
+        ffi::PA_ERR_NOENTITY => Error::NoSuchDevice,
+        ffi::PA_ERR_CONNECTIONREFUSED => Error::BackendUnavailable,
+        ffi::PA_ERR_INVALID => Error::Invalid,
+        code => Error::Backend(BackendError::new(
+            Some(Backend::PulseAudio),
+            ErrorCode::PulseAudio(code),
+            "Unexpected PulseAudio error.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_error_codes() {
+        assert_eq!(to_error(ffi::PA_ERR_NOENTITY), Error::NoSuchDevice);
+        assert_eq!(
+            to_error(ffi::PA_ERR_CONNECTIONREFUSED),
+            Error::BackendUnavailable
+        );
+        let unsupported = to_error(ffi::PA_ERR_NOTSUPPORTED);
+        assert_eq!(unsupported.backend(), Some(Backend::PulseAudio));
+        assert_eq!(
+            unsupported.code(),
+            Some(ErrorCode::PulseAudio(ffi::PA_ERR_NOTSUPPORTED))
+        );
+    }
+}
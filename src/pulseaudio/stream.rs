@@ -0,0 +1,282 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::error::{Error, Result};
+use crate::priority;
+use crate::pulseaudio::device::Device;
+use crate::pulseaudio::{ffi, to_error};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamInfo, StreamOptions};
+
+/// The rate used when the options ask for the device's default. The server resamples as needed.
+const FALLBACK_SAMPLE_RATE: u32 = 48_000;
+/// The number of frames per callback when the options don't specify one.
+const DEFAULT_FRAMES_PER_BUFFER: usize = 512;
+
+/// A connection to the server, carrying a single stream. Freed when dropped.
+struct Simple(*mut ffi::pa_simple);
+
+// A connection is only ever used by one thread at a time: The opening thread until the stream is
+// started, and the stream's worker afterwards.
+unsafe impl Send for Simple {}
+unsafe impl Sync for Simple {}
+
+impl Drop for Simple {
+    fn drop(&mut self) {
+        unsafe { ffi::pa_simple_free(self.0) };
+    }
+}
+
+/// A PulseAudio stream. The callback runs on a dedicated thread.
+pub struct Stream<Frame> {
+    is_stopping: Arc<AtomicBool>,
+    frames_per_buffer: usize,
+    // The worker's body until the stream is started, and its handle afterwards.
+    pending_worker: Option<Box<dyn FnOnce() + Send>>,
+    worker: Option<JoinHandle<()>>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        let worker = self
+            .pending_worker
+            .take()
+            .ok_or(Error::StreamAlreadyStarted)?;
+        self.worker = Some(std::thread::spawn(worker));
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// The callback gets the same number of frames every time: 512 by default.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::fixed(self.frames_per_buffer)
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.is_stopping.store(true, Ordering::Release);
+        if let Some(worker) = self.worker.take() {
+            // A panicking callback has nothing left to clean up.
+            let _ = worker.join();
+        }
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let (simple, frames_per_buffer) = connect(device, &options, ffi::PA_STREAM_PLAYBACK)?;
+    let is_stopping = Arc::new(AtomicBool::new(false));
+    let worker_is_stopping = Arc::clone(&is_stopping);
+    let mut buffer = frame_buffer::<Frame>(frames_per_buffer);
+    let mut callback: Callback<Frame> = options.callback;
+    let realtime_priority = options.realtime_priority;
+    let worker = move || {
+        if realtime_priority {
+            priority::promote_current_thread(None);
+        }
+        let byte_count = frames_per_buffer * std::mem::size_of::<Frame>();
+        while !worker_is_stopping.load(Ordering::Acquire) {
+            let output = unsafe {
+                std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut Frame, frames_per_buffer)
+            };
+            callback(output);
+            // Blocks until the server has room for the buffer.
+            let mut error = 0;
+            let written = unsafe {
+                ffi::pa_simple_write(
+                    simple.0,
+                    buffer.as_ptr() as *const c_void,
+                    byte_count,
+                    &mut error,
+                )
+            };
+            if written < 0 {
+                return;
+            }
+        }
+    };
+    Ok(new_stream(is_stopping, frames_per_buffer, Box::new(worker)))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let (simple, frames_per_buffer) = connect(device, &options, ffi::PA_STREAM_RECORD)?;
+    let is_stopping = Arc::new(AtomicBool::new(false));
+    let worker_is_stopping = Arc::clone(&is_stopping);
+    let mut buffer = frame_buffer::<Frame>(frames_per_buffer);
+    let mut callback: InputCallback<Frame> = options.callback;
+    let realtime_priority = options.realtime_priority;
+    let worker = move || {
+        if realtime_priority {
+            priority::promote_current_thread(None);
+        }
+        let byte_count = frames_per_buffer * std::mem::size_of::<Frame>();
+        let mut error = 0;
+        // Recording starts as soon as the stream is connected. Drop what was captured before the
+        // stream was started.
+        unsafe { ffi::pa_simple_flush(simple.0, &mut error) };
+        while !worker_is_stopping.load(Ordering::Acquire) {
+            // Blocks until the whole buffer has been captured.
+            let read = unsafe {
+                ffi::pa_simple_read(
+                    simple.0,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    byte_count,
+                    &mut error,
+                )
+            };
+            if read < 0 {
+                return;
+            }
+            let input = unsafe {
+                std::slice::from_raw_parts(buffer.as_ptr() as *const Frame, frames_per_buffer)
+            };
+            callback(input);
+        }
+    };
+    Ok(new_stream(is_stopping, frames_per_buffer, Box::new(worker)))
+}
+
+fn new_stream<Frame>(
+    is_stopping: Arc<AtomicBool>,
+    frames_per_buffer: usize,
+    worker: Box<dyn FnOnce() + Send>,
+) -> Stream<Frame> {
+    Stream {
+        is_stopping,
+        frames_per_buffer,
+        pending_worker: Some(worker),
+        worker: None,
+        _frame: PhantomData,
+    }
+}
+
+/// Connects a new stream to the server. Returns it along with the number of frames per callback.
+fn connect<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &StreamOptions<Frame, Kind>,
+    direction: ffi::pa_stream_direction_t,
+) -> Result<(Simple, usize)> {
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate as u32,
+        _ => FALLBACK_SAMPLE_RATE,
+    };
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer as usize,
+        None => DEFAULT_FRAMES_PER_BUFFER,
+    };
+    options.validate_frame_size()?;
+    if options.n_channels > 255 {
+        return Err(Error::IncompatibleNChannels);
+    }
+    let spec = ffi::pa_sample_spec {
+        format: pulse_format(options.format)?,
+        rate: sample_rate,
+        channels: options.n_channels as u8,
+    };
+    // Only ask for a specific latency if the user asked for a buffer size. Otherwise, let the
+    // server pick a power-friendly one.
+    let buffer_bytes = (frames_per_buffer * std::mem::size_of::<Frame>()) as u32;
+    let attr = ffi::pa_buffer_attr {
+        maxlength: u32::MAX,
+        tlength: buffer_bytes * 2,
+        prebuf: u32::MAX,
+        minreq: buffer_bytes,
+        fragsize: buffer_bytes,
+    };
+    let attr_ptr = if options.frames_per_buffer.frames().is_some() {
+        &attr as *const ffi::pa_buffer_attr
+    } else {
+        std::ptr::null()
+    };
+
+    let to_c_string = |s: &str| CString::new(s).or(Err(Error::Invalid));
+    let application_name = to_c_string(device.application_name())?;
+    let stream_name = to_c_string(device.stream_name())?;
+    let pa_name = device.pa_name().map(to_c_string).transpose()?;
+    let mut error = 0;
+    let simple = unsafe {
+        ffi::pa_simple_new(
+            std::ptr::null(),
+            application_name.as_ptr(),
+            direction,
+            pa_name
+                .as_ref()
+                .map_or(std::ptr::null(), |name| name.as_ptr()),
+            stream_name.as_ptr(),
+            &spec,
+            std::ptr::null(),
+            attr_ptr,
+            &mut error,
+        )
+    };
+    if simple.is_null() {
+        return Err(match error {
+            ffi::PA_ERR_NOTSUPPORTED => Error::IncompatibleFormat(options.format),
+            error => to_error(error),
+        });
+    }
+    Ok((Simple(simple), frames_per_buffer))
+}
+
+fn pulse_format(format: Format) -> Result<ffi::pa_sample_format_t> {
+    Ok(match format {
+        Format::F32 => ffi::PA_SAMPLE_FLOAT32NE,
+        Format::I32 => ffi::PA_SAMPLE_S32NE,
+        Format::I24 => ffi::PA_SAMPLE_S24NE,
+        Format::I24In32 => ffi::PA_SAMPLE_S24_32NE,
+        Format::I16 => ffi::PA_SAMPLE_S16NE,
+        Format::U8 => ffi::PA_SAMPLE_U8,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    })
+}
+
+/// Allocates room for a buffer of frames. Stored as u64s for alignment, and so that workers stay
+/// Send regardless of Frame.
+fn frame_buffer<Frame>(frame_count: usize) -> Vec<u64> {
+    vec![0; (frame_count * std::mem::size_of::<Frame>()).div_ceil(8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulseaudio::Host;
+
+    #[test]
+    fn maps_formats() {
+        assert_eq!(pulse_format(Format::I16), Ok(ffi::PA_SAMPLE_S16NE));
+        assert_eq!(
+            pulse_format(Format::I8),
+            Err(Error::IncompatibleFormat(Format::I8))
+        );
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        device.set_stream_name("Test tone");
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Recording input streams to WAV files, through [hound](https://docs.rs/hound). See
+//! [`record_to_wav`].
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::buffered::InputReader;
+use crate::error::{Error, Result};
+use crate::stream_options::{Format, NoCallback, SampleRate, StreamOptions};
+use crate::Device;
+
+/// How many frames are buffered between the stream and the file: about 2.7 seconds at 48kHz.
+const CAPACITY: usize = 1 << 17;
+
+/// How long the writer sleeps when it caught up with the stream.
+const WAIT: Duration = Duration::from_millis(10);
+
+/// A recording in progress. Dropping it stops it, like [`stop`](WavRecorder::stop).
+pub struct WavRecorder {
+    /// Set to stop the writer.
+    stop: Arc<AtomicBool>,
+    writer: Option<JoinHandle<Result<()>>>,
+}
+
+impl WavRecorder {
+    /// Stops recording, writes the frames that were still buffered, and finalizes the file's
+    /// header. Returns the error that stopped the writer early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Release);
+        match self.writer.take() {
+            Some(writer) => writer
+                .join()
+                .map_err(|_| Error::Unknown("The WAV writer panicked."))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        self.join().ok();
+    }
+}
+
+/// Opens and starts an input stream on `device`, and records it to a new WAV file at `path`.
+///
+/// Frames are written on a thread of their own, so the stream's callback never waits on the disk.
+/// The file's header is finalized when the returned recorder is stopped or dropped.
+///
+/// The file is in the options' [`format`](StreamOptions::format), which can be
+/// [`Format::F32`], [`Format::I32`], [`Format::I24`], [`Format::I16`], or [`Format::I8`]. Its
+/// sample rate must be [`SampleRate::Exact`], as the header is written before the stream runs.
+/// Returns [`Error::NoSuchDevice`] if the file can't be created.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use audiohal::*;
+/// let mut device = Host::with_default_backend()?.default_input_device()?;
+/// let recorder = audiohal::record_to_wav(
+///     &mut device,
+///     "recording.wav",
+///     StreamOptions::<[i16; 2], NoCallback> {
+///         sample_rate: SampleRate::Exact(48000),
+///         resample_if_needed: true,
+///         ..Default::default()
+///     },
+/// )?;
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// recorder.stop()?;
+/// # Result::Ok(())
+/// ```
+pub fn record_to_wav<Frame>(
+    device: &mut Device,
+    path: impl AsRef<Path>,
+    options: StreamOptions<Frame, NoCallback>,
+) -> Result<WavRecorder>
+where
+    Frame: sample::Frame + Send + 'static,
+    Frame::Sample: hound::Sample,
+{
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) => {
+            u32::try_from(rate).map_err(|_| Error::IncompatibleSampleRate)?
+        }
+        _ => return Err(Error::IncompatibleSampleRate),
+    };
+    let (bits_per_sample, sample_format) = match options.format {
+        Format::F32 => (32, hound::SampleFormat::Float),
+        Format::I32 => (32, hound::SampleFormat::Int),
+        Format::I24 => (24, hound::SampleFormat::Int),
+        Format::I16 => (16, hound::SampleFormat::Int),
+        Format::I8 => (8, hound::SampleFormat::Int),
+        format => return Err(Error::IncompatibleFormat(format)),
+    };
+    let spec = hound::WavSpec {
+        channels: u16::try_from(options.n_channels).map_err(|_| Error::IncompatibleNChannels)?,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    };
+    let reader = device.open_input_reader(options, CAPACITY)?;
+    let file = hound::WavWriter::create(path, spec).map_err(|_| Error::NoSuchDevice)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer_stop = Arc::clone(&stop);
+    let writer = std::thread::Builder::new()
+        .name("audiohal-wav-writer".into())
+        .spawn(move || write(reader, file, &writer_stop))
+        .map_err(|error| {
+            Error::from_source(None, "Could not spawn the WAV writer thread.", error)
+        })?;
+    Ok(WavRecorder {
+        stop,
+        writer: Some(writer),
+    })
+}
+
+/// Writes the reader's frames to `file` until `stop` is set, then finalizes it.
+fn write<Frame>(
+    mut reader: InputReader<Frame>,
+    mut file: hound::WavWriter<BufWriter<File>>,
+    stop: &AtomicBool,
+) -> Result<()>
+where
+    Frame: sample::Frame,
+    Frame::Sample: hound::Sample,
+{
+    let write_error = |error| Error::from_source(None, "Could not write the WAV file.", error);
+    let mut frames = vec![Frame::equilibrium(); 4096];
+    loop {
+        // Checked before reading, so that the frames captured until then are still written.
+        let stopping = stop.load(Ordering::Acquire);
+        let count = reader.read(&mut frames);
+        for frame in &frames[..count] {
+            for sample in frame.channels() {
+                file.write_sample(sample).map_err(write_error)?;
+            }
+        }
+        if count < frames.len() {
+            if stopping {
+                break;
+            }
+            std::thread::sleep(WAIT);
+        }
+    }
+    file.finalize().map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Host};
+
+    #[test]
+    fn records_to_wav() -> Result<()> {
+        let path = std::env::temp_dir().join("audiohal_records_to_wav.wav");
+        let mut device = Host::with_backend(Backend::Dummy)?.default_input_device()?;
+        let recorder = record_to_wav(
+            &mut device,
+            &path,
+            StreamOptions::<[i16; 2], NoCallback> {
+                sample_rate: SampleRate::Exact(48000),
+                ..Default::default()
+            },
+        )?;
+        std::thread::sleep(Duration::from_millis(50));
+        recorder.stop()?;
+        let file = hound::WavReader::open(&path).unwrap();
+        assert_eq!(file.spec().channels, 2);
+        assert_eq!(file.spec().sample_rate, 48000);
+        // The dummy device captures silence.
+        assert_gt!(file.len(), 0);
+        assert!(file
+            .into_samples::<i16>()
+            .all(|sample| sample.unwrap() == 0));
+        std::fs::remove_file(path).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_inexact_rates() -> Result<()> {
+        let path = std::env::temp_dir().join("audiohal_rejects_inexact_rates.wav");
+        let mut device = Host::with_backend(Backend::Dummy)?.default_input_device()?;
+        let options = StreamOptions::<[f32; 1], NoCallback>::default();
+        assert_eq!(
+            record_to_wav(&mut device, &path, options).err(),
+            Some(Error::IncompatibleSampleRate)
+        );
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! A lock-free single-producer/single-consumer ring buffer, for moving frames between a stream's
+//! callback and the application's threads. Neither half allocates or blocks, so both are safe to
+//! use in callbacks.
+//!
+//! # Examples
+//!
+//! ```
+//! # use audiohal::ring::RingBuffer;
+//! let (mut producer, mut consumer) = RingBuffer::<[f32; 2]>::new(1024).split();
+//! // E.g. in an input stream's callback.
+//! producer.push_slice(&[[0.5, -0.5]; 256]);
+//! // E.g. on the application's thread.
+//! let mut frames = [[0.0; 2]; 512];
+//! assert_eq!(consumer.pop_slice(&mut frames), 256);
+//! ```
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A ring buffer of up to a fixed number of items (e.g. frames), to split into its two halves.
+pub struct RingBuffer<T>(Arc<Shared<T>>);
+
+impl<T: Copy> RingBuffer<T> {
+    /// An empty ring buffer that holds up to `capacity` items. Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> RingBuffer<T> {
+        assert_gt!(capacity, 0);
+        let buffer = (0..capacity.next_power_of_two())
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        RingBuffer(Arc::new(Shared {
+            buffer,
+            capacity,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Splits the buffer into the halves that write to it and read from it, which can be sent to
+    /// different threads.
+    pub fn split(self) -> (Producer<T>, Consumer<T>) {
+        (Producer(Arc::clone(&self.0)), Consumer(self.0))
+    }
+}
+
+/// State shared between both halves. `head` and `tail` are monotonically increasing (wrapping)
+/// counters of popped and pushed items respectively.
+struct Shared<T> {
+    /// Has a power-of-two length, at least `capacity`, so that the counters still index it in
+    /// order when they wrap.
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    capacity: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Safety: Each slot is only ever accessed by one of the halves at a time, as governed by head and
+// tail.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buffer[index & (self.buffer.len() - 1)].get()
+    }
+}
+
+/// The writing half of a ring buffer.
+pub struct Producer<T>(Arc<Shared<T>>);
+
+/// The reading half of a ring buffer.
+pub struct Consumer<T>(Arc<Shared<T>>);
+
+impl<T: Copy> Producer<T> {
+    /// Pushes as many items from `items` as there is room for. Returns how many were pushed.
+    pub fn push_slice(&mut self, items: &[T]) -> usize {
+        let shared = &*self.0;
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let head = shared.head.load(Ordering::Acquire);
+        let free = shared.capacity() - tail.wrapping_sub(head);
+        let count = free.min(items.len());
+        for (i, &item) in items[..count].iter().enumerate() {
+            unsafe {
+                (*shared.slot(tail.wrapping_add(i)))
+                    .as_mut_ptr()
+                    .write(item)
+            };
+        }
+        shared
+            .tail
+            .store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Pushes a single item. Returns it back if the buffer is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.push_slice(std::slice::from_ref(&item)) == 1 {
+            Ok(())
+        } else {
+            Err(item)
+        }
+    }
+
+    /// The number of items currently waiting to be consumed.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.0.capacity()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Copy> Consumer<T> {
+    /// Pops as many items as are available into `items`. Returns how many were popped.
+    pub fn pop_slice(&mut self, items: &mut [T]) -> usize {
+        let shared = &*self.0;
+        let head = shared.head.load(Ordering::Relaxed);
+        let tail = shared.tail.load(Ordering::Acquire);
+        let count = tail.wrapping_sub(head).min(items.len());
+        for (i, item) in items[..count].iter_mut().enumerate() {
+            *item = unsafe { (*shared.slot(head.wrapping_add(i))).as_ptr().read() };
+        }
+        shared
+            .head
+            .store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// The number of items currently available to be popped.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pops a single item, if one is available.
+    pub fn pop(&mut self) -> Option<T> {
+        let shared = &*self.0;
+        let head = shared.head.load(Ordering::Relaxed);
+        if shared.tail.load(Ordering::Acquire) == head {
+            return None;
+        }
+        let item = unsafe { (*shared.slot(head)).as_ptr().read() };
+        shared.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushes_and_pops_in_order() {
+        let (mut producer, mut consumer) = RingBuffer::new(4).split();
+        assert_eq!(producer.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(consumer.pop(), Some(1));
+        let mut out = [0; 4];
+        assert_eq!(consumer.pop_slice(&mut out), 2);
+        assert_eq!(out[..2], [2, 3]);
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn splits_buffers() {
+        let (mut producer, mut consumer) = RingBuffer::<[i16; 2]>::new(3).split();
+        assert_eq!(consumer.capacity(), 3);
+        assert!(consumer.is_empty());
+        assert_eq!(producer.push([1, -1]), Ok(()));
+        assert_eq!(consumer.len(), 1);
+        assert_eq!(consumer.pop(), Some([1, -1]));
+    }
+
+    #[test]
+    fn stops_when_full() {
+        let (mut producer, mut consumer) = RingBuffer::new(2).split();
+        assert_eq!(producer.push_slice(&[1, 2, 3]), 2);
+        assert!(producer.is_full());
+        assert_eq!(producer.push(4), Err(4));
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(producer.push(4), Ok(()));
+        let mut out = [0; 2];
+        assert_eq!(consumer.pop_slice(&mut out), 2);
+        assert_eq!(out, [2, 4]);
+        assert!(producer.is_empty());
+    }
+
+    #[test]
+    fn wraps_counters() {
+        let ring = RingBuffer::new(3);
+        ring.0.head.store(usize::MAX - 1, Ordering::Relaxed);
+        ring.0.tail.store(usize::MAX - 1, Ordering::Relaxed);
+        let (mut producer, mut consumer) = ring.split();
+        let mut out = [0; 3];
+        for start in 0..4 {
+            assert_eq!(
+                producer.push_slice(&[start, start + 1, start + 2, start + 3]),
+                3
+            );
+            assert!(producer.is_full());
+            assert_eq!(consumer.pop_slice(&mut out), 3);
+            assert_eq!(out, [start, start + 1, start + 2]);
+        }
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn transfers_across_threads() {
+        let (mut producer, mut consumer) = RingBuffer::new(16).split();
+        let writer = std::thread::spawn(move || {
+            let mut next = 0_u32;
+            while next < 10_000 {
+                if producer.push(next).is_ok() {
+                    next += 1;
+                } else {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0_u32;
+        while expected < 10_000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            } else {
+                std::thread::yield_now();
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! Playback of [rodio](https://docs.rs/rodio)'s [`Source`]s on audiohal's streams, so that rodio
+//! users keep their decoders and effects and only swap the output. See
+//! [`Device::play_source`].
+//!
+//! Streams take their callback when they're opened, so sources are played on a new stream
+//! rather than attached to an open one.
+use ::rodio::source::UniformSourceIterator;
+use ::rodio::Source;
+use std::convert::TryFrom;
+
+use crate::error::{Error, Result};
+use crate::stream_options::{Callback, SampleRate, StreamOptions};
+use crate::{Device, Stream};
+
+/// An output stream callback that plays `source` at `sample_rate`, and silence once it's
+/// exhausted. The source's channels are mixed to the frame's, and spans at other rates are
+/// resampled.
+pub fn callback<Frame, S>(source: S, sample_rate: u32) -> Result<Callback<Frame>>
+where
+    Frame: sample::Frame<Sample = f32> + Send + 'static,
+    S: Source + Send + 'static,
+{
+    let n_channels =
+        u16::try_from(Frame::n_channels()).map_err(|_| Error::IncompatibleNChannels)?;
+    let mut source = UniformSourceIterator::new(source, n_channels, sample_rate);
+    Ok(Box::new(move |buffer: &mut [Frame]| {
+        for frame in buffer {
+            *frame = Frame::from_fn(|_| source.next().unwrap_or(0.0));
+        }
+    }))
+}
+
+impl Device {
+    /// Opens and starts an output stream that plays `source`, and silence once it's exhausted.
+    ///
+    /// The stream runs at the source's sample rate, resampled to the device's if it doesn't
+    /// support it, and its samples are converted to the device's format. The source's channels are
+    /// mixed to the stream's `N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let sine = ::rodio::source::SineWave::new(440.0);
+    /// let stream = device.play_source::<_, 2>(sine);
+    /// # stream.ok();
+    /// # Result::Ok(())
+    /// ```
+    pub fn play_source<S, const N: usize>(&mut self, source: S) -> Result<Stream<[f32; N]>>
+    where
+        S: Source + Send + 'static,
+        [f32; N]: sample::Frame<Sample = f32>,
+    {
+        let sample_rate = source.sample_rate();
+        let mut stream = self.open_outstream(StreamOptions {
+            sample_rate: SampleRate::Exact(
+                i32::try_from(sample_rate).map_err(|_| Error::IncompatibleSampleRate)?,
+            ),
+            resample_if_needed: true,
+            callback: callback(source, sample_rate)?,
+            ..Default::default()
+        })?;
+        stream.start()?;
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Host};
+    use ::rodio::buffer::SamplesBuffer;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn mixes_sources_to_frames() -> Result<()> {
+        let mono = SamplesBuffer::new(1, 48000, vec![0.5, -0.5]);
+        let mut callback = callback::<[f32; 2], _>(mono, 48000)?;
+        let mut buffer = [[1.0; 2]; 3];
+        callback(&mut buffer);
+        assert_eq!(buffer, [[0.5, 0.5], [-0.5, -0.5], [0.0, 0.0]]);
+        Ok(())
+    }
+
+    #[test]
+    fn plays_sources() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let played = Arc::new(AtomicBool::new(false));
+        let cb_played = Arc::clone(&played);
+        let source = SamplesBuffer::new(2, 44100, vec![0.0; 4410])
+            .periodic_access(Duration::from_millis(1), move |_| {
+                cb_played.store(true, Ordering::Relaxed)
+            });
+        let _stream = device.play_source::<_, 2>(source)?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(played.load(Ordering::Relaxed));
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Checks that stream callbacks are real-time safe, for debugging glitches.
+//!
+//! Callbacks run on the audio thread, which can't wait, or the device runs dry: They shouldn't
+//! allocate, free, or block (e.g. on a lock or on I/O), as any of those can take unbounded time.
+//! Programs that install [`CheckedAllocator`] as their global allocator have their callbacks'
+//! allocations counted as [`Violation`]s, and code that's about to block can count itself with
+//! [`may_block`] (audiohal's own blocking calls do). Portaudio streams send each callback's
+//! violations to their error receiver, as [`CallbackError::NotRealTime`](crate::CallbackError).
+//!
+//! The checks cost a few thread-local reads per allocation, which is why they're behind the
+//! `rt-check` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! #[global_allocator]
+//! static ALLOCATOR: audiohal::rt::CheckedAllocator =
+//!     audiohal::rt::CheckedAllocator::new(std::alloc::System);
+//! ```
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Something a callback did that may have kept the audio thread waiting.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// Allocated (or reallocated) memory.
+    Allocated,
+    /// Freed memory.
+    Freed,
+    /// Called code that [may block](may_block).
+    Blocked,
+}
+
+thread_local! {
+    /// Whether the thread is running a stream callback.
+    static IN_CALLBACK: Cell<bool> = const { Cell::new(false) };
+    /// The first violation of the running callback, and how many it made.
+    static VIOLATIONS: Cell<(Option<Violation>, usize)> = const { Cell::new((None, 0)) };
+}
+
+/// How many violations every callback made.
+static TOTAL_VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts the allocations of stream callbacks, and allocates through
+/// another allocator (usually [`System`]).
+pub struct CheckedAllocator<A = System>(A);
+
+impl<A> CheckedAllocator<A> {
+    /// Allocates through `allocator`.
+    pub const fn new(allocator: A) -> CheckedAllocator<A> {
+        CheckedAllocator(allocator)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CheckedAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        violate(Violation::Allocated);
+        self.0.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        violate(Violation::Allocated);
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        violate(Violation::Allocated);
+        self.0.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        violate(Violation::Freed);
+        self.0.dealloc(ptr, layout);
+    }
+}
+
+/// Counts a [`Violation::Blocked`] if this is called from a stream callback. Call it before
+/// anything that may block, e.g. in a lock's wrapper.
+pub fn may_block() {
+    violate(Violation::Blocked);
+}
+
+/// How many violations callbacks made since the program started, on every stream (including
+/// those that don't send errors).
+pub fn violations() -> u64 {
+    TOTAL_VIOLATIONS.load(Ordering::Relaxed)
+}
+
+/// Counts `violation` against the running callback, if any. Never allocates: The allocator calls
+/// it. Threads that are exiting have no callback.
+fn violate(violation: Violation) {
+    if IN_CALLBACK.try_with(Cell::get).unwrap_or(false) {
+        VIOLATIONS
+            .try_with(|violations| {
+                let (first, count) = violations.get();
+                violations.set((first.or(Some(violation)), count + 1));
+            })
+            .ok();
+        TOTAL_VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Runs a stream callback, and returns its first violation and how many it made, if any.
+pub(crate) fn checked<R>(callback: impl FnOnce() -> R) -> (R, Option<(Violation, usize)>) {
+    let was_in_callback = IN_CALLBACK.with(|in_callback| in_callback.replace(true));
+    let previous = VIOLATIONS.with(|violations| violations.replace((None, 0)));
+    let result = callback();
+    let (first, count) = VIOLATIONS.with(|violations| violations.replace(previous));
+    IN_CALLBACK.with(|in_callback| in_callback.set(was_in_callback));
+    (result, first.map(|first| (first, count)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_callback_violations() {
+        let allocator = CheckedAllocator::new(System);
+        let layout = Layout::new::<[f32; 64]>();
+        let allocate = || unsafe { allocator.dealloc(allocator.alloc(layout), layout) };
+        let total = violations();
+        let ((), violation) = checked(|| {
+            allocate();
+            may_block();
+        });
+        assert_eq!(violation, Some((Violation::Allocated, 3)));
+        assert_ge!(violations(), total + 3);
+        // Outside of callbacks, anything goes.
+        allocate();
+        assert_eq!(checked(|| ()).1, None);
+    }
+}
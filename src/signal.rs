@@ -0,0 +1,82 @@
+//! Adapters between streams and [`sample::Signal`]s (the signals of the `sample` crate, which is
+//! now `dasp`), so that signal graphs plug into streams in a line.
+//!
+//! Output streams play a signal through [`callback`]. Input streams are signals through
+//! [`InputReader`], which implements [`Signal`].
+use sample::{Frame, Signal};
+
+use crate::buffered::InputReader;
+use crate::stream_options::Callback;
+
+/// An output stream callback that plays `signal`, and silence once it's exhausted.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// use sample::{signal, Signal as _};
+/// let sine = signal::rate(48000.0).const_hz(440.0).sine();
+/// let options = StreamOptions::<[f64; 1]> {
+///     callback: audiohal::signal::callback(sine),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+pub fn callback<S>(signal: S) -> Callback<S::Frame>
+where
+    S: Signal + Send + 'static,
+{
+    let mut signal = signal;
+    Box::new(move |buffer| {
+        for frame in buffer {
+            *frame = if signal.is_exhausted() {
+                S::Frame::equilibrium()
+            } else {
+                signal.next()
+            };
+        }
+    })
+}
+
+/// The captured frames, in order. Signals can't wait for the device: Frames read faster than it
+/// captures them are silent. Check [`available`](InputReader::available) first to avoid it, or
+/// use the signal where it's read at the device's pace (e.g. in another stream's callback).
+impl<F> Signal for InputReader<F>
+where
+    F: Frame,
+{
+    type Frame = F;
+
+    fn next(&mut self) -> F {
+        let mut frame = [F::equilibrium()];
+        self.read(&mut frame);
+        frame[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use crate::{Backend, Host, StreamOptions};
+    use std::time::Duration;
+
+    #[test]
+    fn plays_signals() {
+        let frames = vec![[0.5], [-0.5]];
+        let mut callback = callback(sample::signal::from_iter(frames));
+        let mut buffer = [[1.0]; 4];
+        callback(&mut buffer);
+        assert_eq!(buffer, [[0.5], [-0.5], [0.0], [0.0]]);
+    }
+
+    #[test]
+    fn captures_signals() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_input_device()?;
+        let mut signal = device.open_input_reader::<[f32; 1]>(StreamOptions::default(), 1024)?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert_gt!(signal.available(), 0);
+        assert_eq!(signal.next(), [0.0]);
+        Ok(())
+    }
+}
@@ -1,15 +1,55 @@
+use crate::error::{Error, Result};
+use crate::linux::AlsaOptions;
+use crate::macos::CoreAudioOptions;
+use crate::surround::ChannelMask;
+use crate::windows::WasapiOptions;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::time::{Duration, Instant};
+
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Format {
+    /// Double-precision floats. Backends that can't take doubles (e.g. Portaudio) run the device in
+    /// [`Format::F32`], and convert.
+    F64,
     F32,
     I32,
+    /// 24-bit samples, packed in 3 bytes.
     I24,
+    /// 24-bit samples in the low 3 bytes of 32-bit containers, sign-extended (i.e. `i32`s in
+    /// `-0x80_0000..0x80_0000`). Portaudio converts them from [`Format::I32`].
+    I24In32,
     I16,
     I8,
+    /// Unsigned 32-bit integers, centered on `0x8000_0000`. Portaudio converts them from
+    /// [`Format::I32`].
+    U32,
+    /// Unsigned 16-bit integers, centered on `0x8000`. Portaudio converts them from
+    /// [`Format::I16`].
+    U16,
     U8,
 }
 
+impl Format {
+    /// The size of a single sample in bytes.
+    pub fn sample_size(self) -> usize {
+        use Format::*;
+        match self {
+            F64 => 8,
+            F32 | I32 | I24In32 | U32 => 4,
+            I24 => 3,
+            I16 | U16 => 2,
+            I8 | U8 => 1,
+            _ => panic!("Non-exhaustive format."),
+        }
+    }
+}
+
 #[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SampleRate {
     Exact(i32),
     NearestTo(i32),
@@ -22,7 +62,990 @@ impl Default for SampleRate {
     }
 }
 
+/// An [`Exact`](SampleRate::Exact) rate.
+impl From<i32> for SampleRate {
+    fn from(rate: i32) -> SampleRate {
+        SampleRate::Exact(rate)
+    }
+}
+
+/// How many frames a stream's callback gets at a time. Many devices only run at certain sizes
+/// (e.g. powers of two, or their driver's size), so streams can ask for a range, or for a size
+/// near one they'd like, instead of for an exact size.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BufferSize {
+    /// The backend's size, which may vary between callbacks.
+    Default,
+    /// Exactly this many frames. ASIO devices snap it to their nearest supported size, and JACK
+    /// streams fail with [`Error::InvalidFramesPerBuffer`] unless it's the server's size.
+    Exact(i32),
+    /// Any size from `min` to `max`, inclusive: The device's preferred size if it has one in the
+    /// range (on ASIO and JACK), or else the smallest power of two in it, or else `min`.
+    Range { min: i32, max: i32 },
+    /// The power of two nearest to this many frames (the smaller one on ties), or the device's
+    /// own size on backends that have one (ASIO and JACK).
+    PowerOfTwoNear(i32),
+}
+
+impl Default for BufferSize {
+    fn default() -> BufferSize {
+        BufferSize::Default
+    }
+}
+
+/// An [`Exact`](BufferSize::Exact) size.
+impl From<i32> for BufferSize {
+    fn from(frames: i32) -> BufferSize {
+        BufferSize::Exact(frames)
+    }
+}
+
+impl BufferSize {
+    /// The size to open streams with, on devices without a preferred size of their own. `None`
+    /// for the backend's size. Invalid sizes (not positive, or empty ranges) come out as sizes
+    /// that aren't positive, for backends to reject with [`Error::InvalidFramesPerBuffer`].
+    pub(crate) fn frames(self) -> Option<i32> {
+        match self {
+            BufferSize::Default => None,
+            BufferSize::Exact(frames) => Some(frames),
+            BufferSize::Range { min, max } if min <= 0 || min > max => Some(0),
+            BufferSize::Range { min, max } => Some(
+                (min as u32)
+                    .checked_next_power_of_two()
+                    .filter(|&frames| frames <= max as u32)
+                    .map_or(min, |frames| frames as i32),
+            ),
+            BufferSize::PowerOfTwoNear(frames) if frames <= 0 => Some(frames),
+            BufferSize::PowerOfTwoNear(frames) => {
+                let upper = (frames as u32).next_power_of_two();
+                let lower = if upper == frames as u32 {
+                    upper
+                } else {
+                    upper / 2
+                };
+                if upper > i32::MAX as u32 || frames as u32 - lower <= upper - frames as u32 {
+                    Some(lower as i32)
+                } else {
+                    Some(upper as i32)
+                }
+            }
+        }
+    }
+
+    /// Like [`frames`](BufferSize::frames), on devices that prefer `preferred` frames: Streams
+    /// that don't ask for an exact size get it, if it's in their range.
+    #[cfg_attr(not(all(windows, feature = "asio")), allow(dead_code))]
+    pub(crate) fn frames_preferring(self, preferred: i32) -> Option<i32> {
+        match self {
+            BufferSize::Exact(_) => self.frames(),
+            _ if self.accepts(preferred) => Some(preferred),
+            _ => self.frames(),
+        }
+    }
+
+    /// Whether streams can run at `frames`, on devices that only run at one size: Any size that
+    /// isn't exact accepts the device's own, as long as it's in range.
+    #[cfg_attr(
+        not(any(feature = "jack", all(windows, feature = "asio"))),
+        allow(dead_code)
+    )]
+    pub(crate) fn accepts(self, frames: i32) -> bool {
+        match self {
+            BufferSize::Default => true,
+            BufferSize::Exact(exact) => exact == frames,
+            BufferSize::Range { min, max } => 0 < min && min <= frames && frames <= max,
+            BufferSize::PowerOfTwoNear(near) => near > 0,
+        }
+    }
+}
+
+/// How much latency a stream asks its backend for, trading it against resilience to underruns.
+/// It's only a hint: Backends round it to what the device supports. Only Portaudio and WASAPI
+/// streams ask for it, and other backends pick their own latency.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LatencyHint {
+    /// The lowest latency the device is good at, for interactive audio.
+    Low,
+    /// A safe latency, for playback. Streams with a
+    /// [`frames_per_buffer`](StreamOptions::frames_per_buffer) ask for one buffer's worth.
+    High,
+    Exact(Duration),
+}
+
+impl Default for LatencyHint {
+    fn default() -> LatencyHint {
+        LatencyHint::High
+    }
+}
+
+/// How streams round their samples when they're converted to a device format with fewer bits
+/// (e.g. `f32` callbacks on 16-bit devices). Only Portaudio streams dither: Other backends
+/// convert as they do.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DitherMode {
+    /// Truncates samples, which distorts quiet material audibly.
+    Off,
+    /// Adds triangular noise of a device step before rounding, which trades the distortion for a
+    /// little white noise.
+    Tpdf,
+    /// Like [`Tpdf`](DitherMode::Tpdf), also feeding back the rounding error, which pushes the
+    /// noise up in frequency, where it's harder to hear. Streams that Portaudio converts itself
+    /// get its TPDF dither instead.
+    Shaped,
+}
+
+impl Default for DitherMode {
+    fn default() -> DitherMode {
+        DitherMode::Tpdf
+    }
+}
+
+/// What streams do with float samples out of `-1.0..=1.0`, which the device can't play. Only
+/// Portaudio callback streams apply a policy: Other backends clip as their devices do.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClipPolicy {
+    /// Clamps samples to the range.
+    Clamp,
+    /// Leaves samples as they are, which saves checking them: Integer devices wrap them around,
+    /// which is loud.
+    Wrap,
+    /// Clamps samples, and sends a [`CallbackError::Clipped`](crate::CallbackError::Clipped) for
+    /// each buffer that clipped. [`StreamStats::clipped_samples`] counts them.
+    ErrorOnClip,
+}
+
+impl Default for ClipPolicy {
+    fn default() -> ClipPolicy {
+        ClipPolicy::Clamp
+    }
+}
+
+/// Whether, and where, streams reopen once their device disappears. See
+/// [`StreamOptions::reconnect`].
+///
+/// Portaudio only lists devices when its host is created, so streams can only reopen on devices
+/// that were listed then: e.g. a device that came back, or the default output of the time.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReconnectPolicy {
+    /// The stream stays stopped, and can be reopened by the application.
+    None,
+    /// The stream retries its own device until it opens again.
+    RetrySameDevice,
+    /// The stream moves to the host's default device, retrying it until it opens.
+    FallbackToDefault,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> ReconnectPolicy {
+        ReconnectPolicy::None
+    }
+}
+
+/// How resampled streams (see [`StreamOptions::resample_if_needed`]) interpolate between sample
+/// rates. Better qualities take more CPU time per frame. The sinc qualities need the `rubato`
+/// feature: Without it, they resample linearly.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResamplerQuality {
+    /// Linear interpolation. Cheap, but aliases audibly with high frequencies.
+    Linear,
+    /// A short windowed sinc filter.
+    SincFast,
+    /// A long windowed sinc filter, for when quality matters more than CPU time.
+    SincBest,
+}
+
+impl Default for ResamplerQuality {
+    fn default() -> ResamplerQuality {
+        ResamplerQuality::Linear
+    }
+}
+
+/// What streams do when the device doesn't take their channel count.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelMixPolicy {
+    /// Fail with [`Error::IncompatibleNChannels`].
+    Exact,
+    /// Run the device with as many channels as it has, and mix between those and the callback's:
+    /// e.g. play stereo on a mono device, or capture stereo from one. Mono is played on the front
+    /// channels, and 5.1 downmixed to stereo. Only Portaudio streams mix.
+    Mix,
+}
+
+impl Default for ChannelMixPolicy {
+    fn default() -> ChannelMixPolicy {
+        ChannelMixPolicy::Exact
+    }
+}
+
+/// Routes a stream's channels to arbitrary device channels: e.g. a stereo stream to channels 3
+/// and 4 of an 8-channel interface. Unrouted device channels are silent, and unrouted stream
+/// channels are dropped.
+///
+/// ```
+/// # use audiohal::*;
+/// let options = StreamOptions::<[f32; 2]> {
+///     channel_map: Some(ChannelMap::new(8).route(0, 2).route(1, 3)),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMap {
+    device_n_channels: i32,
+    /// (Stream channel, device channel, gain) triples.
+    routes: Vec<(i32, i32, f32)>,
+}
+
+impl ChannelMap {
+    /// A map onto `device_n_channels` device channels, with nothing routed yet.
+    pub fn new(device_n_channels: i32) -> ChannelMap {
+        ChannelMap {
+            device_n_channels,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Routes the stream's `channel` to the device's `device_channel`. For input streams, the
+    /// stream's channel is captured from the device's. A channel can be routed several times:
+    /// Its samples are summed with the others routed to the same channel.
+    pub fn route(self, channel: i32, device_channel: i32) -> ChannelMap {
+        self.route_with_gain(channel, device_channel, 1.0)
+    }
+
+    /// Like [`route`](ChannelMap::route), scaling the channel's samples by `gain`.
+    pub fn route_with_gain(mut self, channel: i32, device_channel: i32, gain: f32) -> ChannelMap {
+        self.routes.push((channel, device_channel, gain));
+        self
+    }
+
+    /// The number of channels the device runs with.
+    pub fn device_n_channels(&self) -> i32 {
+        self.device_n_channels
+    }
+
+    /// The map as weights: Each row is a channel mixed into (a device channel for output streams,
+    /// and a stream channel for input streams), and gives the weights of the channels mixed from.
+    #[allow(dead_code)]
+    pub(crate) fn matrix(&self, n_channels: i32, is_output: bool) -> Result<Vec<Vec<f64>>> {
+        if n_channels <= 0 || self.device_n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let (n_rows, n_columns) = if is_output {
+            (self.device_n_channels, n_channels)
+        } else {
+            (n_channels, self.device_n_channels)
+        };
+        let mut matrix = vec![vec![0.0; n_columns as usize]; n_rows as usize];
+        for &(channel, device_channel, gain) in &self.routes {
+            if !(0..n_channels).contains(&channel)
+                || !(0..self.device_n_channels).contains(&device_channel)
+            {
+                return Err(Error::IncompatibleNChannels);
+            }
+            let (row, column) = if is_output {
+                (device_channel, channel)
+            } else {
+                (channel, device_channel)
+            };
+            matrix[row as usize][column as usize] += f64::from(gain);
+        }
+        Ok(matrix)
+    }
+}
+
+/// The device channels a stream runs on.
+///
+/// ```
+/// # use audiohal::*;
+/// // Channels 5 and 6 (counting from 0) of a multichannel interface.
+/// let options = StreamOptions::<[f32; 2]> {
+///     channels: ChannelSelection::Range(5..7),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelSelection {
+    /// As many of the device's first channels as the stream has.
+    All,
+    /// A range of device channels, counting from 0, with as many channels as the stream has.
+    /// The device runs with all of its channels, and the others are silent (or ignored, for
+    /// input streams).
+    Range(std::ops::Range<i32>),
+}
+
+impl Default for ChannelSelection {
+    fn default() -> ChannelSelection {
+        ChannelSelection::All
+    }
+}
+
+/// Callback of an output stream. Fills the given buffer with frames to be played.
 pub type Callback<Frame> = Box<dyn FnMut(&mut [Frame]) + Send>;
+/// Callback of an input stream. Receives the frames captured by the device.
+pub type InputCallback<Frame> = Box<dyn FnMut(&[Frame]) + Send>;
+/// Callback of a duplex stream. Receives the captured input frames, and fills the output buffer
+/// with frames to be played.
+pub type DuplexCallback<InFrame, OutFrame> = Box<dyn FnMut(&[InFrame], &mut [OutFrame]) + Send>;
+/// Callback of a duplex stream with info. Receives the captured input frames and the output buffer
+/// to fill, which always have as many frames, along with their [`CallbackInfo`], which has both
+/// their capture and playback times.
+pub type DuplexInfoCallback<InFrame, OutFrame> =
+    Box<dyn FnMut(&[InFrame], &mut [OutFrame], &CallbackInfo) + Send>;
+/// Callback of a planar output stream. Fills one buffer of samples per channel.
+pub type PlanarCallback<Sample> = Box<dyn FnMut(&mut [&mut [Sample]]) + Send>;
+/// Callback of a planar input stream. Receives one buffer of captured samples per channel.
+pub type PlanarInputCallback<Sample> = Box<dyn FnMut(&[&[Sample]]) + Send>;
+/// Callback of a dynamic output stream. Fills the given buffer with interleaved samples, for the
+/// given number of channels.
+pub type DynamicCallback<Sample> = Box<dyn FnMut(&mut [Sample], usize) + Send>;
+/// Callback of a dynamic input stream. Receives the captured interleaved samples, and the number of
+/// channels.
+pub type DynamicInputCallback<Sample> = Box<dyn FnMut(&[Sample], usize) + Send>;
+/// Called once a stream stops: after it's stopped, or after its callback ended it (see
+/// [`StreamFlow`]).
+pub type FinishedCallback = Box<dyn FnMut() + Send>;
+/// Callback of an output stream with info. Fills the given buffer with frames to be played, and
+/// gets the buffer's [`CallbackInfo`]. Returns whether the stream goes on.
+pub type InfoCallback<Frame> = Box<dyn FnMut(&mut [Frame], &CallbackInfo) -> StreamFlow + Send>;
+/// Callback of an input stream with info. Receives the captured frames, and their
+/// [`CallbackInfo`]. Returns whether the stream goes on.
+pub type InfoInputCallback<Frame> = Box<dyn FnMut(&[Frame], &CallbackInfo) -> StreamFlow + Send>;
+/// Callback of a raw output stream. Fills the backend's own buffer, in the stream's format.
+pub type RawCallback = Box<dyn FnMut(RawBuffer) + Send>;
+/// Callback of a raw input stream. Reads the backend's own buffer of captured samples.
+pub type RawInputCallback = Box<dyn FnMut(RawInputBuffer) + Send>;
+
+/// The backend's own buffer, which a raw output stream's callback fills in place with interleaved
+/// samples in the stream's format. Nothing copies or converts them on their way to the device.
+#[derive(Debug)]
+pub struct RawBuffer<'a> {
+    data: *mut c_void,
+    n_frames: usize,
+    n_channels: usize,
+    format: Format,
+    _buffer: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> RawBuffer<'a> {
+    /// # Safety
+    ///
+    /// `data` must be writable for `n_frames` frames of `n_channels` samples in `format`, for as
+    /// long as `'a`.
+    #[cfg_attr(
+        not(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) unsafe fn new(
+        data: *mut c_void,
+        n_frames: usize,
+        n_channels: usize,
+        format: Format,
+    ) -> RawBuffer<'a> {
+        RawBuffer {
+            data,
+            n_frames,
+            n_channels,
+            format,
+            _buffer: PhantomData,
+        }
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        self.data
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.n_frames
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.n_channels
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The buffer's size in bytes.
+    pub fn len_bytes(&self) -> usize {
+        self.n_frames * self.n_channels * self.format.sample_size()
+    }
+
+    /// The buffer as interleaved samples. Its samples are unspecified until the callback writes
+    /// them.
+    ///
+    /// # Safety
+    ///
+    /// `Sample` must be the sample type of the stream's format: e.g. `f32` for [`Format::F32`],
+    /// or `[u8; 3]` for [`Format::I24`].
+    pub unsafe fn samples_mut<Sample>(&mut self) -> &mut [Sample] {
+        std::slice::from_raw_parts_mut(self.data as *mut Sample, self.n_frames * self.n_channels)
+    }
+}
+
+/// The backend's own buffer of captured samples, which a raw input stream's callback reads in
+/// place: interleaved, in the stream's format.
+#[derive(Debug)]
+pub struct RawInputBuffer<'a> {
+    data: *const c_void,
+    n_frames: usize,
+    n_channels: usize,
+    format: Format,
+    _buffer: PhantomData<&'a [u8]>,
+}
+
+impl<'a> RawInputBuffer<'a> {
+    /// # Safety
+    ///
+    /// `data` must hold `n_frames` frames of `n_channels` samples in `format`, for as long as
+    /// `'a`.
+    #[cfg_attr(
+        not(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) unsafe fn new(
+        data: *const c_void,
+        n_frames: usize,
+        n_channels: usize,
+        format: Format,
+    ) -> RawInputBuffer<'a> {
+        RawInputBuffer {
+            data,
+            n_frames,
+            n_channels,
+            format,
+            _buffer: PhantomData,
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const c_void {
+        self.data
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.n_frames
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.n_channels
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The buffer's size in bytes.
+    pub fn len_bytes(&self) -> usize {
+        self.n_frames * self.n_channels * self.format.sample_size()
+    }
+
+    /// The buffer as interleaved samples.
+    ///
+    /// # Safety
+    ///
+    /// `Sample` must be the sample type of the stream's format: e.g. `f32` for [`Format::F32`],
+    /// or `[u8; 3]` for [`Format::I24`].
+    pub unsafe fn samples<Sample>(&self) -> &[Sample] {
+        std::slice::from_raw_parts(self.data as *const Sample, self.n_frames * self.n_channels)
+    }
+}
+
+/// Whether a stream goes on after its callback returns. Once a callback returns anything but
+/// [`Continue`](StreamFlow::Continue), it isn't called again, and the stream stops until it is
+/// started again.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFlow {
+    Continue,
+    /// Stops the stream once the frames of this buffer (and the ones before it) are played: e.g.
+    /// at the end of a sound that plays once.
+    Complete,
+    /// Stops the stream as soon as possible, dropping the frames that weren't played yet.
+    Abort,
+}
+
+impl Default for StreamFlow {
+    fn default() -> StreamFlow {
+        StreamFlow::Continue
+    }
+}
+
+/// What a stream with info tells its callback about the buffer it's passed. Times are on the
+/// stream's clock, which starts at an arbitrary point: Only differences between them mean
+/// anything.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallbackInfo {
+    /// When the callback was called.
+    pub current_time: Duration,
+    /// When the first frame of an input stream's buffer was captured by the device. `None` for
+    /// output streams.
+    pub capture_time: Option<Duration>,
+    /// When the first frame of an output stream's buffer will be played by the device. `None` for
+    /// input streams.
+    pub playback_time: Option<Duration>,
+    /// What went wrong since the last buffer.
+    pub status: StreamStatus,
+}
+
+/// Glitches a stream had since its last buffer: e.g. to log them, or to ask for bigger buffers.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamStatus {
+    /// Input frames were missing, and were replaced with silence.
+    pub input_underflow: bool,
+    /// Captured frames were dropped, because the callback didn't keep up.
+    pub input_overflow: bool,
+    /// The device played silence, because the callback didn't keep up.
+    pub output_underflow: bool,
+    /// Some of the callback's frames were dropped.
+    pub output_overflow: bool,
+    /// The buffer is being used to prime the stream's output, before it starts: Its input is
+    /// silence.
+    pub priming_output: bool,
+}
+
+impl StreamStatus {
+    /// Whether nothing went wrong.
+    pub fn is_ok(&self) -> bool {
+        !(self.input_underflow
+            || self.input_overflow
+            || self.output_underflow
+            || self.output_overflow)
+    }
+}
+
+/// The glitches a stream had since it was opened, to monitor its health. See
+/// [`Stream::stats`](crate::Stream::stats).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamStats {
+    /// Buffers whose input frames were missing. See [`StreamStatus`].
+    pub input_underflows: u64,
+    /// Buffers before which captured frames were dropped.
+    pub input_overflows: u64,
+    /// Buffers before which the device played silence.
+    pub output_underflows: u64,
+    /// Buffers some of whose frames were dropped.
+    pub output_overflows: u64,
+    /// The frames of all the buffers that glitched.
+    pub glitched_frames: u64,
+    /// When the stream last glitched, on its clock (see [`CallbackInfo`]). `None` if it never did.
+    pub last_glitch: Option<Duration>,
+    /// The output samples that were out of range, for streams with
+    /// [`ClipPolicy::ErrorOnClip`]. 0 for other streams.
+    pub clipped_samples: u64,
+}
+
+/// What a stream's callback gets, as negotiated when the stream was opened. See
+/// [`Stream::info`](crate::Stream::info).
+///
+/// Streams without a [`frames_per_buffer`](StreamOptions::frames_per_buffer) get whatever the
+/// backend delivers, which may vary between callbacks: Callbacks shouldn't assume that their
+/// buffers have the same length unless the stream [has a fixed size](StreamInfo::is_fixed_size).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamInfo {
+    /// The fewest frames the callback gets at a time. `None` if the backend doesn't bound them.
+    pub min_frames_per_buffer: Option<usize>,
+    /// The most frames the callback gets at a time. `None` if the backend doesn't bound them.
+    pub max_frames_per_buffer: Option<usize>,
+}
+
+impl StreamInfo {
+    /// The info of streams whose callback always gets `frames` frames.
+    pub(crate) fn fixed(frames: usize) -> StreamInfo {
+        StreamInfo {
+            min_frames_per_buffer: Some(frames),
+            max_frames_per_buffer: Some(frames),
+        }
+    }
+
+    /// The info of streams whose callback gets at most `frames` frames.
+    #[cfg_attr(
+        not(any(
+            all(target_os = "android", feature = "aaudio"),
+            all(target_os = "linux", feature = "alsa"),
+            all(target_os = "macos", feature = "coreaudio"),
+            all(target_os = "windows", feature = "wasapi")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn at_most(frames: usize) -> StreamInfo {
+        StreamInfo {
+            max_frames_per_buffer: Some(frames),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the callback always gets the same number of frames.
+    pub fn is_fixed_size(&self) -> bool {
+        self.min_frames_per_buffer.is_some()
+            && self.min_frames_per_buffer == self.max_frames_per_buffer
+    }
+}
+
+/// The number of buckets in a [`Histogram`].
+pub(crate) const HISTOGRAM_BUCKETS: usize = 24;
+
+/// Counts of durations, in buckets whose bounds double: The first bucket counts durations under
+/// 1 µs, the next under 2 µs, then under 4 µs, and so on. The last bucket (from about 4 s) has no
+/// bound. See [`CallbackMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Histogram {
+    counts: [u64; HISTOGRAM_BUCKETS],
+}
+
+impl Histogram {
+    #[cfg_attr(
+        not(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn from_counts(counts: [u64; HISTOGRAM_BUCKETS]) -> Histogram {
+        Histogram { counts }
+    }
+
+    /// The bucket that counts `duration`.
+    #[cfg_attr(
+        not(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn bucket_of(duration: Duration) -> usize {
+        let micros = duration.as_micros();
+        ((128 - micros.leading_zeros()) as usize).min(HISTOGRAM_BUCKETS - 1)
+    }
+
+    fn bound(bucket: usize) -> Duration {
+        if bucket == HISTOGRAM_BUCKETS - 1 {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1 << bucket)
+        }
+    }
+
+    /// How many durations were counted.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Every bucket's (exclusive) upper bound and count, from the shortest durations to the
+    /// longest. The last bucket's bound is [`Duration::MAX`].
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| (Histogram::bound(bucket), *count))
+    }
+
+    /// A bound that the `quantile` of the counted durations are under: e.g. 0.99 for the 99th
+    /// percentile. It's the bound of the bucket the quantile falls in, so it's up to twice the
+    /// quantile's duration. `None` if nothing was counted.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut counted = 0;
+        self.counts.iter().enumerate().find_map(|(bucket, n)| {
+            counted += n;
+            if counted >= rank {
+                Some(Histogram::bound(bucket))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// How long a stream's callbacks took, and how regularly they came, since it was opened: e.g. to
+/// alert when the audio thread overruns. See [`Stream::metrics`](crate::Stream::metrics).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CallbackMetrics {
+    /// How long each callback took, including the stream's own processing (e.g. gains).
+    pub durations: Histogram,
+    /// How far the time between each callback and the last was from a buffer's duration: i.e.
+    /// how late, or early, each callback came.
+    pub jitter: Histogram,
+    /// The callbacks that took longer than their buffer plays for, which the device can't keep up
+    /// with for long.
+    pub overruns: u64,
+    /// How long the longest callback took.
+    pub max_duration: Duration,
+}
+
+/// A stream time and the wall-clock time it was read at, to translate between the two: e.g. for
+/// A/V sync, or to log callback times. See
+/// [`Stream::clock_correlation`](crate::Stream::clock_correlation).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockCorrelation {
+    /// On the stream's clock, as [`CallbackInfo`]'s times are.
+    pub stream_time: Duration,
+    /// When `stream_time` was read.
+    pub instant: Instant,
+    /// How much faster the stream's clock runs than the wall clock's, as a fraction: e.g. 1e-5 if
+    /// it gains 10 µs a second. Estimated since the stream's first correlation, so it gets more
+    /// accurate the longer apart correlations are. 0 until then.
+    pub drift: f64,
+}
+
+impl ClockCorrelation {
+    /// Correlates `stream_time` with `instant`, estimating the drift since `reference`.
+    #[cfg_attr(
+        not(all(
+            feature = "portaudio",
+            not(any(target_os = "android", target_arch = "wasm32"))
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(
+        stream_time: Duration,
+        instant: Instant,
+        reference: Option<(Duration, Instant)>,
+    ) -> ClockCorrelation {
+        let drift = match reference {
+            Some((reference_time, reference_instant)) if instant > reference_instant => {
+                let wall_elapsed = (instant - reference_instant).as_secs_f64();
+                let stream_elapsed = stream_time.as_secs_f64() - reference_time.as_secs_f64();
+                stream_elapsed / wall_elapsed - 1.0
+            }
+            _ => 0.0,
+        };
+        ClockCorrelation {
+            stream_time,
+            instant,
+            drift,
+        }
+    }
+
+    /// When `stream_time` happens (or happened) on the wall clock.
+    pub fn to_instant(&self, stream_time: Duration) -> Instant {
+        let scale = |elapsed: Duration| elapsed.div_f64(1.0 + self.drift);
+        if stream_time >= self.stream_time {
+            self.instant + scale(stream_time - self.stream_time)
+        } else {
+            self.instant - scale(self.stream_time - stream_time)
+        }
+    }
+
+    /// The stream time at `instant`. Saturates at 0.
+    pub fn to_stream_time(&self, instant: Instant) -> Duration {
+        let scale = |elapsed: Duration| elapsed.mul_f64(1.0 + self.drift);
+        if instant >= self.instant {
+            self.stream_time + scale(instant - self.instant)
+        } else {
+            self.stream_time
+                .checked_sub(scale(self.instant - instant))
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// How a stream stops: see [`Stream::stop`](crate::Stream::stop) and
+/// [`Stream::abort`](crate::Stream::abort). Dropped streams abort, unless told otherwise with
+/// [`Stream::set_drop_mode`](crate::Stream::set_drop_mode).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    /// Plays the frames that were already buffered, then stops.
+    Stop,
+    /// Stops as soon as possible, dropping the frames that weren't played yet.
+    Abort,
+}
+
+impl Default for StopMode {
+    fn default() -> StopMode {
+        StopMode::Abort
+    }
+}
+
+/// Where a stream is in its lifecycle. See [`Stream::state`](crate::Stream::state).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// Not started yet, or stopped (or aborted). Starting it runs it again.
+    Stopped,
+    /// Started, and calling its callback (unless paused).
+    Running,
+    /// Its callback ended it (see [`StreamFlow`]), or it's stopping: It won't call its callback
+    /// again, but has to be stopped before it's started again.
+    /// [`Stream::start`](crate::Stream::start) does that.
+    Finished,
+}
+
+/// Determines the callback signature of a [`StreamOptions`].
+///
+/// Implemented by the [`Output`], [`Input`], [`PlanarOutput`], [`PlanarInput`],
+/// [`DynamicOutput`], [`DynamicInput`], [`OutputWithInfo`], [`InputWithInfo`], [`RawOutput`],
+/// [`RawInput`], and [`NoCallback`] markers.
+pub trait CallbackKind {
+    type Callback<Frame>;
+
+    /// Whether the options are for output streams, or for input streams. `None` if they can be
+    /// for either.
+    #[doc(hidden)]
+    const IS_OUTPUT: Option<bool> = None;
+
+    #[doc(hidden)]
+    fn dummy_callback<Frame: 'static>() -> Self::Callback<Frame>;
+}
+
+/// Marker for output (playback) streams. The callback is a [`Callback`].
+pub enum Output {}
+/// Marker for input (capture) streams. The callback is an [`InputCallback`].
+pub enum Input {}
+/// Marker for planar (non-interleaved) output streams. The callback is a [`PlanarCallback`], and
+/// the options' `Frame` is a single sample.
+pub enum PlanarOutput {}
+/// Marker for planar (non-interleaved) input streams. The callback is a [`PlanarInputCallback`],
+/// and the options' `Frame` is a single sample.
+pub enum PlanarInput {}
+/// Marker for interleaved output streams whose channel count is only known at runtime. The
+/// callback is a [`DynamicCallback`], and the options' `Frame` is a single sample.
+pub enum DynamicOutput {}
+/// Marker for interleaved input streams whose channel count is only known at runtime. The
+/// callback is a [`DynamicInputCallback`], and the options' `Frame` is a single sample.
+pub enum DynamicInput {}
+/// Marker for output streams whose callback is an [`InfoCallback`].
+pub enum OutputWithInfo {}
+/// Marker for input streams whose callback is an [`InfoInputCallback`].
+pub enum InputWithInfo {}
+/// Marker for raw output streams, whose callback fills the backend's own buffer in place, in the
+/// device's format: for callbacks that already produce it, and can't spare a copy. The callback is
+/// a [`RawCallback`], and the options' `Frame` is a single sample.
+pub enum RawOutput {}
+/// Marker for raw input streams, whose callback reads the backend's own buffer in place. The
+/// callback is a [`RawInputCallback`], and the options' `Frame` is a single sample.
+pub enum RawInput {}
+/// Marker for options that do not carry their own callback, such as either half of a duplex
+/// stream. The callback is `()`.
+pub enum NoCallback {}
+
+impl CallbackKind for Output {
+    type Callback<Frame> = Callback<Frame>;
+    const IS_OUTPUT: Option<bool> = Some(true);
+
+    fn dummy_callback<Frame: 'static>() -> Callback<Frame> {
+        Box::new(dummy_callback)
+    }
+}
+
+impl CallbackKind for Input {
+    type Callback<Frame> = InputCallback<Frame>;
+    const IS_OUTPUT: Option<bool> = Some(false);
+
+    fn dummy_callback<Frame: 'static>() -> InputCallback<Frame> {
+        Box::new(dummy_input_callback)
+    }
+}
+
+impl CallbackKind for PlanarOutput {
+    type Callback<Sample> = PlanarCallback<Sample>;
+    const IS_OUTPUT: Option<bool> = Some(true);
+
+    fn dummy_callback<Sample: 'static>() -> PlanarCallback<Sample> {
+        Box::new(|_| {})
+    }
+}
+
+impl CallbackKind for PlanarInput {
+    type Callback<Sample> = PlanarInputCallback<Sample>;
+    const IS_OUTPUT: Option<bool> = Some(false);
+
+    fn dummy_callback<Sample: 'static>() -> PlanarInputCallback<Sample> {
+        Box::new(|_| {})
+    }
+}
+
+impl CallbackKind for DynamicOutput {
+    type Callback<Sample> = DynamicCallback<Sample>;
+    const IS_OUTPUT: Option<bool> = Some(true);
+
+    fn dummy_callback<Sample: 'static>() -> DynamicCallback<Sample> {
+        Box::new(|_, _| {})
+    }
+}
+
+impl CallbackKind for DynamicInput {
+    type Callback<Sample> = DynamicInputCallback<Sample>;
+    const IS_OUTPUT: Option<bool> = Some(false);
+
+    fn dummy_callback<Sample: 'static>() -> DynamicInputCallback<Sample> {
+        Box::new(|_, _| {})
+    }
+}
+
+impl CallbackKind for OutputWithInfo {
+    type Callback<Frame> = InfoCallback<Frame>;
+    const IS_OUTPUT: Option<bool> = Some(true);
+
+    fn dummy_callback<Frame: 'static>() -> InfoCallback<Frame> {
+        Box::new(|_, _| StreamFlow::Continue)
+    }
+}
+
+impl CallbackKind for InputWithInfo {
+    type Callback<Frame> = InfoInputCallback<Frame>;
+    const IS_OUTPUT: Option<bool> = Some(false);
+
+    fn dummy_callback<Frame: 'static>() -> InfoInputCallback<Frame> {
+        Box::new(|_, _| StreamFlow::Continue)
+    }
+}
+
+impl CallbackKind for RawOutput {
+    type Callback<Sample> = RawCallback;
+    const IS_OUTPUT: Option<bool> = Some(true);
+
+    fn dummy_callback<Sample: 'static>() -> RawCallback {
+        Box::new(|_| {})
+    }
+}
+
+impl CallbackKind for RawInput {
+    type Callback<Sample> = RawInputCallback;
+    const IS_OUTPUT: Option<bool> = Some(false);
+
+    fn dummy_callback<Sample: 'static>() -> RawInputCallback {
+        Box::new(|_| {})
+    }
+}
+
+impl CallbackKind for NoCallback {
+    type Callback<Frame> = ();
+
+    fn dummy_callback<Frame: 'static>() {}
+}
 
 /// Configures the creation of input/output streams.
 ///
@@ -34,74 +1057,799 @@ pub type Callback<Frame> = Box<dyn FnMut(&mut [Frame]) + Send>;
 /// # use audiohal::*;
 /// fn my_stream_callback(_: &mut [[f32; 2]]) {}
 /// // Creates a StreamOptions with a stereo f32 frame.
-/// let options = StreamOptions {
+/// let options: StreamOptions<_> = StreamOptions {
 ///     callback: Box::new(my_stream_callback),
 ///     ..Default::default()
 /// };
 /// assert_eq!(options.format, Format::F32);
 /// assert_eq!(options.n_channels, 2);
 /// ```
-pub struct StreamOptions<Frame> {
+///
+/// The same struct configures input streams, in which case `Kind` is [`Input`] and the callback is
+/// an [`InputCallback`]:
+///
+/// ```
+/// # use audiohal::*;
+/// let options: StreamOptions<[f32; 1], Input> = StreamOptions {
+///     callback: Box::new(|captured: &[[f32; 1]]| println!("Got {} frames.", captured.len())),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+///
+/// Options can also be set one at a time, with [`StreamOptions::builder`].
+///
+/// Planar streams' callbacks get a buffer per channel instead of a buffer of frames. Their `Frame`
+/// is a single sample, and `n_channels` sets how many buffers there are:
+///
+/// ```
+/// # use audiohal::*;
+/// let options: StreamOptions<f32, PlanarOutput> = StreamOptions {
+///     format: Format::F32,
+///     n_channels: 2,
+///     frames_per_buffer: BufferSize::Default,
+///     sample_rate: SampleRate::DeviceDefault,
+///     resample_if_needed: false,
+///     resampler_quality: ResamplerQuality::Linear,
+///     channel_mix_policy: ChannelMixPolicy::Exact,
+///     channel_map: None,
+///     channel_mask: None,
+///     channels: ChannelSelection::All,
+///     exclusive: false,
+///     latency: LatencyHint::High,
+///     realtime_priority: true,
+///     prime_output: false,
+///     watchdog: None,
+///     reconnect: ReconnectPolicy::None,
+///     wasapi: None,
+///     coreaudio: None,
+///     alsa: None,
+///     gain: 1.0,
+///     channel_gains: None,
+///     dither: DitherMode::Tpdf,
+///     clip_policy: ClipPolicy::Clamp,
+///     on_finished: None,
+///     callback: Box::new(|channels: &mut [&mut [f32]]| {
+///         for channel in channels {
+///             channel.iter_mut().for_each(|sample| *sample = 0.0);
+///         }
+///     }),
+/// };
+/// # options;
+/// ```
+pub struct StreamOptions<Frame, Kind: CallbackKind = Output> {
     pub format: Format,
     pub n_channels: i32,
 
-    pub frames_per_buffer: Option<i32>,
+    /// How many frames the callback gets at a time. [`Default`](BufferSize::Default) by default.
+    pub frames_per_buffer: BufferSize,
     pub sample_rate: SampleRate,
+    /// If the device doesn't run at an [`Exact`](SampleRate::Exact) sample rate, runs it at its
+    /// default rate, and resamples between that and the callback's rate instead of returning
+    /// [`Error::IncompatibleSampleRate`]. Only Portaudio streams resample. Off by default.
+    pub resample_if_needed: bool,
+    /// How resampled streams interpolate. [`Linear`](ResamplerQuality::Linear) by default.
+    pub resampler_quality: ResamplerQuality,
+    /// [`Exact`](ChannelMixPolicy::Exact) by default.
+    pub channel_mix_policy: ChannelMixPolicy,
+    /// Routes the stream's channels to the device's, instead of running the device with the
+    /// stream's channels. Only Portaudio callback streams route: Other streams return
+    /// [`Error::IncompatibleStreamMode`]. `None` by default.
+    pub channel_map: Option<ChannelMap>,
+    /// The speakers the stream's channels are for, in the order of their positions (see
+    /// [`ChannelMask`]). Must have the stream's channel count. Streams without a mask use the
+    /// backend's default layout. Only Portaudio callback streams and WASAPI streams lay out
+    /// channels: Other streams return [`Error::IncompatibleStreamMode`] for masks other than
+    /// [`ChannelMask::default_for`] the channel count. `None` by default.
+    pub channel_mask: Option<ChannelMask>,
+    /// Only Portaudio callback streams select channels: Other streams return
+    /// [`Error::IncompatibleStreamMode`] for anything but [`All`](ChannelSelection::All).
+    /// Streams can't have both a selection and a channel map or mask. `All` by default.
+    pub channels: ChannelSelection,
+    /// Whether the stream takes the device for itself, for the lowest latency and bit-exact
+    /// output: WASAPI streams, native or Portaudio's, open in exclusive mode, CoreAudio streams
+    /// hog the device, and ALSA streams open its hardware PCM (`hw:`, rather than `plughw:`, or
+    /// `default`) with no conversion or mixing. Other streams return
+    /// [`Error::IncompatibleStreamMode`]. `false` by default.
+    pub exclusive: bool,
+    /// The latency the stream asks for (see [`LatencyHint`]). [`High`](LatencyHint::High) by
+    /// default.
+    pub latency: LatencyHint,
+    /// Whether the thread that runs the callback is promoted to real-time priority (MMCSS's "Pro
+    /// Audio" task on Windows, a time-constraint policy on macOS, and `SCHED_FIFO` on Linux,
+    /// through RealtimeKit for processes that can't set it themselves), so that other work doesn't
+    /// delay it. Threads the system won't promote (e.g. for lack of
+    /// privileges) keep their priority. Only Portaudio, ALSA, PulseAudio, and WASAPI streams
+    /// promote their threads: Other backends run callbacks on the system's own audio threads,
+    /// which already are. `true` by default.
+    pub realtime_priority: bool,
+    /// Whether output streams call the callback to fill the device's buffers before they start,
+    /// rather than starting with silence, so that the callback's first frames play as soon as the
+    /// stream starts. Portaudio streams prime their buffers if asked, and ALSA streams always do:
+    /// Other streams start with silence. Blocking streams prime with
+    /// [`Stream::prime`](crate::Stream::prime) instead, and writers with
+    /// [`Device::open_primed_output_writer`](crate::Device::open_primed_output_writer). `false`
+    /// by default.
+    pub prime_output: bool,
+    /// How long a running stream's callback can go uncalled (e.g. because its device stalled, or
+    /// its driver hung) before a watchdog thread sends
+    /// [`CallbackError::StreamStalled`](crate::CallbackError::StreamStalled) to its
+    /// [error receiver](crate::Stream::take_error_receiver), so the application can reopen it.
+    /// Stalls are noticed within half again the timeout, and reported once each. Only Portaudio's
+    /// callback streams are watched: Other streams return [`Error::IncompatibleStreamMode`].
+    /// `None` by default.
+    pub watchdog: Option<Duration>,
+    /// Whether a running stream reopens, with the same callback, after its device disappears (see
+    /// [`CallbackError::DeviceLost`](crate::CallbackError::DeviceLost)), and on which device. Only
+    /// Portaudio's callback streams reconnect: Other streams return
+    /// [`Error::IncompatibleStreamMode`] for anything but [`ReconnectPolicy::None`].
+    /// [`ReconnectPolicy::None`] by default.
+    pub reconnect: ReconnectPolicy,
+    /// How WASAPI streams, native or through Portaudio, use WASAPI. Streams on other backends
+    /// ignore it. `None` by default, which is [`WasapiOptions::default`]'s settings for native
+    /// streams, and Portaudio's own for Portaudio's.
+    pub wasapi: Option<WasapiOptions>,
+    /// How CoreAudio streams use the device and AUHAL. Streams on other backends ignore it. `None`
+    /// by default, which is [`CoreAudioOptions::default`]'s settings.
+    pub coreaudio: Option<CoreAudioOptions>,
+    /// How ALSA streams, native or through Portaudio, size and schedule their buffers. Streams on
+    /// other backends ignore it. `None` by default, which is [`AlsaOptions::default`]'s settings.
+    pub alsa: Option<AlsaOptions>,
+    /// Scales the stream's output on its way to the device, e.g. to calibrate it, without touching
+    /// the callback. Gains must be finite and non-negative. Only Portaudio output callback streams
+    /// apply gains: Other streams return [`Error::IncompatibleStreamMode`] for anything but 1. 1
+    /// by default.
+    pub gain: f32,
+    /// A gain for each of the stream's channels, on top of [`gain`](StreamOptions::gain). Must
+    /// have the stream's channel count. Streams whose channels are routed or mixed onto the
+    /// device's can only have the same gain on every channel. `None` by default.
+    pub channel_gains: Option<Vec<f32>>,
+    /// How the stream rounds samples for devices with fewer bits. [`Tpdf`](DitherMode::Tpdf) by
+    /// default.
+    pub dither: DitherMode,
+    /// [`Clamp`](ClipPolicy::Clamp) by default.
+    pub clip_policy: ClipPolicy,
+    /// Called once the stream stops. Only Portaudio callback streams call it: Other streams return
+    /// [`Error::IncompatibleStreamMode`]. `None` by default.
+    pub on_finished: Option<FinishedCallback>,
 
-    pub callback: Callback<Frame>,
+    pub callback: Kind::Callback<Frame>,
 }
 
-// Default dummy callback that does nothing.
+impl<Frame, Kind: CallbackKind> StreamOptions<Frame, Kind> {
+    /// Makes sure the channel count is sensible, and that it matches the size of `Frame`.
+    ///
+    /// Backends that don't have their own way of validating frames use this. Streams that get
+    /// here with a channel map don't route channels, and fail with
+    /// [`Error::IncompatibleStreamMode`].
+    #[allow(dead_code)]
+    pub(crate) fn validate_frame_size(&self) -> Result<()> {
+        self.validate_layout(false)?;
+        self.validate_frame_size_with_mask()
+    }
+
+    /// Like [`validate_frame_size`](StreamOptions::validate_frame_size), for backends that lay out
+    /// channels with any mask.
+    #[allow(dead_code)]
+    pub(crate) fn validate_frame_size_with_mask(&self) -> Result<()> {
+        self.validate_layout(true)?;
+        if self.n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let expected = self.format.sample_size() * self.n_channels as usize;
+        let actual = std::mem::size_of::<Frame>();
+        if expected != actual {
+            return Err(Error::InvalidFrameSize { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Like [`validate_frame_size`](StreamOptions::validate_frame_size), for planar and dynamic
+    /// streams, whose `Frame` is a single sample.
+    #[allow(dead_code)]
+    pub(crate) fn validate_sample_size(&self) -> Result<()> {
+        self.validate_layout(false)?;
+        if self.n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let expected = self.format.sample_size();
+        let actual = std::mem::size_of::<Frame>();
+        if expected != actual {
+            return Err(Error::InvalidFrameSize { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// The gain of each of the stream's channels, or `None` at unity gain. Returns
+    /// [`Error::Invalid`] for gains that aren't finite and non-negative, and
+    /// [`Error::IncompatibleNChannels`] for channel gains without the stream's channel count.
+    pub(crate) fn channel_gains(&self) -> Result<Option<Vec<f32>>> {
+        let gains = match &self.channel_gains {
+            Some(gains) if gains.len() != self.n_channels.max(0) as usize => {
+                return Err(Error::IncompatibleNChannels);
+            }
+            Some(gains) => gains.iter().map(|gain| gain * self.gain).collect(),
+            None if self.gain == 1.0 => return Ok(None),
+            None => vec![self.gain; self.n_channels.max(0) as usize],
+        };
+        if gains.iter().any(|gain| !(gain.is_finite() && *gain >= 0.0)) {
+            return Err(Error::Invalid);
+        }
+        Ok(Some(gains))
+    }
+
+    /// Rejects channel maps and selections, finished callbacks, exclusive streams, gains,
+    /// watchdogs, reconnect policies, and masks that don't have the stream's channel count. Unless
+    /// `lays_out_channels`, also rejects masks other than the default one.
+    pub(crate) fn validate_layout(&self, lays_out_channels: bool) -> Result<()> {
+        if self.channel_map.is_some()
+            || self.channels != ChannelSelection::All
+            || self.on_finished.is_some()
+            || self.exclusive
+            || self.gain != 1.0
+            || self.channel_gains.is_some()
+            || self.watchdog.is_some()
+            || self.reconnect != ReconnectPolicy::None
+        {
+            return Err(Error::IncompatibleStreamMode);
+        }
+        if let Some(mask) = self.channel_mask {
+            if mask.n_channels() != self.n_channels {
+                return Err(Error::IncompatibleNChannels);
+            }
+            if !lays_out_channels && ChannelMask::default_for(self.n_channels) != Some(mask) {
+                return Err(Error::IncompatibleStreamMode);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Default dummy callbacks that do nothing.
 fn dummy_callback<T>(_: &mut [T]) {}
+fn dummy_input_callback<T>(_: &[T]) {}
 
-impl<Frame, Sample> Default for StreamOptions<Frame>
+impl<Frame, Sample, Kind> Default for StreamOptions<Frame, Kind>
 where
     Frame: 'static + sample::Frame<Sample = Sample> + HasDefaultNChannels,
     Sample: sample::Sample + HasDefaultFormat,
+    Kind: CallbackKind,
 {
-    fn default() -> StreamOptions<Frame> {
+    fn default() -> StreamOptions<Frame, Kind> {
         StreamOptions {
             format: Sample::FORMAT,
             n_channels: Frame::N_CHANNELS,
             sample_rate: SampleRate::default(),
-            frames_per_buffer: None,
+            frames_per_buffer: BufferSize::Default,
+            resample_if_needed: false,
+            resampler_quality: ResamplerQuality::default(),
+            channel_mix_policy: ChannelMixPolicy::default(),
+            channel_map: None,
+            channel_mask: None,
+            channels: ChannelSelection::default(),
+            exclusive: false,
+            latency: LatencyHint::default(),
+            realtime_priority: true,
+            prime_output: false,
+            watchdog: None,
+            reconnect: ReconnectPolicy::default(),
+            wasapi: None,
+            coreaudio: None,
+            alsa: None,
+            gain: 1.0,
+            channel_gains: None,
+            dither: DitherMode::default(),
+            clip_policy: ClipPolicy::default(),
+            on_finished: None,
+
+            callback: Kind::dummy_callback(),
+        }
+    }
+}
 
-            callback: Box::new(dummy_callback),
+/// The settings of [`StreamOptions`] that can be saved and loaded (with the `serde` feature),
+/// i.e. all but its callbacks, channel layouts, and channel gains. Options with a callback are
+/// made with struct update syntax:
+///
+/// ```
+/// # use audiohal::*;
+/// let config = StreamConfig {
+///     sample_rate: SampleRate::Exact(48000),
+///     ..StreamOptions::<[f32; 2]>::default().config()
+/// };
+/// let options = StreamOptions::<[f32; 2]> {
+///     callback: Box::new(|buffer| buffer.iter_mut().for_each(|frame| *frame = [0.0; 2])),
+///     ..config.into()
+/// };
+/// # options;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamConfig {
+    pub format: Format,
+    pub n_channels: i32,
+    pub frames_per_buffer: BufferSize,
+    pub sample_rate: SampleRate,
+    pub resample_if_needed: bool,
+    pub resampler_quality: ResamplerQuality,
+    pub channel_mix_policy: ChannelMixPolicy,
+    pub exclusive: bool,
+    pub latency: LatencyHint,
+    pub realtime_priority: bool,
+    pub prime_output: bool,
+    pub watchdog: Option<Duration>,
+    pub reconnect: ReconnectPolicy,
+    pub wasapi: Option<WasapiOptions>,
+    pub coreaudio: Option<CoreAudioOptions>,
+    pub alsa: Option<AlsaOptions>,
+    pub gain: f32,
+    pub dither: DitherMode,
+    pub clip_policy: ClipPolicy,
+}
+
+impl<Frame, Kind: CallbackKind> StreamOptions<Frame, Kind> {
+    /// The options' [`StreamConfig`].
+    pub fn config(&self) -> StreamConfig {
+        StreamConfig {
+            format: self.format,
+            n_channels: self.n_channels,
+            frames_per_buffer: self.frames_per_buffer,
+            sample_rate: self.sample_rate,
+            resample_if_needed: self.resample_if_needed,
+            resampler_quality: self.resampler_quality,
+            channel_mix_policy: self.channel_mix_policy,
+            exclusive: self.exclusive,
+            latency: self.latency,
+            realtime_priority: self.realtime_priority,
+            prime_output: self.prime_output,
+            watchdog: self.watchdog,
+            reconnect: self.reconnect,
+            wasapi: self.wasapi,
+            coreaudio: self.coreaudio,
+            alsa: self.alsa,
+            gain: self.gain,
+            dither: self.dither,
+            clip_policy: self.clip_policy,
+        }
+    }
+}
+
+impl<Frame, Kind: CallbackKind> StreamOptions<Frame, Kind> {
+    /// The same options, with `callback` (of another kind) instead of the options' own.
+    pub(crate) fn with_callback<NewKind: CallbackKind>(
+        self,
+        callback: NewKind::Callback<Frame>,
+    ) -> StreamOptions<Frame, NewKind> {
+        StreamOptions {
+            format: self.format,
+            n_channels: self.n_channels,
+            frames_per_buffer: self.frames_per_buffer,
+            sample_rate: self.sample_rate,
+            resample_if_needed: self.resample_if_needed,
+            resampler_quality: self.resampler_quality,
+            channel_mix_policy: self.channel_mix_policy,
+            channel_map: self.channel_map,
+            channel_mask: self.channel_mask,
+            channels: self.channels,
+            exclusive: self.exclusive,
+            latency: self.latency,
+            realtime_priority: self.realtime_priority,
+            prime_output: self.prime_output,
+            watchdog: self.watchdog,
+            reconnect: self.reconnect,
+            wasapi: self.wasapi,
+            coreaudio: self.coreaudio,
+            alsa: self.alsa,
+            gain: self.gain,
+            channel_gains: self.channel_gains,
+            dither: self.dither,
+            clip_policy: self.clip_policy,
+            on_finished: self.on_finished,
+            callback,
+        }
+    }
+}
+
+impl StreamConfig {
+    /// Options with the config's settings, the default channel layout, and `callback`, for frames
+    /// that have no default options: e.g. samples. Configs have no channel maps, masks, per-channel
+    /// gains, or finished callbacks: Options with them keep them with
+    /// [`StreamOptions::with_callback`].
+    pub(crate) fn with_callback<Frame, Kind: CallbackKind>(
+        self,
+        callback: Kind::Callback<Frame>,
+    ) -> StreamOptions<Frame, Kind> {
+        StreamOptions {
+            format: self.format,
+            n_channels: self.n_channels,
+            frames_per_buffer: self.frames_per_buffer,
+            sample_rate: self.sample_rate,
+            resample_if_needed: self.resample_if_needed,
+            resampler_quality: self.resampler_quality,
+            channel_mix_policy: self.channel_mix_policy,
+            channel_map: None,
+            channel_mask: None,
+            channels: ChannelSelection::default(),
+            exclusive: self.exclusive,
+            latency: self.latency,
+            realtime_priority: self.realtime_priority,
+            prime_output: self.prime_output,
+            watchdog: self.watchdog,
+            reconnect: self.reconnect,
+            wasapi: self.wasapi,
+            coreaudio: self.coreaudio,
+            alsa: self.alsa,
+            gain: self.gain,
+            channel_gains: None,
+            dither: self.dither,
+            clip_policy: self.clip_policy,
+            on_finished: None,
+            callback,
         }
     }
 }
 
+/// Default options, with the config's settings.
+impl<Frame, Sample, Kind> From<StreamConfig> for StreamOptions<Frame, Kind>
+where
+    Frame: 'static + sample::Frame<Sample = Sample> + HasDefaultNChannels,
+    Sample: sample::Sample + HasDefaultFormat,
+    Kind: CallbackKind,
+{
+    fn from(config: StreamConfig) -> StreamOptions<Frame, Kind> {
+        config.with_callback(Kind::dummy_callback())
+    }
+}
+
 /// This trait is implemented for primitive types that have a direct [`Format`] equivalent.
 pub trait HasDefaultFormat {
     const FORMAT: Format;
 }
 
+impl HasDefaultFormat for f64 {
+    const FORMAT: Format = Format::F64;
+}
 impl HasDefaultFormat for f32 {
     const FORMAT: Format = Format::F32;
 }
 impl HasDefaultFormat for i16 {
     const FORMAT: Format = Format::I16;
 }
+impl HasDefaultFormat for u32 {
+    const FORMAT: Format = Format::U32;
+}
+impl HasDefaultFormat for u16 {
+    const FORMAT: Format = Format::U16;
+}
 
 /// This trait is implemented for array primitive types (e.g. array [`sample::Frame`](frames)).
 pub trait HasDefaultNChannels {
     const N_CHANNELS: i32;
 }
 
-impl<T> HasDefaultNChannels for [T; 1] {
-    const N_CHANNELS: i32 = 1;
-}
-impl<T> HasDefaultNChannels for [T; 2] {
-    const N_CHANNELS: i32 = 2;
+impl<T, const N: usize> HasDefaultNChannels for [T; N] {
+    const N_CHANNELS: i32 = N as i32;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::surround::ChannelPosition;
 
     #[test]
     fn correct_default_n_channels() {
         assert_eq!(StreamOptions::<[f32; 1]>::default().n_channels, 1);
         assert_eq!(StreamOptions::<[f32; 2]>::default().n_channels, 2);
+        assert_eq!(StreamOptions::<[f32; 2], Input>::default().n_channels, 2);
+        assert_eq!(StreamOptions::<[f32; 6]>::default().n_channels, 6);
+        assert_eq!(StreamOptions::<[f32; 8]>::default().n_channels, 8);
+        assert_eq!(StreamOptions::<[i16; 16]>::default().n_channels, 16);
+    }
+
+    #[test]
+    fn correlates_clocks() {
+        let start = Instant::now();
+        let reference = (Duration::from_secs(5), start);
+        let later = start + Duration::from_secs(100);
+        // The stream gained 1ms in 100s.
+        let correlation =
+            ClockCorrelation::new(Duration::from_millis(105_001), later, Some(reference));
+        assert_lt!((correlation.drift - 1e-5).abs(), 1e-9);
+        let in_a_while = later + Duration::from_secs(10);
+        let stream_time = correlation.to_stream_time(in_a_while);
+        assert_eq!(stream_time.as_micros(), 115_001_100);
+        let instant = correlation.to_instant(stream_time);
+        assert_lt!(
+            (instant.max(in_a_while) - instant.min(in_a_while)),
+            Duration::from_micros(1)
+        );
+        assert_eq!(
+            correlation.to_stream_time(start - Duration::from_secs(200)),
+            Duration::default()
+        );
+        assert_eq!(
+            ClockCorrelation::new(Duration::from_secs(5), start, None).drift,
+            0.0
+        );
+    }
+
+    #[test]
+    fn correct_default_format() {
+        assert_eq!(StreamOptions::<[f64; 2]>::default().format, Format::F64);
+        assert_eq!(StreamOptions::<[u16; 1]>::default().format, Format::U16);
+        assert_eq!(StreamOptions::<[u32; 1]>::default().format, Format::U32);
+        assert_eq!(
+            StreamOptions::<[f64; 2]>::default().validate_frame_size(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validates_sample_size() {
+        let options = StreamOptions::<f32, PlanarOutput> {
+            format: Format::F32,
+            n_channels: 2,
+            frames_per_buffer: BufferSize::Default,
+            sample_rate: SampleRate::DeviceDefault,
+            resample_if_needed: false,
+            resampler_quality: ResamplerQuality::Linear,
+            channel_mix_policy: ChannelMixPolicy::Exact,
+            channel_map: None,
+            channel_mask: None,
+            channels: ChannelSelection::All,
+            exclusive: false,
+            latency: LatencyHint::High,
+            realtime_priority: true,
+            prime_output: false,
+            watchdog: None,
+            reconnect: ReconnectPolicy::None,
+            wasapi: None,
+            coreaudio: None,
+            alsa: None,
+            gain: 1.0,
+            channel_gains: None,
+            dither: DitherMode::Tpdf,
+            clip_policy: ClipPolicy::Clamp,
+            on_finished: None,
+            callback: PlanarOutput::dummy_callback(),
+        };
+        assert_eq!(options.validate_sample_size(), Ok(()));
+        assert_eq!(
+            StreamOptions {
+                format: Format::I16,
+                ..options
+            }
+            .validate_sample_size(),
+            Err(Error::InvalidFrameSize {
+                expected: 2,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn builds_channel_matrices() {
+        let map = ChannelMap::new(4).route(0, 2).route_with_gain(1, 3, 0.5);
+        assert_eq!(
+            map.matrix(2, true),
+            Ok(vec![
+                vec![0.0, 0.0],
+                vec![0.0, 0.0],
+                vec![1.0, 0.0],
+                vec![0.0, 0.5]
+            ])
+        );
+        assert_eq!(
+            map.matrix(2, false),
+            Ok(vec![vec![0.0, 0.0, 1.0, 0.0], vec![0.0, 0.0, 0.0, 0.5]])
+        );
+        // Both stereo channels on the device's first.
+        let map = ChannelMap::new(2).route(0, 0).route(1, 0);
+        assert_eq!(
+            map.matrix(2, true),
+            Ok(vec![vec![1.0, 1.0], vec![0.0, 0.0]])
+        );
+        assert_eq!(
+            ChannelMap::new(2).route(2, 0).matrix(2, true),
+            Err(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            ChannelMap::new(2).route(0, 2).matrix(2, true),
+            Err(Error::IncompatibleNChannels)
+        );
+    }
+
+    #[test]
+    fn validates_frame_size() {
+        assert_eq!(
+            StreamOptions::<[f32; 2]>::default().validate_frame_size(),
+            Ok(())
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                n_channels: 3,
+                ..Default::default()
+            }
+            .validate_frame_size(),
+            Err(Error::InvalidFrameSize {
+                expected: 12,
+                actual: 8
+            })
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                n_channels: 0,
+                ..Default::default()
+            }
+            .validate_frame_size(),
+            Err(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                channel_map: Some(ChannelMap::new(2)),
+                ..Default::default()
+            }
+            .validate_frame_size(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                on_finished: Some(Box::new(|| {})),
+                ..Default::default()
+            }
+            .validate_frame_size(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                exclusive: true,
+                ..Default::default()
+            }
+            .validate_frame_size(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                watchdog: Some(Duration::from_secs(1)),
+                ..Default::default()
+            }
+            .validate_frame_size_with_mask(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                reconnect: ReconnectPolicy::RetrySameDevice,
+                ..Default::default()
+            }
+            .validate_frame_size_with_mask(),
+            Err(Error::IncompatibleStreamMode)
+        );
+    }
+
+    #[test]
+    fn negotiates_buffer_sizes() {
+        assert_eq!(BufferSize::Default.frames(), None);
+        assert_eq!(BufferSize::Exact(300).frames(), Some(300));
+        assert_eq!(BufferSize::PowerOfTwoNear(300).frames(), Some(256));
+        assert_eq!(BufferSize::PowerOfTwoNear(400).frames(), Some(512));
+        assert_eq!(BufferSize::PowerOfTwoNear(384).frames(), Some(256));
+        assert_eq!(BufferSize::PowerOfTwoNear(i32::MAX).frames(), Some(1 << 30));
+        assert_eq!(BufferSize::Range { min: 100, max: 200 }.frames(), Some(128));
+        assert_eq!(BufferSize::Range { min: 129, max: 200 }.frames(), Some(129));
+        // Invalid sizes are left for streams to reject.
+        assert_eq!(BufferSize::Range { min: 200, max: 100 }.frames(), Some(0));
+        assert_eq!(BufferSize::PowerOfTwoNear(-4).frames(), Some(-4));
+
+        // Devices' own sizes win, unless streams ask for an exact one.
+        assert_eq!(BufferSize::Default.frames_preferring(480), Some(480));
+        assert_eq!(
+            BufferSize::PowerOfTwoNear(256).frames_preferring(480),
+            Some(480)
+        );
+        assert_eq!(BufferSize::Exact(256).frames_preferring(480), Some(256));
+        let range = BufferSize::Range { min: 256, max: 512 };
+        assert_eq!(range.frames_preferring(480), Some(480));
+        assert_eq!(range.frames_preferring(1024), Some(256));
+        assert!(!range.accepts(1024));
+        assert!(!BufferSize::Range { min: 0, max: 512 }.accepts(256));
+    }
+
+    #[test]
+    fn bounds_buffer_sizes() {
+        assert!(StreamInfo::fixed(256).is_fixed_size());
+        assert!(!StreamInfo::at_most(256).is_fixed_size());
+        assert_eq!(StreamInfo::at_most(256).min_frames_per_buffer, None);
+        assert!(!StreamInfo::default().is_fixed_size());
+    }
+
+    #[test]
+    fn converts_to_and_from_configs() {
+        let config = StreamConfig {
+            frames_per_buffer: BufferSize::Exact(256),
+            sample_rate: SampleRate::Exact(48000),
+            wasapi: Some(WasapiOptions {
+                event_driven: false,
+                ..Default::default()
+            }),
+            ..StreamOptions::<[i16; 2]>::default().config()
+        };
+        assert_eq!(config.format, Format::I16);
+        assert_eq!(config.n_channels, 2);
+        let options: StreamOptions<[i16; 2]> = config.into();
+        assert_eq!(options.config(), config);
+    }
+
+    #[test]
+    fn keeps_options_with_other_callbacks() {
+        let options = StreamOptions::<[f32; 2], NoCallback> {
+            channel_mask: ChannelMask::default_for(2),
+            channel_gains: Some(vec![0.5, 1.0]),
+            on_finished: Some(Box::new(|| {})),
+            ..Default::default()
+        };
+        let config = options.config();
+        let options = options.with_callback::<Output>(Box::new(|_| {}));
+        assert_eq!(options.config(), config);
+        assert_eq!(options.channel_mask, ChannelMask::default_for(2));
+        assert_eq!(options.channel_gains, Some(vec![0.5, 1.0]));
+        assert!(options.on_finished.is_some());
+    }
+
+    #[test]
+    fn combines_gains() {
+        let with_gains = |gain, channel_gains| StreamOptions::<[f32; 2]> {
+            gain,
+            channel_gains,
+            ..Default::default()
+        };
+        assert_eq!(with_gains(1.0, None).channel_gains(), Ok(None));
+        assert_eq!(
+            with_gains(0.5, None).channel_gains(),
+            Ok(Some(vec![0.5, 0.5]))
+        );
+        assert_eq!(
+            with_gains(0.5, Some(vec![1.0, 2.0])).channel_gains(),
+            Ok(Some(vec![0.5, 1.0]))
+        );
+        assert_eq!(
+            with_gains(1.0, Some(vec![1.0])).channel_gains(),
+            Err(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            with_gains(f32::NAN, None).channel_gains(),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            with_gains(0.5, None).validate_frame_size(),
+            Err(Error::IncompatibleStreamMode)
+        );
+    }
+
+    #[test]
+    fn validates_channel_masks() {
+        let with_mask = |mask| StreamOptions::<[f32; 2]> {
+            channel_mask: Some(mask),
+            ..Default::default()
+        };
+        assert_eq!(with_mask(ChannelMask::STEREO).validate_frame_size(), Ok(()));
+        let side =
+            ChannelMask::from_positions(&[ChannelPosition::SideLeft, ChannelPosition::SideRight]);
+        assert_eq!(
+            with_mask(side).validate_frame_size(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(with_mask(side).validate_frame_size_with_mask(), Ok(()));
+        assert_eq!(
+            StreamOptions::<[f32; 2]> {
+                channels: ChannelSelection::Range(5..7),
+                ..Default::default()
+            }
+            .validate_frame_size_with_mask(),
+            Err(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            with_mask(ChannelMask::SURROUND_5_1).validate_frame_size_with_mask(),
+            Err(Error::IncompatibleNChannels)
+        );
     }
 }
@@ -0,0 +1,242 @@
+//! Speaker positions, and surround (5.1 and 7.1) channel orders. Streams take surround frames in
+//! the WAVE order (front left, front right, center, LFE, back left, back right, then side left and
+//! side right for 7.1), which most backends use. The ones that don't are reordered.
+use crate::backend::Backend;
+
+/// The speaker a channel is played on (or captured for). In the order of their WAVE channel mask
+/// bits, which is the order their channels are in when a [`ChannelMask`] has several.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPosition {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    /// The subwoofer.
+    Lfe,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    TopCenter,
+    TopFrontLeft,
+    TopFrontCenter,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackCenter,
+    TopBackRight,
+}
+
+impl ChannelPosition {
+    const ALL: [ChannelPosition; 18] = [
+        ChannelPosition::FrontLeft,
+        ChannelPosition::FrontRight,
+        ChannelPosition::FrontCenter,
+        ChannelPosition::Lfe,
+        ChannelPosition::BackLeft,
+        ChannelPosition::BackRight,
+        ChannelPosition::FrontLeftOfCenter,
+        ChannelPosition::FrontRightOfCenter,
+        ChannelPosition::BackCenter,
+        ChannelPosition::SideLeft,
+        ChannelPosition::SideRight,
+        ChannelPosition::TopCenter,
+        ChannelPosition::TopFrontLeft,
+        ChannelPosition::TopFrontCenter,
+        ChannelPosition::TopFrontRight,
+        ChannelPosition::TopBackLeft,
+        ChannelPosition::TopBackCenter,
+        ChannelPosition::TopBackRight,
+    ];
+
+    /// The position's WAVE channel mask bit (e.g. `SPEAKER_FRONT_LEFT`).
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// A set of speaker positions, one per channel, as a WAVE channel mask (`dwChannelMask`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMask(u32);
+
+impl ChannelMask {
+    pub const MONO: ChannelMask = ChannelMask(0x4);
+    pub const STEREO: ChannelMask = ChannelMask(0x3);
+    /// Front left, front right, back left and back right.
+    pub const QUAD: ChannelMask = ChannelMask(0x33);
+    /// Front left, front right, center, LFE, back left and back right.
+    pub const SURROUND_5_1: ChannelMask = ChannelMask(0x3f);
+    /// [`SURROUND_5_1`](ChannelMask::SURROUND_5_1), with side left and side right.
+    pub const SURROUND_7_1: ChannelMask = ChannelMask(0x63f);
+
+    /// A mask of WAVE `SPEAKER_*` bits. Bits past the last [`ChannelPosition`] are ignored.
+    pub fn from_bits(bits: u32) -> ChannelMask {
+        ChannelMask(bits & ((1 << ChannelPosition::ALL.len()) - 1))
+    }
+
+    pub fn from_positions(positions: &[ChannelPosition]) -> ChannelMask {
+        ChannelMask(
+            positions
+                .iter()
+                .fold(0, |bits, position| bits | position.bit()),
+        )
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// The mask's positions, in the order of their channels.
+    pub fn positions(self) -> Vec<ChannelPosition> {
+        ChannelPosition::ALL
+            .iter()
+            .copied()
+            .filter(|&position| self.contains(position))
+            .collect()
+    }
+
+    pub fn contains(self, position: ChannelPosition) -> bool {
+        self.0 & position.bit() != 0
+    }
+
+    /// The number of channels with the mask.
+    pub fn n_channels(self) -> i32 {
+        self.0.count_ones() as i32
+    }
+
+    /// The usual layout for `n_channels` channels, if there is one (e.g. 5.1 for 6 channels).
+    pub fn default_for(n_channels: i32) -> Option<ChannelMask> {
+        match n_channels {
+            1 => Some(ChannelMask::MONO),
+            2 => Some(ChannelMask::STEREO),
+            // Stereo and center.
+            3 => Some(ChannelMask(0x7)),
+            4 => Some(ChannelMask::QUAD),
+            // 5.1 without the LFE.
+            5 => Some(ChannelMask(0x37)),
+            6 => Some(ChannelMask::SURROUND_5_1),
+            // 5.1 and back center.
+            7 => Some(ChannelMask(0x13f)),
+            8 => Some(ChannelMask::SURROUND_7_1),
+            _ => None,
+        }
+    }
+}
+
+/// The positions of the channels of a device that runs `n_channels` channels with its backend's
+/// default layout, in the device's channel order.
+#[cfg_attr(
+    not(all(
+        feature = "portaudio",
+        not(any(target_os = "android", target_arch = "wasm32"))
+    )),
+    allow(dead_code)
+)]
+pub fn device_positions(backend: Backend, n_channels: i32) -> Option<Vec<ChannelPosition>> {
+    let positions = ChannelMask::default_for(n_channels)?.positions();
+    Some(match device_order(backend, n_channels as usize) {
+        Some(order) => {
+            let mut device_positions = positions.clone();
+            for (&position, &channel) in positions.iter().zip(order) {
+                device_positions[channel] = position;
+            }
+            device_positions
+        }
+        None => positions,
+    })
+}
+
+/// ALSA puts the back channels before the center and LFE.
+const ALSA_5_1: [usize; 6] = [0, 1, 4, 5, 2, 3];
+const ALSA_7_1: [usize; 8] = [0, 1, 4, 5, 2, 3, 6, 7];
+
+/// Where each of a WAVE-ordered frame's channels goes on `backend`'s devices, if their order is
+/// another one.
+pub fn device_order(backend: Backend, n_channels: usize) -> Option<&'static [usize]> {
+    match (backend, n_channels) {
+        (Backend::Alsa, 6) => Some(&ALSA_5_1),
+        (Backend::Alsa, 8) => Some(&ALSA_7_1),
+        _ => None,
+    }
+}
+
+/// Moves the channels of WAVE-ordered `frames` to where `order` says.
+pub fn reorder<S: Copy, const N: usize>(frames: &mut [[S; N]], order: &[usize]) {
+    for frame in frames {
+        let wave = *frame;
+        for (&sample, &channel) in wave.iter().zip(order) {
+            frame[channel] = sample;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_mask_positions() {
+        use ChannelPosition::*;
+        assert_eq!(ChannelMask::STEREO.positions(), vec![FrontLeft, FrontRight]);
+        assert_eq!(
+            ChannelMask::from_positions(&[SideRight, FrontCenter, SideLeft]).positions(),
+            vec![FrontCenter, SideLeft, SideRight]
+        );
+        assert_eq!(
+            ChannelMask::from_positions(&SURROUND_7_1),
+            ChannelMask::SURROUND_7_1
+        );
+        for n_channels in 1..=8 {
+            let mask = ChannelMask::default_for(n_channels).unwrap();
+            assert_eq!(mask.n_channels(), n_channels);
+        }
+        assert_eq!(ChannelMask::default_for(16), None);
+        assert_eq!(ChannelMask::from_bits(0xffff_ffff).n_channels(), 18);
+    }
+
+    #[test]
+    fn lays_out_alsa_devices() {
+        use ChannelPosition::*;
+        assert_eq!(
+            device_positions(Backend::Alsa, 6),
+            Some(vec![
+                FrontLeft,
+                FrontRight,
+                BackLeft,
+                BackRight,
+                FrontCenter,
+                Lfe
+            ])
+        );
+        assert_eq!(
+            device_positions(Backend::Wasapi, 6),
+            Some(ChannelMask::SURROUND_5_1.positions())
+        );
+    }
+
+    const SURROUND_7_1: [ChannelPosition; 8] = [
+        ChannelPosition::FrontLeft,
+        ChannelPosition::FrontRight,
+        ChannelPosition::FrontCenter,
+        ChannelPosition::Lfe,
+        ChannelPosition::BackLeft,
+        ChannelPosition::BackRight,
+        ChannelPosition::SideLeft,
+        ChannelPosition::SideRight,
+    ];
+
+    #[test]
+    fn reorders_alsa_frames() {
+        assert_eq!(device_order(Backend::Wasapi, 6), None);
+        assert_eq!(device_order(Backend::Alsa, 2), None);
+        let mut frames = [[0, 1, 2, 3, 4, 5]];
+        reorder(&mut frames, device_order(Backend::Alsa, 6).unwrap());
+        // Front left, front right, back left, back right, center, LFE.
+        assert_eq!(frames, [[0, 1, 4, 5, 2, 3]]);
+        let mut frames = [[0, 1, 2, 3, 4, 5, 6, 7]];
+        reorder(&mut frames, device_order(Backend::Alsa, 8).unwrap());
+        assert_eq!(frames, [[0, 1, 4, 5, 2, 3, 6, 7]]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Playback of [symphonia](https://docs.rs/symphonia)'s decoded tracks, e.g. for players that
+//! read their files with symphonia. See [`Device::play_track`].
+//!
+//! Decoding takes too long, and too unpredictably, for the stream's callback. Packets are
+//! decoded on a worker thread instead, ahead of the callback, which only takes decoded frames
+//! from a [ring buffer](crate::ring).
+use ::symphonia::core::audio::SampleBuffer;
+use ::symphonia::core::codecs::Decoder;
+use ::symphonia::core::errors::Error as DecodeError;
+use ::symphonia::core::formats::FormatReader;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::ring::{Producer, RingBuffer};
+use crate::stream_options::{ChannelMixPolicy, SampleRate, StreamOptions};
+use crate::{Device, Stream};
+
+/// A track playing on an output stream, decoded on a worker thread. Dropping the player stops
+/// both.
+pub struct Player<Frame> {
+    /// Set to stop the worker.
+    stop: Arc<AtomicBool>,
+    /// Set by the worker once it decoded the last packet.
+    decoded: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    _stream: Stream<Frame>,
+}
+
+impl<Frame> Player<Frame> {
+    /// Whether the whole track was decoded (or failed to decode). Buffered frames may still be
+    /// playing.
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.load(Ordering::Acquire)
+    }
+}
+
+impl<Frame> Drop for Player<Frame> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+impl Device {
+    /// Opens and starts an output stream that plays the track `track_id` of `format`, decoded by
+    /// `decoder`, and silence after it. Up to `capacity` decoded frames are buffered ahead of the
+    /// stream.
+    ///
+    /// The stream runs at the track's sample rate, resampled to the device's if it doesn't
+    /// support it, and is mixed to the device's channels. `N` must be the track's channel count,
+    /// or this returns [`Error::IncompatibleNChannels`]. Packets that fail to decode are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use audiohal::{Host, Result};
+    /// use symphonia::core::{codecs::DecoderOptions, io::MediaSourceStream, probe::Hint};
+    /// let file = std::fs::File::open("song.flac").unwrap();
+    /// let probed = symphonia::default::get_probe()
+    ///     .format(
+    ///         &Hint::new(),
+    ///         MediaSourceStream::new(Box::new(file), Default::default()),
+    ///         &Default::default(),
+    ///         &Default::default(),
+    ///     )
+    ///     .unwrap();
+    /// let track = probed.format.default_track().unwrap();
+    /// let decoder = symphonia::default::get_codecs()
+    ///     .make(&track.codec_params, &DecoderOptions::default())
+    ///     .unwrap();
+    /// let track_id = track.id;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let player = device.play_track::<2>(probed.format, decoder, track_id, 48_000)?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn play_track<const N: usize>(
+        &mut self,
+        format: Box<dyn FormatReader>,
+        decoder: Box<dyn Decoder>,
+        track_id: u32,
+        capacity: usize,
+    ) -> Result<Player<[f32; N]>>
+    where
+        [f32; N]: sample::Frame<Sample = f32>,
+    {
+        let params = decoder.codec_params();
+        if params.channels.map(|channels| channels.count()) != Some(N) {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let sample_rate = params.sample_rate.ok_or(Error::IncompatibleSampleRate)?;
+        let (producer, mut consumer) = RingBuffer::new(capacity).split();
+        let mut stream = self.open_outstream(StreamOptions {
+            sample_rate: SampleRate::Exact(
+                i32::try_from(sample_rate).map_err(|_| Error::IncompatibleSampleRate)?,
+            ),
+            resample_if_needed: true,
+            channel_mix_policy: ChannelMixPolicy::Mix,
+            callback: Box::new(move |buffer: &mut [[f32; N]]| {
+                let count = consumer.pop_slice(buffer);
+                for frame in &mut buffer[count..] {
+                    *frame = [0.0; N];
+                }
+            }),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let decoded = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            format,
+            decoder,
+            track_id,
+            producer,
+            stop: Arc::clone(&stop),
+            // Sleeps for about a quarter of the buffer whenever it's full.
+            wait: Duration::from_secs_f64(capacity as f64 / f64::from(sample_rate) / 4.0),
+        };
+        let worker_decoded = Arc::clone(&decoded);
+        let worker = std::thread::Builder::new()
+            .name("audiohal-decoder".into())
+            .spawn(move || {
+                worker.run();
+                worker_decoded.store(true, Ordering::Release);
+            })
+            .map_err(|error| {
+                Error::from_source(None, "Could not spawn the decoder thread.", error)
+            })?;
+        Ok(Player {
+            stop,
+            decoded,
+            worker: Some(worker),
+            _stream: stream,
+        })
+    }
+}
+
+/// Decodes packets into the stream's ring buffer.
+struct Worker<const N: usize> {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    producer: Producer<[f32; N]>,
+    stop: Arc<AtomicBool>,
+    wait: Duration,
+}
+
+impl<const N: usize> Worker<N> {
+    /// Decodes until the track ends, fails, or the player is dropped.
+    fn run(mut self) {
+        let mut samples = None;
+        let mut frames = Vec::new();
+        while !self.stop.load(Ordering::Acquire) {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => return,
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(DecodeError::DecodeError(_)) => continue,
+                Err(_) => return,
+            };
+            let samples = samples.get_or_insert_with(|| {
+                SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec())
+            });
+            if samples.capacity() < decoded.capacity() * N {
+                *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+            }
+            samples.copy_interleaved_ref(decoded);
+            frames.clear();
+            frames.extend(samples.samples().chunks_exact(N).map(|chunk| {
+                let mut frame = [0.0; N];
+                frame.copy_from_slice(chunk);
+                frame
+            }));
+            self.push(&frames);
+        }
+    }
+
+    /// Pushes all of `frames`, waiting for the stream to make room.
+    fn push(&mut self, frames: &[[f32; N]]) {
+        let mut pushed = 0;
+        while pushed < frames.len() && !self.stop.load(Ordering::Acquire) {
+            pushed += self.producer.push_slice(&frames[pushed..]);
+            if pushed < frames.len() {
+                std::thread::sleep(self.wait);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Host};
+    use ::symphonia::core::codecs::DecoderOptions;
+    use ::symphonia::core::io::MediaSourceStream;
+    use ::symphonia::default::formats::WavReader;
+
+    /// A 16-bit WAV file of `n_channels` channels at 48kHz.
+    fn wav(n_channels: u16, samples: &[i16]) -> Vec<u8> {
+        let data_len = (samples.len() * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend(&(36 + data_len).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(&16u32.to_le_bytes());
+        wav.extend(&1u16.to_le_bytes());
+        wav.extend(&n_channels.to_le_bytes());
+        wav.extend(&48000u32.to_le_bytes());
+        wav.extend(&(48000 * 2 * u32::from(n_channels)).to_le_bytes());
+        wav.extend(&(2 * n_channels).to_le_bytes());
+        wav.extend(&16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(&data_len.to_le_bytes());
+        samples
+            .iter()
+            .for_each(|sample| wav.extend(&sample.to_le_bytes()));
+        wav
+    }
+
+    fn reader(wav: Vec<u8>) -> (Box<dyn FormatReader>, Box<dyn Decoder>, u32) {
+        let source =
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(wav)), Default::default());
+        let format = WavReader::try_new(source, &Default::default()).unwrap();
+        let track = format.default_track().unwrap();
+        let decoder = ::symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .unwrap();
+        let track_id = track.id;
+        (Box::new(format), decoder, track_id)
+    }
+
+    #[test]
+    fn plays_tracks() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let (format, decoder, track_id) = reader(wav(2, &[1000; 2 * 4800]));
+        let player = device.play_track::<2>(format, decoder, track_id, 1024)?;
+        // The dummy device plays a tenth of a second in well under a second.
+        for _ in 0..100 {
+            if player.is_decoded() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("The track wasn't decoded.");
+    }
+
+    #[test]
+    fn rejects_other_channel_counts() -> Result<()> {
+        let mut device = Host::with_backend(Backend::Dummy)?.default_output_device()?;
+        let (format, decoder, track_id) = reader(wav(1, &[0; 16]));
+        assert_eq!(
+            device
+                .play_track::<2>(format, decoder, track_id, 1024)
+                .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+}
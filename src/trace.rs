@@ -0,0 +1,114 @@
+//! The spans and events that the root [`Host`]/[`Device`]/[`Stream`] emit through `tracing`, for
+//! debugging what a stream negotiated (e.g. when there's no sound). Without the `tracing`
+//! feature, they compile to nothing.
+//!
+//! Hosts and devices are traced as they're created, streams from the span of their opening to
+//! their close, and every error on the way as a warning.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::facade::{Device, Host, Stream};
+use crate::stream_options::{CallbackKind, StreamOptions};
+
+/// A span, entered until dropped.
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    _entered: tracing::span::EnteredSpan,
+}
+
+/// The span of opening a `kind` stream (e.g. "output") on `device`, with the settings it asked
+/// for. The stream's [`opened`] event is in it.
+pub(crate) fn open_span<Frame, Kind: CallbackKind>(
+    device: &str,
+    kind: &'static str,
+    options: &StreamOptions<Frame, Kind>,
+) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        Span {
+            _entered: tracing::debug_span!(
+                "open_stream",
+                device,
+                kind,
+                format = ?options.format,
+                n_channels = options.n_channels,
+                sample_rate = ?options.sample_rate,
+                frames_per_buffer = ?options.frames_per_buffer,
+                exclusive = options.exclusive,
+            )
+            .entered(),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        Span {}
+    }
+}
+
+/// Traces a stream's opening, with the latencies it negotiated.
+pub(crate) fn opened<Frame>(result: Result<Stream<Frame>>) -> Result<Stream<Frame>> {
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(stream) => tracing::info!(
+            input_latency = ?stream.input_latency(),
+            output_latency = ?stream.output_latency(),
+            "opened stream"
+        ),
+        Err(error) => tracing::warn!(%error, "failed to open stream"),
+    }
+    result
+}
+
+/// Traces a stream's start, stop, pause, etc.
+pub(crate) fn transition(event: &'static str, result: Result<()>) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(()) => tracing::debug!(event, "stream transitioned"),
+        Err(error) => tracing::warn!(event, %error, "stream failed to transition"),
+    }
+    result
+}
+
+pub(crate) fn closed() {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("closed stream");
+}
+
+/// Traces creating a host with `backend`, or with the default backend if it's `None`.
+pub(crate) fn host(backend: Option<Backend>, result: Result<Host>) -> Result<Host> {
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(host) => tracing::info!(
+            requested = ?backend,
+            backend = ?host.backend(),
+            name = host.name(),
+            "created host"
+        ),
+        Err(error) => tracing::warn!(requested = ?backend, %error, "failed to create host"),
+    }
+    result
+}
+
+/// Traces creating a `kind` device (e.g. "default output").
+pub(crate) fn device(kind: &'static str, result: Result<Device>) -> Result<Device> {
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(device) => tracing::debug!(kind, name = device.name(), "found device"),
+        Err(error) => tracing::warn!(kind, %error, "failed to find device"),
+    }
+    result
+}
+
+/// Traces enumerating a host's devices.
+pub(crate) fn devices(result: Result<Vec<Device>>) -> Result<Vec<Device>> {
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(devices) => tracing::debug!(
+            n_devices = devices.len(),
+            names = ?devices.iter().map(Device::name).collect::<Vec<_>>(),
+            "enumerated devices"
+        ),
+        Err(error) => tracing::warn!(%error, "failed to enumerate devices"),
+    }
+    result
+}
@@ -0,0 +1,130 @@
+//! The interface all backends implement.
+//!
+//! Every backend module (e.g. [`null`](crate::null)) exposes concrete `Host`, `Device`, and
+//! `Stream` types implementing these traits. Code generic over them works with any backend,
+//! including ones implemented outside of this crate:
+//!
+//! ```
+//! # use audiohal::*;
+//! fn play_silence<H: HostTrait>(host: &mut H) -> Result<()> {
+//!     let mut stream = host
+//!         .default_output_device()?
+//!         .open_outstream(StreamOptions::<[f32; 2]>::default())?;
+//!     stream.start()
+//! }
+//! play_silence(&mut null::Host::new()?)?;
+//! # Result::Ok(())
+//! ```
+use crate::error::Result;
+use crate::stream_options::{Input, StreamOptions};
+
+/// An audio API, through which devices are found.
+pub trait HostTrait {
+    type Device: DeviceTrait;
+
+    /// Returns the host API's descriptive name (e.g. "CoreAudio").
+    fn name(&self) -> &str;
+
+    /// Returns the system's default output device.
+    fn default_output_device(&mut self) -> Result<Self::Device>;
+
+    /// Returns the system's default input device.
+    fn default_input_device(&mut self) -> Result<Self::Device>;
+}
+
+/// An output or input device, on which streams are opened.
+pub trait DeviceTrait {
+    type Stream<Frame: 'static>: StreamTrait;
+
+    /// Returns the device's name.
+    fn name(&self) -> &str;
+
+    /// Creates an output stream. Returns [`Error::IncompatibleNChannels`] on input devices.
+    ///
+    /// [`Error::IncompatibleNChannels`]: crate::Error::IncompatibleNChannels
+    fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Self::Stream<Frame>>;
+
+    /// Creates an input stream. Returns [`Error::IncompatibleNChannels`] on output devices.
+    ///
+    /// [`Error::IncompatibleNChannels`]: crate::Error::IncompatibleNChannels
+    fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Self::Stream<Frame>>;
+}
+
+/// A stream of frames to or from a device. Stopped and closed when dropped.
+pub trait StreamTrait {
+    /// Starts calling the stream's callback. Returns [`Error::StreamAlreadyStarted`] if called
+    /// twice.
+    ///
+    /// [`Error::StreamAlreadyStarted`]: crate::Error::StreamAlreadyStarted
+    fn start(&mut self) -> Result<()>;
+
+    /// Stops and closes the stream.
+    fn close(self)
+    where
+        Self: Sized,
+    {
+    }
+}
+
+/// Implements the traits for a backend module's `Host`, `Device`, and `Stream`, by forwarding to
+/// their inherent methods of the same names.
+macro_rules! impl_traits {
+    (Host) => {
+        impl $crate::traits::HostTrait for Host {
+            type Device = Device;
+
+            fn name(&self) -> &str {
+                Host::name(self)
+            }
+
+            fn default_output_device(&mut self) -> $crate::Result<Device> {
+                Host::default_output_device(self)
+            }
+
+            fn default_input_device(&mut self) -> $crate::Result<Device> {
+                Host::default_input_device(self)
+            }
+        }
+        $crate::traits::impl_traits!(Device);
+    };
+    (Device) => {
+        impl $crate::traits::DeviceTrait for Device {
+            type Stream<Frame: 'static> = Stream<Frame>;
+
+            fn name(&self) -> &str {
+                Device::name(self)
+            }
+
+            fn open_outstream<Frame: 'static>(
+                &mut self,
+                options: $crate::StreamOptions<Frame>,
+            ) -> $crate::Result<Stream<Frame>> {
+                Device::open_outstream(self, options)
+            }
+
+            fn open_input_stream<Frame: 'static>(
+                &mut self,
+                options: $crate::StreamOptions<Frame, $crate::Input>,
+            ) -> $crate::Result<Stream<Frame>> {
+                Device::open_input_stream(self, options)
+            }
+        }
+
+        impl<Frame: 'static> $crate::traits::StreamTrait for Stream<Frame> {
+            fn start(&mut self) -> $crate::Result<()> {
+                Stream::start(self)
+            }
+
+            fn close(self) {
+                Stream::close(self)
+            }
+        }
+    };
+}
+pub(crate) use impl_traits;
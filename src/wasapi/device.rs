@@ -0,0 +1,191 @@
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+use crate::surround::ChannelPosition;
+use crate::wasapi::stream::{self, Stream};
+use crate::wasapi::{check, ffi, wide_to_string, ComPtr, ShareMode};
+
+pub struct Device {
+    device: ComPtr<ffi::IMMDevice>,
+    name: String,
+    id: String,
+    is_output: bool,
+    share_mode: ShareMode,
+}
+
+impl Device {
+    pub(super) fn new(device: ComPtr<ffi::IMMDevice>, is_output: bool) -> Result<Device> {
+        let name = friendly_name(&device)?;
+        let id = endpoint_id(&device)?;
+        Ok(Device {
+            device,
+            name,
+            id,
+            is_output,
+            share_mode: ShareMode::default(),
+        })
+    }
+
+    /// The endpoint's friendly name (e.g. "Speakers (Realtek High Definition Audio)").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The endpoint's ID, which stays the same across sessions (unlike its name, which users can
+    /// change).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The share mode of streams opened from now on. Defaults to [`ShareMode::Shared`]. Streams
+    /// whose options are [`exclusive`](crate::StreamOptions::exclusive) are always exclusive.
+    pub fn share_mode(&self) -> ShareMode {
+        self.share_mode
+    }
+
+    /// Sets the share mode of streams opened from now on.
+    pub fn set_share_mode(&mut self, share_mode: ShareMode) {
+        self.share_mode = share_mode;
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        stream::new_instream(self, options)
+    }
+
+    /// Creates an input stream capturing what this output device plays. See the default
+    /// backend's `Device::open_loopback_stream`.
+    pub fn open_loopback_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        stream::new_loopback_stream(self, options)
+    }
+
+    /// The positions of the shared-mode mixer's channels, in their order. `None` if its format
+    /// doesn't say.
+    pub fn channel_positions(&self) -> Result<Option<Vec<ChannelPosition>>> {
+        let client = stream::activate(self)?;
+        Ok(stream::mix_channel_mask(&client)?.map(|mask| mask.positions()))
+    }
+
+    /// The endpoint's master volume, from 0 to 1.
+    pub fn volume(&self) -> Result<f32> {
+        let endpoint_volume = self.endpoint_volume()?;
+        let mut volume = 0.0;
+        check(unsafe { com_call!(endpoint_volume, GetMasterVolumeLevelScalar(&mut volume)) })?;
+        Ok(volume)
+    }
+
+    /// Sets the endpoint's master volume, from 0 to 1, for every application.
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(Error::Invalid);
+        }
+        let endpoint_volume = self.endpoint_volume()?;
+        check(unsafe {
+            com_call!(
+                endpoint_volume,
+                SetMasterVolumeLevelScalar(volume, std::ptr::null())
+            )
+        })
+    }
+
+    pub fn is_muted(&self) -> Result<bool> {
+        let endpoint_volume = self.endpoint_volume()?;
+        let mut muted = 0;
+        check(unsafe { com_call!(endpoint_volume, GetMute(&mut muted)) })?;
+        Ok(muted != 0)
+    }
+
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        let endpoint_volume = self.endpoint_volume()?;
+        check(unsafe {
+            com_call!(
+                endpoint_volume,
+                SetMute(ffi::BOOL::from(muted), std::ptr::null())
+            )
+        })
+    }
+
+    fn endpoint_volume(&self) -> Result<ComPtr<ffi::IAudioEndpointVolume>> {
+        let mut volume = std::ptr::null_mut::<c_void>();
+        check(unsafe {
+            com_call!(
+                self.device,
+                Activate(
+                    &ffi::IID_IAudioEndpointVolume,
+                    ffi::CLSCTX_ALL,
+                    std::ptr::null_mut(),
+                    &mut volume
+                )
+            )
+        })?;
+        ComPtr::from_raw(volume as *mut ffi::IAudioEndpointVolume)
+    }
+
+    pub(super) fn endpoint(&self) -> &ComPtr<ffi::IMMDevice> {
+        &self.device
+    }
+
+    /// Whether this is a render (as opposed to capture) endpoint.
+    pub fn is_output(&self) -> bool {
+        self.is_output
+    }
+}
+
+fn friendly_name(device: &ComPtr<ffi::IMMDevice>) -> Result<String> {
+    let mut store = std::ptr::null_mut();
+    check(unsafe { com_call!(device, OpenPropertyStore(ffi::STGM_READ, &mut store)) })?;
+    let store = ComPtr::from_raw(store)?;
+    let mut value = ffi::PROPVARIANT {
+        vt: 0,
+        wReserved1: 0,
+        wReserved2: 0,
+        wReserved3: 0,
+        data: [0; 2],
+    };
+    check(unsafe { com_call!(store, GetValue(&ffi::PKEY_Device_FriendlyName, &mut value)) })?;
+    let name = if value.vt == ffi::VT_LPWSTR && value.data[0] != 0 {
+        unsafe { wide_to_string(value.data[0] as *const u16) }
+    } else {
+        String::new()
+    };
+    unsafe { ffi::PropVariantClear(&mut value) };
+    Ok(name)
+}
+
+fn endpoint_id(device: &ComPtr<ffi::IMMDevice>) -> Result<String> {
+    let mut id = std::ptr::null_mut();
+    check(unsafe { com_call!(device, GetId(&mut id)) })?;
+    let string = unsafe { wide_to_string(id) };
+    unsafe { ffi::CoTaskMemFree(id as *mut c_void) };
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Result;
+    use crate::wasapi::{Host, ShareMode};
+
+    #[test]
+    fn defaults_to_shared_mode() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        assert_eq!(device.share_mode(), ShareMode::Shared);
+        device.set_share_mode(ShareMode::Exclusive);
+        assert_eq!(device.share_mode(), ShareMode::Exclusive);
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+//! Hand-written bindings to the subset of COM, MMDevice, and WASAPI used by the backend.
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
+
+use std::os::raw::c_void;
+
+pub type HRESULT = i32;
+pub type HANDLE = *mut c_void;
+pub type BOOL = i32;
+pub type REFERENCE_TIME = i64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GUID {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+const fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> GUID {
+    GUID {
+        data1,
+        data2,
+        data3,
+        data4,
+    }
+}
+
+pub const CLSID_MMDeviceEnumerator: GUID = guid(
+    0xBCDE_0395,
+    0xE52F,
+    0x467C,
+    [0x8E, 0x3D, 0xC4, 0x57, 0x92, 0x91, 0x69, 0x2E],
+);
+pub const IID_IMMDeviceEnumerator: GUID = guid(
+    0xA956_64D2,
+    0x9614,
+    0x4F35,
+    [0xA7, 0x46, 0xDE, 0x8D, 0xB6, 0x36, 0x17, 0xE6],
+);
+pub const IID_IAudioClient: GUID = guid(
+    0x1CB9_AD4C,
+    0xDBFA,
+    0x4C32,
+    [0xB1, 0x78, 0xC2, 0xF5, 0x68, 0xA7, 0x03, 0xB2],
+);
+pub const IID_IAudioRenderClient: GUID = guid(
+    0xF294_ACFC,
+    0x3146,
+    0x4483,
+    [0xA7, 0xBF, 0xAD, 0xDC, 0xA7, 0xC2, 0x60, 0xE2],
+);
+pub const IID_IAudioCaptureClient: GUID = guid(
+    0xC8AD_BD64,
+    0xE71E,
+    0x48A0,
+    [0xA4, 0xDE, 0x18, 0x5C, 0x39, 0x5C, 0xD3, 0x17],
+);
+pub const IID_IAudioEndpointVolume: GUID = guid(
+    0x5CDF_2C82,
+    0x841E,
+    0x4546,
+    [0x97, 0x22, 0x0C, 0xF7, 0x40, 0x78, 0x22, 0x9A],
+);
+pub const KSDATAFORMAT_SUBTYPE_PCM: GUID = guid(
+    0x0000_0001,
+    0x0000,
+    0x0010,
+    [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+);
+pub const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: GUID = guid(
+    0x0000_0003,
+    0x0000,
+    0x0010,
+    [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+);
+
+#[repr(C)]
+pub struct PROPERTYKEY {
+    pub fmtid: GUID,
+    pub pid: u32,
+}
+
+pub const PKEY_Device_FriendlyName: PROPERTYKEY = PROPERTYKEY {
+    fmtid: guid(
+        0xA45C_254E,
+        0xDF1C,
+        0x4EFD,
+        [0x80, 0x20, 0x67, 0xD1, 0x46, 0xA8, 0x50, 0xE0],
+    ),
+    pid: 14,
+};
+
+/// Only the `VT_LPWSTR` case of the value union is used.
+#[repr(C)]
+pub struct PROPVARIANT {
+    pub vt: u16,
+    pub wReserved1: u16,
+    pub wReserved2: u16,
+    pub wReserved3: u16,
+    pub data: [usize; 2],
+}
+
+pub const VT_LPWSTR: u16 = 31;
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct WAVEFORMATEX {
+    pub wFormatTag: u16,
+    pub nChannels: u16,
+    pub nSamplesPerSec: u32,
+    pub nAvgBytesPerSec: u32,
+    pub nBlockAlign: u16,
+    pub wBitsPerSample: u16,
+    pub cbSize: u16,
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct WAVEFORMATEXTENSIBLE {
+    pub Format: WAVEFORMATEX,
+    pub wValidBitsPerSample: u16,
+    pub dwChannelMask: u32,
+    pub SubFormat: GUID,
+}
+
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+pub const SPEAKER_FRONT_LEFT: u32 = 0x1;
+pub const SPEAKER_FRONT_RIGHT: u32 = 0x2;
+pub const SPEAKER_FRONT_CENTER: u32 = 0x4;
+
+pub const E_OUTOFMEMORY: HRESULT = 0x8007_000E_u32 as HRESULT;
+pub const E_NOTFOUND: HRESULT = 0x8007_0490_u32 as HRESULT;
+pub const RPC_E_CHANGED_MODE: HRESULT = 0x8001_0106_u32 as HRESULT;
+pub const AUDCLNT_E_DEVICE_INVALIDATED: HRESULT = 0x8889_0004_u32 as HRESULT;
+pub const AUDCLNT_E_NOT_STOPPED: HRESULT = 0x8889_0005_u32 as HRESULT;
+pub const AUDCLNT_E_UNSUPPORTED_FORMAT: HRESULT = 0x8889_0008_u32 as HRESULT;
+pub const AUDCLNT_E_DEVICE_IN_USE: HRESULT = 0x8889_000A_u32 as HRESULT;
+pub const AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED: HRESULT = 0x8889_000E_u32 as HRESULT;
+pub const AUDCLNT_E_BUFFER_SIZE_ERROR: HRESULT = 0x8889_0016_u32 as HRESULT;
+pub const AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED: HRESULT = 0x8889_0019_u32 as HRESULT;
+pub const AUDCLNT_E_INVALID_DEVICE_PERIOD: HRESULT = 0x8889_0020_u32 as HRESULT;
+
+pub const COINIT_MULTITHREADED: u32 = 0x0;
+pub const CLSCTX_ALL: u32 = 0x17;
+pub const STGM_READ: u32 = 0x0;
+
+pub const eRender: u32 = 0;
+pub const eCapture: u32 = 1;
+pub const eConsole: u32 = 0;
+
+pub const AUDCLNT_SHAREMODE_SHARED: u32 = 0;
+pub const AUDCLNT_SHAREMODE_EXCLUSIVE: u32 = 1;
+pub const AUDCLNT_STREAMFLAGS_LOOPBACK: u32 = 0x0002_0000;
+pub const AUDCLNT_STREAMFLAGS_EVENTCALLBACK: u32 = 0x0004_0000;
+pub const AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY: u32 = 0x0800_0000;
+pub const AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM: u32 = 0x8000_0000;
+pub const AUDCLNT_BUFFERFLAGS_SILENT: u32 = 0x2;
+
+pub const WAIT_OBJECT_0: u32 = 0;
+pub const WAIT_TIMEOUT: u32 = 0x0000_0102;
+pub const INFINITE: u32 = 0xFFFF_FFFF;
+
+#[repr(C)]
+pub struct IUnknownVtbl {
+    pub QueryInterface: unsafe extern "system" fn(
+        this: *mut IUnknown,
+        riid: *const GUID,
+        ppvObject: *mut *mut c_void,
+    ) -> HRESULT,
+    pub AddRef: unsafe extern "system" fn(this: *mut IUnknown) -> u32,
+    pub Release: unsafe extern "system" fn(this: *mut IUnknown) -> u32,
+}
+
+#[repr(C)]
+pub struct IUnknown {
+    pub lpVtbl: *const IUnknownVtbl,
+}
+
+#[repr(C)]
+pub struct IMMDeviceEnumeratorVtbl {
+    pub parent: IUnknownVtbl,
+    pub EnumAudioEndpoints: usize,
+    pub GetDefaultAudioEndpoint: unsafe extern "system" fn(
+        this: *mut IMMDeviceEnumerator,
+        dataFlow: u32,
+        role: u32,
+        ppEndpoint: *mut *mut IMMDevice,
+    ) -> HRESULT,
+    pub GetDevice: usize,
+    pub RegisterEndpointNotificationCallback: usize,
+    pub UnregisterEndpointNotificationCallback: usize,
+}
+
+#[repr(C)]
+pub struct IMMDeviceEnumerator {
+    pub lpVtbl: *const IMMDeviceEnumeratorVtbl,
+}
+
+#[repr(C)]
+pub struct IMMDeviceVtbl {
+    pub parent: IUnknownVtbl,
+    pub Activate: unsafe extern "system" fn(
+        this: *mut IMMDevice,
+        iid: *const GUID,
+        dwClsCtx: u32,
+        pActivationParams: *mut PROPVARIANT,
+        ppInterface: *mut *mut c_void,
+    ) -> HRESULT,
+    pub OpenPropertyStore: unsafe extern "system" fn(
+        this: *mut IMMDevice,
+        stgmAccess: u32,
+        ppProperties: *mut *mut IPropertyStore,
+    ) -> HRESULT,
+    pub GetId: unsafe extern "system" fn(this: *mut IMMDevice, ppstrId: *mut *mut u16) -> HRESULT,
+    pub GetState: usize,
+}
+
+#[repr(C)]
+pub struct IMMDevice {
+    pub lpVtbl: *const IMMDeviceVtbl,
+}
+
+#[repr(C)]
+pub struct IPropertyStoreVtbl {
+    pub parent: IUnknownVtbl,
+    pub GetCount: usize,
+    pub GetAt: usize,
+    pub GetValue: unsafe extern "system" fn(
+        this: *mut IPropertyStore,
+        key: *const PROPERTYKEY,
+        pv: *mut PROPVARIANT,
+    ) -> HRESULT,
+    pub SetValue: usize,
+    pub Commit: usize,
+}
+
+#[repr(C)]
+pub struct IPropertyStore {
+    pub lpVtbl: *const IPropertyStoreVtbl,
+}
+
+#[repr(C)]
+pub struct IAudioClientVtbl {
+    pub parent: IUnknownVtbl,
+    pub Initialize: unsafe extern "system" fn(
+        this: *mut IAudioClient,
+        ShareMode: u32,
+        StreamFlags: u32,
+        hnsBufferDuration: REFERENCE_TIME,
+        hnsPeriodicity: REFERENCE_TIME,
+        pFormat: *const WAVEFORMATEX,
+        AudioSessionGuid: *const GUID,
+    ) -> HRESULT,
+    pub GetBufferSize:
+        unsafe extern "system" fn(this: *mut IAudioClient, pNumBufferFrames: *mut u32) -> HRESULT,
+    pub GetStreamLatency: unsafe extern "system" fn(
+        this: *mut IAudioClient,
+        phnsLatency: *mut REFERENCE_TIME,
+    ) -> HRESULT,
+    pub GetCurrentPadding:
+        unsafe extern "system" fn(this: *mut IAudioClient, pNumPaddingFrames: *mut u32) -> HRESULT,
+    pub IsFormatSupported: usize,
+    pub GetMixFormat: unsafe extern "system" fn(
+        this: *mut IAudioClient,
+        ppDeviceFormat: *mut *mut WAVEFORMATEX,
+    ) -> HRESULT,
+    pub GetDevicePeriod: unsafe extern "system" fn(
+        this: *mut IAudioClient,
+        phnsDefaultDevicePeriod: *mut REFERENCE_TIME,
+        phnsMinimumDevicePeriod: *mut REFERENCE_TIME,
+    ) -> HRESULT,
+    pub Start: unsafe extern "system" fn(this: *mut IAudioClient) -> HRESULT,
+    pub Stop: unsafe extern "system" fn(this: *mut IAudioClient) -> HRESULT,
+    pub Reset: unsafe extern "system" fn(this: *mut IAudioClient) -> HRESULT,
+    pub SetEventHandle:
+        unsafe extern "system" fn(this: *mut IAudioClient, eventHandle: HANDLE) -> HRESULT,
+    pub GetService: unsafe extern "system" fn(
+        this: *mut IAudioClient,
+        riid: *const GUID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub struct IAudioClient {
+    pub lpVtbl: *const IAudioClientVtbl,
+}
+
+#[repr(C)]
+pub struct IAudioRenderClientVtbl {
+    pub parent: IUnknownVtbl,
+    pub GetBuffer: unsafe extern "system" fn(
+        this: *mut IAudioRenderClient,
+        NumFramesRequested: u32,
+        ppData: *mut *mut u8,
+    ) -> HRESULT,
+    pub ReleaseBuffer: unsafe extern "system" fn(
+        this: *mut IAudioRenderClient,
+        NumFramesWritten: u32,
+        dwFlags: u32,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub struct IAudioRenderClient {
+    pub lpVtbl: *const IAudioRenderClientVtbl,
+}
+
+#[repr(C)]
+pub struct IAudioCaptureClientVtbl {
+    pub parent: IUnknownVtbl,
+    pub GetBuffer: unsafe extern "system" fn(
+        this: *mut IAudioCaptureClient,
+        ppData: *mut *mut u8,
+        pNumFramesToRead: *mut u32,
+        pdwFlags: *mut u32,
+        pu64DevicePosition: *mut u64,
+        pu64QPCPosition: *mut u64,
+    ) -> HRESULT,
+    pub ReleaseBuffer:
+        unsafe extern "system" fn(this: *mut IAudioCaptureClient, NumFramesRead: u32) -> HRESULT,
+    pub GetNextPacketSize: unsafe extern "system" fn(
+        this: *mut IAudioCaptureClient,
+        pNumFramesInNextPacket: *mut u32,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub struct IAudioCaptureClient {
+    pub lpVtbl: *const IAudioCaptureClientVtbl,
+}
+
+#[repr(C)]
+pub struct IAudioEndpointVolumeVtbl {
+    pub parent: IUnknownVtbl,
+    pub RegisterControlChangeNotify: usize,
+    pub UnregisterControlChangeNotify: usize,
+    pub GetChannelCount: usize,
+    pub SetMasterVolumeLevel: usize,
+    pub SetMasterVolumeLevelScalar: unsafe extern "system" fn(
+        this: *mut IAudioEndpointVolume,
+        fLevel: f32,
+        pguidEventContext: *const GUID,
+    ) -> HRESULT,
+    pub GetMasterVolumeLevel: usize,
+    pub GetMasterVolumeLevelScalar:
+        unsafe extern "system" fn(this: *mut IAudioEndpointVolume, pfLevel: *mut f32) -> HRESULT,
+    pub SetChannelVolumeLevel: usize,
+    pub SetChannelVolumeLevelScalar: usize,
+    pub GetChannelVolumeLevel: usize,
+    pub GetChannelVolumeLevelScalar: usize,
+    pub SetMute: unsafe extern "system" fn(
+        this: *mut IAudioEndpointVolume,
+        bMute: BOOL,
+        pguidEventContext: *const GUID,
+    ) -> HRESULT,
+    pub GetMute:
+        unsafe extern "system" fn(this: *mut IAudioEndpointVolume, pbMute: *mut BOOL) -> HRESULT,
+    pub GetVolumeStepInfo: usize,
+    pub VolumeStepUp: usize,
+    pub VolumeStepDown: usize,
+    pub QueryHardwareSupport: usize,
+    pub GetVolumeRange: usize,
+}
+
+#[repr(C)]
+pub struct IAudioEndpointVolume {
+    pub lpVtbl: *const IAudioEndpointVolumeVtbl,
+}
+
+#[link(name = "ole32")]
+extern "system" {
+    pub fn CoInitializeEx(pvReserved: *mut c_void, dwCoInit: u32) -> HRESULT;
+    pub fn CoUninitialize();
+    pub fn CoCreateInstance(
+        rclsid: *const GUID,
+        pUnkOuter: *mut IUnknown,
+        dwClsContext: u32,
+        riid: *const GUID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+    pub fn CoTaskMemFree(pv: *mut c_void);
+    pub fn PropVariantClear(pvar: *mut PROPVARIANT) -> HRESULT;
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn CreateEventW(
+        lpEventAttributes: *mut c_void,
+        bManualReset: BOOL,
+        bInitialState: BOOL,
+        lpName: *const u16,
+    ) -> HANDLE;
+    pub fn SetEvent(hEvent: HANDLE) -> BOOL;
+    pub fn CloseHandle(hObject: HANDLE) -> BOOL;
+    pub fn WaitForMultipleObjects(
+        nCount: u32,
+        lpHandles: *const HANDLE,
+        bWaitAll: BOOL,
+        dwMilliseconds: u32,
+    ) -> u32;
+}
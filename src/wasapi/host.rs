@@ -0,0 +1,73 @@
+use std::os::raw::c_void;
+
+use crate::error::Result;
+use crate::wasapi::device::Device;
+use crate::wasapi::{check, ensure_com_initialized, ffi, ComPtr};
+
+/// The WASAPI host. Initializes COM on the calling thread if needed.
+pub struct Host {
+    enumerator: ComPtr<ffi::IMMDeviceEnumerator>,
+}
+
+impl Host {
+    /// Connects to the system's MMDevice enumerator.
+    pub fn new() -> Result<Host> {
+        ensure_com_initialized();
+        let mut enumerator = std::ptr::null_mut::<c_void>();
+        check(unsafe {
+            ffi::CoCreateInstance(
+                &ffi::CLSID_MMDeviceEnumerator,
+                std::ptr::null_mut(),
+                ffi::CLSCTX_ALL,
+                &ffi::IID_IMMDeviceEnumerator,
+                &mut enumerator,
+            )
+        })?;
+        Ok(Host {
+            enumerator: ComPtr::from_raw(enumerator as *mut ffi::IMMDeviceEnumerator)?,
+        })
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "WASAPI"
+    }
+
+    /// Creates and returns the system's default output device.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        self.default_device(true)
+    }
+
+    /// Creates and returns the system's default input device.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        self.default_device(false)
+    }
+
+    fn default_device(&mut self, is_output: bool) -> Result<Device> {
+        let flow = if is_output {
+            ffi::eRender
+        } else {
+            ffi::eCapture
+        };
+        let mut device = std::ptr::null_mut();
+        check(unsafe {
+            com_call!(
+                self.enumerator,
+                GetDefaultAudioEndpoint(flow, ffi::eConsole, &mut device)
+            )
+        })?;
+        Device::new(ComPtr::from_raw(device)?, is_output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_default_devices() -> Result<()> {
+        let mut host = Host::new()?;
+        println!("Default output is {}", host.default_output_device()?.name());
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+//! Native WASAPI backend for Windows.
+//!
+//! Talks to the MMDevice and IAudioClient APIs directly, instead of going through Portaudio.
+//! Exposes the same [`Host`]/[`Device`]/[`Stream`] surface as the crate's default backend, plus
+//! exclusive-mode streams (see [`ShareMode`]).
+use std::ptr::NonNull;
+
+use crate::backend::Backend;
+use crate::error::{BackendError, Error, ErrorCategory, ErrorCode, Result};
+
+/// Calls a method through a COM interface pointer's vtable.
+macro_rules! com_call {
+    ($ptr:expr, $method:ident($($arg:expr),*)) => {{
+        let ptr = $ptr.as_ptr();
+        ((*(*ptr).lpVtbl).$method)(ptr $(, $arg)*)
+    }};
+}
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// How streams opened on a [`Device`] share the audio endpoint with the rest of the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareMode {
+    /// The stream goes through the system mixer, alongside other applications. Formats and sample
+    /// rates that differ from the mixer's are converted.
+    Shared,
+    /// The stream talks to the endpoint directly, bypassing the mixer for the lowest possible
+    /// latency. Other applications cannot use the endpoint while the stream is open, and the
+    /// format must be natively supported by the hardware.
+    Exclusive,
+}
+
+impl Default for ShareMode {
+    fn default() -> ShareMode {
+        ShareMode::Shared
+    }
+}
+
+/// Converts a COM result code into a [`Result`].
+fn check(hr: ffi::HRESULT) -> Result<()> {
+    match hr {
+        hr if hr >= 0 => Ok(()),
+        ffi::E_OUTOFMEMORY => Err(Error::OutOfMemory),
+        ffi::E_NOTFOUND | ffi::AUDCLNT_E_DEVICE_INVALIDATED => Err(Error::NoSuchDevice),
+        ffi::AUDCLNT_E_NOT_STOPPED => Err(Error::StreamAlreadyStarted),
+        ffi::AUDCLNT_E_BUFFER_SIZE_ERROR | ffi::AUDCLNT_E_INVALID_DEVICE_PERIOD => {
+            Err(Error::InvalidFramesPerBuffer)
+        }
+        ffi::AUDCLNT_E_DEVICE_IN_USE => Err(Error::Backend(
+            BackendError::new(
+                Some(Backend::Wasapi),
+                ErrorCode::HResult(hr),
+                "The device is in use by an exclusive-mode stream.",
+            )
+            .with_category(ErrorCategory::DeviceUnavailable),
+        )),
+        ffi::AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED => Err(Error::Backend(
+            BackendError::new(
+                Some(Backend::Wasapi),
+                ErrorCode::HResult(hr),
+                "Exclusive mode is disabled for this device.",
+            )
+            .with_category(ErrorCategory::DeviceUnavailable),
+        )),
+        hr => Err(Error::Backend(BackendError::new(
+            Some(Backend::Wasapi),
+            ErrorCode::HResult(hr),
+            "Unexpected WASAPI error.",
+        ))),
+    }
+}
+
+/// Initializes COM on the current thread, once per thread. It's uninitialized when the thread
+/// exits.
+fn ensure_com_initialized() {
+    struct ComGuard(bool);
+
+    impl Drop for ComGuard {
+        fn drop(&mut self) {
+            if self.0 {
+                unsafe { ffi::CoUninitialize() };
+            }
+        }
+    }
+
+    thread_local! {
+        static COM: ComGuard = {
+            let hr = unsafe { ffi::CoInitializeEx(std::ptr::null_mut(), ffi::COINIT_MULTITHREADED) };
+            // A thread that already joined a single-threaded apartment can still use the
+            // (free-threaded) audio objects. It just must not uninitialize COM on our behalf.
+            ComGuard(hr != ffi::RPC_E_CHANGED_MODE && hr >= 0)
+        };
+    }
+    COM.with(|_| ());
+}
+
+/// An owning pointer to a COM interface. Releases its reference when dropped.
+struct ComPtr<T>(NonNull<T>);
+
+// The MMDevice and WASAPI objects used here are free-threaded.
+unsafe impl<T> Send for ComPtr<T> {}
+unsafe impl<T> Sync for ComPtr<T> {}
+
+impl<T> ComPtr<T> {
+    /// Takes ownership of an interface pointer returned through an out-parameter.
+    fn from_raw(ptr: *mut T) -> Result<ComPtr<T>> {
+        NonNull::new(ptr)
+            .map(ComPtr)
+            .ok_or(Error::Unknown("WASAPI returned a null interface."))
+    }
+
+    fn as_ptr(&self) -> *mut T {
+        self.0.as_ptr()
+    }
+
+    fn as_unknown(&self) -> *mut ffi::IUnknown {
+        self.0.as_ptr() as *mut ffi::IUnknown
+    }
+}
+
+impl<T> Clone for ComPtr<T> {
+    fn clone(&self) -> ComPtr<T> {
+        unsafe { ((*(*self.as_unknown()).lpVtbl).AddRef)(self.as_unknown()) };
+        ComPtr(self.0)
+    }
+}
+
+impl<T> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        unsafe { ((*(*self.as_unknown()).lpVtbl).Release)(self.as_unknown()) };
+    }
+}
+
+/// An auto-reset Win32 event.
+struct Event(ffi::HANDLE);
+
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+impl Event {
+    fn new() -> Result<Event> {
+        let handle = unsafe { ffi::CreateEventW(std::ptr::null_mut(), 0, 0, std::ptr::null()) };
+        if handle.is_null() {
+            return Err(Error::from_source(
+                Some(Backend::Wasapi),
+                "Could not create a Win32 event.",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(Event(handle))
+    }
+
+    fn set(&self) {
+        unsafe { ffi::SetEvent(self.0) };
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { ffi::CloseHandle(self.0) };
+    }
+}
+
+/// Converts a null-terminated UTF-16 string.
+unsafe fn wide_to_string(ptr: *const u16) -> String {
+    let len = (0..).take_while(|&i| *ptr.add(i) != 0).count();
+    String::from_utf16_lossy(std::slice::from_raw_parts(ptr, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_result_codes() {
+        // S_FALSE is a success code.
+        assert_eq!(check(1), Ok(()));
+        assert_eq!(check(ffi::E_NOTFOUND), Err(Error::NoSuchDevice));
+        assert_eq!(
+            check(ffi::AUDCLNT_E_BUFFER_SIZE_ERROR),
+            Err(Error::InvalidFramesPerBuffer)
+        );
+    }
+
+    #[test]
+    fn converts_wide_strings() {
+        let wide: Vec<u16> = "Speakers\0".encode_utf16().collect();
+        assert_eq!(unsafe { wide_to_string(wide.as_ptr()) }, "Speakers");
+    }
+}
@@ -0,0 +1,629 @@
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::priority;
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{LatencyHint, SampleRate, StreamInfo, StreamOptions};
+use crate::surround::ChannelMask;
+use crate::wasapi::device::Device;
+use crate::wasapi::{check, ensure_com_initialized, ffi, ComPtr, Event, ShareMode};
+
+/// A stream driven by an event-driven IAudioClient. The callback runs on a dedicated thread.
+pub struct Stream<Frame> {
+    client: ComPtr<ffi::IAudioClient>,
+    stop_event: Arc<Event>,
+    // The worker's body until the stream is started, and its handle afterwards.
+    pending_worker: Option<Box<dyn FnOnce() + Send>>,
+    worker: Option<JoinHandle<()>>,
+    info: StreamInfo,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        let worker = self
+            .pending_worker
+            .take()
+            .ok_or(Error::StreamAlreadyStarted)?;
+        self.worker = Some(std::thread::spawn(worker));
+        check(unsafe { com_call!(self.client, Start()) })
+    }
+
+    /// The stream's latency, as reported by the audio engine. Does not include the latency of the
+    /// endpoint hardware.
+    pub fn latency(&self) -> Result<Duration> {
+        let mut latency = 0;
+        check(unsafe { com_call!(self.client, GetStreamLatency(&mut latency)) })?;
+        Ok(from_hns(latency))
+    }
+
+    /// Event-driven exclusive output streams swap whole buffers, so their callback always gets a
+    /// buffer's worth of frames. Other streams get whatever the engine has room for (or captured),
+    /// up to a buffer's worth.
+    pub fn info(&self) -> StreamInfo {
+        self.info
+    }
+
+    pub fn close(self) {}
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.stop_event.set();
+        if let Some(worker) = self.worker.take() {
+            // A panicking callback has nothing left to clean up.
+            let _ = worker.join();
+        }
+        unsafe { com_call!(self.client, Stop()) };
+    }
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    if !device.is_output() {
+        return Err(Error::IncompatibleNChannels);
+    }
+    let mut options = options;
+    let share_mode = share_mode(device, &mut options);
+    let (client, buffer_frames, poll_interval) = open_client(device, &options, share_mode, false)?;
+    let mut render = std::ptr::null_mut::<c_void>();
+    check(unsafe {
+        com_call!(
+            client,
+            GetService(&ffi::IID_IAudioRenderClient, &mut render)
+        )
+    })?;
+    let render = ComPtr::from_raw(render as *mut ffi::IAudioRenderClient)?;
+    // Start with a buffer of silence, so that the first callback does not have to race the
+    // hardware.
+    let mut data = std::ptr::null_mut();
+    check(unsafe { com_call!(render, GetBuffer(buffer_frames, &mut data)) })?;
+    check(unsafe {
+        com_call!(
+            render,
+            ReleaseBuffer(buffer_frames, ffi::AUDCLNT_BUFFERFLAGS_SILENT)
+        )
+    })?;
+
+    let wakeup = Wakeup::new(&client, poll_interval)?;
+    let stop_event = Arc::new(Event::new()?);
+    // Polled exclusive streams top up their buffer like shared ones.
+    let is_exclusive = share_mode == ShareMode::Exclusive && poll_interval.is_none();
+    let info = if is_exclusive {
+        StreamInfo::fixed(buffer_frames as usize)
+    } else {
+        StreamInfo::at_most(buffer_frames as usize)
+    };
+    let worker = RenderWorker {
+        client: client.clone(),
+        render,
+        is_exclusive,
+        wakeup,
+        stop_event: Arc::clone(&stop_event),
+        buffer_frames,
+        mmcss_task: mmcss_task(&options),
+        callback: options.callback,
+    };
+    Ok(Stream {
+        client,
+        stop_event,
+        pending_worker: Some(Box::new(move || worker.run())),
+        worker: None,
+        info,
+        _frame: PhantomData,
+    })
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    if device.is_output() {
+        return Err(Error::IncompatibleNChannels);
+    }
+    new_capture_stream(device, options, false)
+}
+
+/// Captures what a render endpoint plays, after the audio engine mixed it.
+pub(super) fn new_loopback_stream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    if !device.is_output() {
+        return Err(Error::IncompatibleNChannels);
+    }
+    new_capture_stream(device, options, true)
+}
+
+fn new_capture_stream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+    loopback: bool,
+) -> Result<Stream<Frame>> {
+    let mut options = options;
+    let share_mode = share_mode(device, &mut options);
+    // Loopback streams capture the mix, which only shared-mode streams go through.
+    if loopback && share_mode == ShareMode::Exclusive {
+        return Err(Error::IncompatibleStreamMode);
+    }
+    let (client, buffer_frames, poll_interval) =
+        open_client(device, &options, share_mode, loopback)?;
+    let mut capture = std::ptr::null_mut::<c_void>();
+    check(unsafe {
+        com_call!(
+            client,
+            GetService(&ffi::IID_IAudioCaptureClient, &mut capture)
+        )
+    })?;
+    let capture = ComPtr::from_raw(capture as *mut ffi::IAudioCaptureClient)?;
+
+    let wakeup = Wakeup::new(&client, poll_interval)?;
+    let stop_event = Arc::new(Event::new()?);
+    // Unsigned 8-bit samples are centered around 128 rather than 0.
+    let silence_byte = if options.format == Format::U8 {
+        0x80
+    } else {
+        0
+    };
+    let info = StreamInfo::at_most(buffer_frames as usize);
+    let worker = CaptureWorker {
+        capture,
+        wakeup,
+        stop_event: Arc::clone(&stop_event),
+        silence: vec![silence_byte; buffer_frames as usize * std::mem::size_of::<Frame>()],
+        mmcss_task: mmcss_task(&options),
+        callback: options.callback,
+    };
+    Ok(Stream {
+        client,
+        stop_event,
+        pending_worker: Some(Box::new(move || worker.run())),
+        worker: None,
+        info,
+        _frame: PhantomData,
+    })
+}
+
+/// The device's share mode, or exclusive mode if the options ask for it. Takes the request out
+/// of the options, so that they validate.
+fn share_mode<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &mut StreamOptions<Frame, Kind>,
+) -> ShareMode {
+    let wasapi_exclusive = options.wasapi.is_some_and(|wasapi| wasapi.exclusive);
+    if std::mem::take(&mut options.exclusive) || wasapi_exclusive {
+        ShareMode::Exclusive
+    } else {
+        device.share_mode()
+    }
+}
+
+/// The MMCSS task the stream's worker joins, if the stream asks for real-time priority.
+fn mmcss_task<Frame, Kind: CallbackKind>(
+    options: &StreamOptions<Frame, Kind>,
+) -> Option<&'static str> {
+    if !options.realtime_priority {
+        return None;
+    }
+    options
+        .wasapi
+        .unwrap_or_default()
+        .thread_priority
+        .task_name()
+}
+
+/// Activates and initializes an audio client for the given options. Returns the client along
+/// with the size of its buffer, in frames, and how often to poll it, in milliseconds, unless it's
+/// event-driven. Loopback clients capture from render endpoints.
+fn open_client<Frame, Kind: CallbackKind>(
+    device: &Device,
+    options: &StreamOptions<Frame, Kind>,
+    share_mode: ShareMode,
+    loopback: bool,
+) -> Result<(ComPtr<ffi::IAudioClient>, u32, Option<u32>)> {
+    match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    if let Some(frames_per_buffer) = options.frames_per_buffer.frames() {
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+    }
+    options.validate_frame_size_with_mask()?;
+
+    let mut client = activate(device)?;
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate as u32,
+        SampleRate::DeviceDefault => mix_sample_rate(&client)?,
+    };
+    let format = wave_format(
+        options.format,
+        options.n_channels,
+        options.channel_mask,
+        sample_rate,
+    )?;
+    let wasapi = options.wasapi.unwrap_or_default();
+    let events = if wasapi.event_driven {
+        ffi::AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+    } else {
+        0
+    };
+    let (share_mode, flags) = match share_mode {
+        // Let the audio engine convert between the client's and the mixer's format.
+        ShareMode::Shared if wasapi.auto_convert_pcm => (
+            ffi::AUDCLNT_SHAREMODE_SHARED,
+            events
+                | ffi::AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM
+                | ffi::AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY,
+        ),
+        ShareMode::Shared => (ffi::AUDCLNT_SHAREMODE_SHARED, events),
+        ShareMode::Exclusive => (ffi::AUDCLNT_SHAREMODE_EXCLUSIVE, events),
+    };
+    let flags = if loopback {
+        flags | ffi::AUDCLNT_STREAMFLAGS_LOOPBACK
+    } else {
+        flags
+    };
+    let mut duration = match (options.frames_per_buffer.frames(), options.latency) {
+        (Some(frames), _) => to_hns(frames as u32, sample_rate),
+        (None, LatencyHint::Exact(latency)) => (latency.as_nanos() / 100) as ffi::REFERENCE_TIME,
+        // In shared mode, zero selects the engine's default.
+        (None, LatencyHint::High) if share_mode == ffi::AUDCLNT_SHAREMODE_SHARED => 0,
+        (None, latency) => {
+            let (mut default_period, mut min_period) = (0, 0);
+            check(unsafe {
+                com_call!(
+                    client,
+                    GetDevicePeriod(&mut default_period, &mut min_period)
+                )
+            })?;
+            if latency == LatencyHint::Low {
+                min_period
+            } else {
+                default_period
+            }
+        }
+    };
+    let initialize = |client: &ComPtr<ffi::IAudioClient>, duration| unsafe {
+        // Exclusive event-driven streams require the periodicity to equal the buffer duration.
+        let periodicity = if share_mode == ffi::AUDCLNT_SHAREMODE_EXCLUSIVE {
+            duration
+        } else {
+            0
+        };
+        com_call!(
+            client,
+            Initialize(
+                share_mode,
+                flags,
+                duration,
+                periodicity,
+                &format as *const ffi::WAVEFORMATEXTENSIBLE as *const ffi::WAVEFORMATEX,
+                std::ptr::null()
+            )
+        )
+    };
+    let mut hr = initialize(&client, duration);
+    if hr == ffi::AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED {
+        // The client reports the nearest aligned size. A client that failed to initialize can't be
+        // reused, so retry with a fresh one.
+        let mut aligned_frames = 0;
+        check(unsafe { com_call!(client, GetBufferSize(&mut aligned_frames)) })?;
+        duration = to_hns(aligned_frames, sample_rate);
+        client = activate(device)?;
+        hr = initialize(&client, duration);
+    }
+    match hr {
+        ffi::AUDCLNT_E_UNSUPPORTED_FORMAT => return Err(Error::IncompatibleFormat(options.format)),
+        hr => check(hr)?,
+    }
+    let mut buffer_frames = 0;
+    check(unsafe { com_call!(client, GetBufferSize(&mut buffer_frames)) })?;
+    // Polled streams check every half a buffer.
+    let poll_interval = if wasapi.event_driven {
+        None
+    } else {
+        Some((u64::from(buffer_frames) * 500 / u64::from(sample_rate)).max(1) as u32)
+    };
+    Ok((client, buffer_frames, poll_interval))
+}
+
+pub(super) fn activate(device: &Device) -> Result<ComPtr<ffi::IAudioClient>> {
+    let mut client = std::ptr::null_mut::<c_void>();
+    check(unsafe {
+        com_call!(
+            device.endpoint(),
+            Activate(
+                &ffi::IID_IAudioClient,
+                ffi::CLSCTX_ALL,
+                std::ptr::null_mut(),
+                &mut client
+            )
+        )
+    })?;
+    ComPtr::from_raw(client as *mut ffi::IAudioClient)
+}
+
+/// The sample rate of the shared-mode mixer.
+fn mix_sample_rate(client: &ComPtr<ffi::IAudioClient>) -> Result<u32> {
+    with_mix_format(client, |format| format.nSamplesPerSec)
+}
+
+/// The channel mask of the shared-mode mixer, if its format has one.
+pub(super) fn mix_channel_mask(client: &ComPtr<ffi::IAudioClient>) -> Result<Option<ChannelMask>> {
+    with_mix_format(client, |format| {
+        if format.wFormatTag == ffi::WAVE_FORMAT_EXTENSIBLE {
+            let format = unsafe {
+                *(format as *const ffi::WAVEFORMATEX as *const ffi::WAVEFORMATEXTENSIBLE)
+            };
+            Some(ChannelMask::from_bits(format.dwChannelMask))
+        } else {
+            None
+        }
+    })
+}
+
+fn with_mix_format<T>(
+    client: &ComPtr<ffi::IAudioClient>,
+    f: impl FnOnce(&ffi::WAVEFORMATEX) -> T,
+) -> Result<T> {
+    let mut format = std::ptr::null_mut();
+    check(unsafe { com_call!(client, GetMixFormat(&mut format)) })?;
+    if format.is_null() {
+        return Err(Error::Unknown("WASAPI returned a null mix format."));
+    }
+    let result = f(unsafe { &*format });
+    unsafe { ffi::CoTaskMemFree(format as *mut c_void) };
+    Ok(result)
+}
+
+/// Describes interleaved PCM in the given format. Without a mask, mono and stereo get their usual
+/// one, and other channel counts none.
+fn wave_format(
+    format: Format,
+    n_channels: i32,
+    channel_mask: Option<ChannelMask>,
+    sample_rate: u32,
+) -> Result<ffi::WAVEFORMATEXTENSIBLE> {
+    let sub_format = match format {
+        Format::F64 | Format::F32 => ffi::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
+        // 8-bit PCM is unsigned; there is no signed 8-bit wave format.
+        Format::I32 | Format::I24 | Format::I16 | Format::U8 => ffi::KSDATAFORMAT_SUBTYPE_PCM,
+        _ => return Err(Error::IncompatibleFormat(format)),
+    };
+    let bits_per_sample = (format.sample_size() * 8) as u16;
+    let block_align = (format.sample_size() * n_channels as usize) as u16;
+    let channel_mask = match (channel_mask, n_channels) {
+        (Some(mask), _) => mask.bits(),
+        (None, 1) => ffi::SPEAKER_FRONT_CENTER,
+        (None, 2) => ffi::SPEAKER_FRONT_LEFT | ffi::SPEAKER_FRONT_RIGHT,
+        // Let the engine map the channels directly to the endpoint's.
+        (None, _) => 0,
+    };
+    Ok(ffi::WAVEFORMATEXTENSIBLE {
+        Format: ffi::WAVEFORMATEX {
+            wFormatTag: ffi::WAVE_FORMAT_EXTENSIBLE,
+            nChannels: n_channels as u16,
+            nSamplesPerSec: sample_rate,
+            nAvgBytesPerSec: sample_rate * u32::from(block_align),
+            nBlockAlign: block_align,
+            wBitsPerSample: bits_per_sample,
+            cbSize: (std::mem::size_of::<ffi::WAVEFORMATEXTENSIBLE>()
+                - std::mem::size_of::<ffi::WAVEFORMATEX>()) as u16,
+        },
+        wValidBitsPerSample: bits_per_sample,
+        dwChannelMask: channel_mask,
+        SubFormat: sub_format,
+    })
+}
+
+/// Converts a frame count to a duration in 100-nanosecond units.
+fn to_hns(frames: u32, sample_rate: u32) -> ffi::REFERENCE_TIME {
+    (i64::from(frames) * 10_000_000 + i64::from(sample_rate) / 2) / i64::from(sample_rate)
+}
+
+fn from_hns(hns: ffi::REFERENCE_TIME) -> Duration {
+    Duration::from_nanos(hns as u64 * 100)
+}
+
+/// Waits until either the buffer is ready (returns true), or the stream is stopped (false).
+/// How a worker learns that its device's buffer is ready for it.
+enum Wakeup {
+    /// The device signals the event.
+    Event(Arc<Event>),
+    /// The worker checks every so many milliseconds.
+    Poll(u32),
+}
+
+impl Wakeup {
+    /// Has event-driven clients signal a new event.
+    fn new(client: &ComPtr<ffi::IAudioClient>, poll_interval: Option<u32>) -> Result<Wakeup> {
+        if let Some(poll_interval) = poll_interval {
+            return Ok(Wakeup::Poll(poll_interval));
+        }
+        let ready_event = Arc::new(Event::new()?);
+        check(unsafe { com_call!(client, SetEventHandle(ready_event.0)) })?;
+        Ok(Wakeup::Event(ready_event))
+    }
+}
+
+fn wait_until_ready(wakeup: &Wakeup, stop_event: &Event) -> bool {
+    match wakeup {
+        Wakeup::Event(ready_event) => {
+            let handles = [stop_event.0, ready_event.0];
+            let result =
+                unsafe { ffi::WaitForMultipleObjects(2, handles.as_ptr(), 0, ffi::INFINITE) };
+            result == ffi::WAIT_OBJECT_0 + 1
+        }
+        Wakeup::Poll(interval) => {
+            let result = unsafe { ffi::WaitForMultipleObjects(1, &stop_event.0, 0, *interval) };
+            result == ffi::WAIT_TIMEOUT
+        }
+    }
+}
+
+struct RenderWorker<Frame> {
+    client: ComPtr<ffi::IAudioClient>,
+    render: ComPtr<ffi::IAudioRenderClient>,
+    wakeup: Wakeup,
+    stop_event: Arc<Event>,
+    buffer_frames: u32,
+    is_exclusive: bool,
+    mmcss_task: Option<&'static str>,
+    callback: Callback<Frame>,
+}
+
+impl<Frame> RenderWorker<Frame> {
+    /// Runs until the stream is stopped, or the device fails (e.g. it is unplugged).
+    fn run(mut self) {
+        ensure_com_initialized();
+        if let Some(task) = self.mmcss_task {
+            priority::join_mmcss_task(task);
+        }
+        while wait_until_ready(&self.wakeup, &self.stop_event) {
+            // Exclusive streams swap whole buffers. Shared ones top up whatever the engine has
+            // consumed.
+            let frame_count = if self.is_exclusive {
+                self.buffer_frames
+            } else {
+                let mut padding = 0;
+                if check(unsafe { com_call!(self.client, GetCurrentPadding(&mut padding)) })
+                    .is_err()
+                {
+                    return;
+                }
+                self.buffer_frames - padding
+            };
+            if frame_count == 0 {
+                continue;
+            }
+            let mut data = std::ptr::null_mut();
+            if check(unsafe { com_call!(self.render, GetBuffer(frame_count, &mut data)) }).is_err()
+            {
+                return;
+            }
+            let output =
+                unsafe { std::slice::from_raw_parts_mut(data as *mut Frame, frame_count as usize) };
+            (self.callback)(output);
+            unsafe { com_call!(self.render, ReleaseBuffer(frame_count, 0)) };
+        }
+    }
+}
+
+struct CaptureWorker<Frame> {
+    capture: ComPtr<ffi::IAudioCaptureClient>,
+    wakeup: Wakeup,
+    stop_event: Arc<Event>,
+    // Handed to the callback in place of packets flagged as silent, whose contents are undefined.
+    silence: Vec<u8>,
+    mmcss_task: Option<&'static str>,
+    callback: InputCallback<Frame>,
+}
+
+impl<Frame> CaptureWorker<Frame> {
+    /// Runs until the stream is stopped, or the device fails (e.g. it is unplugged).
+    fn run(mut self) {
+        ensure_com_initialized();
+        if let Some(task) = self.mmcss_task {
+            priority::join_mmcss_task(task);
+        }
+        while wait_until_ready(&self.wakeup, &self.stop_event) {
+            loop {
+                let mut packet_frames = 0;
+                if check(unsafe { com_call!(self.capture, GetNextPacketSize(&mut packet_frames)) })
+                    .is_err()
+                {
+                    return;
+                }
+                if packet_frames == 0 {
+                    break;
+                }
+                let (mut data, mut frame_count, mut flags) = (std::ptr::null_mut(), 0, 0);
+                if check(unsafe {
+                    com_call!(
+                        self.capture,
+                        GetBuffer(
+                            &mut data,
+                            &mut frame_count,
+                            &mut flags,
+                            std::ptr::null_mut(),
+                            std::ptr::null_mut()
+                        )
+                    )
+                })
+                .is_err()
+                {
+                    return;
+                }
+                let input = if flags & ffi::AUDCLNT_BUFFERFLAGS_SILENT == 0 {
+                    unsafe {
+                        std::slice::from_raw_parts(data as *const Frame, frame_count as usize)
+                    }
+                } else {
+                    let max_frames = self.silence.len() / std::mem::size_of::<Frame>();
+                    unsafe {
+                        std::slice::from_raw_parts(
+                            self.silence.as_ptr() as *const Frame,
+                            (frame_count as usize).min(max_frames),
+                        )
+                    }
+                };
+                (self.callback)(input);
+                unsafe { com_call!(self.capture, ReleaseBuffer(frame_count)) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasapi::Host;
+
+    #[test]
+    fn describes_formats() -> Result<()> {
+        let format = wave_format(Format::I24, 2, None, 48_000)?;
+        let (block_align, bytes_per_sec, channel_mask) = (
+            format.Format.nBlockAlign,
+            format.Format.nAvgBytesPerSec,
+            format.dwChannelMask,
+        );
+        assert_eq!(block_align, 6);
+        assert_eq!(bytes_per_sec, 288_000);
+        assert_eq!(channel_mask, 0x3);
+        let format = wave_format(Format::F32, 6, Some(ChannelMask::SURROUND_5_1), 48_000)?;
+        let channel_mask = format.dwChannelMask;
+        assert_eq!(channel_mask, 0x3f);
+        assert_eq!(
+            wave_format(Format::I8, 1, None, 48_000).err(),
+            Some(Error::IncompatibleFormat(Format::I8))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn converts_durations() {
+        assert_eq!(to_hns(480, 48_000), 100_000);
+        assert_eq!(from_hns(100_000), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn can_start_outstream() -> Result<()> {
+        let mut device = Host::new()?.default_output_device()?;
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        std::thread::sleep(Duration::from_millis(100));
+        stream.close();
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+use crate::wav::stream::{self, Stream};
+use crate::wav::Pace;
+
+/// A WAV file to write to, or read from.
+pub struct Device {
+    path: PathBuf,
+    name: String,
+    is_output: bool,
+    pace: Pace,
+    length: Option<u64>,
+}
+
+impl Device {
+    pub(super) fn new(path: &Path, is_output: bool) -> Device {
+        Device {
+            path: path.to_owned(),
+            name: path.display().to_string(),
+            is_output,
+            pace: Pace::default(),
+            length: None,
+        }
+    }
+
+    /// The file's path.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The pace of streams opened from now on. Defaults to [`Pace::RealTime`].
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    /// Sets the pace of streams opened from now on.
+    pub fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+    }
+
+    /// The number of frames output streams write before finishing, if limited. Input streams
+    /// finish at the end of their file.
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    /// Limits the number of frames output streams opened from now on write. Unlimited (i.e. until
+    /// the stream is closed) by default.
+    pub fn set_length(&mut self, length: Option<u64>) {
+        self.length = length;
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    ///
+    /// The file uses the sample rate of `options`, or 48kHz by default.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(self, options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    ///
+    /// The options must match the file's format and channel count. Exact sample rates must match
+    /// the file's, other rates are ignored.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(self, options)
+    }
+}
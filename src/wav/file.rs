@@ -0,0 +1,196 @@
+//! Reading and writing WAV headers.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::stream_options::Format;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// The size of the header written by [`write_header`].
+const HEADER_SIZE: u64 = 44;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub format: Format,
+    pub n_channels: u16,
+    pub sample_rate: u32,
+}
+
+impl Header {
+    fn block_align(&self) -> u16 {
+        self.format.sample_size() as u16 * self.n_channels
+    }
+}
+
+/// Returns the format tag and bits per sample of the format. None if WAV can't store it.
+fn format_tag(format: Format) -> Option<(u16, u16)> {
+    match format {
+        Format::F64 => Some((WAVE_FORMAT_IEEE_FLOAT, 64)),
+        Format::F32 => Some((WAVE_FORMAT_IEEE_FLOAT, 32)),
+        Format::I32 => Some((WAVE_FORMAT_PCM, 32)),
+        Format::I24 => Some((WAVE_FORMAT_PCM, 24)),
+        Format::I16 => Some((WAVE_FORMAT_PCM, 16)),
+        // 8-bit WAV samples are unsigned.
+        Format::U8 => Some((WAVE_FORMAT_PCM, 8)),
+        _ => None,
+    }
+}
+
+fn from_format_tag(tag: u16, bits_per_sample: u16) -> Option<Format> {
+    match (tag, bits_per_sample) {
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => Some(Format::F64),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => Some(Format::F32),
+        (WAVE_FORMAT_PCM, 32) => Some(Format::I32),
+        (WAVE_FORMAT_PCM, 24) => Some(Format::I24),
+        (WAVE_FORMAT_PCM, 16) => Some(Format::I16),
+        (WAVE_FORMAT_PCM, 8) => Some(Format::U8),
+        _ => None,
+    }
+}
+
+/// Whether WAV can store samples of the format.
+pub fn is_supported(format: Format) -> bool {
+    format_tag(format).is_some()
+}
+
+/// Writes a header for an empty data chunk. [`finish`] fills in the sizes.
+pub fn write_header(writer: &mut impl Write, header: &Header) -> io::Result<()> {
+    let (tag, bits_per_sample) = format_tag(header.format)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Unsupported format."))?;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE as u32 - 8).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&tag.to_le_bytes())?;
+    writer.write_all(&header.n_channels.to_le_bytes())?;
+    writer.write_all(&header.sample_rate.to_le_bytes())?;
+    let byte_rate = header.sample_rate * u32::from(header.block_align());
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&header.block_align().to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&0u32.to_le_bytes())
+}
+
+/// Fills in the sizes of a header written by [`write_header`], once `n_data_bytes` were written.
+pub fn finish(writer: &mut (impl Write + Seek), n_data_bytes: u64) -> io::Result<()> {
+    // WAV files can't be larger than 4GB. Readers usually cope with the data chunk being longer
+    // than advertised.
+    let n_data_bytes = n_data_bytes.min(u64::from(u32::MAX) - HEADER_SIZE) as u32;
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(&(n_data_bytes + HEADER_SIZE as u32 - 8).to_le_bytes())?;
+    writer.seek(SeekFrom::Start(HEADER_SIZE - 4))?;
+    writer.write_all(&n_data_bytes.to_le_bytes())?;
+    writer.flush()
+}
+
+/// Reads a WAV file's header, up to the start of its data. Returns the header and the size of the
+/// data in bytes.
+pub fn read_header(reader: &mut impl Read) -> io::Result<(Header, u64)> {
+    let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut riff = [0; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(invalid("Not a WAV file."));
+    }
+    let mut header = None;
+    loop {
+        let mut chunk = [0; 8];
+        reader.read_exact(&mut chunk)?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        match &chunk[0..4] {
+            b"fmt " => {
+                let mut fmt = vec![0; size as usize];
+                reader.read_exact(&mut fmt)?;
+                if fmt.len() < 16 {
+                    return Err(invalid("Truncated fmt chunk."));
+                }
+                let read_u16 = |at: usize| u16::from_le_bytes([fmt[at], fmt[at + 1]]);
+                let mut tag = read_u16(0);
+                // The actual tag is at the start of the extensible format's sub-format GUID.
+                if tag == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+                    tag = read_u16(24);
+                }
+                let format = from_format_tag(tag, read_u16(14))
+                    .ok_or_else(|| invalid("Unsupported sample format."))?;
+                header = Some(Header {
+                    format,
+                    n_channels: read_u16(2),
+                    sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+                });
+            }
+            b"data" => {
+                let header = header.ok_or_else(|| invalid("Missing fmt chunk."))?;
+                return Ok((header, u64::from(size)));
+            }
+            _ => {
+                io::copy(&mut reader.take(u64::from(size)), &mut io::sink())?;
+            }
+        }
+        // Chunks are padded to an even size.
+        if size % 2 == 1 && &chunk[0..4] != b"data" {
+            reader.read_exact(&mut [0])?;
+        }
+    }
+}
+
+/// Converts native-endian samples to WAV's little-endian ones, or back.
+pub fn swap_to_little_endian(bytes: &mut [u8], format: Format) {
+    if cfg!(target_endian = "big") {
+        for sample in bytes.chunks_exact_mut(format.sample_size()) {
+            sample.reverse();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trips_headers() -> io::Result<()> {
+        let header = Header {
+            format: Format::I24,
+            n_channels: 2,
+            sample_rate: 44100,
+        };
+        let mut file = Cursor::new(Vec::new());
+        write_header(&mut file, &header)?;
+        file.write_all(&[0; 60])?;
+        finish(&mut file, 60)?;
+        assert_eq!(file.get_ref().len(), 104);
+        file.set_position(0);
+        assert_eq!(read_header(&mut file)?, (header, 60));
+        Ok(())
+    }
+
+    #[test]
+    fn skips_unknown_chunks() -> io::Result<()> {
+        let mut file = Cursor::new(Vec::new());
+        file.write_all(b"RIFF\0\0\0\0WAVELIST\x03\0\0\0abc\0")?;
+        let mut rest = Cursor::new(Vec::new());
+        write_header(
+            &mut rest,
+            &Header {
+                format: Format::F32,
+                n_channels: 1,
+                sample_rate: 8000,
+            },
+        )?;
+        file.write_all(&rest.get_ref()[12..])?;
+        file.set_position(0);
+        let (header, _) = read_header(&mut file)?;
+        assert_eq!(header.format, Format::F32);
+        assert_eq!(file.position(), 12 + 12 + 44 - 12);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        assert!(!is_supported(Format::I8));
+        assert!(!is_supported(Format::U16));
+        assert!(read_header(&mut Cursor::new(b"RIFX\0\0\0\0WAVE".to_vec())).is_err());
+    }
+}
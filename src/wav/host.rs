@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use crate::error::Result;
+use crate::wav::device::Device;
+
+/// The WAV host. Its devices are files, so it has no default devices.
+pub struct Host(());
+
+impl Host {
+    pub fn new() -> Result<Host> {
+        Ok(Host(()))
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "WAV"
+    }
+
+    /// Returns an output that writes to the file at `path`. The file is created (or truncated)
+    /// when a stream is opened.
+    pub fn output_device(&mut self, path: impl AsRef<Path>) -> Device {
+        Device::new(path.as_ref(), true)
+    }
+
+    /// Returns an input that reads from the file at `path`.
+    pub fn input_device(&mut self, path: impl AsRef<Path>) -> Device {
+        Device::new(path.as_ref(), false)
+    }
+}
@@ -0,0 +1,81 @@
+//! A backend whose devices are WAV files, for integration tests and offline rendering.
+//!
+//! Output devices write what their streams' callbacks produce to a file, and input devices feed
+//! their streams' callbacks with a file's frames. Streams run on their own thread, either in real
+//! time or as fast as possible (see [`Pace`]). Every format except [`Format::I8`],
+//! [`Format::I24In32`], [`Format::U16`] and [`Format::U32`] is supported.
+//!
+//! # Examples
+//! ```no_run
+//! # use audiohal::*;
+//! # fn main() -> Result<()> {
+//! let mut host = wav::Host::new()?;
+//! let mut device = host.output_device("sine.wav");
+//! device.set_pace(wav::Pace::AsFastAsPossible);
+//! // One second of audio.
+//! device.set_length(Some(48000));
+//! let mut phase = 0.0f32;
+//! let mut stream = device.open_outstream(StreamOptions::<[f32; 1]> {
+//!     sample_rate: SampleRate::Exact(48000),
+//!     callback: Box::new(move |frames| {
+//!         for frame in frames {
+//!             frame[0] = phase.sin();
+//!             phase += 440.0 * 2.0 * std::f32::consts::PI / 48000.0;
+//!         }
+//!     }),
+//!     ..Default::default()
+//! })?;
+//! stream.start()?;
+//! stream.wait()
+//! # }
+//! ```
+//!
+//! [`Format::I8`]: crate::Format::I8
+//! [`Format::I24In32`]: crate::Format::I24In32
+//! [`Format::U16`]: crate::Format::U16
+//! [`Format::U32`]: crate::Format::U32
+
+mod device;
+mod file;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Device);
+
+/// WAV files aren't default devices: Use [`Host::output_device`] and [`Host::input_device`]
+/// instead. The default devices are [`Error::NoSuchDevice`](crate::Error::NoSuchDevice).
+impl crate::traits::HostTrait for Host {
+    type Device = Device;
+
+    fn name(&self) -> &str {
+        Host::name(self)
+    }
+
+    fn default_output_device(&mut self) -> crate::Result<Device> {
+        Err(crate::Error::NoSuchDevice)
+    }
+
+    fn default_input_device(&mut self) -> crate::Result<Device> {
+        Err(crate::Error::NoSuchDevice)
+    }
+}
+
+/// How fast streams consume (or produce) frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// At the stream's sample rate, like a real device. The default.
+    RealTime,
+    /// As fast as the callback and the disk allow.
+    AsFastAsPossible,
+}
+
+impl Default for Pace {
+    fn default() -> Pace {
+        Pace::RealTime
+    }
+}
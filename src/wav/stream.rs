@@ -0,0 +1,433 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::stream_options::{Callback, CallbackKind, Format, Input, InputCallback};
+use crate::stream_options::{SampleRate, StreamOptions};
+use crate::wav::device::Device;
+use crate::wav::file::{self, Header};
+use crate::wav::Pace;
+
+const DEFAULT_SAMPLE_RATE: i32 = 48000;
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 512;
+
+/// A stream writing to, or reading from, a WAV file. Its thread is stopped when dropped, and an
+/// output's file is completed.
+pub struct Stream<Frame> {
+    pace: Pace,
+    period: Duration,
+    // Moved to the stream's thread when started.
+    pump: Option<Box<dyn Pump>>,
+    thread: Option<Thread>,
+    _frame: PhantomData<Frame>,
+}
+
+struct Thread {
+    is_stopped: Arc<AtomicBool>,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        let pump = self.pump.take().ok_or(Error::StreamAlreadyStarted)?;
+        let (pace, period) = (self.pace, self.period);
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let is_stopped = is_stopped.clone();
+            std::thread::spawn(move || run(pump, pace, period, &is_stopped))
+        };
+        self.thread = Some(Thread { is_stopped, handle });
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// Blocks until the stream finishes: When an input reaches the end of its file, or an output
+    /// wrote the device's length. Returns the first I/O error the stream ran into.
+    ///
+    /// Outputs of unlimited length never finish. Returns [`Error::Invalid`] if the stream isn't
+    /// started.
+    pub fn wait(&mut self) -> Result<()> {
+        let thread = self.thread.take().ok_or(Error::Invalid)?;
+        thread
+            .handle
+            .join()
+            .unwrap_or(Err(Error::Unknown("The stream callback panicked.")))
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.is_stopped.store(true, Ordering::Relaxed);
+            let _ = thread.handle.join();
+        }
+    }
+}
+
+/// Moves a buffer of frames between the callback and the file.
+trait Pump: Send {
+    /// Returns false once the stream is finished.
+    fn pump(&mut self) -> Result<bool>;
+    /// Called once the stream is finished or stopped.
+    fn finish(&mut self) -> Result<()>;
+}
+
+fn run(
+    mut pump: Box<dyn Pump>,
+    pace: Pace,
+    period: Duration,
+    is_stopped: &AtomicBool,
+) -> Result<()> {
+    // Sleeps until absolute deadlines, so that the timing doesn't drift.
+    let mut deadline = Instant::now();
+    let result = loop {
+        if is_stopped.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        match pump.pump() {
+            Ok(true) => (),
+            result => break result.map(|_| ()),
+        }
+        if pace == Pace::RealTime {
+            deadline += period;
+            if let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    };
+    result.and(pump.finish())
+}
+
+struct OutputPump<Frame> {
+    callback: Callback<Frame>,
+    buffer: Vec<Frame>,
+    format: Format,
+    file: BufWriter<File>,
+    // The number of frames left to write, if limited.
+    remaining: Option<u64>,
+    n_data_bytes: u64,
+}
+
+// Frames are arrays of samples.
+unsafe impl<Frame> Send for OutputPump<Frame> {}
+
+impl<Frame> Pump for OutputPump<Frame> {
+    fn pump(&mut self) -> Result<bool> {
+        let n_frames = match self.remaining {
+            Some(0) => return Ok(false),
+            Some(remaining) => remaining.min(self.buffer.len() as u64) as usize,
+            None => self.buffer.len(),
+        };
+        (self.callback)(&mut self.buffer[..n_frames]);
+        let bytes = as_bytes_mut(&mut self.buffer[..n_frames]);
+        file::swap_to_little_endian(bytes, self.format);
+        self.file.write_all(bytes).map_err(write_error)?;
+        self.n_data_bytes += bytes.len() as u64;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= n_frames as u64;
+        }
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        file::finish(&mut self.file, self.n_data_bytes).map_err(write_error)
+    }
+}
+
+struct InputPump<Frame> {
+    callback: InputCallback<Frame>,
+    buffer: Vec<Frame>,
+    format: Format,
+    file: std::io::Take<BufReader<File>>,
+}
+
+// Frames are arrays of samples.
+unsafe impl<Frame> Send for InputPump<Frame> {}
+
+impl<Frame> Pump for InputPump<Frame> {
+    fn pump(&mut self) -> Result<bool> {
+        let frame_size = std::mem::size_of::<Frame>();
+        let bytes = as_bytes_mut(&mut self.buffer);
+        let mut n_bytes = 0;
+        while n_bytes < bytes.len() {
+            match self.file.read(&mut bytes[n_bytes..]) {
+                Ok(0) => break,
+                Ok(n) => n_bytes += n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(error) => {
+                    return Err(Error::from_source(
+                        None,
+                        "Could not read the WAV file.",
+                        error,
+                    ))
+                }
+            }
+        }
+        // Drops any trailing partial frame.
+        let n_frames = n_bytes / frame_size;
+        if n_frames == 0 {
+            return Ok(false);
+        }
+        file::swap_to_little_endian(&mut bytes[..n_frames * frame_size], self.format);
+        (self.callback)(&self.buffer[..n_frames]);
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn as_bytes_mut<Frame>(frames: &mut [Frame]) -> &mut [u8] {
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            frames.as_mut_ptr() as *mut u8,
+            std::mem::size_of_val(frames),
+        )
+    }
+}
+
+fn write_error(error: std::io::Error) -> Error {
+    Error::from_source(None, "Could not write the WAV file.", error)
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let frames_per_buffer = validate(&options)?;
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+        _ => DEFAULT_SAMPLE_RATE,
+    };
+    let header = Header {
+        format: options.format,
+        n_channels: options.n_channels as u16,
+        sample_rate: sample_rate as u32,
+    };
+    let mut file = BufWriter::new(File::create(device.path()).map_err(|_| Error::NoSuchDevice)?);
+    file::write_header(&mut file, &header).map_err(write_error)?;
+    let pump = OutputPump {
+        callback: options.callback,
+        buffer: silence(frames_per_buffer),
+        format: options.format,
+        file,
+        remaining: device.length(),
+        n_data_bytes: 0,
+    };
+    Ok(new_stream(
+        device,
+        sample_rate,
+        frames_per_buffer,
+        Box::new(pump),
+    ))
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    device: &Device,
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let frames_per_buffer = validate(&options)?;
+    let mut file = BufReader::new(File::open(device.path()).map_err(|_| Error::NoSuchDevice)?);
+    let (header, n_data_bytes) = file::read_header(&mut file).map_err(|error| {
+        Error::from_source(None, "Could not read the WAV file's header.", error)
+    })?;
+    if header.format != options.format {
+        return Err(Error::IncompatibleFormat(options.format));
+    }
+    if i32::from(header.n_channels) != options.n_channels {
+        return Err(Error::IncompatibleNChannels);
+    }
+    // The file's sample rate can't be changed.
+    let sample_rate = header.sample_rate as i32;
+    match options.sample_rate {
+        SampleRate::Exact(rate) if rate != sample_rate => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    let pump = InputPump {
+        callback: options.callback,
+        buffer: silence(frames_per_buffer),
+        format: options.format,
+        file: file.take(n_data_bytes),
+    };
+    Ok(new_stream(
+        device,
+        sample_rate,
+        frames_per_buffer,
+        Box::new(pump),
+    ))
+}
+
+/// Returns the number of frames per buffer.
+fn validate<Frame, Kind: CallbackKind>(options: &StreamOptions<Frame, Kind>) -> Result<usize> {
+    match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        _ => (),
+    }
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer,
+        None => DEFAULT_FRAMES_PER_BUFFER,
+    };
+    options.validate_frame_size()?;
+    if !file::is_supported(options.format) || options.n_channels > i32::from(u16::MAX) {
+        return Err(Error::IncompatibleFormat(options.format));
+    }
+    Ok(frames_per_buffer as usize)
+}
+
+fn new_stream<Frame>(
+    device: &Device,
+    sample_rate: i32,
+    frames_per_buffer: usize,
+    pump: Box<dyn Pump>,
+) -> Stream<Frame> {
+    Stream {
+        pace: device.pace(),
+        period: Duration::from_secs_f64(frames_per_buffer as f64 / f64::from(sample_rate)),
+        pump: Some(pump),
+        thread: None,
+        _frame: PhantomData,
+    }
+}
+
+/// A buffer of zeroes.
+fn silence<Frame>(n_frames: usize) -> Vec<Frame> {
+    let mut buffer = Vec::with_capacity(n_frames);
+    unsafe {
+        // Frames are arrays of samples, for which all-zeroes is valid.
+        std::ptr::write_bytes(buffer.as_mut_ptr(), 0, n_frames);
+        buffer.set_len(n_frames);
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_options::{BufferSize, ReconnectPolicy};
+    use crate::wav::Host;
+    use std::sync::Mutex;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("audiohal-{}-{}.wav", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trips_frames() -> Result<()> {
+        let path = temp_path("round-trip");
+        let mut host = Host::new()?;
+
+        let mut device = host.output_device(&path);
+        device.set_pace(Pace::AsFastAsPossible);
+        device.set_length(Some(1000));
+        let mut next = 0i16;
+        let mut stream = device.open_outstream(StreamOptions::<[i16; 2]> {
+            frames_per_buffer: BufferSize::Exact(64),
+            callback: Box::new(move |frames| {
+                for frame in frames {
+                    *frame = [next, -next];
+                    next += 1;
+                }
+            }),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        assert_eq!(stream.start().err(), Some(Error::StreamAlreadyStarted));
+        stream.wait()?;
+        stream.close();
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let mut device = host.input_device(&path);
+        device.set_pace(Pace::AsFastAsPossible);
+        let mut stream = {
+            let captured = captured.clone();
+            device.open_input_stream(StreamOptions::<[i16; 2], Input> {
+                callback: Box::new(move |frames| {
+                    captured.lock().unwrap().extend_from_slice(frames)
+                }),
+                ..Default::default()
+            })?
+        };
+        stream.start()?;
+        stream.wait()?;
+        let _ = std::fs::remove_file(&path);
+
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured.len(), 1000);
+        assert!(captured.iter().zip(0..).all(|(frame, i)| *frame == [i, -i]));
+        Ok(())
+    }
+
+    #[test]
+    fn checks_input_options() -> Result<()> {
+        let path = temp_path("options");
+        let mut host = Host::new()?;
+        let mut device = host.output_device(&path);
+        device.set_length(Some(0));
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 1]> {
+            sample_rate: SampleRate::Exact(8000),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        stream.wait()?;
+
+        let mut device = host.input_device(&path);
+        assert_eq!(
+            device
+                .open_input_stream(StreamOptions::<[f32; 2], Input>::default())
+                .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+        assert_eq!(
+            device
+                .open_input_stream(StreamOptions::<[i16; 1], Input>::default())
+                .err(),
+            Some(Error::IncompatibleFormat(Format::I16))
+        );
+        assert_eq!(
+            device
+                .open_input_stream(StreamOptions::<[f32; 1], Input> {
+                    sample_rate: SampleRate::Exact(44100),
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::IncompatibleSampleRate)
+        );
+        assert_eq!(
+            device
+                .open_input_stream(StreamOptions::<[f32; 1], Input> {
+                    watchdog: Some(Duration::from_secs(1)),
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        assert_eq!(
+            device
+                .open_input_stream(StreamOptions::<[f32; 1], Input> {
+                    reconnect: ReconnectPolicy::RetrySameDevice,
+                    ..Default::default()
+                })
+                .err(),
+            Some(Error::IncompatibleStreamMode)
+        );
+        assert!(device
+            .open_input_stream(StreamOptions::<[f32; 1], Input>::default())
+            .is_ok());
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
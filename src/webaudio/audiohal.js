@@ -0,0 +1,290 @@
+// JavaScript half of audiohal's WebAudio backend.
+//
+// Provides the "audiohal" imports of the wasm module, and calls back into its
+// `audiohal_process` export. Usage:
+//
+//   import { AudioHal } from "./audiohal.js";
+//   const audiohal = new AudioHal();
+//   const { instance } = await WebAssembly.instantiateStreaming(fetch("app.wasm"), {
+//     audiohal: audiohal.imports,
+//   });
+//   audiohal.attach(instance);
+//
+// Streams run on an AudioWorklet when the browser has one, and on a ScriptProcessorNode
+// otherwise. Either way, the Rust callback runs on the main thread: Worklets request buffers
+// ahead of time through their message port.
+
+const ERROR_UNAVAILABLE = -1;
+const ERROR_SAMPLE_RATE = -2;
+const ERROR_NO_INPUT = -3;
+
+// Runs in the AudioWorkletGlobalScope. Queues the main thread's buffers for output, or sends
+// captured frames to the main thread.
+const PROCESSOR_SOURCE = `
+class AudiohalProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    const { isOutput, nChannels, framesPerBuffer } = options.processorOptions;
+    this.isOutput = isOutput;
+    this.nChannels = nChannels;
+    this.framesPerBuffer = framesPerBuffer;
+    this.queue = [];
+    this.offset = 0;
+    this.queuedFrames = 0;
+    this.requested = 0;
+    this.port.onmessage = (event) => {
+      this.queue.push(event.data);
+      this.queuedFrames += event.data.length / this.nChannels;
+      this.requested -= 1;
+    };
+  }
+
+  process(inputs, outputs) {
+    if (this.isOutput) {
+      this.play(outputs[0]);
+    } else if (inputs[0].length > 0) {
+      this.record(inputs[0]);
+    }
+    return true;
+  }
+
+  play(output) {
+    const nFrames = output[0].length;
+    for (let i = 0; i < nFrames && this.queue.length > 0; ++i) {
+      const buffer = this.queue[0];
+      for (let c = 0; c < output.length; ++c) {
+        output[c][i] = buffer[this.offset * this.nChannels + Math.min(c, this.nChannels - 1)];
+      }
+      this.offset += 1;
+      this.queuedFrames -= 1;
+      if (this.offset * this.nChannels >= buffer.length) {
+        this.queue.shift();
+        this.offset = 0;
+      }
+    }
+    // Keep two buffers ahead.
+    while (this.queuedFrames + this.requested * this.framesPerBuffer < 2 * this.framesPerBuffer) {
+      this.requested += 1;
+      this.port.postMessage(null);
+    }
+  }
+
+  record(input) {
+    const nFrames = input[0].length;
+    const frames = new Float32Array(nFrames * this.nChannels);
+    for (let i = 0; i < nFrames; ++i) {
+      for (let c = 0; c < this.nChannels; ++c) {
+        frames[i * this.nChannels + c] = input[Math.min(c, input.length - 1)][i];
+      }
+    }
+    this.port.postMessage(frames, [frames.buffer]);
+  }
+}
+registerProcessor("audiohal-processor", AudiohalProcessor);
+`;
+
+// ScriptProcessorNode buffer sizes must be powers of two between 256 and 16384.
+function scriptProcessorBufferSize(framesPerBuffer) {
+  let size = 256;
+  while (size < framesPerBuffer && size < 16384) {
+    size *= 2;
+  }
+  return size;
+}
+
+export class AudioHal {
+  constructor() {
+    this.instance = null;
+    this.streams = new Map();
+    this.nextHandle = 1;
+    this.imports = {
+      audiohal_create_stream: (...args) => this.createStream(...args),
+      audiohal_sample_rate: (handle) => this.streams.get(handle).context.sampleRate,
+      audiohal_start_stream: (handle) => this.startStream(handle),
+      audiohal_close_stream: (handle) => this.closeStream(handle),
+    };
+  }
+
+  // Must be called with the instantiated module before it opens any stream.
+  attach(instance) {
+    this.instance = instance;
+  }
+
+  // The stream's buffer in wasm memory. Views are invalidated whenever the memory grows.
+  buffer(stream) {
+    return new Float32Array(
+      this.instance.exports.memory.buffer,
+      stream.bufferPtr,
+      stream.framesPerBuffer * stream.nChannels
+    );
+  }
+
+  // Calls the Rust callback. For outputs, returns a copy of the frames it rendered.
+  process(stream) {
+    this.instance.exports.audiohal_process(stream.state);
+    return stream.isOutput ? this.buffer(stream).slice() : null;
+  }
+
+  createStream(isOutput, nChannels, sampleRate, framesPerBuffer, bufferPtr, state) {
+    const AudioContext = globalThis.AudioContext || globalThis.webkitAudioContext;
+    if (!AudioContext) {
+      return ERROR_UNAVAILABLE;
+    }
+    if (!isOutput && !(globalThis.navigator && navigator.mediaDevices)) {
+      return ERROR_NO_INPUT;
+    }
+    let context;
+    try {
+      context = new AudioContext(sampleRate > 0 ? { sampleRate, latencyHint: "interactive" } : {
+        latencyHint: "interactive",
+      });
+    } catch (e) {
+      return ERROR_SAMPLE_RATE;
+    }
+    const stream = {
+      isOutput: isOutput !== 0,
+      nChannels,
+      framesPerBuffer,
+      bufferPtr,
+      state,
+      context,
+      node: null,
+      source: null,
+      // Captured frames not yet passed to the callback (inputs), or rendered frames not yet
+      // played (ScriptProcessor outputs).
+      pending: new Float32Array(0),
+      closed: false,
+    };
+    const handle = this.nextHandle++;
+    this.streams.set(handle, stream);
+    stream.ready = this.createNode(stream).catch((e) => console.error("audiohal:", e));
+    return handle;
+  }
+
+  async createNode(stream) {
+    const { context, nChannels } = stream;
+    if (context.audioWorklet) {
+      const url = URL.createObjectURL(new Blob([PROCESSOR_SOURCE], { type: "text/javascript" }));
+      await context.audioWorklet.addModule(url);
+      URL.revokeObjectURL(url);
+      stream.node = new AudioWorkletNode(context, "audiohal-processor", {
+        numberOfInputs: stream.isOutput ? 0 : 1,
+        numberOfOutputs: stream.isOutput ? 1 : 0,
+        outputChannelCount: stream.isOutput ? [nChannels] : [],
+        processorOptions: {
+          isOutput: stream.isOutput,
+          nChannels,
+          framesPerBuffer: stream.framesPerBuffer,
+        },
+      });
+      stream.node.port.onmessage = (event) => {
+        if (stream.closed) {
+          return;
+        }
+        if (stream.isOutput) {
+          const frames = this.process(stream);
+          stream.node.port.postMessage(frames, [frames.buffer]);
+        } else {
+          this.consume(stream, event.data);
+        }
+      };
+    } else {
+      const bufferSize = scriptProcessorBufferSize(stream.framesPerBuffer);
+      stream.node = context.createScriptProcessor(
+        bufferSize,
+        stream.isOutput ? 0 : nChannels,
+        stream.isOutput ? nChannels : 1
+      );
+      stream.node.onaudioprocess = (event) => {
+        if (stream.closed) {
+          return;
+        }
+        if (stream.isOutput) {
+          this.render(stream, event.outputBuffer);
+        } else {
+          this.consume(stream, interleave(event.inputBuffer, nChannels));
+        }
+      };
+    }
+    if (stream.isOutput) {
+      stream.node.connect(context.destination);
+    } else {
+      const media = await navigator.mediaDevices.getUserMedia({ audio: true });
+      stream.source = context.createMediaStreamSource(media);
+      stream.source.connect(stream.node);
+      // ScriptProcessors only run while connected to the destination. They output silence.
+      if (!context.audioWorklet) {
+        stream.node.connect(context.destination);
+      }
+    }
+  }
+
+  // Fills a ScriptProcessor's output, calling the callback as many times as needed.
+  render(stream, outputBuffer) {
+    const nFrames = outputBuffer.length;
+    const needed = nFrames * stream.nChannels;
+    while (stream.pending.length < needed) {
+      stream.pending = concat(stream.pending, this.process(stream));
+    }
+    for (let c = 0; c < outputBuffer.numberOfChannels; ++c) {
+      const channel = outputBuffer.getChannelData(c);
+      for (let i = 0; i < nFrames; ++i) {
+        channel[i] = stream.pending[i * stream.nChannels + c];
+      }
+    }
+    stream.pending = stream.pending.slice(needed);
+  }
+
+  // Passes captured frames to the callback, one full buffer at a time.
+  consume(stream, frames) {
+    stream.pending = concat(stream.pending, frames);
+    const bufferLength = stream.framesPerBuffer * stream.nChannels;
+    while (stream.pending.length >= bufferLength) {
+      this.buffer(stream).set(stream.pending.subarray(0, bufferLength));
+      this.process(stream);
+      stream.pending = stream.pending.slice(bufferLength);
+    }
+  }
+
+  startStream(handle) {
+    const stream = this.streams.get(handle);
+    // Browsers only allow resuming from a user gesture. Contexts start suspended otherwise.
+    stream.context.resume();
+    return 0;
+  }
+
+  closeStream(handle) {
+    const stream = this.streams.get(handle);
+    this.streams.delete(handle);
+    stream.closed = true;
+    stream.ready.then(() => {
+      if (stream.source) {
+        stream.source.mediaStream.getTracks().forEach((track) => track.stop());
+        stream.source.disconnect();
+      }
+      if (stream.node) {
+        stream.node.disconnect();
+      }
+      stream.context.close();
+    });
+  }
+}
+
+function interleave(audioBuffer, nChannels) {
+  const nFrames = audioBuffer.length;
+  const frames = new Float32Array(nFrames * nChannels);
+  for (let c = 0; c < nChannels; ++c) {
+    const channel = audioBuffer.getChannelData(Math.min(c, audioBuffer.numberOfChannels - 1));
+    for (let i = 0; i < nFrames; ++i) {
+      frames[i * nChannels + c] = channel[i];
+    }
+  }
+  return frames;
+}
+
+function concat(a, b) {
+  const result = new Float32Array(a.length + b.length);
+  result.set(a);
+  result.set(b, a.length);
+  return result;
+}
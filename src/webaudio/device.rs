@@ -0,0 +1,41 @@
+use crate::error::{Error, Result};
+use crate::stream_options::{Input, StreamOptions};
+use crate::webaudio::stream::{self, Stream};
+
+/// The browser's default output or input.
+pub struct Device {
+    is_output: bool,
+}
+
+impl Device {
+    pub(super) fn new(is_output: bool) -> Device {
+        Device { is_output }
+    }
+
+    /// Always "default".
+    pub fn name(&self) -> &str {
+        "default"
+    }
+
+    /// Creates an output stream. See the default backend's `Device::open_outstream`.
+    pub fn open_outstream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        if !self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_outstream(options)
+    }
+
+    /// Creates an input stream. See the default backend's `Device::open_input_stream`.
+    pub fn open_input_stream<Frame: 'static>(
+        &mut self,
+        options: StreamOptions<Frame, Input>,
+    ) -> Result<Stream<Frame>> {
+        if self.is_output {
+            return Err(Error::IncompatibleNChannels);
+        }
+        stream::new_instream(options)
+    }
+}
@@ -0,0 +1,35 @@
+//! The imports provided by `audiohal.js`, and the export it calls back into.
+use std::os::raw::c_void;
+
+pub const ERROR_UNAVAILABLE: i32 = -1;
+pub const ERROR_SAMPLE_RATE: i32 = -2;
+pub const ERROR_NO_INPUT: i32 = -3;
+
+#[link(wasm_import_module = "audiohal")]
+extern "C" {
+    /// Creates an AudioContext and the node driving the stream. `buffer` holds `frames_per_buffer`
+    /// interleaved frames, and `state` is passed back to [`audiohal_process`]. Returns the stream's
+    /// handle, or one of the errors above.
+    pub fn audiohal_create_stream(
+        is_output: i32,
+        n_channels: i32,
+        sample_rate: f32,
+        frames_per_buffer: i32,
+        buffer: *mut f32,
+        state: *mut c_void,
+    ) -> i32;
+    pub fn audiohal_sample_rate(stream: i32) -> f32;
+    pub fn audiohal_start_stream(stream: i32) -> i32;
+    /// The stream's state is never passed to [`audiohal_process`] again after this returns.
+    pub fn audiohal_close_stream(stream: i32);
+}
+
+/// Called by `audiohal.js` whenever a stream's buffer needs to be filled (outputs) or was filled
+/// (inputs).
+///
+/// # Safety
+/// `state` must be the state of a stream that is still open.
+#[no_mangle]
+pub unsafe extern "C" fn audiohal_process(state: *mut c_void) {
+    crate::webaudio::stream::process(state);
+}
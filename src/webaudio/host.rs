@@ -0,0 +1,31 @@
+use crate::error::Result;
+use crate::webaudio::device::Device;
+
+/// The WebAudio host. Browsers only expose the default output and input.
+pub struct Host(());
+
+impl Host {
+    pub fn new() -> Result<Host> {
+        Ok(Host(()))
+    }
+
+    /// Same as [`Host::new`]. Matches the default backend of other platforms.
+    pub fn with_default_backend() -> Result<Host> {
+        Host::new()
+    }
+
+    /// Returns the host API's descriptive name.
+    pub fn name(&self) -> &str {
+        "WebAudio"
+    }
+
+    /// Returns the default output.
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(Device::new(true))
+    }
+
+    /// Returns the default input (i.e. `getUserMedia`'s).
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(Device::new(false))
+    }
+}
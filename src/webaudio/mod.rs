@@ -0,0 +1,36 @@
+//! WebAudio backend for `wasm32-unknown-unknown`.
+//!
+//! These are the crate's default [`Host`]/[`Device`]/[`Stream`] in the browser. The backend has a
+//! JavaScript half, [`GLUE`] (`src/webaudio/audiohal.js`), which provides the wasm module's
+//! `audiohal` imports:
+//!
+//! ```js
+//! import { AudioHal } from "./audiohal.js";
+//! const audiohal = new AudioHal();
+//! const { instance } = await WebAssembly.instantiateStreaming(fetch("app.wasm"), {
+//!   audiohal: audiohal.imports,
+//! });
+//! audiohal.attach(instance);
+//! ```
+//!
+//! Streams are driven by an AudioWorklet, or by a ScriptProcessorNode on browsers without one.
+//! Either way, callbacks run on the main thread, between other events. They only support
+//! [`Format::F32`](crate::Format::F32), and each stream gets its own AudioContext.
+//!
+//! Browsers only let audio start from a user gesture: Call [`Stream::start`] from an input event
+//! handler. Input streams ask for the microphone permission when opened.
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+crate::traits::impl_traits!(Host);
+
+/// The contents of `audiohal.js`, for build scripts to write out next to the wasm module.
+pub const GLUE: &str = include_str!("audiohal.js");
@@ -0,0 +1,200 @@
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::stream_options::{CallbackKind, Format, Input, SampleRate, StreamInfo, StreamOptions};
+use crate::webaudio::ffi;
+
+/// ScriptProcessorNode's most common buffer size. Worklets keep two buffers queued.
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 1024;
+
+/// A WebAudio stream. Closed (along with its AudioContext) when dropped.
+pub struct Stream<Frame> {
+    handle: i32,
+    is_started: bool,
+    frames_per_buffer: i32,
+    // Not passed to the callback once the stream is closed.
+    _state: Box<State>,
+    _frame: PhantomData<Frame>,
+}
+
+impl<Frame> Stream<Frame> {
+    /// Starts (i.e. resumes) the stream's AudioContext. Only works from a user gesture on most
+    /// browsers.
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        unsafe { ffi::audiohal_start_stream(self.handle) };
+        self.is_started = true;
+        Ok(())
+    }
+
+    pub fn close(self) {}
+
+    /// The AudioContext's sample rate.
+    pub fn sample_rate(&self) -> f32 {
+        unsafe { ffi::audiohal_sample_rate(self.handle) }
+    }
+
+    /// The callback gets the same number of frames every time: 1024 by default.
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo::fixed(self.frames_per_buffer as usize)
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        unsafe { ffi::audiohal_close_stream(self.handle) };
+    }
+}
+
+/// The user callback, wrapped to work on the stream's interleaved buffer.
+type Process = Box<dyn FnMut(&mut [f32]) + Send>;
+
+struct State {
+    buffer: Vec<f32>,
+    process: Process,
+}
+
+pub(super) fn new_outstream<Frame: 'static>(
+    options: StreamOptions<Frame>,
+) -> Result<Stream<Frame>> {
+    let (sample_rate, frames_per_buffer) = validate(&options)?;
+    let n_channels = options.n_channels as usize;
+    let mut callback = options.callback;
+    let process = Box::new(move |buffer: &mut [f32]| {
+        callback(unsafe {
+            std::slice::from_raw_parts_mut(
+                buffer.as_mut_ptr() as *mut Frame,
+                buffer.len() / n_channels,
+            )
+        });
+    });
+    open(
+        true,
+        options.n_channels,
+        sample_rate,
+        frames_per_buffer,
+        process,
+    )
+}
+
+pub(super) fn new_instream<Frame: 'static>(
+    options: StreamOptions<Frame, Input>,
+) -> Result<Stream<Frame>> {
+    let (sample_rate, frames_per_buffer) = validate(&options)?;
+    let n_channels = options.n_channels as usize;
+    let mut callback = options.callback;
+    let process = Box::new(move |buffer: &mut [f32]| {
+        callback(unsafe {
+            std::slice::from_raw_parts(buffer.as_ptr() as *const Frame, buffer.len() / n_channels)
+        });
+    });
+    open(
+        false,
+        options.n_channels,
+        sample_rate,
+        frames_per_buffer,
+        process,
+    )
+}
+
+/// Returns the requested sample rate (if any), and the number of frames per buffer.
+fn validate<Frame, Kind: CallbackKind>(
+    options: &StreamOptions<Frame, Kind>,
+) -> Result<(SampleRate, i32)> {
+    let sample_rate = match options.sample_rate {
+        SampleRate::Exact(rate) | SampleRate::NearestTo(rate) if rate <= 0 => {
+            return Err(Error::IncompatibleSampleRate)
+        }
+        SampleRate::Exact(rate) => SampleRate::Exact(rate),
+        SampleRate::NearestTo(rate) => SampleRate::NearestTo(rate),
+        _ => SampleRate::DeviceDefault,
+    };
+    let frames_per_buffer = match options.frames_per_buffer.frames() {
+        Some(frames_per_buffer) if frames_per_buffer <= 0 => {
+            return Err(Error::InvalidFramesPerBuffer)
+        }
+        Some(frames_per_buffer) => frames_per_buffer,
+        None => DEFAULT_FRAMES_PER_BUFFER,
+    };
+    options.validate_frame_size()?;
+    // WebAudio only has float samples.
+    if options.format != Format::F32 {
+        return Err(Error::IncompatibleFormat(options.format));
+    }
+    Ok((sample_rate, frames_per_buffer))
+}
+
+fn open<Frame>(
+    is_output: bool,
+    n_channels: i32,
+    sample_rate: SampleRate,
+    frames_per_buffer: i32,
+    process: Process,
+) -> Result<Stream<Frame>> {
+    let mut state = Box::new(State {
+        buffer: vec![0.0; (frames_per_buffer * n_channels) as usize],
+        process,
+    });
+    let create = |sample_rate: i32, state: &mut State| unsafe {
+        ffi::audiohal_create_stream(
+            is_output as i32,
+            n_channels,
+            sample_rate as f32,
+            frames_per_buffer,
+            state.buffer.as_mut_ptr(),
+            state as *mut State as *mut c_void,
+        )
+    };
+    let handle = match sample_rate {
+        SampleRate::Exact(rate) => create(rate, &mut state),
+        // Browsers support a limited range of rates. Fall back to the device's.
+        SampleRate::NearestTo(rate) => match create(rate, &mut state) {
+            ffi::ERROR_SAMPLE_RATE => create(0, &mut state),
+            handle => handle,
+        },
+        _ => create(0, &mut state),
+    };
+    match handle {
+        ffi::ERROR_UNAVAILABLE => Err(Error::BackendUnavailable),
+        ffi::ERROR_SAMPLE_RATE => Err(Error::IncompatibleSampleRate),
+        ffi::ERROR_NO_INPUT => Err(Error::NoSuchDevice),
+        handle if handle < 0 => Err(Error::Unknown("Unexpected WebAudio error.")),
+        handle => Ok(Stream {
+            handle,
+            is_started: false,
+            frames_per_buffer,
+            _state: state,
+            _frame: PhantomData,
+        }),
+    }
+}
+
+/// Runs the callback of the stream the state belongs to.
+pub(super) unsafe fn process(state: *mut c_void) {
+    let state = (state as *mut State)
+        .as_mut()
+        .expect("Could not get State from user data.");
+    (state.process)(&mut state.buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_accepts_f32() {
+        let options = StreamOptions::<[i16; 2]>::default();
+        assert_eq!(
+            validate(&options).err(),
+            Some(Error::IncompatibleFormat(Format::I16))
+        );
+        let options = StreamOptions::<[f32; 2]>::default();
+        assert_eq!(
+            validate(&options).ok().map(|(_, frames)| frames),
+            Some(DEFAULT_FRAMES_PER_BUFFER)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Settings of WASAPI streams, natively or through Portaudio, which streams on other backends
+//! ignore. See [`WasapiOptions`].
+
+/// The MMCSS task that a WASAPI stream's thread joins, which sets how the system schedules it.
+/// Streams that don't ask for [real-time priority](crate::StreamOptions::realtime_priority)
+/// join none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThreadPriority {
+    /// The thread keeps its priority.
+    None,
+    Audio,
+    Capture,
+    Distribution,
+    Games,
+    Playback,
+    /// The highest.
+    ProAudio,
+    WindowManager,
+}
+
+impl Default for ThreadPriority {
+    fn default() -> ThreadPriority {
+        ThreadPriority::ProAudio
+    }
+}
+
+impl ThreadPriority {
+    /// The task's name in MMCSS's registry.
+    #[cfg(all(target_os = "windows", feature = "wasapi"))]
+    pub(crate) fn task_name(self) -> Option<&'static str> {
+        match self {
+            ThreadPriority::None => None,
+            ThreadPriority::Audio => Some("Audio"),
+            ThreadPriority::Capture => Some("Capture"),
+            ThreadPriority::Distribution => Some("Distribution"),
+            ThreadPriority::Games => Some("Games"),
+            ThreadPriority::Playback => Some("Playback"),
+            ThreadPriority::ProAudio => Some("Pro Audio"),
+            ThreadPriority::WindowManager => Some("Window Manager"),
+        }
+    }
+}
+
+/// How a stream uses WASAPI, attached to its [`StreamOptions::wasapi`](crate::StreamOptions).
+/// Both native WASAPI streams and Portaudio's WASAPI streams take them.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// # use audiohal::windows::WasapiOptions;
+/// let options = StreamOptions::<[f32; 2]> {
+///     // Polled, in exclusive mode, on Windows. The same stream elsewhere.
+///     wasapi: Some(WasapiOptions {
+///         exclusive: true,
+///         event_driven: false,
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+/// # options;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WasapiOptions {
+    /// [`ProAudio`](ThreadPriority::ProAudio) by default.
+    pub thread_priority: ThreadPriority,
+    /// Whether shared-mode streams have the audio engine convert formats, channel counts, and
+    /// sample rates that differ from its mixer's (`AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM`). Streams
+    /// that don't return [`Error::IncompatibleFormat`](crate::Error::IncompatibleFormat) for
+    /// them. `true` by default.
+    pub auto_convert_pcm: bool,
+    /// Whether the stream opens in exclusive mode, like
+    /// [`StreamOptions::exclusive`](crate::StreamOptions::exclusive). `false` by default.
+    pub exclusive: bool,
+    /// Whether the device signals the stream when its buffer needs data, rather than the stream
+    /// polling it every half a buffer. Some drivers only do one of them well. `true` by default.
+    pub event_driven: bool,
+}
+
+impl Default for WasapiOptions {
+    fn default() -> WasapiOptions {
+        WasapiOptions {
+            thread_priority: ThreadPriority::default(),
+            auto_convert_pcm: true,
+            exclusive: false,
+            event_driven: true,
+        }
+    }
+}